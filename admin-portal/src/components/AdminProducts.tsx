@@ -7,6 +7,17 @@ interface Product {
   price: number;
   inventory: number;
   created_at: string;
+  status: 'draft' | 'published' | 'scheduled';
+  publish_at?: string;
+  sku?: string;
+  barcode?: string;
+  weight_oz?: number;
+  length_in?: number;
+  width_in?: number;
+  height_in?: number;
+  hs_code?: string;
+  country_of_origin?: string;
+  domestic_only?: boolean;
 }
 
 interface ProductInput {
@@ -14,6 +25,15 @@ interface ProductInput {
   description?: string;
   price: number;
   inventory: number;
+  sku?: string;
+  barcode?: string;
+  weight_oz?: number;
+  length_in?: number;
+  width_in?: number;
+  height_in?: number;
+  hs_code?: string;
+  country_of_origin?: string;
+  domestic_only?: boolean;
 }
 
 export function AdminProducts({ token }: { token: string }) {
@@ -55,7 +75,15 @@ export function AdminProducts({ token }: { token: string }) {
 
   const handleEdit = (p: Product) => {
     setEditing(p);
-    setForm({ name: p.name, description: p.description, price: p.price, inventory: p.inventory });
+    setForm({ name: p.name, description: p.description, price: p.price, inventory: p.inventory, sku: p.sku, barcode: p.barcode, weight_oz: p.weight_oz, length_in: p.length_in, width_in: p.width_in, height_in: p.height_in, hs_code: p.hs_code, country_of_origin: p.country_of_origin, domestic_only: p.domestic_only });
+  };
+
+  const handleSetStatus = async (id: number, action: 'publish' | 'unpublish') => {
+    await fetch(`/api/admin/products/${id}/${action}`, {
+      method: 'POST',
+      headers: { Authorization: `Bearer ${token}` },
+    });
+    fetchProducts();
   };
 
   const handleDelete = async (id: number) => {
@@ -75,6 +103,17 @@ export function AdminProducts({ token }: { token: string }) {
         <input placeholder="Description" value={form.description || ''} onChange={e => setForm(f => ({ ...f, description: e.target.value }))} />
         <input type="number" placeholder="Price" value={form.price} onChange={e => setForm(f => ({ ...f, price: Number(e.target.value) }))} required />
         <input type="number" placeholder="Inventory" value={form.inventory} onChange={e => setForm(f => ({ ...f, inventory: Number(e.target.value) }))} required />
+        <input placeholder="SKU" value={form.sku || ''} onChange={e => setForm(f => ({ ...f, sku: e.target.value }))} />
+        <input placeholder="Barcode" value={form.barcode || ''} onChange={e => setForm(f => ({ ...f, barcode: e.target.value }))} />
+        <input type="number" placeholder="Weight (oz)" value={form.weight_oz ?? ''} onChange={e => setForm(f => ({ ...f, weight_oz: e.target.value ? Number(e.target.value) : undefined }))} />
+        <input type="number" placeholder="L (in)" value={form.length_in ?? ''} onChange={e => setForm(f => ({ ...f, length_in: e.target.value ? Number(e.target.value) : undefined }))} />
+        <input type="number" placeholder="W (in)" value={form.width_in ?? ''} onChange={e => setForm(f => ({ ...f, width_in: e.target.value ? Number(e.target.value) : undefined }))} />
+        <input type="number" placeholder="H (in)" value={form.height_in ?? ''} onChange={e => setForm(f => ({ ...f, height_in: e.target.value ? Number(e.target.value) : undefined }))} />
+        <input placeholder="HS code" value={form.hs_code || ''} onChange={e => setForm(f => ({ ...f, hs_code: e.target.value }))} />
+        <input placeholder="Country of origin" maxLength={2} value={form.country_of_origin || ''} onChange={e => setForm(f => ({ ...f, country_of_origin: e.target.value }))} />
+        <label>
+          <input type="checkbox" checked={!!form.domestic_only} onChange={e => setForm(f => ({ ...f, domestic_only: e.target.checked }))} /> Domestic only
+        </label>
         <button type="submit">{editing ? 'Update' : 'Add'} Product</button>
         {editing && <button type="button" onClick={() => { setEditing(null); setForm({ name: '', price: 0, inventory: 0 }); }}>Cancel</button>}
       </form>
@@ -82,7 +121,11 @@ export function AdminProducts({ token }: { token: string }) {
       <ul>
         {products.map(p => (
           <li key={p.id}>
-            <strong>{p.name}</strong> (${p.price}) - {p.inventory} in stock
+            <strong>{p.name}</strong>{p.sku && ` [${p.sku}]`} (${p.price}) - {p.inventory} in stock - {p.status}
+            {p.status === 'scheduled' && p.publish_at && ` (${new Date(p.publish_at).toLocaleString()})`}
+            {p.status === 'published'
+              ? <button onClick={() => handleSetStatus(p.id, 'unpublish')} style={{ marginLeft: 8 }}>Unpublish</button>
+              : <button onClick={() => handleSetStatus(p.id, 'publish')} style={{ marginLeft: 8 }}>Publish</button>}
             <button onClick={() => handleEdit(p)} style={{ marginLeft: 8 }}>Edit</button>
             <button onClick={() => handleDelete(p.id)} style={{ marginLeft: 8 }}>Delete</button>
           </li>
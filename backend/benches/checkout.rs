@@ -0,0 +1,82 @@
+// Checkout pricing benchmarks - run with `cargo bench --bench checkout`
+// The backend's modules are private to its library, so the pricing math is included from its
+// source file rather than imported. Carts are synthetic: every product has a sale price and three quantity
+// breaks, the shape that makes tiered_price do the most work per line
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+#[path = "../src/cart_math.rs"]
+mod cart_math;
+
+const TAX_RATE: f64 = 0.0825;
+const SHIPPING: i64 = 799;
+
+struct Line {
+    base_price: f64,
+    quantity: i32,
+    rules: Vec<(i32, f64)>, // (min_quantity, price) as loaded into the PriceBook
+}
+
+fn cart(lines: usize) -> Vec<Line> {
+    (0..lines)
+        .map(|i| {
+            let base_price = 10.0 + (i % 50) as f64 * 1.37;
+            Line {
+                base_price,
+                quantity: 1 + (i % 12) as i32,
+                rules: vec![
+                    (1, base_price * 0.9), // Sale
+                    (5, base_price * 0.85),
+                    (10, base_price * 0.8),
+                    (25, base_price * 0.7),
+                ],
+            }
+        })
+        .collect()
+}
+
+// What cart_pricing::price_cart computes once the products, rules and coupon are loaded
+fn price(cart: &[Line], coupon_amount_off: Option<i64>) -> cart_math::Totals {
+    let line_totals = cart.iter().map(|line| {
+        let unit_price = cart_math::tiered_price(line.base_price, line.rules.iter().copied(), line.quantity);
+        cart_math::line_amounts(unit_price, line.quantity).1
+    });
+    cart_math::totals(line_totals, coupon_amount_off, TAX_RATE, SHIPPING)
+}
+
+fn bench_unit_price(c: &mut Criterion) {
+    let line = &cart(1)[0];
+    c.bench_function("tiered_price", |b| {
+        b.iter(|| cart_math::tiered_price(black_box(line.base_price), line.rules.iter().copied(), black_box(12)))
+    });
+}
+
+fn bench_cart_totals(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cart_totals");
+    for size in [1, 10, 100] {
+        let cart = cart(size);
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::new("no_coupon", size), &cart, |b, cart| {
+            b.iter(|| price(black_box(cart), None))
+        });
+        group.bench_with_input(BenchmarkId::new("coupon", size), &cart, |b, cart| {
+            b.iter(|| price(black_box(cart), Some(black_box(1500))))
+        });
+    }
+    group.finish();
+}
+
+// A coupon larger than the cart stops at Stripe's minimum charge; check the math still holds
+// before timing it, so a broken refactor fails here rather than benchmarking wrong numbers
+fn bench_discount_floor(c: &mut Criterion) {
+    let totals = price(&cart(1), Some(1_000_000));
+    assert_eq!(totals.subtotal - totals.discount, cart_math::MIN_CHARGE_CENTS);
+    assert_eq!(totals.tax, cart_math::sales_tax(cart_math::MIN_CHARGE_CENTS, TAX_RATE));
+    assert_eq!(totals.total, cart_math::MIN_CHARGE_CENTS + totals.tax + SHIPPING);
+    c.bench_function("apply_discount", |b| {
+        b.iter(|| cart_math::apply_discount(black_box(12_345), black_box(20_000)))
+    });
+}
+
+criterion_group!(benches, bench_unit_price, bench_cart_totals, bench_discount_floor);
+criterion_main!(benches);
@@ -0,0 +1,98 @@
+// Order listing benchmarks - run with `cargo bench --bench order_listing`
+// Compares OrderRepo::list_with_items (items aggregated with json_agg in the orders query) with loading
+// the orders and then each order's items in its own query. Needs the migrated database in
+// DATABASE_URL (skipped when unset); the customer and orders it creates are removed afterwards
+
+use backend::testing::{Order, OrderListFilter, OrderRepo, PgOrderRepo};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+const ORDERS: i64 = 200;
+const ITEMS_PER_ORDER: i64 = 3;
+
+async fn seed(pool: &PgPool) -> Uuid {
+    let customer_id: Uuid =
+        sqlx::query_scalar("INSERT INTO customers (email) VALUES ('order-listing-bench') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+    sqlx::query(
+        "WITH new_orders AS (
+             INSERT INTO orders (payment_provider, payment_id, total_amount, status, customer_id, created_at)
+             SELECT 'stripe', 'pi_bench_' || n, 3000, 'completed', $1, NOW() - n * INTERVAL '1 minute'
+             FROM generate_series(1, $2) AS n
+             RETURNING id
+         )
+         INSERT INTO order_items (order_id, product_name, quantity, unit_price, total_price)
+         SELECT o.id, 'Bench item ' || i, 1, 1000, 1000
+         FROM new_orders o CROSS JOIN generate_series(1, $3) AS i",
+    )
+    .bind(customer_id)
+    .bind(ORDERS)
+    .bind(ITEMS_PER_ORDER)
+    .execute(pool)
+    .await
+    .unwrap();
+    customer_id
+}
+
+async fn cleanup(pool: &PgPool, customer_id: Uuid) {
+    sqlx::query("DELETE FROM orders WHERE customer_id = $1").bind(customer_id).execute(pool).await.unwrap();
+    sqlx::query("DELETE FROM customers WHERE id = $1").bind(customer_id).execute(pool).await.unwrap();
+}
+
+// What the admin customer detail did before list_with_items: the orders, then one query per order
+async fn naive(pool: &PgPool, repo: &PgOrderRepo, customer_id: Uuid, limit: i64) -> usize {
+    let orders = sqlx::query_as::<_, Order>(
+        "SELECT * FROM orders WHERE customer_id = $1 ORDER BY created_at DESC, id DESC LIMIT $2",
+    )
+    .bind(customer_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .unwrap();
+    let mut items = 0;
+    for order in &orders {
+        items += repo.items(order.id).await.unwrap().len();
+    }
+    items
+}
+
+async fn json_agg(repo: &PgOrderRepo, customer_id: Uuid, limit: i64) -> usize {
+    let filter = OrderListFilter { customer_id: Some(customer_id), limit: Some(limit), ..Default::default() };
+    repo.list_with_items(&filter).await.unwrap().iter().map(|o| o.items.len()).sum()
+}
+
+fn bench_listing(c: &mut Criterion) {
+    let Ok(database_url) = std::env::var("DATABASE_URL") else {
+        eprintln!("DATABASE_URL is not set, skipping the order listing benchmarks");
+        return;
+    };
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(PgPool::connect(&database_url)).expect("connect to DATABASE_URL");
+    let repo = PgOrderRepo::new(Arc::new(pool.clone()));
+    let customer_id = rt.block_on(seed(&pool));
+
+    let mut group = c.benchmark_group("order_listing");
+    for limit in [50, ORDERS] {
+        let expected = (limit * ITEMS_PER_ORDER) as usize;
+        assert_eq!(rt.block_on(naive(&pool, &repo, customer_id, limit)), expected);
+        assert_eq!(rt.block_on(json_agg(&repo, customer_id, limit)), expected);
+
+        group.throughput(Throughput::Elements(limit as u64));
+        group.bench_with_input(BenchmarkId::new("naive", limit), &limit, |b, &limit| {
+            b.iter(|| rt.block_on(naive(&pool, &repo, customer_id, limit)))
+        });
+        group.bench_with_input(BenchmarkId::new("json_agg", limit), &limit, |b, &limit| {
+            b.iter(|| rt.block_on(json_agg(&repo, customer_id, limit)))
+        });
+    }
+    group.finish();
+
+    rt.block_on(cleanup(&pool, customer_id));
+}
+
+criterion_group!(benches, bench_listing);
+criterion_main!(benches);
@@ -0,0 +1,116 @@
+// Checkout load scenario for k6 (https://k6.io)
+// Browses the catalog, prices a cart and starts checkout, the path every order takes. Run it
+// against a server started with `--profile load` so no card is charged and nothing is sent:
+//
+//   cargo run --release -- --profile load
+//   k6 run backend/loadtest/checkout.js
+//
+// BASE_URL (default http://localhost:3000), VUS (peak virtual users, default 50) and
+// DURATION (time at peak, default 2m) override the defaults, e.g. k6 run -e VUS=200 ...
+// The thresholds are the release bar: k6 exits non-zero when any of them is missed.
+// Products need inventory for the whole run; each checkout reserves stock until its hold expires.
+
+import http from 'k6/http';
+import { check, fail, group, sleep } from 'k6';
+
+const BASE_URL = __ENV.BASE_URL || 'http://localhost:3000';
+const VUS = parseInt(__ENV.VUS || '50', 10);
+const DURATION = __ENV.DURATION || '2m';
+const JSON_HEADERS = { headers: { 'Content-Type': 'application/json' } };
+
+export const options = {
+  scenarios: {
+    checkout: {
+      executor: 'ramping-vus',
+      startVUs: 0,
+      stages: [
+        { duration: '30s', target: VUS },
+        { duration: DURATION, target: VUS },
+        { duration: '15s', target: 0 },
+      ],
+      gracefulRampDown: '10s',
+    },
+  },
+  thresholds: {
+    http_req_failed: ['rate<0.01'],
+    'http_req_duration{step:products}': ['p(95)<200'],
+    'http_req_duration{step:cart_price}': ['p(95)<250', 'p(99)<500'],
+    'http_req_duration{step:payment_intent}': ['p(95)<600', 'p(99)<1200'],
+    checks: ['rate>0.99'],
+  },
+};
+
+// Published products to fill carts from, fetched once before the run
+export function setup() {
+  const res = http.get(`${BASE_URL}/api/products?limit=100`);
+  if (res.status !== 200) {
+    fail(`GET /api/products returned ${res.status}; is the server up?`);
+  }
+  const products = res.json('data').filter((p) => p.inventory > 0);
+  if (products.length === 0) {
+    fail('No products with inventory to check out');
+  }
+  return { productIds: products.map((p) => p.id) };
+}
+
+// 1-4 distinct products, quantities 1-3, so some lines reach quantity breaks
+function randomCart(productIds) {
+  const lines = 1 + Math.floor(Math.random() * 4);
+  const picked = new Set();
+  while (picked.size < Math.min(lines, productIds.length)) {
+    picked.add(productIds[Math.floor(Math.random() * productIds.length)]);
+  }
+  return [...picked].map((id) => ({ product_id: id, quantity: 1 + Math.floor(Math.random() * 3) }));
+}
+
+export default function (data) {
+  const items = randomCart(data.productIds);
+  const toAddress = {
+    name: 'Load Test',
+    street1: '417 Montgomery St',
+    city: 'San Francisco',
+    state: 'CA',
+    zip: '94104',
+    country: 'US',
+  };
+
+  group('browse', () => {
+    const res = http.get(`${BASE_URL}/api/products?limit=24`, { tags: { step: 'products' } });
+    check(res, { 'products 200': (r) => r.status === 200 });
+  });
+  sleep(Math.random() * 2);
+
+  let total;
+  group('cart', () => {
+    const res = http.post(
+      `${BASE_URL}/api/cart/price`,
+      JSON.stringify({ items, to_address: toAddress }),
+      { ...JSON_HEADERS, tags: { step: 'cart_price' } },
+    );
+    check(res, { 'cart priced': (r) => r.status === 200 && r.json('total') > 0 });
+    total = res.status === 200 ? res.json('total') : undefined;
+  });
+  if (total === undefined) {
+    return;
+  }
+  sleep(Math.random() * 3);
+
+  group('checkout', () => {
+    const res = http.post(
+      `${BASE_URL}/api/create-payment-intent`,
+      JSON.stringify({
+        amount: total,
+        currency: 'usd',
+        items,
+        to_address: toAddress,
+        email: `load+${__VU}-${__ITER}@example.com`,
+      }),
+      { ...JSON_HEADERS, tags: { step: 'payment_intent' } },
+    );
+    check(res, {
+      'payment intent created': (r) => r.status === 200 && !!r.json('client_secret'),
+      'charged the priced total': (r) => r.status !== 200 || r.json('amount') === total,
+    });
+  });
+  sleep(1);
+}
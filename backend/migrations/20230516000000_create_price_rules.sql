@@ -0,0 +1,17 @@
+-- Create price_rules table for scheduled sales and quantity-break pricing
+CREATE TABLE IF NOT EXISTS price_rules (
+    id SERIAL PRIMARY KEY,
+    product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    rule_type VARCHAR(50) NOT NULL, -- 'sale' or 'quantity_break'
+    price DOUBLE PRECISION NOT NULL, -- Unit price while the rule applies
+    min_quantity INTEGER NOT NULL DEFAULT 1, -- Minimum line quantity for the rule to apply
+    starts_at TIMESTAMP WITH TIME ZONE, -- NULL = active immediately
+    ends_at TIMESTAMP WITH TIME ZONE, -- NULL = no end date
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT price_rules_rule_type_check CHECK (rule_type IN ('sale', 'quantity_break')),
+    CONSTRAINT price_rules_min_quantity_check CHECK (min_quantity >= 1),
+    CONSTRAINT price_rules_price_check CHECK (price >= 0)
+);
+
+CREATE INDEX IF NOT EXISTS idx_price_rules_product_id ON price_rules(product_id);
+CREATE INDEX IF NOT EXISTS idx_price_rules_window ON price_rules(starts_at, ends_at);
@@ -0,0 +1,10 @@
+-- Add draft/publish workflow to products
+ALTER TABLE products
+    ADD COLUMN IF NOT EXISTS status VARCHAR(20) NOT NULL DEFAULT 'published', -- 'draft', 'published', 'scheduled'
+    ADD COLUMN IF NOT EXISTS publish_at TIMESTAMP WITH TIME ZONE; -- When a scheduled product goes live
+
+ALTER TABLE products
+    ADD CONSTRAINT products_status_check CHECK (status IN ('draft', 'published', 'scheduled'));
+
+CREATE INDEX IF NOT EXISTS idx_products_status ON products(status);
+CREATE INDEX IF NOT EXISTS idx_products_publish_at ON products(publish_at) WHERE status = 'scheduled';
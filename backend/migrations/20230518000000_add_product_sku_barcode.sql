@@ -0,0 +1,8 @@
+-- Add SKU and barcode identifiers to products
+ALTER TABLE products
+    ADD COLUMN IF NOT EXISTS sku VARCHAR(64), -- Merchant stock keeping unit
+    ADD COLUMN IF NOT EXISTS barcode VARCHAR(64); -- UPC/EAN/GTIN for scanning
+
+-- NULLs are allowed (and never conflict); any set value must be unique
+CREATE UNIQUE INDEX IF NOT EXISTS idx_products_sku_unique ON products(sku);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_products_barcode_unique ON products(barcode);
@@ -0,0 +1,6 @@
+-- Add shipping weight and dimensions to products
+ALTER TABLE products
+    ADD COLUMN IF NOT EXISTS weight_oz DOUBLE PRECISION, -- Shipping weight in ounces
+    ADD COLUMN IF NOT EXISTS length_in DOUBLE PRECISION, -- Packed dimensions in inches
+    ADD COLUMN IF NOT EXISTS width_in DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS height_in DOUBLE PRECISION;
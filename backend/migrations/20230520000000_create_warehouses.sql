@@ -0,0 +1,48 @@
+-- Create warehouses table for multi-origin shipping
+CREATE TABLE IF NOT EXISTS warehouses (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    street1 VARCHAR(255) NOT NULL,
+    street2 VARCHAR(255),
+    city VARCHAR(100) NOT NULL,
+    state VARCHAR(100) NOT NULL,
+    zip VARCHAR(20) NOT NULL,
+    country VARCHAR(2) NOT NULL DEFAULT 'US',
+    phone VARCHAR(50),
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Stock held at each warehouse
+CREATE TABLE IF NOT EXISTS warehouse_inventory (
+    warehouse_id INTEGER NOT NULL REFERENCES warehouses(id) ON DELETE CASCADE,
+    product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL DEFAULT 0 CHECK (quantity >= 0),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (warehouse_id, product_id)
+);
+
+-- One fulfillment (shipment) per origin warehouse for an order
+CREATE TABLE IF NOT EXISTS order_fulfillments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    order_id UUID NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    warehouse_id INTEGER REFERENCES warehouses(id) ON DELETE SET NULL,
+    status VARCHAR(50) NOT NULL DEFAULT 'pending', -- 'pending', 'shipped', 'delivered', 'cancelled'
+    carrier VARCHAR(100),
+    tracking_code VARCHAR(255),
+    shipment_id VARCHAR(255), -- EasyPost shipment ID
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Products (and quantities) contained in each fulfillment
+CREATE TABLE IF NOT EXISTS order_fulfillment_items (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    fulfillment_id UUID NOT NULL REFERENCES order_fulfillments(id) ON DELETE CASCADE,
+    product_id INTEGER REFERENCES products(id) ON DELETE SET NULL,
+    quantity INTEGER NOT NULL CHECK (quantity > 0)
+);
+
+CREATE INDEX IF NOT EXISTS idx_warehouse_inventory_product_id ON warehouse_inventory(product_id);
+CREATE INDEX IF NOT EXISTS idx_order_fulfillments_order_id ON order_fulfillments(order_id);
+CREATE INDEX IF NOT EXISTS idx_order_fulfillment_items_fulfillment_id ON order_fulfillment_items(fulfillment_id);
@@ -0,0 +1,25 @@
+-- Rules-based shipping: zones matched by destination and their shipping methods
+CREATE TABLE IF NOT EXISTS shipping_zones (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    countries TEXT[] NOT NULL DEFAULT '{}', -- ISO country codes; empty = any country
+    states TEXT[] NOT NULL DEFAULT '{}', -- State/province codes; empty = any state
+    zip_prefixes TEXT[] NOT NULL DEFAULT '{}', -- Postal code prefixes; empty = any postal code
+    priority INTEGER NOT NULL DEFAULT 0, -- Higher priority zones are matched first
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS shipping_methods (
+    id SERIAL PRIMARY KEY,
+    zone_id INTEGER NOT NULL REFERENCES shipping_zones(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL, -- e.g. 'Standard', 'Express'
+    base_rate DOUBLE PRECISION NOT NULL DEFAULT 0, -- Flat rate per order
+    per_item_surcharge DOUBLE PRECISION NOT NULL DEFAULT 0, -- Added per unit shipped
+    free_over DOUBLE PRECISION, -- Free when the cart subtotal reaches this amount
+    min_delivery_days INTEGER,
+    max_delivery_days INTEGER,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_shipping_methods_zone_id ON shipping_methods(zone_id);
@@ -0,0 +1,2 @@
+-- Business days a warehouse needs to pick, pack and hand an order to the carrier
+ALTER TABLE warehouses ADD COLUMN IF NOT EXISTS handling_days INTEGER NOT NULL DEFAULT 1 CHECK (handling_days >= 0);
@@ -0,0 +1,4 @@
+-- Customs metadata for international shipments
+ALTER TABLE products ADD COLUMN IF NOT EXISTS hs_code VARCHAR(20); -- Harmonized System tariff number
+ALTER TABLE products ADD COLUMN IF NOT EXISTS country_of_origin VARCHAR(2); -- ISO country code where made
+ALTER TABLE products ADD COLUMN IF NOT EXISTS domestic_only BOOLEAN NOT NULL DEFAULT FALSE; -- Cannot ship abroad
@@ -0,0 +1,37 @@
+-- Child shipments for orders split across several ship-to addresses (e.g. gifts).
+-- Rows are written at checkout keyed by the payment intent and linked to the
+-- order once the payment succeeds.
+CREATE TABLE IF NOT EXISTS order_shipments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    payment_intent_id VARCHAR(255) NOT NULL,
+    order_id UUID REFERENCES orders(id) ON DELETE CASCADE, -- NULL until payment succeeds
+    address_key VARCHAR(100) NOT NULL, -- Address book entry chosen at checkout
+    recipient_name VARCHAR(255),
+    street1 VARCHAR(255) NOT NULL,
+    street2 VARCHAR(255),
+    city VARCHAR(100) NOT NULL,
+    state VARCHAR(100) NOT NULL,
+    zip VARCHAR(20) NOT NULL,
+    country VARCHAR(2) NOT NULL DEFAULT 'US',
+    phone VARCHAR(50),
+    shipping_method VARCHAR(255) NOT NULL,
+    subtotal_amount BIGINT NOT NULL, -- Item total for this address, in cents
+    shipping_amount BIGINT NOT NULL, -- Shipping charged for this address, in cents
+    status VARCHAR(50) NOT NULL DEFAULT 'pending', -- 'pending', 'fulfilling', 'shipped'
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Products (and quantities) going to each address
+CREATE TABLE IF NOT EXISTS order_shipment_items (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    shipment_id UUID NOT NULL REFERENCES order_shipments(id) ON DELETE CASCADE,
+    product_id INTEGER REFERENCES products(id) ON DELETE SET NULL,
+    quantity INTEGER NOT NULL CHECK (quantity > 0)
+);
+
+-- Fulfillments created for a specific ship-to address
+ALTER TABLE order_fulfillments ADD COLUMN IF NOT EXISTS order_shipment_id UUID REFERENCES order_shipments(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_order_shipments_payment_intent_id ON order_shipments(payment_intent_id);
+CREATE INDEX IF NOT EXISTS idx_order_shipments_order_id ON order_shipments(order_id);
+CREATE INDEX IF NOT EXISTS idx_order_shipment_items_shipment_id ON order_shipment_items(shipment_id);
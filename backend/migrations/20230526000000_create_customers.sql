@@ -0,0 +1,33 @@
+-- Customers, one per email address seen at checkout
+CREATE TABLE IF NOT EXISTS customers (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email VARCHAR(255) NOT NULL UNIQUE, -- Stored lowercase
+    name VARCHAR(255),
+    phone VARCHAR(50),
+    password_hash TEXT, -- NULL until the customer sets a password
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    disabled BOOLEAN NOT NULL DEFAULT FALSE,
+    password_reset_token_hash VARCHAR(64), -- SHA-256 of the emailed reset token
+    password_reset_expires_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS customer_id UUID REFERENCES customers(id) ON DELETE SET NULL;
+
+-- Backfill customers from existing orders
+INSERT INTO customers (email, name)
+SELECT LOWER(customer_email), MAX(customer_name)
+FROM orders
+WHERE customer_email IS NOT NULL AND customer_email <> ''
+GROUP BY LOWER(customer_email)
+ON CONFLICT (email) DO NOTHING;
+
+UPDATE orders o SET customer_id = c.id
+FROM customers c
+WHERE LOWER(o.customer_email) = c.email AND o.customer_id IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_orders_customer_id ON orders(customer_id);
+CREATE INDEX IF NOT EXISTS idx_customers_name ON customers(name);
+CREATE INDEX IF NOT EXISTS idx_customers_tags ON customers USING GIN(tags);
+CREATE INDEX IF NOT EXISTS idx_customers_password_reset_token_hash ON customers(password_reset_token_hash);
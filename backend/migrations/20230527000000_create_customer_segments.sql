@@ -0,0 +1,35 @@
+-- Customer segments built from purchase data and synced to marketing lists.
+-- NULL filters are ignored; spend is in cents and only completed orders count.
+CREATE TABLE IF NOT EXISTS customer_segments (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL UNIQUE,
+    min_order_count INTEGER,
+    max_order_count INTEGER,
+    min_total_spend BIGINT,
+    max_total_spend BIGINT,
+    min_days_since_last_order INTEGER, -- e.g. 90 for lapsed customers
+    max_days_since_last_order INTEGER, -- e.g. 30 for recently active customers
+    tags TEXT[] NOT NULL DEFAULT '{}', -- Customers must carry all of these tags
+    provider VARCHAR(20), -- 'brevo', 'letre' or NULL (not synced)
+    provider_list_id VARCHAR(100),
+    member_count INTEGER NOT NULL DEFAULT 0,
+    materialized_at TIMESTAMP WITH TIME ZONE,
+    synced_at TIMESTAMP WITH TIME ZONE,
+    last_sync_error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Materialized membership. Rows leaving the segment keep removed_at set until
+-- the provider list has been updated.
+CREATE TABLE IF NOT EXISTS customer_segment_members (
+    segment_id INTEGER NOT NULL REFERENCES customer_segments(id) ON DELETE CASCADE,
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    email VARCHAR(255) NOT NULL,
+    added_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    removed_at TIMESTAMP WITH TIME ZONE,
+    synced_at TIMESTAMP WITH TIME ZONE, -- NULL until pushed to the provider list
+    PRIMARY KEY (segment_id, customer_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_customer_segment_members_customer_id ON customer_segment_members(customer_id);
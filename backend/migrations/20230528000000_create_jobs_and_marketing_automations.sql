@@ -0,0 +1,44 @@
+-- Database-backed job queue for deferred background work
+CREATE TABLE IF NOT EXISTS jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_type VARCHAR(100) NOT NULL, -- e.g. 'marketing_email'
+    payload JSONB NOT NULL DEFAULT '{}',
+    status VARCHAR(20) NOT NULL DEFAULT 'pending', -- 'pending', 'running', 'completed', 'failed'
+    run_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 5,
+    last_error TEXT,
+    locked_at TIMESTAMP WITH TIME ZONE, -- Set while a worker runs the job
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_jobs_pending_run_at ON jobs(run_at) WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS idx_jobs_status ON jobs(status);
+
+-- Marketing emails sent in response to domain events. Several rows with the
+-- same trigger and different delays form a series (e.g. the welcome series).
+CREATE TABLE IF NOT EXISTS marketing_automations (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    trigger VARCHAR(50) NOT NULL, -- 'first_purchase', 'shipment_delivered', 'order_refunded'
+    delay_days INTEGER NOT NULL DEFAULT 0 CHECK (delay_days >= 0),
+    subject VARCHAR(255) NOT NULL,
+    html_body TEXT NOT NULL, -- Supports {{name}}, {{email}}, {{order_id}}, {{tracking_code}}
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_marketing_automations_trigger ON marketing_automations(trigger) WHERE active = TRUE;
+
+-- Default flows; edit or deactivate them from the admin API
+INSERT INTO marketing_automations (name, trigger, delay_days, subject, html_body) VALUES
+    ('Welcome series: thank you', 'first_purchase', 0, 'Welcome to R-Com Store!',
+     '<p>Hi {{name}},</p><p>Thanks for your first order ({{order_id}}). We''re glad to have you with us.</p>'),
+    ('Welcome series: getting started', 'first_purchase', 3, 'Getting the most out of your purchase',
+     '<p>Hi {{name}},</p><p>Here are a few tips to enjoy your new purchase, plus what''s new in the store.</p>'),
+    ('Review request', 'shipment_delivered', 7, 'How was your order?',
+     '<p>Hi {{name}},</p><p>Your package {{tracking_code}} arrived a few days ago. We''d love to hear what you think!</p>'),
+    ('Win-back', 'order_refunded', 14, 'We''d love to have you back',
+     '<p>Hi {{name}},</p><p>We''re sorry your last order didn''t work out. Come see what''s new in the store.</p>');
@@ -0,0 +1,24 @@
+-- Order reviews collected from review request emails. A one-click rating creates
+-- a draft; the customer can then add text, which submits it for moderation.
+CREATE TABLE IF NOT EXISTS reviews (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    order_id UUID NOT NULL UNIQUE REFERENCES orders(id) ON DELETE CASCADE,
+    customer_id UUID REFERENCES customers(id) ON DELETE SET NULL,
+    customer_email VARCHAR(255),
+    rating INTEGER NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    body TEXT,
+    status VARCHAR(20) NOT NULL DEFAULT 'draft' CHECK (status IN ('draft', 'submitted', 'published', 'hidden')),
+    submitted_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_reviews_status ON reviews(status);
+CREATE INDEX IF NOT EXISTS idx_reviews_customer_id ON reviews(customer_id);
+
+-- Add the one-click rating links to the default review request (if it hasn't been edited)
+UPDATE marketing_automations
+SET html_body = '<p>Hi {{name}},</p><p>Your package {{tracking_code}} arrived a few days ago. How would you rate your order?</p>{{rating_links}}<p>You can add a few words after picking a rating.</p>',
+    updated_at = NOW()
+WHERE trigger = 'shipment_delivered'
+  AND html_body = '<p>Hi {{name}},</p><p>Your package {{tracking_code}} arrived a few days ago. We''d love to hear what you think!</p>';
@@ -0,0 +1,42 @@
+-- Referral program: every customer gets a shareable code; a referred friend's
+-- first completed order earns the referrer store credit or a coupon
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS referral_code VARCHAR(20) UNIQUE;
+
+CREATE TABLE IF NOT EXISTS referral_visits (
+    id BIGSERIAL PRIMARY KEY,
+    referrer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    visited_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_referral_visits_referrer_id ON referral_visits(referrer_id);
+
+-- Single-use discount codes (issued as referral rewards), applied at checkout
+CREATE TABLE IF NOT EXISTS coupons (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    code VARCHAR(50) NOT NULL UNIQUE,
+    amount_off BIGINT NOT NULL CHECK (amount_off > 0), -- in cents
+    customer_id UUID REFERENCES customers(id) ON DELETE SET NULL, -- Who it was issued to
+    source VARCHAR(50) NOT NULL, -- e.g. 'referral'
+    expires_at TIMESTAMP WITH TIME ZONE,
+    redeemed_at TIMESTAMP WITH TIME ZONE,
+    redeemed_order_id UUID REFERENCES orders(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_coupons_customer_id ON coupons(customer_id);
+
+-- One row per referred customer, recorded when their first order completes
+CREATE TABLE IF NOT EXISTS referrals (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    referrer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    referred_customer_id UUID NOT NULL UNIQUE REFERENCES customers(id) ON DELETE CASCADE,
+    order_id UUID REFERENCES orders(id) ON DELETE SET NULL,
+    status VARCHAR(20) NOT NULL CHECK (status IN ('rewarded', 'ineligible')), -- ineligible: friend had ordered before
+    reward_type VARCHAR(20), -- 'store_credit' or 'coupon'
+    reward_amount BIGINT, -- in cents
+    coupon_id UUID REFERENCES coupons(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    rewarded_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_referrals_referrer_id ON referrals(referrer_id);
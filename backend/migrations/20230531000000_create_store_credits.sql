@@ -0,0 +1,21 @@
+-- Store credit ledger: one row per credit (positive) or debit (negative) entry.
+-- A customer's balance is the sum of their entries.
+CREATE TABLE IF NOT EXISTS store_credits (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    amount BIGINT NOT NULL CHECK (amount <> 0), -- in cents
+    reason VARCHAR(20) NOT NULL CHECK (reason IN ('refund', 'referral', 'goodwill', 'checkout', 'adjustment')),
+    note TEXT,
+    order_id UUID REFERENCES orders(id) ON DELETE SET NULL,
+    created_by VARCHAR(255), -- Admin username for manual entries
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_store_credits_customer_id ON store_credits(customer_id);
+CREATE INDEX IF NOT EXISTS idx_store_credits_order_id ON store_credits(order_id);
+
+-- Referral rewards issued as store credit before the ledger existed
+INSERT INTO store_credits (customer_id, amount, reason, note, order_id, created_at)
+SELECT referrer_id, reward_amount, 'referral', 'Referral reward', order_id, rewarded_at
+FROM referrals
+WHERE status = 'rewarded' AND reward_type = 'store_credit' AND reward_amount > 0;
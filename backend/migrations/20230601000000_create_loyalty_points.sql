@@ -0,0 +1,17 @@
+-- Loyalty points ledger: points earned on completed orders, redeemed at checkout
+-- and reversed on refunds. A customer's balance is the sum of their entries.
+CREATE TABLE IF NOT EXISTS loyalty_points (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    points INTEGER NOT NULL CHECK (points <> 0), -- negative for redemptions and reversals
+    reason VARCHAR(20) NOT NULL CHECK (reason IN ('earned', 'redeemed', 'reversed', 'adjustment')),
+    order_id UUID REFERENCES orders(id) ON DELETE SET NULL,
+    note TEXT,
+    created_by VARCHAR(255), -- Admin username for adjustments
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_loyalty_points_customer_id ON loyalty_points(customer_id);
+-- Each order earns, redeems and is reversed at most once
+CREATE UNIQUE INDEX IF NOT EXISTS idx_loyalty_points_order_reason ON loyalty_points(order_id, reason)
+    WHERE order_id IS NOT NULL AND reason IN ('earned', 'redeemed', 'reversed');
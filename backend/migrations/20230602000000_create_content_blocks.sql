@@ -0,0 +1,19 @@
+-- CMS content blocks: storefront copy (hero banner, announcement bar, homepage
+-- sections) editable from the admin without a redeploy
+CREATE TABLE IF NOT EXISTS content_blocks (
+    id SERIAL PRIMARY KEY,
+    key VARCHAR(100) NOT NULL UNIQUE, -- Stable identifier, e.g. 'summer-sale-hero'
+    placement VARCHAR(30) NOT NULL CHECK (placement IN ('hero', 'announcement', 'home_section')),
+    title VARCHAR(255),
+    format VARCHAR(20) NOT NULL DEFAULT 'markdown' CHECK (format IN ('markdown', 'html')),
+    body TEXT NOT NULL,
+    position INTEGER NOT NULL DEFAULT 0, -- Sort order within a placement
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    starts_at TIMESTAMP WITH TIME ZONE, -- Shown from (NULL = immediately)
+    ends_at TIMESTAMP WITH TIME ZONE,   -- Shown until (NULL = indefinitely)
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    CHECK (ends_at IS NULL OR starts_at IS NULL OR ends_at > starts_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_content_blocks_placement ON content_blocks(placement, position);
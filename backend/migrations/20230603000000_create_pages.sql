@@ -0,0 +1,28 @@
+-- Static storefront pages (shipping info, returns policy, ...) written in markdown
+-- and edited from the admin; served rendered and sanitized by /api/pages/:slug
+CREATE TABLE IF NOT EXISTS pages (
+    id SERIAL PRIMARY KEY,
+    slug VARCHAR(100) NOT NULL UNIQUE, -- URL path segment, e.g. 'shipping'
+    title VARCHAR(255) NOT NULL,
+    body_markdown TEXT NOT NULL,
+    meta_description VARCHAR(320),
+    published BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Starter copy for the pages linked from the storefront footer
+INSERT INTO pages (slug, title, meta_description, body_markdown) VALUES
+(
+    'shipping',
+    'Shipping Information',
+    'Shipping rates, processing times and delivery estimates.',
+    E'## Processing\n\nOrders placed before 2pm on business days ship the same day.\n\n## Rates\n\nShipping is calculated at checkout based on your address and the items in your cart.\n\n## Tracking\n\nYou will receive a tracking number by email as soon as your order ships.'
+),
+(
+    'returns',
+    'Returns Policy',
+    'How to return an item and get a refund.',
+    E'## 30-day returns\n\nUnused items in their original packaging can be returned within 30 days of delivery.\n\n## Refunds\n\nRefunds go back to the original payment method, or to store credit if you prefer, once we receive the item.\n\n## How to return\n\nContact us with your order number and we will send you a return label.'
+)
+ON CONFLICT (slug) DO NOTHING;
@@ -0,0 +1,18 @@
+-- Blog posts for SEO content. Posts are drafts until published_at is set and
+-- go live once it has passed, so posts can be scheduled ahead of time.
+CREATE TABLE IF NOT EXISTS posts (
+    id SERIAL PRIMARY KEY,
+    slug VARCHAR(150) NOT NULL UNIQUE,
+    title VARCHAR(255) NOT NULL,
+    excerpt TEXT, -- Summary for listings, meta description and RSS
+    body_markdown TEXT NOT NULL,
+    cover_image_url TEXT,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    author VARCHAR(255),
+    published_at TIMESTAMP WITH TIME ZONE, -- NULL = draft
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_posts_published_at ON posts(published_at DESC);
+CREATE INDEX IF NOT EXISTS idx_posts_tags ON posts USING GIN(tags);
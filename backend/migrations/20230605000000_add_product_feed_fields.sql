@@ -0,0 +1,14 @@
+-- Product fields needed by shopping feeds (Google Merchant Center)
+ALTER TABLE products ADD COLUMN IF NOT EXISTS image_url TEXT; -- Main product image (absolute URL or storefront path)
+ALTER TABLE products ADD COLUMN IF NOT EXISTS brand VARCHAR(100);
+ALTER TABLE products ADD COLUMN IF NOT EXISTS category VARCHAR(100); -- Store category, e.g. 'Apparel > T-Shirts'
+
+CREATE INDEX IF NOT EXISTS idx_products_category ON products(category);
+
+-- Maps store categories onto Google's product taxonomy (ID or full path,
+-- e.g. '212' or 'Apparel & Accessories > Clothing > Shirts & Tops')
+CREATE TABLE IF NOT EXISTS google_category_mappings (
+    category VARCHAR(100) PRIMARY KEY,
+    google_product_category VARCHAR(255) NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,23 @@
+-- Scoped keys for machine-to-machine access to the integration endpoints
+CREATE TABLE IF NOT EXISTS api_keys (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    key_prefix VARCHAR(16) NOT NULL, -- First characters of the key, shown so admins can tell keys apart
+    key_hash VARCHAR(64) NOT NULL UNIQUE, -- SHA-256 of the full key; the key itself is never stored
+    scopes TEXT[] NOT NULL, -- 'read:products', 'write:inventory', 'write:prices', 'read:orders', 'read:customers'
+    rate_limit_per_minute INTEGER NOT NULL DEFAULT 60,
+    last_used_at TIMESTAMP WITH TIME ZONE,
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- One row per authenticated request
+CREATE TABLE IF NOT EXISTS api_key_usage (
+    id BIGSERIAL PRIMARY KEY,
+    api_key_id INTEGER NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    method VARCHAR(10) NOT NULL,
+    path TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_key_usage_api_key_id ON api_key_usage(api_key_id, created_at DESC);
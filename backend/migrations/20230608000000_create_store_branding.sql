@@ -0,0 +1,13 @@
+-- Store identity used by every transactional email and printable order document (a single row)
+CREATE TABLE IF NOT EXISTS store_branding (
+    id INTEGER PRIMARY KEY DEFAULT 1 CHECK (id = 1),
+    store_name VARCHAR(255) NOT NULL DEFAULT 'R-Com Store',
+    logo_url TEXT,
+    primary_color VARCHAR(7) NOT NULL DEFAULT '#1976d2', -- Header background
+    accent_color VARCHAR(7) NOT NULL DEFAULT '#1976d2', -- Buttons and highlights
+    support_email VARCHAR(255),
+    footer_address TEXT, -- Postal address shown in email footers
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO store_branding (id) VALUES (1) ON CONFLICT (id) DO NOTHING;
@@ -0,0 +1,24 @@
+-- Emails handed to a provider, updated as delivery events come back
+CREATE TABLE IF NOT EXISTS email_log (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    provider VARCHAR(20) NOT NULL, -- 'brevo'
+    message_id VARCHAR(255), -- Provider's id, used to match webhook events
+    recipient VARCHAR(255) NOT NULL,
+    subject TEXT,
+    template_id BIGINT, -- Provider template, when sent from one
+    status VARCHAR(20) NOT NULL DEFAULT 'sent', -- 'sent', 'delivered', 'opened', 'clicked', 'deferred', 'soft_bounced', 'bounced', 'blocked', 'spam', 'unsubscribed', 'failed'
+    last_error TEXT, -- Reason given with bounces and blocks
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_log_message_id ON email_log(message_id);
+CREATE INDEX IF NOT EXISTS idx_email_log_recipient ON email_log(recipient);
+
+-- Addresses that must not be emailed again (hard bounces, spam complaints)
+CREATE TABLE IF NOT EXISTS email_suppressions (
+    email VARCHAR(255) PRIMARY KEY, -- Lowercased
+    reason VARCHAR(20) NOT NULL, -- 'hard_bounce', 'invalid_email', 'spam'
+    source VARCHAR(20) NOT NULL, -- Provider that reported it
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,22 @@
+-- One-time codes sent to confirm a customer controls a phone number
+CREATE TABLE IF NOT EXISTS phone_verifications (
+    id SERIAL PRIMARY KEY,
+    phone VARCHAR(20) NOT NULL, -- E.164
+    customer_id UUID REFERENCES customers(id) ON DELETE CASCADE, -- NULL for guest checkout
+    provider VARCHAR(20) NOT NULL, -- 'twilio_verify' or 'textbelt'
+    code_hash VARCHAR(64), -- SHA-256 of the code; NULL when Twilio Verify holds it
+    attempts INT NOT NULL DEFAULT 0,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    verified_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_phone_verifications_phone ON phone_verifications(phone, created_at);
+
+-- Numbers that passed verification; SMS notifications only go to these
+CREATE TABLE IF NOT EXISTS verified_phones (
+    phone VARCHAR(20) PRIMARY KEY, -- E.164
+    verified_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS phone_verified_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,19 @@
+-- Numbers that texted STOP; no SMS is sent to them until they text START
+CREATE TABLE IF NOT EXISTS sms_opt_outs (
+    phone VARCHAR(20) PRIMARY KEY, -- E.164
+    keyword VARCHAR(20) NOT NULL, -- Keyword they sent, e.g. 'STOP' or 'UNSUBSCRIBE'
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Every inbound text, kept for support
+CREATE TABLE IF NOT EXISTS sms_inbound_messages (
+    id SERIAL PRIMARY KEY,
+    message_sid VARCHAR(64) NOT NULL UNIQUE, -- Twilio MessageSid; retried webhooks are ignored
+    from_phone VARCHAR(20) NOT NULL,
+    to_phone VARCHAR(20),
+    body TEXT NOT NULL,
+    keyword VARCHAR(10), -- 'stop', 'start' or 'help' when the text was a compliance keyword
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_sms_inbound_messages_from_phone ON sms_inbound_messages(from_phone);
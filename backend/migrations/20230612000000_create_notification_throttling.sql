@@ -0,0 +1,19 @@
+-- Customer's IANA timezone (e.g. 'America/New_York'), used for SMS quiet hours
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS timezone VARCHAR(64);
+
+-- Recent sends per channel, counted against provider rate limits
+CREATE TABLE IF NOT EXISTS notification_sends (
+    id BIGSERIAL PRIMARY KEY,
+    channel VARCHAR(10) NOT NULL, -- 'sms' or 'email'
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_notification_sends_channel ON notification_sends(channel, created_at);
+
+-- Channels held back after the provider ran out of quota; queued sends wait until paused_until
+CREATE TABLE IF NOT EXISTS notification_pauses (
+    channel VARCHAR(10) PRIMARY KEY,
+    paused_until TIMESTAMP WITH TIME ZONE NOT NULL,
+    reason TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
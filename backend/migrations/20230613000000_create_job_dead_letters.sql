@@ -0,0 +1,13 @@
+-- Jobs that used up their retries move to the dead-letter state ('dead') until an admin requeues them
+UPDATE jobs SET status = 'dead' WHERE status = 'failed';
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS dead_at TIMESTAMP WITH TIME ZONE;
+UPDATE jobs SET dead_at = updated_at WHERE status = 'dead' AND dead_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_jobs_dead_at ON jobs(dead_at DESC) WHERE status = 'dead';
+
+-- Last time each operational alert fired, so a condition that persists isn't re-sent every check
+CREATE TABLE IF NOT EXISTS job_alerts (
+    alert VARCHAR(50) PRIMARY KEY, -- 'dead_letter_depth', 'webhook_failure_rate'
+    value DOUBLE PRECISION NOT NULL, -- Measurement that crossed the threshold
+    sent_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
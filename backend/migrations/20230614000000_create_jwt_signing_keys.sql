@@ -0,0 +1,12 @@
+-- Rotating JWT signing keys. Tokens carry the key id (kid) in their header and are checked against
+-- every key that isn't retired; new tokens are signed with the newest active key. Tokens without
+-- a kid were signed with JWT_SECRET, which stays accepted as key 'env'
+CREATE TABLE IF NOT EXISTS jwt_signing_keys (
+    kid VARCHAR(32) PRIMARY KEY,
+    secret TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    activates_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP, -- Signs new tokens from this time
+    retired_at TIMESTAMP WITH TIME ZONE -- No longer accepted
+);
+
+CREATE INDEX IF NOT EXISTS idx_jwt_signing_keys_active ON jwt_signing_keys(activates_at DESC) WHERE retired_at IS NULL;
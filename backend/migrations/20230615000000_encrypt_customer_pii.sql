@@ -0,0 +1,17 @@
+-- Customer emails and phones, and order shipment names, street addresses and phones, are stored
+-- encrypted by the application (longer than the plain values, so TEXT). Encrypted values can't be
+-- compared in SQL; exact-match lookups use keyed hashes filled in by the application at startup
+ALTER TABLE customers ALTER COLUMN email TYPE TEXT;
+ALTER TABLE customers ALTER COLUMN phone TYPE TEXT;
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS email_hash VARCHAR(64); -- HMAC-SHA256 of the lowercased email
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS phone_hash VARCHAR(64); -- HMAC-SHA256 of the E.164 phone
+
+-- One customer per email now means one per email hash
+ALTER TABLE customers DROP CONSTRAINT IF EXISTS customers_email_key;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_customers_email_hash ON customers(email_hash);
+CREATE INDEX IF NOT EXISTS idx_customers_phone_hash ON customers(phone_hash);
+
+ALTER TABLE order_shipments ALTER COLUMN recipient_name TYPE TEXT;
+ALTER TABLE order_shipments ALTER COLUMN street1 TYPE TEXT;
+ALTER TABLE order_shipments ALTER COLUMN street2 TYPE TEXT;
+ALTER TABLE order_shipments ALTER COLUMN phone TYPE TEXT;
@@ -0,0 +1,23 @@
+-- Saved admin product searches, evaluated on a schedule and emailed as digests.
+-- NULL filters are ignored; prices are in dollars like products.price.
+CREATE TABLE IF NOT EXISTS saved_searches (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL UNIQUE,
+    name_contains VARCHAR(255),
+    category VARCHAR(100),
+    brand VARCHAR(100),
+    status VARCHAR(20), -- 'draft', 'published' or 'scheduled'
+    min_inventory INTEGER,
+    max_inventory INTEGER, -- e.g. 4 for "under 5 in stock"
+    min_price DOUBLE PRECISION,
+    max_price DOUBLE PRECISION,
+    frequency VARCHAR(20), -- 'daily', 'weekly' or NULL (no digest)
+    recipients TEXT[] NOT NULL DEFAULT '{}',
+    send_when_empty BOOLEAN NOT NULL DEFAULT FALSE,
+    last_run_at TIMESTAMP WITH TIME ZONE,
+    last_match_count INTEGER,
+    last_sent_at TIMESTAMP WITH TIME ZONE,
+    last_error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
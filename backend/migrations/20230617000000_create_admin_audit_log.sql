@@ -0,0 +1,15 @@
+-- Record of admin bulk changes. Entries that can be reverted keep the data
+-- needed to do so in undo until they are undone.
+CREATE TABLE IF NOT EXISTS admin_audit_log (
+    id SERIAL PRIMARY KEY,
+    admin_username VARCHAR(255) NOT NULL,
+    action VARCHAR(50) NOT NULL, -- e.g. 'bulk_price_update'
+    summary TEXT NOT NULL,
+    details JSONB NOT NULL DEFAULT '{}',
+    undo JSONB,
+    undone_at TIMESTAMP WITH TIME ZONE,
+    undone_by VARCHAR(255),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_admin_audit_log_created_at ON admin_audit_log(created_at DESC);
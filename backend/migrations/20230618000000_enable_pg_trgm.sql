@@ -0,0 +1,2 @@
+-- Trigram name similarity for duplicate product detection on create and CSV import
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
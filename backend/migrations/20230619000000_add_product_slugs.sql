@@ -0,0 +1,22 @@
+-- URL slugs for products, generated from the name. Slugs a product had before
+-- being renamed are kept in product_slug_history so old links still resolve.
+ALTER TABLE products ADD COLUMN IF NOT EXISTS slug VARCHAR(160);
+
+UPDATE products SET slug = TRIM(BOTH '-' FROM REGEXP_REPLACE(LOWER(name), '[^a-z0-9]+', '-', 'g'))
+WHERE slug IS NULL;
+UPDATE products SET slug = 'product' WHERE slug = '';
+-- Later products with the same name get their id appended
+UPDATE products p SET slug = p.slug || '-' || p.id
+FROM (SELECT id, ROW_NUMBER() OVER (PARTITION BY slug ORDER BY id) AS n FROM products) d
+WHERE d.id = p.id AND d.n > 1;
+
+ALTER TABLE products ALTER COLUMN slug SET NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_products_slug_unique ON products(slug);
+
+CREATE TABLE IF NOT EXISTS product_slug_history (
+    slug VARCHAR(160) PRIMARY KEY,
+    product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_product_slug_history_product_id ON product_slug_history(product_id);
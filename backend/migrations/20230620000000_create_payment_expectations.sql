@@ -0,0 +1,14 @@
+-- Amount the server computed for each payment intent at checkout. The payment webhook
+-- records what was actually received and flags the row when the two differ.
+CREATE TABLE IF NOT EXISTS payment_expectations (
+    payment_intent_id VARCHAR(255) PRIMARY KEY,
+    expected_amount BIGINT NOT NULL, -- in cents
+    currency VARCHAR(10) NOT NULL,
+    order_id UUID REFERENCES orders(id) ON DELETE SET NULL, -- NULL until payment succeeds
+    received_amount BIGINT, -- in cents, from the payment webhook
+    mismatch BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    verified_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_payment_expectations_mismatch ON payment_expectations(verified_at) WHERE mismatch;
@@ -0,0 +1,34 @@
+-- Snapshot of the priced cart behind each payment intent. Its id, the id reserved
+-- for the order and the customer travel in the payment metadata, so the webhook
+-- creates the order under that id and links it back to these lines.
+CREATE TABLE IF NOT EXISTS checkout_carts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    order_id UUID NOT NULL UNIQUE, -- Id the order is created with once payment succeeds
+    customer_id UUID REFERENCES customers(id) ON DELETE SET NULL,
+    payment_intent_id VARCHAR(255),
+    subtotal_amount BIGINT NOT NULL, -- All amounts in cents, as priced at checkout
+    discount_amount BIGINT NOT NULL DEFAULT 0,
+    tax_amount BIGINT NOT NULL DEFAULT 0,
+    shipping_amount BIGINT NOT NULL DEFAULT 0,
+    total_amount BIGINT NOT NULL,
+    currency VARCHAR(10) NOT NULL DEFAULT 'USD',
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    converted_at TIMESTAMP WITH TIME ZONE -- When the order was created
+);
+
+CREATE TABLE IF NOT EXISTS checkout_cart_items (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    cart_id UUID NOT NULL REFERENCES checkout_carts(id) ON DELETE CASCADE,
+    product_id INTEGER REFERENCES products(id) ON DELETE SET NULL,
+    product_name VARCHAR(255) NOT NULL,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    unit_price BIGINT NOT NULL, -- Effective price per unit in cents
+    line_total BIGINT NOT NULL,
+    ship_to VARCHAR(100) -- Address book key for multi-address checkout
+);
+
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS cart_id UUID REFERENCES checkout_carts(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_checkout_carts_payment_intent_id ON checkout_carts(payment_intent_id);
+CREATE INDEX IF NOT EXISTS idx_checkout_cart_items_cart_id ON checkout_cart_items(cart_id);
+CREATE INDEX IF NOT EXISTS idx_orders_cart_id ON orders(cart_id);
@@ -0,0 +1,8 @@
+-- Authorize-then-capture payments. With PAYMENT_CAPTURE_METHOD=manual the order is
+-- created as 'authorized' and moves to 'completed' when captured, or 'voided' when
+-- the authorization is cancelled.
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS authorization_expires_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS amount_captured BIGINT; -- in cents, NULL until captured
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS captured_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_orders_authorization_expires_at ON orders(authorization_expires_at) WHERE status = 'authorized';
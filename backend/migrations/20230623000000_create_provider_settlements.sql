@@ -0,0 +1,32 @@
+-- Payouts and the balance movements they settle, pulled from Stripe (balance transactions)
+-- and Square (payout entries) for reconciliation against orders.
+CREATE TABLE IF NOT EXISTS provider_payouts (
+    provider VARCHAR(20) NOT NULL, -- 'stripe' or 'square'
+    payout_id VARCHAR(255) NOT NULL,
+    amount BIGINT NOT NULL, -- in cents, what reached the bank account
+    currency VARCHAR(10) NOT NULL,
+    status VARCHAR(50) NOT NULL,
+    arrival_date TIMESTAMP WITH TIME ZONE,
+    synced_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (provider, payout_id)
+);
+
+CREATE TABLE IF NOT EXISTS provider_transactions (
+    provider VARCHAR(20) NOT NULL,
+    transaction_id VARCHAR(255) NOT NULL, -- Stripe balance transaction or Square payout entry id
+    payout_id VARCHAR(255) NOT NULL,
+    type VARCHAR(50) NOT NULL, -- charge, refund, adjustment, fee, ...
+    payment_id VARCHAR(255), -- Stripe payment intent (or charge) or Square payment it belongs to
+    gross_amount BIGINT NOT NULL, -- in cents, negative for money going out
+    fee_amount BIGINT NOT NULL DEFAULT 0,
+    net_amount BIGINT NOT NULL,
+    currency VARCHAR(10) NOT NULL,
+    order_id UUID REFERENCES orders(id) ON DELETE SET NULL, -- NULL until matched
+    occurred_at TIMESTAMP WITH TIME ZONE,
+    synced_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (provider, transaction_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_provider_transactions_payout ON provider_transactions(provider, payout_id);
+CREATE INDEX IF NOT EXISTS idx_provider_transactions_payment_id ON provider_transactions(payment_id);
+CREATE INDEX IF NOT EXISTS idx_provider_transactions_unmatched ON provider_transactions(occurred_at) WHERE order_id IS NULL;
@@ -0,0 +1,37 @@
+-- Chargebacks opened against Stripe payments, kept in sync by the charge.dispute.* webhooks,
+-- with the evidence admins gather before the response deadline.
+CREATE TABLE IF NOT EXISTS disputes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    stripe_dispute_id VARCHAR(255) NOT NULL UNIQUE,
+    charge_id VARCHAR(255) NOT NULL,
+    payment_intent_id VARCHAR(255),
+    order_id UUID REFERENCES orders(id) ON DELETE SET NULL,
+    amount BIGINT NOT NULL, -- in cents
+    currency VARCHAR(10) NOT NULL,
+    reason VARCHAR(100) NOT NULL,
+    status VARCHAR(50) NOT NULL, -- Stripe status: needs_response, under_review, won, lost, ...
+    evidence_due_by TIMESTAMP WITH TIME ZONE,
+    evidence_status VARCHAR(20) NOT NULL DEFAULT 'none', -- none, draft, submitted
+    evidence JSONB NOT NULL DEFAULT '{}', -- Text evidence fields, keyed by Stripe field name
+    submitted_at TIMESTAMP WITH TIME ZONE,
+    reminder_sent_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_disputes_order_id ON disputes(order_id);
+CREATE INDEX IF NOT EXISTS idx_disputes_evidence_due_by ON disputes(evidence_due_by) WHERE evidence_status <> 'submitted';
+
+-- Files uploaded to Stripe as dispute evidence
+CREATE TABLE IF NOT EXISTS dispute_evidence_files (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    dispute_id UUID NOT NULL REFERENCES disputes(id) ON DELETE CASCADE,
+    evidence_type VARCHAR(50) NOT NULL, -- Stripe file evidence field: receipt, shipping_documentation, ...
+    filename VARCHAR(255) NOT NULL,
+    content_type VARCHAR(100) NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    stripe_file_id VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_dispute_evidence_files_dispute_id ON dispute_evidence_files(dispute_id);
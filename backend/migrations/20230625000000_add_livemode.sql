@@ -0,0 +1,13 @@
+-- Live vs test tagging. Stripe events carry livemode in their payload; orders take it from the
+-- event that created them. Anything else recorded before this is assumed live.
+ALTER TABLE webhook_events ADD COLUMN IF NOT EXISTS livemode BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS livemode BOOLEAN NOT NULL DEFAULT TRUE;
+
+UPDATE webhook_events SET livemode = (payload->>'livemode')::BOOLEAN
+WHERE provider = 'stripe' AND payload->>'livemode' IN ('true', 'false');
+
+UPDATE orders o SET livemode = w.livemode
+FROM webhook_events w
+WHERE o.webhook_event_id = w.id;
+
+CREATE INDEX IF NOT EXISTS idx_orders_livemode_created_at ON orders(livemode, created_at);
@@ -0,0 +1,6 @@
+-- Orders entered by staff for phone and offline sales. They use payment_provider 'manual' and
+-- start 'completed' when already paid offline, or 'pending' until an invoice or the Stripe
+-- payment link sent for them is paid.
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS payment_method VARCHAR(20); -- offline or invoice; NULL for checkout payments
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS created_by VARCHAR(255);    -- Admin who entered the order
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS payment_link_url TEXT;      -- Latest Stripe Checkout link sent to the customer
@@ -0,0 +1,28 @@
+-- Stripe Payment Links generated by staff for a product or an ad-hoc amount (invoices,
+-- social selling). Each completed checkout creates an order linked back to its payment link
+-- and adds to the link's sales totals.
+CREATE TABLE IF NOT EXISTS payment_links (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    stripe_payment_link_id VARCHAR(255) NOT NULL UNIQUE,
+    stripe_price_id VARCHAR(255) NOT NULL,
+    url TEXT NOT NULL,
+    product_id INTEGER REFERENCES products(id) ON DELETE SET NULL, -- NULL for ad-hoc amounts
+    description TEXT NOT NULL,       -- Line item name shown at checkout
+    reference VARCHAR(255),          -- Invoice number, post or campaign the link was made for
+    unit_amount BIGINT NOT NULL,     -- in cents
+    quantity INTEGER NOT NULL DEFAULT 1,
+    currency VARCHAR(3) NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    livemode BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by VARCHAR(255),
+    sales_count INTEGER NOT NULL DEFAULT 0,
+    revenue BIGINT NOT NULL DEFAULT 0, -- in cents
+    last_sale_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_payment_links_created_at ON payment_links(created_at);
+
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS payment_link_id UUID REFERENCES payment_links(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_orders_payment_link_id ON orders(payment_link_id);
@@ -0,0 +1,60 @@
+-- B2B quotes. Customers request one from a cart; admins adjust prices (each change is kept
+-- as a revision) and send it with an accept link. Accepting converts it into a pending
+-- manual order paid through a Stripe payment link.
+CREATE TABLE IF NOT EXISTS quotes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    customer_id UUID REFERENCES customers(id) ON DELETE SET NULL,
+    customer_email VARCHAR(255) NOT NULL,
+    customer_name VARCHAR(255),
+    company VARCHAR(255),
+    message TEXT, -- The customer's request notes
+    status VARCHAR(20) NOT NULL DEFAULT 'requested', -- requested, sent, accepted, expired, cancelled
+    revision INTEGER NOT NULL DEFAULT 1,
+    subtotal BIGINT NOT NULL, -- Amounts in cents
+    discount BIGINT NOT NULL DEFAULT 0,
+    tax BIGINT NOT NULL DEFAULT 0,
+    shipping BIGINT NOT NULL DEFAULT 0,
+    total BIGINT NOT NULL,
+    currency VARCHAR(3) NOT NULL DEFAULT 'USD',
+    accept_token_hash VARCHAR(64), -- SHA-256 of the token in the emailed links
+    expires_at TIMESTAMP WITH TIME ZONE,
+    sent_by VARCHAR(255),
+    sent_at TIMESTAMP WITH TIME ZONE,
+    accepted_at TIMESTAMP WITH TIME ZONE,
+    order_id UUID REFERENCES orders(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_quotes_status ON quotes(status);
+CREATE INDEX IF NOT EXISTS idx_quotes_customer_id ON quotes(customer_id);
+
+CREATE TABLE IF NOT EXISTS quote_items (
+    id SERIAL PRIMARY KEY,
+    quote_id UUID NOT NULL REFERENCES quotes(id) ON DELETE CASCADE,
+    product_id INTEGER REFERENCES products(id) ON DELETE SET NULL,
+    product_name TEXT NOT NULL,
+    quantity INTEGER NOT NULL,
+    list_price BIGINT NOT NULL, -- Store price when requested, in cents
+    unit_price BIGINT NOT NULL, -- Quoted price, in cents
+    line_total BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_quote_items_quote_id ON quote_items(quote_id);
+
+-- Every version of the quote's lines and totals, oldest first
+CREATE TABLE IF NOT EXISTS quote_revisions (
+    id SERIAL PRIMARY KEY,
+    quote_id UUID NOT NULL REFERENCES quotes(id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,
+    items JSONB NOT NULL,
+    subtotal BIGINT NOT NULL,
+    discount BIGINT NOT NULL,
+    tax BIGINT NOT NULL,
+    shipping BIGINT NOT NULL,
+    total BIGINT NOT NULL,
+    changed_by VARCHAR(255), -- Admin username; NULL for the customer's request
+    note TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (quote_id, revision)
+);
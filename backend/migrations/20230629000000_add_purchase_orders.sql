@@ -0,0 +1,23 @@
+-- Purchase order (net terms) checkout for wholesale customers. The order is created
+-- 'awaiting_payment' with the buyer's PO number and a due date, ships like any other order,
+-- and moves to 'completed' once the payments recorded against its invoice cover the total.
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS po_number VARCHAR(100);
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS payment_due_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS paid_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS payment_reminder_sent_at TIMESTAMP WITH TIME ZONE; -- Latest overdue reminder
+
+CREATE INDEX IF NOT EXISTS idx_orders_payment_due_at ON orders(payment_due_at) WHERE status = 'awaiting_payment';
+
+-- Payments received against purchase order invoices; several may settle one invoice
+CREATE TABLE IF NOT EXISTS invoice_payments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    order_id UUID NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    amount BIGINT NOT NULL, -- in cents
+    method VARCHAR(20) NOT NULL, -- check, ach, wire, card or other
+    reference VARCHAR(255), -- Check number, bank reference, ...
+    received_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    recorded_by VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_invoice_payments_order_id ON invoice_payments(order_id);
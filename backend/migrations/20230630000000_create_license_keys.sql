@@ -0,0 +1,30 @@
+-- License keys for software products. A product either draws keys from an uploaded pool or
+-- has them generated from a pattern. Keys are assigned when the order is confirmed and sent in
+-- the confirmation email; orders that find the pool empty get theirs when more are uploaded.
+CREATE TABLE IF NOT EXISTS license_products (
+    product_id INTEGER PRIMARY KEY REFERENCES products(id) ON DELETE CASCADE,
+    source VARCHAR(20) NOT NULL, -- pool or generated
+    key_pattern VARCHAR(100) NOT NULL DEFAULT 'XXXXX-XXXXX-XXXXX-XXXXX', -- Generated keys: X is a random character
+    keys_per_unit INTEGER NOT NULL DEFAULT 1,
+    low_pool_threshold INTEGER NOT NULL DEFAULT 10, -- Pool keys: alert ops when fewer remain
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS license_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    license_key TEXT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'available', -- available, assigned, revoked
+    order_id UUID REFERENCES orders(id) ON DELETE SET NULL,
+    assigned_at TIMESTAMP WITH TIME ZONE,
+    replaces_id UUID REFERENCES license_keys(id) ON DELETE SET NULL, -- Reissues: the revoked key this one replaces
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    revoked_by VARCHAR(255), -- Admin username; NULL when revoked by a refund
+    revoke_reason TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (product_id, license_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_license_keys_available ON license_keys(product_id, created_at) WHERE status = 'available';
+CREATE INDEX IF NOT EXISTS idx_license_keys_order_id ON license_keys(order_id);
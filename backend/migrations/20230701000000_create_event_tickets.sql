@@ -0,0 +1,44 @@
+-- Event tickets. A ticketed product has a date and a fixed capacity; checkout holds seats for
+-- the cart until the payment goes through or the hold expires, so capacity is never oversold.
+-- Each unit bought is issued a ticket with a unique code that is scanned at check-in.
+CREATE TABLE IF NOT EXISTS ticket_events (
+    product_id INTEGER PRIMARY KEY REFERENCES products(id) ON DELETE CASCADE,
+    starts_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    ends_at TIMESTAMP WITH TIME ZONE,
+    venue TEXT,
+    capacity INTEGER NOT NULL CHECK (capacity >= 0),
+    sold INTEGER NOT NULL DEFAULT 0 CHECK (sold >= 0),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CHECK (sold <= capacity)
+);
+
+CREATE TABLE IF NOT EXISTS ticket_holds (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    product_id INTEGER NOT NULL REFERENCES ticket_events(product_id) ON DELETE CASCADE,
+    cart_id UUID NOT NULL REFERENCES checkout_carts(id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    status VARCHAR(20) NOT NULL DEFAULT 'held', -- held, converted (sold to the order), released
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (cart_id, product_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_ticket_holds_held ON ticket_holds(product_id, expires_at) WHERE status = 'held';
+
+CREATE TABLE IF NOT EXISTS tickets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    code VARCHAR(64) NOT NULL UNIQUE, -- Encoded in the QR code
+    product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    order_id UUID NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    status VARCHAR(20) NOT NULL DEFAULT 'valid', -- valid, void
+    checked_in_at TIMESTAMP WITH TIME ZONE,
+    checked_in_by VARCHAR(255), -- Admin username that scanned the ticket
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_tickets_order_id ON tickets(order_id);
+CREATE INDEX IF NOT EXISTS idx_tickets_product_id ON tickets(product_id);
+
+-- Syncing a ticketed product's inventory from its remaining seats stamps the product
+ALTER TABLE products ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP;
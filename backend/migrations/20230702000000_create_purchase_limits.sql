@@ -0,0 +1,25 @@
+-- Per-customer purchase limits, e.g. for limited drops. Checkout counts what the buyer already
+-- ordered of the product (by customer account, email or card fingerprint) within the window.
+-- Admins can raise or lower the limit for a single customer email.
+CREATE TABLE IF NOT EXISTS purchase_limits (
+    product_id INTEGER PRIMARY KEY REFERENCES products(id) ON DELETE CASCADE,
+    max_per_customer INTEGER NOT NULL CHECK (max_per_customer >= 0),
+    window_days INTEGER CHECK (window_days > 0), -- NULL counts every earlier order
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS purchase_limit_overrides (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    product_id INTEGER NOT NULL REFERENCES purchase_limits(product_id) ON DELETE CASCADE,
+    customer_email VARCHAR(255) NOT NULL, -- Lowercased
+    max_per_customer INTEGER NOT NULL CHECK (max_per_customer >= 0),
+    note TEXT,
+    created_by VARCHAR(255) NOT NULL, -- Admin username
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (product_id, customer_email)
+);
+
+-- Card fingerprint of the order's Stripe payment, recorded for orders of limited products
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS payment_fingerprint VARCHAR(255);
+CREATE INDEX IF NOT EXISTS idx_orders_payment_fingerprint ON orders(payment_fingerprint) WHERE payment_fingerprint IS NOT NULL;
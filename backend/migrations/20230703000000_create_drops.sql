@@ -0,0 +1,32 @@
+-- Virtual waiting room for high-demand product drops. Shoppers join the drop's queue and are
+-- let through to checkout in order at admit_per_minute (a token bucket holding up to burst
+-- admissions). Checkout for the drop's products needs an admitted queue token while it runs.
+CREATE TABLE IF NOT EXISTS drops (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(255) NOT NULL,
+    product_ids INTEGER[] NOT NULL,
+    starts_at TIMESTAMP WITH TIME ZONE NOT NULL, -- Admission starts; the queue can be joined earlier
+    ends_at TIMESTAMP WITH TIME ZONE, -- Checkout is open to everyone again after this
+    admit_per_minute INTEGER NOT NULL CHECK (admit_per_minute > 0),
+    burst INTEGER NOT NULL CHECK (burst > 0), -- Most admissions let through at once
+    admission_minutes INTEGER NOT NULL DEFAULT 10 CHECK (admission_minutes > 0), -- Time to check out once admitted
+    tokens DOUBLE PRECISION NOT NULL DEFAULT 0, -- Token bucket level
+    tokens_updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_position BIGINT NOT NULL DEFAULT 0, -- Position handed to the latest shopper to join
+    created_by VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS drop_queue_entries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    drop_id UUID NOT NULL REFERENCES drops(id) ON DELETE CASCADE,
+    position BIGINT NOT NULL,
+    admitted_at TIMESTAMP WITH TIME ZONE,
+    admission_expires_at TIMESTAMP WITH TIME ZONE,
+    last_seen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(), -- Shoppers who stop polling are skipped
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (drop_id, position)
+);
+
+CREATE INDEX IF NOT EXISTS idx_drop_queue_waiting ON drop_queue_entries(drop_id, position) WHERE admitted_at IS NULL;
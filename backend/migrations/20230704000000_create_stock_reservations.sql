@@ -0,0 +1,23 @@
+-- Stock reserved by checkout. create-payment-intent takes the cart's items out of product
+-- inventory for CART_HOLD_MINUTES; the reservation becomes the sale when the order is created,
+-- or goes back into inventory when the checkout is abandoned and the customer is told.
+CREATE TABLE IF NOT EXISTS stock_reservations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    cart_id UUID NOT NULL REFERENCES checkout_carts(id) ON DELETE CASCADE,
+    product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    status VARCHAR(20) NOT NULL DEFAULT 'held', -- held, converted (sold to the order), released
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    released_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (cart_id, product_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_stock_reservations_held ON stock_reservations(expires_at) WHERE status = 'held';
+
+-- Who to tell when the cart's hold expires (guests give an email at checkout)
+ALTER TABLE checkout_carts ADD COLUMN IF NOT EXISTS contact_email VARCHAR(255);
+ALTER TABLE checkout_carts ADD COLUMN IF NOT EXISTS hold_expired_notified_at TIMESTAMP WITH TIME ZONE;
+
+-- Reserving and releasing stock stamps the product
+ALTER TABLE products ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP;
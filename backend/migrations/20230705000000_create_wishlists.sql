@@ -0,0 +1,25 @@
+-- Customer wishlists. A price_watches row on a wishlist item asks for an email when the product's
+-- price drops below watched_price (the price when it was added, or when they were last told)
+CREATE TABLE IF NOT EXISTS wishlist_items (
+    id SERIAL PRIMARY KEY,
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (customer_id, product_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_wishlist_items_product_id ON wishlist_items(product_id);
+
+CREATE TABLE IF NOT EXISTS price_watches (
+    wishlist_item_id INTEGER PRIMARY KEY REFERENCES wishlist_items(id) ON DELETE CASCADE,
+    watched_price BIGINT NOT NULL, -- in cents
+    notified_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Customer preference for price drop emails
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS price_drop_emails BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Sale rules are announced as price drops once, when they start
+ALTER TABLE price_rules ADD COLUMN IF NOT EXISTS announced_at TIMESTAMP WITH TIME ZONE;
+UPDATE price_rules SET announced_at = NOW() WHERE announced_at IS NULL AND (starts_at IS NULL OR starts_at <= NOW());
@@ -0,0 +1,44 @@
+-- Catalog A/B tests. Visitors are bucketed into a variant by a hash of the experiment key and
+-- their visitor id, so the same visitor always sees the same variant without storing it.
+-- variants is a JSON array of { key, weight, config }; the first variant is the control
+CREATE TABLE IF NOT EXISTS experiments (
+    id SERIAL PRIMARY KEY,
+    key VARCHAR(80) NOT NULL UNIQUE,
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    kind VARCHAR(40) NOT NULL, -- product_title, price_badge, free_shipping_message, custom
+    product_ids INTEGER[] NOT NULL DEFAULT '{}', -- Products the experiment applies to; empty for all
+    variants JSONB NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'draft', -- draft, running, stopped
+    started_at TIMESTAMP WITH TIME ZONE,
+    stopped_at TIMESTAMP WITH TIME ZONE,
+    created_by VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Storefront analytics events: experiment exposures and the purchases they are measured by.
+-- visitor_id is 'c:<customer id>' for logged-in customers and 'a:<cookie id>' otherwise
+CREATE TABLE IF NOT EXISTS analytics_events (
+    id BIGSERIAL PRIMARY KEY,
+    event_type VARCHAR(40) NOT NULL, -- experiment_exposure, purchase
+    visitor_id VARCHAR(80) NOT NULL,
+    customer_id UUID REFERENCES customers(id) ON DELETE SET NULL,
+    experiment_id INTEGER REFERENCES experiments(id) ON DELETE CASCADE,
+    variant VARCHAR(80),
+    order_id UUID,
+    amount BIGINT, -- in cents, for purchases
+    properties JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_analytics_events_visitor ON analytics_events(visitor_id, event_type);
+CREATE INDEX IF NOT EXISTS idx_analytics_events_experiment ON analytics_events(experiment_id, variant);
+-- A visitor's first exposure is the one that counts
+CREATE UNIQUE INDEX IF NOT EXISTS idx_analytics_events_exposure_once
+    ON analytics_events(experiment_id, visitor_id) WHERE event_type = 'experiment_exposure';
+CREATE UNIQUE INDEX IF NOT EXISTS idx_analytics_events_purchase_once
+    ON analytics_events(order_id) WHERE event_type = 'purchase';
+
+-- Who was shopping, so purchases can be credited to the variants they saw
+ALTER TABLE checkout_carts ADD COLUMN IF NOT EXISTS visitor_id VARCHAR(80);
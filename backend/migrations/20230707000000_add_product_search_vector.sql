@@ -0,0 +1,11 @@
+-- Full-text search over published products, used by /api/products/search when no external
+-- search engine (Meilisearch) is configured. Name and brand weigh more than the description.
+ALTER TABLE products ADD COLUMN IF NOT EXISTS search_vector TSVECTOR
+    GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', COALESCE(name, '')), 'A') ||
+        setweight(to_tsvector('english', COALESCE(brand, '')), 'A') ||
+        setweight(to_tsvector('english', COALESCE(category, '')), 'B') ||
+        setweight(to_tsvector('english', COALESCE(description, '')), 'C')
+    ) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_products_search_vector ON products USING GIN (search_vector);
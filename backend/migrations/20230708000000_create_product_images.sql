@@ -0,0 +1,34 @@
+-- Uploaded product images. The original is kept on disk under MEDIA_DIR and a background worker
+-- renders it at several widths in the original's format (JPEG, or PNG with transparency),
+-- WebP and AVIF, so the storefront can serve responsive srcset URLs.
+CREATE TABLE IF NOT EXISTS product_images (
+    id SERIAL PRIMARY KEY,
+    product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL DEFAULT 0, -- Lowest first; the first ready image is the product's main image
+    alt_text VARCHAR(255),
+    original_path TEXT NOT NULL, -- Relative to MEDIA_DIR
+    content_type VARCHAR(50) NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    bytes BIGINT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'processing', -- 'processing', 'ready' or 'failed'
+    error TEXT,
+    locked_at TIMESTAMP WITH TIME ZONE, -- Set while a worker renders the variants
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    processed_at TIMESTAMP WITH TIME ZONE,
+    CONSTRAINT product_images_status_check CHECK (status IN ('processing', 'ready', 'failed'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_product_images_product ON product_images(product_id, position, id);
+CREATE INDEX IF NOT EXISTS idx_product_images_processing ON product_images(created_at) WHERE status = 'processing';
+
+CREATE TABLE IF NOT EXISTS product_image_variants (
+    id SERIAL PRIMARY KEY,
+    image_id INTEGER NOT NULL REFERENCES product_images(id) ON DELETE CASCADE,
+    format VARCHAR(10) NOT NULL, -- 'jpeg', 'png', 'webp' or 'avif'
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    bytes BIGINT NOT NULL,
+    path TEXT NOT NULL, -- Relative to MEDIA_DIR
+    UNIQUE (image_id, format, width)
+);
@@ -0,0 +1,6 @@
+-- Inventory can't go below zero. Checkouts lock the product row and decrement only when enough
+-- is left, so an oversell is answered with 409; the constraint catches any write path that
+-- skips that. NOT VALID leaves existing rows unchecked so the migration can't fail on old data;
+-- run VALIDATE CONSTRAINT once any negative counts have been corrected.
+ALTER TABLE products DROP CONSTRAINT IF EXISTS products_inventory_non_negative;
+ALTER TABLE products ADD CONSTRAINT products_inventory_non_negative CHECK (inventory >= 0) NOT VALID;
@@ -0,0 +1,5 @@
+-- Event ids are unique per provider, not across providers. Logging an event inserts it with
+-- ON CONFLICT (provider, event_id) DO NOTHING, so of two concurrent deliveries of the same
+-- event exactly one gets a row to process and the other is answered as a duplicate.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_webhook_events_provider_event_id ON webhook_events(provider, event_id);
+ALTER TABLE webhook_events DROP CONSTRAINT IF EXISTS webhook_events_event_id_key;
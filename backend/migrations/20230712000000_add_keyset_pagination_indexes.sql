@@ -0,0 +1,7 @@
+-- Order and webhook event lists page newest first by (created_at, id); these indexes let a
+-- page seek straight to its cursor. They cover the created_at-only indexes they replace
+CREATE INDEX IF NOT EXISTS idx_orders_created_at_id ON orders (created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_webhook_events_created_at_id ON webhook_events (created_at DESC, id DESC);
+
+DROP INDEX IF EXISTS idx_orders_created_at;
+DROP INDEX IF EXISTS idx_webhook_events_created_at;
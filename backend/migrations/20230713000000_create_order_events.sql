@@ -0,0 +1,16 @@
+-- What happened to each order (created, refunded, fulfilled, shipped...), recorded from the
+-- domain events as they're published, for the admin activity feed
+CREATE TABLE IF NOT EXISTS order_events (
+    id BIGSERIAL PRIMARY KEY,
+    order_id UUID NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    event VARCHAR(50) NOT NULL, -- Domain event name, e.g. 'order_refunded'
+    summary TEXT NOT NULL,
+    details JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_order_events_created_at ON order_events(created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_order_events_order_id ON order_events(order_id);
+
+-- The activity feed lists job outcomes by when they happened
+CREATE INDEX IF NOT EXISTS idx_jobs_updated_at ON jobs(updated_at);
@@ -0,0 +1,15 @@
+-- Days whose summary digest has gone out; the scheduler claims a day by inserting its row,
+-- so with several servers running only one sends it
+CREATE TABLE IF NOT EXISTS daily_digests (
+    digest_date DATE PRIMARY KEY, -- The day summarized, in STORE_TIMEZONE
+    summary JSONB,
+    sent_to TEXT[] NOT NULL DEFAULT '{}',
+    error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Digest recipients who asked to stop getting it
+CREATE TABLE IF NOT EXISTS daily_digest_opt_outs (
+    email VARCHAR(255) PRIMARY KEY, -- Lowercase
+    opted_out_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
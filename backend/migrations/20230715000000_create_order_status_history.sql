@@ -0,0 +1,21 @@
+-- Every status an order has been in, written with each change (see order_status)
+CREATE TABLE IF NOT EXISTS order_status_history (
+    id BIGSERIAL PRIMARY KEY,
+    order_id UUID NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    from_status VARCHAR(50), -- NULL for the status the order was created with
+    to_status VARCHAR(50) NOT NULL,
+    changed_by VARCHAR(255), -- Admin username, or the webhook or background task that made the change
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_order_status_history_order_id ON order_status_history(order_id, id);
+
+-- Existing orders start their history with their current status
+INSERT INTO order_status_history (order_id, from_status, to_status, created_at)
+SELECT id, NULL, status, COALESCE(updated_at, created_at, CURRENT_TIMESTAMP) FROM orders
+WHERE NOT EXISTS (SELECT 1 FROM order_status_history h WHERE h.order_id = orders.id);
+
+-- Only known statuses from now on; NOT VALID leaves any old rows with other values to be fixed by hand
+ALTER TABLE orders DROP CONSTRAINT IF EXISTS orders_status_check;
+ALTER TABLE orders ADD CONSTRAINT orders_status_check
+    CHECK (status IN ('pending', 'authorized', 'awaiting_payment', 'completed', 'failed', 'refunded', 'voided')) NOT VALID;
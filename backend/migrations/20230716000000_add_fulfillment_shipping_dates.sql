@@ -0,0 +1,9 @@
+-- When each fulfillment (one shipment of a split order) left the warehouse and arrived
+ALTER TABLE order_fulfillments ADD COLUMN IF NOT EXISTS shipped_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE order_fulfillments ADD COLUMN IF NOT EXISTS delivered_at TIMESTAMP WITH TIME ZONE;
+
+-- Existing shipments get their last update as an approximation
+UPDATE order_fulfillments SET shipped_at = COALESCE(updated_at, created_at)
+WHERE status IN ('shipped', 'delivered') AND shipped_at IS NULL;
+UPDATE order_fulfillments SET delivered_at = COALESCE(updated_at, created_at)
+WHERE status = 'delivered' AND delivered_at IS NULL;
@@ -0,0 +1,33 @@
+-- Dropship vendors: suppliers that ship some products straight to the customer
+CREATE TABLE IF NOT EXISTS vendors (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    email VARCHAR(255), -- Purchase orders are emailed here
+    webhook_url TEXT, -- and/or POSTed here, signed with webhook_secret
+    webhook_secret VARCHAR(64) NOT NULL,
+    token_prefix VARCHAR(20) NOT NULL, -- Start of the vendor portal token, to tell tokens apart
+    token_hash VARCHAR(64) NOT NULL UNIQUE, -- SHA-256 of the vendor portal token, which is only shown once
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Products shipped by a vendor instead of from our warehouses
+ALTER TABLE products ADD COLUMN IF NOT EXISTS dropship_vendor_id INTEGER REFERENCES vendors(id) ON DELETE SET NULL;
+
+-- Purchase orders sent to vendors, one per vendor (and ship-to address) of a paid order. The items
+-- are the fulfillment the vendor ships, so its tracking shows up like any other shipment
+CREATE TABLE IF NOT EXISTS vendor_orders (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    vendor_id INTEGER NOT NULL REFERENCES vendors(id),
+    order_id UUID NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    fulfillment_id UUID NOT NULL UNIQUE REFERENCES order_fulfillments(id) ON DELETE CASCADE,
+    status VARCHAR(50) NOT NULL DEFAULT 'pending', -- 'pending' (not sent yet), 'sent', 'acknowledged', 'shipped'
+    last_error TEXT, -- Why the last send failed; it's retried through the job queue
+    sent_at TIMESTAMP WITH TIME ZONE,
+    acknowledged_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_vendor_orders_vendor_id ON vendor_orders(vendor_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_vendor_orders_order_id ON vendor_orders(order_id);
+CREATE INDEX IF NOT EXISTS idx_products_dropship_vendor_id ON products(dropship_vendor_id) WHERE dropship_vendor_id IS NOT NULL;
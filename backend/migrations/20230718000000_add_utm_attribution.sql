@@ -0,0 +1,11 @@
+-- Marketing attribution: the UTM parameters the shopper last arrived with, captured by the
+-- storefront and sent with checkout. Kept on the checkout cart and copied to its order
+ALTER TABLE checkout_carts ADD COLUMN IF NOT EXISTS utm_source VARCHAR(255);
+ALTER TABLE checkout_carts ADD COLUMN IF NOT EXISTS utm_medium VARCHAR(255);
+ALTER TABLE checkout_carts ADD COLUMN IF NOT EXISTS utm_campaign VARCHAR(255);
+
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS utm_source VARCHAR(255);
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS utm_medium VARCHAR(255);
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS utm_campaign VARCHAR(255);
+
+CREATE INDEX IF NOT EXISTS idx_orders_utm_campaign ON orders(utm_campaign, created_at) WHERE utm_campaign IS NOT NULL;
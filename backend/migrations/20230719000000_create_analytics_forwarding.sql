@@ -0,0 +1,15 @@
+-- Server-side conversion tracking: purchases and refunds forwarded from the backend to GA4's
+-- Measurement Protocol or a generic pixel endpoint, so ad blockers don't hide them. One row
+CREATE TABLE IF NOT EXISTS analytics_forwarding (
+    id INTEGER PRIMARY KEY DEFAULT 1 CHECK (id = 1),
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    provider VARCHAR(20) NOT NULL DEFAULT 'ga4', -- 'ga4' or 'pixel'
+    ga4_measurement_id VARCHAR(50), -- G-XXXXXXX
+    ga4_api_secret VARCHAR(255), -- Measurement Protocol API secret, never returned by the API
+    pixel_url TEXT, -- Receives a JSON POST per event
+    forward_test_orders BOOLEAN NOT NULL DEFAULT FALSE, -- Also send orders paid with test keys
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- GA client id (from the _ga cookie) of the checkout, so server-side hits join the browser session
+ALTER TABLE checkout_carts ADD COLUMN IF NOT EXISTS ga_client_id VARCHAR(100);
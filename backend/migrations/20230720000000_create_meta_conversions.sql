@@ -0,0 +1,19 @@
+-- Meta (Facebook) Conversions API: Purchase and AddToCart events sent from the backend, with
+-- hashed customer identifiers. One row
+CREATE TABLE IF NOT EXISTS meta_conversions (
+    id INTEGER PRIMARY KEY DEFAULT 1 CHECK (id = 1),
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    pixel_id VARCHAR(50),
+    access_token TEXT, -- Conversions API token, never returned by the API
+    test_event_code VARCHAR(50), -- Routes events to Events Manager's Test Events tab
+    require_consent BOOLEAN NOT NULL DEFAULT TRUE, -- Only send events for shoppers who accepted ad tracking
+    forward_test_orders BOOLEAN NOT NULL DEFAULT FALSE, -- Also send orders paid with test keys
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- The checkout's ad tracking consent and browser identifiers, for its Purchase event
+ALTER TABLE checkout_carts ADD COLUMN IF NOT EXISTS tracking_consent BOOLEAN;
+ALTER TABLE checkout_carts ADD COLUMN IF NOT EXISTS fbp VARCHAR(255); -- _fbp cookie
+ALTER TABLE checkout_carts ADD COLUMN IF NOT EXISTS fbc VARCHAR(255); -- _fbc cookie (ad click id)
+ALTER TABLE checkout_carts ADD COLUMN IF NOT EXISTS client_ip VARCHAR(64);
+ALTER TABLE checkout_carts ADD COLUMN IF NOT EXISTS client_user_agent TEXT;
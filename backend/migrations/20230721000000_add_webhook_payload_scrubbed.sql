@@ -0,0 +1,2 @@
+-- Webhook payloads are stored with customer PII redacted; older rows are scrubbed at startup
+ALTER TABLE webhook_events ADD COLUMN payload_scrubbed BOOLEAN NOT NULL DEFAULT FALSE;
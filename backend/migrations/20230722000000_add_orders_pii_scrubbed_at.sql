@@ -0,0 +1,2 @@
+-- When the customer's personal data was removed from an order (admin danger zone tools)
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS pii_scrubbed_at TIMESTAMPTZ;
@@ -0,0 +1,17 @@
+-- Order items keep the terms they were sold under, so invoices and dispute evidence don't change
+-- when products, tax settings or coupons do: the SKU and catalog price, the tax rate and coupon in
+-- effect, and each line's share of the discount and tax. Checkout carts carry the tax rate and
+-- coupon from pricing to the order. NULL on items sold before snapshots
+ALTER TABLE checkout_carts
+    ADD COLUMN IF NOT EXISTS tax_rate DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS coupon_code VARCHAR(50),
+    ADD COLUMN IF NOT EXISTS coupon_amount_off BIGINT;
+
+ALTER TABLE order_items
+    ADD COLUMN IF NOT EXISTS sku VARCHAR(64),
+    ADD COLUMN IF NOT EXISTS list_price BIGINT,
+    ADD COLUMN IF NOT EXISTS discount_amount BIGINT,
+    ADD COLUMN IF NOT EXISTS tax_rate DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS tax_amount BIGINT,
+    ADD COLUMN IF NOT EXISTS coupon_code VARCHAR(50),
+    ADD COLUMN IF NOT EXISTS coupon_amount_off BIGINT;
@@ -0,0 +1,39 @@
+-- Recurring maintenance tasks run by the scheduler (see scheduler.rs). Rows are added on startup
+-- for every task the server knows; admins change the schedule, disable tasks or ask for a run
+CREATE TABLE IF NOT EXISTS scheduled_tasks (
+    name VARCHAR(100) PRIMARY KEY,
+    cron VARCHAR(100) NOT NULL, -- Five-field cron expression, in STORE_TIMEZONE
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    next_run_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    run_requested_at TIMESTAMP WITH TIME ZONE, -- Set by an admin to run the task on the next tick
+    run_requested_by VARCHAR(255),
+    running_since TIMESTAMP WITH TIME ZONE, -- Set while a run is in progress
+    last_run_at TIMESTAMP WITH TIME ZONE,
+    last_status VARCHAR(20), -- 'succeeded', 'failed'
+    last_error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- One row per run, for the run history
+CREATE TABLE IF NOT EXISTS scheduled_task_runs (
+    id BIGSERIAL PRIMARY KEY,
+    task_name VARCHAR(100) NOT NULL REFERENCES scheduled_tasks(name) ON DELETE CASCADE,
+    triggered_by VARCHAR(255) NOT NULL, -- 'schedule' or the admin who asked for the run
+    instance_id VARCHAR(255) NOT NULL, -- The server that ran it
+    status VARCHAR(20) NOT NULL DEFAULT 'running', -- 'running', 'succeeded', 'failed'
+    summary TEXT,
+    error TEXT,
+    started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_scheduled_task_runs_task ON scheduled_task_runs(task_name, started_at DESC);
+
+-- Leader election: with several replicas, only the holder of an unexpired lease runs tasks
+CREATE TABLE IF NOT EXISTS scheduler_leases (
+    name VARCHAR(100) PRIMARY KEY,
+    holder VARCHAR(255) NOT NULL,
+    acquired_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
@@ -0,0 +1,48 @@
+-- The rest of the customer emails and phones are stored encrypted by the application too (see
+-- 20230615000000_encrypt_customer_pii.sql). Columns that are matched on get a keyed hash next to
+-- them; existing rows are encrypted and hashed by the application at startup (pii::backfill)
+ALTER TABLE orders ALTER COLUMN customer_email TYPE TEXT;
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS customer_email_hash VARCHAR(64); -- HMAC-SHA256 of the lowercased email
+DROP INDEX IF EXISTS idx_orders_customer_email;
+CREATE INDEX IF NOT EXISTS idx_orders_customer_email_hash ON orders(customer_email_hash);
+
+ALTER TABLE reviews ALTER COLUMN customer_email TYPE TEXT;
+ALTER TABLE quotes ALTER COLUMN customer_email TYPE TEXT;
+ALTER TABLE checkout_carts ALTER COLUMN contact_email TYPE TEXT;
+
+-- Compared on refresh to tell whether the provider list needs the new address
+ALTER TABLE customer_segment_members ALTER COLUMN email TYPE TEXT;
+ALTER TABLE customer_segment_members ADD COLUMN IF NOT EXISTS email_hash VARCHAR(64);
+
+-- One override per product and email hash
+ALTER TABLE purchase_limit_overrides ALTER COLUMN customer_email TYPE TEXT;
+ALTER TABLE purchase_limit_overrides ADD COLUMN IF NOT EXISTS customer_email_hash VARCHAR(64);
+ALTER TABLE purchase_limit_overrides DROP CONSTRAINT IF EXISTS purchase_limit_overrides_product_id_customer_email_key;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_purchase_limit_overrides_product_email_hash
+    ON purchase_limit_overrides(product_id, customer_email_hash);
+
+ALTER TABLE daily_digest_opt_outs ALTER COLUMN email TYPE TEXT;
+ALTER TABLE daily_digest_opt_outs ADD COLUMN IF NOT EXISTS email_hash VARCHAR(64);
+ALTER TABLE daily_digest_opt_outs DROP CONSTRAINT IF EXISTS daily_digest_opt_outs_pkey;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_daily_digest_opt_outs_email_hash ON daily_digest_opt_outs(email_hash);
+
+-- Phones are hashed as stored, in E.164
+ALTER TABLE phone_verifications ALTER COLUMN phone TYPE TEXT;
+ALTER TABLE phone_verifications ADD COLUMN IF NOT EXISTS phone_hash VARCHAR(64);
+DROP INDEX IF EXISTS idx_phone_verifications_phone;
+CREATE INDEX IF NOT EXISTS idx_phone_verifications_phone_hash ON phone_verifications(phone_hash, created_at);
+
+ALTER TABLE verified_phones ALTER COLUMN phone TYPE TEXT;
+ALTER TABLE verified_phones ADD COLUMN IF NOT EXISTS phone_hash VARCHAR(64);
+ALTER TABLE verified_phones DROP CONSTRAINT IF EXISTS verified_phones_pkey;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_verified_phones_phone_hash ON verified_phones(phone_hash);
+
+ALTER TABLE sms_opt_outs ALTER COLUMN phone TYPE TEXT;
+ALTER TABLE sms_opt_outs ADD COLUMN IF NOT EXISTS phone_hash VARCHAR(64);
+ALTER TABLE sms_opt_outs DROP CONSTRAINT IF EXISTS sms_opt_outs_pkey;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sms_opt_outs_phone_hash ON sms_opt_outs(phone_hash);
+
+ALTER TABLE sms_inbound_messages ALTER COLUMN from_phone TYPE TEXT;
+ALTER TABLE sms_inbound_messages ADD COLUMN IF NOT EXISTS from_phone_hash VARCHAR(64);
+DROP INDEX IF EXISTS idx_sms_inbound_messages_from_phone;
+CREATE INDEX IF NOT EXISTS idx_sms_inbound_messages_from_phone_hash ON sms_inbound_messages(from_phone_hash);
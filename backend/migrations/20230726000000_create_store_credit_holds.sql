@@ -0,0 +1,14 @@
+-- Store credit held by checkout. create-payment-intent takes the discount off the customer's
+-- available credit until the payment goes through; the hold becomes the ledger debit when the
+-- order is created, or is released with the checkout's stock when it is abandoned or cancelled.
+CREATE TABLE IF NOT EXISTS store_credit_holds (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    cart_id UUID NOT NULL UNIQUE REFERENCES checkout_carts(id) ON DELETE CASCADE,
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    amount BIGINT NOT NULL CHECK (amount > 0), -- in cents
+    status VARCHAR(20) NOT NULL DEFAULT 'held', -- held, converted (debited for the order), released
+    released_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_store_credit_holds_customer_held ON store_credit_holds(customer_id) WHERE status = 'held';
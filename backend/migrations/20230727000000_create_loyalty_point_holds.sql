@@ -0,0 +1,15 @@
+-- Loyalty points held by checkout, like store_credit_holds: create-payment-intent takes the
+-- redeemed points off the customer's available balance until the payment goes through; the hold
+-- becomes the 'redeemed' ledger entry when the order is created, or is released with the
+-- checkout's stock when it is abandoned or cancelled.
+CREATE TABLE IF NOT EXISTS loyalty_point_holds (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    cart_id UUID NOT NULL UNIQUE REFERENCES checkout_carts(id) ON DELETE CASCADE,
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    points BIGINT NOT NULL CHECK (points > 0),
+    status VARCHAR(20) NOT NULL DEFAULT 'held', -- held, converted (redeemed on the order), released
+    released_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_loyalty_point_holds_customer_held ON loyalty_point_holds(customer_id) WHERE status = 'held';
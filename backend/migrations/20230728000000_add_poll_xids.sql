@@ -0,0 +1,11 @@
+-- The integration polling feeds page on the id of the transaction that inserted each order and
+-- customer. created_at is when that transaction started, and a retried unit of work can commit
+-- well after a later row, so a feed paging on created_at could move past a row before it was
+-- visible and never return it. The feeds only return rows whose transaction is older than every
+-- one still running, so nothing can commit behind the cursor. Rows from before this migration
+-- share its transaction id and keep their created_at order
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS poll_xid XID8 NOT NULL DEFAULT pg_current_xact_id();
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS poll_xid XID8 NOT NULL DEFAULT pg_current_xact_id();
+
+CREATE INDEX IF NOT EXISTS idx_orders_poll_xid ON orders(poll_xid, created_at, id);
+CREATE INDEX IF NOT EXISTS idx_customers_poll_xid ON customers(poll_xid, created_at, id);
@@ -0,0 +1,7 @@
+-- Coupons held by checkout, like store_credit_holds: create-payment-intent claims the coupon for
+-- its cart, so a single-use code can only be on one open checkout at a time. The coupon is
+-- redeemed for the cart's order when it is created, or let go with the checkout's stock when it
+-- is abandoned or cancelled.
+ALTER TABLE coupons ADD COLUMN IF NOT EXISTS held_cart_id UUID REFERENCES checkout_carts(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_coupons_held_cart_id ON coupons(held_cart_id);
@@ -0,0 +1,6 @@
+-- Purchase limits count a buyer's open checkouts as well as their orders, so the cart records
+-- the buyer email it was checked against (HMAC-SHA256 of the lowercased email, like
+-- orders.customer_email_hash)
+ALTER TABLE checkout_carts ADD COLUMN IF NOT EXISTS buyer_email_hash VARCHAR(64);
+
+CREATE INDEX IF NOT EXISTS idx_checkout_carts_buyer_email_hash ON checkout_carts(buyer_email_hash) WHERE converted_at IS NULL;
@@ -0,0 +1,220 @@
+// Activity Module - One timeline of what happened in the store, for the dashboard's activity widget
+// GET /api/admin/activity merges, newest first:
+// - audit: admin bulk changes from the audit log
+// - webhook: payment provider events received, and whether they were processed
+// - job: background job outcomes (completed, failed for good, or failed and waiting to retry)
+// - order: order lifecycle events, recorded by OrderActivitySubscriber as they're published
+// Items come straight from each source's table, so the feed needs no copying or cleanup beyond
+// order_events. Pages by (occurred_at, source:id), which is unique across the sources
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::pagination::{self, Page};
+use crate::AppState;
+
+const SOURCES: [&str; 4] = ["audit", "webhook", "job", "order"];
+const STATUSES: [&str; 5] = ["ok", "failed", "pending", "retrying", "undone"];
+
+// One entry of the timeline
+#[derive(Debug, Serialize)]
+pub struct ActivityItem {
+    pub source: String, // "audit", "webhook", "job" or "order"
+    pub id: String,     // Audit entry, webhook event, job or order event id
+    pub kind: String,   // Audit action, webhook event type, job type or order event
+    pub summary: String,
+    pub status: String, // "ok", "failed", "pending" (webhook not processed yet), "retrying" (job) or "undone" (audit)
+    pub actor: Option<String>, // Admin, for audit entries
+    pub order_id: Option<Uuid>,
+    pub occurred_at: DateTime<Utc>,
+    pub details: serde_json::Value,
+}
+
+impl ActivityItem {
+    fn cursor_key(&self) -> (DateTime<Utc>, String) {
+        (self.occurred_at, format!("{}:{}", self.source, self.id))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ActivityQuery {
+    pub source: Option<String>, // Comma separated sources; all when unset
+    pub status: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub cursor: Option<String>, // next_cursor of the previous page
+    pub limit: Option<i64>,     // Defaults to 50, at most 200
+}
+
+pub fn activity_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/activity", get(list_activity))
+        .with_state(app_state)
+}
+
+async fn list_activity(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ActivityQuery>,
+) -> Result<Json<Page<ActivityItem>>, (StatusCode, String)> {
+    let sources: Option<Vec<String>> = query.source.as_deref().map(|list| {
+        list.split(',')
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect()
+    });
+    if let Some(unknown) = sources.iter().flatten().find(|s| !SOURCES.contains(&s.as_str())) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown source \"{}\"; expected {}", unknown, SOURCES.join(", ")),
+        ));
+    }
+    if let Some(status) = query.status.as_deref().filter(|s| !STATUSES.contains(s)) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown status \"{}\"; expected {}", status, STATUSES.join(", ")),
+        ));
+    }
+    let size = pagination::page_size(query.limit);
+    let before: Option<(DateTime<Utc>, String)> = pagination::after(query.cursor.as_deref())?;
+
+    // Each source is skipped entirely when it isn't asked for
+    let items = sqlx::query_as!(
+        ActivityItem,
+        r#"
+        SELECT source AS "source!", id AS "id!", kind AS "kind!", summary AS "summary!", status AS "status!",
+               actor, order_id, occurred_at AS "occurred_at!", details AS "details!"
+        FROM (
+            SELECT 'audit' AS source, id::TEXT AS id, action AS kind, summary,
+                   CASE WHEN undone_at IS NULL THEN 'ok' ELSE 'undone' END AS status,
+                   admin_username AS actor, NULL::UUID AS order_id, created_at AS occurred_at, details
+            FROM admin_audit_log
+            WHERE ($1::TEXT[] IS NULL OR 'audit' = ANY($1)) AND created_at IS NOT NULL
+            UNION ALL
+            SELECT 'webhook', id::TEXT, event_type,
+                   provider || ' ' || event_type || COALESCE(': ' || error_message, ''),
+                   CASE WHEN error_message IS NOT NULL THEN 'failed' WHEN processed THEN 'ok' ELSE 'pending' END,
+                   NULL, NULL, created_at,
+                   jsonb_build_object('provider', provider, 'event_id', event_id, 'processed_at', processed_at)
+            FROM webhook_events
+            WHERE ($1::TEXT[] IS NULL OR 'webhook' = ANY($1)) AND created_at IS NOT NULL
+            UNION ALL
+            SELECT 'job', id::TEXT, job_type,
+                   CASE status
+                       WHEN 'completed' THEN job_type || ' completed'
+                       WHEN 'dead' THEN job_type || ' failed after ' || attempts || ' attempts: ' || COALESCE(last_error, '')
+                       ELSE job_type || ' failed (attempt ' || attempts || ' of ' || max_attempts || '), retrying: ' || COALESCE(last_error, '')
+                   END,
+                   CASE status WHEN 'completed' THEN 'ok' WHEN 'dead' THEN 'failed' ELSE 'retrying' END,
+                   NULL, NULL, updated_at,
+                   jsonb_build_object('attempts', attempts, 'max_attempts', max_attempts, 'run_at', run_at)
+            FROM jobs
+            WHERE ($1::TEXT[] IS NULL OR 'job' = ANY($1)) AND updated_at IS NOT NULL
+              AND (status IN ('completed', 'dead') OR (status = 'pending' AND last_error IS NOT NULL))
+            UNION ALL
+            SELECT 'order', id::TEXT, event, summary, 'ok', NULL, order_id, created_at, details
+            FROM order_events
+            WHERE ($1::TEXT[] IS NULL OR 'order' = ANY($1))
+        ) activity
+        WHERE ($2::TEXT IS NULL OR status = $2)
+          AND ($3::TIMESTAMPTZ IS NULL OR occurred_at >= $3)
+          AND ($4::TIMESTAMPTZ IS NULL OR occurred_at < $4)
+          AND ($5::TIMESTAMPTZ IS NULL OR (occurred_at, source || ':' || id) < ($5, $6))
+        ORDER BY occurred_at DESC, source || ':' || id DESC
+        LIMIT $7
+        "#,
+        sources.as_deref(),
+        query.status.as_deref(),
+        query.since,
+        query.until,
+        before.as_ref().map(|(at, _)| *at),
+        before.map(|(_, key)| key),
+        size + 1,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(Page::new(items, size, ActivityItem::cursor_key)))
+}
+
+fn format_money(cents: i64, currency: &str) -> String {
+    format!("{:.2} {}", cents as f64 / 100.0, currency.to_uppercase())
+}
+
+// Records order lifecycle events for the activity feed. Customer contact details stay out
+pub struct OrderActivitySubscriber {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl OrderActivitySubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for OrderActivitySubscriber {
+    fn name(&self) -> &'static str {
+        "order_activity"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let (order_id, summary, details) = match event {
+            DomainEvent::OrderCreated { order_id, provider, total_amount, currency, .. } => (
+                *order_id,
+                format!("Order placed: {} via {}", format_money(*total_amount, currency), provider),
+                json!({ "provider": provider.to_string(), "total_amount": total_amount, "currency": currency }),
+            ),
+            DomainEvent::OrderRefunded { order_id, amount_refunded, currency, .. } => (
+                *order_id,
+                format!("Refunded {}", format_money(*amount_refunded, currency)),
+                json!({ "amount_refunded": amount_refunded, "currency": currency }),
+            ),
+            DomainEvent::OrderFulfilled { order_id } => (*order_id, "Fulfilled".to_string(), json!({})),
+            DomainEvent::OrderVoided { order_id } => (*order_id, "Authorization voided".to_string(), json!({})),
+            DomainEvent::OrderShipped { order_id, carrier, tracking_code, .. } => (
+                *order_id,
+                match (carrier, tracking_code) {
+                    (Some(carrier), Some(code)) => format!("Shipped with {} ({})", carrier, code),
+                    (Some(carrier), None) => format!("Shipped with {}", carrier),
+                    _ => "Shipped".to_string(),
+                },
+                json!({ "carrier": carrier, "tracking_code": tracking_code }),
+            ),
+            DomainEvent::ShipmentDelivered { order_id: Some(order_id), carrier, tracking_code, .. } => (
+                *order_id,
+                format!("Delivered by {} ({})", carrier, tracking_code),
+                json!({ "carrier": carrier, "tracking_code": tracking_code }),
+            ),
+            DomainEvent::DisputeOpened { dispute_id, order_id: Some(order_id), amount, currency, reason, .. } => (
+                *order_id,
+                format!("Dispute opened for {}: {}", format_money(*amount, currency), reason),
+                json!({ "dispute_id": dispute_id, "amount": amount, "currency": currency, "reason": reason }),
+            ),
+            _ => return Ok(()),
+        };
+        sqlx::query!(
+            "INSERT INTO order_events (order_id, event, summary, details) VALUES ($1, $2, $3, $4)",
+            order_id,
+            event.name(),
+            summary,
+            details,
+        )
+        .execute(&*self.pool)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+        Ok(())
+    }
+}
@@ -4,10 +4,10 @@ use axum_extra::{headers::{authorization::Bearer, Authorization}, TypedHeader};
 use serde::{Deserialize, Serialize};
 // PgPool accessed through AppState
 // use sqlx::PgPool;
+use crate::jwt_keys;
 use crate::AppState;
 use argon2::{self, password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString}, Argon2};
 use totp_rs::{TOTP, Secret, Algorithm};
-use jsonwebtoken::{encode, EncodingKey, Header, decode, DecodingKey, Validation, TokenData};
 use rand::Rng;
 use base32::{Alphabet, encode as base32_encode};
 use std::sync::Arc;
@@ -41,7 +41,6 @@ pub struct JwtResponse {
     pub token: String,
 }
 
-#[derive(sqlx::FromRow)]
 struct AdminUser {
     id: i32,
     username: String,
@@ -49,6 +48,7 @@ struct AdminUser {
     totp_secret: Option<String>,
 }
 
+#[allow(dead_code)]
 pub struct AuthenticatedAdmin {
     pub username: String,
 }
@@ -65,19 +65,24 @@ where
         let TypedHeader(Authorization(bearer)) = TypedHeader::<Authorization<Bearer>>::from_request_parts(parts, state)
             .await
             .map_err(|_| (StatusCode::UNAUTHORIZED, "Missing or invalid Authorization header".to_string()))?;
-        let jwt_secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "supersecretjwtkey".to_string());
-        let token_data: TokenData<Claims> = decode::<Claims>(
-            bearer.token(),
-            &DecodingKey::from_secret(jwt_secret.as_bytes()),
-            &Validation::default(),
-        )
-        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid token".to_string()))?;
-        Ok(AuthenticatedAdmin {
-            username: token_data.claims.sub,
-        })
+        verify_admin_token(bearer.token())
     }
 }
 
+// Admin tokens use the signing key's secret as is
+fn admin_key(secret: &str) -> Vec<u8> {
+    secret.as_bytes().to_vec()
+}
+
+// Check an admin JWT from any source (header, or query string where headers can't be set)
+pub fn verify_admin_token(token: &str) -> Result<AuthenticatedAdmin, (StatusCode, String)> {
+    let claims: Claims = jwt_keys::verify(token, admin_key)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid token".to_string()))?;
+    Ok(AuthenticatedAdmin {
+        username: claims.sub,
+    })
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
@@ -97,29 +102,40 @@ async fn register_admin(
     State(app_state): State<Arc<AppState>>,
     Json(req): Json<RegisterRequest>,
 ) -> Result<StatusCode, (StatusCode, String)> {
+    create_admin(&app_state.pool, &req.username, &req.password).await?;
+    Ok(StatusCode::CREATED)
+}
+
+// Add an admin user with an Argon2 hash of the password
+pub async fn create_admin(pool: &sqlx::PgPool, username: &str, password: &str) -> Result<(), (StatusCode, String)> {
     let salt = SaltString::generate(&mut rand::thread_rng());
     let password_hash = Argon2::default()
-        .hash_password(req.password.as_bytes(), &salt)
+        .hash_password(password.as_bytes(), &salt)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Hash error: {}", e)))?
         .to_string();
-    sqlx::query("INSERT INTO admin_users (username, password_hash) VALUES ($1, $2)")
-        .bind(&req.username)
-        .bind(&password_hash)
-        .execute(&*app_state.pool)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
-    Ok(StatusCode::CREATED)
+    sqlx::query!(
+        "INSERT INTO admin_users (username, password_hash) VALUES ($1, $2)",
+        username,
+        password_hash,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(())
 }
 
 async fn login_admin(
     State(app_state): State<Arc<AppState>>,
     Json(req): Json<LoginRequest>,
 ) -> Result<Json<TotpSetupResponse>, (StatusCode, String)> {
-    let user: AdminUser = sqlx::query_as("SELECT * FROM admin_users WHERE username = $1")
-        .bind(&req.username)
-        .fetch_one(&*app_state.pool)
-        .await
-        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid username or password".to_string()))?;
+    let user = sqlx::query_as!(
+        AdminUser,
+        "SELECT id, username, password_hash, totp_secret FROM admin_users WHERE username = $1",
+        req.username,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid username or password".to_string()))?;
     let parsed_hash = PasswordHash::new(&user.password_hash)
         .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid password format".to_string()))?;
     if Argon2::default().verify_password(req.password.as_bytes(), &parsed_hash).is_err() {
@@ -133,9 +149,7 @@ async fn login_admin(
             "otpauth://totp/AdminPortal:{}?secret={}&issuer=RustEcomAdmin",
             user.username, secret
         );
-        sqlx::query("UPDATE admin_users SET totp_secret = $1 WHERE id = $2")
-            .bind(&secret)
-            .bind(user.id)
+        sqlx::query!("UPDATE admin_users SET totp_secret = $1 WHERE id = $2", secret, user.id)
             .execute(&*app_state.pool)
             .await
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
@@ -150,20 +164,21 @@ async fn totp_setup(
     Json(req): Json<LoginRequest>,
 ) -> Result<Json<TotpSetupResponse>, (StatusCode, String)> {
     // For explicit TOTP setup (if needed)
-    let user: AdminUser = sqlx::query_as("SELECT * FROM admin_users WHERE username = $1")
-        .bind(&req.username)
-        .fetch_one(&*app_state.pool)
-        .await
-        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid username".to_string()))?;
+    let user = sqlx::query_as!(
+        AdminUser,
+        "SELECT id, username, password_hash, totp_secret FROM admin_users WHERE username = $1",
+        req.username,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid username".to_string()))?;
     let secret_bytes: [u8; 20] = rand::thread_rng().gen();
     let secret = base32_encode(Alphabet::RFC4648 { padding: false }, &secret_bytes);
     let qr_url = format!(
         "otpauth://totp/AdminPortal:{}?secret={}&issuer=RustEcomAdmin",
         user.username, secret
     );
-    sqlx::query("UPDATE admin_users SET totp_secret = $1 WHERE id = $2")
-        .bind(&secret)
-        .bind(user.id)
+    sqlx::query!("UPDATE admin_users SET totp_secret = $1 WHERE id = $2", secret, user.id)
         .execute(&*app_state.pool)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
@@ -174,11 +189,14 @@ async fn totp_verify(
     State(app_state): State<Arc<AppState>>,
     Json(req): Json<TotpVerifyRequest>,
 ) -> Result<Json<JwtResponse>, (StatusCode, String)> {
-    let user: AdminUser = sqlx::query_as("SELECT * FROM admin_users WHERE username = $1")
-        .bind(&req.username)
-        .fetch_one(&*app_state.pool)
-        .await
-        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid username".to_string()))?;
+    let user = sqlx::query_as!(
+        AdminUser,
+        "SELECT id, username, password_hash, totp_secret FROM admin_users WHERE username = $1",
+        req.username,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid username".to_string()))?;
     let secret = user.totp_secret.ok_or((StatusCode::UNAUTHORIZED, "TOTP not set up".to_string()))?;
     let totp = TOTP::new(
         Algorithm::SHA1,
@@ -191,12 +209,16 @@ async fn totp_verify(
     if code != req.code {
         return Err((StatusCode::UNAUTHORIZED, "Invalid TOTP code".to_string()));
     }
-    // Issue JWT
+    let token = issue_admin_token(user.username)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("JWT error: {}", e)))?;
+    Ok(Json(JwtResponse { token }))
+}
+
+// An admin JWT, valid for 8 hours
+pub fn issue_admin_token(username: String) -> Result<String, jsonwebtoken::errors::Error> {
     let claims = Claims {
-        sub: user.username,
+        sub: username,
         exp: (sqlx::types::chrono::Utc::now() + chrono::Duration::hours(8)).timestamp() as usize,
     };
-    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(app_state.jwt_secret.as_bytes()))
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("JWT error: {}", e)))?;
-    Ok(Json(JwtResponse { token }))
+    jwt_keys::sign(&claims, admin_key)
 }
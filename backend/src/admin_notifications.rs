@@ -0,0 +1,130 @@
+// Admin Notifications Module - Real-time feed for admin dashboards
+// New orders, failed payments and low-stock alerts are forwarded from the domain event bus
+// to every admin connected on /api/admin/ws
+
+use async_trait::async_trait;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::admin_auth::verify_admin_token;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::AppState;
+
+// Events buffered per connection before a slow dashboard starts missing them
+const CHANNEL_CAPACITY: usize = 256;
+
+// Fan-out channel shared by the event subscriber and all open sockets
+#[derive(Clone)]
+pub struct AdminNotifier {
+    sender: broadcast::Sender<DomainEvent>,
+}
+
+impl AdminNotifier {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<DomainEvent> {
+        self.sender.subscribe()
+    }
+}
+
+// Forwards the events dashboards care about; dropped silently when nobody is connected
+pub struct AdminNotificationSubscriber {
+    notifier: AdminNotifier,
+}
+
+impl AdminNotificationSubscriber {
+    pub fn new(notifier: AdminNotifier) -> Self {
+        Self { notifier }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for AdminNotificationSubscriber {
+    fn name(&self) -> &'static str {
+        "admin_notifications"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        if matches!(
+            event,
+            DomainEvent::OrderCreated { .. }
+                | DomainEvent::PaymentFailed { .. }
+                | DomainEvent::DisputeOpened { .. }
+                | DomainEvent::LowStock { .. }
+        ) {
+            let _ = self.notifier.sender.send(event.clone());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SocketAuthQuery {
+    pub token: Option<String>, // Browsers can't set headers on WebSocket upgrades
+}
+
+pub fn admin_notification_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/ws", get(admin_socket))
+        .with_state(app_state)
+}
+
+// Authenticate the upgrade with an admin JWT from the Authorization header or ?token=
+async fn admin_socket(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<SocketAuthQuery>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, (StatusCode, String)> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string)
+        .or(query.token)
+        .ok_or((StatusCode::UNAUTHORIZED, "Missing admin token".to_string()))?;
+    let admin = verify_admin_token(&token)?;
+
+    let events = app_state.admin_notifier.subscribe();
+    Ok(ws.on_upgrade(move |socket| stream_events(socket, events, admin.username)))
+}
+
+// Push each event as a JSON text frame until either side goes away
+async fn stream_events(mut socket: WebSocket, mut events: broadcast::Receiver<DomainEvent>, username: String) {
+    tracing::info!("Admin {} connected to notifications", username);
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    let Ok(payload) = serde_json::to_string(&event) else { continue };
+                    if socket.send(Message::Text(payload)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::error!("Admin notifications for {} skipped {} events", username, skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                Some(Ok(_)) => {} // Dashboards don't send anything; pings are answered automatically
+            },
+        }
+    }
+    tracing::info!("Admin {} disconnected from notifications", username);
+}
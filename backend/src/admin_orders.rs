@@ -0,0 +1,522 @@
+// Admin Orders Module - Order list and details, internal order notes and printable order documents
+// Packing slips carry the gift message and wrap instructions; invoices for gift
+// orders are printed without prices
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Html, Response},
+    routing::{delete, get},
+    Json, Router,
+};
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::branding::{escape_html, load_branding, Branding};
+use crate::order_shipments::OrderShipment;
+use crate::order_snapshots;
+use crate::pii::Encrypted;
+use crate::export_stream::{self, ExportQuery};
+use crate::pagination::{self, Page};
+use crate::repos::{OrderExportFilter, OrderListFilter, OrderRepo};
+use crate::route_limits::{self, RouteGroup};
+use crate::warehouses::OrderFulfillment;
+use crate::webhooks::{Order, OrderItem};
+use crate::AppState;
+
+// Database model for internal order notes
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct OrderNote {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub author: String,
+    pub body: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+// An order with the items bought
+#[derive(Serialize)]
+pub struct OrderDetail {
+    #[serde(flatten)]
+    pub order: Order,
+    pub items: Vec<OrderItem>,
+}
+
+#[derive(Deserialize)]
+pub struct OrderListQuery {
+    pub status: Option<String>,
+    pub customer_id: Option<Uuid>,
+    pub cursor: Option<String>, // next_cursor of the previous page
+    pub limit: Option<i64>,     // Defaults to 50, at most 200
+}
+
+#[derive(Deserialize)]
+pub struct OrderNoteInput {
+    pub body: String,
+}
+
+#[derive(Deserialize)]
+pub struct PackingSlipQuery {
+    pub fulfillment_id: Option<Uuid>, // Slip for one box/warehouse instead of the whole order
+}
+
+#[derive(Deserialize)]
+pub struct InvoiceQuery {
+    pub gift: Option<bool>, // Defaults to a gift invoice when the order has gift options
+}
+
+// Line printed on a packing slip or invoice
+#[derive(Debug, sqlx::FromRow)]
+struct DocumentLine {
+    product_name: String,
+    sku: Option<String>,
+    quantity: i32,
+    unit_price: Option<i64>,  // in cents, absent on packing slips
+    total_price: Option<i64>, // in cents, absent on packing slips
+}
+
+pub fn admin_order_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/orders", get(list_orders))
+        .route("/api/admin/orders/export", get(export_orders).layer(route_limits::limit(RouteGroup::Reports)))
+        .route("/api/admin/orders/:id", get(get_order))
+        .route("/api/admin/orders/:id/notes", get(list_order_notes).post(create_order_note))
+        .route("/api/admin/orders/:id/notes/:note_id", delete(delete_order_note))
+        .route("/api/admin/orders/:id/packing-slip", get(packing_slip))
+        .route("/api/admin/orders/:id/invoice", get(invoice))
+        .with_state(app_state)
+}
+
+async fn load_order(orders: &dyn OrderRepo, order_id: Uuid) -> Result<Order, (StatusCode, String)> {
+    orders
+        .find(order_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Order not found".to_string()))
+}
+
+// A page of orders, newest first, each with its items
+async fn list_orders(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<OrderListQuery>,
+) -> Result<Json<Page<OrderDetail>>, (StatusCode, String)> {
+    let size = pagination::page_size(query.limit);
+    let filter = OrderListFilter {
+        customer_id: query.customer_id,
+        status: query.status,
+        before: pagination::after(query.cursor.as_deref())?,
+        limit: Some(size + 1),
+    };
+    let orders = app_state.repos.orders.list_with_items(&filter)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(Page::new(orders, size, |o| (o.order.created_at, o.order.id))))
+}
+
+// Matching orders as CSV (or JSON with ?format=json), oldest first, streamed as they're read
+async fn export_orders(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ExportQuery>,
+    Query(filter): Query<OrderExportFilter>,
+) -> Response {
+    let orders = app_state.repos.orders.clone();
+    export_stream::respond(query.format, "orders", move |mut out| async move {
+        let mut rows = orders.stream(&filter);
+        while let Some(order) = rows.try_next().await? {
+            out.write(&order).await?;
+        }
+        Ok(out)
+    })
+}
+
+async fn get_order(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<OrderDetail>, (StatusCode, String)> {
+    let order = load_order(&*app_state.repos.orders, order_id).await?;
+    let items = app_state.repos.orders.items(order_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(OrderDetail { order, items }))
+}
+
+async fn list_order_notes(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<Vec<OrderNote>>, (StatusCode, String)> {
+    let notes = sqlx::query_as!(
+        OrderNote,
+        "SELECT id, order_id, author, body, created_at FROM order_notes WHERE order_id = $1 ORDER BY created_at DESC",
+        order_id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(notes))
+}
+
+async fn create_order_note(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+    Json(input): Json<OrderNoteInput>,
+) -> Result<(StatusCode, Json<OrderNote>), (StatusCode, String)> {
+    let body = input.body.trim();
+    if body.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "body is required".to_string()));
+    }
+    load_order(&*app_state.repos.orders, order_id).await?;
+
+    let note = sqlx::query_as!(
+        OrderNote,
+        "INSERT INTO order_notes (order_id, author, body) VALUES ($1, $2, $3) RETURNING id, order_id, author, body, created_at",
+        order_id,
+        &admin.username,
+        body,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok((StatusCode::CREATED, Json(note)))
+}
+
+async fn delete_order_note(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path((order_id, note_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM order_notes WHERE id = $1 AND order_id = $2", note_id, order_id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Note not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Printable packing slip for an order, or for one of its fulfillments
+async fn packing_slip(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+    Query(query): Query<PackingSlipQuery>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let order = load_order(&*app_state.repos.orders, order_id).await?;
+
+    let mut ship_to = None;
+    let lines = match query.fulfillment_id {
+        Some(fulfillment_id) => {
+            let fulfillment = sqlx::query_as!(
+                OrderFulfillment,
+                "SELECT id, order_id, warehouse_id, status, carrier, tracking_code, shipment_id, created_at, updated_at, order_shipment_id, shipped_at, delivered_at FROM order_fulfillments WHERE id = $1 AND order_id = $2",
+                fulfillment_id,
+                order_id,
+            )
+            .fetch_optional(&*app_state.pool)
+            .await
+            .map_err(db_err)?
+            .ok_or((StatusCode::NOT_FOUND, "Fulfillment not found".to_string()))?;
+            if let Some(shipment_id) = fulfillment.order_shipment_id {
+                ship_to = sqlx::query_as!(OrderShipment, r#"SELECT id, payment_intent_id, order_id, address_key, recipient_name AS "recipient_name: Encrypted", street1 AS "street1: Encrypted", street2 AS "street2: Encrypted", city, state, zip, country, phone AS "phone: Encrypted", shipping_method, subtotal_amount, shipping_amount, status, created_at FROM order_shipments WHERE id = $1"#, shipment_id)
+                    .fetch_optional(&*app_state.pool)
+                    .await
+                    .map_err(db_err)?;
+            }
+            sqlx::query_as!(
+                DocumentLine,
+                r#"SELECT COALESCE(p.name, 'Deleted product') AS "product_name!", p.sku, i.quantity,
+                        NULL::BIGINT AS unit_price, NULL::BIGINT AS total_price
+                 FROM order_fulfillment_items i LEFT JOIN products p ON p.id = i.product_id
+                 WHERE i.fulfillment_id = $1 ORDER BY 1"#,
+                fulfillment.id,
+            )
+            .fetch_all(&*app_state.pool)
+            .await
+            .map_err(db_err)?
+        }
+        None => order_lines(&app_state.pool, order_id).await.map_err(db_err)?,
+    };
+
+    let gift = order.gift_options();
+    let mut gift_html = String::new();
+    if gift.gift_wrap {
+        gift_html.push_str(r#"<p class="gift-wrap">🎁 Gift wrap this order</p>"#);
+    }
+    if let Some(message) = &gift.gift_message {
+        gift_html.push_str(&format!(
+            r#"<div class="gift-message"><strong>Gift message</strong><p>{}</p></div>"#,
+            escape_html(message).replace('\n', "<br>")
+        ));
+    }
+
+    let ship_to_html = ship_to
+        .map(|s| {
+            let address = s.address();
+            format!(
+                "<p><strong>Ship to</strong><br>{}{}{}<br>{}, {} {}<br>{}</p>",
+                address.name.as_deref().map(|n| format!("{}<br>", escape_html(n))).unwrap_or_default(),
+                escape_html(&address.street1),
+                address.street2.as_deref().map(|s| format!("<br>{}", escape_html(s))).unwrap_or_default(),
+                escape_html(&address.city),
+                escape_html(&address.state),
+                escape_html(&address.zip),
+                escape_html(&s.country),
+            )
+        })
+        .unwrap_or_default();
+
+    let rows: String = lines
+        .iter()
+        .map(|l| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td class=\"qty\">{}</td></tr>",
+                escape_html(&l.product_name),
+                escape_html(l.sku.as_deref().unwrap_or("")),
+                l.quantity
+            )
+        })
+        .collect();
+
+    Ok(Html(render_document(
+        &load_branding(&app_state.pool).await,
+        "Packing Slip",
+        &order,
+        &format!(
+            "{}{}<table><tr><th>Item</th><th>SKU</th><th class=\"qty\">Qty</th></tr>{}</table>",
+            ship_to_html, gift_html, rows
+        ),
+    )))
+}
+
+// Printable invoice; gift invoices list the items without any prices
+async fn invoice(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+    Query(query): Query<InvoiceQuery>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let order = load_order(&*app_state.repos.orders, order_id).await?;
+    let lines = order_lines(&app_state.pool, order_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    let is_gift = query.gift.unwrap_or_else(|| order.gift_options().is_gift());
+
+    let body = if is_gift {
+        let rows: String = lines
+            .iter()
+            .map(|l| format!("<tr><td>{}</td><td class=\"qty\">{}</td></tr>", escape_html(&l.product_name), l.quantity))
+            .collect();
+        format!(
+            "<p class=\"gift-wrap\">Gift invoice: prices are not shown</p>\
+             <table><tr><th>Item</th><th class=\"qty\">Qty</th></tr>{}</table>",
+            rows
+        )
+    } else {
+        let rows: String = lines
+            .iter()
+            .map(|l| {
+                format!(
+                    "<tr><td>{}</td><td class=\"qty\">{}</td><td class=\"money\">{}</td><td class=\"money\">{}</td></tr>",
+                    escape_html(&l.product_name),
+                    l.quantity,
+                    format_money(l.unit_price.unwrap_or(0)),
+                    format_money(l.total_price.unwrap_or(0))
+                )
+            })
+            .collect();
+        // Discount, tax and shipping as charged, from the order's snapshot
+        let adjustments: String = order_snapshots::load_as_sold(&app_state.pool, order_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+            .map(|sold| sold.adjustments())
+            .unwrap_or_default()
+            .iter()
+            .map(|(label, cents)| format!("<p class=\"money\">{}: {}</p>", escape_html(label), format_money(*cents)))
+            .collect();
+        format!(
+            "<table><tr><th>Item</th><th class=\"qty\">Qty</th><th class=\"money\">Unit price</th><th class=\"money\">Total</th></tr>{}</table>\
+             {}<p class=\"total\">Total: {} {}</p>",
+            rows,
+            adjustments,
+            format_money(order.total_amount),
+            escape_html(&order.currency)
+        )
+    };
+
+    let branding = load_branding(&app_state.pool).await;
+    Ok(Html(render_document(&branding, if is_gift { "Gift Invoice" } else { "Invoice" }, &order, &body)))
+}
+
+// Items recorded on the order at purchase time; items sold before SKU snapshots show the current SKU
+async fn order_lines(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Vec<DocumentLine>, sqlx::Error> {
+    sqlx::query_as!(
+        DocumentLine,
+        r#"SELECT i.product_name, COALESCE(i.sku, p.sku) AS sku, i.quantity, i.unit_price AS "unit_price?", i.total_price AS "total_price?"
+         FROM order_items i LEFT JOIN products p ON p.id = i.product_id
+         WHERE i.order_id = $1 ORDER BY i.created_at, i.product_name"#,
+        order_id,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+fn render_document(branding: &Branding, title: &str, order: &Order, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <title>{title} - {order_id}</title>
+    <style>
+        body {{ font-family: Arial, sans-serif; line-height: 1.5; color: #333; }}
+        .container {{ max-width: 700px; margin: 0 auto; padding: 20px; }}
+        .letterhead {{ display: flex; align-items: center; gap: 15px; padding-bottom: 10px; border-bottom: 3px solid {primary}; }}
+        .letterhead .logo {{ max-height: 48px; }}
+        h1 {{ color: {primary}; }}
+        .document-footer {{ margin-top: 30px; color: #666; font-size: 12px; }}
+        table {{ width: 100%; border-collapse: collapse; margin: 20px 0; }}
+        th, td {{ text-align: left; padding: 6px 8px; border-bottom: 1px solid #ddd; }}
+        .qty, .money {{ text-align: right; }}
+        .gift-wrap {{ font-weight: bold; }}
+        .gift-message {{ border: 1px dashed #999; padding: 10px 15px; margin: 15px 0; }}
+        .total {{ font-size: 18px; font-weight: bold; text-align: right; }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        {letterhead}
+        <h1>{title}</h1>
+        <p><strong>Order:</strong> {order_id}<br><strong>Date:</strong> {date}</p>
+        {body}
+        {footer}
+    </div>
+</body>
+</html>"#,
+        primary = branding.primary_color,
+        letterhead = branding.document_header_html(),
+        footer = branding.document_footer_html(),
+        title = title,
+        order_id = order.id,
+        date = order.created_at.format("%b %-d, %Y"),
+        body = body,
+    )
+}
+
+fn format_money(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}${:.2}", sign, cents.abs() as f64 / 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repos::memory::MemoryOrderRepo;
+    use crate::repos::{PgProductRepo, PgWebhookRepo, Repos};
+    use crate::webhooks::OrderStatus;
+
+    fn order(id: Uuid) -> Order {
+        Order {
+            id,
+            payment_provider: "stripe".to_string(),
+            payment_id: "pi_test".to_string(),
+            payment_intent_id: Some("pi_test".to_string()),
+            customer_email: Some("jane@example.com".into()),
+            customer_name: Some("Jane Doe".to_string()),
+            total_amount: 2500,
+            currency: "usd".to_string(),
+            status: OrderStatus::Completed,
+            webhook_event_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            gift_wrap: false,
+            gift_message: None,
+            customer_id: None,
+            cart_id: None,
+            authorization_expires_at: None,
+            amount_captured: None,
+            captured_at: None,
+            livemode: false,
+            payment_method: None,
+            created_by: None,
+            payment_link_url: None,
+            payment_link_id: None,
+            po_number: None,
+            payment_due_at: None,
+            paid_at: None,
+            payment_reminder_sent_at: None,
+        }
+    }
+
+    fn item(order_id: Uuid) -> OrderItem {
+        OrderItem {
+            id: Uuid::new_v4(),
+            order_id,
+            product_id: Some(1),
+            product_name: "Mug".to_string(),
+            product_description: None,
+            quantity: 2,
+            unit_price: 1250,
+            total_price: 2500,
+            created_at: Utc::now(),
+        }
+    }
+
+    // Orders come from the in-memory repo; the pool is never connected
+    fn app_state(orders: Arc<MemoryOrderRepo>) -> Arc<AppState> {
+        for (key, value) in [
+            ("DATABASE_URL", "postgres://localhost/unused"),
+            ("STRIPE_SECRET_KEY", "sk_test_admin_orders"),
+            ("STRIPE_WEBHOOK_SECRET", "whsec_admin_orders"),
+            ("JWT_SECRET", "admin-orders-test"),
+        ] {
+            if std::env::var(key).is_err() {
+                std::env::set_var(key, value);
+            }
+        }
+        crate::app_config::init();
+        let pool = Arc::new(sqlx::PgPool::connect_lazy("postgres://localhost/unused").unwrap());
+        let repos = Repos {
+            products: Arc::new(PgProductRepo::new(pool.clone())),
+            orders,
+            webhook_events: Arc::new(PgWebhookRepo::new(pool.clone())),
+        };
+        crate::build_app_state_with_repos(pool, crate::StripeClient::new("sk_test_admin_orders"), repos)
+    }
+
+    fn admin() -> AuthenticatedAdmin {
+        AuthenticatedAdmin { username: "test".to_string() }
+    }
+
+    #[tokio::test]
+    async fn get_order_returns_the_order_with_its_items() {
+        let orders = Arc::new(MemoryOrderRepo::default());
+        let (id, other) = (Uuid::new_v4(), Uuid::new_v4());
+        orders.insert(order(id), vec![item(id)]);
+        orders.insert(order(other), vec![item(other)]);
+
+        let Json(detail) = get_order(admin(), State(app_state(orders)), Path(id)).await.unwrap();
+        assert_eq!(detail.order.id, id);
+        assert_eq!(detail.order.customer_email.as_deref(), Some("jane@example.com"));
+        assert_eq!(detail.items.len(), 1);
+        assert_eq!(detail.items[0].order_id, id);
+    }
+
+    #[tokio::test]
+    async fn get_order_is_404_for_an_unknown_order() {
+        let orders = Arc::new(MemoryOrderRepo::default());
+        let Err((status, _)) = get_order(admin(), State(app_state(orders)), Path(Uuid::new_v4())).await else {
+            panic!("expected an error");
+        };
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
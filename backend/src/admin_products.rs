@@ -1,17 +1,54 @@
 // AXUM 0.7.4 UPDATE: Only needed routing imports
 use axum::{
-    extract::{Path, State},
-    routing::{get, put},
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
     Json, Router,
 };
+use futures_util::TryStreamExt;
 use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
 // PgPool accessed through AppState
 // use sqlx::PgPool;
+use std::collections::HashMap;
 use std::sync::Arc;
 use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::bulk_insert;
+use crate::events::{DomainEvent, EventBus};
+use crate::export_stream::{self, ExportQuery};
+use crate::pagination::{self, Listing, Page, PageQuery};
+use crate::pricing;
+use crate::product_slugs::{self, AssignedSlug};
+use crate::repos::{NewProduct, ProductUpdate};
+use crate::route_limits::{self, RouteGroup};
 use crate::AppState;
 
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+
+// Publication state of a product
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
+pub enum ProductStatus {
+    #[sqlx(rename = "draft")]
+    #[serde(rename = "draft")]
+    Draft,
+    #[sqlx(rename = "published")]
+    #[serde(rename = "published")]
+    Published,
+    #[sqlx(rename = "scheduled")]
+    #[serde(rename = "scheduled")]
+    Scheduled,
+}
+
+// Lets a column of statuses bind as one array, for bulk inserts
+impl sqlx::postgres::PgHasArrayType for ProductStatus {
+    fn array_type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("_varchar")
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Product {
     pub id: i32,
     pub name: String,
@@ -19,6 +56,21 @@ pub struct Product {
     pub price: f64,
     pub inventory: i32,
     pub created_at: sqlx::types::chrono::NaiveDateTime,
+    pub status: ProductStatus,
+    pub publish_at: Option<DateTime<Utc>>,
+    pub sku: Option<String>,
+    pub barcode: Option<String>,
+    pub weight_oz: Option<f64>,
+    pub length_in: Option<f64>,
+    pub width_in: Option<f64>,
+    pub height_in: Option<f64>,
+    pub hs_code: Option<String>,
+    pub country_of_origin: Option<String>,
+    pub domestic_only: bool,
+    pub image_url: Option<String>,
+    pub brand: Option<String>,
+    pub category: Option<String>,
+    pub slug: String, // Generated from the name; storefront URLs are /product/{slug}
 }
 
 #[derive(Deserialize)]
@@ -27,42 +79,345 @@ pub struct ProductInput {
     pub description: Option<String>,
     pub price: f64,
     pub inventory: i32,
+    pub status: Option<ProductStatus>, // Defaults to published on create, unchanged on update
+    pub publish_at: Option<DateTime<Utc>>,
+    pub sku: Option<String>,
+    pub barcode: Option<String>,
+    pub weight_oz: Option<f64>,
+    pub length_in: Option<f64>,
+    pub width_in: Option<f64>,
+    pub height_in: Option<f64>,
+    pub hs_code: Option<String>,
+    pub country_of_origin: Option<String>,
+    pub domestic_only: Option<bool>, // Defaults to false on create, unchanged on update
+    pub image_url: Option<String>,
+    pub brand: Option<String>,
+    pub category: Option<String>,
+}
+
+impl ProductInput {
+    // Codes trimmed with blanks as absent and country codes upper-cased, as products store them
+    pub fn normalized(self) -> Self {
+        Self {
+            sku: normalize_code(self.sku),
+            barcode: normalize_code(self.barcode),
+            hs_code: normalize_code(self.hs_code),
+            country_of_origin: normalize_country(self.country_of_origin),
+            image_url: normalize_code(self.image_url),
+            brand: normalize_code(self.brand),
+            category: normalize_code(self.category),
+            ..self
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LookupQuery {
+    pub sku: Option<String>,
+    pub barcode: Option<String>,
+}
+
+// One row of the product CSV import/export format
+#[derive(Serialize, Deserialize)]
+pub struct ProductCsvRow {
+    pub id: Option<i32>,
+    pub sku: Option<String>,
+    pub barcode: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    pub price: f64,
+    pub inventory: i32,
+    pub status: Option<ProductStatus>,
+    pub weight_oz: Option<f64>,
+    pub length_in: Option<f64>,
+    pub width_in: Option<f64>,
+    pub height_in: Option<f64>,
+    pub hs_code: Option<String>,
+    pub country_of_origin: Option<String>,
+    pub domestic_only: Option<bool>,
+    pub image_url: Option<String>,
+    pub brand: Option<String>,
+    pub category: Option<String>,
+}
+
+impl From<Product> for ProductCsvRow {
+    fn from(p: Product) -> Self {
+        ProductCsvRow {
+            id: Some(p.id),
+            sku: p.sku,
+            barcode: p.barcode,
+            name: p.name,
+            description: p.description,
+            price: p.price,
+            inventory: p.inventory,
+            status: Some(p.status),
+            weight_oz: p.weight_oz,
+            length_in: p.length_in,
+            width_in: p.width_in,
+            height_in: p.height_in,
+            hs_code: p.hs_code,
+            country_of_origin: p.country_of_origin,
+            domestic_only: Some(p.domestic_only),
+            image_url: p.image_url,
+            brand: p.brand,
+            category: p.category,
+        }
+    }
+}
+
+impl ProductCsvRow {
+    // The row as a new product, normalized as products store it
+    fn into_input(self) -> ProductInput {
+        ProductInput {
+            name: self.name,
+            description: self.description,
+            price: self.price,
+            inventory: self.inventory,
+            status: self.status,
+            publish_at: None,
+            sku: self.sku,
+            barcode: self.barcode,
+            weight_oz: self.weight_oz,
+            length_in: self.length_in,
+            width_in: self.width_in,
+            height_in: self.height_in,
+            hs_code: self.hs_code,
+            country_of_origin: self.country_of_origin,
+            domestic_only: self.domestic_only,
+            image_url: self.image_url,
+            brand: self.brand,
+            category: self.category,
+        }
+        .normalized()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ImportSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub errors: Vec<String>,
+    pub skipped_duplicates: Vec<ImportDuplicate>, // New rows not imported because they look like existing products
+}
+
+#[derive(Deserialize)]
+pub struct ForceQuery {
+    #[serde(default)]
+    pub force: bool, // Create products even when likely duplicates exist
+}
+
+// Existing product a new one looks like: a similar name, or the same SKU ignoring case and punctuation
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct DuplicateCandidate {
+    pub id: i32,
+    pub name: String,
+    pub sku: Option<String>,
+    pub similarity: f64, // Name trigram similarity, 0 to 1
+    pub reason: String,  // "sku" or "name"
+}
+
+#[derive(Serialize)]
+pub struct DuplicateProductsResponse {
+    pub error: String,
+    pub duplicates: Vec<DuplicateCandidate>,
+}
+
+#[derive(Serialize)]
+pub struct ImportDuplicate {
+    pub line: usize,
+    pub name: String,
+    pub duplicates: Vec<DuplicateCandidate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub earlier_line: Option<usize>, // Or the new product from this line of the file it looks like
+}
+
+#[derive(Deserialize)]
+pub struct ScheduleRequest {
+    pub publish_at: DateTime<Utc>,
 }
 
 pub fn admin_product_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
         .route("/api/admin/products", get(list_products).post(create_product))
+        .route("/api/admin/products/lookup", get(lookup_product))
+        .route("/api/admin/products/export", get(export_products_csv).layer(route_limits::limit(RouteGroup::Reports)))
+        .route("/api/admin/products/import", post(import_products_csv).layer(route_limits::limit(RouteGroup::Imports)))
         .route("/api/admin/products/:id", put(update_product).delete(delete_product))
+        .route("/api/admin/products/:id/preview", get(preview_product))
+        .route("/api/admin/products/:id/publish", post(publish_product))
+        .route("/api/admin/products/:id/unpublish", post(unpublish_product))
+        .route("/api/admin/products/:id/schedule", post(schedule_product))
         .with_state(app_state)
 }
 
+// Background task that flips scheduled products live once publish_at has passed
+pub fn spawn_publish_scheduler(app_state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            match sqlx::query_scalar!(
+                "UPDATE products SET status = 'published'
+                 WHERE status = 'scheduled' AND publish_at <= NOW()
+                 RETURNING id",
+            )
+            .fetch_all(&*app_state.pool)
+            .await
+            {
+                Ok(ids) if !ids.is_empty() => {
+                    tracing::info!("Published {} scheduled product(s)", ids.len());
+                    for product_id in ids {
+                        app_state.events.publish(DomainEvent::ProductUpdated { product_id });
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to publish scheduled products: {}", e),
+            }
+        }
+    });
+}
+
+// Every product in id order, or a page of them when `cursor` or `limit` is given
 async fn list_products(
     _admin: AuthenticatedAdmin,
     State(app_state): State<Arc<AppState>>,
-) -> Json<Vec<Product>> {
-    let products = sqlx::query_as::<_, Product>("SELECT * FROM products ORDER BY id")
-        .fetch_all(&*app_state.pool)
-        .await
-        .unwrap_or_default();
-    Json(products)
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Listing<Product>>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    if !query.wants_page() {
+        let products = app_state.repos.products.list().await.map_err(db_error)?;
+        return Ok(Json(Listing::All(products)));
+    }
+    let size = pagination::page_size(query.limit);
+    let after = pagination::after(query.cursor.as_deref())?;
+    let products = app_state.repos.products.list_after(false, after, size + 1).await.map_err(db_error)?;
+    Ok(Json(Listing::Page(Page::new(products, size, |p| p.id))))
+}
+
+// A SKU compared ignoring case and punctuation
+fn sku_key(sku: Option<&str>) -> Option<String> {
+    sku.map(|s| s.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase())
+        .filter(|s| !s.is_empty())
+}
+
+// Products that a new product named `name` with this SKU probably duplicates, most similar first
+async fn find_duplicates(pool: &sqlx::PgPool, name: &str, sku: Option<&str>) -> Result<Vec<DuplicateCandidate>, sqlx::Error> {
+    let mut found = find_duplicates_of_each(pool, &[(name, sku)]).await?;
+    Ok(found.remove(0))
+}
+
+// find_duplicates for each of several new products (an import), in one query
+async fn find_duplicates_of_each(
+    pool: &sqlx::PgPool,
+    products: &[(&str, Option<&str>)],
+) -> Result<Vec<Vec<DuplicateCandidate>>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT n.ord AS "ord!", c.id AS "id!", c.name AS "name!", c.sku, c.similarity AS "similarity!", c.reason AS "reason!"
+         FROM UNNEST($1::TEXT[], $2::TEXT[]) WITH ORDINALITY AS n(name, sku_key, ord)
+         CROSS JOIN LATERAL (
+             SELECT * FROM (
+                 SELECT p.id, p.name, p.sku, similarity(p.name, n.name)::FLOAT8 AS similarity,
+                        CASE WHEN REGEXP_REPLACE(LOWER(p.sku), '[^a-z0-9]', '', 'g') = n.sku_key THEN 'sku' ELSE 'name' END AS reason
+                 FROM products p
+             ) candidates
+             WHERE reason = 'sku' OR similarity >= $3
+             ORDER BY reason = 'sku' DESC, similarity DESC, id
+             LIMIT 5
+         ) c
+         ORDER BY n.ord, c.reason = 'sku' DESC, c.similarity DESC, c.id"#,
+        &products.iter().map(|(name, _)| name.trim().to_string()).collect::<Vec<_>>(),
+        &products.iter().map(|(_, sku)| sku_key(*sku)).collect::<Vec<_>>() as _,
+        app_config::get().limits.duplicate_name_similarity,
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut found = vec![Vec::new(); products.len()];
+    for row in rows {
+        found[row.ord as usize - 1].push(DuplicateCandidate {
+            id: row.id,
+            name: row.name,
+            sku: row.sku,
+            similarity: row.similarity,
+            reason: row.reason,
+        });
+    }
+    Ok(found)
+}
+
+// A new product of an import that looks like one on an earlier line of the same file
+struct PendingMatch {
+    later: usize,   // Positions in the import's new products
+    earlier: usize,
+    same_sku: bool,
+    similarity: f64,
 }
 
+// The same check among the new products of an import, which aren't inserted yet: every pair of
+// them that matches, read in one query. Which earlier ones are kept is decided as the file is read
+async fn find_pending_matches(pool: &sqlx::PgPool, products: &[(&str, Option<&str>)]) -> Result<Vec<PendingMatch>, sqlx::Error> {
+    if products.len() < 2 {
+        return Ok(Vec::new());
+    }
+    let rows = sqlx::query!(
+        r#"SELECT later.ord AS "later!", earlier.ord AS "earlier!",
+                  COALESCE(earlier.sku_key = later.sku_key, FALSE) AS "same_sku!",
+                  similarity(earlier.name, later.name)::FLOAT8 AS "similarity!"
+         FROM UNNEST($1::TEXT[], $2::TEXT[]) WITH ORDINALITY AS later(name, sku_key, ord)
+         JOIN UNNEST($1::TEXT[], $2::TEXT[]) WITH ORDINALITY AS earlier(name, sku_key, ord)
+           ON earlier.ord < later.ord
+          AND (earlier.sku_key = later.sku_key OR similarity(earlier.name, later.name)::FLOAT8 >= $3)"#,
+        &products.iter().map(|(name, _)| name.trim().to_string()).collect::<Vec<_>>(),
+        &products.iter().map(|(_, sku)| sku_key(*sku)).collect::<Vec<_>>() as _,
+        app_config::get().limits.duplicate_name_similarity,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| PendingMatch {
+            later: row.later as usize - 1,
+            earlier: row.earlier as usize - 1,
+            same_sku: row.same_sku,
+            similarity: row.similarity,
+        })
+        .collect())
+}
+
+// Create a product; likely duplicates are answered with 409 and the matches unless ?force=true
 async fn create_product(
     _admin: AuthenticatedAdmin,
     State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ForceQuery>,
     Json(input): Json<ProductInput>,
-) -> Json<Product> {
-    let rec = sqlx::query_as::<_, Product>(
-        "INSERT INTO products (name, description, price, inventory) VALUES ($1, $2, $3, $4) RETURNING *"
-    )
-    .bind(&input.name)
-    .bind(&input.description)
-    .bind(input.price)
-    .bind(input.inventory)
-    .fetch_one(&*app_state.pool)
-    .await
-    .unwrap();
-    Json(rec)
+) -> Result<Json<Product>, Response> {
+    check_inventory(&input).map_err(IntoResponse::into_response)?;
+    if !query.force {
+        let duplicates = find_duplicates(&app_state.pool, &input.name, input.sku.as_deref())
+            .await
+            .map_err(|e| db_error(e).into_response())?;
+        if !duplicates.is_empty() {
+            return Err((
+                StatusCode::CONFLICT,
+                Json(DuplicateProductsResponse {
+                    error: "Likely duplicate of an existing product; retry with ?force=true to create it anyway".to_string(),
+                    duplicates,
+                }),
+            )
+                .into_response());
+        }
+    }
+
+    let assigned = product_slugs::assign_slug(&app_state.pool, &input.name, None)
+        .await
+        .map_err(|e| db_error(e).into_response())?;
+    let rec = app_state
+        .repos
+        .products
+        .create(&input.normalized(), &assigned.slug)
+        .await
+        .map_err(|e| db_error(e).into_response())?;
+    app_state.events.publish(DomainEvent::ProductCreated { product_id: rec.id });
+    Ok(Json(rec))
 }
 
 async fn update_product(
@@ -70,19 +425,34 @@ async fn update_product(
     State(app_state): State<Arc<AppState>>,
     Path(id): Path<i32>,
     Json(input): Json<ProductInput>,
-) -> Json<Product> {
-    let rec = sqlx::query_as::<_, Product>(
-        "UPDATE products SET name = $1, description = $2, price = $3, inventory = $4 WHERE id = $5 RETURNING *"
-    )
-    .bind(&input.name)
-    .bind(&input.description)
-    .bind(input.price)
-    .bind(input.inventory)
-    .bind(id)
-    .fetch_one(&*app_state.pool)
-    .await
-    .unwrap();
-    Json(rec)
+) -> Result<Json<Product>, (StatusCode, String)> {
+    check_inventory(&input)?;
+    let previous: Option<(i32, f64)> = app_state
+        .repos
+        .products
+        .find(id)
+        .await
+        .unwrap_or_default()
+        .map(|p| (p.inventory, p.price));
+    let assigned = product_slugs::assign_slug(&app_state.pool, &input.name, Some(id))
+        .await
+        .map_err(db_error)?;
+    let rec = app_state
+        .repos
+        .products
+        .update(id, &input.normalized(), &assigned.slug)
+        .await
+        .map_err(db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Product not found".to_string()))?;
+    product_slugs::keep_old_slug(&app_state.pool, id, &assigned)
+        .await
+        .map_err(db_error)?;
+    app_state.events.publish(DomainEvent::ProductUpdated { product_id: rec.id });
+    publish_stock_events(&app_state.events, &rec, previous.map(|(inventory, _)| inventory));
+    if let Some((_, previous_price)) = previous {
+        pricing::publish_price_drop(&app_state.events, rec.id, &rec.name, previous_price, rec.price);
+    }
+    Ok(Json(rec))
 }
 
 async fn delete_product(
@@ -90,10 +460,344 @@ async fn delete_product(
     State(app_state): State<Arc<AppState>>,
     Path(id): Path<i32>,
 ) -> Json<bool> {
-    let res = sqlx::query("DELETE FROM products WHERE id = $1")
-        .bind(id)
-        .execute(&*app_state.pool)
+    let deleted = app_state.repos.products.delete(id).await.unwrap();
+    if deleted {
+        app_state.events.publish(DomainEvent::ProductDeleted { product_id: id });
+    }
+    Json(deleted)
+}
+
+// Preview a product exactly as it will appear, regardless of publication state
+async fn preview_product(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Product>, (StatusCode, String)> {
+    app_state
+        .repos
+        .products
+        .find(id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "Product not found".to_string()))
+}
+
+async fn publish_product(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Product>, (StatusCode, String)> {
+    set_product_status(&app_state, id, ProductStatus::Published, None).await
+}
+
+async fn unpublish_product(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Product>, (StatusCode, String)> {
+    set_product_status(&app_state, id, ProductStatus::Draft, None).await
+}
+
+async fn schedule_product(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(req): Json<ScheduleRequest>,
+) -> Result<Json<Product>, (StatusCode, String)> {
+    if req.publish_at <= Utc::now() {
+        return Err((StatusCode::BAD_REQUEST, "publish_at must be in the future".to_string()));
+    }
+    set_product_status(&app_state, id, ProductStatus::Scheduled, Some(req.publish_at)).await
+}
+
+// Helper to move a product between draft/published/scheduled
+async fn set_product_status(
+    app_state: &AppState,
+    id: i32,
+    status: ProductStatus,
+    publish_at: Option<DateTime<Utc>>,
+) -> Result<Json<Product>, (StatusCode, String)> {
+    let rec = app_state
+        .repos
+        .products
+        .set_status(id, status, publish_at)
         .await
-        .unwrap();
-    Json(res.rows_affected() > 0)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Product not found".to_string()))?;
+    app_state.events.publish(DomainEvent::ProductUpdated { product_id: rec.id });
+    Ok(Json(rec))
+}
+
+// Look up a single product by SKU or barcode (POS-style scanning)
+async fn lookup_product(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<LookupQuery>,
+) -> Result<Json<Product>, (StatusCode, String)> {
+    let products = &app_state.repos.products;
+    let (column, code, found) = match (normalize_code(query.sku), normalize_code(query.barcode)) {
+        (Some(sku), _) => ("sku", sku.clone(), products.find_by_sku(&sku).await),
+        (None, Some(barcode)) => ("barcode", barcode.clone(), products.find_by_barcode(&barcode).await),
+        (None, None) => {
+            return Err((StatusCode::BAD_REQUEST, "Provide sku or barcode".to_string()));
+        }
+    };
+
+    found
+        .map_err(db_error)?
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, format!("No product with {} {}", column, code)))
+}
+
+// Export all products as CSV (or JSON with ?format=json), streamed as they're read
+async fn export_products_csv(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let products = app_state.repos.products.clone();
+    export_stream::respond(query.format, "products", move |mut out| async move {
+        let mut rows = products.stream_all();
+        while let Some(product) = rows.try_next().await? {
+            out.write(&ProductCsvRow::from(product)).await?;
+        }
+        Ok(out)
+    })
+}
+
+// Import products from CSV. Rows matching an existing SKU (or id) are updated,
+// everything else is inserted. Per-row failures are reported, not fatal.
+// New rows that look like existing products are skipped unless ?force=true.
+// The file is read first, then checked with a handful of queries for the whole file: one read of
+// the products it matches, one duplicate check, one slug lookup. Updates and inserts then run in
+// chunks (see bulk_insert); the rows of a chunk that fails are retried one at a time so each
+// error is reported on its own line
+async fn import_products_csv(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ForceQuery>,
+    body: String,
+) -> Result<Json<ImportSummary>, (StatusCode, String)> {
+    let mut reader = csv::Reader::from_reader(body.as_bytes());
+    let mut summary = ImportSummary { created: 0, updated: 0, errors: Vec::new(), skipped_duplicates: Vec::new() };
+    let mut rows: Vec<(usize, Option<i32>, ProductInput)> = Vec::new(); // CSV line, id column and product of each valid row
+    let mut sku_lines: HashMap<String, usize> = HashMap::new(); // CSV line each SKU first appeared on
+
+    for (index, result) in reader.deserialize::<ProductCsvRow>().enumerate() {
+        let line = index + 2; // header is line 1
+        let row = match result {
+            Ok(row) => row,
+            Err(e) => {
+                summary.errors.push(format!("Line {}: {}", line, e));
+                continue;
+            }
+        };
+        // Checked per row, so a bad row can't fail the chunk it would be saved with
+        if row.inventory < 0 {
+            summary.errors.push(format!("Line {}: inventory can't be negative", line));
+            continue;
+        }
+        let id = row.id;
+        let input = row.into_input();
+
+        // Each SKU once per file, so a later line can't silently overwrite an earlier one
+        if let Some(sku) = &input.sku {
+            if let Some(first_line) = sku_lines.get(sku) {
+                summary.errors.push(format!("Line {}: SKU {} is already on line {}", line, sku, first_line));
+                continue;
+            }
+            sku_lines.insert(sku.clone(), line);
+        }
+        rows.push((line, id, input));
+    }
+
+    // The saved product each row updates, matched on SKU and then id
+    let skus: Vec<String> = sku_lines.into_keys().collect();
+    let ids: Vec<i32> = rows.iter().filter_map(|(_, id, _)| *id).collect();
+    let existing = app_state.repos.products.find_by_skus_or_ids(&skus, &ids).await.map_err(db_error)?;
+    let by_sku: HashMap<&str, &Product> = existing.iter().filter_map(|p| Some((p.sku.as_deref()?, p))).collect();
+    let by_id: HashMap<i32, &Product> = existing.iter().map(|p| (p.id, p)).collect();
+    let matched: Vec<Option<&Product>> = rows
+        .iter()
+        .map(|(_, id, input)| {
+            let by_sku = input.sku.as_deref().and_then(|sku| by_sku.get(sku));
+            by_sku.or_else(|| id.and_then(|id| by_id.get(&id))).copied()
+        })
+        .collect();
+
+    // New rows that look like a saved product, or like a new one on an earlier line that's kept
+    let mut kept = vec![true; rows.len()];
+    if !query.force {
+        let new_rows: Vec<usize> = (0..rows.len()).filter(|&i| matched[i].is_none()).collect();
+        let named: Vec<(&str, Option<&str>)> =
+            new_rows.iter().map(|&i| (rows[i].2.name.as_str(), rows[i].2.sku.as_deref())).collect();
+        let duplicates = find_duplicates_of_each(&app_state.pool, &named).await.map_err(db_error)?;
+        let pending = find_pending_matches(&app_state.pool, &named).await.map_err(db_error)?;
+        for (position, (&row, duplicates)) in new_rows.iter().zip(duplicates).enumerate() {
+            let (line, _, input) = &rows[row];
+            if !duplicates.is_empty() {
+                kept[row] = false;
+                summary.skipped_duplicates.push(ImportDuplicate { line: *line, name: input.name.clone(), duplicates, earlier_line: None });
+                continue;
+            }
+            // The most like it of the earlier new rows that are kept: same SKU first, then by name
+            let earlier = pending
+                .iter()
+                .filter(|m| m.later == position && kept[new_rows[m.earlier]])
+                .max_by(|a, b| {
+                    (a.same_sku, a.similarity)
+                        .partial_cmp(&(b.same_sku, b.similarity))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                        .then(b.earlier.cmp(&a.earlier))
+                });
+            if let Some(earlier) = earlier {
+                kept[row] = false;
+                let earlier_line = Some(rows[new_rows[earlier.earlier]].0);
+                summary.skipped_duplicates.push(ImportDuplicate { line: *line, name: input.name.clone(), duplicates: Vec::new(), earlier_line });
+            }
+        }
+    }
+
+    let requests: Vec<product_slugs::SlugRequest> = (0..rows.len())
+        .filter(|&i| kept[i])
+        .map(|i| product_slugs::SlugRequest {
+            name: &rows[i].2.name,
+            saved: matched[i].map(|p| (p.id, p.name.as_str(), p.slug.as_str())),
+        })
+        .collect();
+    let slugs = product_slugs::assign_slugs(&app_state.pool, &requests).await.map_err(db_error)?;
+
+    let mut updates: Vec<ProductUpdate> = Vec::new();
+    let mut update_lines: Vec<usize> = Vec::new();
+    let mut product_lines: HashMap<i32, usize> = HashMap::new(); // CSV line each saved product is updated from
+    let mut replaced: HashMap<i32, (usize, AssignedSlug)> = HashMap::new(); // Renamed products' old slugs, by id
+    let mut previous: HashMap<i32, (i32, f64)> = HashMap::new(); // Inventory and price before the import, by id
+    let mut new_products: Vec<NewProduct> = Vec::new();
+    let mut new_lines: Vec<usize> = Vec::new(); // CSV line of each new product
+    let kept_rows = rows.into_iter().zip(matched).zip(kept).filter(|(_, kept)| *kept).map(|(row, _)| row);
+    for (((line, _, input), matched), assigned) in kept_rows.zip(slugs) {
+        match matched {
+            Some(product) => {
+                // One statement can't update a product twice, so the same goes for products as for SKUs
+                if let Some(first_line) = product_lines.get(&product.id) {
+                    summary.errors.push(format!("Line {}: product {} is already on line {}", line, product.id, first_line));
+                    continue;
+                }
+                product_lines.insert(product.id, line);
+                previous.insert(product.id, (product.inventory, product.price));
+                let slug = assigned.slug.clone();
+                if assigned.replaced.is_some() {
+                    replaced.insert(product.id, (line, assigned));
+                }
+                updates.push(ProductUpdate { id: product.id, input, slug });
+                update_lines.push(line);
+            }
+            None => {
+                new_products.push(NewProduct { input, slug: assigned.slug });
+                new_lines.push(line);
+            }
+        }
+    }
+
+    let products = &*app_state.repos.products;
+    let updated = bulk_insert::in_chunks(&updates, bulk_insert::DEFAULT_CHUNK_SIZE, |chunk| products.update_many(chunk)).await;
+    let renamed: Vec<(i32, &AssignedSlug)> =
+        updated.inserted.iter().filter_map(|p| replaced.get(&p.id).map(|(_, assigned)| (p.id, assigned))).collect();
+    if let Err(e) = product_slugs::keep_old_slugs(&app_state.pool, &renamed).await {
+        let message = db_error(e).1;
+        for (id, _) in &renamed {
+            summary.errors.push(format!("Line {}: {}", replaced[id].0, message));
+        }
+    }
+    for product in &updated.inserted {
+        summary.updated += 1;
+        app_state.events.publish(DomainEvent::ProductUpdated { product_id: product.id });
+        if let Some(&(inventory, price)) = previous.get(&product.id) {
+            publish_stock_events(&app_state.events, product, Some(inventory));
+            pricing::publish_price_drop(&app_state.events, product.id, &product.name, price, product.price);
+        }
+    }
+    for failure in updated.failed {
+        summary.errors.push(format!("Line {}: {}", update_lines[failure.row], db_error(failure.error).1));
+    }
+
+    let inserted = bulk_insert::in_chunks(&new_products, bulk_insert::DEFAULT_CHUNK_SIZE, |chunk| products.create_many(chunk)).await;
+    for product in inserted.inserted {
+        summary.created += 1;
+        app_state.events.publish(DomainEvent::ProductCreated { product_id: product.id });
+    }
+    for failure in inserted.failed {
+        summary.errors.push(format!("Line {}: {}", new_lines[failure.row], db_error(failure.error).1));
+    }
+
+    Ok(Json(summary))
+}
+
+// Inventory level at or below which a product counts as low on stock
+fn low_stock_threshold() -> i32 {
+    app_config::get().limits.low_stock_threshold
+}
+
+// Publish back-in-stock / low-stock events when an inventory change crosses a boundary
+pub fn publish_stock_events(events: &EventBus, product: &Product, previous_inventory: Option<i32>) {
+    for event in stock_events(product, previous_inventory) {
+        events.publish(event);
+    }
+}
+
+// The back-in-stock / low-stock events an inventory change calls for
+pub fn stock_events(product: &Product, previous_inventory: Option<i32>) -> Vec<DomainEvent> {
+    let mut events = Vec::new();
+    let Some(previous) = previous_inventory else {
+        return events;
+    };
+    if previous == 0 && product.inventory > 0 {
+        events.push(DomainEvent::ProductBackInStock {
+            product_id: product.id,
+            product_name: product.name.clone(),
+            inventory: product.inventory,
+        });
+    }
+    let threshold = low_stock_threshold();
+    if previous > threshold && product.inventory <= threshold {
+        events.push(DomainEvent::LowStock {
+            product_id: product.id,
+            product_name: product.name.clone(),
+            inventory: product.inventory,
+            threshold,
+        });
+    }
+    events
+}
+
+// Trim optional text input (SKU, barcode, brand, ...) and treat blanks as absent
+fn normalize_code(code: Option<String>) -> Option<String> {
+    code.map(|c| c.trim().to_string()).filter(|c| !c.is_empty())
+}
+
+// Upper-case ISO country codes, treating blanks as absent
+fn normalize_country(code: Option<String>) -> Option<String> {
+    normalize_code(code).map(|c| c.to_uppercase())
+}
+
+// Inventory counts units on hand; checkouts and fulfillments never take it below zero
+fn check_inventory(input: &ProductInput) -> Result<(), (StatusCode, String)> {
+    if input.inventory < 0 {
+        return Err((StatusCode::BAD_REQUEST, "inventory can't be negative".to_string()));
+    }
+    Ok(())
+}
+
+// Map database errors, surfacing unique constraint violations as 409 Conflict
+fn db_error(e: sqlx::Error) -> (StatusCode, String) {
+    if let Some(db_err) = e.as_database_error() {
+        if db_err.code().as_deref() == Some("23505") {
+            return (
+                StatusCode::CONFLICT,
+                format!("Duplicate value violates {}", db_err.constraint().unwrap_or("unique constraint")),
+            );
+        }
+    }
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e))
 }
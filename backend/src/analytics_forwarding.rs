@@ -0,0 +1,367 @@
+// Analytics Forwarding Module - Server-side purchase and refund tracking
+// Paid and refunded orders are sent from the backend to GA4's Measurement Protocol or to a generic
+// pixel endpoint through the job queue, so conversions are counted even when an ad blocker stops
+// the browser tag. Settings are one row edited by admins. The checkout's GA client id (the _ga
+// cookie, or ga_client_id in the payment intent request) ties the hit to the shopper's session
+
+use async_trait::async_trait;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::jobs;
+use crate::repos::{order_items, order_query};
+use crate::request_id::WithRequestId;
+use crate::webhooks::{Order, OrderItem};
+use crate::AppState;
+
+pub const ANALYTICS_EVENT_JOB: &str = "analytics_event";
+const GA4_COLLECT_URL: &str = "https://www.google-analytics.com/mp/collect";
+const GA_COOKIE: &str = "_ga";
+const MAX_CLIENT_ID_LEN: usize = 100; // Column width
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+const PROVIDERS: [&str; 2] = ["ga4", "pixel"];
+
+// Database model for the analytics_forwarding row; the GA4 API secret stays server-side
+#[derive(Debug, Clone, Serialize)]
+pub struct ForwardingSettings {
+    pub enabled: bool,
+    pub provider: String,
+    pub ga4_measurement_id: Option<String>,
+    #[serde(skip_serializing)]
+    pub ga4_api_secret: Option<String>,
+    pub pixel_url: Option<String>,
+    pub forward_test_orders: bool,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct SettingsResponse {
+    #[serde(flatten)]
+    pub settings: ForwardingSettings,
+    pub ga4_api_secret_set: bool,
+}
+
+#[derive(Deserialize)]
+pub struct SettingsInput {
+    pub enabled: bool,
+    pub provider: String,
+    pub ga4_measurement_id: Option<String>,
+    pub ga4_api_secret: Option<String>, // Omit to keep the current secret
+    pub pixel_url: Option<String>,
+    #[serde(default)]
+    pub forward_test_orders: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AnalyticsEventPayload {
+    event: String, // "purchase" or "refund"
+    order_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    amount: Option<i64>, // Refunded amount, in cents
+}
+
+// Current settings; None until an admin saves them
+pub async fn load_settings(pool: &sqlx::PgPool) -> Result<Option<ForwardingSettings>, sqlx::Error> {
+    sqlx::query_as!(
+        ForwardingSettings,
+        "SELECT enabled, provider, ga4_measurement_id, ga4_api_secret, pixel_url, forward_test_orders, updated_at
+         FROM analytics_forwarding WHERE id = 1",
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// GA client id from a _ga cookie value: "GA1.1.1234567890.1700000000" becomes "1234567890.1700000000"
+fn client_id_from_ga_cookie(value: &str) -> Option<String> {
+    let parts: Vec<&str> = value.trim().split('.').collect();
+    (parts.len() >= 4 && parts[0].starts_with("GA")).then(|| parts[parts.len() - 2..].join("."))
+}
+
+// The checkout's GA client id: sent by the storefront, or read from the _ga cookie
+pub fn client_id(sent: Option<&str>, headers: &HeaderMap) -> Option<String> {
+    sent.map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .or_else(|| {
+            headers
+                .get_all(header::COOKIE)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(';'))
+                .filter_map(|pair| pair.trim().split_once('='))
+                .find(|(name, _)| *name == GA_COOKIE)
+                .and_then(|(_, value)| client_id_from_ga_cookie(value))
+        })
+        .filter(|id| id.len() <= MAX_CLIENT_ID_LEN)
+}
+
+pub async fn set_client_id(executor: impl sqlx::PgExecutor<'_>, cart_id: Uuid, client_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!("UPDATE checkout_carts SET ga_client_id = $1 WHERE id = $2", client_id, cart_id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+fn major_units(cents: i64) -> f64 {
+    cents as f64 / 100.0
+}
+
+// The event's GA4 name and parameters, shared by both providers
+fn event_params(event: &AnalyticsEventPayload, order: &Order, items: &[OrderItem]) -> serde_json::Value {
+    let currency = order.currency.to_uppercase();
+    if event.event == "refund" {
+        return json!({
+            "transaction_id": order.id,
+            "value": major_units(event.amount.unwrap_or(order.total_amount)),
+            "currency": currency,
+        });
+    }
+    let items: Vec<serde_json::Value> = items
+        .iter()
+        .map(|item| {
+            json!({
+                "item_id": item.product_id.map(|id| id.to_string()).unwrap_or_default(),
+                "item_name": item.product_name,
+                "price": major_units(item.unit_price),
+                "quantity": item.quantity,
+            })
+        })
+        .collect();
+    json!({
+        "transaction_id": order.id,
+        "value": major_units(order.total_amount),
+        "currency": currency,
+        "items": items,
+    })
+}
+
+async fn send(request: reqwest::RequestBuilder, target: &str) -> Result<(), String> {
+    let response = request
+        .with_request_id()
+        .send()
+        .await
+        .map_err(|e| format!("{} request failed: {}", target, e))?;
+    if !response.status().is_success() {
+        return Err(format!("{} returned {}", target, response.status()));
+    }
+    Ok(())
+}
+
+// Job handler: forward one purchase or refund with the settings in effect now. Events queued
+// before forwarding was turned off, or for test orders, are dropped
+pub async fn run_analytics_event(pool: &sqlx::PgPool, payload: &serde_json::Value) -> Result<(), String> {
+    let event: AnalyticsEventPayload =
+        serde_json::from_value(payload.clone()).map_err(|e| format!("Invalid analytics event payload: {}", e))?;
+    let db_err = |e: sqlx::Error| format!("DB error: {}", e);
+    let Some(settings) = load_settings(pool).await.map_err(db_err)?.filter(|s| s.enabled) else {
+        return Ok(());
+    };
+    let order = order_query!("SELECT", "FROM orders WHERE id = $1", event.order_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(db_err)?
+        .ok_or_else(|| format!("Order {} not found", event.order_id))?;
+    if !order.livemode && !settings.forward_test_orders {
+        return Ok(());
+    }
+    let items = order_items(pool, order.id).await.map_err(db_err)?;
+    // Orders without a browser session (e.g. manual orders) get a stable id of their own
+    let ga_client_id = sqlx::query_scalar!("SELECT ga_client_id FROM checkout_carts WHERE id = $1", order.cart_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(db_err)?
+        .flatten();
+    let client_id = ga_client_id.unwrap_or_else(|| order.id.to_string());
+    let params = event_params(&event, &order, &items);
+    let occurred_at = if event.event == "purchase" { order.created_at } else { Utc::now() };
+
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| format!("HTTP client error: {}", e))?;
+    match settings.provider.as_str() {
+        "ga4" => {
+            let (Some(measurement_id), Some(api_secret)) = (&settings.ga4_measurement_id, &settings.ga4_api_secret) else {
+                return Err("GA4 measurement id or API secret not configured".to_string());
+            };
+            let mut body = json!({
+                "client_id": client_id,
+                "timestamp_micros": occurred_at.timestamp_micros(),
+                "events": [{ "name": event.event, "params": params }],
+            });
+            if let Some(customer_id) = order.customer_id {
+                body["user_id"] = json!(customer_id);
+            }
+            let request = client
+                .post(GA4_COLLECT_URL)
+                .query(&[("measurement_id", measurement_id), ("api_secret", api_secret)])
+                .json(&body);
+            send(request, "GA4").await?;
+        }
+        _ => {
+            let url = settings.pixel_url.as_ref().ok_or("Pixel URL not configured")?;
+            let body = json!({
+                "event": event.event,
+                "client_id": client_id,
+                "user_id": order.customer_id,
+                "livemode": order.livemode,
+                "occurred_at": occurred_at,
+                "params": params,
+            });
+            send(client.post(url).json(&body), "Pixel").await?;
+        }
+    }
+    tracing::info!("Forwarded {} of order {} to {}", event.event, order.id, settings.provider);
+    Ok(())
+}
+
+// Queues a forwarding job for every purchase and refund while forwarding is on
+pub struct AnalyticsForwardingSubscriber {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl AnalyticsForwardingSubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for AnalyticsForwardingSubscriber {
+    fn name(&self) -> &'static str {
+        "analytics_forwarding"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let payload = match event {
+            DomainEvent::OrderCreated { order_id, .. } => AnalyticsEventPayload {
+                event: "purchase".to_string(),
+                order_id: *order_id,
+                amount: None,
+            },
+            DomainEvent::OrderRefunded { order_id, amount_refunded, .. } => AnalyticsEventPayload {
+                event: "refund".to_string(),
+                order_id: *order_id,
+                amount: Some(*amount_refunded),
+            },
+            _ => return Ok(()),
+        };
+        let enabled = load_settings(&self.pool)
+            .await
+            .map_err(|e| format!("DB error: {}", e))?
+            .is_some_and(|s| s.enabled);
+        if !enabled {
+            return Ok(());
+        }
+        let order_id = payload.order_id;
+        let payload = serde_json::to_value(&payload).map_err(|e| e.to_string())?;
+        jobs::enqueue(&self.pool, ANALYTICS_EVENT_JOB, payload, Utc::now())
+            .await
+            .map_err(|e| format!("Failed to queue analytics event for order {}: {}", order_id, e))?;
+        Ok(())
+    }
+}
+
+pub fn analytics_forwarding_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/analytics-forwarding", get(get_settings).put(update_settings))
+        .with_state(app_state)
+}
+
+fn optional(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+fn settings_response(settings: ForwardingSettings) -> SettingsResponse {
+    SettingsResponse {
+        ga4_api_secret_set: settings.ga4_api_secret.is_some(),
+        settings,
+    }
+}
+
+async fn get_settings(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<SettingsResponse>, (StatusCode, String)> {
+    let settings = load_settings(&app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .unwrap_or(ForwardingSettings {
+            enabled: false,
+            provider: PROVIDERS[0].to_string(),
+            ga4_measurement_id: None,
+            ga4_api_secret: None,
+            pixel_url: None,
+            forward_test_orders: false,
+            updated_at: None,
+        });
+    Ok(Json(settings_response(settings)))
+}
+
+// Forwarding can only be enabled once the chosen provider has everything it needs
+async fn update_settings(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<SettingsInput>,
+) -> Result<Json<SettingsResponse>, (StatusCode, String)> {
+    let provider = input.provider.trim().to_lowercase();
+    if !PROVIDERS.contains(&provider.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("provider must be one of {}", PROVIDERS.join(", "))));
+    }
+    let measurement_id = optional(input.ga4_measurement_id);
+    let pixel_url = optional(input.pixel_url);
+    if pixel_url.as_deref().is_some_and(|url| !url.starts_with("https://") && !url.starts_with("http://")) {
+        return Err((StatusCode::BAD_REQUEST, "pixel_url must start with https:// or http://".to_string()));
+    }
+    let current = load_settings(&app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let api_secret = optional(input.ga4_api_secret).or_else(|| current.and_then(|c| c.ga4_api_secret));
+    if input.enabled {
+        let missing = match provider.as_str() {
+            "ga4" if measurement_id.is_none() || api_secret.is_none() => Some("ga4_measurement_id and ga4_api_secret"),
+            "pixel" if pixel_url.is_none() => Some("pixel_url"),
+            _ => None,
+        };
+        if let Some(missing) = missing {
+            return Err((StatusCode::BAD_REQUEST, format!("{} required to enable {} forwarding", missing, provider)));
+        }
+    }
+
+    let settings = sqlx::query_as!(
+        ForwardingSettings,
+        "INSERT INTO analytics_forwarding (id, enabled, provider, ga4_measurement_id, ga4_api_secret, pixel_url, forward_test_orders)
+         VALUES (1, $1, $2, $3, $4, $5, $6)
+         ON CONFLICT (id) DO UPDATE SET
+             enabled = EXCLUDED.enabled,
+             provider = EXCLUDED.provider,
+             ga4_measurement_id = EXCLUDED.ga4_measurement_id,
+             ga4_api_secret = EXCLUDED.ga4_api_secret,
+             pixel_url = EXCLUDED.pixel_url,
+             forward_test_orders = EXCLUDED.forward_test_orders,
+             updated_at = NOW()
+         RETURNING enabled, provider, ga4_measurement_id, ga4_api_secret, pixel_url, forward_test_orders, updated_at",
+        input.enabled,
+        provider,
+        measurement_id,
+        api_secret,
+        pixel_url,
+        input.forward_test_orders,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    Ok(Json(settings_response(settings)))
+}
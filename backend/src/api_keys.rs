@@ -0,0 +1,286 @@
+// API Keys Module - Scoped keys for machine-to-machine access
+// Admins issue keys limited to a set of scopes; integration endpoints authenticate them from
+// X-API-Key, enforce a per-key requests-per-minute limit, and log every request for auditing
+
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRequestParts, Path, State},
+    http::{request::Parts, StatusCode},
+    routing::{delete, get},
+    Json, Router,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::AppState;
+
+pub const SCOPE_READ_PRODUCTS: &str = "read:products";
+pub const SCOPE_WRITE_INVENTORY: &str = "write:inventory";
+pub const SCOPE_WRITE_PRICES: &str = "write:prices";
+pub const SCOPE_READ_ORDERS: &str = "read:orders";
+pub const SCOPE_READ_CUSTOMERS: &str = "read:customers";
+pub const SCOPES: [&str; 5] = [
+    SCOPE_READ_PRODUCTS,
+    SCOPE_WRITE_INVENTORY,
+    SCOPE_WRITE_PRICES,
+    SCOPE_READ_ORDERS,
+    SCOPE_READ_CUSTOMERS,
+];
+
+const KEY_PREFIX: &str = "rk_";
+const DISPLAY_PREFIX_LEN: usize = 11; // "rk_" plus 8 characters
+const DEFAULT_RATE_LIMIT_PER_MINUTE: i32 = 60;
+const MAX_RATE_LIMIT_PER_MINUTE: i32 = 10_000;
+const USAGE_LOG_LIMIT: i64 = 100;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+// Database model for API keys; the key's hash is only ever matched in SQL, never loaded
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub rate_limit_per_minute: i32,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+// Returned once on creation; only the hash is kept afterwards
+#[derive(Serialize)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyInput {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub rate_limit_per_minute: Option<i32>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ApiKeyUsage {
+    pub id: i64,
+    pub method: String,
+    pub path: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+fn generate_key() -> String {
+    let bytes: [u8; 24] = rand::thread_rng().gen();
+    format!("{}{}", KEY_PREFIX, hex::encode(bytes))
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// Fixed one-minute windows per key, counted in memory
+#[derive(Default)]
+pub struct ApiKeyRateLimiter {
+    windows: Mutex<HashMap<i32, (Instant, i32)>>,
+}
+
+impl ApiKeyRateLimiter {
+    // Count a request against the key; false once the window's limit is used up
+    fn allow(&self, key_id: i32, limit: i32) -> bool {
+        let mut windows = self.windows.lock().unwrap();
+        windows.retain(|_, (started, _)| started.elapsed() < RATE_WINDOW);
+        let (_, count) = windows.entry(key_id).or_insert((Instant::now(), 0));
+        if *count >= limit {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+}
+
+// A request authenticated with X-API-Key; handlers check the scope they need with `require`
+pub struct ApiKeyAuth {
+    scopes: Vec<String>,
+}
+
+impl ApiKeyAuth {
+    pub fn require(&self, scope: &str) -> Result<(), (StatusCode, String)> {
+        if self.scopes.iter().any(|s| s == scope) {
+            Ok(())
+        } else {
+            Err((StatusCode::FORBIDDEN, format!("API key is missing the {} scope", scope)))
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for ApiKeyAuth {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let provided = parts
+            .headers
+            .get("x-api-key")
+            .and_then(|value| value.to_str().ok())
+            .ok_or((StatusCode::UNAUTHORIZED, "Missing X-API-Key header".to_string()))?;
+
+        let key = sqlx::query_as!(
+            ApiKey,
+            "SELECT id, name, key_prefix, scopes, rate_limit_per_minute, last_used_at, revoked_at, created_at FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL",
+            hash_key(provided),
+        )
+        .fetch_optional(&*state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+        let Some(key) = key else {
+            // The single shared key from before scoped keys existed keeps full access
+            let legacy = app_config::get().auth.integration_api_key.as_ref();
+            if legacy.is_some_and(|legacy| constant_time_eq(provided.as_bytes(), legacy.expose().as_bytes())) {
+                return Ok(ApiKeyAuth {
+                    scopes: SCOPES.iter().map(|s| s.to_string()).collect(),
+                });
+            }
+            return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
+        };
+
+        if !state.api_key_limiter.allow(key.id, key.rate_limit_per_minute) {
+            return Err((StatusCode::TOO_MANY_REQUESTS, "API key rate limit exceeded".to_string()));
+        }
+
+        // Usage logging must never fail the request itself
+        let logged = sqlx::query!(
+            "WITH touched AS (UPDATE api_keys SET last_used_at = NOW() WHERE id = $1)
+             INSERT INTO api_key_usage (api_key_id, method, path) VALUES ($1, $2, $3)",
+            key.id,
+            parts.method.as_str(),
+            parts.uri.path(),
+        )
+        .execute(&*state.pool)
+        .await;
+        if let Err(e) = logged {
+            tracing::error!("Failed to log usage for API key {}: {}", key.id, e);
+        }
+
+        Ok(ApiKeyAuth { scopes: key.scopes })
+    }
+}
+
+pub fn api_key_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/api-keys", get(list_api_keys).post(create_api_key))
+        .route("/api/admin/api-keys/:id", delete(revoke_api_key))
+        .route("/api/admin/api-keys/:id/usage", get(list_usage))
+        .with_state(app_state)
+}
+
+async fn list_api_keys(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ApiKey>>, (StatusCode, String)> {
+    let keys = sqlx::query_as!(ApiKey, "SELECT id, name, key_prefix, scopes, rate_limit_per_minute, last_used_at, revoked_at, created_at FROM api_keys ORDER BY id")
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(keys))
+}
+
+async fn create_api_key(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<CreateApiKeyInput>,
+) -> Result<(StatusCode, Json<CreatedApiKey>), (StatusCode, String)> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "name is required".to_string()));
+    }
+    let mut scopes: Vec<String> = Vec::new();
+    for scope in &input.scopes {
+        let scope = scope.trim();
+        if !SCOPES.contains(&scope) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Unknown scope '{}'; expected one of {}", scope, SCOPES.join(", ")),
+            ));
+        }
+        if !scopes.iter().any(|s| s == scope) {
+            scopes.push(scope.to_string());
+        }
+    }
+    if scopes.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "scopes must not be empty".to_string()));
+    }
+    let rate_limit = input.rate_limit_per_minute.unwrap_or(DEFAULT_RATE_LIMIT_PER_MINUTE);
+    if !(1..=MAX_RATE_LIMIT_PER_MINUTE).contains(&rate_limit) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("rate_limit_per_minute must be between 1 and {}", MAX_RATE_LIMIT_PER_MINUTE),
+        ));
+    }
+
+    let key = generate_key();
+    let api_key = sqlx::query_as!(
+        ApiKey,
+        "INSERT INTO api_keys (name, key_prefix, key_hash, scopes, rate_limit_per_minute)
+         VALUES ($1, $2, $3, $4, $5) RETURNING id, name, key_prefix, scopes, rate_limit_per_minute, last_used_at, revoked_at, created_at",
+        name,
+        &key[..DISPLAY_PREFIX_LEN],
+        hash_key(&key),
+        &scopes,
+        rate_limit,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(CreatedApiKey { api_key, key })))
+}
+
+// Revoked keys stay listed, with their usage, but stop authenticating
+async fn revoke_api_key(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("UPDATE api_keys SET revoked_at = COALESCE(revoked_at, NOW()) WHERE id = $1", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "API key not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Most recent requests first
+async fn list_usage(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<ApiKeyUsage>>, (StatusCode, String)> {
+    let usage = sqlx::query_as!(
+        ApiKeyUsage,
+        "SELECT id, method, path, created_at FROM api_key_usage
+         WHERE api_key_id = $1
+         ORDER BY created_at DESC, id DESC
+         LIMIT $2",
+        id,
+        USAGE_LOG_LIMIT,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(usage))
+}
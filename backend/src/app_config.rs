@@ -0,0 +1,1145 @@
+// App Config Module - Typed application settings loaded once at startup
+// Every environment variable the backend reads is declared here, grouped by area. Startup
+// fails with a list of every missing or invalid variable instead of falling back silently,
+// and admins can inspect the effective settings (secrets redacted) at /api/admin/config
+
+use axum::{extract::State, routing, Json, Router};
+use config::{Config, ConfigError, Environment};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::load_profile;
+use crate::AppState;
+
+static CONFIG: OnceLock<AppConfig> = OnceLock::new();
+
+// A credential: usable in code, but never serialized or logged
+#[derive(Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str("********")
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Secret(********)")
+    }
+}
+
+// Comma separated values, e.g. CORS_ALLOWED_ORIGINS=https://a.com,https://b.com
+fn comma_list<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    Ok(value
+        .split(',')
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DatabaseSettings {
+    #[serde(rename(deserialize = "database_url"))]
+    pub url: Secret, // Required
+    #[serde(rename(deserialize = "database_max_connections"))]
+    pub max_connections: u32,
+    #[serde(rename(deserialize = "database_connect_attempts"))]
+    pub connect_attempts: u32, // At startup, before giving up on Postgres
+    #[serde(rename(deserialize = "database_acquire_timeout_secs"))]
+    pub acquire_timeout_secs: u64, // A query waiting longer for a connection fails
+    #[serde(rename(deserialize = "database_slow_acquire_ms"))]
+    pub slow_acquire_ms: u64,
+    #[serde(rename(deserialize = "database_health_check_interval_secs"))]
+    pub health_check_interval_secs: u64,
+    #[serde(rename(deserialize = "database_breaker_failures"))]
+    pub breaker_failures: u32, // Failed health checks in a row that mark the database down
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        Self {
+            url: Secret::default(),
+            max_connections: 5,
+            connect_attempts: 10,
+            acquire_timeout_secs: 5,
+            slow_acquire_ms: 250,
+            health_check_interval_secs: 5,
+            breaker_failures: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerSettings {
+    #[serde(rename(deserialize = "port"))]
+    pub port: u16,
+    #[serde(rename(deserialize = "cors_allowed_origins"), deserialize_with = "comma_list")]
+    pub cors_allowed_origins: Vec<String>, // Empty = any origin
+    #[serde(rename(deserialize = "public_api_url"))]
+    pub public_api_url: String,
+    #[serde(rename(deserialize = "storefront_url"))]
+    pub storefront_url: String,
+    #[serde(rename(deserialize = "environment_mode"))]
+    pub environment_mode: Option<String>, // "live" or "test"; unset = whatever STRIPE_SECRET_KEY is
+    #[serde(rename(deserialize = "static_dir"))]
+    pub static_dir: Option<String>, // Built storefront (trunk's dist) to serve; unset = API only
+    #[serde(rename(deserialize = "tls_cert_path"))]
+    pub tls_cert_path: Option<String>, // PEM certificate chain; with the key, serves HTTPS
+    #[serde(rename(deserialize = "tls_key_path"))]
+    pub tls_key_path: Option<String>,
+    #[serde(rename(deserialize = "hostname"))]
+    pub hostname: String, // Names this replica in the scheduler lease and run history
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            port: 3000,
+            cors_allowed_origins: Vec::new(),
+            public_api_url: "http://localhost:3000".to_string(),
+            storefront_url: "http://localhost:8080".to_string(),
+            environment_mode: None,
+            static_dir: None,
+            tls_cert_path: None,
+            tls_key_path: None,
+            hostname: "backend".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthSettings {
+    #[serde(rename(deserialize = "jwt_secret"))]
+    pub jwt_secret: Secret,
+    #[serde(rename(deserialize = "review_token_secret"))]
+    pub review_token_secret: Option<Secret>, // Falls back to jwt_secret
+    #[serde(rename(deserialize = "integration_api_key"))]
+    pub integration_api_key: Option<Secret>,
+    #[serde(rename(deserialize = "pii_encryption_key"))]
+    pub pii_encryption_key: Option<Secret>, // Base64 of 32 random bytes; PII is stored in plain text without it
+    #[serde(rename(deserialize = "pii_scrub_allowed_fields"), deserialize_with = "comma_list")]
+    pub pii_scrub_allowed_fields: Vec<String>, // Kept as is in logs and stored webhook payloads
+}
+
+impl Default for AuthSettings {
+    fn default() -> Self {
+        Self {
+            jwt_secret: Secret("supersecretjwtkey".to_string()),
+            review_token_secret: None,
+            integration_api_key: None,
+            pii_encryption_key: None,
+            pii_scrub_allowed_fields: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StripeSettings {
+    #[serde(rename(deserialize = "stripe_secret_key"))]
+    pub secret_key: Secret, // Required
+    #[serde(rename(deserialize = "stripe_webhook_secret"))]
+    pub webhook_secret: Secret,
+}
+
+impl StripeSettings {
+    // Live secret and restricted keys start sk_live_ / rk_live_
+    pub fn is_live_key(&self) -> bool {
+        let key = self.secret_key.expose();
+        key.starts_with("sk_live_") || key.starts_with("rk_live_")
+    }
+}
+
+impl Default for StripeSettings {
+    fn default() -> Self {
+        Self {
+            secret_key: Secret::default(),
+            webhook_secret: Secret("whsec_test_secret".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SquareSettings {
+    #[serde(rename(deserialize = "square_access_token"))]
+    pub access_token: Option<Secret>,
+    #[serde(rename(deserialize = "square_application_id"))]
+    pub application_id: Option<String>,
+    #[serde(rename(deserialize = "square_environment"))]
+    pub environment: String, // "sandbox" or "production"
+    #[serde(rename(deserialize = "square_location_id"))]
+    pub location_id: String,
+    #[serde(rename(deserialize = "square_webhook_signature_key"))]
+    pub webhook_signature_key: Secret,
+    #[serde(rename(deserialize = "square_webhook_url"))]
+    pub webhook_url: String,
+}
+
+impl Default for SquareSettings {
+    fn default() -> Self {
+        Self {
+            access_token: None,
+            application_id: None,
+            environment: "sandbox".to_string(),
+            location_id: "LP7V5561FPK0B".to_string(),
+            webhook_signature_key: Secret("your_webhook_signature_key".to_string()),
+            webhook_url: "https://your-domain.com/api/webhooks/square".to_string(),
+        }
+    }
+}
+
+// SMTP for transactional email; all of host, port, username, password and from_email or none
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SmtpSettings {
+    #[serde(rename(deserialize = "smtp_host"))]
+    pub host: Option<String>,
+    #[serde(rename(deserialize = "smtp_port"))]
+    pub port: Option<u16>,
+    #[serde(rename(deserialize = "smtp_username"))]
+    pub username: Option<String>,
+    #[serde(rename(deserialize = "smtp_password"))]
+    pub password: Option<Secret>,
+    #[serde(rename(deserialize = "from_email"))]
+    pub from_email: Option<String>,
+    #[serde(rename(deserialize = "from_name"))]
+    pub from_name: String, // Also the store name in feeds
+}
+
+impl Default for SmtpSettings {
+    fn default() -> Self {
+        Self {
+            host: None,
+            port: None,
+            username: None,
+            password: None,
+            from_email: None,
+            from_name: "R-Com Store".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BrevoSettings {
+    #[serde(rename(deserialize = "brevo_api_key"))]
+    pub api_key: Option<Secret>,
+    #[serde(rename(deserialize = "brevo_api_base_url"))]
+    pub api_base_url: String,
+    #[serde(rename(deserialize = "brevo_from_email"))]
+    pub from_email: String,
+    #[serde(rename(deserialize = "brevo_from_name"))]
+    pub from_name: String,
+    #[serde(rename(deserialize = "brevo_webhook_token"))]
+    pub webhook_token: Option<Secret>,
+}
+
+impl Default for BrevoSettings {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            api_base_url: "https://api.brevo.com/v3".to_string(),
+            from_email: "noreply@yourdomain.com".to_string(),
+            from_name: "R-Com Store".to_string(),
+            webhook_token: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MailchimpSettings {
+    #[serde(rename(deserialize = "mailchimp_api_key"))]
+    pub api_key: Option<Secret>,
+    #[serde(rename(deserialize = "mailchimp_api_base_url"))]
+    pub api_base_url: Option<String>, // Defaults to the datacenter in the API key's "-us21" suffix
+    #[serde(rename(deserialize = "mailchimp_list_id"))]
+    pub list_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LetreSettings {
+    #[serde(rename(deserialize = "letre_api_key"))]
+    pub api_key: Option<Secret>,
+    #[serde(rename(deserialize = "letre_api_url"))]
+    pub api_url: String,
+}
+
+impl Default for LetreSettings {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            api_url: "https://api.letre.io".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MarketingSettings {
+    #[serde(rename(deserialize = "marketing_provider"))]
+    pub provider: String, // "brevo", "mailchimp" or "letre"
+}
+
+impl Default for MarketingSettings {
+    fn default() -> Self {
+        Self {
+            provider: "brevo".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SmsSettings {
+    #[serde(rename(deserialize = "sms_provider"))]
+    pub provider: String, // "textbelt" or "twilio"
+    #[serde(rename(deserialize = "textbelt_api_key"))]
+    pub textbelt_api_key: Option<Secret>,
+    #[serde(rename(deserialize = "textbelt_api_url"))]
+    pub textbelt_api_url: String,
+}
+
+impl Default for SmsSettings {
+    fn default() -> Self {
+        Self {
+            provider: "textbelt".to_string(),
+            textbelt_api_key: None,
+            textbelt_api_url: "https://textbelt.com/text".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TwilioSettings {
+    #[serde(rename(deserialize = "twilio_account_sid"))]
+    pub account_sid: Option<String>,
+    #[serde(rename(deserialize = "twilio_auth_token"))]
+    pub auth_token: Option<Secret>,
+    #[serde(rename(deserialize = "twilio_from_phone"))]
+    pub from_phone: Option<String>,
+    #[serde(rename(deserialize = "twilio_verify_service_sid"))]
+    pub verify_service_sid: Option<String>,
+    #[serde(rename(deserialize = "twilio_verify_api_url"))]
+    pub verify_api_url: String,
+}
+
+impl Default for TwilioSettings {
+    fn default() -> Self {
+        Self {
+            account_sid: None,
+            auth_token: None,
+            from_phone: None,
+            verify_service_sid: None,
+            verify_api_url: "https://verify.twilio.com/v2".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EasyPostSettings {
+    #[serde(rename(deserialize = "easypost_api_key"))]
+    pub api_key: Option<Secret>,
+    #[serde(rename(deserialize = "easypost_api_url"))]
+    pub api_url: String,
+    #[serde(rename(deserialize = "easypost_carrier_accounts"), deserialize_with = "comma_list")]
+    pub carrier_accounts: Vec<String>, // Empty = all accounts
+    #[serde(rename(deserialize = "easypost_carriers"), deserialize_with = "comma_list")]
+    pub carriers: Vec<String>, // Empty = any
+    #[serde(rename(deserialize = "easypost_services"), deserialize_with = "comma_list")]
+    pub services: Vec<String>, // Empty = any
+    #[serde(rename(deserialize = "easypost_rate_cache_ttl_secs"))]
+    pub rate_cache_ttl_secs: u64,
+    #[serde(rename(deserialize = "easypost_tracking_cache_ttl_secs"))]
+    pub tracking_cache_ttl_secs: u64,
+    #[serde(rename(deserialize = "easypost_tracking_stale_secs"))]
+    pub tracking_stale_secs: u64, // Past the TTL, how long a lookup may still be answered while it refreshes
+    #[serde(rename(deserialize = "easypost_webhook_secret"))]
+    pub webhook_secret: Option<Secret>,
+}
+
+impl Default for EasyPostSettings {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            api_url: "https://api.easypost.com/v2".to_string(),
+            carrier_accounts: Vec::new(),
+            carriers: Vec::new(),
+            services: Vec::new(),
+            rate_cache_ttl_secs: 300,
+            tracking_cache_ttl_secs: 180,
+            tracking_stale_secs: 3600,
+            webhook_secret: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShippingSettings {
+    #[serde(rename(deserialize = "shipping_rate_provider"))]
+    pub rate_provider: String, // "rules" or "easypost"
+    #[serde(rename(deserialize = "shipping_fallback_flat_rate"))]
+    pub fallback_flat_rate: f64,
+    #[serde(rename(deserialize = "shipping_home_country"))]
+    pub home_country: String,
+    #[serde(rename(deserialize = "customs_signer"))]
+    pub customs_signer: Option<String>,
+}
+
+impl Default for ShippingSettings {
+    fn default() -> Self {
+        Self {
+            rate_provider: "rules".to_string(),
+            fallback_flat_rate: 9.99,
+            home_country: "US".to_string(),
+            customs_signer: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CheckoutSettings {
+    #[serde(rename(deserialize = "tax_rate"))]
+    pub tax_rate: f64, // Sales tax on the discounted merchandise subtotal, e.g. 0.08 for 8%
+    #[serde(rename(deserialize = "payment_capture_method"))]
+    pub capture_method: String, // "automatic", or "manual" to authorize at checkout and capture on fulfillment
+    #[serde(rename(deserialize = "authorization_window_hours"))]
+    pub authorization_window_hours: u64, // Uncaptured authorizations are settled or voided after this
+    #[serde(rename(deserialize = "quote_valid_days"))]
+    pub quote_valid_days: u64, // How long a sent quote can be accepted
+    #[serde(rename(deserialize = "net_terms_days"))]
+    pub net_terms_days: u64, // Purchase order invoices are due this many days after the order
+    #[serde(rename(deserialize = "purchase_order_customer_tag"))]
+    pub purchase_order_tag: String, // Customers with this tag may check out with a purchase order
+    #[serde(rename(deserialize = "invoice_reminder_interval_days"))]
+    pub invoice_reminder_interval_days: u64, // Overdue invoice reminders repeat this often
+    #[serde(rename(deserialize = "ticket_hold_minutes"))]
+    pub ticket_hold_minutes: u64, // Event tickets in an unpaid checkout are held this long
+    #[serde(rename(deserialize = "cart_hold_minutes"))]
+    pub cart_hold_minutes: u64, // Stock reserved by an unpaid checkout goes back after this
+    #[serde(rename(deserialize = "manual_order_hold_days"))]
+    pub manual_order_hold_days: u64, // Unpaid manual and quote orders are cancelled and their stock returned after this
+}
+
+impl Default for CheckoutSettings {
+    fn default() -> Self {
+        Self {
+            tax_rate: 0.08,
+            capture_method: "automatic".to_string(),
+            authorization_window_hours: 144, // Stripe card authorizations expire after 7 days
+            quote_valid_days: 30,
+            net_terms_days: 30,
+            purchase_order_tag: "wholesale".to_string(),
+            invoice_reminder_interval_days: 7,
+            ticket_hold_minutes: 15,
+            cart_hold_minutes: 15,
+            manual_order_hold_days: 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IntegrationSettings {
+    #[serde(rename(deserialize = "erp_webhook_url"))]
+    pub erp_webhook_url: Option<String>,
+    #[serde(rename(deserialize = "erp_webhook_secret"))]
+    pub erp_webhook_secret: Option<Secret>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RewardSettings {
+    #[serde(rename(deserialize = "referral_reward_type"))]
+    pub referral_reward_type: String, // "coupon" or "store_credit"
+    #[serde(rename(deserialize = "referral_reward_amount"))]
+    pub referral_reward_amount: i64, // Cents
+    #[serde(rename(deserialize = "loyalty_points_per_dollar"))]
+    pub loyalty_points_per_dollar: i64,
+    #[serde(rename(deserialize = "loyalty_redeem_points_per_dollar"))]
+    pub loyalty_redeem_points_per_dollar: i64,
+}
+
+impl Default for RewardSettings {
+    fn default() -> Self {
+        Self {
+            referral_reward_type: "coupon".to_string(),
+            referral_reward_amount: 1000,
+            loyalty_points_per_dollar: 1,
+            loyalty_redeem_points_per_dollar: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    #[serde(rename(deserialize = "quiet_hours_start"))]
+    pub quiet_hours_start: u32,
+    #[serde(rename(deserialize = "quiet_hours_end"))]
+    pub quiet_hours_end: u32,
+    #[serde(rename(deserialize = "store_timezone"))]
+    pub store_timezone: String, // IANA name
+    #[serde(rename(deserialize = "email_batch_interval_minutes"))]
+    pub email_batch_interval_minutes: i64,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            quiet_hours_start: 21,
+            quiet_hours_end: 8,
+            store_timezone: "UTC".to_string(),
+            email_batch_interval_minutes: 15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitSettings {
+    #[serde(rename(deserialize = "sms_rate_limit_per_minute"))]
+    pub sms_per_minute: i64,
+    #[serde(rename(deserialize = "email_rate_limit_per_minute"))]
+    pub email_per_minute: i64,
+    #[serde(rename(deserialize = "low_stock_threshold"))]
+    pub low_stock_threshold: i32,
+    #[serde(rename(deserialize = "duplicate_name_similarity"))]
+    pub duplicate_name_similarity: f64, // Trigram similarity at which a new product's name counts as a likely duplicate
+    // Requests of each expensive route group handled at once; more get a 503
+    #[serde(rename(deserialize = "label_purchase_concurrency"))]
+    pub label_purchase_concurrency: usize,
+    #[serde(rename(deserialize = "csv_import_concurrency"))]
+    pub csv_import_concurrency: usize,
+    #[serde(rename(deserialize = "pdf_concurrency"))]
+    pub pdf_concurrency: usize,
+    #[serde(rename(deserialize = "report_concurrency"))]
+    pub report_concurrency: usize,
+    #[serde(rename(deserialize = "overload_retry_after_secs"))]
+    pub overload_retry_after_secs: u64,
+}
+
+impl Default for LimitSettings {
+    fn default() -> Self {
+        Self {
+            sms_per_minute: 30,
+            email_per_minute: 60,
+            low_stock_threshold: 5,
+            duplicate_name_similarity: 0.6,
+            label_purchase_concurrency: 4,
+            csv_import_concurrency: 1,
+            pdf_concurrency: 4,
+            report_concurrency: 2,
+            overload_retry_after_secs: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScheduleSettings {
+    #[serde(rename(deserialize = "alert_check_interval_secs"))]
+    pub alert_check_interval_secs: u64,
+    #[serde(rename(deserialize = "jwt_key_refresh_secs"))]
+    pub jwt_key_refresh_secs: u64, // Also how long a rotated key waits before it signs
+    #[serde(rename(deserialize = "reconciliation_sync_interval_secs"))]
+    pub reconciliation_sync_interval_secs: u64,
+    #[serde(rename(deserialize = "scheduler_tick_secs"))]
+    pub scheduler_tick_secs: u64, // How often the scheduler looks for due tasks
+    #[serde(rename(deserialize = "scheduler_lease_secs"))]
+    pub scheduler_lease_secs: u64, // How long a replica stays scheduler leader without renewing
+    #[serde(rename(deserialize = "stale_cart_days"))]
+    pub stale_cart_days: i64, // purge_stale_carts task
+    #[serde(rename(deserialize = "order_pii_retention_days"))]
+    pub order_pii_retention_days: i64, // scrub_order_pii task
+}
+
+impl Default for ScheduleSettings {
+    fn default() -> Self {
+        Self {
+            alert_check_interval_secs: 300,
+            jwt_key_refresh_secs: 60,
+            reconciliation_sync_interval_secs: 21600,
+            scheduler_tick_secs: 15,
+            scheduler_lease_secs: 60,
+            stale_cart_days: 30,
+            order_pii_retention_days: 730,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeedSettings {
+    #[serde(rename(deserialize = "feed_currency"))]
+    pub currency: String,
+}
+
+impl Default for FeedSettings {
+    fn default() -> Self {
+        Self {
+            currency: "USD".to_string(),
+        }
+    }
+}
+
+// Uploaded media (product images) and how its URLs are built
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MediaSettings {
+    #[serde(rename(deserialize = "media_dir"))]
+    pub dir: String, // Shared by every server that runs background jobs
+    #[serde(rename(deserialize = "media_base_url"))]
+    pub base_url: Option<String>, // e.g. a CDN; defaults to PUBLIC_API_URL + /media
+    #[serde(rename(deserialize = "max_image_upload_mb"))]
+    pub max_image_upload_mb: usize,
+}
+
+impl Default for MediaSettings {
+    fn default() -> Self {
+        Self {
+            dir: "media".to_string(),
+            base_url: None,
+            max_image_upload_mb: 20,
+        }
+    }
+}
+
+impl MediaSettings {
+    // Base URL that media paths are appended to, without a trailing slash
+    pub fn public_url(&self, server: &ServerSettings) -> String {
+        match &self.base_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => format!("{}/media", server.public_api_url.trim_end_matches('/')),
+        }
+    }
+}
+
+// Optional external search engine; storefront search uses Postgres full-text search without it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchSettings {
+    #[serde(rename(deserialize = "meilisearch_url"))]
+    pub meilisearch_url: Option<String>,
+    #[serde(rename(deserialize = "meilisearch_api_key"))]
+    pub meilisearch_api_key: Option<Secret>,
+    #[serde(rename(deserialize = "meilisearch_index"))]
+    pub meilisearch_index: String,
+    #[serde(rename(deserialize = "search_reindex_interval_secs"))]
+    pub reindex_interval_secs: u64, // Full re-sync, catching sale prices that ended and stock changes
+}
+
+impl Default for SearchSettings {
+    fn default() -> Self {
+        Self {
+            meilisearch_url: None,
+            meilisearch_api_key: None,
+            meilisearch_index: "products".to_string(),
+            reindex_interval_secs: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlertSettings {
+    #[serde(rename(deserialize = "alert_slack_webhook_url"))]
+    pub slack_webhook_url: Option<Secret>,
+    #[serde(rename(deserialize = "alert_email"))]
+    pub email: Option<String>,
+    #[serde(rename(deserialize = "dead_letter_alert_threshold"))]
+    pub dead_letter_threshold: i64,
+    #[serde(rename(deserialize = "webhook_failure_alert_rate"))]
+    pub webhook_failure_rate: f64,
+    #[serde(rename(deserialize = "dispute_reminder_hours"))]
+    pub dispute_reminder_hours: u64, // Reminder this long before a dispute's evidence is due
+}
+
+impl Default for AlertSettings {
+    fn default() -> Self {
+        Self {
+            slack_webhook_url: None,
+            email: None,
+            dead_letter_threshold: 10,
+            webhook_failure_rate: 0.25,
+            dispute_reminder_hours: 72,
+        }
+    }
+}
+
+// Daily summary email to the merchant (see daily_digest)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DigestSettings {
+    #[serde(rename(deserialize = "daily_digest_recipients"), deserialize_with = "comma_list")]
+    pub recipients: Vec<String>, // No digest when empty
+    #[serde(rename(deserialize = "daily_digest_hour"))]
+    pub hour: u32, // Hour in STORE_TIMEZONE after which yesterday's digest is sent
+}
+
+impl Default for DigestSettings {
+    fn default() -> Self {
+        Self {
+            recipients: Vec::new(),
+            hour: 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OpsSettings {
+    #[serde(rename(deserialize = "ops_webhook_url"))]
+    pub webhook_url: Option<Secret>,
+    #[serde(rename(deserialize = "ops_batch_interval_secs"))]
+    pub batch_interval_secs: u64,
+    #[serde(rename(deserialize = "ops_large_order_amount"))]
+    pub large_order_amount: f64, // Dollars
+    #[serde(rename(deserialize = "ops_payment_failure_spike"))]
+    pub payment_failure_spike: usize,
+}
+
+impl Default for OpsSettings {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            batch_interval_secs: 60,
+            large_order_amount: 500.0,
+            payment_failure_spike: 5,
+        }
+    }
+}
+
+// Timeouts, circuit breakers and retries of calls to Stripe, EasyPost and Brevo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderSettings {
+    #[serde(rename(deserialize = "provider_timeout_secs"))]
+    pub timeout_secs: u64,
+    #[serde(rename(deserialize = "provider_breaker_failures"))]
+    pub breaker_failures: u32, // Failed calls in a row that open a provider's breaker
+    #[serde(rename(deserialize = "provider_breaker_cooldown_secs"))]
+    pub breaker_cooldown_secs: u64, // How long calls fail fast before one is let through to probe
+    #[serde(rename(deserialize = "provider_retry_attempts"))]
+    pub retry_attempts: u32, // Tries of an idempotent call, the first included
+    #[serde(rename(deserialize = "provider_retry_base_delay_ms"))]
+    pub retry_base_delay_ms: u64,
+    #[serde(rename(deserialize = "provider_retry_budget"))]
+    pub retry_budget: u32, // Retries one API request may spend across all its provider calls
+    #[serde(rename(deserialize = "provider_max_retry_after_secs"))]
+    pub max_retry_after_secs: u64, // A longer Retry-After is returned to the caller instead of waited out
+}
+
+impl Default for ProviderSettings {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 20,
+            breaker_failures: 5,
+            breaker_cooldown_secs: 30,
+            retry_attempts: 3,
+            retry_base_delay_ms: 200,
+            retry_budget: 4,
+            max_retry_after_secs: 5,
+        }
+    }
+}
+
+// Optional subsystems, all on by default (e.g. turn background_jobs off on web-only replicas)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeatureFlags {
+    #[serde(rename(deserialize = "enable_graphql"))]
+    pub graphql: bool,
+    #[serde(rename(deserialize = "enable_product_feed"))]
+    pub product_feed: bool,
+    #[serde(rename(deserialize = "enable_background_jobs"))]
+    pub background_jobs: bool,
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self {
+            graphql: true,
+            product_feed: true,
+            background_jobs: true,
+        }
+    }
+}
+
+// How the server was started: `--profile load` runs it without external providers (see load_profile)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerProfile {
+    #[default]
+    Standard,
+    Load,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppConfig {
+    pub profile: ServerProfile,
+    pub database: DatabaseSettings,
+    pub server: ServerSettings,
+    pub auth: AuthSettings,
+    pub stripe: StripeSettings,
+    pub square: SquareSettings,
+    pub smtp: SmtpSettings,
+    pub brevo: BrevoSettings,
+    pub mailchimp: MailchimpSettings,
+    pub letre: LetreSettings,
+    pub marketing: MarketingSettings,
+    pub sms: SmsSettings,
+    pub twilio: TwilioSettings,
+    pub easypost: EasyPostSettings,
+    pub shipping: ShippingSettings,
+    pub checkout: CheckoutSettings,
+    pub integrations: IntegrationSettings,
+    pub rewards: RewardSettings,
+    pub notifications: NotificationSettings,
+    pub limits: LimitSettings,
+    pub schedules: ScheduleSettings,
+    pub feeds: FeedSettings,
+    pub search: SearchSettings,
+    pub media: MediaSettings,
+    pub alerts: AlertSettings,
+    pub digest: DigestSettings,
+    pub ops: OpsSettings,
+    pub providers: ProviderSettings,
+    pub features: FeatureFlags,
+}
+
+// Deserialize one section from the environment. A variable that doesn't parse is reported
+// and dropped, then the section is read again, so every bad variable shows up in one run
+fn load_section<T: DeserializeOwned + Default>(vars: &mut HashMap<String, String>, errors: &mut Vec<String>) -> T {
+    loop {
+        let section = Config::builder()
+            .add_source(Environment::default().source(Some(vars.clone())))
+            .build()
+            .and_then(|config| config.try_deserialize::<T>());
+        match section {
+            Ok(section) => return section,
+            Err(ConfigError::Type {
+                key: Some(key),
+                unexpected,
+                expected,
+                ..
+            }) => {
+                errors.push(format!("{}: expected {}, got {}", key.to_uppercase(), expected, unexpected));
+                vars.remove(&key);
+            }
+            Err(e) => {
+                errors.push(e.to_string());
+                return T::default();
+            }
+        }
+    }
+}
+
+fn check_choice(errors: &mut Vec<String>, var: &str, value: &str, allowed: &[&str]) {
+    if !allowed.contains(&value) {
+        errors.push(format!("{}: must be one of {}, got \"{}\"", var, allowed.join(", "), value));
+    }
+}
+
+fn check_positive<T: PartialOrd + Default>(errors: &mut Vec<String>, var: &str, value: T) {
+    if value <= T::default() {
+        errors.push(format!("{}: must be greater than zero", var));
+    }
+}
+
+impl AppConfig {
+    // Read and validate the environment; Err lists every problem found
+    pub fn from_env() -> Result<Self, Vec<String>> {
+        // Empty variables count as unset; choice values are case-insensitive
+        let mut vars: HashMap<String, String> = std::env::vars()
+            .map(|(key, value)| (key.to_lowercase(), value.trim().to_string()))
+            .filter(|(_, value)| !value.is_empty())
+            .collect();
+        for key in ["sms_provider", "shipping_rate_provider", "marketing_provider", "square_environment", "referral_reward_type", "payment_capture_method", "environment_mode"] {
+            if let Some(value) = vars.get_mut(key) {
+                *value = value.to_lowercase();
+            }
+        }
+
+        let mut errors = Vec::new();
+        let mut config = Self {
+            profile: ServerProfile::Standard,
+            database: load_section(&mut vars, &mut errors),
+            server: load_section(&mut vars, &mut errors),
+            auth: load_section(&mut vars, &mut errors),
+            stripe: load_section(&mut vars, &mut errors),
+            square: load_section(&mut vars, &mut errors),
+            smtp: load_section(&mut vars, &mut errors),
+            brevo: load_section(&mut vars, &mut errors),
+            mailchimp: load_section(&mut vars, &mut errors),
+            letre: load_section(&mut vars, &mut errors),
+            marketing: load_section(&mut vars, &mut errors),
+            sms: load_section(&mut vars, &mut errors),
+            twilio: load_section(&mut vars, &mut errors),
+            easypost: load_section(&mut vars, &mut errors),
+            shipping: load_section(&mut vars, &mut errors),
+            checkout: load_section(&mut vars, &mut errors),
+            integrations: load_section(&mut vars, &mut errors),
+            rewards: load_section(&mut vars, &mut errors),
+            notifications: load_section(&mut vars, &mut errors),
+            limits: load_section(&mut vars, &mut errors),
+            schedules: load_section(&mut vars, &mut errors),
+            feeds: load_section(&mut vars, &mut errors),
+            search: load_section(&mut vars, &mut errors),
+            media: load_section(&mut vars, &mut errors),
+            alerts: load_section(&mut vars, &mut errors),
+            digest: load_section(&mut vars, &mut errors),
+            ops: load_section(&mut vars, &mut errors),
+            providers: load_section(&mut vars, &mut errors),
+            features: load_section(&mut vars, &mut errors),
+        };
+        match load_profile::requested(std::env::args().skip(1)) {
+            Ok(true) => {
+                config.profile = ServerProfile::Load;
+                load_profile::disable_providers(&mut config);
+            }
+            Ok(false) => {}
+            Err(e) => errors.push(e),
+        }
+        config.validate(&mut errors);
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(errors)
+        }
+    }
+
+    // Whether this server handles real payments: ENVIRONMENT_MODE, else the Stripe key's kind
+    pub fn is_live(&self) -> bool {
+        match &self.server.environment_mode {
+            Some(mode) => mode == "live",
+            None => self.stripe.is_live_key(),
+        }
+    }
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.database.url.expose().is_empty() {
+            errors.push("DATABASE_URL: required".to_string());
+        }
+        match self.profile {
+            ServerProfile::Standard if self.stripe.secret_key.expose().is_empty() => {
+                errors.push("STRIPE_SECRET_KEY: required".to_string());
+            }
+            ServerProfile::Load if self.is_live() => {
+                errors.push("--profile load: not allowed in live mode (live Stripe key or ENVIRONMENT_MODE=live)".to_string());
+            }
+            _ => {}
+        }
+
+        check_choice(errors, "SQUARE_ENVIRONMENT", &self.square.environment, &["sandbox", "production"]);
+        check_choice(errors, "MARKETING_PROVIDER", &self.marketing.provider, &["brevo", "mailchimp", "letre"]);
+        check_choice(errors, "SMS_PROVIDER", &self.sms.provider, &["textbelt", "twilio"]);
+        check_choice(errors, "SHIPPING_RATE_PROVIDER", &self.shipping.rate_provider, &["rules", "easypost"]);
+        check_choice(errors, "REFERRAL_REWARD_TYPE", &self.rewards.referral_reward_type, &["coupon", "store_credit"]);
+        check_choice(errors, "PAYMENT_CAPTURE_METHOD", &self.checkout.capture_method, &["automatic", "manual"]);
+
+        // Live and test credentials must never be mixed
+        let mode = if self.is_live() { "live" } else { "test" };
+        if let Some(environment_mode) = &self.server.environment_mode {
+            check_choice(errors, "ENVIRONMENT_MODE", environment_mode, &["live", "test"]);
+            let key = self.stripe.secret_key.expose();
+            if !key.is_empty() && self.stripe.is_live_key() != (environment_mode == "live") {
+                errors.push(format!("STRIPE_SECRET_KEY: a {} key can't be used in {} mode", if self.stripe.is_live_key() { "live" } else { "test" }, environment_mode));
+            }
+        }
+        if self.square.access_token.is_some() && (self.square.environment == "production") != self.is_live() {
+            errors.push(format!("SQUARE_ENVIRONMENT: {} can't be used in {} mode", self.square.environment, mode));
+        }
+
+        // SMTP is all or nothing
+        let smtp = [
+            ("SMTP_HOST", self.smtp.host.is_some()),
+            ("SMTP_PORT", self.smtp.port.is_some()),
+            ("SMTP_USERNAME", self.smtp.username.is_some()),
+            ("SMTP_PASSWORD", self.smtp.password.is_some()),
+            ("FROM_EMAIL", self.smtp.from_email.is_some()),
+        ];
+        if smtp.iter().any(|(_, set)| *set) {
+            // A value that failed to parse was already reported
+            for (var, _) in smtp.iter().filter(|(_, set)| !*set) {
+                if errors.iter().any(|e| e.starts_with(&format!("{}:", var))) {
+                    continue;
+                }
+                errors.push(format!("{}: required when SMTP email is configured", var));
+            }
+        }
+
+        if let Some(key) = &self.auth.pii_encryption_key {
+            if crate::pii::parse_key(key.expose()).is_none() {
+                errors.push("PII_ENCRYPTION_KEY: must be 32 bytes, base64 encoded (e.g. `openssl rand -base64 32`)".to_string());
+            }
+        }
+        if let (Some(key), None) = (&self.mailchimp.api_key, &self.mailchimp.api_base_url) {
+            if !key.expose().contains('-') {
+                errors.push("MAILCHIMP_API_KEY: missing the \"-us21\" style datacenter suffix (or set MAILCHIMP_API_BASE_URL)".to_string());
+            }
+        }
+        if self.sms.provider == "twilio" {
+            for (var, set) in [
+                ("TWILIO_ACCOUNT_SID", self.twilio.account_sid.is_some()),
+                ("TWILIO_AUTH_TOKEN", self.twilio.auth_token.is_some()),
+                ("TWILIO_FROM_PHONE", self.twilio.from_phone.is_some()),
+            ] {
+                if !set {
+                    errors.push(format!("{}: required when SMS_PROVIDER is twilio", var));
+                }
+            }
+        }
+        if self.shipping.rate_provider == "easypost" && self.easypost.api_key.is_none() {
+            errors.push("EASYPOST_API_KEY: required when SHIPPING_RATE_PROVIDER is easypost".to_string());
+        }
+
+        if let Some(url) = &self.search.meilisearch_url {
+            if !url.starts_with("http") {
+                errors.push(format!("MEILISEARCH_URL: invalid URL \"{}\"", url));
+            }
+        }
+
+        if let Some(dir) = &self.server.static_dir {
+            if !std::path::Path::new(dir).join("index.html").is_file() {
+                errors.push(format!("STATIC_DIR: no index.html in \"{}\"", dir));
+            }
+        }
+        match (&self.server.tls_cert_path, &self.server.tls_key_path) {
+            (Some(cert), Some(key)) => {
+                for (var, path) in [("TLS_CERT_PATH", cert), ("TLS_KEY_PATH", key)] {
+                    if !std::path::Path::new(path).is_file() {
+                        errors.push(format!("{}: no file at \"{}\"", var, path));
+                    }
+                }
+            }
+            (Some(_), None) => errors.push("TLS_KEY_PATH: required when TLS_CERT_PATH is set".to_string()),
+            (None, Some(_)) => errors.push("TLS_CERT_PATH: required when TLS_KEY_PATH is set".to_string()),
+            (None, None) => {}
+        }
+        for origin in &self.server.cors_allowed_origins {
+            if origin.parse::<axum::http::HeaderValue>().is_err() || !origin.starts_with("http") {
+                errors.push(format!("CORS_ALLOWED_ORIGINS: invalid origin \"{}\"", origin));
+            }
+        }
+        if self.notifications.store_timezone.parse::<chrono_tz::Tz>().is_err() {
+            errors.push(format!("STORE_TIMEZONE: unknown timezone \"{}\"", self.notifications.store_timezone));
+        }
+        for (var, hour) in [
+            ("QUIET_HOURS_START", self.notifications.quiet_hours_start),
+            ("QUIET_HOURS_END", self.notifications.quiet_hours_end),
+            ("DAILY_DIGEST_HOUR", self.digest.hour),
+        ] {
+            if hour > 23 {
+                errors.push(format!("{}: must be an hour from 0 to 23", var));
+            }
+        }
+        for recipient in self.digest.recipients.iter().filter(|r| !r.contains('@')) {
+            errors.push(format!("DAILY_DIGEST_RECIPIENTS: invalid email \"{}\"", recipient));
+        }
+        if !(self.alerts.webhook_failure_rate > 0.0 && self.alerts.webhook_failure_rate <= 1.0) {
+            errors.push("WEBHOOK_FAILURE_ALERT_RATE: must be between 0 and 1".to_string());
+        }
+        if !(0.0..1.0).contains(&self.checkout.tax_rate) {
+            errors.push("TAX_RATE: must be at least 0 and below 1".to_string());
+        }
+        if !(self.limits.duplicate_name_similarity > 0.0 && self.limits.duplicate_name_similarity <= 1.0) {
+            errors.push("DUPLICATE_NAME_SIMILARITY: must be between 0 and 1".to_string());
+        }
+
+        check_positive(errors, "DATABASE_MAX_CONNECTIONS", self.database.max_connections);
+        check_positive(errors, "DATABASE_CONNECT_ATTEMPTS", self.database.connect_attempts);
+        check_positive(errors, "DATABASE_ACQUIRE_TIMEOUT_SECS", self.database.acquire_timeout_secs);
+        check_positive(errors, "DATABASE_HEALTH_CHECK_INTERVAL_SECS", self.database.health_check_interval_secs);
+        check_positive(errors, "DATABASE_BREAKER_FAILURES", self.database.breaker_failures);
+        check_positive(errors, "REFERRAL_REWARD_AMOUNT", self.rewards.referral_reward_amount);
+        check_positive(errors, "LOYALTY_POINTS_PER_DOLLAR", self.rewards.loyalty_points_per_dollar);
+        check_positive(errors, "LOYALTY_REDEEM_POINTS_PER_DOLLAR", self.rewards.loyalty_redeem_points_per_dollar);
+        check_positive(errors, "EMAIL_BATCH_INTERVAL_MINUTES", self.notifications.email_batch_interval_minutes);
+        check_positive(errors, "SMS_RATE_LIMIT_PER_MINUTE", self.limits.sms_per_minute);
+        check_positive(errors, "EMAIL_RATE_LIMIT_PER_MINUTE", self.limits.email_per_minute);
+        check_positive(errors, "ALERT_CHECK_INTERVAL_SECS", self.schedules.alert_check_interval_secs);
+        check_positive(errors, "JWT_KEY_REFRESH_SECS", self.schedules.jwt_key_refresh_secs);
+        check_positive(errors, "AUTHORIZATION_WINDOW_HOURS", self.checkout.authorization_window_hours);
+        check_positive(errors, "QUOTE_VALID_DAYS", self.checkout.quote_valid_days);
+        check_positive(errors, "NET_TERMS_DAYS", self.checkout.net_terms_days);
+        check_positive(errors, "INVOICE_REMINDER_INTERVAL_DAYS", self.checkout.invoice_reminder_interval_days);
+        check_positive(errors, "TICKET_HOLD_MINUTES", self.checkout.ticket_hold_minutes);
+        check_positive(errors, "CART_HOLD_MINUTES", self.checkout.cart_hold_minutes);
+        check_positive(errors, "MANUAL_ORDER_HOLD_DAYS", self.checkout.manual_order_hold_days);
+        check_positive(errors, "MAX_IMAGE_UPLOAD_MB", self.media.max_image_upload_mb);
+        check_positive(errors, "SEARCH_REINDEX_INTERVAL_SECS", self.search.reindex_interval_secs);
+        check_positive(errors, "RECONCILIATION_SYNC_INTERVAL_SECS", self.schedules.reconciliation_sync_interval_secs);
+        check_positive(errors, "SCHEDULER_TICK_SECS", self.schedules.scheduler_tick_secs);
+        check_positive(errors, "STALE_CART_DAYS", self.schedules.stale_cart_days);
+        if self.schedules.scheduler_lease_secs <= self.schedules.scheduler_tick_secs {
+            errors.push("SCHEDULER_LEASE_SECS: must be greater than SCHEDULER_TICK_SECS".to_string());
+        }
+        if self.schedules.order_pii_retention_days < 30 {
+            errors.push("ORDER_PII_RETENTION_DAYS: must be at least 30".to_string());
+        }
+        check_positive(errors, "DEAD_LETTER_ALERT_THRESHOLD", self.alerts.dead_letter_threshold);
+        check_positive(errors, "DISPUTE_REMINDER_HOURS", self.alerts.dispute_reminder_hours);
+        check_positive(errors, "OPS_BATCH_INTERVAL_SECS", self.ops.batch_interval_secs);
+        check_positive(errors, "OPS_PAYMENT_FAILURE_SPIKE", self.ops.payment_failure_spike);
+        check_positive(errors, "LABEL_PURCHASE_CONCURRENCY", self.limits.label_purchase_concurrency);
+        check_positive(errors, "CSV_IMPORT_CONCURRENCY", self.limits.csv_import_concurrency);
+        check_positive(errors, "PDF_CONCURRENCY", self.limits.pdf_concurrency);
+        check_positive(errors, "REPORT_CONCURRENCY", self.limits.report_concurrency);
+        check_positive(errors, "PROVIDER_TIMEOUT_SECS", self.providers.timeout_secs);
+        check_positive(errors, "PROVIDER_BREAKER_FAILURES", self.providers.breaker_failures);
+        check_positive(errors, "PROVIDER_BREAKER_COOLDOWN_SECS", self.providers.breaker_cooldown_secs);
+        check_positive(errors, "PROVIDER_RETRY_ATTEMPTS", self.providers.retry_attempts);
+    }
+}
+
+// Load the configuration or exit listing everything that needs fixing
+pub fn init() -> &'static AppConfig {
+    match AppConfig::from_env() {
+        Ok(config) => CONFIG.get_or_init(|| config),
+        Err(errors) => {
+            eprintln!("✗ Invalid configuration ({} problem{}):", errors.len(), if errors.len() == 1 { "" } else { "s" });
+            for error in &errors {
+                eprintln!("  - {}", error);
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+// The settings loaded by init() at startup
+pub fn get() -> &'static AppConfig {
+    CONFIG.get().expect("configuration not loaded; call app_config::init() at startup")
+}
+
+pub fn config_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/config", routing::get(get_config))
+        .with_state(app_state)
+}
+
+// Effective settings with every secret replaced by "********" (null when unset)
+async fn get_config(_admin: AuthenticatedAdmin, State(_app_state): State<Arc<AppState>>) -> Json<&'static AppConfig> {
+    Json(get())
+}
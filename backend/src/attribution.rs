@@ -0,0 +1,156 @@
+// Attribution Module - UTM campaign tracking from landing page to order
+// The storefront remembers the utm_source / utm_medium / utm_campaign a shopper last arrived with
+// and sends them with checkout. They're kept on the checkout cart and copied to the order when
+// the payment succeeds, so the attribution report can break revenue down by campaign
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::daily_digest::PLACED_STATUSES;
+use crate::environment_mode;
+use crate::route_limits::{self, RouteGroup};
+use crate::AppState;
+
+const MAX_VALUE_LEN: usize = 255; // Column width
+const DEFAULT_REPORT_DAYS: i64 = 30;
+
+// UTM parameters sent with checkout; blank values count as missing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Utm {
+    #[serde(default)]
+    pub utm_source: Option<String>,
+    #[serde(default)]
+    pub utm_medium: Option<String>,
+    #[serde(default)]
+    pub utm_campaign: Option<String>,
+}
+
+impl Utm {
+    // Trimmed and cut to fit, so a long or padded tag never fails a checkout
+    pub fn normalized(&self) -> Utm {
+        let clean = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(|v| v.chars().take(MAX_VALUE_LEN).collect::<String>())
+        };
+        Utm {
+            utm_source: clean(&self.utm_source),
+            utm_medium: clean(&self.utm_medium),
+            utm_campaign: clean(&self.utm_campaign),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.utm_source.is_none() && self.utm_medium.is_none() && self.utm_campaign.is_none()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AttributionQuery {
+    pub from: Option<DateTime<Utc>>, // Order placement window; defaults to the last 30 days
+    pub to: Option<DateTime<Utc>>,
+    pub mode: Option<String>, // live, test or all orders; defaults to the server's mode
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct CampaignRevenue {
+    pub utm_source: Option<String>, // All three None for orders without attribution
+    pub utm_medium: Option<String>,
+    pub utm_campaign: Option<String>,
+    pub currency: String,
+    pub orders: i64,
+    pub revenue: i64,  // in cents, of the orders placed
+    pub refunded: i64, // in cents, of those orders since refunded
+    pub average_order_value: i64, // in cents
+}
+
+#[derive(Debug, Serialize)]
+pub struct AttributionReport {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub campaigns: Vec<CampaignRevenue>,
+}
+
+// Remember the checkout's UTM parameters until its order is created
+pub async fn set_on_cart(executor: impl sqlx::PgExecutor<'_>, cart_id: Uuid, utm: &Utm) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE checkout_carts SET utm_source = $1, utm_medium = $2, utm_campaign = $3 WHERE id = $4",
+        utm.utm_source.as_deref(),
+        utm.utm_medium.as_deref(),
+        utm.utm_campaign.as_deref(),
+        cart_id,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// Copy the checkout cart's UTM parameters onto the order it became
+pub async fn copy_to_order(executor: impl sqlx::PgExecutor<'_>, cart_id: Uuid, order_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE orders o SET utm_source = c.utm_source, utm_medium = c.utm_medium, utm_campaign = c.utm_campaign
+         FROM checkout_carts c
+         WHERE c.id = $1 AND o.id = $2",
+        cart_id,
+        order_id,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+pub fn attribution_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/api/admin/reports/attribution",
+            get(attribution_report).layer(route_limits::limit(RouteGroup::Reports)),
+        )
+        .with_state(app_state)
+}
+
+// Orders and revenue per source / medium / campaign and currency, highest revenue first
+async fn attribution_report(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<AttributionQuery>,
+) -> Result<Json<AttributionReport>, (StatusCode, String)> {
+    let livemode = environment_mode::livemode_filter(query.mode.as_deref(), app_state.mode)?;
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - chrono::Duration::days(DEFAULT_REPORT_DAYS));
+    if from >= to {
+        return Err((StatusCode::BAD_REQUEST, "from must be before to".to_string()));
+    }
+
+    let campaigns = sqlx::query_as!(
+        CampaignRevenue,
+        r#"SELECT utm_source, utm_medium, utm_campaign, currency,
+                COUNT(*) AS "orders!",
+                COALESCE(SUM(total_amount), 0)::BIGINT AS "revenue!",
+                COALESCE(SUM(total_amount) FILTER (WHERE status = 'refunded'), 0)::BIGINT AS "refunded!",
+                COALESCE(AVG(total_amount), 0)::BIGINT AS "average_order_value!"
+         FROM orders
+         WHERE created_at >= $1 AND created_at < $2 AND ($3::BOOLEAN IS NULL OR livemode = $3) AND status = ANY($4)
+         GROUP BY utm_source, utm_medium, utm_campaign, currency
+         ORDER BY "revenue!" DESC, "orders!" DESC"#,
+        from,
+        to,
+        livemode,
+        &PLACED_STATUSES[..] as _,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    Ok(Json(AttributionReport { from, to, campaigns }))
+}
@@ -0,0 +1,148 @@
+// Audit Log Module - Record of admin bulk changes
+// Bulk operations record who did what, and entries that can be reverted carry the data
+// needed to undo them. The owning module performs the undo and marks the entry undone
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::pagination::{self, Page};
+use crate::AppState;
+
+// Database model for audit log entries
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i32,
+    pub admin_username: String,
+    pub action: String,
+    pub summary: String,
+    pub details: serde_json::Value,
+    #[serde(skip)]
+    pub undo: Option<serde_json::Value>,
+    pub undoable: bool,
+    pub undone_at: Option<DateTime<Utc>>,
+    pub undone_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct AuditLogQuery {
+    pub action: Option<String>,
+    pub cursor: Option<String>, // next_cursor of the previous page
+    pub limit: Option<i64>,     // Defaults to 50, at most 200
+}
+
+// Add an entry inside the caller's transaction, so it exists exactly when the change does
+pub async fn record(
+    conn: &mut sqlx::PgConnection,
+    admin_username: &str,
+    action: &str,
+    summary: &str,
+    details: serde_json::Value,
+    undo: Option<serde_json::Value>,
+) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar!(
+        "INSERT INTO admin_audit_log (admin_username, action, summary, details, undo)
+         VALUES ($1, $2, $3, $4, $5) RETURNING id",
+        admin_username,
+        action,
+        summary,
+        details,
+        undo,
+    )
+    .fetch_one(conn)
+    .await
+}
+
+// Lock an entry of the given action that hasn't been undone yet, for undoing it
+pub async fn claim_for_undo(
+    conn: &mut sqlx::PgConnection,
+    id: i32,
+    action: &str,
+) -> Result<AuditEntry, (StatusCode, String)> {
+    let entry = sqlx::query_as!(
+        AuditEntry,
+        r#"SELECT id, admin_username, action, summary, details, undo, FALSE AS "undoable!", undone_at, undone_by, created_at
+         FROM admin_audit_log WHERE id = $1 AND action = $2 FOR UPDATE"#,
+        id,
+        action,
+    )
+    .fetch_optional(conn)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Audit log entry not found".to_string()))?;
+    if entry.undone_at.is_some() {
+        return Err((StatusCode::CONFLICT, "This change was already undone".to_string()));
+    }
+    if entry.undo.is_none() {
+        return Err((StatusCode::CONFLICT, "This change can't be undone".to_string()));
+    }
+    Ok(entry)
+}
+
+pub async fn mark_undone(conn: &mut sqlx::PgConnection, id: i32, admin_username: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!("UPDATE admin_audit_log SET undone_at = NOW(), undone_by = $1 WHERE id = $2", admin_username, id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+pub fn audit_log_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/audit-log", get(list_audit_log))
+        .route("/api/admin/audit-log/:id", get(get_audit_entry))
+        .with_state(app_state)
+}
+
+// A page of entries, most recent first, optionally of one action. Ids follow the order entries
+// were recorded in, so the list pages by id alone
+async fn list_audit_log(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Json<Page<AuditEntry>>, (StatusCode, String)> {
+    let size = pagination::page_size(query.limit);
+    let before: Option<i32> = pagination::after(query.cursor.as_deref())?;
+    let entries = sqlx::query_as!(
+        AuditEntry,
+        r#"SELECT id, admin_username, action, summary, details, undo, undone_at, undone_by, created_at,
+                (undo IS NOT NULL AND undone_at IS NULL) AS "undoable!"
+         FROM admin_audit_log
+         WHERE ($1::TEXT IS NULL OR action = $1) AND ($2::INT4 IS NULL OR id < $2)
+         ORDER BY id DESC
+         LIMIT $3"#,
+        query.action.as_deref(),
+        before,
+        size + 1,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(Page::new(entries, size, |e| e.id)))
+}
+
+async fn get_audit_entry(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<AuditEntry>, (StatusCode, String)> {
+    let entry = sqlx::query_as!(
+        AuditEntry,
+        r#"SELECT id, admin_username, action, summary, details, undo, undone_at, undone_by, created_at,
+                (undo IS NOT NULL AND undone_at IS NULL) AS "undoable!"
+         FROM admin_audit_log WHERE id = $1"#,
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Audit log entry not found".to_string()))?;
+    Ok(Json(entry))
+}
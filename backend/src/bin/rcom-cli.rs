@@ -0,0 +1,6 @@
+// rcom-cli binary - admin and operations commands (see cli.rs); run `rcom-cli help` for the list
+
+#[tokio::main]
+async fn main() {
+    backend::cli::main().await;
+}
@@ -0,0 +1,362 @@
+// Blog Module - Markdown posts for SEO content
+// Admins write posts with a cover image, tags and a publish date; the storefront
+// lists published posts, renders them as sanitized HTML and links an RSS feed
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::content_blocks::markdown_to_html;
+use crate::AppState;
+
+const DEFAULT_PAGE_SIZE: i64 = 10;
+const MAX_PAGE_SIZE: i64 = 50;
+const RSS_ITEMS: i64 = 20;
+
+// Database model for posts
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Post {
+    pub id: i32,
+    pub slug: String,
+    pub title: String,
+    pub excerpt: Option<String>,
+    pub body_markdown: String,
+    pub cover_image_url: Option<String>,
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PostInput {
+    pub slug: String,
+    pub title: String,
+    pub excerpt: Option<String>,
+    pub body_markdown: String,
+    pub cover_image_url: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    pub published_at: Option<DateTime<Utc>>, // Leave out to save as a draft
+}
+
+// Listing entry (no body)
+#[derive(Debug, Serialize)]
+pub struct PostSummary {
+    pub slug: String,
+    pub title: String,
+    pub excerpt: Option<String>,
+    pub cover_image_url: Option<String>,
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PostList {
+    pub posts: Vec<PostSummary>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PublicPost {
+    #[serde(flatten)]
+    pub summary: PostSummary,
+    pub html: String, // Rendered from markdown and sanitized
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct PostListQuery {
+    pub tag: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+impl From<&Post> for PostSummary {
+    fn from(post: &Post) -> Self {
+        Self {
+            slug: post.slug.clone(),
+            title: post.title.clone(),
+            excerpt: post.excerpt.clone(),
+            cover_image_url: post.cover_image_url.clone(),
+            tags: post.tags.clone(),
+            author: post.author.clone(),
+            published_at: post.published_at,
+        }
+    }
+}
+
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+fn validate_input(input: &PostInput) -> Result<(), String> {
+    let slug = input.slug.trim();
+    if slug.is_empty() || slug.len() > 150 {
+        return Err("slug must be 1-150 characters".to_string());
+    }
+    if !slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+        return Err("slug may only contain lowercase letters, digits and '-'".to_string());
+    }
+    if input.title.trim().is_empty() {
+        return Err("title is required".to_string());
+    }
+    if input.body_markdown.trim().is_empty() {
+        return Err("body_markdown is required".to_string());
+    }
+    if input
+        .cover_image_url
+        .as_deref()
+        .is_some_and(|url| !url.is_empty() && !url.starts_with("https://") && !url.starts_with('/'))
+    {
+        return Err("cover_image_url must be an https:// URL or a site path".to_string());
+    }
+    Ok(())
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+pub fn blog_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/posts", get(list_published_posts))
+        .route("/api/posts/:slug", get(get_published_post))
+        .route("/api/blog/rss.xml", get(rss_feed))
+        .route("/api/admin/posts", get(list_posts).post(create_post))
+        .route("/api/admin/posts/:id", get(get_post).put(update_post).delete(delete_post))
+        .with_state(app_state)
+}
+
+async fn list_published_posts(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<PostListQuery>,
+) -> Result<Json<PostList>, (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let tag = query.tag.as_deref().map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty());
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    // Posts are visible on the storefront once their publish time has passed
+    let posts = sqlx::query_as!(
+        Post,
+        "SELECT id, slug, title, excerpt, body_markdown, cover_image_url, tags, author, published_at, created_at, updated_at FROM posts
+         WHERE published_at IS NOT NULL AND published_at <= NOW() AND ($1::TEXT IS NULL OR $1 = ANY(tags))
+         ORDER BY published_at DESC, id DESC LIMIT $2 OFFSET $3",
+        tag.as_deref(),
+        limit,
+        offset,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(db_err)?;
+    let total: i64 = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "total!" FROM posts
+         WHERE published_at IS NOT NULL AND published_at <= NOW() AND ($1::TEXT IS NULL OR $1 = ANY(tags))"#,
+        tag.as_deref(),
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(db_err)?;
+
+    Ok(Json(PostList {
+        posts: posts.iter().map(PostSummary::from).collect(),
+        total,
+        limit,
+        offset,
+    }))
+}
+
+async fn get_published_post(
+    State(app_state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<PublicPost>, (StatusCode, String)> {
+    let post = sqlx::query_as!(
+        Post,
+        "SELECT id, slug, title, excerpt, body_markdown, cover_image_url, tags, author, published_at, created_at, updated_at FROM posts
+         WHERE slug = $1 AND published_at IS NOT NULL AND published_at <= NOW()",
+        slug,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Post not found".to_string()))?;
+    Ok(Json(PublicPost {
+        summary: PostSummary::from(&post),
+        html: ammonia::clean(&markdown_to_html(&post.body_markdown)),
+        updated_at: post.updated_at,
+    }))
+}
+
+// RSS 2.0 feed of the latest published posts, linking to the storefront
+async fn rss_feed(State(app_state): State<Arc<AppState>>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let posts = sqlx::query_as!(
+        Post,
+        "SELECT id, slug, title, excerpt, body_markdown, cover_image_url, tags, author, published_at, created_at, updated_at FROM posts
+         WHERE published_at IS NOT NULL AND published_at <= NOW()
+         ORDER BY published_at DESC, id DESC LIMIT $1",
+        RSS_ITEMS,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    let config = app_config::get();
+    let storefront_url = config.server.storefront_url.trim_end_matches('/');
+    let store_name = &config.smtp.from_name;
+
+    let items: String = posts
+        .iter()
+        .map(|post| {
+            let link = format!("{}/blog/{}", storefront_url, post.slug);
+            let categories: String = post
+                .tags
+                .iter()
+                .map(|tag| format!("<category>{}</category>", escape_xml(tag)))
+                .collect();
+            format!(
+                "<item><title>{}</title><link>{}</link><guid isPermaLink=\"true\">{}</guid>{}{}{}</item>",
+                escape_xml(&post.title),
+                escape_xml(&link),
+                escape_xml(&link),
+                post.excerpt
+                    .as_deref()
+                    .map(|e| format!("<description>{}</description>", escape_xml(e)))
+                    .unwrap_or_default(),
+                post.published_at
+                    .map(|at| format!("<pubDate>{}</pubDate>", at.to_rfc2822()))
+                    .unwrap_or_default(),
+                categories,
+            )
+        })
+        .collect();
+
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\"><channel><title>{} Blog</title><link>{}/blog</link><description>Latest posts from {}</description>{}</channel></rss>\n",
+        escape_xml(store_name),
+        escape_xml(storefront_url),
+        escape_xml(store_name),
+        items
+    );
+    Ok(([(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")], xml))
+}
+
+async fn list_posts(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Post>>, (StatusCode, String)> {
+    let posts = sqlx::query_as!(Post, "SELECT id, slug, title, excerpt, body_markdown, cover_image_url, tags, author, published_at, created_at, updated_at FROM posts ORDER BY published_at DESC NULLS FIRST, id DESC")
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(posts))
+}
+
+async fn get_post(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Post>, (StatusCode, String)> {
+    sqlx::query_as!(Post, "SELECT id, slug, title, excerpt, body_markdown, cover_image_url, tags, author, published_at, created_at, updated_at FROM posts WHERE id = $1", id)
+        .fetch_optional(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "Post not found".to_string()))
+}
+
+async fn create_post(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<PostInput>,
+) -> Result<(StatusCode, Json<Post>), (StatusCode, String)> {
+    validate_input(&input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let post = sqlx::query_as!(
+        Post,
+        "INSERT INTO posts (slug, title, excerpt, body_markdown, cover_image_url, tags, author, published_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id, slug, title, excerpt, body_markdown, cover_image_url, tags, author, published_at, created_at, updated_at",
+        input.slug.trim(),
+        input.title.trim(),
+        input.excerpt.as_deref().map(str::trim).filter(|e| !e.is_empty()),
+        &input.body_markdown,
+        input.cover_image_url.as_deref().map(str::trim).filter(|u| !u.is_empty()),
+        &normalize_tags(&input.tags),
+        input.author.as_deref().map(str::trim).filter(|a| !a.is_empty()),
+        input.published_at,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?;
+    Ok((StatusCode::CREATED, Json(post)))
+}
+
+async fn update_post(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(input): Json<PostInput>,
+) -> Result<Json<Post>, (StatusCode, String)> {
+    validate_input(&input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    sqlx::query_as!(
+        Post,
+        "UPDATE posts
+         SET slug = $1, title = $2, excerpt = $3, body_markdown = $4, cover_image_url = $5,
+             tags = $6, author = $7, published_at = $8, updated_at = NOW()
+         WHERE id = $9 RETURNING id, slug, title, excerpt, body_markdown, cover_image_url, tags, author, published_at, created_at, updated_at",
+        input.slug.trim(),
+        input.title.trim(),
+        input.excerpt.as_deref().map(str::trim).filter(|e| !e.is_empty()),
+        &input.body_markdown,
+        input.cover_image_url.as_deref().map(str::trim).filter(|u| !u.is_empty()),
+        &normalize_tags(&input.tags),
+        input.author.as_deref().map(str::trim).filter(|a| !a.is_empty()),
+        input.published_at,
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?
+    .map(Json)
+    .ok_or((StatusCode::NOT_FOUND, "Post not found".to_string()))
+}
+
+async fn delete_post(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM posts WHERE id = $1", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Post not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,253 @@
+// Branding Module - Store name, logo, colors and contact details for customer-facing documents
+// One settings row, edited by admins, rendered into the shared email layout and the
+// printable invoice and packing slip so nothing outside this file hardcodes the store's look
+
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::AppState;
+
+const DEFAULT_STORE_NAME: &str = "R-Com Store";
+const DEFAULT_COLOR: &str = "#1976d2";
+
+// Database model for the store_branding row
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Branding {
+    pub store_name: String,
+    pub logo_url: Option<String>,
+    pub primary_color: String,
+    pub accent_color: String,
+    pub support_email: Option<String>,
+    pub footer_address: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Default for Branding {
+    fn default() -> Self {
+        Self {
+            store_name: DEFAULT_STORE_NAME.to_string(),
+            logo_url: None,
+            primary_color: DEFAULT_COLOR.to_string(),
+            accent_color: DEFAULT_COLOR.to_string(),
+            support_email: None,
+            footer_address: None,
+            updated_at: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BrandingInput {
+    pub store_name: String,
+    pub logo_url: Option<String>,
+    pub primary_color: String,
+    pub accent_color: String,
+    pub support_email: Option<String>,
+    pub footer_address: Option<String>,
+}
+
+// Current branding; emails still go out with the defaults if it can't be read
+pub async fn load_branding(pool: &sqlx::PgPool) -> Branding {
+    match sqlx::query_as!(
+        Branding,
+        "SELECT store_name, logo_url, primary_color, accent_color, support_email, footer_address, updated_at
+         FROM store_branding WHERE id = 1",
+    )
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(branding) => branding.unwrap_or_default(),
+        Err(e) => {
+            tracing::error!("Failed to load store branding, using defaults: {}", e);
+            Branding::default()
+        }
+    }
+}
+
+pub fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+impl Branding {
+    fn logo_html(&self, class: &str) -> String {
+        self.logo_url
+            .as_deref()
+            .map(|url| {
+                format!(
+                    r#"<img class="{}" src="{}" alt="{}">"#,
+                    class,
+                    escape_html(url),
+                    escape_html(&self.store_name)
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    // Copyright line, postal address and support contact
+    fn footer_html(&self) -> String {
+        let mut footer = format!(
+            "<p>© {} {}. All rights reserved.</p>",
+            Utc::now().year(),
+            escape_html(&self.store_name)
+        );
+        if let Some(address) = &self.footer_address {
+            footer.push_str(&format!("<p>{}</p>", escape_html(address).replace('\n', "<br>")));
+        }
+        if let Some(email) = &self.support_email {
+            footer.push_str(&format!(
+                r#"<p>Questions? Contact <a href="mailto:{0}">{0}</a></p>"#,
+                escape_html(email)
+            ));
+        }
+        footer
+    }
+
+    // Full email document around an HTML fragment; the heading is shown in the colored header.
+    // Bodies that are already complete documents (admin-authored templates) are sent untouched
+    pub fn email_html(&self, heading: &str, content: &str) -> String {
+        if content.contains("<html") {
+            return content.to_string();
+        }
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+    <style>
+        body {{ font-family: Arial, sans-serif; line-height: 1.6; color: #333; }}
+        .container {{ max-width: 600px; margin: 0 auto; padding: 20px; }}
+        .header {{ background: {primary}; color: white; padding: 20px; text-align: center; }}
+        .logo {{ max-height: 48px; margin-bottom: 10px; }}
+        .content {{ padding: 20px; background: #f9f9f9; }}
+        .content a {{ color: {accent}; }}
+        .button {{ display: inline-block; padding: 12px 24px; background: {accent}; color: white !important; text-decoration: none; border-radius: 4px; margin: 20px 0; }}
+        .highlight {{ font-size: 24px; font-weight: bold; color: {accent}; text-align: center; }}
+        .footer {{ text-align: center; padding: 20px; color: #666; font-size: 12px; }}
+        table {{ width: 100%; border-collapse: collapse; margin: 20px 0; }}
+        th, td {{ padding: 10px; text-align: left; border-bottom: 1px solid #ddd; }}
+        th {{ background: #f0f0f0; }}
+        .total {{ font-size: 18px; font-weight: bold; margin: 20px 0; }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <div class="header">
+            {logo}
+            <h1>{heading}</h1>
+        </div>
+        <div class="content">
+            {content}
+        </div>
+        <div class="footer">
+            {footer}
+        </div>
+    </div>
+</body>
+</html>"#,
+            primary = self.primary_color,
+            accent = self.accent_color,
+            logo = self.logo_html("logo"),
+            heading = escape_html(heading),
+            content = content,
+            footer = self.footer_html(),
+        )
+    }
+
+    // Letterhead for printable order documents
+    pub fn document_header_html(&self) -> String {
+        format!(
+            r#"<div class="letterhead">{}<div><strong>{}</strong>{}</div></div>"#,
+            self.logo_html("logo"),
+            escape_html(&self.store_name),
+            self.footer_address
+                .as_deref()
+                .map(|address| format!("<br>{}", escape_html(address).replace('\n', "<br>")))
+                .unwrap_or_default(),
+        )
+    }
+
+    pub fn document_footer_html(&self) -> String {
+        self.support_email
+            .as_deref()
+            .map(|email| format!(r#"<p class="document-footer">Questions? Contact {}</p>"#, escape_html(email)))
+            .unwrap_or_default()
+    }
+}
+
+fn is_hex_color(value: &str) -> bool {
+    value
+        .strip_prefix('#')
+        .is_some_and(|hex| matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn optional(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+pub fn branding_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/branding", get(get_branding).put(update_branding))
+        .with_state(app_state)
+}
+
+async fn get_branding(_admin: AuthenticatedAdmin, State(app_state): State<Arc<AppState>>) -> Json<Branding> {
+    Json(load_branding(&app_state.pool).await)
+}
+
+async fn update_branding(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<BrandingInput>,
+) -> Result<Json<Branding>, (StatusCode, String)> {
+    let store_name = input.store_name.trim();
+    if store_name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "store_name is required".to_string()));
+    }
+    let primary_color = input.primary_color.trim().to_lowercase();
+    let accent_color = input.accent_color.trim().to_lowercase();
+    if !is_hex_color(&primary_color) || !is_hex_color(&accent_color) {
+        return Err((StatusCode::BAD_REQUEST, "Colors must be hex values like #1976d2".to_string()));
+    }
+    let logo_url = optional(input.logo_url);
+    if logo_url.as_deref().is_some_and(|url| !url.starts_with("https://") && !url.starts_with("http://")) {
+        return Err((StatusCode::BAD_REQUEST, "logo_url must start with https:// or http://".to_string()));
+    }
+    let support_email = optional(input.support_email);
+    if support_email.as_deref().is_some_and(|email| !email.contains('@')) {
+        return Err((StatusCode::BAD_REQUEST, "support_email must be an email address".to_string()));
+    }
+
+    let branding = sqlx::query_as!(
+        Branding,
+        "INSERT INTO store_branding (id, store_name, logo_url, primary_color, accent_color, support_email, footer_address)
+         VALUES (1, $1, $2, $3, $4, $5, $6)
+         ON CONFLICT (id) DO UPDATE SET
+             store_name = EXCLUDED.store_name,
+             logo_url = EXCLUDED.logo_url,
+             primary_color = EXCLUDED.primary_color,
+             accent_color = EXCLUDED.accent_color,
+             support_email = EXCLUDED.support_email,
+             footer_address = EXCLUDED.footer_address,
+             updated_at = NOW()
+         RETURNING store_name, logo_url, primary_color, accent_color, support_email, footer_address, updated_at",
+        store_name,
+        logo_url,
+        primary_color,
+        accent_color,
+        support_email,
+        optional(input.footer_address),
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    Ok(Json(branding))
+}
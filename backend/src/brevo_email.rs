@@ -3,8 +3,8 @@
 // API Documentation: https://developers.brevo.com/docs/send-a-transactional-email
 
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
@@ -13,6 +13,13 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::sync::Arc;
 
+use crate::api_keys::constant_time_eq;
+use crate::app_config;
+use crate::branding::{escape_html, Branding};
+use crate::email_log::{is_suppressed, record_event, record_sent, suppress};
+use crate::provider_breaker::{degraded_status, GuardedSend, Provider};
+use crate::provider_retry::RetrySend;
+use crate::request_id::WithRequestId;
 use crate::AppState;
 
 // ============================================================================
@@ -29,19 +36,12 @@ pub struct BrevoConfig {
 
 impl BrevoConfig {
     pub fn from_env() -> Option<Self> {
-        let api_key = std::env::var("BREVO_API_KEY").ok()?;
-        let from_email = std::env::var("BREVO_FROM_EMAIL")
-            .unwrap_or_else(|_| "noreply@yourdomain.com".to_string());
-        let from_name = std::env::var("BREVO_FROM_NAME")
-            .unwrap_or_else(|_| "R-Com Store".to_string());
-        let api_base_url = std::env::var("BREVO_API_BASE_URL")
-            .unwrap_or_else(|_| "https://api.brevo.com/v3".to_string());
-
+        let brevo = &app_config::get().brevo;
         Some(Self {
-            api_key,
-            api_base_url,
-            from_email,
-            from_name,
+            api_key: brevo.api_key.as_ref()?.expose().to_string(),
+            api_base_url: brevo.api_base_url.clone(),
+            from_email: brevo.from_email.clone(),
+            from_name: brevo.from_name.clone(),
         })
     }
 }
@@ -74,7 +74,10 @@ pub struct SendTransactionalEmailRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tags: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub params: Option<serde_json::Value>,
+    #[serde(rename = "templateId")]
+    pub template_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<serde_json::Value>, // Values for {{ params.* }} in the template
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -85,17 +88,44 @@ pub struct BrevoApiResponse {
 }
 
 // API request structures for our endpoints
+// Either a Brevo template_id (with params) or a subject and html_content
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SendEmailRequest {
     pub to_email: String,
     pub to_name: Option<String>,
-    pub subject: String,
-    pub html_content: String,
+    pub subject: Option<String>, // Overrides the template's subject when both are given
+    pub html_content: Option<String>,
     pub text_content: Option<String>,
     pub tags: Option<Vec<String>>,
+    pub template_id: Option<i64>,
+    pub params: Option<serde_json::Value>,
+}
+
+// Delivery event POSTed by Brevo's transactional webhook
+#[derive(Debug, Deserialize)]
+pub struct BrevoWebhookEvent {
+    pub event: String,
+    pub email: Option<String>,
+    #[serde(rename = "message-id")]
+    pub message_id: Option<String>,
+    pub reason: Option<String>,
+}
+
+// Brevo sends one event per request, or an array when batching is enabled
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum BrevoWebhookPayload {
+    Batch(Vec<BrevoWebhookEvent>),
+    Single(BrevoWebhookEvent),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BrevoWebhookQuery {
+    pub token: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[allow(dead_code)]
 pub struct SendMarketingCampaignRequest {
     pub recipients: Vec<EmailAddress>,
     pub subject: String,
@@ -143,7 +173,8 @@ impl BrevoClient {
             .header("api-key", &self.config.api_key)
             .header("content-type", "application/json")
             .json(&request)
-            .send()
+            .with_request_id()
+            .send_guarded(Provider::Brevo)
             .await
             .map_err(|e| format!("Failed to send request to Brevo: {}", e))?;
 
@@ -193,7 +224,8 @@ impl BrevoClient {
             .header("api-key", &self.config.api_key)
             .header("content-type", "application/json")
             .json(&body)
-            .send()
+            .with_request_id()
+            .send_guarded(Provider::Brevo)
             .await
             .map_err(|e| format!("Failed to add contact to Brevo: {}", e))?;
 
@@ -213,6 +245,171 @@ impl BrevoClient {
         }
     }
 
+    /// Remove existing contacts from a Brevo list (up to 150 emails per call)
+    pub async fn remove_contacts_from_list(&self, list_id: i64, emails: &[String]) -> Result<(), String> {
+        let url = format!("{}/contacts/lists/{}/contacts/remove", self.config.api_base_url, list_id);
+
+        let response = self
+            .client
+            .post(&url)
+            .header("accept", "application/json")
+            .header("api-key", &self.config.api_key)
+            .header("content-type", "application/json")
+            .json(&json!({ "emails": emails }))
+            .with_request_id()
+            .send_guarded(Provider::Brevo)
+            .await
+            .map_err(|e| format!("Failed to remove contacts from Brevo list: {}", e))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = response.text().await.unwrap_or_default();
+        // Brevo answers 400 when none of the emails are on the list, which is already the goal
+        if status.as_u16() == 400 && body.contains("already removed") {
+            return Ok(());
+        }
+        Err(format!("Brevo API returned error status {}: {}", status, body))
+    }
+
+    // Contact endpoint for an email, percent-encoding characters like '+'
+    fn contact_url(&self, email: &str) -> Result<reqwest::Url, String> {
+        let mut url = reqwest::Url::parse(&format!("{}/contacts", self.config.api_base_url))
+            .map_err(|e| format!("Invalid BREVO_API_BASE_URL: {}", e))?;
+        url.path_segments_mut()
+            .map_err(|_| "Invalid BREVO_API_BASE_URL".to_string())?
+            .push(email);
+        Ok(url)
+    }
+
+    /// Get a contact's attributes and lists; None if Brevo doesn't know the address
+    pub async fn get_contact(&self, email: &str) -> Result<Option<serde_json::Value>, String> {
+        let response = self
+            .client
+            .get(self.contact_url(email)?)
+            .header("accept", "application/json")
+            .header("api-key", &self.config.api_key)
+            .with_request_id()
+            .send_retrying(Provider::Brevo)
+            .await
+            .map_err(|e| format!("Failed to get contact from Brevo: {}", e))?;
+
+        let status = response.status();
+        if status.as_u16() == 404 {
+            return Ok(None);
+        }
+        let body = response.text().await.unwrap_or_default();
+        if status.is_success() {
+            serde_json::from_str(&body)
+                .map(Some)
+                .map_err(|e| format!("Failed to parse Brevo response: {}", e))
+        } else {
+            Err(format!("Brevo API returned error status {}: {}", status, body))
+        }
+    }
+
+    /// Overwrite some attributes of an existing contact
+    pub async fn update_contact_attributes(&self, email: &str, attributes: serde_json::Value) -> Result<(), String> {
+        let response = self
+            .client
+            .put(self.contact_url(email)?)
+            .header("accept", "application/json")
+            .header("api-key", &self.config.api_key)
+            .header("content-type", "application/json")
+            .json(&json!({ "attributes": attributes }))
+            .with_request_id()
+            .send_guarded(Provider::Brevo)
+            .await
+            .map_err(|e| format!("Failed to update contact in Brevo: {}", e))?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            let body = response.text().await.unwrap_or_default();
+            Err(format!("Brevo API returned error status {}: {}", status, body))
+        }
+    }
+
+    /// Delete a contact entirely; contacts that are already gone count as deleted
+    pub async fn delete_contact(&self, email: &str) -> Result<(), String> {
+        let response = self
+            .client
+            .delete(self.contact_url(email)?)
+            .header("accept", "application/json")
+            .header("api-key", &self.config.api_key)
+            .with_request_id()
+            .send_guarded(Provider::Brevo)
+            .await
+            .map_err(|e| format!("Failed to delete contact from Brevo: {}", e))?;
+
+        let status = response.status();
+        if status.is_success() || status.as_u16() == 404 {
+            Ok(())
+        } else {
+            let body = response.text().await.unwrap_or_default();
+            Err(format!("Brevo API returned error status {}: {}", status, body))
+        }
+    }
+
+    /// Create an email campaign to a list and send it right away; returns the campaign id
+    pub async fn send_email_campaign(
+        &self,
+        list_id: i64,
+        name: &str,
+        subject: &str,
+        html_content: &str,
+        reply_to: &str,
+    ) -> Result<i64, String> {
+        let response = self
+            .client
+            .post(format!("{}/emailCampaigns", self.config.api_base_url))
+            .header("accept", "application/json")
+            .header("api-key", &self.config.api_key)
+            .header("content-type", "application/json")
+            .json(&json!({
+                "name": name,
+                "subject": subject,
+                "sender": { "name": self.config.from_name, "email": self.config.from_email },
+                "replyTo": reply_to,
+                "htmlContent": html_content,
+                "recipients": { "listIds": [list_id] },
+            }))
+            .with_request_id()
+            .send_guarded(Provider::Brevo)
+            .await
+            .map_err(|e| format!("Failed to create Brevo campaign: {}", e))?;
+
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            return Err(format!("Brevo API returned error status {}: {}", status, body));
+        }
+        let campaign_id = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|campaign| campaign["id"].as_i64())
+            .ok_or_else(|| format!("Brevo response is missing the campaign id: {}", body))?;
+
+        let response = self
+            .client
+            .post(format!("{}/emailCampaigns/{}/sendNow", self.config.api_base_url, campaign_id))
+            .header("accept", "application/json")
+            .header("api-key", &self.config.api_key)
+            .with_request_id()
+            .send_guarded(Provider::Brevo)
+            .await
+            .map_err(|e| format!("Failed to send Brevo campaign: {}", e))?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(campaign_id)
+        } else {
+            let body = response.text().await.unwrap_or_default();
+            Err(format!("Brevo API returned error status {}: {}", status, body))
+        }
+    }
+
     /// Get contact lists from Brevo
     pub async fn get_contact_lists(&self) -> Result<serde_json::Value, String> {
         let url = format!("{}/contacts/lists", self.config.api_base_url);
@@ -222,7 +419,8 @@ impl BrevoClient {
             .get(&url)
             .header("accept", "application/json")
             .header("api-key", &self.config.api_key)
-            .send()
+            .with_request_id()
+            .send_retrying(Provider::Brevo)
             .await
             .map_err(|e| format!("Failed to get contact lists from Brevo: {}", e))?;
 
@@ -244,13 +442,73 @@ impl BrevoClient {
     }
 }
 
+/// Send through Brevo, skipping suppressed recipients and logging each one so
+/// webhook events can update its delivery status
+pub async fn send_logged_email(
+    pool: &sqlx::PgPool,
+    client: &BrevoClient,
+    mut request: SendTransactionalEmailRequest,
+) -> Result<BrevoApiResponse, String> {
+    let mut recipients = Vec::new();
+    for to in request.to.drain(..) {
+        if is_suppressed(pool, &to.email).await.map_err(|e| format!("DB error: {}", e))? {
+            tracing::info!("Skipping suppressed address {}", to.email);
+        } else {
+            recipients.push(to);
+        }
+    }
+    if recipients.is_empty() {
+        return Err("All recipients are on the suppression list".to_string());
+    }
+    request.to = recipients;
+
+    let emails: Vec<String> = request.to.iter().map(|to| to.email.clone()).collect();
+    let subject = request.subject.clone();
+    let template_id = request.template_id;
+    let response = client.send_transactional_email(request).await?;
+
+    for email in emails {
+        if let Err(e) = record_sent(pool, "brevo", response.message_id.as_deref(), &email, subject.as_deref(), template_id).await {
+            tracing::error!("Failed to log Brevo email to {}: {}", email, e);
+        }
+    }
+    Ok(response)
+}
+
+// Email log status for a Brevo webhook event; None for events we don't track
+fn event_status(event: &str) -> Option<&'static str> {
+    match event {
+        "delivered" => Some("delivered"),
+        "opened" | "unique_opened" | "proxy_open" => Some("opened"),
+        "click" => Some("clicked"),
+        "deferred" => Some("deferred"),
+        "soft_bounce" => Some("soft_bounced"),
+        "hard_bounce" | "invalid_email" => Some("bounced"),
+        "blocked" => Some("blocked"),
+        "spam" => Some("spam"),
+        "unsubscribed" => Some("unsubscribed"),
+        "error" => Some("failed"),
+        _ => None,
+    }
+}
+
+// Events after which the address must never be emailed again
+fn suppression_reason(event: &str) -> Option<&'static str> {
+    match event {
+        "hard_bounce" => Some("hard_bounce"),
+        "invalid_email" => Some("invalid_email"),
+        "spam" => Some("spam"),
+        _ => None,
+    }
+}
+
 // ============================================================================
 // Axum Route Handlers
 // ============================================================================
 
-/// Send a transactional email via Brevo
+/// Send a transactional email via Brevo, from raw HTML or a Brevo template
 pub async fn send_email_handler(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     Json(request): Json<SendEmailRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     let config = BrevoConfig::from_env().ok_or((
@@ -258,6 +516,22 @@ pub async fn send_email_handler(
         "Brevo not configured. Set BREVO_API_KEY in environment.".to_string(),
     ))?;
 
+    if request.template_id.is_none() && (request.subject.is_none() || request.html_content.is_none()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Provide a template_id, or a subject and html_content".to_string(),
+        ));
+    }
+    if is_suppressed(&state.pool, &request.to_email)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("{} is on the suppression list", request.to_email),
+        ));
+    }
+
     let client = BrevoClient::new(config.clone());
 
     let brevo_request = SendTransactionalEmailRequest {
@@ -270,16 +544,17 @@ pub async fn send_email_handler(
             name: request.to_name.clone(),
         }],
         reply_to: None,
-        subject: Some(request.subject),
-        html_content: Some(request.html_content),
+        subject: request.subject,
+        html_content: request.html_content,
         text_content: request.text_content,
         tags: request.tags,
-        params: None,
+        template_id: request.template_id,
+        params: request.params,
     };
 
-    match client.send_transactional_email(brevo_request).await {
+    match send_logged_email(&state.pool, &client, brevo_request).await {
         Ok(response) => {
-            println!("✓ Email sent successfully via Brevo: {:?}", response.message_id);
+            tracing::info!("Email sent successfully via Brevo: {:?}", response.message_id);
             Ok((
                 StatusCode::OK,
                 Json(json!({
@@ -290,9 +565,9 @@ pub async fn send_email_handler(
             ))
         }
         Err(e) => {
-            eprintln!("✗ Failed to send email via Brevo: {}", e);
+            tracing::error!("Failed to send email via Brevo: {}", e);
             Ok((
-                StatusCode::INTERNAL_SERVER_ERROR,
+                degraded_status(Provider::Brevo, StatusCode::INTERNAL_SERVER_ERROR),
                 Json(json!({
                     "success": false,
                     "error": e
@@ -335,13 +610,13 @@ pub async fn add_contact_handler(
         .await
     {
         Ok(response) => {
-            println!("✓ Contact added to Brevo: {}", request.email);
+            tracing::info!("Contact added to Brevo: {}", request.email);
             Ok((StatusCode::OK, Json(response)))
         }
         Err(e) => {
-            eprintln!("✗ Failed to add contact to Brevo: {}", e);
+            tracing::error!("Failed to add contact to Brevo: {}", e);
             Ok((
-                StatusCode::INTERNAL_SERVER_ERROR,
+                degraded_status(Provider::Brevo, StatusCode::INTERNAL_SERVER_ERROR),
                 Json(json!({
                     "success": false,
                     "error": e
@@ -365,9 +640,9 @@ pub async fn get_lists_handler(
     match client.get_contact_lists().await {
         Ok(lists) => Ok((StatusCode::OK, Json(lists))),
         Err(e) => {
-            eprintln!("✗ Failed to get contact lists from Brevo: {}", e);
+            tracing::error!("Failed to get contact lists from Brevo: {}", e);
             Ok((
-                StatusCode::INTERNAL_SERVER_ERROR,
+                degraded_status(Provider::Brevo, StatusCode::INTERNAL_SERVER_ERROR),
                 Json(json!({
                     "success": false,
                     "error": e
@@ -378,7 +653,9 @@ pub async fn get_lists_handler(
 }
 
 /// Send a welcome email using Brevo template
+#[allow(dead_code)]
 pub async fn send_welcome_email(
+    branding: &Branding,
     email: &str,
     name: Option<&str>,
 ) -> Result<BrevoApiResponse, String> {
@@ -387,43 +664,21 @@ pub async fn send_welcome_email(
 
     let client = BrevoClient::new(config.clone());
 
-    let html_content = format!(
-        r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <style>
-        body {{ font-family: Arial, sans-serif; line-height: 1.6; color: #333; }}
-        .container {{ max-width: 600px; margin: 0 auto; padding: 20px; }}
-        .header {{ background: #4CAF50; color: white; padding: 20px; text-align: center; }}
-        .content {{ padding: 20px; background: #f9f9f9; }}
-        .footer {{ text-align: center; padding: 20px; color: #666; font-size: 12px; }}
-        .button {{ background: #4CAF50; color: white; padding: 12px 24px; text-decoration: none; border-radius: 4px; display: inline-block; margin: 20px 0; }}
-    </style>
-</head>
-<body>
-    <div class="container">
-        <div class="header">
-            <h1>Welcome to R-Com!</h1>
-        </div>
-        <div class="content">
-            <p>Hi {},</p>
-            <p>Thank you for joining R-Com! We're excited to have you as part of our community.</p>
+    let html_content = branding.email_html(
+        &format!("Welcome to {}!", branding.store_name),
+        &format!(
+            r#"<p>Hi {},</p>
+            <p>Thank you for joining {}! We're excited to have you as part of our community.</p>
             <p>As a welcome gift, here's a special discount code for your first purchase:</p>
-            <p style="text-align: center; font-size: 24px; font-weight: bold; color: #4CAF50;">WELCOME10</p>
+            <p class="highlight">WELCOME10</p>
             <p>Use this code at checkout to get 10% off your first order!</p>
             <a href="https://your-domain.com/shop" class="button">Start Shopping</a>
             <p>If you have any questions, feel free to reach out to our support team.</p>
-        </div>
-        <div class="footer">
-            <p>© 2025 R-Com Store. All rights reserved.</p>
-            <p>You're receiving this email because you signed up for R-Com.</p>
-        </div>
-    </div>
-</body>
-</html>
-        "#,
-        name.unwrap_or("there")
+            <p>You're receiving this email because you signed up for {}.</p>"#,
+            escape_html(name.unwrap_or("there")),
+            escape_html(&branding.store_name),
+            escape_html(&branding.store_name)
+        ),
     );
 
     let request = SendTransactionalEmailRequest {
@@ -436,13 +691,15 @@ pub async fn send_welcome_email(
             name: name.map(String::from),
         }],
         reply_to: None,
-        subject: Some("Welcome to R-Com!".to_string()),
+        subject: Some(format!("Welcome to {}!", branding.store_name)),
         html_content: Some(html_content),
         text_content: Some(format!(
-            "Hi {},\n\nThank you for joining R-Com! Use code WELCOME10 for 10% off your first order.\n\nHappy shopping!",
-            name.unwrap_or("there")
+            "Hi {},\n\nThank you for joining {}! Use code WELCOME10 for 10% off your first order.\n\nHappy shopping!",
+            name.unwrap_or("there"),
+            branding.store_name
         )),
         tags: Some(vec!["welcome".to_string(), "onboarding".to_string()]),
+        template_id: None,
         params: None,
     };
 
@@ -461,4 +718,51 @@ pub fn brevo_email_routes(_state: Arc<AppState>) -> Router<Arc<AppState>> {
         .route("/api/brevo/send-email", post(send_email_handler))
         .route("/api/brevo/add-contact", post(add_contact_handler))
         .route("/api/brevo/lists", get(get_lists_handler))
+        .route("/api/webhooks/brevo", post(brevo_webhook_handler))
+}
+
+/// Ingest Brevo delivery events: update the email log and suppress hard-bounced or
+/// complaining addresses. Authenticated with BREVO_WEBHOOK_TOKEN as a bearer token or ?token=
+pub async fn brevo_webhook_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BrevoWebhookQuery>,
+    headers: HeaderMap,
+    Json(payload): Json<BrevoWebhookPayload>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let expected = app_config::get()
+        .brevo
+        .webhook_token
+        .as_ref()
+        .map(|token| token.expose())
+        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Brevo webhook not configured".to_string()))?;
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string)
+        .or(query.token)
+        .ok_or((StatusCode::UNAUTHORIZED, "Missing webhook token".to_string()))?;
+    if !constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+        state.ops.webhook_signature_failed("Brevo");
+        return Err((StatusCode::UNAUTHORIZED, "Invalid webhook token".to_string()));
+    }
+
+    let events = match payload {
+        BrevoWebhookPayload::Batch(events) => events,
+        BrevoWebhookPayload::Single(event) => vec![event],
+    };
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    for event in events {
+        let Some(email) = event.email.as_deref() else { continue };
+        if let (Some(status), Some(message_id)) = (event_status(&event.event), event.message_id.as_deref()) {
+            record_event(&state.pool, "brevo", message_id, email, status, event.reason.as_deref())
+                .await
+                .map_err(db_err)?;
+        }
+        if let Some(reason) = suppression_reason(&event.event) {
+            suppress(&state.pool, email, reason, "brevo").await.map_err(db_err)?;
+            tracing::info!("Suppressed {} after Brevo {} event", email, event.event);
+        }
+    }
+    Ok(StatusCode::OK)
 }
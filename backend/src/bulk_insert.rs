@@ -0,0 +1,53 @@
+// Bulk Insert Module - Multi-row inserts in chunks
+// Inserting a large import one row per statement pays a round trip per row. Bulk inserts
+// instead bind one array per column and insert `SELECT * FROM UNNEST($1::text[], ...)`, so a
+// whole chunk is one statement whatever its size (arrays don't count against the bind limit).
+// A chunk commits or fails as a unit, so when one fails `in_chunks` inserts its rows one at a
+// time: the good rows still go in and each bad one is reported with its own error
+
+use std::future::Future;
+
+pub const DEFAULT_CHUNK_SIZE: usize = 500;
+
+// A row that wasn't inserted: its position in the input, and why
+pub struct RowFailure {
+    pub row: usize,
+    pub error: sqlx::Error,
+}
+
+pub struct ChunkedInsert<R> {
+    pub inserted: Vec<R>,
+    pub failed: Vec<RowFailure>,
+}
+
+// Insert `rows` `chunk_size` at a time with `insert`, which writes one chunk in one statement
+pub async fn in_chunks<'a, T, R, F, Fut>(rows: &'a [T], chunk_size: usize, mut insert: F) -> ChunkedInsert<R>
+where
+    F: FnMut(&'a [T]) -> Fut,
+    Fut: Future<Output = Result<Vec<R>, sqlx::Error>>,
+{
+    let chunk_size = chunk_size.max(1);
+    let mut result = ChunkedInsert { inserted: Vec::new(), failed: Vec::new() };
+    for (index, chunk) in rows.chunks(chunk_size).enumerate() {
+        let start = index * chunk_size;
+        let error = match insert(chunk).await {
+            Ok(inserted) => {
+                result.inserted.extend(inserted);
+                continue;
+            }
+            Err(error) => error,
+        };
+        if chunk.len() == 1 {
+            result.failed.push(RowFailure { row: start, error });
+            continue;
+        }
+        // Find the rows that failed it
+        for (offset, row) in chunk.iter().enumerate() {
+            match insert(std::slice::from_ref(row)).await {
+                Ok(inserted) => result.inserted.extend(inserted),
+                Err(error) => result.failed.push(RowFailure { row: start + offset, error }),
+            }
+        }
+    }
+    result
+}
@@ -0,0 +1,59 @@
+// Cart Math Module - The arithmetic checkout charges by
+// Tiered unit prices, line totals, coupon discounts and sales tax, with no database or app
+// state. cart_pricing, pricing and coupons call in here, and benches/checkout.rs includes this
+// file directly, so the benchmarks time exactly the code checkout runs. Keep it free of other
+// crate modules for that reason
+// Catalog and rule prices are in dollars; every other amount is in cents
+
+pub const MIN_CHARGE_CENTS: i64 = 50; // Stripe's minimum charge amount
+
+// Convert a dollar price to integer cents
+pub fn to_cents(price: f64) -> i64 {
+    (price * 100.0).round() as i64
+}
+
+// Lowest unit price among the (min_quantity, price) rules the line quantity qualifies for
+pub fn tiered_price(base_price: f64, rules: impl IntoIterator<Item = (i32, f64)>, quantity: i32) -> f64 {
+    rules
+        .into_iter()
+        .filter(|(min_quantity, _)| *min_quantity <= quantity.max(1))
+        .map(|(_, price)| price)
+        .fold(base_price, f64::min)
+}
+
+// Unit price and line total in cents
+pub fn line_amounts(unit_price: f64, quantity: i32) -> (i64, i64) {
+    let unit = to_cents(unit_price);
+    (unit, unit * quantity as i64)
+}
+
+// What's left to charge after `amount_off`, never below Stripe's minimum charge
+pub fn apply_discount(amount: i64, amount_off: i64) -> i64 {
+    (amount - amount_off).max(MIN_CHARGE_CENTS.min(amount))
+}
+
+pub fn sales_tax(taxable: i64, tax_rate: f64) -> i64 {
+    (taxable as f64 * tax_rate).round() as i64
+}
+
+// total = subtotal - discount + tax + shipping
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Totals {
+    pub subtotal: i64,
+    pub discount: i64,
+    pub tax: i64,
+    pub total: i64,
+}
+
+// Coupons come off the merchandise, and tax is charged on what's left of it
+pub fn totals(line_totals: impl IntoIterator<Item = i64>, coupon_amount_off: Option<i64>, tax_rate: f64, shipping: i64) -> Totals {
+    let subtotal: i64 = line_totals.into_iter().sum();
+    let discount = coupon_amount_off.map_or(0, |off| subtotal - apply_discount(subtotal, off));
+    let tax = sales_tax(subtotal - discount, tax_rate);
+    Totals {
+        subtotal,
+        discount,
+        tax,
+        total: subtotal - discount + tax + shipping,
+    }
+}
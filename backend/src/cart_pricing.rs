@@ -0,0 +1,183 @@
+// Cart Pricing Module - Authoritative cart totals
+// Prices a cart the way checkout charges it: current unit prices with sales and quantity
+// breaks, the coupon discount, sales tax and shipping. The storefront displays these numbers
+// and create-payment-intent charges them, so nothing the client sends can change the amount
+
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::app_config;
+use crate::cart_math;
+use crate::coupons;
+use crate::customer_auth::AuthenticatedCustomer;
+use crate::easypost_shipping::{ensure_ships_internationally, is_international, Address};
+use crate::order_shipments::{plan_shipments, AddressBookEntry, PlannedShipment};
+use crate::pricing::{to_cents, CartLine, PriceBook};
+use crate::shipping_rules::quote_rules;
+use crate::tickets;
+use crate::AppState;
+
+// A cart to price, with the coupon and ship-to details that affect the total
+#[derive(Deserialize, Clone)]
+pub struct CartPriceRequest {
+    #[serde(default)]
+    pub items: Vec<CartLine>,
+    #[serde(default)]
+    pub address_book: Vec<AddressBookEntry>, // Ship-to addresses referenced by items[].ship_to
+    pub to_address: Option<Address>, // Single-address shipping; shipping is 0 until an address is given
+    pub shipping_method_id: Option<i32>, // Shipping rules method for to_address; cheapest when omitted
+    pub coupon_code: Option<String>, // Single-use discount code
+}
+
+// A cart line at its server-side price
+#[derive(Serialize, Clone)]
+pub struct PricedLine {
+    pub product_id: i32,
+    pub name: String,
+    pub quantity: i32,
+    pub unit_price: i64, // in cents
+    pub line_total: i64, // in cents
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ship_to: Option<String>,
+}
+
+// Every amount is in cents; total = subtotal - discount + tax + shipping
+#[derive(Serialize, Clone)]
+pub struct CartPrice {
+    pub lines: Vec<PricedLine>,
+    pub subtotal: i64,
+    pub discount: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coupon_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coupon_amount_off: Option<i64>, // in cents, the coupon's face value
+    pub tax_rate: f64,
+    pub tax: i64,
+    pub shipping: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shipping_method: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shipments: Vec<PlannedShipment>, // Per-address charges for multi-address carts
+    pub requires_shipping: bool, // False when every line is a license key or event ticket product
+    pub total: i64,
+    pub currency: String,
+}
+
+// Price a cart from the database for the signed-in customer, if any (coupons issued to a
+// customer only apply to their carts); Err is a message for the customer
+pub async fn price_cart(pool: &sqlx::PgPool, request: &CartPriceRequest, customer_id: Option<Uuid>) -> Result<CartPrice, String> {
+    if request.items.is_empty() {
+        return Err("Cart is empty".to_string());
+    }
+
+    let ids: Vec<i32> = request.items.iter().map(|l| l.product_id).collect();
+    let products: Vec<(i32, String, f64)> =
+        sqlx::query!("SELECT id, name, price FROM products WHERE id = ANY($1) AND status = 'published'", &ids)
+            .map(|r| (r.id, r.name, r.price))
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+    let products: HashMap<i32, (String, f64)> = products
+        .into_iter()
+        .map(|(id, name, price)| (id, (name, price)))
+        .collect();
+    let book = PriceBook::load_active(pool)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    let mut lines = Vec::new();
+    for item in &request.items {
+        if item.quantity < 1 {
+            return Err(format!("Invalid quantity for product {}", item.product_id));
+        }
+        let (name, base_price) = products
+            .get(&item.product_id)
+            .ok_or_else(|| format!("Unknown product {}", item.product_id))?;
+        let (unit_price, line_total) = cart_math::line_amounts(book.unit_price(item.product_id, *base_price, item.quantity), item.quantity);
+        lines.push(PricedLine {
+            product_id: item.product_id,
+            name: name.clone(),
+            quantity: item.quantity,
+            unit_price,
+            line_total,
+            ship_to: item.ship_to.clone(),
+        });
+    }
+    tickets::check_available(pool, &lines).await?;
+    // License keys are emailed and tickets are scanned at the door; anything else ships
+    let shipped: Option<i32> = sqlx::query_scalar!(
+        "SELECT id FROM products WHERE id = ANY($1) \
+         AND NOT EXISTS (SELECT 1 FROM license_products l WHERE l.product_id = products.id) \
+         AND NOT EXISTS (SELECT 1 FROM ticket_events t WHERE t.product_id = products.id) LIMIT 1",
+        &ids,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Database error: {}", e))?;
+
+    let coupon = match request.coupon_code.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+        Some(code) => Some(coupons::find_redeemable(pool, code, customer_id).await?),
+        None => None,
+    };
+
+    let (shipping, shipping_method, shipments) = if !request.address_book.is_empty() {
+        let shipments = plan_shipments(pool, &request.address_book, &request.items).await?;
+        (shipments.iter().map(|s| s.shipping_amount).sum(), None, shipments)
+    } else if let Some(address) = &request.to_address {
+        if is_international(None, address) {
+            ensure_ships_internationally(pool, &request.items).await?;
+        }
+        let quote = quote_rules(pool, address, &request.items).await?;
+        let option = match request.shipping_method_id {
+            Some(method_id) => quote
+                .options
+                .iter()
+                .find(|o| o.method_id == Some(method_id))
+                .ok_or_else(|| format!("Shipping method {} is not available for this address", method_id))?,
+            None => quote.options.first().ok_or("No shipping available for this address")?,
+        };
+        let rate: f64 = option.rate.parse().map_err(|_| format!("Invalid shipping rate for {}", option.service))?;
+        (to_cents(rate), Some(option.service.clone()), Vec::new())
+    } else {
+        (0, None, Vec::new())
+    };
+
+    let tax_rate = app_config::get().checkout.tax_rate;
+    let totals = cart_math::totals(lines.iter().map(|l| l.line_total), coupon.as_ref().map(|c| c.amount_off), tax_rate, shipping);
+    Ok(CartPrice {
+        lines,
+        subtotal: totals.subtotal,
+        discount: totals.discount,
+        coupon_amount_off: coupon.as_ref().map(|c| c.amount_off),
+        coupon_code: coupon.map(|c| c.code),
+        tax_rate,
+        tax: totals.tax,
+        shipping,
+        shipping_method,
+        shipments,
+        requires_shipping: shipped.is_some(),
+        total: totals.total,
+        currency: "usd".to_string(),
+    })
+}
+
+pub fn cart_pricing_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/cart/price", post(get_cart_price))
+        .with_state(app_state)
+}
+
+// The totals checkout will charge for this cart
+async fn get_cart_price(
+    State(app_state): State<Arc<AppState>>,
+    customer: Option<AuthenticatedCustomer>,
+    Json(request): Json<CartPriceRequest>,
+) -> Result<Json<CartPrice>, (StatusCode, String)> {
+    price_cart(&app_state.pool, &request, customer.map(|c| c.customer_id))
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
@@ -0,0 +1,300 @@
+// Checkout Carts Module - The priced cart behind each payment
+// create-payment-intent saves the cart it charged for and reserves the id of the order it
+// will become. cart_id, order_id and customer_id ride along in the payment metadata, so the
+// webhook creates the order under that id and links it back to the cart and its lines
+
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use stripe::{CancelPaymentIntent, PaymentIntent, PaymentIntentCancellationReason, PaymentIntentId, PaymentIntentStatus};
+
+use crate::app_config;
+use crate::branding::{escape_html, load_branding};
+use crate::cart_pricing::CartPrice;
+use crate::notifications::{queue_email, EmailPriority};
+use crate::pii::Encrypted;
+use crate::provider_breaker;
+use crate::stock_reservations;
+use crate::store_credit::CUSTOMER_METADATA_KEY;
+use crate::tickets;
+use crate::AppState;
+
+pub const CART_METADATA_KEY: &str = "cart_id";
+pub const ORDER_METADATA_KEY: &str = "order_id";
+
+// Ids linking a payment back to its checkout cart
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CheckoutLink {
+    pub cart_id: Option<Uuid>,
+    pub order_id: Option<Uuid>,
+    pub customer_id: Option<Uuid>,
+}
+
+impl CheckoutLink {
+    pub fn from_metadata(metadata: &HashMap<String, String>) -> Self {
+        let id = |key: &str| metadata.get(key).and_then(|v| Uuid::parse_str(v).ok());
+        CheckoutLink {
+            cart_id: id(CART_METADATA_KEY),
+            order_id: id(ORDER_METADATA_KEY),
+            customer_id: id(CUSTOMER_METADATA_KEY),
+        }
+    }
+
+    pub fn add_to_metadata(&self, metadata: &mut HashMap<String, String>) {
+        for (key, id) in [
+            (CART_METADATA_KEY, self.cart_id),
+            (ORDER_METADATA_KEY, self.order_id),
+            (CUSTOMER_METADATA_KEY, self.customer_id),
+        ] {
+            if let Some(id) = id {
+                metadata.insert(key.to_string(), id.to_string());
+            }
+        }
+    }
+}
+
+// Save the priced cart and reserve an order id for it
+pub async fn save_cart(
+    conn: impl sqlx::Acquire<'_, Database = sqlx::Postgres>,
+    customer_id: Option<Uuid>,
+    price: &CartPrice,
+) -> Result<CheckoutLink, sqlx::Error> {
+    let order_id = Uuid::new_v4();
+    let mut tx = conn.begin().await?;
+    let cart_id: Uuid = sqlx::query_scalar!(
+        "INSERT INTO checkout_carts
+            (order_id, customer_id, subtotal_amount, discount_amount, tax_amount, shipping_amount, total_amount, currency,
+             tax_rate, coupon_code, coupon_amount_off)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING id",
+        order_id,
+        customer_id,
+        price.subtotal,
+        price.discount,
+        price.tax,
+        price.shipping,
+        price.total,
+        price.currency.to_uppercase(),
+        price.tax_rate,
+        price.coupon_code.as_deref(),
+        price.coupon_amount_off,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // Every line in one statement (see bulk_insert); carts are small enough for a single chunk
+    let lines = &price.lines;
+    sqlx::query!(
+        "INSERT INTO checkout_cart_items (cart_id, product_id, product_name, quantity, unit_price, line_total, ship_to)
+         SELECT $1, * FROM UNNEST($2::INT4[], $3::VARCHAR[], $4::INT4[], $5::INT8[], $6::INT8[], $7::VARCHAR[])",
+        cart_id,
+        &lines.iter().map(|l| l.product_id).collect::<Vec<_>>(),
+        &lines.iter().map(|l| l.name.clone()).collect::<Vec<_>>(),
+        &lines.iter().map(|l| l.quantity).collect::<Vec<_>>(),
+        &lines.iter().map(|l| l.unit_price).collect::<Vec<_>>(),
+        &lines.iter().map(|l| l.line_total).collect::<Vec<_>>(),
+        &lines.iter().map(|l| l.ship_to.clone()).collect::<Vec<_>>() as _,
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(CheckoutLink {
+        cart_id: Some(cart_id),
+        order_id: Some(order_id),
+        customer_id,
+    })
+}
+
+// Record the payment intent created for a saved cart
+pub async fn attach_payment(pool: &sqlx::PgPool, cart_id: Uuid, payment_intent_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!("UPDATE checkout_carts SET payment_intent_id = $1 WHERE id = $2", payment_intent_id, cart_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// Remember who to tell if the cart's hold expires before they pay
+pub async fn set_contact_email(executor: impl sqlx::PgExecutor<'_>, cart_id: Uuid, email: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE checkout_carts SET contact_email = $1 WHERE id = $2",
+        Encrypted(email.trim().to_lowercase()) as _,
+        cart_id,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// Remember the storefront visitor, so experiment results can credit the purchase
+pub async fn set_visitor(executor: impl sqlx::PgExecutor<'_>, cart_id: Uuid, visitor_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!("UPDATE checkout_carts SET visitor_id = $1 WHERE id = $2", visitor_id, cart_id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+// Cancel the payment of a checkout whose hold expired, so it can't go through once the stock
+// or seats are given back. false while the payment has succeeded or is still processing: the
+// webhook will create the order and it keeps what was held
+pub async fn cancel_abandoned_payment(state: &AppState, payment_intent_id: &str) -> bool {
+    let Ok(id) = payment_intent_id.parse::<PaymentIntentId>() else {
+        return false;
+    };
+    let intent = match provider_breaker::stripe(PaymentIntent::retrieve(&state.stripe_client, &id, &[])).await {
+        Ok(intent) => intent,
+        Err(e) => {
+            tracing::error!("Failed to check payment {} of expired checkout: {}", payment_intent_id, e);
+            return false;
+        }
+    };
+    match intent.status {
+        PaymentIntentStatus::Canceled => true,
+        PaymentIntentStatus::Succeeded | PaymentIntentStatus::Processing | PaymentIntentStatus::RequiresCapture => false,
+        _ => {
+            let params = CancelPaymentIntent {
+                cancellation_reason: Some(PaymentIntentCancellationReason::Abandoned),
+            };
+            match provider_breaker::stripe(PaymentIntent::cancel(&state.stripe_client, payment_intent_id, params)).await {
+                Ok(_) => true,
+                Err(e) => {
+                    tracing::error!("Failed to cancel payment {} of expired checkout: {}", payment_intent_id, e);
+                    false
+                }
+            }
+        }
+    }
+}
+
+// Cancel a payment whose checkout couldn't be recorded, so it can't go through once what the
+// checkout held is given back. Failures are logged only
+pub async fn cancel_unrecorded_payment(state: &AppState, payment_intent_id: &str) {
+    let params = CancelPaymentIntent {
+        cancellation_reason: Some(PaymentIntentCancellationReason::Abandoned),
+    };
+    if let Err(e) = provider_breaker::stripe(PaymentIntent::cancel(&state.stripe_client, payment_intent_id, params)).await {
+        tracing::error!("Failed to cancel payment {} of unrecorded checkout: {}", payment_intent_id, e);
+    }
+}
+
+// Give back the seats, stock, points and store credit a checkout held
+pub async fn release_holds(state: &AppState, cart_id: Uuid) -> Result<(), sqlx::Error> {
+    tickets::release_cart_holds(&state.pool, cart_id).await?;
+    stock_reservations::release_cart(state, cart_id).await
+}
+
+// Tell the customer, once per checkout, that their hold ran out and the items went back on sale
+pub async fn notify_hold_expired(pool: &sqlx::PgPool, cart_id: Uuid) -> Result<(), String> {
+    // Emails are stored encrypted, so the fallback is picked here rather than in SQL
+    let recipient: Option<(Option<Encrypted>, Option<Encrypted>)> = sqlx::query!(
+        r#"UPDATE checkout_carts c SET hold_expired_notified_at = NOW()
+         WHERE c.id = $1 AND c.hold_expired_notified_at IS NULL AND c.converted_at IS NULL
+         RETURNING c.contact_email AS "contact_email: Encrypted",
+                   (SELECT cu.email FROM customers cu WHERE cu.id = c.customer_id) AS "customer_email: Encrypted""#,
+        cart_id,
+    )
+    .map(|r| (r.contact_email, r.customer_email))
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))?;
+    let Some(email) = recipient.and_then(|(contact, customer)| contact.or(customer).map(Encrypted::into_inner)) else {
+        return Ok(());
+    };
+    let items: Vec<(String, i32)> = sqlx::query!(
+        "SELECT product_name, quantity FROM checkout_cart_items WHERE cart_id = $1 ORDER BY id",
+        cart_id,
+    )
+    .map(|r| (r.product_name, r.quantity))
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))?;
+
+    let list: String = items
+        .iter()
+        .map(|(name, quantity)| format!("<li>{} &times; {}</li>", quantity, escape_html(name)))
+        .collect();
+    let cart_url = format!("{}/cart", app_config::get().server.storefront_url.trim_end_matches('/'));
+    let html_body = load_branding(pool).await.email_html(
+        "Your Cart Hold Expired",
+        &format!(
+            "<p>We held these items for you, but the checkout wasn't completed in time, \
+             so they're available to other shoppers again:</p><ul>{}</ul>\
+             <p>Your payment was not taken. If they're still in stock you can \
+             <a href=\"{}\">return to your cart</a> and check out again.</p>",
+            list,
+            cart_url
+        ),
+    );
+    queue_email(pool, &email, "Your cart hold expired", &html_body, EmailPriority::Immediate).await
+}
+
+// Split `amount` across lines in proportion to their weights, rounding down and giving the
+// cents left over to the last line, so the shares always add up to the amount
+fn allocate(amount: i64, weights: &[i64]) -> Vec<i64> {
+    let total: i64 = weights.iter().sum();
+    if total <= 0 {
+        return vec![0; weights.len()];
+    }
+    let mut shares: Vec<i64> = weights
+        .iter()
+        .map(|&w| (amount as i128 * w as i128 / total as i128) as i64)
+        .collect();
+    let left_over = amount - shares.iter().sum::<i64>();
+    if let Some(last) = shares.last_mut() {
+        *last += left_over;
+    }
+    shares
+}
+
+// Copy the cart's lines onto its new order as order_items and mark the cart converted. Each item
+// keeps what it was sold under: SKU and catalog price, the cart's tax rate and coupon, and its
+// share of the discount and of the tax (charged on what's left after the discount)
+pub async fn convert_to_order(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    cart_id: Uuid,
+    order_id: Uuid,
+) -> Result<u64, sqlx::Error> {
+    let (discount, tax): (i64, i64) = sqlx::query!(
+        "SELECT discount_amount, tax_amount FROM checkout_carts WHERE id = $1",
+        cart_id,
+    )
+    .map(|r| (r.discount_amount, r.tax_amount))
+    .fetch_optional(&mut **tx)
+    .await?
+    .unwrap_or_default();
+    let lines: Vec<(Uuid, i64)> = sqlx::query!(
+        "SELECT id, line_total FROM checkout_cart_items WHERE cart_id = $1 ORDER BY id",
+        cart_id,
+    )
+    .map(|r| (r.id, r.line_total))
+    .fetch_all(&mut **tx)
+    .await?;
+    let line_totals: Vec<i64> = lines.iter().map(|(_, total)| *total).collect();
+    let discounts = allocate(discount, &line_totals);
+    let taxable: Vec<i64> = line_totals.iter().zip(&discounts).map(|(total, off)| total - off).collect();
+    let taxes = allocate(tax, &taxable);
+
+    let res = sqlx::query!(
+        "INSERT INTO order_items
+            (order_id, product_id, product_name, product_description, quantity, unit_price, total_price,
+             sku, list_price, discount_amount, tax_rate, tax_amount, coupon_code, coupon_amount_off)
+         SELECT $2, i.product_id, i.product_name, p.description, i.quantity, i.unit_price, i.line_total,
+                p.sku, ROUND(p.price * 100)::BIGINT, s.discount, c.tax_rate, s.tax, c.coupon_code, c.coupon_amount_off
+         FROM UNNEST($3::UUID[], $4::INT8[], $5::INT8[]) WITH ORDINALITY AS s(item_id, discount, tax, n)
+         JOIN checkout_cart_items i ON i.id = s.item_id
+         JOIN checkout_carts c ON c.id = i.cart_id
+         LEFT JOIN products p ON p.id = i.product_id
+         WHERE c.id = $1
+         ORDER BY s.n",
+        cart_id,
+        order_id,
+        &lines.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        &discounts,
+        &taxes,
+    )
+    .execute(&mut **tx)
+    .await?;
+    crate::attribution::copy_to_order(&mut **tx, cart_id, order_id).await?;
+    sqlx::query!("UPDATE checkout_carts SET converted_at = NOW() WHERE id = $1 AND converted_at IS NULL", cart_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(res.rows_affected())
+}
@@ -0,0 +1,416 @@
+// CLI Module - rcom-cli, operations tasks that used to need psql
+// Runs against the same configuration (.env / environment) and database as the server:
+// creating admin users, rotating JWT signing keys, re-running failed webhooks, refreshing
+// materialized report data and exporting orders and products. Messages go to stderr, so an
+// export can be written to stdout and piped
+
+use chrono::{DateTime, NaiveDate, Utc};
+use dotenv::dotenv;
+use futures_util::TryStreamExt;
+use rand::distributions::{Alphanumeric, DistString};
+use std::sync::Arc;
+use stripe::Client as StripeClient;
+
+use crate::admin_products::ProductCsvRow;
+use crate::export_stream::{self, ExportError, ExportFormat};
+use crate::repos::{OrderExportFilter, Repos, WebhookEventFilter};
+use crate::segments;
+use crate::{admin_auth, app_config, db_health, jwt_keys, log_scrubbing, reconciliation, webhooks, AppState};
+
+const USAGE: &str = "Usage: rcom-cli <command>
+
+Commands:
+  create-admin <username> [--password-stdin]   Add an admin user; the password is generated unless read from stdin
+  jwt-keys list                                List JWT signing keys
+  jwt-keys rotate [--immediate]                Add a signing key (signs after JWT_KEY_REFRESH_SECS unless --immediate)
+  jwt-keys retire <kid>                        Stop accepting a key (\"env\" is JWT_SECRET)
+  webhooks failed [--provider P] [--limit N]   List webhook events that failed or never finished
+  webhooks retry <event_id>... | --all         Run failed Stripe events again, fetched from Stripe
+  reports refresh [segments|reconciliation|all] [--days N]
+                                               Rebuild segment memberships and sync payouts (N days, default 30)
+  export orders|products [--format csv|json] [--out FILE] [--status S] [--from DATE] [--to DATE]
+                                               Export to FILE or stdout; the filters apply to orders";
+
+const DEFAULT_FAILED_LIMIT: i64 = 50;
+const RETRY_ALL_LIMIT: i64 = 500;
+const DEFAULT_RECONCILIATION_DAYS: i64 = 30;
+
+// Problems running a command; usage errors also print the usage
+enum CliError {
+    Usage(String),
+    Failed(String),
+}
+
+impl From<String> for CliError {
+    fn from(e: String) -> Self {
+        CliError::Failed(e)
+    }
+}
+
+fn db_error(e: sqlx::Error) -> CliError {
+    CliError::Failed(format!("DB error: {}", e))
+}
+
+// Arguments after the command, with --flags (and their values) picked out by name
+struct Args {
+    positional: Vec<String>,
+    flags: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    // `valued` lists the flags that take a value
+    fn parse(args: &[String], valued: &[&str]) -> Result<Self, CliError> {
+        let mut parsed = Args { positional: Vec::new(), flags: Vec::new() };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                parsed.positional.push(arg.clone());
+            } else if valued.contains(&arg.as_str()) {
+                let value = args.next().ok_or_else(|| CliError::Usage(format!("{} needs a value", arg)))?;
+                parsed.flags.push((arg.clone(), Some(value.clone())));
+            } else {
+                parsed.flags.push((arg.clone(), None));
+            }
+        }
+        Ok(parsed)
+    }
+
+    // Fail on flags the command doesn't take
+    fn only(self, known: &[&str]) -> Result<Self, CliError> {
+        match self.flags.iter().find(|(flag, _)| !known.contains(&flag.as_str())) {
+            Some((flag, _)) => Err(CliError::Usage(format!("unknown option {}", flag))),
+            None => Ok(self),
+        }
+    }
+
+    fn has(&self, flag: &str) -> bool {
+        self.flags.iter().any(|(f, _)| f == flag)
+    }
+
+    fn value(&self, flag: &str) -> Option<&str> {
+        self.flags.iter().find(|(f, _)| f == flag).and_then(|(_, v)| v.as_deref())
+    }
+}
+
+// Entry point for the rcom-cli binary; exits 2 on usage errors and 1 when a command fails
+pub async fn main() {
+    dotenv().ok();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || matches!(args[0].as_str(), "help" | "--help" | "-h") {
+        println!("{}", USAGE);
+        return;
+    }
+    let config = app_config::init();
+    log_scrubbing::init_stderr(&config.auth.pii_scrub_allowed_fields);
+    let pool = Arc::new(db_health::connect(&config.database).await);
+    if let Err(e) = jwt_keys::load_keys(&pool).await {
+        eprintln!("Failed to load JWT signing keys: {}", e);
+    }
+
+    match run(&pool, &args).await {
+        Ok(()) => {}
+        Err(CliError::Usage(e)) => {
+            eprintln!("✗ {}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+        Err(CliError::Failed(e)) => {
+            eprintln!("✗ {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn run(pool: &Arc<sqlx::PgPool>, args: &[String]) -> Result<(), CliError> {
+    let command = args[0].as_str();
+    let subcommand = args.get(1).map(String::as_str);
+    match (command, subcommand) {
+        ("create-admin", _) => create_admin(pool, Args::parse(&args[1..], &[])?.only(&["--password-stdin"])?).await,
+        ("jwt-keys", Some("list")) => list_keys(pool).await,
+        ("jwt-keys", Some("rotate")) => rotate_key(pool, Args::parse(&args[2..], &[])?.only(&["--immediate"])?).await,
+        ("jwt-keys", Some("retire")) => retire_key(pool, Args::parse(&args[2..], &[])?.only(&[])?).await,
+        ("webhooks", Some("failed")) => {
+            let valued = ["--provider", "--limit"];
+            failed_webhooks(pool, Args::parse(&args[2..], &valued)?.only(&valued)?).await
+        }
+        ("webhooks", Some("retry")) => retry_webhooks(pool, Args::parse(&args[2..], &[])?.only(&["--all"])?).await,
+        ("reports", Some("refresh")) => refresh_reports(pool, Args::parse(&args[2..], &["--days"])?.only(&["--days"])?).await,
+        ("export", _) => {
+            let valued = ["--format", "--out", "--status", "--from", "--to"];
+            export(pool, Args::parse(&args[1..], &valued)?.only(&valued)?).await
+        }
+        _ => Err(CliError::Usage(format!("unknown command \"{}\"", args.join(" ")))),
+    }
+}
+
+// The state handlers run with, for commands that go through them; no server is started
+fn app_state(pool: &Arc<sqlx::PgPool>) -> Arc<AppState> {
+    let stripe_client = StripeClient::new(app_config::get().stripe.secret_key.expose());
+    crate::build_app_state(pool.clone(), stripe_client)
+}
+
+async fn create_admin(pool: &sqlx::PgPool, args: Args) -> Result<(), CliError> {
+    let [username] = args.positional.as_slice() else {
+        return Err(CliError::Usage("create-admin takes one username".to_string()));
+    };
+    let username = username.trim();
+    let generated = !args.has("--password-stdin");
+    let password = if generated {
+        Alphanumeric.sample_string(&mut rand::thread_rng(), 24)
+    } else {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line).map_err(|e| format!("Failed to read the password: {}", e))?;
+        line.trim_end_matches(['\r', '\n']).to_string()
+    };
+    if username.is_empty() || password.is_empty() {
+        return Err(CliError::Usage("username and password can't be empty".to_string()));
+    }
+    let exists: bool = sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM admin_users WHERE username = $1) AS "exists!""#, username)
+        .fetch_one(pool)
+        .await
+        .map_err(db_error)?;
+    if exists {
+        return Err(CliError::Failed(format!("Admin {} already exists", username)));
+    }
+    admin_auth::create_admin(pool, username, &password).await.map_err(|(_, e)| e)?;
+    eprintln!("✓ Created admin {}", username);
+    if generated {
+        // The only time the password is shown; it goes to stdout so it can be captured
+        println!("{}", password);
+    }
+    Ok(())
+}
+
+async fn list_keys(pool: &sqlx::PgPool) -> Result<(), CliError> {
+    let keys = jwt_keys::list(pool).await.map_err(db_error)?;
+    println!("{:<18} {:<26} {:<26} {:<8}", "KID", "ACTIVATES", "RETIRED", "SIGNING");
+    let time = |t: Option<DateTime<Utc>>| t.map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string()).unwrap_or_else(|| "-".to_string());
+    for key in keys {
+        println!(
+            "{:<18} {:<26} {:<26} {:<8}",
+            key.kid,
+            time(key.activates_at),
+            time(key.retired_at),
+            if key.signing { "yes" } else { "" }
+        );
+    }
+    Ok(())
+}
+
+async fn rotate_key(pool: &sqlx::PgPool, args: Args) -> Result<(), CliError> {
+    if !args.positional.is_empty() {
+        return Err(CliError::Usage("jwt-keys rotate takes no arguments".to_string()));
+    }
+    // rotate() reports the new key itself
+    jwt_keys::rotate(pool, args.has("--immediate")).await.map_err(db_error)?;
+    Ok(())
+}
+
+async fn retire_key(pool: &sqlx::PgPool, args: Args) -> Result<(), CliError> {
+    let [kid] = args.positional.as_slice() else {
+        return Err(CliError::Usage("jwt-keys retire takes one key id".to_string()));
+    };
+    jwt_keys::retire(pool, kid).await.map_err(|(_, e)| CliError::Failed(e))
+}
+
+async fn failed_webhooks(pool: &Arc<sqlx::PgPool>, args: Args) -> Result<(), CliError> {
+    let limit = match args.value("--limit") {
+        Some(limit) => limit.parse::<i64>().ok().filter(|l| *l > 0).ok_or_else(|| CliError::Usage("--limit must be a positive number".to_string()))?,
+        None => DEFAULT_FAILED_LIMIT,
+    };
+    let filter = WebhookEventFilter {
+        provider: args.value("--provider").map(str::to_string),
+        processed: Some(false),
+        before: None,
+        limit,
+    };
+    let repos = Repos::postgres(pool.clone());
+    let events = repos.webhook_events.list(&filter).await.map_err(db_error)?;
+    if events.is_empty() {
+        eprintln!("No failed webhook events");
+        return Ok(());
+    }
+    for event in &events {
+        println!(
+            "{}  {:<8} {:<34} {:<36} {}",
+            event.created_at.format("%Y-%m-%d %H:%M:%S"),
+            event.provider,
+            event.event_type,
+            event.event_id,
+            event.error_message.as_deref().unwrap_or("(not finished, no error recorded)")
+        );
+    }
+    Ok(())
+}
+
+async fn retry_webhooks(pool: &Arc<sqlx::PgPool>, args: Args) -> Result<(), CliError> {
+    let all = args.has("--all");
+    if all != args.positional.is_empty() {
+        return Err(CliError::Usage("webhooks retry takes event ids or --all".to_string()));
+    }
+    let events: Vec<(sqlx::types::Uuid, String, String, bool)> = if all {
+        sqlx::query!(
+            "SELECT id, event_id, provider, processed FROM webhook_events
+             WHERE NOT processed AND provider = 'stripe' ORDER BY created_at LIMIT $1",
+            RETRY_ALL_LIMIT,
+        )
+        .map(|r| (r.id, r.event_id, r.provider, r.processed))
+        .fetch_all(&**pool)
+        .await
+        .map_err(db_error)?
+    } else {
+        let found: Vec<(sqlx::types::Uuid, String, String, bool)> = sqlx::query!(
+            "SELECT id, event_id, provider, processed FROM webhook_events WHERE event_id = ANY($1) ORDER BY created_at",
+            &args.positional,
+        )
+        .map(|r| (r.id, r.event_id, r.provider, r.processed))
+        .fetch_all(&**pool)
+        .await
+        .map_err(db_error)?;
+        if let Some(missing) = args.positional.iter().find(|id| !found.iter().any(|(_, event_id, _, _)| event_id == *id)) {
+            return Err(CliError::Failed(format!("No webhook event {}", missing)));
+        }
+        found
+    };
+    if events.is_empty() {
+        eprintln!("No failed Stripe webhook events");
+        return Ok(());
+    }
+
+    let state = app_state(pool);
+    let mut failed = 0;
+    for (id, event_id, provider, processed) in &events {
+        let result = if *processed {
+            Err("already processed".to_string())
+        } else if provider != "stripe" {
+            Err(format!("{} events can't be fetched again; have {} resend it", provider, provider))
+        } else {
+            webhooks::stripe::reprocess_event(&state, *id, event_id).await
+        };
+        match result {
+            Ok(()) => eprintln!("✓ {} processed", event_id),
+            Err(e) => {
+                failed += 1;
+                eprintln!("✗ {}: {}", event_id, e);
+            }
+        }
+    }
+    // Emails and other side effects of the events run as subscribers; let them finish
+    state.events.wait_idle().await;
+    match failed {
+        0 => Ok(()),
+        n => Err(CliError::Failed(format!("{} of {} events failed again", n, events.len()))),
+    }
+}
+
+async fn refresh_reports(pool: &Arc<sqlx::PgPool>, args: Args) -> Result<(), CliError> {
+    let which = match args.positional.as_slice() {
+        [] => "all",
+        [which] if matches!(which.as_str(), "segments" | "reconciliation" | "all") => which.as_str(),
+        _ => return Err(CliError::Usage("reports refresh takes segments, reconciliation or all".to_string())),
+    };
+    let days = match args.value("--days") {
+        Some(days) => days.parse::<i64>().ok().filter(|d| *d > 0).ok_or_else(|| CliError::Usage("--days must be a positive number".to_string()))?,
+        None => DEFAULT_RECONCILIATION_DAYS,
+    };
+
+    let mut failed = false;
+    if which != "reconciliation" {
+        let all_segments = segments::all_segments(pool).await.map_err(db_error)?;
+        for segment in &all_segments {
+            match segments::refresh_segment(pool, segment).await {
+                Ok(r) => {
+                    eprintln!("✓ Segment '{}': {} members (+{} / -{})", segment.name, r.member_count, r.added, r.removed);
+                    if let Some(e) = r.sync_error {
+                        failed = true;
+                        eprintln!("✗ Segment '{}' sync failed: {}", segment.name, e);
+                    }
+                }
+                Err(e) => {
+                    failed = true;
+                    eprintln!("✗ Failed to refresh segment '{}': {}", segment.name, e);
+                }
+            }
+        }
+        if all_segments.is_empty() {
+            eprintln!("No customer segments");
+        }
+    }
+    if which != "segments" {
+        let summary = reconciliation::sync(&app_state(pool), days).await?;
+        eprintln!(
+            "✓ Reconciliation: {} payout(s), {} transaction(s), {} newly matched",
+            summary.payouts, summary.transactions, summary.matched
+        );
+    }
+    if failed {
+        return Err(CliError::Failed("some segments failed to refresh".to_string()));
+    }
+    Ok(())
+}
+
+// YYYY-MM-DD (midnight UTC) or an RFC 3339 time
+fn parse_time(flag: &str, value: Option<&str>) -> Result<Option<DateTime<Utc>>, CliError> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).map(|t| t.and_utc()));
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| Some(t.with_timezone(&Utc)))
+        .map_err(|_| CliError::Usage(format!("{}: expected YYYY-MM-DD or an RFC 3339 time", flag)))
+}
+
+async fn export(pool: &Arc<sqlx::PgPool>, args: Args) -> Result<(), CliError> {
+    let [what] = args.positional.as_slice() else {
+        return Err(CliError::Usage("export takes orders or products".to_string()));
+    };
+    let format = match args.value("--format").unwrap_or("csv") {
+        "csv" => ExportFormat::Csv,
+        "json" => ExportFormat::Json,
+        other => return Err(CliError::Usage(format!("--format: expected csv or json, got \"{}\"", other))),
+    };
+    let repos = Repos::postgres(pool.clone());
+    let out: Box<dyn tokio::io::AsyncWrite + Unpin + Send> = match args.value("--out") {
+        Some(path) => Box::new(tokio::fs::File::create(path).await.map_err(|e| format!("{}: {}", path, e))?),
+        None => Box::new(tokio::io::stdout()),
+    };
+
+    let rows = match what.as_str() {
+        "orders" => {
+            let filter = OrderExportFilter {
+                status: args.value("--status").map(str::to_string),
+                customer_id: None,
+                from: parse_time("--from", args.value("--from"))?,
+                to: parse_time("--to", args.value("--to"))?,
+            };
+            export_stream::write_to(format, out, |mut out| async move {
+                let mut rows = repos.orders.stream(&filter);
+                while let Some(order) = rows.try_next().await? {
+                    out.write(&order).await?;
+                }
+                drop(rows);
+                Ok::<_, ExportError>(out)
+            })
+            .await
+        }
+        "products" => {
+            if args.has("--status") || args.has("--from") || args.has("--to") {
+                return Err(CliError::Usage("products exports take no filters".to_string()));
+            }
+            export_stream::write_to(format, out, |mut out| async move {
+                let mut rows = repos.products.stream_all();
+                while let Some(product) = rows.try_next().await? {
+                    out.write(&ProductCsvRow::from(product)).await?;
+                }
+                drop(rows);
+                Ok::<_, ExportError>(out)
+            })
+            .await
+        }
+        other => return Err(CliError::Usage(format!("can't export \"{}\"; use orders or products", other))),
+    }
+    .map_err(|e| CliError::Failed(e.to_string()))?;
+    eprintln!("✓ Exported {} {}{}", rows, what, args.value("--out").map(|p| format!(" to {}", p)).unwrap_or_default());
+    Ok(())
+}
@@ -0,0 +1,37 @@
+// Compression Module - gzip / brotli response compression
+// Product lists, search results, reports, CSV exports and the XML feeds are text that shrinks
+// several times over. A response is compressed when the client accepts brotli or gzip, the body
+// is at least MIN_SIZE_BYTES and its content type is text-like. Images, PDFs and event streams
+// (live order tracking) pass through as they are, as do precompressed storefront files, which
+// already have a Content-Encoding
+
+use axum::http::{header, Extensions, HeaderMap, StatusCode, Version};
+use tower_http::compression::{
+    predicate::{Predicate, SizeAbove},
+    CompressionLayer,
+};
+
+const MIN_SIZE_BYTES: u16 = 1024; // Smaller bodies gain little and cost CPU on every request
+
+pub fn compression_layer() -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new().compress_when(SizeAbove::new(MIN_SIZE_BYTES).and(is_compressible))
+}
+
+// JSON, XML, CSV, HTML, JS and other text, but not event streams (compressing those would
+// buffer events)
+fn is_compressible(_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions) -> bool {
+    let Some(content_type) = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    if mime == "text/event-stream" {
+        return false;
+    }
+    mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime.as_str(),
+            "application/json" | "application/xml" | "application/javascript" | "application/wasm" | "image/svg+xml"
+        )
+}
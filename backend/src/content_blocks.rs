@@ -0,0 +1,285 @@
+// Content Blocks Module - Tiny CMS for storefront copy
+// Admins edit the hero banner, announcement bar and homepage sections as markdown or
+// HTML with an optional active window; the storefront fetches the rendered blocks
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use pulldown_cmark::{html, Options, Parser};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::AppState;
+
+// Where on the storefront a block is rendered
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
+pub enum Placement {
+    #[sqlx(rename = "hero")]
+    #[serde(rename = "hero")]
+    Hero,
+    #[sqlx(rename = "announcement")]
+    #[serde(rename = "announcement")]
+    Announcement,
+    #[sqlx(rename = "home_section")]
+    #[serde(rename = "home_section")]
+    HomeSection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
+pub enum ContentFormat {
+    #[default]
+    #[sqlx(rename = "markdown")]
+    #[serde(rename = "markdown")]
+    Markdown,
+    #[sqlx(rename = "html")]
+    #[serde(rename = "html")]
+    Html,
+}
+
+// Database model for content blocks
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ContentBlock {
+    pub id: i32,
+    pub key: String,
+    pub placement: Placement,
+    pub title: Option<String>,
+    pub format: ContentFormat,
+    pub body: String,
+    pub position: i32,
+    pub active: bool,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContentBlockInput {
+    pub key: String,
+    pub placement: Placement,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub format: ContentFormat,
+    pub body: String,
+    #[serde(default)]
+    pub position: i32,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+// What the storefront receives: the body rendered to HTML
+#[derive(Debug, Serialize)]
+pub struct PublicContentBlock {
+    pub key: String,
+    pub placement: Placement,
+    pub title: Option<String>,
+    pub html: String,
+    pub position: i32,
+}
+
+#[derive(Deserialize)]
+pub struct ContentBlockQuery {
+    pub placement: Option<Placement>,
+}
+
+impl ContentBlock {
+    pub fn render_html(&self) -> String {
+        match self.format {
+            ContentFormat::Html => self.body.clone(),
+            ContentFormat::Markdown => markdown_to_html(&self.body),
+        }
+    }
+}
+
+// Render markdown (with tables and strikethrough) to HTML
+pub fn markdown_to_html(markdown: &str) -> String {
+    let parser = Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH);
+    let mut out = String::new();
+    html::push_html(&mut out, parser);
+    out
+}
+
+impl From<ContentBlock> for PublicContentBlock {
+    fn from(block: ContentBlock) -> Self {
+        Self {
+            html: block.render_html(),
+            key: block.key,
+            placement: block.placement,
+            title: block.title,
+            position: block.position,
+        }
+    }
+}
+
+fn validate_input(input: &ContentBlockInput) -> Result<(), String> {
+    let key = input.key.trim();
+    if key.is_empty() || key.len() > 100 {
+        return Err("key must be 1-100 characters".to_string());
+    }
+    if !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err("key may only contain letters, digits, '-' and '_'".to_string());
+    }
+    if input.body.trim().is_empty() {
+        return Err("body is required".to_string());
+    }
+    if let (Some(starts_at), Some(ends_at)) = (input.starts_at, input.ends_at) {
+        if ends_at <= starts_at {
+            return Err("ends_at must be after starts_at".to_string());
+        }
+    }
+    Ok(())
+}
+
+pub fn content_block_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/content-blocks", get(list_live_blocks))
+        .route("/api/content-blocks/:key", get(get_live_block))
+        .route("/api/admin/content-blocks", get(list_blocks).post(create_block))
+        .route("/api/admin/content-blocks/:id", get(get_block).put(update_block).delete(delete_block))
+        .with_state(app_state)
+}
+
+async fn list_live_blocks(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ContentBlockQuery>,
+) -> Result<Json<Vec<PublicContentBlock>>, (StatusCode, String)> {
+    // Blocks that are active and inside their window right now
+    let blocks = sqlx::query_as!(
+        ContentBlock,
+        r#"SELECT id, key, placement AS "placement: Placement", title, format AS "format: ContentFormat", body, position, active, starts_at, ends_at, created_at, updated_at FROM content_blocks
+         WHERE active = TRUE AND (starts_at IS NULL OR starts_at <= NOW()) AND (ends_at IS NULL OR ends_at > NOW())
+           AND ($1::VARCHAR IS NULL OR placement = $1)
+         ORDER BY placement, position, id"#,
+        query.placement as _,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(blocks.into_iter().map(PublicContentBlock::from).collect()))
+}
+
+async fn get_live_block(
+    State(app_state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+) -> Result<Json<PublicContentBlock>, (StatusCode, String)> {
+    sqlx::query_as!(
+        ContentBlock,
+        r#"SELECT id, key, placement AS "placement: Placement", title, format AS "format: ContentFormat", body, position, active, starts_at, ends_at, created_at, updated_at FROM content_blocks
+         WHERE key = $1 AND active = TRUE AND (starts_at IS NULL OR starts_at <= NOW()) AND (ends_at IS NULL OR ends_at > NOW())"#,
+        key,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .map(|block| Json(block.into()))
+    .ok_or((StatusCode::NOT_FOUND, "Content block not found".to_string()))
+}
+
+async fn list_blocks(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ContentBlock>>, (StatusCode, String)> {
+    let blocks = sqlx::query_as!(ContentBlock, r#"SELECT id, key, placement AS "placement: Placement", title, format AS "format: ContentFormat", body, position, active, starts_at, ends_at, created_at, updated_at FROM content_blocks ORDER BY placement, position, id"#)
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(blocks))
+}
+
+async fn get_block(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<ContentBlock>, (StatusCode, String)> {
+    sqlx::query_as!(ContentBlock, r#"SELECT id, key, placement AS "placement: Placement", title, format AS "format: ContentFormat", body, position, active, starts_at, ends_at, created_at, updated_at FROM content_blocks WHERE id = $1"#, id)
+        .fetch_optional(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "Content block not found".to_string()))
+}
+
+async fn create_block(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<ContentBlockInput>,
+) -> Result<(StatusCode, Json<ContentBlock>), (StatusCode, String)> {
+    validate_input(&input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let block = sqlx::query_as!(
+        ContentBlock,
+        r#"INSERT INTO content_blocks (key, placement, title, format, body, position, active, starts_at, ends_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id, key, placement AS "placement: Placement", title, format AS "format: ContentFormat", body, position, active, starts_at, ends_at, created_at, updated_at"#,
+        input.key.trim(),
+        input.placement as _,
+        input.title.as_deref().map(str::trim).filter(|t| !t.is_empty()),
+        input.format as _,
+        &input.body,
+        input.position,
+        input.active,
+        input.starts_at,
+        input.ends_at,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?;
+    Ok((StatusCode::CREATED, Json(block)))
+}
+
+async fn update_block(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(input): Json<ContentBlockInput>,
+) -> Result<Json<ContentBlock>, (StatusCode, String)> {
+    validate_input(&input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    sqlx::query_as!(
+        ContentBlock,
+        r#"UPDATE content_blocks
+         SET key = $1, placement = $2, title = $3, format = $4, body = $5, position = $6,
+             active = $7, starts_at = $8, ends_at = $9, updated_at = NOW()
+         WHERE id = $10 RETURNING id, key, placement AS "placement: Placement", title, format AS "format: ContentFormat", body, position, active, starts_at, ends_at, created_at, updated_at"#,
+        input.key.trim(),
+        input.placement as _,
+        input.title.as_deref().map(str::trim).filter(|t| !t.is_empty()),
+        input.format as _,
+        &input.body,
+        input.position,
+        input.active,
+        input.starts_at,
+        input.ends_at,
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?
+    .map(Json)
+    .ok_or((StatusCode::NOT_FOUND, "Content block not found".to_string()))
+}
+
+async fn delete_block(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM content_blocks WHERE id = $1", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Content block not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,156 @@
+// Coupons Module - Single-use discount codes
+// Coupons are issued by other features (e.g. referral rewards), applied to the
+// payment intent amount at checkout, held by that checkout while the customer pays and
+// marked redeemed when the order is created
+
+use axum::http::StatusCode;
+use rand::Rng;
+use serde::Serialize;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+
+use crate::unit_of_work::WorkError;
+
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789"; // No 0/O or 1/I look-alikes
+pub use crate::cart_math::MIN_CHARGE_CENTS;
+
+pub const METADATA_KEY: &str = "coupon_code";
+
+// Database model for coupons
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Coupon {
+    pub id: Uuid,
+    pub code: String,
+    pub amount_off: i64, // in cents
+    pub customer_id: Option<Uuid>,
+    pub source: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub redeemed_at: Option<DateTime<Utc>>,
+    pub redeemed_order_id: Option<Uuid>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+// Random human-friendly code, e.g. "REF-7KQ2M9XD"
+pub fn generate_code(prefix: &str) -> String {
+    let mut rng = rand::thread_rng();
+    let suffix: String = (0..8)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect();
+    format!("{}-{}", prefix, suffix)
+}
+
+// Issue a new single-use coupon
+pub async fn issue_coupon<'e, E>(
+    executor: E,
+    prefix: &str,
+    amount_off: i64,
+    customer_id: Option<Uuid>,
+    source: &str,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<Coupon, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    sqlx::query_as!(
+        Coupon,
+        "INSERT INTO coupons (code, amount_off, customer_id, source, expires_at)
+         VALUES ($1, $2, $3, $4, $5) RETURNING id, code, amount_off, customer_id, source, expires_at, redeemed_at, redeemed_order_id, created_at",
+        generate_code(prefix),
+        amount_off,
+        customer_id,
+        source,
+        expires_at,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+// Look up a coupon the customer (None for guests) can still use
+pub async fn find_redeemable(pool: &sqlx::PgPool, code: &str, customer_id: Option<Uuid>) -> Result<Coupon, String> {
+    let coupon = sqlx::query_as!(Coupon, "SELECT id, code, amount_off, customer_id, source, expires_at, redeemed_at, redeemed_order_id, created_at FROM coupons WHERE code = UPPER($1)", code.trim())
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?
+        .ok_or("Unknown coupon code")?;
+    if coupon.customer_id.is_some() && coupon.customer_id != customer_id {
+        return Err("This coupon belongs to another account".to_string());
+    }
+    if coupon.redeemed_at.is_some() {
+        return Err("Coupon has already been used".to_string());
+    }
+    if coupon.expires_at.is_some_and(|at| at < Utc::now()) {
+        return Err("Coupon has expired".to_string());
+    }
+    Ok(coupon)
+}
+
+// Mark a coupon used by the customer's (None for guests) order; returns false if it was already
+// redeemed, an open checkout holds it or it was issued to another customer
+pub async fn redeem(
+    executor: impl sqlx::PgExecutor<'_>,
+    code: &str,
+    order_id: Uuid,
+    customer_id: Option<Uuid>,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query!(
+        "UPDATE coupons SET redeemed_at = NOW(), redeemed_order_id = $2
+         WHERE code = UPPER($1) AND redeemed_at IS NULL AND held_cart_id IS NULL
+           AND (customer_id IS NULL OR customer_id = $3)",
+        code.trim(),
+        order_id,
+        customer_id,
+    )
+    .execute(executor)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+// Hold the coupon for a checkout until its order is created, as part of the checkout's unit of
+// work. Fails the checkout with 409 when another checkout holds or redeemed the coupon since this
+// one was priced
+pub async fn hold_for_cart(
+    conn: &mut sqlx::PgConnection,
+    cart_id: Uuid,
+    code: &str,
+) -> Result<(), WorkError<(StatusCode, String)>> {
+    let res = sqlx::query!(
+        "UPDATE coupons SET held_cart_id = $2
+         WHERE code = UPPER($1) AND redeemed_at IS NULL AND held_cart_id IS NULL",
+        code.trim(),
+        cart_id,
+    )
+    .execute(&mut *conn)
+    .await?;
+    if res.rows_affected() == 0 {
+        return Err(WorkError::Aborted((
+            StatusCode::CONFLICT,
+            "Coupon is already in use by another checkout".to_string(),
+        )));
+    }
+    Ok(())
+}
+
+// Let go of the coupon a checkout held, so it can be used again
+pub async fn release_hold(executor: impl sqlx::PgExecutor<'_>, cart_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE coupons SET held_cart_id = NULL WHERE held_cart_id = $1 AND redeemed_at IS NULL",
+        cart_id,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// Redeem the coupon the checkout held once its order exists. None when it holds none any more:
+// the hold was released, or another Stripe event already created the order
+pub async fn redeem_held(executor: impl sqlx::PgExecutor<'_>, cart_id: Uuid, order_id: Uuid) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "UPDATE coupons SET redeemed_at = NOW(), redeemed_order_id = $2
+         WHERE held_cart_id = $1 AND redeemed_at IS NULL
+         RETURNING code",
+        cart_id,
+        order_id,
+    )
+    .fetch_optional(executor)
+    .await
+}
@@ -0,0 +1,114 @@
+// Customer Authentication Module - Storefront customer login
+// Customers set a password through the reset flow and log in for a bearer token.
+// Tokens are signed with a key derived from the current JWT signing key so they never pass as admin tokens.
+
+use argon2::{password_hash::{PasswordHash, PasswordVerifier}, Argon2};
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRequestParts, State},
+    http::{request::Parts, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use axum_extra::{
+    headers::{authorization::Bearer, Authorization},
+    TypedHeader,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::jwt_keys;
+use crate::pii;
+use crate::AppState;
+
+const TOKEN_TTL_HOURS: i64 = 24;
+
+#[derive(Deserialize)]
+pub struct CustomerLoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Serialize)]
+pub struct CustomerLoginResponse {
+    pub token: String,
+    pub customer_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CustomerClaims {
+    sub: Uuid,
+    exp: usize,
+}
+
+pub struct AuthenticatedCustomer {
+    pub customer_id: Uuid,
+}
+
+fn customer_key(secret: &str) -> Vec<u8> {
+    format!("customer:{}", secret).into_bytes()
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthenticatedCustomer
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let TypedHeader(Authorization(bearer)) = TypedHeader::<Authorization<Bearer>>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| (StatusCode::UNAUTHORIZED, "Missing or invalid Authorization header".to_string()))?;
+        verify_customer_token(bearer.token())
+    }
+}
+
+// Check a customer JWT from any source (header, or query string where headers can't be set)
+pub fn verify_customer_token(token: &str) -> Result<AuthenticatedCustomer, (StatusCode, String)> {
+    let claims: CustomerClaims = jwt_keys::verify(token, customer_key)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid token".to_string()))?;
+    Ok(AuthenticatedCustomer {
+        customer_id: claims.sub,
+    })
+}
+
+pub fn customer_auth_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/customers/login", post(login_customer))
+        .with_state(app_state)
+}
+
+async fn login_customer(
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<CustomerLoginRequest>,
+) -> Result<Json<CustomerLoginResponse>, (StatusCode, String)> {
+    let invalid = || (StatusCode::UNAUTHORIZED, "Invalid email or password".to_string());
+    let customer: Option<(Uuid, Option<String>, bool)> = sqlx::query!(
+        "SELECT id, password_hash, disabled FROM customers WHERE email_hash = $1",
+        pii::email_hash(&req.email),
+    )
+    .map(|r| (r.id, r.password_hash, r.disabled))
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let (customer_id, password_hash) = match customer {
+        Some((id, Some(hash), false)) => (id, hash),
+        _ => return Err(invalid()),
+    };
+
+    let parsed_hash = PasswordHash::new(&password_hash)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Hash error: {}", e)))?;
+    if Argon2::default().verify_password(req.password.as_bytes(), &parsed_hash).is_err() {
+        return Err(invalid());
+    }
+
+    let claims = CustomerClaims {
+        sub: customer_id,
+        exp: (sqlx::types::chrono::Utc::now() + chrono::Duration::hours(TOKEN_TTL_HOURS)).timestamp() as usize,
+    };
+    let token = jwt_keys::sign(&claims, customer_key)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("JWT error: {}", e)))?;
+    Ok(Json(CustomerLoginResponse { token, customer_id }))
+}
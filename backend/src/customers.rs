@@ -0,0 +1,400 @@
+// Customers Module - Customer records and admin customer management
+// Customers are created from checkout emails; admins can search them, review
+// lifetime value and orders, tag them, send password resets and disable accounts
+
+use argon2::{
+    password_hash::{PasswordHasher, SaltString},
+    Argon2,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post, put},
+    Json, Router,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::admin_orders::OrderDetail;
+use crate::app_config;
+use crate::branding::load_branding;
+use crate::environment_mode;
+use crate::lettre_email::EmailConfig;
+use crate::pii::{self, Encrypted};
+use crate::repos::OrderListFilter;
+use crate::webhooks::OrderStatus;
+use crate::AppState;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+const PASSWORD_RESET_TTL_MINUTES: i32 = 60;
+const MIN_PASSWORD_LEN: usize = 8;
+
+// Database model for customers
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Customer {
+    pub id: Uuid,
+    pub email: Encrypted,
+    pub name: Option<String>,
+    pub phone: Option<Encrypted>,
+    pub phone_verified_at: Option<DateTime<Utc>>,
+    pub timezone: Option<String>,
+    #[serde(skip_serializing)]
+    pub password_hash: Option<String>,
+    pub tags: Vec<String>,
+    pub disabled: bool,
+    #[serde(skip_serializing)]
+    pub password_reset_token_hash: Option<String>,
+    #[serde(skip_serializing)]
+    pub password_reset_expires_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// query_as! for Customer rows, with the columns between `$before` and `$after` (see product_query!)
+macro_rules! customer_query {
+    ($before:literal, $after:literal $(, $arg:expr)* $(,)?) => {
+        sqlx::query_as!(
+            $crate::customers::Customer,
+            $before
+                + " id, email AS \"email: crate::pii::Encrypted\", name, phone AS \"phone: crate::pii::Encrypted\",
+                    phone_verified_at, timezone, password_hash, tags, disabled, password_reset_token_hash,
+                    password_reset_expires_at, created_at, updated_at "
+                + $after
+            $(, $arg)*
+        )
+    };
+}
+pub(crate) use customer_query;
+
+// Customer row with order statistics for the admin list
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct CustomerSummary {
+    pub id: Uuid,
+    pub email: Encrypted,
+    pub name: Option<String>,
+    pub phone: Option<Encrypted>,
+    pub tags: Vec<String>,
+    pub disabled: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub order_count: i64,
+    pub lifetime_value: i64, // in cents, completed orders only
+    pub last_order_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct CustomerDetail {
+    #[serde(flatten)]
+    pub customer: Customer,
+    pub has_password: bool,
+    pub password_reset_pending: bool, // A reset link was sent and hasn't expired
+    pub order_count: i64,
+    pub lifetime_value: i64, // in cents, completed orders only
+    pub store_credit_balance: i64, // in cents
+    pub loyalty_points_balance: i64,
+    pub orders: Vec<OrderDetail>, // Newest first, with their items
+}
+
+#[derive(Deserialize)]
+pub struct CustomerSearchQuery {
+    pub q: Option<String>, // Matches part of the name, or the exact email
+    pub tag: Option<String>,
+    pub disabled: Option<bool>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub mode: Option<String>, // live, test or all orders in the stats; defaults to the server's mode
+}
+
+#[derive(Deserialize)]
+pub struct CustomerTagsInput {
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetConfirm {
+    pub token: String,
+    pub password: String,
+}
+
+// Find or create the customer for a checkout email, returning its id
+pub async fn upsert_customer(
+    executor: impl sqlx::PgExecutor<'_>,
+    email: &str,
+    name: Option<&str>,
+) -> Result<Uuid, sqlx::Error> {
+    let email = email.trim().to_lowercase();
+    sqlx::query_scalar!(
+        "INSERT INTO customers (email, email_hash, name) VALUES ($1, $2, $3)
+         ON CONFLICT (email_hash) DO UPDATE SET name = COALESCE(customers.name, EXCLUDED.name), updated_at = NOW()
+         RETURNING id",
+        Encrypted::from(email.as_str()) as _,
+        pii::email_hash(&email),
+        name,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+// Trimmed, lowercase, de-duplicated tags
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = tags
+        .iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    normalized.sort();
+    normalized.dedup();
+    normalized
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub fn customer_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/customers", get(list_customers))
+        .route("/api/admin/customers/:id", get(get_customer))
+        .route("/api/admin/customers/:id/tags", put(set_customer_tags))
+        .route("/api/admin/customers/:id/password-reset", post(send_password_reset))
+        .route("/api/admin/customers/:id/disable", post(disable_customer))
+        .route("/api/admin/customers/:id/enable", post(enable_customer))
+        .route("/api/customers/password-reset/confirm", post(confirm_password_reset))
+        .with_state(app_state)
+}
+
+async fn list_customers(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<CustomerSearchQuery>,
+) -> Result<Json<Vec<CustomerSummary>>, (StatusCode, String)> {
+    let q = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    let search = q.map(|q| format!("%{}%", q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")));
+    let email_hash = q.map(pii::email_hash); // Emails are encrypted, so only an exact match can find one
+    let tag = query.tag.as_deref().map(|t| t.trim().to_lowercase());
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let livemode = environment_mode::livemode_filter(query.mode.as_deref(), app_state.mode)?;
+
+    let customers = sqlx::query_as!(
+        CustomerSummary,
+        r#"SELECT c.id, c.email AS "email: Encrypted", c.name, c.phone AS "phone: Encrypted", c.tags, c.disabled, c.created_at,
+                COUNT(o.id) AS "order_count!",
+                COALESCE(SUM(o.total_amount) FILTER (WHERE o.status = 'completed'), 0)::BIGINT AS "lifetime_value!",
+                MAX(o.created_at) AS last_order_at
+         FROM customers c
+         LEFT JOIN orders o ON o.customer_id = c.id AND ($7::BOOLEAN IS NULL OR o.livemode = $7)
+         WHERE ($1::TEXT IS NULL OR c.email_hash = $6 OR c.name ILIKE $1)
+           AND ($2::TEXT IS NULL OR $2 = ANY(c.tags))
+           AND ($3::BOOLEAN IS NULL OR c.disabled = $3)
+         GROUP BY c.id
+         ORDER BY c.created_at DESC
+         LIMIT $4 OFFSET $5"#,
+        search,
+        tag,
+        query.disabled,
+        limit,
+        offset,
+        email_hash,
+        livemode,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    Ok(Json(customers))
+}
+
+async fn load_customer(pool: &sqlx::PgPool, id: Uuid) -> Result<Customer, (StatusCode, String)> {
+    customer_query!("SELECT", "FROM customers WHERE id = $1", id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Customer not found".to_string()))
+}
+
+async fn get_customer(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<CustomerDetail>, (StatusCode, String)> {
+    let customer = load_customer(&app_state.pool, id).await?;
+    let filter = OrderListFilter { customer_id: Some(id), ..Default::default() };
+    let orders = app_state.repos.orders.list_with_items(&filter)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    // Every order is listed, but the stats only count the server's mode
+    let live = app_state.mode.is_live();
+    let order_count = orders.iter().filter(|o| o.order.livemode == live).count() as i64;
+    let lifetime_value = orders
+        .iter()
+        .filter(|o| o.order.livemode == live && o.order.status == OrderStatus::Completed)
+        .map(|o| o.order.total_amount)
+        .sum();
+
+    let store_credit_balance = crate::store_credit::balance(&*app_state.pool, id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    let loyalty_points_balance = crate::loyalty::balance(&*app_state.pool, id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    let password_reset_pending = customer.password_reset_token_hash.is_some()
+        && customer.password_reset_expires_at.is_some_and(|expires| expires > Utc::now());
+
+    Ok(Json(CustomerDetail {
+        has_password: customer.password_hash.is_some(),
+        password_reset_pending,
+        customer,
+        order_count,
+        lifetime_value,
+        store_credit_balance,
+        loyalty_points_balance,
+        orders,
+    }))
+}
+
+async fn set_customer_tags(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(input): Json<CustomerTagsInput>,
+) -> Result<Json<Customer>, (StatusCode, String)> {
+    let customer = customer_query!(
+        "UPDATE customers SET tags = $1, updated_at = NOW() WHERE id = $2 RETURNING",
+        "",
+        &normalize_tags(&input.tags),
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Customer not found".to_string()))?;
+    Ok(Json(customer))
+}
+
+// Email the customer a one-time link to set a new password
+async fn send_password_reset(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let customer = load_customer(&app_state.pool, id).await?;
+    if customer.disabled {
+        return Err((StatusCode::CONFLICT, "Customer account is disabled".to_string()));
+    }
+    let config = EmailConfig::from_env()
+        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Email not configured".to_string()))?;
+
+    let token_bytes: [u8; 32] = rand::thread_rng().gen();
+    let token = hex::encode(token_bytes);
+    sqlx::query!(
+        "UPDATE customers
+         SET password_reset_token_hash = $1,
+             password_reset_expires_at = NOW() + make_interval(mins => $2),
+             updated_at = NOW()
+         WHERE id = $3",
+        hash_token(&token),
+        PASSWORD_RESET_TTL_MINUTES,
+        id,
+    )
+    .execute(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    let storefront_url = &app_config::get().server.storefront_url;
+    let reset_url = format!("{}/reset-password?token={}", storefront_url.trim_end_matches('/'), token);
+    let html_body = load_branding(&app_state.pool).await.email_html(
+        "Reset your password",
+        &format!(
+            "<p>Hi{},</p><p>A password reset was requested for your account.</p><p><a href=\"{}\" class=\"button\">Set a new password</a></p><p>This link expires in {} minutes. If you didn't expect this email you can ignore it.</p>",
+            customer.name.as_ref().map(|n| format!(" {}", n)).unwrap_or_default(),
+            reset_url,
+            PASSWORD_RESET_TTL_MINUTES
+        ),
+    );
+    crate::webhooks::stripe::send_html_email(&app_state.pool, &config, &customer.email, "Reset your password", &html_body)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+
+    tracing::info!("Password reset email sent to {}", customer.email);
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn set_disabled(pool: &sqlx::PgPool, id: Uuid, disabled: bool) -> Result<Json<Customer>, (StatusCode, String)> {
+    // Disabling also voids any outstanding password reset link
+    let customer = customer_query!(
+        "UPDATE customers
+         SET disabled = $1,
+             password_reset_token_hash = CASE WHEN $1 THEN NULL ELSE password_reset_token_hash END,
+             password_reset_expires_at = CASE WHEN $1 THEN NULL ELSE password_reset_expires_at END,
+             updated_at = NOW()
+         WHERE id = $2 RETURNING",
+        "",
+        disabled,
+        id,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Customer not found".to_string()))?;
+    Ok(Json(customer))
+}
+
+async fn disable_customer(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Customer>, (StatusCode, String)> {
+    set_disabled(&app_state.pool, id, true).await
+}
+
+async fn enable_customer(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Customer>, (StatusCode, String)> {
+    set_disabled(&app_state.pool, id, false).await
+}
+
+// Public endpoint used by the storefront's reset-password page
+async fn confirm_password_reset(
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<PasswordResetConfirm>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if req.password.chars().count() < MIN_PASSWORD_LEN {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Password must be at least {} characters", MIN_PASSWORD_LEN),
+        ));
+    }
+
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = Argon2::default()
+        .hash_password(req.password.as_bytes(), &salt)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Hash error: {}", e)))?
+        .to_string();
+
+    let res = sqlx::query!(
+        "UPDATE customers
+         SET password_hash = $1, password_reset_token_hash = NULL, password_reset_expires_at = NULL, updated_at = NOW()
+         WHERE password_reset_token_hash = $2 AND password_reset_expires_at > NOW() AND disabled = FALSE",
+        &password_hash,
+        hash_token(req.token.trim()),
+    )
+    .execute(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::BAD_REQUEST, "Invalid or expired reset token".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
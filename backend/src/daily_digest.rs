@@ -0,0 +1,576 @@
+// Daily Digest Module - Yesterday's store summary, emailed to the merchant every morning
+// Once DAILY_DIGEST_HOUR has passed in STORE_TIMEZONE, the previous day's orders, revenue, top
+// products, refunds and failures (webhooks, dead jobs, outbound webhook deliveries, emails) are
+// emailed to DAILY_DIGEST_RECIPIENTS. Only the server's own mode (live or test) is counted.
+// Every email has a one-click opt-out link; admins can also opt recipients out and back in,
+// preview any day's digest and send it on demand without moving the schedule
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Html,
+    routing::{get, post, put},
+    Json, Router,
+};
+use chrono::{Days, NaiveDate, NaiveTime, TimeZone, Timelike};
+use chrono_tz::Tz;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::types::chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::branding::{escape_html, load_branding};
+use crate::environment_mode::EnvironmentMode;
+use crate::notifications::{queue_email, EmailPriority};
+use crate::pii::{self, Encrypted};
+use crate::AppState;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const TOP_PRODUCTS: i64 = 5;
+
+// Orders in these statuses were placed and paid (or will be, on net terms); refunded ones count
+// towards the day they were placed and again under refunds on the day of the refund
+pub(crate) const PLACED_STATUSES: [&str; 4] = ["authorized", "awaiting_payment", "completed", "refunded"];
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct CurrencyTotal {
+    pub currency: String,
+    pub count: i64,
+    pub amount: i64, // in cents
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TopProduct {
+    pub product_id: Option<i32>, // None once the product is deleted
+    pub name: String,
+    pub currency: String,
+    pub quantity: i64,
+    pub revenue: i64, // in cents
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DigestFailures {
+    pub webhook_events: i64,     // Payment provider events that failed to process
+    pub dead_jobs: i64,          // Background jobs out of retries
+    pub webhook_deliveries: i64, // Outbound webhooks that couldn't be delivered
+    pub emails: i64,             // Emails that failed or bounced
+}
+
+impl DigestFailures {
+    fn total(&self) -> i64 {
+        self.webhook_events + self.dead_jobs + self.webhook_deliveries + self.emails
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DailySummary {
+    pub date: NaiveDate,
+    pub timezone: String,
+    pub livemode: bool,
+    pub order_count: i64,
+    pub revenue: Vec<CurrencyTotal>, // Orders placed, per currency
+    pub top_products: Vec<TopProduct>,
+    pub refunds: Vec<CurrencyTotal>, // Orders refunded, per currency
+    pub failures: DigestFailures,
+}
+
+#[derive(Serialize)]
+pub struct DigestSent {
+    pub sent_to: Vec<String>,
+    pub opted_out: Vec<String>, // Recipients skipped because they opted out
+    pub summary: DailySummary,
+}
+
+#[derive(Serialize)]
+pub struct DigestRecipient {
+    pub email: String,
+    pub opted_out_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct DigestQuery {
+    pub date: Option<NaiveDate>, // Defaults to yesterday
+}
+
+#[derive(Deserialize)]
+pub struct SendDigestRequest {
+    #[serde(default)]
+    pub date: Option<NaiveDate>, // Defaults to yesterday
+    #[serde(default)]
+    pub recipients: Option<Vec<String>>, // Defaults to DAILY_DIGEST_RECIPIENTS
+}
+
+#[derive(Deserialize)]
+pub struct OptOutRequest {
+    pub opted_out: bool,
+}
+
+#[derive(Deserialize)]
+pub struct OptOutQuery {
+    pub token: String,
+}
+
+pub(crate) fn store_timezone() -> Tz {
+    app_config::get().notifications.store_timezone.parse().unwrap_or(Tz::UTC)
+}
+
+fn yesterday(tz: Tz) -> NaiveDate {
+    let today = Utc::now().with_timezone(&tz).date_naive();
+    today - Days::new(1)
+}
+
+// The day's [start, end) in UTC; a midnight skipped by a DST change starts at the next valid time
+fn day_window(date: NaiveDate, tz: Tz) -> (DateTime<Utc>, DateTime<Utc>) {
+    let midnight = |date: NaiveDate| {
+        let local = date.and_time(NaiveTime::MIN);
+        tz.from_local_datetime(&local)
+            .earliest()
+            .or_else(|| tz.from_local_datetime(&(local + chrono::Duration::hours(1))).earliest())
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+    };
+    (midnight(date), midnight(date + Days::new(1)))
+}
+
+pub async fn daily_summary(pool: &sqlx::PgPool, date: NaiveDate, livemode: bool) -> Result<DailySummary, sqlx::Error> {
+    let tz = store_timezone();
+    let (from, to) = day_window(date, tz);
+
+    let revenue = sqlx::query_as!(
+        CurrencyTotal,
+        r#"SELECT currency, COUNT(*) AS "count!", COALESCE(SUM(total_amount), 0)::BIGINT AS "amount!" FROM orders
+         WHERE created_at >= $1 AND created_at < $2 AND livemode = $3 AND status = ANY($4)
+         GROUP BY currency ORDER BY "amount!" DESC"#,
+        from,
+        to,
+        livemode,
+        &PLACED_STATUSES[..] as _,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let top_products = sqlx::query_as!(
+        TopProduct,
+        r#"SELECT i.product_id, i.product_name AS name, o.currency,
+                SUM(i.quantity)::BIGINT AS "quantity!", SUM(i.total_price)::BIGINT AS "revenue!"
+         FROM order_items i JOIN orders o ON o.id = i.order_id
+         WHERE o.created_at >= $1 AND o.created_at < $2 AND o.livemode = $3 AND o.status = ANY($4)
+         GROUP BY i.product_id, i.product_name, o.currency
+         ORDER BY "revenue!" DESC, "quantity!" DESC LIMIT $5"#,
+        from,
+        to,
+        livemode,
+        &PLACED_STATUSES[..] as _,
+        TOP_PRODUCTS,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    // Refunds mark the order refunded, so the refund's day is when the order last changed
+    let refunds = sqlx::query_as!(
+        CurrencyTotal,
+        r#"SELECT currency, COUNT(*) AS "count!", COALESCE(SUM(total_amount), 0)::BIGINT AS "amount!" FROM orders
+         WHERE status = 'refunded' AND updated_at >= $1 AND updated_at < $2 AND livemode = $3
+         GROUP BY currency ORDER BY "amount!" DESC"#,
+        from,
+        to,
+        livemode,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let failures = sqlx::query_as!(
+        DigestFailures,
+        r#"SELECT
+            (SELECT COUNT(*) FROM webhook_events
+             WHERE error_message IS NOT NULL AND created_at >= $1 AND created_at < $2 AND livemode = $3) AS "webhook_events!",
+            (SELECT COUNT(*) FROM jobs WHERE status = 'dead' AND dead_at >= $1 AND dead_at < $2) AS "dead_jobs!",
+            (SELECT COUNT(*) FROM webhook_deliveries
+             WHERE status = 'failed' AND created_at >= $1 AND created_at < $2) AS "webhook_deliveries!",
+            (SELECT COUNT(*) FROM email_log
+             WHERE status IN ('failed', 'bounced') AND updated_at >= $1 AND updated_at < $2) AS "emails!""#,
+        from,
+        to,
+        livemode,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(DailySummary {
+        date,
+        timezone: tz.name().to_string(),
+        livemode,
+        order_count: revenue.iter().map(|r| r.count).sum(),
+        revenue,
+        top_products,
+        refunds,
+        failures,
+    })
+}
+
+fn format_money(cents: i64, currency: &str) -> String {
+    format!("{:.2} {}", cents as f64 / 100.0, currency.to_uppercase())
+}
+
+fn totals_text(totals: &[CurrencyTotal]) -> String {
+    if totals.is_empty() {
+        return "0.00".to_string();
+    }
+    totals
+        .iter()
+        .map(|t| format_money(t.amount, &t.currency))
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+fn digest_subject(summary: &DailySummary) -> String {
+    format!(
+        "Daily summary for {}: {} order{}, {}",
+        summary.date.format("%a %b %-d"),
+        summary.order_count,
+        if summary.order_count == 1 { "" } else { "s" },
+        totals_text(&summary.revenue)
+    )
+}
+
+// Body of the digest email; the branding wraps it in the store's header and footer
+fn digest_html(summary: &DailySummary, opt_out_url: Option<&str>) -> String {
+    let mut html = String::new();
+    if !summary.livemode {
+        html.push_str("<p><strong>Test mode:</strong> these are test orders.</p>");
+    }
+    html.push_str(&format!(
+        "<table cellpadding=\"6\" style=\"border-collapse:collapse;width:100%\">
+<tr><td>Orders</td><td align=\"right\"><strong>{}</strong></td></tr>
+<tr><td>Revenue</td><td align=\"right\"><strong>{}</strong></td></tr>
+<tr><td>Refunds</td><td align=\"right\">{} ({})</td></tr>
+<tr><td>Failures</td><td align=\"right\">{}</td></tr>
+</table>",
+        summary.order_count,
+        escape_html(&totals_text(&summary.revenue)),
+        summary.refunds.iter().map(|r| r.count).sum::<i64>(),
+        escape_html(&totals_text(&summary.refunds)),
+        summary.failures.total()
+    ));
+
+    html.push_str("<h3>Top products</h3>");
+    if summary.top_products.is_empty() {
+        html.push_str("<p>No sales.</p>");
+    } else {
+        let rows: String = summary
+            .top_products
+            .iter()
+            .map(|p| {
+                format!(
+                    "<tr><td>{}</td><td align=\"right\">{}</td><td align=\"right\">{}</td></tr>",
+                    escape_html(&p.name),
+                    p.quantity,
+                    escape_html(&format_money(p.revenue, &p.currency))
+                )
+            })
+            .collect();
+        html.push_str(&format!(
+            "<table cellpadding=\"6\" style=\"border-collapse:collapse;width:100%\">
+<tr><th align=\"left\">Product</th><th align=\"right\">Sold</th><th align=\"right\">Revenue</th></tr>{}</table>",
+            rows
+        ));
+    }
+
+    let failures = &summary.failures;
+    if failures.total() > 0 {
+        html.push_str("<h3>Failures</h3><ul>");
+        for (count, what) in [
+            (failures.webhook_events, "payment webhook events failed to process"),
+            (failures.dead_jobs, "background jobs ran out of retries"),
+            (failures.webhook_deliveries, "outbound webhooks couldn't be delivered"),
+            (failures.emails, "emails failed or bounced"),
+        ] {
+            if count > 0 {
+                html.push_str(&format!("<li>{} {}</li>", count, what));
+            }
+        }
+        html.push_str("</ul>");
+    }
+
+    if let Some(url) = opt_out_url {
+        html.push_str(&format!(
+            "<p style=\"font-size:12px;color:#888\">Times are {}. <a href=\"{}\">Stop sending me this digest</a></p>",
+            escape_html(&summary.timezone),
+            escape_html(url)
+        ));
+    }
+    html
+}
+
+// Opt-out links are signed with JWT_SECRET and don't expire
+fn opt_out_signature(email: &str) -> Result<HmacSha256, String> {
+    let mut mac = HmacSha256::new_from_slice(app_config::get().auth.jwt_secret.expose().as_bytes())
+        .map_err(|e| format!("Failed to create HMAC: {}", e))?;
+    mac.update(format!("digest_opt_out:{}", email).as_bytes());
+    Ok(mac)
+}
+
+// Token format: "<hex email>.<hex HMAC-SHA256>"
+fn opt_out_url(email: &str) -> Result<String, String> {
+    let signature = opt_out_signature(email)?.finalize().into_bytes();
+    Ok(format!(
+        "{}/api/digest/opt-out?token={}.{}",
+        app_config::get().server.public_api_url.trim_end_matches('/'),
+        hex::encode(email),
+        hex::encode(signature)
+    ))
+}
+
+fn verify_opt_out_token(token: &str) -> Result<String, (StatusCode, String)> {
+    let invalid = || (StatusCode::UNAUTHORIZED, "Invalid opt-out link".to_string());
+    let (email, signature) = token.split_once('.').ok_or_else(invalid)?;
+    let email = hex::decode(email).ok().and_then(|e| String::from_utf8(e).ok()).ok_or_else(invalid)?;
+    let signature = hex::decode(signature).map_err(|_| invalid())?;
+    opt_out_signature(&email)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .verify_slice(&signature)
+        .map_err(|_| invalid())?;
+    Ok(email)
+}
+
+// Trimmed, lowercase, de-duplicated addresses
+fn normalize_recipients(recipients: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = recipients
+        .iter()
+        .map(|r| r.trim().to_lowercase())
+        .filter(|r| !r.is_empty())
+        .collect();
+    normalized.sort();
+    normalized.dedup();
+    normalized
+}
+
+// Build the day's digest and queue it for every recipient who hasn't opted out
+pub async fn send_digest(pool: &sqlx::PgPool, date: NaiveDate, recipients: &[String]) -> Result<DigestSent, String> {
+    let recipients = normalize_recipients(recipients);
+    let hashes: Vec<String> = recipients.iter().map(|r| pii::email_hash(r)).collect();
+    let opted_out: Vec<String> = sqlx::query_scalar!(
+        r#"SELECT email AS "email: Encrypted" FROM daily_digest_opt_outs WHERE email_hash = ANY($1)"#,
+        &hashes,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))?
+    .into_iter()
+    .map(Encrypted::into_inner)
+    .collect();
+    let summary = daily_summary(pool, date, EnvironmentMode::from_config().is_live())
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+
+    let subject = digest_subject(&summary);
+    let branding = load_branding(pool).await;
+    let mut sent_to = Vec::new();
+    for recipient in recipients.iter().filter(|r| !opted_out.contains(r)) {
+        let html_body = branding.email_html("Daily summary", &digest_html(&summary, Some(&opt_out_url(recipient)?)));
+        queue_email(pool, recipient, &subject, &html_body, EmailPriority::Immediate)
+            .await
+            .map_err(|e| format!("Failed to queue digest for {}: {}", recipient, e))?;
+        sent_to.push(recipient.clone());
+    }
+    Ok(DigestSent { sent_to, opted_out, summary })
+}
+
+// Claim a day's digest; false when it was already sent (or is being sent by another server)
+async fn claim_day(pool: &sqlx::PgPool, date: NaiveDate) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query!("INSERT INTO daily_digests (digest_date) VALUES ($1) ON CONFLICT DO NOTHING", date)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+async fn record_day(pool: &sqlx::PgPool, date: NaiveDate, result: &Result<DigestSent, String>) -> Result<(), sqlx::Error> {
+    let (summary, sent_to, error) = match result {
+        Ok(sent) => (serde_json::to_value(&sent.summary).ok(), sent.sent_to.clone(), None),
+        Err(e) => (None, Vec::new(), Some(e.clone())),
+    };
+    sqlx::query!(
+        "UPDATE daily_digests SET summary = $1, sent_to = $2, error = $3 WHERE digest_date = $4",
+        summary,
+        &sent_to,
+        error,
+        date,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Send yesterday's digest once DAILY_DIGEST_HOUR has passed (the send_daily_digest scheduled
+// task, which checks every few minutes). Claiming the day first means it goes out once
+pub async fn send_if_due(pool: &sqlx::PgPool) -> Result<String, String> {
+    let config = &app_config::get().digest;
+    if config.recipients.is_empty() {
+        return Ok("No DAILY_DIGEST_RECIPIENTS, nothing to send".to_string());
+    }
+    let tz = store_timezone();
+    if Utc::now().with_timezone(&tz).hour() < config.hour {
+        return Ok(format!("Not due before {}:00", config.hour));
+    }
+    let date = yesterday(tz);
+    let claimed = claim_day(pool, date)
+        .await
+        .map_err(|e| format!("Failed to claim the daily digest for {}: {}", date, e))?;
+    if !claimed {
+        return Ok(format!("Digest for {} already sent", date));
+    }
+    let result = send_digest(pool, date, &config.recipients).await;
+    if let Err(e) = record_day(pool, date, &result).await {
+        tracing::error!("Failed to record the daily digest for {}: {}", date, e);
+    }
+    let sent = result.map_err(|e| format!("Daily digest for {} failed: {}", date, e))?;
+    Ok(format!("Daily digest for {} queued to {} recipient(s)", date, sent.sent_to.len()))
+}
+
+pub fn daily_digest_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/digest/opt-out", get(opt_out_from_link))
+        .route("/api/admin/digest", get(get_digest_summary))
+        .route("/api/admin/digest/preview", get(preview_digest))
+        .route("/api/admin/digest/send", post(send_digest_now))
+        .route("/api/admin/digest/recipients", get(list_recipients))
+        .route("/api/admin/digest/recipients/:email", put(set_opt_out))
+        .with_state(app_state)
+}
+
+async fn get_digest_summary(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<DigestQuery>,
+) -> Result<Json<DailySummary>, (StatusCode, String)> {
+    let date = query.date.unwrap_or_else(|| yesterday(store_timezone()));
+    let summary = daily_summary(&app_state.pool, date, app_state.mode.is_live())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(summary))
+}
+
+// The digest email as recipients would see it, without the opt-out link
+async fn preview_digest(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<DigestQuery>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let date = query.date.unwrap_or_else(|| yesterday(store_timezone()));
+    let summary = daily_summary(&app_state.pool, date, app_state.mode.is_live())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let branding = load_branding(&app_state.pool).await;
+    Ok(Html(branding.email_html("Daily summary", &digest_html(&summary, None))))
+}
+
+async fn send_digest_now(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<SendDigestRequest>,
+) -> Result<Json<DigestSent>, (StatusCode, String)> {
+    let recipients = req.recipients.unwrap_or_else(|| app_config::get().digest.recipients.clone());
+    let recipients = normalize_recipients(&recipients);
+    if recipients.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "No recipients: pass recipients or set DAILY_DIGEST_RECIPIENTS".to_string(),
+        ));
+    }
+    if let Some(invalid) = recipients.iter().find(|r| !r.contains('@')) {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid recipient email: {}", invalid)));
+    }
+    let date = req.date.unwrap_or_else(|| yesterday(store_timezone()));
+    let sent = send_digest(&app_state.pool, date, &recipients)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    tracing::info!("Daily digest for {} sent on demand to {} recipient(s)", date, sent.sent_to.len());
+    Ok(Json(sent))
+}
+
+// Configured recipients and everyone who opted out, configured or not
+async fn list_recipients(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<DigestRecipient>>, (StatusCode, String)> {
+    // Opt-out emails are encrypted, so the two lists are merged here rather than joined in SQL
+    let opt_outs: Vec<(Encrypted, DateTime<Utc>)> = sqlx::query!(
+        r#"SELECT email AS "email: Encrypted", opted_out_at FROM daily_digest_opt_outs"#,
+    )
+    .map(|r| (r.email, r.opted_out_at))
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let mut recipients: BTreeMap<String, Option<DateTime<Utc>>> = normalize_recipients(&app_config::get().digest.recipients)
+        .into_iter()
+        .map(|email| (email, None))
+        .collect();
+    for (email, opted_out_at) in opt_outs {
+        recipients.insert(email.into_inner(), Some(opted_out_at));
+    }
+    Ok(Json(
+        recipients
+            .into_iter()
+            .map(|(email, opted_out_at)| DigestRecipient { email, opted_out_at })
+            .collect(),
+    ))
+}
+
+// `email` is already trimmed and lowercase
+async fn opt_out(pool: &sqlx::PgPool, email: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO daily_digest_opt_outs (email, email_hash) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        Encrypted::from(email) as _,
+        pii::email_hash(email),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn set_opt_out(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(email): Path<String>,
+    Json(req): Json<OptOutRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let email = email.trim().to_lowercase();
+    if !email.contains('@') {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid email: {}", email)));
+    }
+    let result = if req.opted_out {
+        opt_out(&app_state.pool, &email).await
+    } else {
+        sqlx::query!("DELETE FROM daily_digest_opt_outs WHERE email_hash = $1", pii::email_hash(&email))
+            .execute(&*app_state.pool)
+            .await
+            .map(|_| ())
+    };
+    result.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// The link at the bottom of every digest
+async fn opt_out_from_link(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<OptOutQuery>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let email = verify_opt_out_token(&query.token)?;
+    opt_out(&app_state.pool, &email)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    tracing::info!("{} opted out of the daily digest", email);
+    let branding = load_branding(&app_state.pool).await;
+    Ok(Html(branding.email_html(
+        "Daily summary",
+        &format!(
+            "<p>{} won't get the daily summary any more. An admin can turn it back on.</p>",
+            escape_html(&email)
+        ),
+    )))
+}
@@ -0,0 +1,373 @@
+// Danger Zone Module - Irreversible admin data tools
+// Delete or anonymize test mode orders, purge stale anonymous checkout carts, and scrub personal
+// data from orders past a retention period. Each tool is called twice: without a
+// confirmation_token it only counts what would change and returns a token, which the second call
+// must send back within 10 minutes. The token fixes the cutoff time, so rows that qualify after
+// the preview are left alone. Applying happens in one transaction with an audit log entry
+
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::audit_log;
+use crate::jwt_keys;
+use crate::AppState;
+
+const CONFIRMATION_MINUTES: i64 = 10;
+pub(crate) const MIN_CART_AGE_DAYS: i64 = 1; // Younger carts may still be mid-checkout
+pub(crate) const MIN_RETENTION_DAYS: i64 = 30;
+// Orders still waiting on a payment keep their contact details
+const OPEN_STATUSES: [&str; 3] = ["pending", "authorized", "awaiting_payment"];
+
+const DELETE_TEST_ORDERS_ACTION: &str = "danger_zone.delete_test_orders";
+const ANONYMIZE_TEST_ORDERS_ACTION: &str = "danger_zone.anonymize_test_orders";
+pub(crate) const PURGE_CARTS_ACTION: &str = "danger_zone.purge_stale_carts";
+pub(crate) const SCRUB_ORDERS_ACTION: &str = "danger_zone.scrub_order_pii";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TestOrderAction {
+    Delete,
+    Anonymize,
+}
+
+#[derive(Deserialize)]
+pub struct TestOrdersRequest {
+    pub action: TestOrderAction,
+    pub confirmation_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AgeRequest {
+    pub older_than_days: i64,
+    pub confirmation_token: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DangerZoneResult {
+    pub action: &'static str,
+    pub cutoff: DateTime<Utc>, // Rows created before this are affected
+    pub affected: i64,
+    pub applied: bool,
+    pub confirmation_token: Option<String>, // Preview only; send it back to apply
+    pub expires_at: Option<DateTime<Utc>>,
+    pub audit_id: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConfirmationClaims {
+    sub: String, // Admin username
+    action: String,
+    cutoff: i64, // Unix seconds
+    exp: usize,
+}
+
+fn confirmation_key(secret: &str) -> Vec<u8> {
+    format!("danger-zone:{}", secret).into_bytes()
+}
+
+pub fn danger_zone_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/danger-zone/test-orders", post(test_orders))
+        .route("/api/admin/danger-zone/stale-carts", post(stale_carts))
+        .route("/api/admin/danger-zone/scrub-orders", post(scrub_orders))
+        .with_state(app_state)
+}
+
+// The cutoff to act on: a new one for a preview, or the one the confirmation token was issued with
+fn resolve_cutoff(
+    admin: &AuthenticatedAdmin,
+    action: &str,
+    token: Option<&str>,
+    preview_cutoff: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, (StatusCode, String)> {
+    let Some(token) = token else {
+        return Ok(None);
+    };
+    let claims = jwt_keys::verify::<ConfirmationClaims>(token, confirmation_key)
+        .ok()
+        .filter(|claims| claims.sub == admin.username && claims.action == action)
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            "Invalid or expired confirmation_token; preview again for a new one".to_string(),
+        ))?;
+    let cutoff = DateTime::from_timestamp(claims.cutoff, 0)
+        .ok_or((StatusCode::BAD_REQUEST, "Invalid confirmation_token".to_string()))?;
+    // A token for a longer retention period must not be used to delete more
+    if cutoff > preview_cutoff {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "confirmation_token was issued for different settings; preview again".to_string(),
+        ));
+    }
+    Ok(Some(cutoff))
+}
+
+fn preview(
+    admin: &AuthenticatedAdmin,
+    action: &'static str,
+    cutoff: DateTime<Utc>,
+    affected: i64,
+) -> Result<DangerZoneResult, (StatusCode, String)> {
+    let expires_at = Utc::now() + chrono::Duration::minutes(CONFIRMATION_MINUTES);
+    let claims = ConfirmationClaims {
+        sub: admin.username.clone(),
+        action: action.to_string(),
+        cutoff: cutoff.timestamp(),
+        exp: expires_at.timestamp() as usize,
+    };
+    let token = jwt_keys::sign(&claims, confirmation_key)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to sign confirmation token: {}", e)))?;
+    Ok(DangerZoneResult {
+        action,
+        cutoff,
+        affected,
+        applied: false,
+        confirmation_token: Some(token),
+        expires_at: Some(expires_at),
+        audit_id: None,
+    })
+}
+
+// Record the change in the audit log and commit it
+async fn finish(
+    mut tx: sqlx::Transaction<'_, sqlx::Postgres>,
+    admin: &AuthenticatedAdmin,
+    action: &'static str,
+    summary: String,
+    details: serde_json::Value,
+    cutoff: DateTime<Utc>,
+    affected: i64,
+) -> Result<DangerZoneResult, (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let audit_id = audit_log::record(&mut tx, &admin.username, action, &summary, details, None)
+        .await
+        .map_err(db_err)?;
+    tx.commit().await.map_err(db_err)?;
+    tracing::info!("{} by {} (audit entry {})", summary, admin.username, audit_id);
+    Ok(DangerZoneResult {
+        action,
+        cutoff,
+        affected,
+        applied: true,
+        confirmation_token: None,
+        expires_at: None,
+        audit_id: Some(audit_id),
+    })
+}
+
+// Remove the customer's contact details from orders, their shipments and checkout carts.
+// Amounts, items, statuses and the customer link stay, so reports still add up
+async fn anonymize_orders(conn: &mut sqlx::PgConnection, ids: &[Uuid]) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE orders SET customer_email = NULL, customer_email_hash = NULL, customer_name = NULL, gift_message = NULL,
+                payment_fingerprint = NULL, pii_scrubbed_at = NOW()
+         WHERE id = ANY($1)",
+        ids,
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        "UPDATE order_shipments SET recipient_name = NULL, street1 = '', street2 = NULL, phone = NULL
+         WHERE order_id = ANY($1)",
+        ids,
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        "UPDATE checkout_carts SET contact_email = NULL, buyer_email_hash = NULL, visitor_id = NULL, ga_client_id = NULL, fbp = NULL, fbc = NULL,
+                client_ip = NULL, client_user_agent = NULL
+         WHERE id IN (SELECT cart_id FROM orders WHERE id = ANY($1))",
+        ids,
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// Delete the stale anonymous carts (never became an order) created before the cutoff. Also run
+// by the scheduler; keep the filter in step with the preview count in stale_carts
+pub(crate) async fn purge_stale_carts(conn: &mut sqlx::PgConnection, cutoff: DateTime<Utc>) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM checkout_carts
+         WHERE converted_at IS NULL AND customer_id IS NULL AND created_at < $1
+           AND NOT EXISTS (SELECT 1 FROM orders o WHERE o.cart_id = checkout_carts.id)",
+        cutoff,
+    )
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected() as i64)
+}
+
+// Finished orders placed before the cutoff that still have personal data, locked when `lock` is set
+async fn scrubbable_orders(conn: &mut sqlx::PgConnection, cutoff: DateTime<Utc>, lock: bool) -> Result<Vec<Uuid>, sqlx::Error> {
+    if lock {
+        sqlx::query_scalar!(
+            "SELECT id FROM orders WHERE created_at < $1 AND pii_scrubbed_at IS NULL AND status <> ALL($2) ORDER BY created_at FOR UPDATE",
+            cutoff,
+            &OPEN_STATUSES[..] as _,
+        )
+        .fetch_all(conn)
+        .await
+    } else {
+        sqlx::query_scalar!(
+            "SELECT id FROM orders WHERE created_at < $1 AND pii_scrubbed_at IS NULL AND status <> ALL($2) ORDER BY created_at",
+            cutoff,
+            &OPEN_STATUSES[..] as _,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+// Scrub the finished orders placed before the cutoff, returning their ids. Also run by the scheduler
+pub(crate) async fn scrub_orders_before(conn: &mut sqlx::PgConnection, cutoff: DateTime<Utc>) -> Result<Vec<Uuid>, sqlx::Error> {
+    let ids = scrubbable_orders(&mut *conn, cutoff, true).await?;
+    anonymize_orders(conn, &ids).await?;
+    Ok(ids)
+}
+
+// Delete every test mode order, or strip its customer details
+async fn test_orders(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<TestOrdersRequest>,
+) -> Result<Json<DangerZoneResult>, (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let action = match request.action {
+        TestOrderAction::Delete => DELETE_TEST_ORDERS_ACTION,
+        TestOrderAction::Anonymize => ANONYMIZE_TEST_ORDERS_ACTION,
+    };
+    let now = Utc::now();
+    let confirmed = resolve_cutoff(&admin, action, request.confirmation_token.as_deref(), now)?;
+    let cutoff = confirmed.unwrap_or(now);
+    let deleting = request.action == TestOrderAction::Delete;
+
+    // Already anonymized orders don't count again. Locked once confirmed
+    let mut tx = app_state.pool.begin().await.map_err(db_err)?;
+    let ids: Vec<Uuid> = if confirmed.is_some() {
+        sqlx::query_scalar!(
+            "SELECT id FROM orders WHERE livemode = FALSE AND created_at < $1 AND ($2 OR pii_scrubbed_at IS NULL)
+             ORDER BY created_at FOR UPDATE",
+            cutoff,
+            deleting,
+        )
+        .fetch_all(&mut *tx)
+        .await
+    } else {
+        sqlx::query_scalar!(
+            "SELECT id FROM orders WHERE livemode = FALSE AND created_at < $1 AND ($2 OR pii_scrubbed_at IS NULL)
+             ORDER BY created_at",
+            cutoff,
+            deleting,
+        )
+        .fetch_all(&mut *tx)
+        .await
+    }
+    .map_err(db_err)?;
+    let affected = ids.len() as i64;
+    if confirmed.is_none() {
+        return preview(&admin, action, cutoff, affected).map(Json);
+    }
+
+    if deleting {
+        let cart_ids: Vec<Uuid> = sqlx::query_scalar!(
+            r#"SELECT cart_id AS "cart_id!" FROM orders WHERE id = ANY($1) AND cart_id IS NOT NULL"#,
+            &ids,
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(db_err)?;
+        // Analytics events aren't tied to orders by a foreign key
+        sqlx::query!("DELETE FROM analytics_events WHERE order_id = ANY($1)", &ids)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_err)?;
+        sqlx::query!("DELETE FROM orders WHERE id = ANY($1)", &ids)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_err)?;
+        sqlx::query!("DELETE FROM checkout_carts WHERE id = ANY($1)", &cart_ids)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_err)?;
+    } else {
+        anonymize_orders(&mut tx, &ids).await.map_err(db_err)?;
+    }
+
+    let summary = format!(
+        "{} {} test mode orders",
+        if deleting { "Deleted" } else { "Anonymized" },
+        affected
+    );
+    let details = serde_json::json!({ "cutoff": cutoff, "order_ids": ids });
+    finish(tx, &admin, action, summary, details, cutoff, affected).await.map(Json)
+}
+
+// Delete checkout carts of guests that never became an order
+async fn stale_carts(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<AgeRequest>,
+) -> Result<Json<DangerZoneResult>, (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    if request.older_than_days < MIN_CART_AGE_DAYS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("older_than_days must be at least {}", MIN_CART_AGE_DAYS),
+        ));
+    }
+    let oldest_kept = Utc::now() - chrono::Duration::days(request.older_than_days);
+    let confirmed = resolve_cutoff(&admin, PURGE_CARTS_ACTION, request.confirmation_token.as_deref(), oldest_kept)?;
+    let cutoff = confirmed.unwrap_or(oldest_kept);
+    if confirmed.is_none() {
+        let count: i64 = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM checkout_carts
+             WHERE converted_at IS NULL AND customer_id IS NULL AND created_at < $1
+               AND NOT EXISTS (SELECT 1 FROM orders o WHERE o.cart_id = checkout_carts.id)"#,
+            cutoff,
+        )
+        .fetch_one(&*app_state.pool)
+        .await
+        .map_err(db_err)?;
+        return preview(&admin, PURGE_CARTS_ACTION, cutoff, count).map(Json);
+    }
+    let mut tx = app_state.pool.begin().await.map_err(db_err)?;
+    let affected = purge_stale_carts(&mut tx, cutoff).await.map_err(db_err)?;
+
+    let summary = format!("Purged {} anonymous checkout carts created before {}", affected, cutoff.date_naive());
+    let details = serde_json::json!({ "cutoff": cutoff, "older_than_days": request.older_than_days });
+    finish(tx, &admin, PURGE_CARTS_ACTION, summary, details, cutoff, affected).await.map(Json)
+}
+
+// Remove customer details from finished orders older than the retention period
+async fn scrub_orders(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<AgeRequest>,
+) -> Result<Json<DangerZoneResult>, (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    if request.older_than_days < MIN_RETENTION_DAYS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("older_than_days must be at least {}", MIN_RETENTION_DAYS),
+        ));
+    }
+    let oldest_kept = Utc::now() - chrono::Duration::days(request.older_than_days);
+    let confirmed = resolve_cutoff(&admin, SCRUB_ORDERS_ACTION, request.confirmation_token.as_deref(), oldest_kept)?;
+    let cutoff = confirmed.unwrap_or(oldest_kept);
+
+    let mut tx = app_state.pool.begin().await.map_err(db_err)?;
+    if confirmed.is_none() {
+        let affected = scrubbable_orders(&mut tx, cutoff, false).await.map_err(db_err)?.len() as i64;
+        return preview(&admin, SCRUB_ORDERS_ACTION, cutoff, affected).map(Json);
+    }
+    let affected = scrub_orders_before(&mut tx, cutoff).await.map_err(db_err)?.len() as i64;
+
+    let summary = format!("Scrubbed personal data from {} orders placed before {}", affected, cutoff.date_naive());
+    let details = serde_json::json!({ "cutoff": cutoff, "older_than_days": request.older_than_days });
+    finish(tx, &admin, SCRUB_ORDERS_ACTION, summary, details, cutoff, affected).await.map(Json)
+}
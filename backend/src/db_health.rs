@@ -0,0 +1,174 @@
+// Database Health Module - Connecting to Postgres, watching the pool and failing fast while it's down
+// - At startup the pool connects with retries and backoff, so the server can start alongside
+//   Postgres (e.g. with docker compose) instead of panicking before the database accepts connections
+// - A monitor checks out a connection and runs SELECT 1 every DATABASE_HEALTH_CHECK_INTERVAL_SECS.
+//   Acquires slower than DATABASE_SLOW_ACQUIRE_MS are logged with the pool's size and idle count
+// - After DATABASE_BREAKER_FAILURES failed checks in a row the circuit breaker opens: /api requests
+//   get a 503 straight away instead of each waiting out the acquire timeout, and the ops channel
+//   is told. The next successful check closes it again
+// Health checks, feeds and static files are served either way; GET /health/db reports the state
+
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
+
+use crate::app_config::{self, DatabaseSettings};
+use crate::notifications::ops::OpsNotifier;
+use crate::AppState;
+
+const FIRST_CONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_CONNECT_DELAY: Duration = Duration::from_secs(30);
+
+// What the monitor last saw, shared with the breaker middleware and /health/db
+#[derive(Clone, Default)]
+pub struct DbHealth {
+    state: Arc<HealthState>,
+}
+
+#[derive(Default)]
+struct HealthState {
+    down: AtomicBool, // The breaker is open
+    consecutive_failures: AtomicU32,
+    last_acquire_ms: AtomicU64,
+}
+
+impl DbHealth {
+    pub fn is_down(&self) -> bool {
+        self.state.down.load(Ordering::Relaxed)
+    }
+}
+
+// Connect the pool, retrying with exponential backoff while Postgres isn't accepting connections.
+// Exits once DATABASE_CONNECT_ATTEMPTS have failed
+pub async fn connect(settings: &DatabaseSettings) -> PgPool {
+    let options = PgPoolOptions::new()
+        .max_connections(settings.max_connections)
+        .acquire_timeout(Duration::from_secs(settings.acquire_timeout_secs));
+    let mut delay = FIRST_CONNECT_DELAY;
+    let mut attempt = 1;
+    loop {
+        match options.clone().connect(settings.url.expose()).await {
+            Ok(pool) => return pool,
+            Err(e) if attempt < settings.connect_attempts => {
+                tracing::warn!(
+                    "Postgres not reachable (attempt {} of {}), retrying in {}s: {}",
+                    attempt,
+                    settings.connect_attempts,
+                    delay.as_secs(),
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_CONNECT_DELAY);
+                attempt += 1;
+            }
+            Err(e) => {
+                tracing::error!("Failed to connect to Postgres after {} attempts: {}", attempt, e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+// How long a connection took to check out, after which it answered SELECT 1
+async fn check(pool: &PgPool) -> Result<Duration, sqlx::Error> {
+    let started = Instant::now();
+    let mut conn = pool.acquire().await?;
+    let waited = started.elapsed();
+    sqlx::query!(r#"SELECT 1 AS "one""#).fetch_one(&mut *conn).await?;
+    Ok(waited)
+}
+
+pub fn spawn_monitor(pool: Arc<PgPool>, health: DbHealth, ops: OpsNotifier) {
+    tokio::spawn(async move {
+        let settings = &app_config::get().database;
+        let slow = Duration::from_millis(settings.slow_acquire_ms);
+        let mut interval = tokio::time::interval(Duration::from_secs(settings.health_check_interval_secs));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let (size, idle) = (pool.size(), pool.num_idle());
+            let state = &health.state;
+            match check(&pool).await {
+                Ok(waited) => {
+                    state.last_acquire_ms.store(waited.as_millis() as u64, Ordering::Relaxed);
+                    if waited >= slow {
+                        tracing::warn!(
+                            acquire_ms = waited.as_millis() as u64,
+                            pool_size = size,
+                            idle_connections = idle,
+                            max_connections = settings.max_connections,
+                            "slow database connection acquire"
+                        );
+                    }
+                    state.consecutive_failures.store(0, Ordering::Relaxed);
+                    if state.down.swap(false, Ordering::Relaxed) {
+                        tracing::info!("database reachable again, circuit breaker closed");
+                        ops.notify(":white_check_mark: Database reachable again, API requests are served".to_string());
+                    }
+                }
+                Err(e) => {
+                    let failures = state.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+                    tracing::warn!(failures, error = %e, "database health check failed");
+                    if failures >= settings.breaker_failures && !state.down.swap(true, Ordering::Relaxed) {
+                        tracing::error!(failures, error = %e, "database down, circuit breaker open");
+                        ops.notify(format!(
+                            ":rotating_light: Database unreachable ({} failed checks), API requests get 503 until it's back: {}",
+                            failures, e
+                        ));
+                    }
+                }
+            }
+        }
+    });
+}
+
+// While the breaker is open, answer API requests with 503 rather than letting them pile up
+// waiting for a connection
+pub async fn reject_while_down(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    if state.db_health.is_down() && request.uri().path().starts_with("/api/") {
+        let retry_after = app_config::get().database.health_check_interval_secs.to_string();
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, retry_after)],
+            "Database unavailable, try again shortly",
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+#[derive(Serialize)]
+pub struct DbHealthReport {
+    pub status: &'static str, // "up", or "down" while the breaker is open
+    pub pool_size: u32,
+    pub idle_connections: usize,
+    pub max_connections: u32,
+    pub last_acquire_ms: u64,
+    pub consecutive_failures: u32,
+}
+
+// 503 while the database is down, for load balancer readiness checks
+pub async fn health_report(State(state): State<Arc<AppState>>) -> (StatusCode, Json<DbHealthReport>) {
+    let health = &state.db_health;
+    let down = health.is_down();
+    let report = DbHealthReport {
+        status: if down { "down" } else { "up" },
+        pool_size: state.pool.size(),
+        idle_connections: state.pool.num_idle(),
+        max_connections: app_config::get().database.max_connections,
+        last_acquire_ms: health.state.last_acquire_ms.load(Ordering::Relaxed),
+        consecutive_failures: health.state.consecutive_failures.load(Ordering::Relaxed),
+    };
+    let status = if down { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::OK };
+    (status, Json(report))
+}
@@ -0,0 +1,176 @@
+// Delivery Estimates Module - "Get it by ..." dates for product and cart pages
+// Combines the origin warehouse's handling time with the carrier transit window
+// of the destination's shipping zone, counted in business days
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use chrono::{Datelike, Duration, NaiveDate, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::easypost_shipping::Address;
+use crate::shipping_rules::{find_zone, ShippingMethod};
+use crate::warehouses::{distance_score, Warehouse};
+use crate::AppState;
+
+// Used when no warehouse or shipping zone provides timing data
+const DEFAULT_HANDLING_DAYS: i32 = 1;
+const DEFAULT_TRANSIT_DAYS: (i32, i32) = (3, 7);
+
+#[derive(Deserialize)]
+pub struct EstimateQuery {
+    pub zip: String,
+    pub state: Option<String>,
+    pub country: Option<String>,
+    pub product_ids: Option<String>, // Comma separated, e.g. "1,4,7"
+}
+
+#[derive(Serialize)]
+pub struct DeliveryEstimate {
+    pub success: bool,
+    pub zip: String,
+    pub handling_days: i32,
+    pub min_transit_days: i32,
+    pub max_transit_days: i32,
+    pub earliest_date: NaiveDate,
+    pub latest_date: NaiveDate,
+    pub message: String,
+}
+
+// Advance a date by a number of business days, skipping weekends
+fn add_business_days(mut date: NaiveDate, days: i32) -> NaiveDate {
+    let mut remaining = days;
+    while remaining > 0 {
+        date += Duration::days(1);
+        if !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+            remaining -= 1;
+        }
+    }
+    date
+}
+
+// Slowest handling time among the warehouses that would ship these products
+async fn handling_days(pool: &sqlx::PgPool, destination: &Address, product_ids: &[i32]) -> Result<i32, sqlx::Error> {
+    let mut warehouses = sqlx::query_as!(Warehouse, "SELECT id, name, street1, street2, city, state, zip, country, phone, active, handling_days, created_at FROM warehouses WHERE active = TRUE")
+        .fetch_all(pool)
+        .await?;
+    if warehouses.is_empty() {
+        return Ok(DEFAULT_HANDLING_DAYS);
+    }
+    warehouses.sort_by_key(|w| distance_score(w, destination));
+    let closest = warehouses[0].handling_days;
+    if product_ids.is_empty() {
+        return Ok(closest);
+    }
+
+    let stocked: Vec<(i32, i32)> = sqlx::query!(
+        "SELECT warehouse_id, product_id FROM warehouse_inventory WHERE product_id = ANY($1) AND quantity > 0",
+        product_ids,
+    )
+    .map(|r| (r.warehouse_id, r.product_id))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(product_ids
+        .iter()
+        .map(|product_id| {
+            warehouses
+                .iter()
+                .find(|w| stocked.contains(&(w.id, *product_id)))
+                .map(|w| w.handling_days)
+                .unwrap_or(closest)
+        })
+        .max()
+        .unwrap_or(closest))
+}
+
+// Transit window of the cheapest shipping method serving the destination
+async fn transit_days(pool: &sqlx::PgPool, destination: &Address) -> Result<(i32, i32), sqlx::Error> {
+    let zone = match find_zone(pool, destination).await? {
+        Some(zone) => zone,
+        None => return Ok(DEFAULT_TRANSIT_DAYS),
+    };
+    let method = sqlx::query_as!(
+        ShippingMethod,
+        "SELECT id, zone_id, name, base_rate, per_item_surcharge, free_over, min_delivery_days, max_delivery_days, active, created_at FROM shipping_methods
+         WHERE zone_id = $1 AND active = TRUE
+           AND (min_delivery_days IS NOT NULL OR max_delivery_days IS NOT NULL)
+         ORDER BY base_rate, id LIMIT 1",
+        zone.id,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(match method {
+        Some(m) => {
+            let min = m.min_delivery_days.or(m.max_delivery_days).unwrap_or(DEFAULT_TRANSIT_DAYS.0);
+            let max = m.max_delivery_days.unwrap_or(min).max(min);
+            (min, max)
+        }
+        None => DEFAULT_TRANSIT_DAYS,
+    })
+}
+
+pub fn delivery_estimate_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/shipping/estimate", get(get_delivery_estimate))
+        .with_state(app_state)
+}
+
+// Estimate the delivery date range for a destination ZIP code
+async fn get_delivery_estimate(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EstimateQuery>,
+) -> Result<Json<DeliveryEstimate>, (StatusCode, String)> {
+    let zip = query.zip.trim().to_string();
+    if zip.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "zip is required".to_string()));
+    }
+    let product_ids = query
+        .product_ids
+        .as_deref()
+        .unwrap_or("")
+        .split(',')
+        .filter(|id| !id.trim().is_empty())
+        .map(|id| id.trim().parse::<i32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid product_ids".to_string()))?;
+
+    let destination = Address {
+        name: None,
+        street1: String::new(),
+        street2: None,
+        city: String::new(),
+        state: query.state.unwrap_or_default(),
+        zip: zip.clone(),
+        country: query.country,
+        phone: None,
+        email: None,
+    };
+
+    let handling = handling_days(&state.pool, &destination, &product_ids)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let (min_transit, max_transit) = transit_days(&state.pool, &destination)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    let today = Utc::now().date_naive();
+    let earliest_date = add_business_days(today, handling + min_transit);
+    let latest_date = add_business_days(today, handling + max_transit);
+
+    Ok(Json(DeliveryEstimate {
+        success: true,
+        zip,
+        handling_days: handling,
+        min_transit_days: min_transit,
+        max_transit_days: max_transit,
+        earliest_date,
+        latest_date,
+        message: format!("Get it by {}", latest_date.format("%a, %b %-d")),
+    }))
+}
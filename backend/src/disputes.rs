@@ -0,0 +1,534 @@
+// Disputes Module - Chargeback workspace
+// The charge.dispute.* webhooks keep a row per Stripe dispute with its deadline and status.
+// Admins collect text evidence and files (uploaded to Stripe as they arrive) and submit the
+// lot through the Stripe API. A reminder goes out before the evidence deadline if nothing
+// has been submitted
+
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::events::DomainEvent;
+use crate::job_alerts::send_email_alert;
+use crate::pii::Encrypted;
+use crate::provider_breaker::{self, GuardedSend, Provider};
+use crate::request_id::WithRequestId;
+use crate::AppState;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+const MAX_EVIDENCE_FILE_BYTES: usize = 5 * 1024 * 1024; // Stripe's limit for dispute evidence
+const STRIPE_API_URL: &str = "https://api.stripe.com/v1";
+const STRIPE_FILES_URL: &str = "https://files.stripe.com/v1/files";
+
+// Stripe evidence fields that take text
+const TEXT_EVIDENCE_FIELDS: &[&str] = &[
+    "access_activity_log",
+    "billing_address",
+    "cancellation_policy_disclosure",
+    "cancellation_rebuttal",
+    "customer_email_address",
+    "customer_name",
+    "customer_purchase_ip",
+    "duplicate_charge_explanation",
+    "duplicate_charge_id",
+    "product_description",
+    "refund_policy_disclosure",
+    "refund_refusal_explanation",
+    "service_date",
+    "shipping_address",
+    "shipping_carrier",
+    "shipping_date",
+    "shipping_tracking_number",
+    "uncategorized_text",
+];
+
+// Stripe evidence fields that take an uploaded file
+const FILE_EVIDENCE_FIELDS: &[&str] = &[
+    "cancellation_policy",
+    "customer_communication",
+    "customer_signature",
+    "duplicate_charge_documentation",
+    "receipt",
+    "refund_policy",
+    "service_documentation",
+    "shipping_documentation",
+    "uncategorized_file",
+];
+
+const EVIDENCE_CONTENT_TYPES: &[&str] = &["application/pdf", "image/jpeg", "image/png"];
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Dispute {
+    pub id: Uuid,
+    pub stripe_dispute_id: String,
+    pub charge_id: String,
+    pub payment_intent_id: Option<String>,
+    pub order_id: Option<Uuid>,
+    pub amount: i64, // in cents
+    pub currency: String,
+    pub reason: String,
+    pub status: String,
+    pub evidence_due_by: Option<DateTime<Utc>>,
+    pub evidence_status: String, // none, draft, submitted
+    pub evidence: Value,
+    pub submitted_at: Option<DateTime<Utc>>,
+    pub reminder_sent_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct EvidenceFile {
+    pub id: Uuid,
+    pub dispute_id: Uuid,
+    pub evidence_type: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub stripe_file_id: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct DisputeDetail {
+    #[serde(flatten)]
+    pub dispute: Dispute,
+    pub files: Vec<EvidenceFile>,
+}
+
+#[derive(Deserialize)]
+pub struct DisputeQuery {
+    pub status: Option<String>,
+    pub open: Option<bool>, // Only disputes whose evidence hasn't been submitted
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct EvidenceFileQuery {
+    pub evidence_type: String,
+    pub filename: String,
+}
+
+#[derive(Deserialize)]
+struct StripeFile {
+    id: String,
+}
+
+fn db_error(e: sqlx::Error) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e))
+}
+
+fn from_timestamp(ts: i64) -> Option<DateTime<Utc>> {
+    DateTime::<Utc>::from_timestamp(ts, 0)
+}
+
+// Insert or refresh a dispute from a charge.dispute.* webhook. New disputes are linked to
+// their order, pre-filled with what the order knows and announced to admins
+pub async fn record_stripe_dispute(state: &AppState, dispute: &stripe::Dispute) -> Result<(), String> {
+    let charge_id = dispute.charge.id().to_string();
+    let payment_intent_id = dispute.payment_intent.as_ref().map(|pi| pi.id().to_string());
+    let evidence_due_by = dispute.evidence_details.due_by.and_then(from_timestamp);
+
+    let (id, inserted): (Uuid, bool) = sqlx::query!(
+        r#"INSERT INTO disputes
+            (stripe_dispute_id, charge_id, payment_intent_id, order_id, amount, currency, reason, status, evidence_due_by)
+         VALUES ($1, $2::TEXT, $3::TEXT,
+                 (SELECT id FROM orders WHERE payment_intent_id = $3::TEXT OR payment_id = $2::TEXT ORDER BY created_at LIMIT 1),
+                 $4, $5, $6, $7, $8)
+         ON CONFLICT (stripe_dispute_id) DO UPDATE
+         SET amount = EXCLUDED.amount, reason = EXCLUDED.reason, status = EXCLUDED.status,
+             evidence_due_by = EXCLUDED.evidence_due_by, updated_at = NOW()
+         RETURNING id, (xmax = 0) AS "inserted!""#,
+        dispute.id.as_str(),
+        &charge_id,
+        payment_intent_id.as_deref(),
+        dispute.amount,
+        dispute.currency.to_string().to_uppercase(),
+        &dispute.reason,
+        dispute.status.as_str(),
+        evidence_due_by,
+    )
+    .map(|r| (r.id, r.inserted))
+    .fetch_one(&*state.pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))?;
+
+    if !inserted {
+        tracing::info!("Dispute {} is now {}", dispute.id, dispute.status.as_str());
+        return Ok(());
+    }
+
+    // The order email is encrypted, so the evidence is built here rather than in SQL
+    let order: Option<(Uuid, Option<Encrypted>, Option<String>)> = sqlx::query!(
+        r#"SELECT o.id, o.customer_email AS "customer_email: Encrypted", o.customer_name FROM disputes d JOIN orders o ON o.id = d.order_id WHERE d.id = $1"#,
+        id,
+    )
+    .map(|r| (r.id, r.customer_email, r.customer_name))
+    .fetch_optional(&*state.pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))?;
+    let order_id = order.as_ref().map(|(order_id, _, _)| *order_id);
+    if let Some((_, email, name)) = order {
+        sqlx::query!(
+            "UPDATE disputes SET evidence = jsonb_strip_nulls(jsonb_build_object(
+                 'customer_email_address', $2::TEXT,
+                 'customer_name', $3::TEXT))
+             WHERE id = $1",
+            id,
+            email.map(Encrypted::into_inner),
+            name,
+        )
+        .execute(&*state.pool)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+    }
+
+    tracing::info!("Recorded dispute {} for order {:?}", dispute.id, order_id);
+    state.events.publish(DomainEvent::DisputeOpened {
+        dispute_id: id,
+        order_id,
+        amount: dispute.amount,
+        currency: dispute.currency.to_string().to_uppercase(),
+        reason: dispute.reason.clone(),
+        evidence_due_by,
+    });
+    Ok(())
+}
+
+// Upload a file to Stripe for use as dispute evidence, returning its file id
+async fn upload_stripe_file(filename: &str, content_type: &str, data: &[u8]) -> Result<String, String> {
+    let boundary = format!("rcom-{}", Uuid::new_v4().simple());
+    let filename = filename.replace(['"', '\r', '\n'], "");
+    let mut body = format!(
+        "--{b}\r\nContent-Disposition: form-data; name=\"purpose\"\r\n\r\ndispute_evidence\r\n\
+         --{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{f}\"\r\nContent-Type: {t}\r\n\r\n",
+        b = boundary,
+        f = filename,
+        t = content_type,
+    )
+    .into_bytes();
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+    let response = reqwest::Client::new()
+        .post(STRIPE_FILES_URL)
+        .bearer_auth(app_config::get().stripe.secret_key.expose())
+        .header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+        .body(body)
+        .with_request_id()
+        .send_guarded(Provider::Stripe)
+        .await
+        .map_err(|e| format!("Stripe file upload failed: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!("Stripe file upload returned {}: {}", status, text));
+    }
+    let file: StripeFile = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Stripe response: {}", e))?;
+    Ok(file.id)
+}
+
+// Send the evidence to Stripe and submit it for review
+async fn submit_to_stripe(dispute: &Dispute, files: &[EvidenceFile]) -> Result<(), String> {
+    let mut form: Vec<(String, String)> = Vec::new();
+    if let Value::Object(fields) = &dispute.evidence {
+        for (field, value) in fields {
+            if let Some(text) = value.as_str() {
+                form.push((format!("evidence[{}]", field), text.to_string()));
+            }
+        }
+    }
+    // Stripe takes one file per field; the latest upload wins
+    let mut latest: HashMap<&str, &str> = HashMap::new();
+    for file in files {
+        latest.insert(&file.evidence_type, &file.stripe_file_id);
+    }
+    for (field, file_id) in latest {
+        form.push((format!("evidence[{}]", field), file_id.to_string()));
+    }
+    form.push(("submit".to_string(), "true".to_string()));
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/disputes/{}", STRIPE_API_URL, dispute.stripe_dispute_id))
+        .bearer_auth(app_config::get().stripe.secret_key.expose())
+        .form(&form)
+        .with_request_id()
+        .send_guarded(Provider::Stripe)
+        .await
+        .map_err(|e| format!("Stripe request failed: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!("Stripe returned {}: {}", status, text));
+    }
+    Ok(())
+}
+
+// Remind admins about disputes whose evidence is due within DISPUTE_REMINDER_HOURS
+async fn send_due_reminders(state: &AppState) -> Result<(), sqlx::Error> {
+    let reminder_hours = app_config::get().alerts.dispute_reminder_hours as i32;
+    let due = sqlx::query_as!(
+        Dispute,
+        "UPDATE disputes SET reminder_sent_at = NOW()
+         WHERE evidence_status <> 'submitted' AND reminder_sent_at IS NULL
+           AND status IN ('needs_response', 'warning_needs_response')
+           AND evidence_due_by > NOW() AND evidence_due_by <= NOW() + make_interval(hours => $1::INT)
+         RETURNING id, stripe_dispute_id, charge_id, payment_intent_id, order_id, amount, currency, reason, status, evidence_due_by, evidence_status, evidence, submitted_at, reminder_sent_at, created_at, updated_at",
+        reminder_hours,
+    )
+    .fetch_all(&*state.pool)
+    .await?;
+
+    let alert_email = app_config::get().alerts.email.clone();
+    for dispute in due {
+        let due_by = dispute
+            .evidence_due_by
+            .map(|d| d.format("%Y-%m-%d %H:%M UTC").to_string())
+            .unwrap_or_default();
+        let text = format!(
+            "Dispute {} ({} {:.2}, {}) needs evidence by {}",
+            dispute.stripe_dispute_id,
+            dispute.currency,
+            dispute.amount as f64 / 100.0,
+            dispute.reason,
+            due_by
+        );
+        state.ops.notify(format!(":hourglass: {}", text));
+        if let Some(email) = &alert_email {
+            if let Err(e) = send_email_alert(&state.pool, email, "Dispute evidence due soon", &text).await {
+                tracing::error!("Failed to send dispute reminder to {}: {}", email, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Background task that sends evidence deadline reminders (checked every ALERT_CHECK_INTERVAL_SECS)
+pub fn spawn_dispute_reminders(state: Arc<AppState>) {
+    let interval_secs = app_config::get().schedules.alert_check_interval_secs;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            if let Err(e) = send_due_reminders(&state).await {
+                tracing::error!("Failed to check dispute deadlines: {}", e);
+            }
+        }
+    });
+}
+
+pub fn dispute_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/disputes", get(list_disputes))
+        .route("/api/admin/disputes/:id", get(get_dispute))
+        .route("/api/admin/disputes/:id/evidence", put(update_evidence))
+        .route(
+            "/api/admin/disputes/:id/files",
+            post(upload_evidence_file).layer(DefaultBodyLimit::max(MAX_EVIDENCE_FILE_BYTES)),
+        )
+        .route("/api/admin/disputes/:id/submit", post(submit_evidence))
+        .with_state(app_state)
+}
+
+async fn load_dispute(pool: &sqlx::PgPool, id: Uuid) -> Result<Dispute, (StatusCode, String)> {
+    sqlx::query_as!(Dispute, "SELECT id, stripe_dispute_id, charge_id, payment_intent_id, order_id, amount, currency, reason, status, evidence_due_by, evidence_status, evidence, submitted_at, reminder_sent_at, created_at, updated_at FROM disputes WHERE id = $1", id)
+        .fetch_optional(pool)
+        .await
+        .map_err(db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Dispute not found".to_string()))
+}
+
+async fn load_detail(pool: &sqlx::PgPool, id: Uuid) -> Result<DisputeDetail, (StatusCode, String)> {
+    let dispute = load_dispute(pool, id).await?;
+    let files = sqlx::query_as!(
+        EvidenceFile,
+        "SELECT id, dispute_id, evidence_type, filename, content_type, size_bytes, stripe_file_id, created_at FROM dispute_evidence_files WHERE dispute_id = $1 ORDER BY created_at",
+        id,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(db_error)?;
+    Ok(DisputeDetail { dispute, files })
+}
+
+fn ensure_editable(dispute: &Dispute) -> Result<(), (StatusCode, String)> {
+    if dispute.evidence_status == "submitted" {
+        return Err((StatusCode::CONFLICT, "Evidence has already been submitted".to_string()));
+    }
+    if matches!(dispute.status.as_str(), "won" | "lost" | "charge_refunded" | "warning_closed") {
+        return Err((StatusCode::CONFLICT, format!("Dispute is closed ({})", dispute.status)));
+    }
+    Ok(())
+}
+
+// Disputes, soonest evidence deadline first
+async fn list_disputes(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<DisputeQuery>,
+) -> Result<Json<Vec<Dispute>>, (StatusCode, String)> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let disputes = sqlx::query_as!(
+        Dispute,
+        "SELECT id, stripe_dispute_id, charge_id, payment_intent_id, order_id, amount, currency, reason, status, evidence_due_by, evidence_status, evidence, submitted_at, reminder_sent_at, created_at, updated_at FROM disputes
+         WHERE ($1::VARCHAR IS NULL OR status = $1)
+           AND (NOT $2 OR evidence_status <> 'submitted')
+         ORDER BY evidence_due_by NULLS LAST, created_at DESC
+         LIMIT $3 OFFSET $4",
+        query.status.as_deref(),
+        query.open.unwrap_or(false),
+        limit,
+        offset,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    Ok(Json(disputes))
+}
+
+async fn get_dispute(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<DisputeDetail>, (StatusCode, String)> {
+    Ok(Json(load_detail(&app_state.pool, id).await?))
+}
+
+// Merge text evidence fields into the draft; an empty string clears a field
+async fn update_evidence(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(fields): Json<HashMap<String, String>>,
+) -> Result<Json<DisputeDetail>, (StatusCode, String)> {
+    let dispute = load_dispute(&app_state.pool, id).await?;
+    ensure_editable(&dispute)?;
+    if let Some(field) = fields.keys().find(|f| !TEXT_EVIDENCE_FIELDS.contains(&f.as_str())) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown evidence field: {}", field)));
+    }
+
+    let mut evidence = match dispute.evidence {
+        Value::Object(map) => map,
+        _ => Map::new(),
+    };
+    for (field, value) in fields {
+        if value.trim().is_empty() {
+            evidence.remove(&field);
+        } else {
+            evidence.insert(field, Value::String(value));
+        }
+    }
+
+    sqlx::query!(
+        "UPDATE disputes SET evidence = $2, evidence_status = 'draft', updated_at = NOW() WHERE id = $1",
+        id,
+        Value::Object(evidence),
+    )
+    .execute(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    Ok(Json(load_detail(&app_state.pool, id).await?))
+}
+
+// Attach a file (PDF, JPEG or PNG request body) as evidence; it is uploaded to Stripe right away
+async fn upload_evidence_file(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<EvidenceFileQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<EvidenceFile>), (StatusCode, String)> {
+    let dispute = load_dispute(&app_state.pool, id).await?;
+    ensure_editable(&dispute)?;
+    if !FILE_EVIDENCE_FIELDS.contains(&query.evidence_type.as_str()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("evidence_type must be one of: {}", FILE_EVIDENCE_FIELDS.join(", ")),
+        ));
+    }
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(';').next().unwrap_or(v).trim().to_lowercase())
+        .unwrap_or_default();
+    if !EVIDENCE_CONTENT_TYPES.contains(&content_type.as_str()) {
+        return Err((StatusCode::UNSUPPORTED_MEDIA_TYPE, "Evidence must be a PDF, JPEG or PNG".to_string()));
+    }
+    if body.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "File is empty".to_string()));
+    }
+
+    let stripe_file_id = upload_stripe_file(&query.filename, &content_type, &body)
+        .await
+        .map_err(|e| (provider_breaker::degraded_status(Provider::Stripe, StatusCode::BAD_GATEWAY), e))?;
+
+    let mut tx = app_state.pool.begin().await.map_err(db_error)?;
+    let file = sqlx::query_as!(
+        EvidenceFile,
+        "INSERT INTO dispute_evidence_files (dispute_id, evidence_type, filename, content_type, size_bytes, stripe_file_id)
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING id, dispute_id, evidence_type, filename, content_type, size_bytes, stripe_file_id, created_at",
+        id,
+        &query.evidence_type,
+        &query.filename,
+        &content_type,
+        body.len() as i64,
+        &stripe_file_id,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    sqlx::query!("UPDATE disputes SET evidence_status = 'draft', updated_at = NOW() WHERE id = $1", id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    tx.commit().await.map_err(db_error)?;
+
+    Ok((StatusCode::CREATED, Json(file)))
+}
+
+// Submit the gathered evidence to Stripe. Stripe allows one submission per dispute
+async fn submit_evidence(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<DisputeDetail>, (StatusCode, String)> {
+    let detail = load_detail(&app_state.pool, id).await?;
+    ensure_editable(&detail.dispute)?;
+    if detail.dispute.evidence_status == "none" {
+        return Err((StatusCode::BAD_REQUEST, "Add evidence before submitting".to_string()));
+    }
+
+    submit_to_stripe(&detail.dispute, &detail.files)
+        .await
+        .map_err(|e| (provider_breaker::degraded_status(Provider::Stripe, StatusCode::BAD_GATEWAY), e))?;
+
+    sqlx::query!(
+        "UPDATE disputes SET evidence_status = 'submitted', submitted_at = NOW(), updated_at = NOW() WHERE id = $1",
+        id,
+    )
+    .execute(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    tracing::info!("Submitted evidence for dispute {}", detail.dispute.stripe_dispute_id);
+    Ok(Json(load_detail(&app_state.pool, id).await?))
+}
@@ -0,0 +1,520 @@
+// Drops Module - Virtual waiting room for high-demand product drops
+// Shoppers join a drop's queue and get a signed queue token with their position. Admission is a
+// token bucket: it refills at admit_per_minute up to burst, and each token lets the next waiting
+// shopper through to checkout for admission_minutes. Shoppers who stop polling are skipped until
+// they come back. While a drop runs, checkout for its products needs an admitted queue token
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::cart_pricing::PricedLine;
+use crate::jwt_keys;
+use crate::AppState;
+
+const POLL_SECS: i64 = 5; // How often the waiting room page checks its position
+const STALE_SECS: i64 = 60; // Waiting shoppers not seen for this long are skipped
+
+// Database model for drops
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ProductDrop {
+    pub id: Uuid,
+    pub name: String,
+    pub product_ids: Vec<i32>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub admit_per_minute: i32,
+    pub burst: i32,
+    pub admission_minutes: i32,
+    pub tokens: f64, // Admissions the bucket held at tokens_updated_at
+    pub tokens_updated_at: DateTime<Utc>,
+    pub last_position: i64,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// Drop with its queue counts for the admin list
+#[derive(Debug, Serialize)]
+pub struct DropSummary {
+    #[serde(flatten)]
+    pub drop: ProductDrop,
+    pub joined: i64,
+    pub waiting: i64, // Not yet admitted and still polling
+    pub admitted: i64,
+}
+
+// Public view of a drop for the waiting room page
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct DropInfo {
+    pub id: Uuid,
+    pub name: String,
+    pub product_ids: Vec<i32>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub waiting: i64,
+}
+
+#[derive(Deserialize)]
+pub struct DropInput {
+    pub name: String,
+    pub product_ids: Vec<i32>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub admit_per_minute: i32,
+    pub burst: Option<i32>, // Defaults to admit_per_minute
+    pub admission_minutes: Option<i32>, // Defaults to 10
+}
+
+#[derive(Deserialize, Default)]
+pub struct JoinRequest {
+    pub token: Option<String>, // A queue token from earlier keeps its place
+}
+
+#[derive(Deserialize)]
+pub struct QueueQuery {
+    pub token: String,
+}
+
+// Where a shopper stands in the queue
+#[derive(Serialize)]
+pub struct QueueStatus {
+    pub drop_id: Uuid,
+    pub token: String,
+    pub status: String, // not_started, waiting, admitted, expired, ended
+    pub position: i64,
+    pub ahead: i64, // Waiting shoppers in front
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_wait_secs: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admitted_until: Option<DateTime<Utc>>, // Check out before this
+    pub poll_after_secs: i64,
+}
+
+// Signed into the queue token; sub is the queue entry
+#[derive(Serialize, Deserialize)]
+struct QueueClaims {
+    sub: Uuid,
+    drop_id: Uuid,
+    exp: usize,
+}
+
+#[derive(sqlx::FromRow)]
+struct QueueEntry {
+    position: i64,
+    admitted_at: Option<DateTime<Utc>>,
+    admission_expires_at: Option<DateTime<Utc>>,
+}
+
+fn drop_key(secret: &str) -> Vec<u8> {
+    format!("drop:{}", secret).into_bytes()
+}
+
+pub fn drop_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/drops/:id", get(get_drop))
+        .route("/api/drops/:id/queue", get(queue_status).post(join_queue))
+        .route("/api/admin/drops", get(list_drops).post(create_drop))
+        .route("/api/admin/drops/:id", put(update_drop).delete(delete_drop))
+        .with_state(app_state)
+}
+
+async fn load_drop(pool: &sqlx::PgPool, drop_id: Uuid) -> Result<ProductDrop, (StatusCode, String)> {
+    sqlx::query_as!(ProductDrop, "SELECT id, name, product_ids, starts_at, ends_at, admit_per_minute, burst, admission_minutes, tokens, tokens_updated_at, last_position, created_by, created_at, updated_at FROM drops WHERE id = $1", drop_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Drop not found".to_string()))
+}
+
+fn has_ended(drop: &ProductDrop) -> bool {
+    drop.ends_at.is_some_and(|end| end <= Utc::now())
+}
+
+// Queue tokens outlive the drop by a day, or last a week for drops without an end
+fn sign_token(drop: &ProductDrop, entry_id: Uuid) -> Result<String, (StatusCode, String)> {
+    let exp = drop.ends_at.unwrap_or(Utc::now() + chrono::Duration::days(6)) + chrono::Duration::days(1);
+    let claims = QueueClaims {
+        sub: entry_id,
+        drop_id: drop.id,
+        exp: exp.timestamp() as usize,
+    };
+    jwt_keys::sign(&claims, drop_key)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to sign queue token: {}", e)))
+}
+
+// Refill the drop's bucket and let the next waiting shoppers through. Concurrent polls don't
+// wait on each other: whoever holds the drop's lock admits for everyone
+async fn admit_waiting(pool: &sqlx::PgPool, drop_id: Uuid) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let tokens: Option<f64> = sqlx::query_scalar!(
+        "UPDATE drops SET
+             tokens = LEAST(burst, tokens + EXTRACT(EPOCH FROM NOW() - tokens_updated_at) * admit_per_minute / 60.0),
+             tokens_updated_at = NOW()
+         WHERE id = (SELECT id FROM drops WHERE id = $1 AND starts_at <= NOW() FOR UPDATE SKIP LOCKED)
+         RETURNING tokens",
+        drop_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let admit = tokens.unwrap_or(0.0).floor() as i64;
+    if admit > 0 {
+        let res = sqlx::query!(
+            "UPDATE drop_queue_entries e SET admitted_at = NOW(), admission_expires_at = NOW() + make_interval(mins => d.admission_minutes)
+             FROM drops d
+             WHERE d.id = e.drop_id AND e.id IN (
+                 SELECT id FROM drop_queue_entries
+                 WHERE drop_id = $1 AND admitted_at IS NULL AND last_seen_at > NOW() - make_interval(secs => $3)
+                 ORDER BY position LIMIT $2
+             )",
+            drop_id,
+            admit,
+            STALE_SECS as f64,
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!("UPDATE drops SET tokens = tokens - $2 WHERE id = $1", drop_id, res.rows_affected() as f64)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+// Mark the shopper as still here, run admission and report where they stand
+async fn entry_status(pool: &sqlx::PgPool, drop: &ProductDrop, entry_id: Uuid, token: String) -> Result<QueueStatus, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    sqlx::query!("UPDATE drop_queue_entries SET last_seen_at = NOW() WHERE id = $1", entry_id)
+        .execute(pool)
+        .await
+        .map_err(db_error)?;
+    if !has_ended(drop) {
+        admit_waiting(pool, drop.id).await.map_err(db_error)?;
+    }
+
+    let entry = sqlx::query_as!(
+        QueueEntry,
+        "SELECT position, admitted_at, admission_expires_at FROM drop_queue_entries WHERE id = $1 AND drop_id = $2",
+        entry_id,
+        drop.id,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(db_error)?
+    .ok_or((StatusCode::NOT_FOUND, "Queue place not found; join the queue again".to_string()))?;
+    let ahead: i64 = if entry.admitted_at.is_none() {
+        sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "ahead!" FROM drop_queue_entries
+             WHERE drop_id = $1 AND admitted_at IS NULL AND position < $2 AND last_seen_at > NOW() - make_interval(secs => $3)"#,
+            drop.id,
+            entry.position,
+            STALE_SECS as f64,
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(db_error)?
+    } else {
+        0
+    };
+
+    let status = if has_ended(drop) {
+        "ended"
+    } else if entry.admission_expires_at.is_some_and(|until| until > Utc::now()) {
+        "admitted"
+    } else if entry.admitted_at.is_some() {
+        "expired"
+    } else if drop.starts_at > Utc::now() {
+        "not_started"
+    } else {
+        "waiting"
+    };
+    let estimated_wait_secs = matches!(status, "waiting" | "not_started").then(|| {
+        let until_start = (drop.starts_at - Utc::now()).num_seconds().max(0);
+        until_start + (ahead + 1) * 60 / drop.admit_per_minute as i64
+    });
+    Ok(QueueStatus {
+        drop_id: drop.id,
+        token,
+        status: status.to_string(),
+        position: entry.position,
+        ahead,
+        estimated_wait_secs,
+        admitted_until: entry.admission_expires_at.filter(|_| status == "admitted"),
+        poll_after_secs: POLL_SECS,
+    })
+}
+
+fn verify_token(token: &str, drop_id: Uuid) -> Option<Uuid> {
+    jwt_keys::verify::<QueueClaims>(token, drop_key)
+        .ok()
+        .filter(|claims| claims.drop_id == drop_id)
+        .map(|claims| claims.sub)
+}
+
+// While a drop runs, checkout for its products needs an admitted queue token for it
+pub async fn require_admission(
+    pool: &sqlx::PgPool,
+    lines: &[PricedLine],
+    token: Option<&str>,
+) -> Result<(), (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let ids: Vec<i32> = lines.iter().map(|l| l.product_id).collect();
+    let running = sqlx::query!(
+        "SELECT id, name FROM drops
+         WHERE product_ids && $1 AND starts_at <= NOW() AND (ends_at IS NULL OR ends_at > NOW())
+         ORDER BY starts_at",
+        &ids,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(db_error)?;
+
+    for (drop_id, name) in running.into_iter().map(|d| (d.id, d.name)) {
+        let entry_id = token.and_then(|t| verify_token(t, drop_id));
+        let admitted = match entry_id {
+            Some(entry_id) => sqlx::query_scalar!(
+                r#"SELECT EXISTS (SELECT 1 FROM drop_queue_entries WHERE id = $1 AND admission_expires_at > NOW()) AS "admitted!""#,
+                entry_id,
+            )
+            .fetch_one(pool)
+            .await
+            .map_err(db_error)?,
+            None => false,
+        };
+        if !admitted {
+            return Err((
+                StatusCode::FORBIDDEN,
+                format!("{} has a waiting room; join the queue at /api/drops/{}/queue to check out", name, drop_id),
+            ));
+        }
+    }
+    Ok(())
+}
+
+async fn get_drop(
+    State(app_state): State<Arc<AppState>>,
+    Path(drop_id): Path<Uuid>,
+) -> Result<Json<DropInfo>, (StatusCode, String)> {
+    let info = sqlx::query_as!(
+        DropInfo,
+        r#"SELECT d.id, d.name, d.product_ids, d.starts_at, d.ends_at,
+                (SELECT COUNT(*) FROM drop_queue_entries e
+                 WHERE e.drop_id = d.id AND e.admitted_at IS NULL AND e.last_seen_at > NOW() - make_interval(secs => $2)) AS "waiting!"
+         FROM drops d WHERE d.id = $1"#,
+        drop_id,
+        STALE_SECS as f64,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Drop not found".to_string()))?;
+    Ok(Json(info))
+}
+
+// Join the queue, or get back to an earlier place by sending its token
+async fn join_queue(
+    State(app_state): State<Arc<AppState>>,
+    Path(drop_id): Path<Uuid>,
+    request: Option<Json<JoinRequest>>,
+) -> Result<(StatusCode, Json<QueueStatus>), (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let drop = load_drop(&app_state.pool, drop_id).await?;
+    if has_ended(&drop) {
+        return Err((StatusCode::GONE, "This drop has ended".to_string()));
+    }
+
+    // An expired admission goes to the back of the queue
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    if let Some(token) = request.token {
+        if let Some(entry_id) = verify_token(&token, drop_id) {
+            if let Ok(status) = entry_status(&app_state.pool, &drop, entry_id, token).await {
+                if status.status != "expired" {
+                    return Ok((StatusCode::OK, Json(status)));
+                }
+            }
+        }
+    }
+
+    let mut tx = app_state.pool.begin().await.map_err(db_error)?;
+    let position: i64 =
+        sqlx::query_scalar!(
+            "UPDATE drops SET last_position = last_position + 1 WHERE id = $1 RETURNING last_position",
+            drop_id,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    let entry_id: Uuid =
+        sqlx::query_scalar!(
+            "INSERT INTO drop_queue_entries (drop_id, position) VALUES ($1, $2) RETURNING id",
+            drop_id,
+            position,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    tx.commit().await.map_err(db_error)?;
+
+    let token = sign_token(&drop, entry_id)?;
+    let status = entry_status(&app_state.pool, &drop, entry_id, token).await?;
+    Ok((StatusCode::CREATED, Json(status)))
+}
+
+// Polled by the waiting room page; also keeps the shopper's place
+async fn queue_status(
+    State(app_state): State<Arc<AppState>>,
+    Path(drop_id): Path<Uuid>,
+    Query(query): Query<QueueQuery>,
+) -> Result<Json<QueueStatus>, (StatusCode, String)> {
+    let entry_id = verify_token(&query.token, drop_id)
+        .ok_or((StatusCode::UNAUTHORIZED, "Invalid queue token".to_string()))?;
+    let drop = load_drop(&app_state.pool, drop_id).await?;
+    entry_status(&app_state.pool, &drop, entry_id, query.token).await.map(Json)
+}
+
+async fn list_drops(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<DropSummary>>, (StatusCode, String)> {
+    let rows = sqlx::query!(
+        r#"SELECT d.id, d.name, d.product_ids, d.starts_at, d.ends_at, d.admit_per_minute, d.burst, d.admission_minutes,
+                d.tokens, d.tokens_updated_at, d.last_position, d.created_by, d.created_at, d.updated_at,
+                (SELECT COUNT(*) FROM drop_queue_entries e WHERE e.drop_id = d.id) AS "joined!",
+                (SELECT COUNT(*) FROM drop_queue_entries e
+                 WHERE e.drop_id = d.id AND e.admitted_at IS NULL AND e.last_seen_at > NOW() - make_interval(secs => $1)) AS "waiting!",
+                (SELECT COUNT(*) FROM drop_queue_entries e WHERE e.drop_id = d.id AND e.admitted_at IS NOT NULL) AS "admitted!"
+         FROM drops d
+         ORDER BY d.starts_at DESC"#,
+        STALE_SECS as f64,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let drops = rows
+        .into_iter()
+        .map(|r| DropSummary {
+            drop: ProductDrop {
+                id: r.id,
+                name: r.name,
+                product_ids: r.product_ids,
+                starts_at: r.starts_at,
+                ends_at: r.ends_at,
+                admit_per_minute: r.admit_per_minute,
+                burst: r.burst,
+                admission_minutes: r.admission_minutes,
+                tokens: r.tokens,
+                tokens_updated_at: r.tokens_updated_at,
+                last_position: r.last_position,
+                created_by: r.created_by,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+            },
+            joined: r.joined,
+            waiting: r.waiting,
+            admitted: r.admitted,
+        })
+        .collect();
+    Ok(Json(drops))
+}
+
+fn validate_input(input: &DropInput) -> Result<(), (StatusCode, String)> {
+    let bad_request = |msg: &str| Err((StatusCode::BAD_REQUEST, msg.to_string()));
+    if input.name.trim().is_empty() {
+        return bad_request("name is required");
+    }
+    if input.product_ids.is_empty() {
+        return bad_request("product_ids must list at least one product");
+    }
+    if input.ends_at.is_some_and(|end| end <= input.starts_at) {
+        return bad_request("ends_at must be after starts_at");
+    }
+    if input.admit_per_minute < 1 {
+        return bad_request("admit_per_minute must be at least 1");
+    }
+    if input.burst.is_some_and(|b| b < 1) || input.admission_minutes.is_some_and(|m| m < 1) {
+        return bad_request("burst and admission_minutes must be at least 1");
+    }
+    Ok(())
+}
+
+async fn create_drop(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<DropInput>,
+) -> Result<(StatusCode, Json<ProductDrop>), (StatusCode, String)> {
+    validate_input(&input)?;
+    let drop = sqlx::query_as!(
+        ProductDrop,
+        "INSERT INTO drops (name, product_ids, starts_at, ends_at, admit_per_minute, burst, admission_minutes, created_by)
+         VALUES ($1, $2, $3, $4, $5, COALESCE($6::INT, $5), COALESCE($7::INT, 10), $8)
+         RETURNING id, name, product_ids, starts_at, ends_at, admit_per_minute, burst, admission_minutes, tokens, tokens_updated_at, last_position, created_by, created_at, updated_at",
+        input.name.trim(),
+        &input.product_ids,
+        input.starts_at,
+        input.ends_at,
+        input.admit_per_minute,
+        input.burst,
+        input.admission_minutes,
+        &admin.username,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    tracing::info!("{} created drop {} ({})", admin.username, drop.name, drop.id);
+    Ok((StatusCode::CREATED, Json(drop)))
+}
+
+// Change the drop's products, times or admission rate; the queue keeps its order
+async fn update_drop(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(drop_id): Path<Uuid>,
+    Json(input): Json<DropInput>,
+) -> Result<Json<ProductDrop>, (StatusCode, String)> {
+    validate_input(&input)?;
+    let drop = sqlx::query_as!(
+        ProductDrop,
+        "UPDATE drops SET
+             name = $2, product_ids = $3, starts_at = $4, ends_at = $5, admit_per_minute = $6,
+             burst = COALESCE($7::INT, $6), admission_minutes = COALESCE($8, admission_minutes),
+             tokens = LEAST(tokens, COALESCE($7::INT, $6)::FLOAT8), updated_at = NOW()
+         WHERE id = $1
+         RETURNING id, name, product_ids, starts_at, ends_at, admit_per_minute, burst, admission_minutes, tokens, tokens_updated_at, last_position, created_by, created_at, updated_at",
+        drop_id,
+        input.name.trim(),
+        &input.product_ids,
+        input.starts_at,
+        input.ends_at,
+        input.admit_per_minute,
+        input.burst,
+        input.admission_minutes,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Drop not found".to_string()))?;
+    Ok(Json(drop))
+}
+
+async fn delete_drop(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(drop_id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM drops WHERE id = $1", drop_id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Drop not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,743 @@
+// Dropship Module - Order items shipped by their vendor instead of from our warehouses
+// Products flagged with a dropship vendor are routed when the order is paid: each vendor (and
+// ship-to address) gets a purchase order covering a fulfillment of its items, emailed and/or
+// POSTed to its signed webhook through the job queue. Vendors acknowledge and add tracking in
+// the vendor portal (/api/vendor, X-Vendor-Token); the shipment then reaches the customer like
+// any other, with the shipping email and the order's tracking status
+
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRequestParts, Path, Query, State},
+    http::{request::Parts, StatusCode},
+    routing::{get, post, put},
+    Json, Router,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::branding::{escape_html, load_branding};
+use crate::easypost_shipping::Address;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::integrations::sign_body;
+use crate::jobs;
+use crate::notifications::{queue_email, EmailPriority};
+use crate::order_shipments::OrderShipment;
+use crate::pii::Encrypted;
+use crate::request_id::WithRequestId;
+use crate::split_shipments::{self, ShipFulfillmentRequest, ShipmentLine};
+use crate::warehouses::OrderFulfillment;
+use crate::AppState;
+
+pub const VENDOR_PURCHASE_ORDER_JOB: &str = "vendor_purchase_order";
+const TOKEN_PREFIX: &str = "vnd_";
+const DISPLAY_PREFIX_LEN: usize = 12; // "vnd_" plus 8 characters
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+const LIST_LIMIT: i64 = 100;
+const VENDOR_ORDER_STATUSES: [&str; 4] = ["pending", "sent", "acknowledged", "shipped"];
+
+// Database model for vendors
+#[allow(dead_code)]
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Vendor {
+    pub id: i32,
+    pub name: String,
+    pub email: Option<String>,
+    pub webhook_url: Option<String>,
+    pub webhook_secret: String,
+    pub token_prefix: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub active: bool,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+// Returned on creation and token rotation; only the hash is kept afterwards
+#[derive(Serialize)]
+pub struct VendorWithToken {
+    #[serde(flatten)]
+    pub vendor: Vendor,
+    pub portal_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct VendorInput {
+    pub name: String,
+    pub email: Option<String>,
+    pub webhook_url: Option<String>,
+    pub active: Option<bool>, // Defaults to true
+}
+
+#[derive(Deserialize)]
+pub struct ProductVendorInput {
+    pub vendor_id: Option<i32>, // null to ship the product from our warehouses again
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct VendorOrder {
+    pub id: Uuid,
+    pub vendor_id: i32,
+    pub order_id: Uuid,
+    pub fulfillment_id: Uuid,
+    pub status: String,
+    pub last_error: Option<String>,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+// A purchase order as the vendor receives it
+#[derive(Serialize)]
+pub struct PurchaseOrder {
+    #[serde(flatten)]
+    pub vendor_order: VendorOrder,
+    pub customer_name: Option<String>,
+    pub customer_email: Option<String>,
+    pub ship_to: Option<Address>, // Set for orders shipped to several addresses; otherwise see the order
+    pub items: Vec<ShipmentLine>,
+    pub carrier: Option<String>,
+    pub tracking_code: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct VendorOrderQuery {
+    pub vendor_id: Option<i32>,
+    pub status: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PurchaseOrderJobPayload {
+    vendor_order_id: Uuid,
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn generate_token() -> String {
+    let bytes: [u8; 24] = rand::thread_rng().gen();
+    format!("{}{}", TOKEN_PREFIX, hex::encode(bytes))
+}
+
+fn generate_secret() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    hex::encode(bytes)
+}
+
+// A vendor portal request, authenticated with X-Vendor-Token
+pub struct AuthenticatedVendor {
+    pub vendor_id: i32,
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AuthenticatedVendor {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let provided = parts
+            .headers
+            .get("x-vendor-token")
+            .and_then(|value| value.to_str().ok())
+            .ok_or((StatusCode::UNAUTHORIZED, "Missing X-Vendor-Token header".to_string()))?;
+        let vendor_id: Option<i32> = sqlx::query_scalar!(
+            "SELECT id FROM vendors WHERE token_hash = $1 AND active = TRUE",
+            hash_token(provided),
+        )
+        .fetch_optional(&*state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+        vendor_id
+            .map(|vendor_id| AuthenticatedVendor { vendor_id })
+            .ok_or((StatusCode::UNAUTHORIZED, "Invalid vendor token".to_string()))
+    }
+}
+
+// Create the purchase orders for an order's dropshipped items, unless it already has them.
+// Multi-address orders get one per vendor and address, others one per vendor
+pub async fn route_order(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Vec<VendorOrder>, String> {
+    let db_err = |e: sqlx::Error| format!("DB error: {}", e);
+    let mut tx = pool.begin().await.map_err(db_err)?;
+    let routed: Option<bool> =
+        sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM vendor_orders WHERE order_id = $1) AS "routed!" FROM orders WHERE id = $1 FOR UPDATE"#,
+            order_id,
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_err)?;
+    if routed != Some(false) {
+        return Ok(Vec::new());
+    }
+
+    let lines: Vec<(i32, Option<Uuid>, i32, i32)> = sqlx::query!(
+        r#"SELECT p.dropship_vendor_id AS "vendor_id!", s.id AS shipment_id, si.product_id AS "product_id!", si.quantity AS "quantity!"
+         FROM order_shipments s
+         JOIN order_shipment_items si ON si.shipment_id = s.id
+         JOIN products p ON p.id = si.product_id
+         JOIN vendors v ON v.id = p.dropship_vendor_id AND v.active
+         WHERE s.order_id = $1
+         UNION ALL
+         SELECT p.dropship_vendor_id, NULL, oi.product_id, oi.quantity
+         FROM order_items oi
+         JOIN products p ON p.id = oi.product_id
+         JOIN vendors v ON v.id = p.dropship_vendor_id AND v.active
+         WHERE oi.order_id = $1 AND NOT EXISTS (SELECT 1 FROM order_shipments WHERE order_id = $1)"#,
+        order_id,
+    )
+    .map(|r| (r.vendor_id, r.shipment_id, r.product_id, r.quantity))
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(db_err)?;
+    if lines.is_empty() {
+        return Ok(Vec::new());
+    }
+    let quantities: Vec<(i32, i32)> = lines.iter().map(|&(_, _, product_id, quantity)| (product_id, quantity)).collect();
+    split_shipments::check_remaining(&mut tx, order_id, &quantities)
+        .await
+        .map_err(|e| <(StatusCode, String)>::from(e).1)?;
+
+    let mut groups = BTreeMap::<_, Vec<_>>::new();
+    for (vendor_id, shipment_id, product_id, quantity) in lines {
+        groups.entry((vendor_id, shipment_id)).or_default().push((product_id, quantity));
+    }
+    let mut created = Vec::new();
+    for ((vendor_id, shipment_id), items) in groups {
+        let fulfillment_id: Uuid = sqlx::query_scalar!(
+            "INSERT INTO order_fulfillments (order_id, order_shipment_id) VALUES ($1, $2) RETURNING id",
+            order_id,
+            shipment_id,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_err)?;
+        for (product_id, quantity) in items {
+            sqlx::query!(
+                "INSERT INTO order_fulfillment_items (fulfillment_id, product_id, quantity) VALUES ($1, $2, $3)",
+                fulfillment_id,
+                product_id,
+                quantity,
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(db_err)?;
+        }
+        let vendor_order = sqlx::query_as!(
+            VendorOrder,
+            "INSERT INTO vendor_orders (vendor_id, order_id, fulfillment_id) VALUES ($1, $2, $3) RETURNING id, vendor_id, order_id, fulfillment_id, status, last_error, sent_at, acknowledged_at, created_at",
+            vendor_id,
+            order_id,
+            fulfillment_id,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_err)?;
+        created.push(vendor_order);
+    }
+    tx.commit().await.map_err(db_err)?;
+
+    for vendor_order in &created {
+        queue_purchase_order(pool, vendor_order.id)
+            .await
+            .map_err(|e| format!("Failed to queue purchase order {}: {}", vendor_order.id, e))?;
+    }
+    Ok(created)
+}
+
+async fn queue_purchase_order(pool: &sqlx::PgPool, vendor_order_id: Uuid) -> Result<Uuid, sqlx::Error> {
+    jobs::enqueue(pool, VENDOR_PURCHASE_ORDER_JOB, json!({ "vendor_order_id": vendor_order_id }), Utc::now()).await
+}
+
+async fn load_purchase_order(pool: &sqlx::PgPool, vendor_order: VendorOrder) -> Result<PurchaseOrder, sqlx::Error> {
+    let (customer_name, customer_email): (Option<String>, Option<Encrypted>) =
+        sqlx::query!(r#"SELECT customer_name, customer_email AS "customer_email: Encrypted" FROM orders WHERE id = $1"#, vendor_order.order_id)
+            .map(|r| (r.customer_name, r.customer_email))
+            .fetch_one(pool)
+            .await?;
+    let fulfillment = sqlx::query_as!(
+        OrderFulfillment,
+        "SELECT id, order_id, warehouse_id, status, carrier, tracking_code, shipment_id, created_at, updated_at, order_shipment_id, shipped_at, delivered_at FROM order_fulfillments WHERE id = $1",
+        vendor_order.fulfillment_id,
+    )
+    .fetch_one(pool)
+    .await?;
+    let ship_to = match fulfillment.order_shipment_id {
+        Some(shipment_id) => sqlx::query_as!(
+            OrderShipment,
+            r#"SELECT id, payment_intent_id, order_id, address_key, recipient_name AS "recipient_name: Encrypted", street1 AS "street1: Encrypted", street2 AS "street2: Encrypted", city, state, zip, country, phone AS "phone: Encrypted", shipping_method, subtotal_amount, shipping_amount, status, created_at FROM order_shipments WHERE id = $1"#,
+            shipment_id,
+        )
+        .fetch_optional(pool)
+        .await?
+        .map(|shipment| shipment.address()),
+        None => None,
+    };
+    Ok(PurchaseOrder {
+        items: split_shipments::shipment_lines(pool, fulfillment.id).await?,
+        vendor_order,
+        customer_name,
+        customer_email: customer_email.map(Encrypted::into_inner),
+        ship_to,
+        carrier: fulfillment.carrier,
+        tracking_code: fulfillment.tracking_code,
+    })
+}
+
+fn purchase_order_html(purchase_order: &PurchaseOrder) -> String {
+    let items: String = purchase_order
+        .items
+        .iter()
+        .map(|item| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                item.product_id.map(|id| id.to_string()).unwrap_or_default(),
+                escape_html(item.product_name.as_deref().unwrap_or("Item")),
+                item.quantity
+            )
+        })
+        .collect();
+    let ship_to = match &purchase_order.ship_to {
+        Some(address) => format!(
+            "{}<br>{}{}<br>{}, {} {}<br>{}",
+            escape_html(address.name.as_deref().or(purchase_order.customer_name.as_deref()).unwrap_or_default()),
+            escape_html(&address.street1),
+            address.street2.as_deref().map(|s| format!("<br>{}", escape_html(s))).unwrap_or_default(),
+            escape_html(&address.city),
+            escape_html(&address.state),
+            escape_html(&address.zip),
+            escape_html(address.country.as_deref().unwrap_or("US")),
+        ),
+        None => escape_html(purchase_order.customer_name.as_deref().unwrap_or("See the order")),
+    };
+    let portal = app_config::get().server.public_api_url.trim_end_matches('/').to_string();
+    format!(
+        r#"<p>Please ship the items below directly to our customer.</p>
+        <p>Purchase order: {}<br>Order: {}</p>
+        <p>Ship to:<br>{}</p>
+        <table><tr><th>Product</th><th>Name</th><th>Quantity</th></tr>{}</table>
+        <p>Acknowledge it with <code>POST {}/api/vendor/orders/{}/acknowledge</code> and add the tracking number with
+        <code>POST {}/api/vendor/orders/{}/ship</code>, using your vendor portal token.</p>"#,
+        purchase_order.vendor_order.id,
+        purchase_order.vendor_order.order_id,
+        ship_to,
+        items,
+        portal,
+        purchase_order.vendor_order.id,
+        portal,
+        purchase_order.vendor_order.id,
+    )
+}
+
+// POST the purchase order to the vendor's webhook, signed with its secret
+async fn post_purchase_order(vendor: &Vendor, url: &str, purchase_order: &PurchaseOrder) -> Result<(), String> {
+    let body = json!({ "event": "purchase_order.created", "purchase_order": purchase_order }).to_string();
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| format!("HTTP client error: {}", e))?;
+    let response = client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header("X-RCom-Event", "purchase_order.created")
+        .header("X-RCom-Signature", sign_body(&vendor.webhook_secret, &body)?)
+        .body(body)
+        .with_request_id()
+        .send()
+        .await
+        .map_err(|e| format!("Vendor webhook request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Vendor webhook returned {}", response.status()));
+    }
+    Ok(())
+}
+
+// Job handler: send a purchase order to its vendor by webhook and/or email. A failed webhook is
+// retried before the email goes out, so a retry never emails twice
+pub async fn run_vendor_purchase_order(pool: &sqlx::PgPool, payload: &serde_json::Value) -> Result<(), String> {
+    let payload: PurchaseOrderJobPayload =
+        serde_json::from_value(payload.clone()).map_err(|e| format!("Invalid purchase order payload: {}", e))?;
+    let db_err = |e: sqlx::Error| format!("DB error: {}", e);
+    let vendor_order = sqlx::query_as!(
+        VendorOrder,
+        "SELECT id, vendor_id, order_id, fulfillment_id, status, last_error, sent_at, acknowledged_at, created_at FROM vendor_orders WHERE id = $1",
+        payload.vendor_order_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(db_err)?
+    .ok_or_else(|| format!("Purchase order {} not found", payload.vendor_order_id))?;
+    let vendor = sqlx::query_as!(Vendor, "SELECT id, name, email, webhook_url, webhook_secret, token_prefix, active, created_at, token_hash FROM vendors WHERE id = $1", vendor_order.vendor_id)
+        .fetch_one(pool)
+        .await
+        .map_err(db_err)?;
+    let purchase_order = load_purchase_order(pool, vendor_order).await.map_err(db_err)?;
+
+    let sent = async {
+        if let Some(url) = &vendor.webhook_url {
+            post_purchase_order(&vendor, url, &purchase_order).await?;
+        }
+        if let Some(email) = &vendor.email {
+            let html_body = load_branding(pool).await.email_html("Purchase Order", &purchase_order_html(&purchase_order));
+            let subject = format!("Purchase order {}", purchase_order.vendor_order.id);
+            queue_email(pool, email, &subject, &html_body, EmailPriority::Immediate).await?;
+        }
+        Ok::<(), String>(())
+    }
+    .await;
+
+    let id = purchase_order.vendor_order.id;
+    sqlx::query!(
+        "UPDATE vendor_orders SET last_error = $2::TEXT,
+                status = CASE WHEN $2::TEXT IS NULL AND status = 'pending' THEN 'sent' ELSE status END,
+                sent_at = CASE WHEN $2::TEXT IS NULL THEN NOW() ELSE sent_at END
+         WHERE id = $1",
+        id,
+        sent.as_ref().err(),
+    )
+    .execute(pool)
+    .await
+    .map_err(db_err)?;
+    sent?;
+    tracing::info!("Sent purchase order {} to vendor {}", id, vendor.name);
+    Ok(())
+}
+
+// Routes an order's dropshipped items to their vendors once it's paid
+pub struct DropshipSubscriber {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl DropshipSubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for DropshipSubscriber {
+    fn name(&self) -> &'static str {
+        "dropship"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let DomainEvent::OrderCreated { order_id, .. } = event else {
+            return Ok(());
+        };
+        for vendor_order in route_order(&self.pool, *order_id).await? {
+            tracing::info!("Routed order {} items to vendor {} ({})", order_id, vendor_order.vendor_id, vendor_order.id);
+        }
+        Ok(())
+    }
+}
+
+pub fn dropship_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/vendors", get(list_vendors).post(create_vendor))
+        .route("/api/admin/vendors/:id", put(update_vendor))
+        .route("/api/admin/vendors/:id/token", post(rotate_token))
+        .route("/api/admin/products/:id/vendor", put(set_product_vendor))
+        .route("/api/admin/vendor-orders", get(list_vendor_orders))
+        .route("/api/admin/vendor-orders/:id/resend", post(resend_purchase_order))
+        .route("/api/vendor/orders", get(vendor_list_orders))
+        .route("/api/vendor/orders/:id/acknowledge", post(vendor_acknowledge))
+        .route("/api/vendor/orders/:id/ship", post(vendor_ship))
+        .with_state(app_state)
+}
+
+// Trim the fields and require a way to send purchase orders
+fn validate_vendor(input: VendorInput) -> Result<VendorInput, (StatusCode, String)> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "name is required".to_string()));
+    }
+    let email = input.email.as_deref().map(str::trim).filter(|e| !e.is_empty());
+    if email.is_some_and(|e| !e.contains('@')) {
+        return Err((StatusCode::BAD_REQUEST, "email is not a valid address".to_string()));
+    }
+    let webhook_url = input.webhook_url.as_deref().map(str::trim).filter(|u| !u.is_empty());
+    if webhook_url.is_some_and(|u| !u.starts_with("https://") && !u.starts_with("http://")) {
+        return Err((StatusCode::BAD_REQUEST, "webhook_url must be an http(s) URL".to_string()));
+    }
+    if email.is_none() && webhook_url.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "email or webhook_url is required".to_string()));
+    }
+    Ok(VendorInput {
+        name: name.to_string(),
+        email: email.map(str::to_string),
+        webhook_url: webhook_url.map(str::to_string),
+        active: input.active,
+    })
+}
+
+async fn list_vendors(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Vendor>>, (StatusCode, String)> {
+    let vendors = sqlx::query_as!(Vendor, "SELECT id, name, email, webhook_url, webhook_secret, token_prefix, active, created_at, token_hash FROM vendors ORDER BY name, id")
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(vendors))
+}
+
+// The portal token and webhook secret are generated here; the token is only returned now
+async fn create_vendor(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<VendorInput>,
+) -> Result<(StatusCode, Json<VendorWithToken>), (StatusCode, String)> {
+    let input = validate_vendor(input)?;
+    let token = generate_token();
+    let vendor = sqlx::query_as!(
+        Vendor,
+        "INSERT INTO vendors (name, email, webhook_url, webhook_secret, token_prefix, token_hash, active)
+         VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id, name, email, webhook_url, webhook_secret, token_prefix, active, created_at, token_hash",
+        input.name,
+        input.email,
+        input.webhook_url,
+        generate_secret(),
+        &token[..DISPLAY_PREFIX_LEN],
+        hash_token(&token),
+        input.active.unwrap_or(true),
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok((StatusCode::CREATED, Json(VendorWithToken { vendor, portal_token: token })))
+}
+
+// Inactive vendors get no new purchase orders and can't use the portal
+async fn update_vendor(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(input): Json<VendorInput>,
+) -> Result<Json<Vendor>, (StatusCode, String)> {
+    let input = validate_vendor(input)?;
+    sqlx::query_as!(
+        Vendor,
+        "UPDATE vendors SET name = $2, email = $3, webhook_url = $4, active = COALESCE($5, active)
+         WHERE id = $1 RETURNING id, name, email, webhook_url, webhook_secret, token_prefix, active, created_at, token_hash",
+        id,
+        input.name,
+        input.email,
+        input.webhook_url,
+        input.active,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .map(Json)
+    .ok_or((StatusCode::NOT_FOUND, "Vendor not found".to_string()))
+}
+
+// Replace the vendor's portal token; the old one stops working right away
+async fn rotate_token(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<VendorWithToken>, (StatusCode, String)> {
+    let token = generate_token();
+    let vendor = sqlx::query_as!(
+        Vendor,
+        "UPDATE vendors SET token_prefix = $2, token_hash = $3 WHERE id = $1 RETURNING id, name, email, webhook_url, webhook_secret, token_prefix, active, created_at, token_hash",
+        id,
+        &token[..DISPLAY_PREFIX_LEN],
+        hash_token(&token),
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Vendor not found".to_string()))?;
+    Ok(Json(VendorWithToken { vendor, portal_token: token }))
+}
+
+// Flag a product as dropshipped by a vendor, from the next paid order on
+async fn set_product_vendor(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+    Json(input): Json<ProductVendorInput>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    if let Some(vendor_id) = input.vendor_id {
+        let exists: bool = sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM vendors WHERE id = $1) AS "exists!""#, vendor_id)
+            .fetch_one(&*app_state.pool)
+            .await
+            .map_err(db_err)?;
+        if !exists {
+            return Err((StatusCode::BAD_REQUEST, format!("Vendor {} not found", vendor_id)));
+        }
+    }
+    let res = sqlx::query!("UPDATE products SET dropship_vendor_id = $2 WHERE id = $1", product_id, input.vendor_id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(db_err)?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Product not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn find_vendor_orders(
+    pool: &sqlx::PgPool,
+    vendor_id: Option<i32>,
+    status: Option<&str>,
+) -> Result<Vec<PurchaseOrder>, (StatusCode, String)> {
+    if let Some(status) = status.filter(|s| !VENDOR_ORDER_STATUSES.contains(s)) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown status '{}'; expected one of {}", status, VENDOR_ORDER_STATUSES.join(", ")),
+        ));
+    }
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let vendor_orders = sqlx::query_as!(
+        VendorOrder,
+        "SELECT id, vendor_id, order_id, fulfillment_id, status, last_error, sent_at, acknowledged_at, created_at FROM vendor_orders
+         WHERE ($1::INT IS NULL OR vendor_id = $1) AND ($2::VARCHAR IS NULL OR status = $2)
+         ORDER BY created_at DESC LIMIT $3",
+        vendor_id,
+        status,
+        LIST_LIMIT,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(db_err)?;
+    let mut purchase_orders = Vec::new();
+    for vendor_order in vendor_orders {
+        purchase_orders.push(load_purchase_order(pool, vendor_order).await.map_err(db_err)?);
+    }
+    Ok(purchase_orders)
+}
+
+// The latest purchase orders, newest first
+async fn list_vendor_orders(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<VendorOrderQuery>,
+) -> Result<Json<Vec<PurchaseOrder>>, (StatusCode, String)> {
+    find_vendor_orders(&app_state.pool, query.vendor_id, query.status.as_deref())
+        .await
+        .map(Json)
+}
+
+// Send a purchase order again, e.g. after fixing the vendor's email or webhook
+async fn resend_purchase_order(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let status: String = sqlx::query_scalar!("SELECT status FROM vendor_orders WHERE id = $1", id)
+        .fetch_optional(&*app_state.pool)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::NOT_FOUND, "Purchase order not found".to_string()))?;
+    if status == "shipped" {
+        return Err((StatusCode::CONFLICT, "Purchase order has already shipped".to_string()));
+    }
+    queue_purchase_order(&app_state.pool, id).await.map_err(db_err)?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+// The vendor's purchase orders, newest first
+async fn vendor_list_orders(
+    vendor: AuthenticatedVendor,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<VendorOrderQuery>,
+) -> Result<Json<Vec<PurchaseOrder>>, (StatusCode, String)> {
+    find_vendor_orders(&app_state.pool, Some(vendor.vendor_id), query.status.as_deref())
+        .await
+        .map(Json)
+}
+
+// The vendor's own purchase order, locked for the change; other vendors' return 404
+async fn lock_vendor_order(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    vendor: &AuthenticatedVendor,
+    id: Uuid,
+) -> Result<VendorOrder, (StatusCode, String)> {
+    sqlx::query_as!(
+        VendorOrder,
+        "SELECT id, vendor_id, order_id, fulfillment_id, status, last_error, sent_at, acknowledged_at, created_at FROM vendor_orders WHERE id = $1 AND vendor_id = $2 FOR UPDATE",
+        id,
+        vendor.vendor_id,
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Purchase order not found".to_string()))
+}
+
+async fn vendor_acknowledge(
+    vendor: AuthenticatedVendor,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<VendorOrder>, (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let mut tx = app_state.pool.begin().await.map_err(db_err)?;
+    let vendor_order = lock_vendor_order(&mut tx, &vendor, id).await?;
+    if vendor_order.status == "shipped" {
+        return Err((StatusCode::CONFLICT, "Purchase order has already shipped".to_string()));
+    }
+    let vendor_order = sqlx::query_as!(
+        VendorOrder,
+        "UPDATE vendor_orders SET status = 'acknowledged', acknowledged_at = COALESCE(acknowledged_at, NOW())
+         WHERE id = $1 RETURNING id, vendor_id, order_id, fulfillment_id, status, last_error, sent_at, acknowledged_at, created_at",
+        id,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_err)?;
+    tx.commit().await.map_err(db_err)?;
+    Ok(Json(vendor_order))
+}
+
+// The vendor shipped the purchase order: its tracking goes on the fulfillment and to the customer
+async fn vendor_ship(
+    vendor: AuthenticatedVendor,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<ShipFulfillmentRequest>,
+) -> Result<Json<PurchaseOrder>, (StatusCode, String)> {
+    let carrier = request.carrier.trim();
+    if carrier.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "carrier is required".to_string()));
+    }
+    let tracking_code = request.tracking_code.as_deref().map(str::trim).filter(|t| !t.is_empty());
+
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let mut tx = app_state.pool.begin().await.map_err(db_err)?;
+    let vendor_order = lock_vendor_order(&mut tx, &vendor, id).await?;
+    if vendor_order.status == "shipped" {
+        return Err((StatusCode::CONFLICT, "Purchase order has already shipped".to_string()));
+    }
+    let fulfillment = split_shipments::mark_shipped(&mut tx, vendor_order.fulfillment_id, carrier, tracking_code, None)
+        .await
+        .map_err(db_err)?;
+    let vendor_order = sqlx::query_as!(
+        VendorOrder,
+        "UPDATE vendor_orders SET status = 'shipped', acknowledged_at = COALESCE(acknowledged_at, NOW())
+         WHERE id = $1 RETURNING id, vendor_id, order_id, fulfillment_id, status, last_error, sent_at, acknowledged_at, created_at",
+        id,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_err)?;
+    tx.commit().await.map_err(db_err)?;
+
+    tracing::info!("Vendor {} shipped purchase order {} for order {}", vendor.vendor_id, id, vendor_order.order_id);
+    app_state.events.publish(DomainEvent::OrderShipped {
+        order_id: fulfillment.order_id,
+        fulfillment_id: fulfillment.id,
+        carrier: fulfillment.carrier.clone(),
+        tracking_code: fulfillment.tracking_code.clone(),
+    });
+    Ok(Json(load_purchase_order(&app_state.pool, vendor_order).await.map_err(db_err)?))
+}
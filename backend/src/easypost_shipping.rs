@@ -4,26 +4,134 @@
 
 use axum::{Json, Router, routing::{post, get}, extract::{State, Path}, http::StatusCode};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::app_config;
+use crate::pricing::{CartLine, PriceBook};
+use crate::provider_breaker::{GuardedSend, Provider};
+use crate::provider_retry::RetrySend;
+use crate::request_id::WithRequestId;
+use crate::route_limits::{self, RouteGroup};
+use crate::warehouses::{plan_fulfillment, Warehouse};
 use crate::AppState;
-use reqwest;
 
 // EasyPost configuration
 pub struct ShippingConfig {
     pub easypost_api_key: String,
     pub easypost_api_url: String,
+    pub carrier_accounts: Vec<String>, // Carrier account IDs to rate with; empty = all accounts
+    pub carriers: Vec<String>,         // Allowed carriers, e.g. "USPS"; empty = any
+    pub services: Vec<String>,         // Allowed service levels, e.g. "Priority"; empty = any
+    pub rate_cache_ttl: Duration,
+    pub tracking_cache_ttl: Duration,
+    pub tracking_stale: Duration,
 }
 
 impl ShippingConfig {
     pub fn from_env() -> Option<Self> {
+        let easypost = &app_config::get().easypost;
         Some(Self {
-            easypost_api_key: std::env::var("EASYPOST_API_KEY").ok()?,
-            easypost_api_url: std::env::var("EASYPOST_API_URL")
-                .unwrap_or_else(|_| "https://api.easypost.com/v2".to_string()),
+            easypost_api_key: easypost.api_key.as_ref()?.expose().to_string(),
+            easypost_api_url: easypost.api_url.clone(),
+            carrier_accounts: easypost.carrier_accounts.clone(),
+            carriers: easypost.carriers.clone(),
+            services: easypost.services.clone(),
+            rate_cache_ttl: Duration::from_secs(easypost.rate_cache_ttl_secs),
+            tracking_cache_ttl: Duration::from_secs(easypost.tracking_cache_ttl_secs),
+            tracking_stale: Duration::from_secs(easypost.tracking_stale_secs),
         })
     }
 }
 
+// Short-lived cache of rated EasyPost shipments, keyed by origin, destination
+// ZIP, parcel and carrier accounts, so repeated quotes for the same cart don't
+// hit the API again
+#[derive(Default)]
+pub struct RateCache {
+    entries: Mutex<HashMap<String, (Instant, EasyPostShipment)>>,
+}
+
+impl RateCache {
+    fn get(&self, key: &str, ttl: Duration) -> Option<EasyPostShipment> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .filter(|(created, _)| created.elapsed() < ttl)
+            .map(|(_, shipment)| shipment.clone())
+    }
+
+    fn insert(&self, key: String, shipment: EasyPostShipment, ttl: Duration) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (created, _)| created.elapsed() < ttl);
+        entries.insert(key, (Instant::now(), shipment));
+    }
+
+    // Drop a shipment that has been bought and can no longer be re-quoted
+    fn invalidate(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+// Tracking lookups per tracking code, so customers refreshing a tracking page don't each hit
+// EasyPost. Within the TTL an entry is served as is. After that, up to `tracking_stale` more,
+// it is still served but one lookup refreshes it in the background. tracker.updated webhooks
+// drop the entry, so the next lookup has the new status
+#[derive(Default)]
+pub struct TrackingCache {
+    entries: Mutex<HashMap<String, TrackingEntry>>,
+}
+
+struct TrackingEntry {
+    fetched: Instant,
+    response: TrackingResponse,
+    refreshing: bool,
+}
+
+enum TrackingLookup {
+    Fresh(TrackingResponse),
+    Stale { response: TrackingResponse, refresh: bool }, // `refresh`: this lookup should start the refresh
+    Miss,
+}
+
+impl TrackingCache {
+    fn lookup(&self, tracking_code: &str, config: &ShippingConfig) -> TrackingLookup {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(entry) = entries.get_mut(tracking_code) else {
+            return TrackingLookup::Miss;
+        };
+        let age = entry.fetched.elapsed();
+        if age < config.tracking_cache_ttl {
+            TrackingLookup::Fresh(entry.response.clone())
+        } else if age < config.tracking_cache_ttl + config.tracking_stale {
+            let refresh = !entry.refreshing;
+            entry.refreshing = true;
+            TrackingLookup::Stale { response: entry.response.clone(), refresh }
+        } else {
+            entries.remove(tracking_code);
+            TrackingLookup::Miss
+        }
+    }
+
+    fn insert(&self, tracking_code: String, response: TrackingResponse, config: &ShippingConfig) {
+        let mut entries = self.entries.lock().unwrap();
+        let keep = config.tracking_cache_ttl + config.tracking_stale;
+        entries.retain(|_, entry| entry.fetched.elapsed() < keep);
+        entries.insert(tracking_code, TrackingEntry { fetched: Instant::now(), response, refreshing: false });
+    }
+
+    // Let the next stale lookup try the refresh again
+    fn refresh_failed(&self, tracking_code: &str) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(tracking_code) {
+            entry.refreshing = false;
+        }
+    }
+
+    pub fn invalidate(&self, tracking_code: &str) {
+        self.entries.lock().unwrap().remove(tracking_code);
+    }
+}
+
 // Add shipping config to AppState
 impl AppState {
     pub fn shipping_config(&self) -> Option<ShippingConfig> {
@@ -38,6 +146,19 @@ pub struct GetRatesRequest {
     pub from_address: Address,
     pub to_address: Address,
     pub parcel: Parcel,
+    pub customs_info: Option<CustomsInfo>, // International only; built from items when omitted
+    pub items: Option<Vec<CartLine>>,      // Parcel contents, used for customs and export checks
+    #[serde(flatten)]
+    pub selection: RateSelection,
+}
+
+// Per-request carrier account and service level choices, narrowing the
+// EASYPOST_CARRIER_ACCOUNTS / EASYPOST_CARRIERS / EASYPOST_SERVICES config
+#[derive(Deserialize, Default, Clone)]
+pub struct RateSelection {
+    pub carrier_accounts: Option<Vec<String>>,
+    pub carriers: Option<Vec<String>>,
+    pub services: Option<Vec<String>>,
 }
 
 #[derive(Deserialize)]
@@ -46,6 +167,17 @@ pub struct CreateShipmentRequest {
     pub to_address: Address,
     pub parcel: Parcel,
     pub rate_id: Option<String>, // If provided, buy this specific rate
+    pub customs_info: Option<CustomsInfo>, // International only; built from items when omitted
+    pub items: Option<Vec<CartLine>>,      // Parcel contents, used for customs and export checks
+}
+
+#[derive(Deserialize)]
+pub struct CartRatesRequest {
+    pub from_address: Option<Address>, // When omitted, ship from the closest stocked warehouses
+    pub to_address: Address,
+    pub items: Vec<CartLine>,
+    #[serde(flatten)]
+    pub selection: RateSelection,
 }
 
 #[derive(Deserialize)]
@@ -99,6 +231,33 @@ pub struct ShippingRate {
     pub delivery_date: Option<String>,
 }
 
+#[derive(Serialize)]
+pub struct CartRatesResponse {
+    pub success: bool,
+    pub parcels: Vec<Parcel>,
+    pub shipment_ids: Vec<String>,
+    pub origins: Vec<OriginParcels>,
+    pub rates: Vec<CartShippingRate>,
+}
+
+// Parcels shipped from a single origin
+#[derive(Serialize)]
+pub struct OriginParcels {
+    pub warehouse_id: Option<i32>,
+    pub warehouse_name: Option<String>,
+    pub parcels: Vec<Parcel>,
+}
+
+// Rate for a carrier service summed across every parcel in the cart
+#[derive(Serialize)]
+pub struct CartShippingRate {
+    pub carrier: String,
+    pub service: String,
+    pub rate: String,
+    pub currency: String,
+    pub delivery_days: Option<i32>,
+}
+
 #[derive(Serialize)]
 pub struct CreateShipmentResponse {
     pub success: bool,
@@ -106,6 +265,13 @@ pub struct CreateShipmentResponse {
     pub tracking_code: String,
     pub label_url: String,
     pub postage_label: PostageLabel,
+    pub forms: Vec<ShipmentForm>, // Customs forms (e.g. commercial invoice) for international labels
+}
+
+#[derive(Serialize)]
+pub struct ShipmentForm {
+    pub form_type: String,
+    pub form_url: String,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -115,7 +281,7 @@ pub struct PostageLabel {
     pub label_zpl_url: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 pub struct TrackingResponse {
     pub success: bool,
     pub tracking_code: String,
@@ -124,7 +290,7 @@ pub struct TrackingResponse {
     pub tracking_details: Vec<TrackingDetail>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TrackingDetail {
     pub datetime: String,
     pub status: String,
@@ -144,15 +310,23 @@ pub struct AddressValidationResponse {
 
 // ===== EasyPost API Response Structures =====
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 struct EasyPostShipment {
     id: String,
     rates: Vec<EasyPostRate>,
     postage_label: Option<EasyPostLabel>,
     tracking_code: Option<String>,
+    #[serde(default)]
+    forms: Vec<EasyPostForm>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
+struct EasyPostForm {
+    form_type: String,
+    form_url: String,
+}
+
+#[derive(Deserialize, Clone)]
 struct EasyPostRate {
     id: String,
     carrier: String,
@@ -163,7 +337,7 @@ struct EasyPostRate {
     delivery_date: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 struct EasyPostLabel {
     label_url: String,
     label_pdf_url: Option<String>,
@@ -219,71 +393,398 @@ struct EasyPostError {
     message: String,
 }
 
-// ===== Routes =====
+// ===== Parcel Packing =====
+
+// Fallbacks for products without shipping data
+const DEFAULT_ITEM_WEIGHT_OZ: f64 = 8.0;
+const DEFAULT_ITEM_DIMENSIONS_IN: (f64, f64, f64) = (6.0, 4.0, 2.0);
+// Split into another parcel above 50 lb
+pub const MAX_PARCEL_WEIGHT_OZ: f64 = 800.0;
+
+// Per-unit shipping data for one cart line
+#[derive(Debug, Clone)]
+pub struct PackableItem {
+    pub weight_oz: f64,
+    pub length_in: f64,
+    pub width_in: f64,
+    pub height_in: f64,
+    pub quantity: i32,
+}
 
-pub fn easypost_shipping_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
-    Router::new()
-        .route("/api/shipping/rates", post(get_shipping_rates))
-        .route("/api/shipping/create-label", post(create_shipping_label))
-        .route("/api/shipping/track/:tracking_code", get(track_shipment))
-        .route("/api/shipping/validate-address", post(validate_address))
-        .with_state(app_state)
+#[derive(sqlx::FromRow)]
+struct ProductDimensions {
+    id: i32,
+    weight_oz: Option<f64>,
+    length_in: Option<f64>,
+    width_in: Option<f64>,
+    height_in: Option<f64>,
 }
 
-// ===== API Handlers =====
+// Aggregate cart items into parcels using first-fit-decreasing by weight.
+// Units are stacked: footprint is the largest unit, height is the sum of heights.
+pub fn pack_parcels(items: &[PackableItem], max_weight_oz: f64) -> Vec<Parcel> {
+    let mut units: Vec<&PackableItem> = items
+        .iter()
+        .flat_map(|item| std::iter::repeat_n(item, item.quantity.max(0) as usize))
+        .collect();
+    units.sort_by(|a, b| b.weight_oz.total_cmp(&a.weight_oz));
+
+    let mut parcels: Vec<Parcel> = Vec::new();
+    for unit in units {
+        let target = parcels
+            .iter_mut()
+            .find(|p| p.weight + unit.weight_oz <= max_weight_oz);
+        match target {
+            Some(parcel) => {
+                parcel.weight += unit.weight_oz;
+                parcel.length = parcel.length.max(unit.length_in);
+                parcel.width = parcel.width.max(unit.width_in);
+                parcel.height += unit.height_in;
+            }
+            None => parcels.push(Parcel {
+                length: unit.length_in,
+                width: unit.width_in,
+                height: unit.height_in,
+                weight: unit.weight_oz,
+            }),
+        }
+    }
+    parcels
+}
 
-// Get shipping rates
-async fn get_shipping_rates(
-    State(state): State<Arc<AppState>>,
-    Json(payload): Json<GetRatesRequest>,
-) -> Result<Json<ShippingRatesResponse>, (StatusCode, String)> {
-    let config = state.shipping_config()
-        .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Shipping not configured".to_string()))?;
+// Load shipping data for cart lines from the products table
+pub async fn packable_items(pool: &sqlx::PgPool, lines: &[CartLine]) -> Result<Vec<PackableItem>, String> {
+    let ids: Vec<i32> = lines.iter().map(|l| l.product_id).collect();
+    let rows = sqlx::query_as!(
+        ProductDimensions,
+        "SELECT id, weight_oz, length_in, width_in, height_in FROM products WHERE id = ANY($1)",
+        &ids,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Database error: {}", e))?;
+    let dims: HashMap<i32, ProductDimensions> = rows.into_iter().map(|d| (d.id, d)).collect();
+
+    let (default_l, default_w, default_h) = DEFAULT_ITEM_DIMENSIONS_IN;
+    lines
+        .iter()
+        .map(|line| {
+            let d = dims
+                .get(&line.product_id)
+                .ok_or_else(|| format!("Unknown product {}", line.product_id))?;
+            Ok(PackableItem {
+                weight_oz: d.weight_oz.unwrap_or(DEFAULT_ITEM_WEIGHT_OZ),
+                length_in: d.length_in.unwrap_or(default_l),
+                width_in: d.width_in.unwrap_or(default_w),
+                height_in: d.height_in.unwrap_or(default_h),
+                quantity: line.quantity,
+            })
+        })
+        .collect()
+}
 
-    // Create shipment to get rates
-    let client = reqwest::Client::new();
-    let url = format!("{}/shipments", config.easypost_api_url);
+// ===== Customs / International =====
 
-    let mut shipment_data = serde_json::json!({
-        "shipment": {
-            "to_address": {
-                "street1": payload.to_address.street1,
-                "city": payload.to_address.city,
-                "state": payload.to_address.state,
-                "zip": payload.to_address.zip,
-                "country": payload.to_address.country.unwrap_or_else(|| "US".to_string()),
-            },
-            "from_address": {
-                "street1": payload.from_address.street1,
-                "city": payload.from_address.city,
-                "state": payload.from_address.state,
-                "zip": payload.from_address.zip,
-                "country": payload.from_address.country.unwrap_or_else(|| "US".to_string()),
-            },
-            "parcel": {
-                "length": payload.parcel.length,
-                "width": payload.parcel.width,
-                "height": payload.parcel.height,
-                "weight": payload.parcel.weight,
+// Customs declaration sent with international shipments (EasyPost customs_info)
+#[derive(Deserialize, Serialize, Clone)]
+pub struct CustomsInfo {
+    #[serde(default = "default_contents_type")]
+    pub contents_type: String, // merchandise, gift, documents, returned_goods, sample, other
+    pub contents_explanation: Option<String>,
+    #[serde(default = "default_customs_certify")]
+    pub customs_certify: bool,
+    pub customs_signer: Option<String>,
+    #[serde(default = "default_non_delivery_option")]
+    pub non_delivery_option: String, // return or abandon
+    pub eel_pfc: Option<String>,
+    pub customs_items: Vec<CustomsItem>,
+}
+
+// One declared line of a customs form
+#[derive(Deserialize, Serialize, Clone)]
+pub struct CustomsItem {
+    pub description: String,
+    pub quantity: i32,
+    pub value: f64,  // Total declared value for the line, in USD
+    pub weight: f64, // Total weight for the line, in ounces
+    pub hs_tariff_number: Option<String>,
+    pub origin_country: String,
+}
+
+fn default_contents_type() -> String {
+    "merchandise".to_string()
+}
+
+fn default_customs_certify() -> bool {
+    true
+}
+
+fn default_non_delivery_option() -> String {
+    "return".to_string()
+}
+
+// Exports valued under $2,500 per HS code are exempt from EEI filing
+const EEI_EXEMPTION: &str = "NOEEI 30.37(a)";
+
+#[derive(sqlx::FromRow)]
+struct CustomsProduct {
+    id: i32,
+    name: String,
+    price: f64,
+    weight_oz: Option<f64>,
+    hs_code: Option<String>,
+    country_of_origin: Option<String>,
+    domestic_only: bool,
+}
+
+// Country orders ship from when no origin address is given
+pub fn home_country() -> String {
+    app_config::get().shipping.home_country.to_uppercase()
+}
+
+// Whether a shipment crosses a border (origin defaults to the home country)
+pub fn is_international(from_country: Option<&str>, to_address: &Address) -> bool {
+    let from = from_country.map(|c| c.to_uppercase()).unwrap_or_else(home_country);
+    let to = to_address.country.as_deref().unwrap_or("US").to_uppercase();
+    from != to
+}
+
+async fn load_customs_products(pool: &sqlx::PgPool, lines: &[CartLine]) -> Result<HashMap<i32, CustomsProduct>, String> {
+    let ids: Vec<i32> = lines.iter().map(|l| l.product_id).collect();
+    let rows = sqlx::query_as!(
+        CustomsProduct,
+        "SELECT id, name, price, weight_oz, hs_code, country_of_origin, domestic_only
+         FROM products WHERE id = ANY($1)",
+        &ids,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Database error: {}", e))?;
+    Ok(rows.into_iter().map(|p| (p.id, p)).collect())
+}
+
+// Reject carts containing products that may only ship domestically
+pub async fn ensure_ships_internationally(pool: &sqlx::PgPool, lines: &[CartLine]) -> Result<(), String> {
+    let products = load_customs_products(pool, lines).await?;
+    let mut blocked: Vec<&str> = products
+        .values()
+        .filter(|p| p.domestic_only)
+        .map(|p| p.name.as_str())
+        .collect();
+    if blocked.is_empty() {
+        return Ok(());
+    }
+    blocked.sort_unstable();
+    Err(format!("Cannot ship internationally: {}", blocked.join(", ")))
+}
+
+// Build a customs declaration from product metadata and current prices
+pub async fn customs_info_for_lines(
+    pool: &sqlx::PgPool,
+    origin_country: &str,
+    lines: &[CartLine],
+) -> Result<CustomsInfo, String> {
+    ensure_ships_internationally(pool, lines).await?;
+    let products = load_customs_products(pool, lines).await?;
+    let book = PriceBook::load_active(pool)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    let customs_items = lines
+        .iter()
+        .map(|line| {
+            let p = products
+                .get(&line.product_id)
+                .ok_or_else(|| format!("Unknown product {}", line.product_id))?;
+            let unit_price = book.unit_price(p.id, p.price, line.quantity);
+            Ok(CustomsItem {
+                description: p.name.clone(),
+                quantity: line.quantity,
+                value: (unit_price * line.quantity as f64 * 100.0).round() / 100.0,
+                weight: p.weight_oz.unwrap_or(DEFAULT_ITEM_WEIGHT_OZ) * line.quantity as f64,
+                hs_tariff_number: p.hs_code.clone(),
+                origin_country: p.country_of_origin.clone().unwrap_or_else(|| origin_country.to_uppercase()),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let total_value: f64 = customs_items.iter().map(|i| i.value).sum();
+    Ok(CustomsInfo {
+        contents_type: default_contents_type(),
+        contents_explanation: None,
+        customs_certify: true,
+        customs_signer: app_config::get().shipping.customs_signer.clone(),
+        non_delivery_option: default_non_delivery_option(),
+        eel_pfc: (total_value < 2500.0).then(|| EEI_EXEMPTION.to_string()),
+        customs_items,
+    })
+}
+
+// Customs for a single shipment: None for domestic, otherwise the supplied
+// declaration or one built from the parcel contents
+async fn resolve_customs(
+    pool: &sqlx::PgPool,
+    from_address: &Address,
+    to_address: &Address,
+    customs_info: Option<CustomsInfo>,
+    items: Option<&[CartLine]>,
+) -> Result<Option<CustomsInfo>, (StatusCode, String)> {
+    if !is_international(from_address.country.as_deref(), to_address) {
+        return Ok(None);
+    }
+    let origin = from_address.country.clone().unwrap_or_else(home_country);
+    match (customs_info, items) {
+        (Some(mut customs), items) => {
+            if let Some(lines) = items {
+                ensure_ships_internationally(pool, lines)
+                    .await
+                    .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+            }
+            if customs.customs_signer.is_none() {
+                customs.customs_signer = app_config::get().shipping.customs_signer.clone();
+            }
+            Ok(Some(customs))
+        }
+        (None, Some(lines)) => customs_info_for_lines(pool, &origin, lines)
+            .await
+            .map(Some)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e)),
+        (None, None) => Err((
+            StatusCode::BAD_REQUEST,
+            "customs_info or items are required for international shipments".to_string(),
+        )),
+    }
+}
+
+// ===== EasyPost Helpers =====
+
+// Build the EasyPost address object, including optional fields when present
+fn address_json(address: &Address) -> serde_json::Value {
+    let mut json = serde_json::json!({
+        "street1": address.street1,
+        "city": address.city,
+        "state": address.state,
+        "zip": address.zip,
+        "country": address.country.clone().unwrap_or_else(|| "US".to_string()),
+    });
+    if let Some(street2) = &address.street2 {
+        json["street2"] = serde_json::json!(street2);
+    }
+    if let Some(name) = &address.name {
+        json["name"] = serde_json::json!(name);
+    }
+    json
+}
+
+impl RateSelection {
+    // Carrier accounts to rate with: the request's choice, limited to configured accounts
+    fn carrier_accounts(&self, config: &ShippingConfig) -> Result<Vec<String>, (StatusCode, String)> {
+        match &self.carrier_accounts {
+            Some(requested) => {
+                if let Some(unknown) = requested
+                    .iter()
+                    .find(|a| !config.carrier_accounts.is_empty() && !config.carrier_accounts.contains(a))
+                {
+                    return Err((StatusCode::BAD_REQUEST, format!("Carrier account {} is not enabled", unknown)));
+                }
+                Ok(requested.clone())
             }
+            None => Ok(config.carrier_accounts.clone()),
+        }
+    }
+
+    // Whether a rate passes both the configured and the requested carrier/service filters
+    fn allows(&self, config: &ShippingConfig, carrier: &str, service: &str) -> bool {
+        let matches = |list: &[String], value: &str| {
+            list.is_empty() || list.iter().any(|v| v.eq_ignore_ascii_case(value))
+        };
+        matches(&config.carriers, carrier)
+            && matches(&config.services, service)
+            && matches(self.carriers.as_deref().unwrap_or_default(), carrier)
+            && matches(self.services.as_deref().unwrap_or_default(), service)
+    }
+}
+
+// Everything needed to rate a single parcel
+struct RateRequest<'a> {
+    from_address: &'a Address,
+    to_address: &'a Address,
+    parcel: &'a Parcel,
+    customs_info: Option<&'a CustomsInfo>,
+    carrier_accounts: &'a [String],
+}
+
+// Cache key for a rated shipment. Customs are part of the key because the
+// cached shipment may later be bought as a label.
+fn rate_cache_key(request: &RateRequest) -> String {
+    format!(
+        "{}|{}|{}|{}|{}|{:.2}x{:.2}x{:.2}|{:.2}|{}|{}",
+        request.from_address.street1.to_lowercase(),
+        request.from_address.zip,
+        request.from_address.country.as_deref().unwrap_or("US"),
+        request.to_address.zip,
+        request.to_address.country.as_deref().unwrap_or("US"),
+        request.parcel.length,
+        request.parcel.width,
+        request.parcel.height,
+        request.parcel.weight,
+        request.carrier_accounts.join(","),
+        request.customs_info.and_then(|c| serde_json::to_string(c).ok()).unwrap_or_default(),
+    )
+}
+
+// Build the EasyPost shipment object for a parcel, with customs when international
+fn shipment_json(
+    from_address: &Address,
+    to_address: &Address,
+    parcel: &Parcel,
+    customs_info: Option<&CustomsInfo>,
+) -> serde_json::Value {
+    let mut shipment = serde_json::json!({
+        "to_address": address_json(to_address),
+        "from_address": address_json(from_address),
+        "parcel": {
+            "length": parcel.length,
+            "width": parcel.width,
+            "height": parcel.height,
+            "weight": parcel.weight,
         }
     });
+    if let Some(customs) = customs_info {
+        shipment["customs_info"] = serde_json::json!(customs);
+    }
+    shipment
+}
 
-    // Add optional fields
-    if let Some(street2) = &payload.to_address.street2 {
-        shipment_data["shipment"]["to_address"]["street2"] = serde_json::json!(street2);
+// Create an EasyPost shipment (which returns rates) for a single parcel,
+// reusing a cached shipment for identical requests within the TTL
+async fn create_rated_shipment(
+    client: &reqwest::Client,
+    config: &ShippingConfig,
+    cache: &RateCache,
+    request: &RateRequest<'_>,
+) -> Result<EasyPostShipment, (StatusCode, String)> {
+    let cache_key = rate_cache_key(request);
+    if let Some(shipment) = cache.get(&cache_key, config.rate_cache_ttl) {
+        return Ok(shipment);
     }
-    if let Some(name) = &payload.to_address.name {
-        shipment_data["shipment"]["to_address"]["name"] = serde_json::json!(name);
+
+    let url = format!("{}/shipments", config.easypost_api_url);
+    let mut shipment_data = serde_json::json!({
+        "shipment": shipment_json(request.from_address, request.to_address, request.parcel, request.customs_info)
+    });
+    if !request.carrier_accounts.is_empty() {
+        shipment_data["shipment"]["carrier_accounts"] = serde_json::json!(request.carrier_accounts);
     }
 
     let response = client
         .post(&url)
         .basic_auth(&config.easypost_api_key, Some(""))
         .json(&shipment_data)
-        .send()
+        .with_request_id()
+        .send_retrying(Provider::EasyPost)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("EasyPost API error: {}", e)))?;
+        .map_err(|e| (e.status(StatusCode::INTERNAL_SERVER_ERROR), format!("EasyPost API error: {}", e)))?;
 
     if !response.status().is_success() {
         let error_text = response.text().await
@@ -293,8 +794,140 @@ async fn get_shipping_rates(
 
     let shipment: EasyPostShipment = response.json().await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to parse response: {}", e)))?;
+    cache.insert(cache_key, shipment.clone(), config.rate_cache_ttl);
+    Ok(shipment)
+}
 
-    let rates: Vec<ShippingRate> = shipment.rates.into_iter().map(|r| ShippingRate {
+// A postage label bought for a single parcel
+pub struct PurchasedLabel {
+    pub shipment_id: String,
+    pub carrier: String,
+    pub service: String,
+    pub rate: String,
+    pub tracking_code: String,
+    pub label_url: String,
+    pub forms: Vec<ShipmentForm>,
+}
+
+// Rate a parcel and buy the cheapest allowed rate, with customs built from
+// the parcel contents when the destination is international
+pub async fn purchase_cheapest_label(
+    state: &AppState,
+    from_address: &Address,
+    to_address: &Address,
+    parcel: &Parcel,
+    lines: &[CartLine],
+) -> Result<PurchasedLabel, (StatusCode, String)> {
+    let config = state.shipping_config()
+        .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Shipping not configured".to_string()))?;
+    let client = reqwest::Client::new();
+    let selection = RateSelection::default();
+    let carrier_accounts = selection.carrier_accounts(&config)?;
+    let customs_info = resolve_customs(&state.pool, from_address, to_address, None, Some(lines)).await?;
+    let request = RateRequest {
+        from_address,
+        to_address,
+        parcel,
+        customs_info: customs_info.as_ref(),
+        carrier_accounts: &carrier_accounts,
+    };
+    let shipment = create_rated_shipment(&client, &config, &state.rate_cache, &request).await?;
+    state.rate_cache.invalidate(&rate_cache_key(&request));
+
+    let rate = shipment.rates.iter()
+        .filter(|r| selection.allows(&config, &r.carrier, &r.service))
+        .min_by(|a, b| {
+            let a: f64 = a.rate.parse().unwrap_or(f64::MAX);
+            let b: f64 = b.rate.parse().unwrap_or(f64::MAX);
+            a.total_cmp(&b)
+        })
+        .ok_or((StatusCode::BAD_REQUEST, "No rates available".to_string()))?;
+
+    let url = format!("{}/shipments/{}/buy", config.easypost_api_url, shipment.id);
+    let response = client
+        .post(&url)
+        .basic_auth(&config.easypost_api_key, Some(""))
+        .json(&serde_json::json!({ "rate": { "id": rate.id } }))
+        .with_request_id()
+        .send_guarded(Provider::EasyPost)
+        .await
+        .map_err(|e| (e.status(StatusCode::INTERNAL_SERVER_ERROR), format!("Failed to buy label: {}", e)))?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err((StatusCode::BAD_REQUEST, format!("Failed to buy label: {}", error_text)));
+    }
+
+    let bought: EasyPostShipment = response.json().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to parse shipment: {}", e)))?;
+    let label = bought.postage_label
+        .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "No label generated".to_string()))?;
+
+    Ok(PurchasedLabel {
+        shipment_id: bought.id,
+        carrier: rate.carrier.clone(),
+        service: rate.service.clone(),
+        rate: rate.rate.clone(),
+        tracking_code: bought.tracking_code.unwrap_or_default(),
+        label_url: label.label_url,
+        forms: bought.forms.into_iter().map(|f| ShipmentForm {
+            form_type: f.form_type,
+            form_url: f.form_url,
+        }).collect(),
+    })
+}
+
+// ===== Routes =====
+
+pub fn easypost_shipping_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/shipping/rates", post(get_shipping_rates))
+        .route("/api/shipping/cart-rates", post(get_cart_shipping_rates))
+        .route("/api/shipping/create-label", post(create_shipping_label).layer(route_limits::limit(RouteGroup::Labels)))
+        .route("/api/shipping/track/:tracking_code", get(track_shipment))
+        .route("/api/shipping/validate-address", post(validate_address))
+        .with_state(app_state)
+}
+
+// ===== API Handlers =====
+
+// Get shipping rates
+async fn get_shipping_rates(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<GetRatesRequest>,
+) -> Result<Json<ShippingRatesResponse>, (StatusCode, String)> {
+    let config = state.shipping_config()
+        .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Shipping not configured".to_string()))?;
+
+    // Create shipment to get rates
+    let client = reqwest::Client::new();
+    let carrier_accounts = payload.selection.carrier_accounts(&config)?;
+    let customs_info = resolve_customs(
+        &state.pool,
+        &payload.from_address,
+        &payload.to_address,
+        payload.customs_info.clone(),
+        payload.items.as_deref(),
+    )
+    .await?;
+    let shipment = create_rated_shipment(
+        &client,
+        &config,
+        &state.rate_cache,
+        &RateRequest {
+            from_address: &payload.from_address,
+            to_address: &payload.to_address,
+            parcel: &payload.parcel,
+            customs_info: customs_info.as_ref(),
+            carrier_accounts: &carrier_accounts,
+        },
+    )
+    .await?;
+
+    let rates: Vec<ShippingRate> = shipment.rates.into_iter()
+        .filter(|r| payload.selection.allows(&config, &r.carrier, &r.service))
+        .map(|r| ShippingRate {
         id: r.id,
         carrier: r.carrier,
         service: r.service,
@@ -311,6 +944,118 @@ async fn get_shipping_rates(
     }))
 }
 
+// Get shipping rates for a cart, packing items into parcels from product dimensions
+async fn get_cart_shipping_rates(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<CartRatesRequest>,
+) -> Result<Json<CartRatesResponse>, (StatusCode, String)> {
+    live_cart_rates(&state, &payload).await.map(Json)
+}
+
+// Live EasyPost rates for a whole cart, packed into parcels per origin
+pub async fn live_cart_rates(
+    state: &AppState,
+    payload: &CartRatesRequest,
+) -> Result<CartRatesResponse, (StatusCode, String)> {
+    let config = state.shipping_config()
+        .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Shipping not configured".to_string()))?;
+
+    // Work out origins: an explicit from_address, or a split across warehouses
+    let planned = match payload.from_address {
+        Some(_) => Vec::new(),
+        None => plan_fulfillment(&state.pool, &payload.to_address, &payload.items)
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?,
+    };
+    let groups: Vec<(Option<&Warehouse>, Address, Vec<CartLine>)> = match &payload.from_address {
+        Some(from) => vec![(None, from.clone(), payload.items.clone())],
+        None => planned
+            .iter()
+            .map(|g| (Some(&g.warehouse), g.warehouse.address(), g.lines.clone()))
+            .collect(),
+    };
+
+    let mut origins = Vec::new();
+    let mut shipments: Vec<(Address, Parcel, Option<CustomsInfo>)> = Vec::new();
+    for (warehouse, from, lines) in groups {
+        let items = packable_items(&state.pool, &lines)
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        let customs_info = resolve_customs(&state.pool, &from, &payload.to_address, None, Some(&lines)).await?;
+        let parcels = pack_parcels(&items, MAX_PARCEL_WEIGHT_OZ);
+        shipments.extend(parcels.iter().map(|p| (from.clone(), p.clone(), customs_info.clone())));
+        origins.push(OriginParcels {
+            warehouse_id: warehouse.map(|w| w.id),
+            warehouse_name: warehouse.map(|w| w.name.clone()),
+            parcels,
+        });
+    }
+    if shipments.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Cart is empty".to_string()));
+    }
+
+    // Rate each parcel, then keep only services offered for every parcel
+    let client = reqwest::Client::new();
+    let carrier_accounts = payload.selection.carrier_accounts(&config)?;
+    let mut shipment_ids = Vec::new();
+    let mut totals: HashMap<(String, String), CartShippingRate> = HashMap::new();
+    let mut counts: HashMap<(String, String), usize> = HashMap::new();
+    for (from, parcel, customs_info) in &shipments {
+        let shipment = create_rated_shipment(
+            &client,
+            &config,
+            &state.rate_cache,
+            &RateRequest {
+                from_address: from,
+                to_address: &payload.to_address,
+                parcel,
+                customs_info: customs_info.as_ref(),
+                carrier_accounts: &carrier_accounts,
+            },
+        )
+        .await?;
+        shipment_ids.push(shipment.id);
+
+        for rate in shipment.rates {
+            if !payload.selection.allows(&config, &rate.carrier, &rate.service) {
+                continue;
+            }
+            let key = (rate.carrier.clone(), rate.service.clone());
+            let amount: f64 = rate.rate.parse().unwrap_or(0.0);
+            *counts.entry(key.clone()).or_default() += 1;
+            let entry = totals.entry(key).or_insert(CartShippingRate {
+                carrier: rate.carrier,
+                service: rate.service,
+                rate: "0.00".to_string(),
+                currency: rate.currency,
+                delivery_days: rate.delivery_days,
+            });
+            let running: f64 = entry.rate.parse().unwrap_or(0.0);
+            entry.rate = format!("{:.2}", running + amount);
+            entry.delivery_days = entry.delivery_days.max(rate.delivery_days);
+        }
+    }
+
+    let mut rates: Vec<CartShippingRate> = totals
+        .into_iter()
+        .filter(|(key, _)| counts.get(key) == Some(&shipments.len()))
+        .map(|(_, rate)| rate)
+        .collect();
+    rates.sort_by(|a, b| {
+        let a: f64 = a.rate.parse().unwrap_or(f64::MAX);
+        let b: f64 = b.rate.parse().unwrap_or(f64::MAX);
+        a.total_cmp(&b)
+    });
+
+    Ok(CartRatesResponse {
+        success: true,
+        parcels: shipments.into_iter().map(|(_, p, _)| p).collect(),
+        shipment_ids,
+        origins,
+        rates,
+    })
+}
+
 // Create shipping label
 async fn create_shipping_label(
     State(state): State<Arc<AppState>>,
@@ -319,6 +1064,17 @@ async fn create_shipping_label(
     let config = state.shipping_config()
         .ok_or((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: "Shipping not configured".to_string() })))?;
 
+    // International labels need a customs declaration attached to the shipment
+    let customs_info = resolve_customs(
+        &state.pool,
+        &payload.from_address,
+        &payload.to_address,
+        payload.customs_info.clone(),
+        payload.items.as_deref(),
+    )
+    .await
+    .map_err(|(status, error)| (status, Json(ErrorResponse { error })))?;
+
     // First, create shipment to get rates (if rate_id not provided)
     let client = reqwest::Client::new();
 
@@ -333,9 +1089,10 @@ async fn create_shipping_label(
             .post(&url)
             .basic_auth(&config.easypost_api_key, Some(""))
             .json(&buy_data)
-            .send()
+            .with_request_id()
+            .send_guarded(Provider::EasyPost)
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: format!("EasyPost API error: {}", e) })))?;
+            .map_err(|e| (e.status(StatusCode::INTERNAL_SERVER_ERROR), Json(ErrorResponse { error: format!("EasyPost API error: {}", e) })))?;
 
         if !response.status().is_success() {
             let error_text = response.text().await
@@ -352,37 +1109,22 @@ async fn create_shipping_label(
         let url = format!("{}/shipments", config.easypost_api_url);
 
         let shipment_data = serde_json::json!({
-            "shipment": {
-                "to_address": {
-                    "street1": payload.to_address.street1,
-                    "city": payload.to_address.city,
-                    "state": payload.to_address.state,
-                    "zip": payload.to_address.zip,
-                    "country": payload.to_address.country.unwrap_or_else(|| "US".to_string()),
-                },
-                "from_address": {
-                    "street1": payload.from_address.street1,
-                    "city": payload.from_address.city,
-                    "state": payload.from_address.state,
-                    "zip": payload.from_address.zip,
-                    "country": payload.from_address.country.unwrap_or_else(|| "US".to_string()),
-                },
-                "parcel": {
-                    "length": payload.parcel.length,
-                    "width": payload.parcel.width,
-                    "height": payload.parcel.height,
-                    "weight": payload.parcel.weight,
-                }
-            }
+            "shipment": shipment_json(
+                &payload.from_address,
+                &payload.to_address,
+                &payload.parcel,
+                customs_info.as_ref(),
+            )
         });
 
         let response = client
             .post(&url)
             .basic_auth(&config.easypost_api_key, Some(""))
             .json(&shipment_data)
-            .send()
+            .with_request_id()
+            .send_guarded(Provider::EasyPost)
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: format!("EasyPost API error: {}", e) })))?;
+            .map_err(|e| (e.status(StatusCode::INTERNAL_SERVER_ERROR), Json(ErrorResponse { error: format!("EasyPost API error: {}", e) })))?;
 
         if !response.status().is_success() {
             let error_text = response.text().await
@@ -404,9 +1146,10 @@ async fn create_shipping_label(
                 .post(&buy_url)
                 .basic_auth(&config.easypost_api_key, Some(""))
                 .json(&buy_data)
-                .send()
+                .with_request_id()
+                .send_guarded(Provider::EasyPost)
                 .await
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: format!("Failed to buy label: {}", e) })))?;
+                .map_err(|e| (e.status(StatusCode::INTERNAL_SERVER_ERROR), Json(ErrorResponse { error: format!("Failed to buy label: {}", e) })))?;
 
             if !buy_response.status().is_success() {
                 let error_text = buy_response.text().await
@@ -425,9 +1168,10 @@ async fn create_shipping_label(
     let get_response = client
         .get(&get_url)
         .basic_auth(&config.easypost_api_key, Some(""))
-        .send()
+        .with_request_id()
+        .send_retrying(Provider::EasyPost)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: format!("Failed to retrieve shipment: {}", e) })))?;
+        .map_err(|e| (e.status(StatusCode::INTERNAL_SERVER_ERROR), Json(ErrorResponse { error: format!("Failed to retrieve shipment: {}", e) })))?;
 
     let final_shipment: EasyPostShipment = get_response.json().await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: format!("Failed to parse shipment: {}", e) })))?;
@@ -445,10 +1189,14 @@ async fn create_shipping_label(
             label_pdf_url: label.label_pdf_url,
             label_zpl_url: label.label_zpl_url,
         },
+        forms: final_shipment.forms.into_iter().map(|f| ShipmentForm {
+            form_type: f.form_type,
+            form_url: f.form_url,
+        }).collect(),
     }))
 }
 
-// Track shipment
+// Track shipment, from the tracking cache when it has the code
 async fn track_shipment(
     State(state): State<Arc<AppState>>,
     Path(tracking_code): Path<String>,
@@ -456,15 +1204,42 @@ async fn track_shipment(
     let config = state.shipping_config()
         .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Shipping not configured".to_string()))?;
 
+    match state.tracking_cache.lookup(&tracking_code, &config) {
+        TrackingLookup::Fresh(response) => Ok(Json(response)),
+        TrackingLookup::Stale { response, refresh } => {
+            if refresh {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    match fetch_tracking(&config, &tracking_code).await {
+                        Ok(fresh) => state.tracking_cache.insert(tracking_code, fresh, &config),
+                        Err((_, e)) => {
+                            tracing::error!("Failed to refresh tracking of {}: {}", tracking_code, e);
+                            state.tracking_cache.refresh_failed(&tracking_code);
+                        }
+                    }
+                });
+            }
+            Ok(Json(response))
+        }
+        TrackingLookup::Miss => {
+            let response = fetch_tracking(&config, &tracking_code).await?;
+            state.tracking_cache.insert(tracking_code, response.clone(), &config);
+            Ok(Json(response))
+        }
+    }
+}
+
+async fn fetch_tracking(config: &ShippingConfig, tracking_code: &str) -> Result<TrackingResponse, (StatusCode, String)> {
     let client = reqwest::Client::new();
     let url = format!("{}/trackers/{}", config.easypost_api_url, tracking_code);
 
     let response = client
         .get(&url)
         .basic_auth(&config.easypost_api_key, Some(""))
-        .send()
+        .with_request_id()
+        .send_retrying(Provider::EasyPost)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("EasyPost API error: {}", e)))?;
+        .map_err(|e| (e.status(StatusCode::INTERNAL_SERVER_ERROR), format!("EasyPost API error: {}", e)))?;
 
     if !response.status().is_success() {
         let error_text = response.text().await
@@ -483,13 +1258,13 @@ async fn track_shipment(
         state: d.tracking_location.as_ref().and_then(|l| l.state.clone()),
     }).collect();
 
-    Ok(Json(TrackingResponse {
+    Ok(TrackingResponse {
         success: true,
         tracking_code: tracker.tracking_code,
         status: tracker.status,
         carrier: tracker.carrier,
         tracking_details: details,
-    }))
+    })
 }
 
 // Validate address
@@ -518,9 +1293,10 @@ async fn validate_address(
         .post(&url)
         .basic_auth(&config.easypost_api_key, Some(""))
         .json(&address_data)
-        .send()
+        .with_request_id()
+        .send_guarded(Provider::EasyPost)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("EasyPost API error: {}", e)))?;
+        .map_err(|e| (e.status(StatusCode::INTERNAL_SERVER_ERROR), format!("EasyPost API error: {}", e)))?;
 
     if !response.status().is_success() {
         let error_text = response.text().await
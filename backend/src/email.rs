@@ -3,6 +3,7 @@ use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor, transp
 use std::sync::Arc;
 use reqwest::Client;
 use serde_json;
+use crate::request_id::WithRequestId;
 
 #[async_trait]
 pub trait EmailService: Send + Sync {
@@ -46,16 +47,7 @@ impl EmailService for SmtpEmailService {
     }
 }
 
-// Stub for MailChimp integration
-pub struct MailChimpEmailService;
-
-#[async_trait]
-impl EmailService for MailChimpEmailService {
-    async fn send_email(&self, _to: &str, _subject: &str, _body: &str) -> Result<(), String> {
-        // TODO: Implement MailChimp API integration
-        Err("MailChimp integration not yet implemented".to_string())
-    }
-}
+// MailChimp lives in mailchimp_marketing.rs behind the MarketingProvider trait
 
 // Stub for SalesForce Marketing Cloud integration
 pub struct SalesForceEmailService;
@@ -111,6 +103,7 @@ impl EmailService for ResendApiEmailService {
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
             .json(&payload)
+            .with_request_id()
             .send()
             .await
             .map_err(|e| format!("Failed to send request to Resend API: {}", e))?;
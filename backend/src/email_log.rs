@@ -0,0 +1,177 @@
+// Email Log Module - Delivery tracking and the suppression list
+// Provider sends are logged by message id so delivery webhooks can update their status;
+// hard bounces and spam complaints add the address to a suppression list every sender checks
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::AppState;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+// Database model for logged emails
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct EmailLogEntry {
+    pub id: Uuid,
+    pub provider: String,
+    pub message_id: Option<String>,
+    pub recipient: String,
+    pub subject: Option<String>,
+    pub template_id: Option<i64>,
+    pub status: String,
+    pub last_error: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct EmailSuppression {
+    pub email: String,
+    pub reason: String,
+    pub source: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct EmailLogQuery {
+    pub recipient: Option<String>,
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+pub async fn is_suppressed(pool: &sqlx::PgPool, email: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM email_suppressions WHERE email = LOWER($1)) AS "exists!""#, email.trim())
+        .fetch_one(pool)
+        .await
+}
+
+// Keeps the first reason an address was suppressed for
+pub async fn suppress(pool: &sqlx::PgPool, email: &str, reason: &str, source: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO email_suppressions (email, reason, source) VALUES (LOWER($1), $2, $3)
+         ON CONFLICT (email) DO NOTHING",
+        email.trim(),
+        reason,
+        source,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn record_sent(
+    pool: &sqlx::PgPool,
+    provider: &str,
+    message_id: Option<&str>,
+    recipient: &str,
+    subject: Option<&str>,
+    template_id: Option<i64>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO email_log (provider, message_id, recipient, subject, template_id) VALUES ($1, $2, LOWER($3), $4, $5)",
+        provider,
+        message_id,
+        recipient.trim(),
+        subject,
+        template_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Apply a delivery event to the logged email with this provider message id (and recipient,
+// since one message can go to several addresses); returns how many rows matched
+pub async fn record_event(
+    pool: &sqlx::PgPool,
+    provider: &str,
+    message_id: &str,
+    recipient: &str,
+    status: &str,
+    error: Option<&str>,
+) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query!(
+        "UPDATE email_log SET status = $1, last_error = COALESCE($2, last_error), updated_at = NOW()
+         WHERE provider = $3 AND message_id = $4 AND recipient = LOWER($5)",
+        status,
+        error,
+        provider,
+        message_id,
+        recipient.trim(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+pub fn email_log_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/email-log", get(list_email_log))
+        .route("/api/admin/email-suppressions", get(list_suppressions))
+        .route("/api/admin/email-suppressions/:email", delete(remove_suppression))
+        .with_state(app_state)
+}
+
+// Newest first, optionally for one recipient or status
+async fn list_email_log(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<EmailLogQuery>,
+) -> Result<Json<Vec<EmailLogEntry>>, (StatusCode, String)> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let entries = sqlx::query_as!(
+        EmailLogEntry,
+        "SELECT id, provider, message_id, recipient, subject, template_id, status, last_error, created_at, updated_at FROM email_log
+         WHERE ($1::TEXT IS NULL OR recipient = LOWER($1))
+           AND ($2::TEXT IS NULL OR status = $2)
+         ORDER BY created_at DESC
+         LIMIT $3 OFFSET $4",
+        query.recipient.as_deref().map(str::trim),
+        query.status.as_deref(),
+        limit,
+        offset,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(entries))
+}
+
+async fn list_suppressions(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<EmailSuppression>>, (StatusCode, String)> {
+    let suppressions = sqlx::query_as!(EmailSuppression, "SELECT email, reason, source, created_at FROM email_suppressions ORDER BY created_at DESC")
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(suppressions))
+}
+
+// Allow sending to an address again, e.g. after the customer fixed their mailbox
+async fn remove_suppression(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(email): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM email_suppressions WHERE email = LOWER($1)", email.trim())
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Address is not suppressed".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
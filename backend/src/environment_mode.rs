@@ -0,0 +1,52 @@
+// Environment Mode Module - Live vs test data isolation
+// The server runs in one mode (ENVIRONMENT_MODE, else the Stripe key's kind) and tags every
+// order and webhook event with it. Webhooks from the other mode are refused, and reports only
+// count the server's own mode unless asked otherwise
+
+use axum::http::StatusCode;
+use serde::Serialize;
+
+use crate::app_config;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvironmentMode {
+    Live,
+    Test,
+}
+
+impl EnvironmentMode {
+    pub fn from_config() -> Self {
+        if app_config::get().is_live() {
+            EnvironmentMode::Live
+        } else {
+            EnvironmentMode::Test
+        }
+    }
+
+    // Value stored in the livemode columns
+    pub fn is_live(self) -> bool {
+        self == EnvironmentMode::Live
+    }
+}
+
+impl std::fmt::Display for EnvironmentMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvironmentMode::Live => write!(f, "live"),
+            EnvironmentMode::Test => write!(f, "test"),
+        }
+    }
+}
+
+// Parses `?mode=live|test|all` on reports into the livemode value to filter on (None for both);
+// a missing mode means the server's own
+pub fn livemode_filter(mode: Option<&str>, server: EnvironmentMode) -> Result<Option<bool>, (StatusCode, String)> {
+    match mode.map(str::to_lowercase).as_deref() {
+        None => Ok(Some(server.is_live())),
+        Some("live") => Ok(Some(true)),
+        Some("test") => Ok(Some(false)),
+        Some("all") => Ok(None),
+        Some(other) => Err((StatusCode::BAD_REQUEST, format!("mode must be live, test or all, got {}", other))),
+    }
+}
@@ -0,0 +1,224 @@
+// Domain Events Module - In-process event bus for business side effects
+// Handlers publish facts (order created, payment failed, ...) and registered
+// subscribers (email, SMS, analytics, marketing sync) react to them independently
+
+pub mod subscribers;
+
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+use crate::order_status::OrderStatus;
+use crate::webhooks::PaymentProvider;
+
+// Events emitted by the application whenever something business-relevant happens
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DomainEvent {
+    OrderCreated {
+        order_id: Uuid,
+        provider: PaymentProvider,
+        payment_id: String,
+        customer_email: Option<String>,
+        customer_name: Option<String>,
+        customer_phone: Option<String>,
+        total_amount: i64, // in cents
+        currency: String,
+    },
+    PaymentFailed {
+        provider: PaymentProvider,
+        payment_id: String,
+        customer_email: Option<String>,
+        amount: i64, // in cents
+        currency: String,
+        reason: Option<String>,
+    },
+    OrderRefunded {
+        order_id: Uuid,
+        provider: PaymentProvider,
+        payment_id: String,
+        customer_email: Option<String>,
+        amount_refunded: i64, // in cents
+        currency: String,
+    },
+    OrderFulfilled {
+        order_id: Uuid,
+    },
+    OrderVoided {
+        order_id: Uuid,
+    },
+    // Published with every status change, alongside the event for what happened (e.g. OrderRefunded)
+    OrderStatusChanged {
+        order_id: Uuid,
+        from: OrderStatus,
+        to: OrderStatus,
+    },
+    DisputeOpened {
+        dispute_id: Uuid,
+        order_id: Option<Uuid>,
+        amount: i64, // in cents
+        currency: String,
+        reason: String,
+        evidence_due_by: Option<DateTime<Utc>>,
+    },
+    OrderShipped {
+        order_id: Uuid,
+        fulfillment_id: Uuid, // Orders can ship in several parts, each with its own tracking
+        carrier: Option<String>,
+        tracking_code: Option<String>,
+    },
+    ShipmentDelivered {
+        order_id: Option<Uuid>,
+        tracking_code: String,
+        carrier: String,
+        customer_phone: Option<String>,
+    },
+    ProductBackInStock {
+        product_id: i32,
+        product_name: String,
+        inventory: i32,
+    },
+    ProductCreated {
+        product_id: i32,
+    },
+    ProductUpdated {
+        product_id: i32,
+    },
+    ProductDeleted {
+        product_id: i32,
+    },
+    LowStock {
+        product_id: i32,
+        product_name: String,
+        inventory: i32,
+        threshold: i32,
+    },
+    PriceDropped {
+        product_id: i32,
+        product_name: String,
+        previous_price: i64, // in cents
+        price: i64,          // in cents
+    },
+}
+
+impl DomainEvent {
+    // Stable event name used for logging and analytics
+    pub fn name(&self) -> &'static str {
+        match self {
+            DomainEvent::OrderCreated { .. } => "order_created",
+            DomainEvent::PaymentFailed { .. } => "payment_failed",
+            DomainEvent::OrderRefunded { .. } => "order_refunded",
+            DomainEvent::OrderFulfilled { .. } => "order_fulfilled",
+            DomainEvent::OrderVoided { .. } => "order_voided",
+            DomainEvent::OrderStatusChanged { .. } => "order_status_changed",
+            DomainEvent::DisputeOpened { .. } => "dispute_opened",
+            DomainEvent::OrderShipped { .. } => "order_shipped",
+            DomainEvent::ShipmentDelivered { .. } => "shipment_delivered",
+            DomainEvent::ProductBackInStock { .. } => "product_back_in_stock",
+            DomainEvent::ProductCreated { .. } => "product_created",
+            DomainEvent::ProductUpdated { .. } => "product_updated",
+            DomainEvent::ProductDeleted { .. } => "product_deleted",
+            DomainEvent::LowStock { .. } => "low_stock",
+            DomainEvent::PriceDropped { .. } => "price_dropped",
+        }
+    }
+}
+
+// A side effect that reacts to domain events
+#[async_trait]
+pub trait EventSubscriber: Send + Sync {
+    fn name(&self) -> &'static str;
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String>;
+}
+
+// Fan-out bus holding all registered subscribers
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Vec<Arc<dyn EventSubscriber>>,
+    in_flight: Arc<InFlight>,
+}
+
+// Subscriber tasks still running, so a short-lived process can wait for them before exiting
+#[derive(Default)]
+struct InFlight {
+    count: AtomicUsize,
+    idle: Notify,
+}
+
+// Counts one subscriber task until dropped, which also happens if the subscriber panics
+struct Running(Arc<InFlight>);
+
+impl InFlight {
+    fn start(in_flight: &Arc<InFlight>) -> Running {
+        in_flight.count.fetch_add(1, Ordering::SeqCst);
+        Running(in_flight.clone())
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Register a subscriber (builder style, used when wiring AppState in main.rs)
+    pub fn subscribe<S: EventSubscriber + 'static>(mut self, subscriber: S) -> Self {
+        self.subscribers.push(Arc::new(subscriber));
+        self
+    }
+
+    // Publish an event to every subscriber without blocking the caller.
+    // Each subscriber runs in its own task so a slow or failing side effect
+    // never delays the HTTP response or the other subscribers.
+    pub fn publish(&self, event: DomainEvent) {
+        tracing::info!("Publishing domain event: {}", event.name());
+        for subscriber in &self.subscribers {
+            let subscriber = subscriber.clone();
+            let event = event.clone();
+            let running = InFlight::start(&self.in_flight);
+            // Subscribers log and call providers under the request that published the event
+            tokio::spawn(crate::request_id::propagate(async move {
+                if let Err(e) = subscriber.handle(&event).await {
+                    tracing::error!(
+                        "Subscriber {} failed to handle {}: {}",
+                        subscriber.name(),
+                        event.name(),
+                        e
+                    );
+                }
+                drop(running);
+            }));
+        }
+    }
+
+    // Wait until every subscriber task published so far has finished (rcom-cli, before exiting)
+    pub async fn wait_idle(&self) {
+        loop {
+            let idle = self.in_flight.idle.notified();
+            if self.in_flight.count.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+
+    // Build the default bus with all built-in subscribers registered
+    pub fn with_default_subscribers(pool: Arc<sqlx::PgPool>) -> Self {
+        Self::new()
+            .subscribe(subscribers::EmailSubscriber::new(pool.clone()))
+            .subscribe(subscribers::SmsSubscriber::new(pool))
+            .subscribe(subscribers::AnalyticsSubscriber)
+            .subscribe(subscribers::MarketingSyncSubscriber)
+    }
+}
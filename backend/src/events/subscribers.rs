@@ -0,0 +1,182 @@
+// Built-in Domain Event Subscribers
+// Email, SMS, analytics and marketing sync side effects, each independent of the
+// handler that published the event
+
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use super::{DomainEvent, EventSubscriber};
+use crate::branding::load_branding;
+use crate::lettre_email::EmailConfig;
+use crate::marketing_providers::provider_from_env;
+use crate::notifications::{queue_email, queue_sms, EmailPriority};
+use crate::phone_verification::is_phone_verified;
+use crate::textbelt_sms::{format_phone_number, SmsConfig};
+use crate::webhooks::{self, PaymentProvider};
+
+// Sends transactional emails (order confirmations, payment failures)
+pub struct EmailSubscriber {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl EmailSubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for EmailSubscriber {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        match event {
+            DomainEvent::OrderCreated {
+                order_id,
+                provider,
+                payment_id,
+                customer_email: Some(email),
+                total_amount,
+                ..
+            } => {
+                match provider {
+                    PaymentProvider::Stripe | PaymentProvider::Manual => {
+                        webhooks::stripe::send_order_confirmation_email(&self.pool, email, *order_id, payment_id, *total_amount).await
+                    }
+                    PaymentProvider::Square => {
+                        webhooks::square::send_order_confirmation_email(&self.pool, email, *order_id, payment_id, *total_amount).await
+                    }
+                }
+                Ok(())
+            }
+            DomainEvent::PaymentFailed {
+                payment_id,
+                customer_email: Some(email),
+                amount,
+                reason,
+                ..
+            } => {
+                EmailConfig::from_env().ok_or_else(|| "Email not configured".to_string())?;
+                let html_body = load_branding(&self.pool).await.email_html(
+                    "Payment Failed",
+                    &format!(
+                        "<p>Hi there,</p><p>Unfortunately your payment of ${:.2} could not be completed{}.</p><p>Reference: {}</p><p>Please try again or use a different payment method.</p>",
+                        *amount as f64 / 100.0,
+                        reason.as_ref().map(|r| format!(" ({})", r)).unwrap_or_default(),
+                        payment_id
+                    ),
+                );
+                // Urgent, but still queued so a provider rate limit delays it instead of dropping it
+                queue_email(&self.pool, email, "Payment Failed", &html_body, EmailPriority::Immediate).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// Sends SMS notifications when a verified phone number is known
+pub struct SmsSubscriber {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl SmsSubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for SmsSubscriber {
+    fn name(&self) -> &'static str {
+        "sms"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let (phone, message) = match event {
+            DomainEvent::OrderCreated {
+                order_id,
+                customer_phone: Some(phone),
+                total_amount,
+                ..
+            } => (
+                phone,
+                format!(
+                    "R-Com Order Confirmed! Order #{} - Total: ${:.2}. Thank you for your purchase!",
+                    order_id,
+                    *total_amount as f64 / 100.0
+                ),
+            ),
+            DomainEvent::ShipmentDelivered {
+                tracking_code,
+                customer_phone: Some(phone),
+                ..
+            } => (
+                phone,
+                format!(
+                    "R-Com Delivery Complete! Your package {} has been delivered. Enjoy your purchase!",
+                    tracking_code
+                ),
+            ),
+            _ => return Ok(()),
+        };
+
+        if SmsConfig::from_env().is_none() {
+            return Ok(());
+        }
+        let formatted_phone = format_phone_number(phone)?;
+        if !is_phone_verified(&self.pool, &formatted_phone).await? {
+            tracing::info!("Skipping SMS to unverified number {}", formatted_phone);
+            return Ok(());
+        }
+        // Held for quiet hours and the SMS rate limit by the notification queue
+        queue_sms(&self.pool, &formatted_phone, &message).await
+    }
+}
+
+// Emits structured analytics records for every event
+pub struct AnalyticsSubscriber;
+
+#[async_trait]
+impl EventSubscriber for AnalyticsSubscriber {
+    fn name(&self) -> &'static str {
+        "analytics"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let record = serde_json::to_string(event)
+            .map_err(|e| format!("Failed to serialize event: {}", e))?;
+        tracing::info!("[analytics] {}", record);
+        Ok(())
+    }
+}
+
+// Keeps the marketing provider's contact list in sync with purchasing customers
+pub struct MarketingSyncSubscriber;
+
+#[async_trait]
+impl EventSubscriber for MarketingSyncSubscriber {
+    fn name(&self) -> &'static str {
+        "marketing_sync"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let (email, name) = match event {
+            DomainEvent::OrderCreated {
+                customer_email: Some(email),
+                customer_name,
+                ..
+            } => (email, customer_name),
+            _ => return Ok(()),
+        };
+
+        // Nothing to sync until a marketing provider is configured
+        let provider = match provider_from_env() {
+            Ok(p) => p,
+            Err(_) => return Ok(()),
+        };
+        provider.add_contact(None, email, name.as_deref()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,561 @@
+// Experiments Module - Catalog A/B tests
+// Admins define experiments (product title or price badge variants, free-shipping threshold
+// messaging, ...) as weighted variants with storefront config. Each visitor is bucketed by a
+// hash of the experiment key and their visitor id (the customer when logged in, otherwise an
+// anonymous id sent as X-Visitor-Id or kept in a cookie), so they always see the same
+// variant. The storefront logs an exposure when it shows a variant; purchases are logged
+// against the checkout's visitor, and the results endpoint compares conversion per variant
+// against the control (the first variant)
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::customer_auth::AuthenticatedCustomer;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::route_limits::{self, RouteGroup};
+use crate::AppState;
+
+const VISITOR_HEADER: &str = "x-visitor-id";
+const VISITOR_COOKIE: &str = "rcom_vid";
+const COOKIE_MAX_AGE_SECS: i64 = 365 * 24 * 60 * 60;
+const KINDS: &[&str] = &["product_title", "price_badge", "free_shipping_message", "custom"];
+
+// One arm of an experiment. config is what the storefront renders, e.g.
+// { "title": "..." }, { "badge": "Best price" } or { "message": "Free shipping over $50" }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Variant {
+    pub key: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default)]
+    pub config: serde_json::Value,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+// Database model for experiments
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Experiment {
+    pub id: i32,
+    pub key: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub kind: String,
+    pub product_ids: Vec<i32>,
+    pub variants: sqlx::types::Json<Vec<Variant>>,
+    pub status: String, // draft, running, stopped
+    pub started_at: Option<DateTime<Utc>>,
+    pub stopped_at: Option<DateTime<Utc>>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ExperimentInput {
+    pub key: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub kind: String,
+    #[serde(default)]
+    pub product_ids: Vec<i32>,
+    pub variants: Vec<Variant>,
+}
+
+// The variant a visitor is bucketed into
+#[derive(Debug, Serialize)]
+pub struct Assignment {
+    pub experiment: String,
+    pub kind: String,
+    pub product_ids: Vec<i32>,
+    pub variant: String,
+    pub config: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Assignments {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visitor_id: Option<Uuid>, // Anonymous id to send back as X-Visitor-Id; absent when logged in
+    pub experiments: Vec<Assignment>,
+}
+
+#[derive(sqlx::FromRow)]
+struct VariantCounts {
+    variant: Option<String>,
+    visitors: i64,
+    converted: i64,
+    orders: i64,
+    revenue: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VariantResult {
+    pub variant: String,
+    pub visitors: i64, // Exposed visitors
+    pub converted: i64, // Of those, visitors who bought after their first exposure
+    pub conversion_rate: f64,
+    pub orders: i64,
+    pub revenue: i64, // in cents
+    pub revenue_per_visitor: f64, // in cents
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lift: Option<f64>, // Relative change in conversion rate against the control
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub z_score: Option<f64>, // Two-proportion z-test against the control; |z| >= 1.96 is ~95% confidence
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExperimentResults {
+    pub experiment: Experiment,
+    pub control: String,
+    pub variants: Vec<VariantResult>,
+}
+
+pub fn experiment_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/experiments", get(get_assignments))
+        .route("/api/experiments/:key/exposures", post(record_exposure))
+        .route("/api/admin/experiments", get(list_experiments).post(create_experiment))
+        .route("/api/admin/experiments/:id", put(update_experiment).delete(delete_experiment))
+        .route("/api/admin/experiments/:id/start", post(start_experiment))
+        .route("/api/admin/experiments/:id/stop", post(stop_experiment))
+        .route("/api/admin/experiments/:id/results", get(get_results).layer(route_limits::limit(RouteGroup::Reports)))
+        .with_state(app_state)
+}
+
+// Anonymous visitor id from the X-Visitor-Id header (cross-origin storefronts), else the cookie
+fn anonymous_id(headers: &HeaderMap) -> Option<Uuid> {
+    let from_header = headers.get(VISITOR_HEADER).and_then(|v| v.to_str().ok());
+    let from_cookie = || {
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(name, _)| *name == VISITOR_COOKIE)
+            .map(|(_, value)| value)
+    };
+    from_header
+        .or_else(from_cookie)
+        .and_then(|value| Uuid::parse_str(value.trim()).ok())
+}
+
+// Who is browsing: the customer when logged in, otherwise the anonymous id
+pub fn visitor_id(customer: Option<&AuthenticatedCustomer>, headers: &HeaderMap) -> Option<String> {
+    match customer {
+        Some(customer) => Some(format!("c:{}", customer.customer_id)),
+        None => anonymous_id(headers).map(|id| format!("a:{}", id)),
+    }
+}
+
+// Visitor for a storefront request, with the anonymous id and the cookie to set for a new visitor
+fn visitor_or_new(customer: Option<&AuthenticatedCustomer>, headers: &HeaderMap) -> (String, Option<Uuid>, HeaderMap) {
+    let mut response_headers = HeaderMap::new();
+    if let Some(customer) = customer {
+        return (format!("c:{}", customer.customer_id), None, response_headers);
+    }
+    if let Some(id) = anonymous_id(headers) {
+        return (format!("a:{}", id), Some(id), response_headers);
+    }
+    let id = Uuid::new_v4();
+    let cookie = format!(
+        "{}={}; Max-Age={}; Path=/; HttpOnly; SameSite=Lax",
+        VISITOR_COOKIE, id, COOKIE_MAX_AGE_SECS
+    );
+    if let Ok(value) = cookie.parse() {
+        response_headers.insert(header::SET_COOKIE, value);
+    }
+    (format!("a:{}", id), Some(id), response_headers)
+}
+
+// Deterministic bucketing: the hash of key and visitor picks a point in the total weight
+fn assign<'a>(experiment: &'a Experiment, visitor: &str) -> Option<&'a Variant> {
+    let total: u64 = experiment.variants.iter().map(|v| v.weight as u64).sum();
+    if total == 0 {
+        return None;
+    }
+    let digest = Sha256::digest(format!("{}:{}", experiment.key, visitor).as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    let mut point = u64::from_be_bytes(bytes) % total;
+    experiment.variants.iter().find(|v| {
+        if point < v.weight as u64 {
+            true
+        } else {
+            point -= v.weight as u64;
+            false
+        }
+    })
+}
+
+fn to_assignment(experiment: &Experiment, variant: &Variant) -> Assignment {
+    Assignment {
+        experiment: experiment.key.clone(),
+        kind: experiment.kind.clone(),
+        product_ids: experiment.product_ids.clone(),
+        variant: variant.key.clone(),
+        config: variant.config.clone(),
+    }
+}
+
+fn validate(input: &ExperimentInput) -> Result<(), (StatusCode, String)> {
+    let bad_request = |message: &str| Err((StatusCode::BAD_REQUEST, message.to_string()));
+    let key = input.key.trim();
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return bad_request("key must be letters, digits, '-' or '_'");
+    }
+    if input.name.trim().is_empty() {
+        return bad_request("name is required");
+    }
+    if !KINDS.contains(&input.kind.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("kind must be one of {}", KINDS.join(", "))));
+    }
+    if input.variants.len() < 2 {
+        return bad_request("An experiment needs at least two variants");
+    }
+    let mut keys: Vec<&str> = input.variants.iter().map(|v| v.key.trim()).collect();
+    if keys.iter().any(|k| k.is_empty()) {
+        return bad_request("Every variant needs a key");
+    }
+    keys.sort_unstable();
+    keys.dedup();
+    if keys.len() != input.variants.len() {
+        return bad_request("Variant keys must be unique");
+    }
+    if input.variants.iter().any(|v| v.weight == 0) {
+        return bad_request("Variant weights must be at least 1");
+    }
+    Ok(())
+}
+
+fn trimmed_variants(variants: Vec<Variant>) -> Vec<Variant> {
+    variants
+        .into_iter()
+        .map(|v| Variant { key: v.key.trim().to_string(), ..v })
+        .collect()
+}
+
+async fn load_experiment(pool: &sqlx::PgPool, id: i32) -> Result<Experiment, (StatusCode, String)> {
+    sqlx::query_as!(Experiment, r#"SELECT id, key, name, description, kind, product_ids, variants AS "variants: sqlx::types::Json<Vec<Variant>>", status, started_at, stopped_at, created_by, created_at, updated_at FROM experiments WHERE id = $1"#, id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Experiment not found".to_string()))
+}
+
+// The visitor's variant in every running experiment. Nothing is logged; the storefront
+// records an exposure when it actually shows a variant
+async fn get_assignments(
+    customer: Option<AuthenticatedCustomer>,
+    State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<(HeaderMap, Json<Assignments>), (StatusCode, String)> {
+    let (visitor, anonymous_id, response_headers) = visitor_or_new(customer.as_ref(), &headers);
+    let experiments = sqlx::query_as!(Experiment, r#"SELECT id, key, name, description, kind, product_ids, variants AS "variants: sqlx::types::Json<Vec<Variant>>", status, started_at, stopped_at, created_by, created_at, updated_at FROM experiments WHERE status = 'running' ORDER BY id"#)
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let assignments = experiments
+        .iter()
+        .filter_map(|experiment| assign(experiment, &visitor).map(|variant| to_assignment(experiment, variant)))
+        .collect();
+    Ok((response_headers, Json(Assignments { visitor_id: anonymous_id, experiments: assignments })))
+}
+
+// Log that the visitor saw their variant. Only the first exposure counts; repeats are no-ops
+async fn record_exposure(
+    customer: Option<AuthenticatedCustomer>,
+    State(app_state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+    headers: HeaderMap,
+) -> Result<(HeaderMap, Json<Assignment>), (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let (visitor, _, response_headers) = visitor_or_new(customer.as_ref(), &headers);
+    let experiment = sqlx::query_as!(
+        Experiment,
+        r#"SELECT id, key, name, description, kind, product_ids, variants AS "variants: sqlx::types::Json<Vec<Variant>>", status, started_at, stopped_at, created_by, created_at, updated_at FROM experiments WHERE key = $1 AND status = 'running'"#,
+        key.trim(),
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(db_error)?
+    .ok_or((StatusCode::NOT_FOUND, "Experiment not found or not running".to_string()))?;
+    let variant = assign(&experiment, &visitor)
+        .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Experiment has no variants".to_string()))?;
+
+    sqlx::query!(
+        "INSERT INTO analytics_events (event_type, visitor_id, customer_id, experiment_id, variant)
+         VALUES ('experiment_exposure', $1, $2, $3, $4)
+         ON CONFLICT (experiment_id, visitor_id) WHERE event_type = 'experiment_exposure' DO NOTHING",
+        &visitor,
+        customer.as_ref().map(|c| c.customer_id),
+        experiment.id,
+        &variant.key,
+    )
+    .execute(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    Ok((response_headers, Json(to_assignment(&experiment, variant))))
+}
+
+async fn list_experiments(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Experiment>>, (StatusCode, String)> {
+    let experiments = sqlx::query_as!(Experiment, r#"SELECT id, key, name, description, kind, product_ids, variants AS "variants: sqlx::types::Json<Vec<Variant>>", status, started_at, stopped_at, created_by, created_at, updated_at FROM experiments ORDER BY created_at DESC"#)
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(experiments))
+}
+
+// Experiments start as drafts
+async fn create_experiment(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<ExperimentInput>,
+) -> Result<(StatusCode, Json<Experiment>), (StatusCode, String)> {
+    validate(&input)?;
+    let experiment = sqlx::query_as!(
+        Experiment,
+        r#"INSERT INTO experiments (key, name, description, kind, product_ids, variants, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id, key, name, description, kind, product_ids, variants AS "variants: sqlx::types::Json<Vec<Variant>>", status, started_at, stopped_at, created_by, created_at, updated_at"#,
+        input.key.trim(),
+        input.name.trim(),
+        input.description.as_deref().map(str::trim).filter(|d| !d.is_empty()),
+        &input.kind,
+        &input.product_ids,
+        sqlx::types::Json(trimmed_variants(input.variants)) as _,
+        &admin.username,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => {
+            (StatusCode::CONFLICT, "An experiment with this key already exists".to_string())
+        }
+        e => (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)),
+    })?;
+    Ok((StatusCode::CREATED, Json(experiment)))
+}
+
+// Only drafts can be edited: changing the key or variants of a started experiment would
+// rebucket visitors and mix up its results
+async fn update_experiment(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(input): Json<ExperimentInput>,
+) -> Result<Json<Experiment>, (StatusCode, String)> {
+    validate(&input)?;
+    let current = load_experiment(&app_state.pool, id).await?;
+    if current.status != "draft" {
+        return Err((StatusCode::CONFLICT, "Only draft experiments can be edited".to_string()));
+    }
+    let experiment = sqlx::query_as!(
+        Experiment,
+        r#"UPDATE experiments SET key = $1, name = $2, description = $3, kind = $4, product_ids = $5,
+                variants = $6, updated_at = NOW()
+         WHERE id = $7 AND status = 'draft' RETURNING id, key, name, description, kind, product_ids, variants AS "variants: sqlx::types::Json<Vec<Variant>>", status, started_at, stopped_at, created_by, created_at, updated_at"#,
+        input.key.trim(),
+        input.name.trim(),
+        input.description.as_deref().map(str::trim).filter(|d| !d.is_empty()),
+        &input.kind,
+        &input.product_ids,
+        sqlx::types::Json(trimmed_variants(input.variants)) as _,
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => {
+            (StatusCode::CONFLICT, "An experiment with this key already exists".to_string())
+        }
+        e => (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)),
+    })?
+    .ok_or((StatusCode::CONFLICT, "Only draft experiments can be edited".to_string()))?;
+    Ok(Json(experiment))
+}
+
+// Deleting an experiment also deletes its exposures
+async fn delete_experiment(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<bool>, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM experiments WHERE id = $1 AND status <> 'running'", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        load_experiment(&app_state.pool, id).await?;
+        return Err((StatusCode::CONFLICT, "Stop the experiment before deleting it".to_string()));
+    }
+    Ok(Json(true))
+}
+
+async fn start_experiment(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Experiment>, (StatusCode, String)> {
+    sqlx::query_as!(
+        Experiment,
+        r#"UPDATE experiments SET status = 'running', started_at = NOW(), updated_at = NOW()
+         WHERE id = $1 AND status = 'draft' RETURNING id, key, name, description, kind, product_ids, variants AS "variants: sqlx::types::Json<Vec<Variant>>", status, started_at, stopped_at, created_by, created_at, updated_at"#,
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .map(Json)
+    .ok_or((StatusCode::CONFLICT, "Only draft experiments can be started".to_string()))
+}
+
+// Stopped experiments keep their results; purchases after the stop no longer count
+async fn stop_experiment(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Experiment>, (StatusCode, String)> {
+    sqlx::query_as!(
+        Experiment,
+        r#"UPDATE experiments SET status = 'stopped', stopped_at = NOW(), updated_at = NOW()
+         WHERE id = $1 AND status = 'running' RETURNING id, key, name, description, kind, product_ids, variants AS "variants: sqlx::types::Json<Vec<Variant>>", status, started_at, stopped_at, created_by, created_at, updated_at"#,
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .map(Json)
+    .ok_or((StatusCode::CONFLICT, "Only running experiments can be stopped".to_string()))
+}
+
+// Two-proportion z-test of a variant's conversion against the control's
+fn z_score(converted: i64, visitors: i64, control_converted: i64, control_visitors: i64) -> Option<f64> {
+    if visitors == 0 || control_visitors == 0 {
+        return None;
+    }
+    let (n1, n2) = (visitors as f64, control_visitors as f64);
+    let pooled = (converted + control_converted) as f64 / (n1 + n2);
+    let se = (pooled * (1.0 - pooled) * (1.0 / n1 + 1.0 / n2)).sqrt();
+    if se == 0.0 {
+        return None;
+    }
+    Some((converted as f64 / n1 - control_converted as f64 / n2) / se)
+}
+
+// Conversion per variant: of the visitors exposed to it, how many bought afterwards
+// (before the experiment stopped), with their orders and revenue
+async fn get_results(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<ExperimentResults>, (StatusCode, String)> {
+    let experiment = load_experiment(&app_state.pool, id).await?;
+    let counts = sqlx::query_as!(
+        VariantCounts,
+        r#"WITH exposures AS (
+             SELECT visitor_id, variant, created_at FROM analytics_events
+             WHERE event_type = 'experiment_exposure' AND experiment_id = $1
+         ), buyers AS (
+             SELECT x.visitor_id, COUNT(p.id) AS orders, SUM(p.amount) AS revenue
+             FROM exposures x
+             JOIN analytics_events p ON p.event_type = 'purchase' AND p.visitor_id = x.visitor_id
+                  AND p.created_at >= x.created_at AND ($2::TIMESTAMPTZ IS NULL OR p.created_at <= $2)
+             GROUP BY x.visitor_id
+         )
+         SELECT x.variant, COUNT(*) AS "visitors!", COUNT(b.visitor_id) AS "converted!",
+                COALESCE(SUM(b.orders), 0)::BIGINT AS "orders!", COALESCE(SUM(b.revenue), 0)::BIGINT AS "revenue!"
+         FROM exposures x LEFT JOIN buyers b ON b.visitor_id = x.visitor_id
+         GROUP BY x.variant"#,
+        experiment.id,
+        experiment.stopped_at,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    let counts_for = |key: &str| counts.iter().find(|c| c.variant.as_deref() == Some(key));
+    let control = experiment.variants.first().map(|v| v.key.clone()).unwrap_or_default();
+    let (control_converted, control_visitors) =
+        counts_for(&control).map(|c| (c.converted, c.visitors)).unwrap_or((0, 0));
+    let control_rate = if control_visitors > 0 { control_converted as f64 / control_visitors as f64 } else { 0.0 };
+
+    let variants = experiment
+        .variants
+        .iter()
+        .map(|variant| {
+            let (visitors, converted, orders, revenue) = counts_for(&variant.key)
+                .map(|c| (c.visitors, c.converted, c.orders, c.revenue))
+                .unwrap_or((0, 0, 0, 0));
+            let conversion_rate = if visitors > 0 { converted as f64 / visitors as f64 } else { 0.0 };
+            let is_control = variant.key == control;
+            VariantResult {
+                variant: variant.key.clone(),
+                visitors,
+                converted,
+                conversion_rate,
+                orders,
+                revenue,
+                revenue_per_visitor: if visitors > 0 { revenue as f64 / visitors as f64 } else { 0.0 },
+                lift: (!is_control && control_rate > 0.0).then(|| (conversion_rate - control_rate) / control_rate),
+                z_score: if is_control { None } else { z_score(converted, visitors, control_converted, control_visitors) },
+            }
+        })
+        .collect();
+
+    Ok(Json(ExperimentResults { experiment, control, variants }))
+}
+
+// Logs each new order as a purchase by the visitor who checked out, so experiment results can
+// credit it to the variants that visitor saw
+pub struct PurchaseSubscriber {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl PurchaseSubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for PurchaseSubscriber {
+    fn name(&self) -> &'static str {
+        "experiment_purchases"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let DomainEvent::OrderCreated { order_id, total_amount, .. } = event else {
+            return Ok(());
+        };
+        // Orders placed without a storefront checkout (Square, manual) count for the customer
+        sqlx::query!(
+            "INSERT INTO analytics_events (event_type, visitor_id, customer_id, order_id, amount)
+             SELECT 'purchase', COALESCE(c.visitor_id, 'c:' || o.customer_id::TEXT), o.customer_id, o.id, $2
+             FROM orders o LEFT JOIN checkout_carts c ON c.order_id = o.id
+             WHERE o.id = $1 AND (c.visitor_id IS NOT NULL OR o.customer_id IS NOT NULL)
+             ON CONFLICT (order_id) WHERE event_type = 'purchase' DO NOTHING",
+            order_id,
+            total_amount,
+        )
+        .execute(&*self.pool)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+// Export Stream Module - CSV and JSON downloads written while the rows are read
+// Exports used to load every row and build the whole file in memory before sending a byte, so a
+// large catalog or order history could run the server out of memory. Now a task reads the rows
+// through a database cursor and writes them in chunks of about 64 KB into a channel holding a
+// few chunks, and the response body is read from that channel:
+// - backpressure: when the client reads slowly the channel fills up, the task waits to send and
+//   stops fetching rows, so memory stays at a few chunks whatever the size of the export
+// - a client that disconnects drops the body; the task's next send fails and it stops, closing
+//   the cursor
+// - an error after the response has started can't change its status any more, so the body
+//   ends with an error and the client sees a failed download rather than a short file
+//
+// `?format=csv` (default) or `?format=json` (one JSON array)
+
+use axum::{
+    body::{Body, Bytes},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+const CHUNK_BYTES: usize = 64 * 1024;
+const BUFFERED_CHUNKS: usize = 4;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Closed, // The client went away
+    Database(sqlx::Error),
+    Encode(String),
+    Write(std::io::Error), // Writing to a file (write_to)
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Closed => f.write_str("client disconnected"),
+            ExportError::Database(e) => write!(f, "DB error: {}", e),
+            ExportError::Encode(e) => write!(f, "encoding error: {}", e),
+            ExportError::Write(e) => write!(f, "write error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<sqlx::Error> for ExportError {
+    fn from(e: sqlx::Error) -> Self {
+        ExportError::Database(e)
+    }
+}
+
+// Where a producer writes its rows; they're sent on in chunks
+pub struct ExportWriter {
+    format: ExportFormat,
+    chunks: mpsc::Sender<Result<Bytes, ExportError>>,
+    buffer: Vec<u8>,
+    rows: u64,
+}
+
+impl ExportWriter {
+    pub async fn write<T: Serialize>(&mut self, row: &T) -> Result<(), ExportError> {
+        match self.format {
+            ExportFormat::Csv => {
+                // The header goes out with the first row only
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(self.rows == 0)
+                    .from_writer(&mut self.buffer);
+                writer.serialize(row).map_err(|e| ExportError::Encode(e.to_string()))?;
+                writer.flush().map_err(|e| ExportError::Encode(e.to_string()))?;
+            }
+            ExportFormat::Json => {
+                self.buffer.push(if self.rows == 0 { b'[' } else { b',' });
+                serde_json::to_writer(&mut self.buffer, row).map_err(|e| ExportError::Encode(e.to_string()))?;
+            }
+        }
+        self.rows += 1;
+        if self.buffer.len() >= CHUNK_BYTES {
+            self.send_buffer().await?;
+        }
+        Ok(())
+    }
+
+    // Waits while the channel is full: this is where a slow client holds the export back
+    async fn send_buffer(&mut self) -> Result<(), ExportError> {
+        let chunk = Bytes::from(std::mem::take(&mut self.buffer));
+        self.chunks.send(Ok(chunk)).await.map_err(|_| ExportError::Closed)
+    }
+
+    async fn finish(mut self) -> Result<u64, ExportError> {
+        if self.format == ExportFormat::Json {
+            self.buffer.extend_from_slice(if self.rows == 0 { b"[]" } else { b"]" });
+        }
+        if !self.buffer.is_empty() {
+            self.send_buffer().await?;
+        }
+        Ok(self.rows)
+    }
+}
+
+// Run `produce` into `out` (a file or stdout) instead of a response, for rcom-cli; the row count
+pub async fn write_to<W, F, Fut>(format: ExportFormat, mut out: W, produce: F) -> Result<u64, ExportError>
+where
+    W: AsyncWrite + Unpin,
+    F: FnOnce(ExportWriter) -> Fut,
+    Fut: Future<Output = Result<ExportWriter, ExportError>>,
+{
+    let (sender, mut receiver) = mpsc::channel(BUFFERED_CHUNKS);
+    let writer = ExportWriter {
+        format,
+        chunks: sender,
+        buffer: Vec::with_capacity(CHUNK_BYTES),
+        rows: 0,
+    };
+    let rows = async move { produce(writer).await?.finish().await };
+    let written = async move {
+        while let Some(chunk) = receiver.recv().await {
+            out.write_all(&chunk?).await.map_err(ExportError::Write)?;
+        }
+        out.flush().await.map_err(ExportError::Write)
+    };
+    // A failed write drops the receiver, so the producer stops with Closed; report the write error
+    let (rows, written) = tokio::join!(rows, written);
+    written?;
+    rows
+}
+
+// Stream `name`.csv or .json as an attachment. `produce` runs in its own task, writes every row
+// and hands the writer back
+pub fn respond<F, Fut>(format: ExportFormat, name: &'static str, produce: F) -> Response
+where
+    F: FnOnce(ExportWriter) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<ExportWriter, ExportError>> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(BUFFERED_CHUNKS);
+    let writer = ExportWriter {
+        format,
+        chunks: sender.clone(),
+        buffer: Vec::with_capacity(CHUNK_BYTES),
+        rows: 0,
+    };
+    tokio::spawn(async move {
+        let result = match produce(writer).await {
+            Ok(writer) => writer.finish().await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(rows) => tracing::info!(export = name, rows, "export sent"),
+            Err(ExportError::Closed) => tracing::info!(export = name, "export abandoned by the client"),
+            Err(e) => {
+                tracing::error!(export = name, error = %e, "export failed partway");
+                let _ = sender.send(Err(e)).await;
+            }
+        }
+    });
+
+    let body = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+    let disposition = format!("attachment; filename=\"{}.{}\"", name, format.extension());
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
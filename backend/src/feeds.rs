@@ -0,0 +1,307 @@
+// Product Feeds Module - Google Merchant Center shopping feed
+// The catalog is rendered as an RSS 2.0 feed with Google's g: namespace, regenerated
+// on a schedule and served from memory so shopping ads stay in sync with the store
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::admin_products::Product;
+use crate::app_config;
+use crate::pricing::PriceBook;
+use crate::AppState;
+use crate::repos::product_query;
+
+// Scheduled task that regenerates the feed
+pub const REFRESH_TASK: &str = "refresh_google_feed";
+
+// Last generated feed, shared between the scheduler and the endpoint
+#[derive(Default)]
+pub struct FeedCache {
+    google: Mutex<Option<CachedFeed>>,
+}
+
+#[derive(Clone)]
+struct CachedFeed {
+    xml: String,
+    generated_at: DateTime<Utc>,
+    item_count: usize,
+    skipped: usize,
+}
+
+impl FeedCache {
+    fn get(&self) -> Option<CachedFeed> {
+        self.google.lock().unwrap().clone()
+    }
+
+    fn set(&self, feed: CachedFeed) {
+        *self.google.lock().unwrap() = Some(feed);
+    }
+}
+
+// Database model for category mappings
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct GoogleCategoryMapping {
+    pub category: String,
+    pub google_product_category: String,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct GoogleCategoryInput {
+    pub google_product_category: String,
+}
+
+#[derive(Serialize)]
+pub struct FeedStatus {
+    pub generated_at: DateTime<Utc>,
+    pub item_count: usize,
+    pub skipped: usize, // Published products left out because they have no image
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Only well-formed GTINs (UPC-A, EAN-8/13, ITF-14) are sent; Google rejects anything else
+fn valid_gtin(code: &str) -> bool {
+    matches!(code.len(), 8 | 12 | 13 | 14) && code.chars().all(|c| c.is_ascii_digit())
+}
+
+fn xml_field(name: &str, value: &str) -> String {
+    format!("<{0}>{1}</{0}>", name, escape_xml(value))
+}
+
+// Render one product as a feed item
+fn render_item(
+    product: &Product,
+    price_book: &PriceBook,
+    google_categories: &std::collections::HashMap<String, String>,
+    storefront_url: &str,
+    currency: &str,
+) -> Option<String> {
+    let image_url = product.image_url.as_deref()?;
+    let image_url = if image_url.starts_with('/') {
+        format!("{}{}", storefront_url, image_url)
+    } else {
+        image_url.to_string()
+    };
+
+    let mut fields = vec![
+        xml_field("g:id", product.sku.as_deref().unwrap_or(&product.id.to_string())),
+        xml_field("g:title", &product.name),
+        xml_field("g:description", product.description.as_deref().unwrap_or(&product.name)),
+        xml_field("g:link", &format!("{}/product/{}", storefront_url, product.slug)),
+        xml_field("g:image_link", &image_url),
+        xml_field("g:condition", "new"),
+        xml_field("g:availability", if product.inventory > 0 { "in_stock" } else { "out_of_stock" }),
+        xml_field("g:price", &format!("{:.2} {}", product.price, currency)),
+    ];
+
+    let effective = price_book.unit_price(product.id, product.price, 1);
+    if effective < product.price {
+        fields.push(xml_field("g:sale_price", &format!("{:.2} {}", effective, currency)));
+    }
+
+    let gtin = product.barcode.as_deref().filter(|code| valid_gtin(code));
+    if let Some(gtin) = gtin {
+        fields.push(xml_field("g:gtin", gtin));
+    }
+    if let Some(sku) = &product.sku {
+        fields.push(xml_field("g:mpn", sku));
+    }
+    if let Some(brand) = &product.brand {
+        fields.push(xml_field("g:brand", brand));
+    }
+    // Without a GTIN, Google needs brand + MPN or an explicit "no identifiers"
+    if gtin.is_none() && (product.brand.is_none() || product.sku.is_none()) {
+        fields.push(xml_field("g:identifier_exists", "no"));
+    }
+
+    if let Some(category) = &product.category {
+        fields.push(xml_field("g:product_type", category));
+        if let Some(google_category) = google_categories.get(category) {
+            fields.push(xml_field("g:google_product_category", google_category));
+        }
+    }
+    if let Some(weight_oz) = product.weight_oz {
+        fields.push(xml_field("g:shipping_weight", &format!("{} oz", weight_oz)));
+    }
+
+    Some(format!("<item>{}</item>", fields.concat()))
+}
+
+// Build the feed from the published catalog with current sale prices
+async fn generate_google_feed(pool: &sqlx::PgPool) -> Result<CachedFeed, sqlx::Error> {
+    let products = product_query!("SELECT", "FROM products WHERE status = 'published' ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+    let price_book = PriceBook::load_active(pool).await?;
+    let google_categories: std::collections::HashMap<String, String> =
+        sqlx::query!("SELECT category, google_product_category FROM google_category_mappings")
+            .map(|r| (r.category, r.google_product_category))
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .collect();
+
+    let config = app_config::get();
+    let storefront_url = config.server.storefront_url.trim_end_matches('/');
+    let currency = &config.feeds.currency;
+    let store_name = &config.smtp.from_name;
+
+    let items: Vec<String> = products
+        .iter()
+        .filter_map(|product| render_item(product, &price_book, &google_categories, storefront_url, currency))
+        .collect();
+
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\" xmlns:g=\"http://base.google.com/ns/1.0\"><channel>{}{}{}{}</channel></rss>\n",
+        xml_field("title", store_name),
+        xml_field("link", storefront_url),
+        xml_field("description", &format!("{} product feed", store_name)),
+        items.concat()
+    );
+    Ok(CachedFeed {
+        xml,
+        generated_at: Utc::now(),
+        item_count: items.len(),
+        skipped: products.len() - items.len(),
+    })
+}
+
+async fn refresh(app_state: &AppState) -> Result<CachedFeed, sqlx::Error> {
+    let feed = generate_google_feed(&app_state.pool).await?;
+    app_state.feed_cache.set(feed.clone());
+    Ok(feed)
+}
+
+// Regenerate the feed (the refresh_google_feed scheduled task); nothing to do with the feed off
+pub async fn scheduled_refresh(app_state: &AppState) -> Result<String, String> {
+    if !app_config::get().features.product_feed {
+        return Ok("Product feed disabled".to_string());
+    }
+    let feed = refresh(app_state).await.map_err(|e| format!("Failed to regenerate Google Merchant feed: {}", e))?;
+    Ok(format!(
+        "Regenerated Google Merchant feed: {} items ({} skipped without image)",
+        feed.item_count, feed.skipped
+    ))
+}
+
+// When the latest successful scheduled refresh started, on whichever replica ran it
+async fn last_scheduled_refresh(pool: &sqlx::PgPool) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT MAX(started_at) FROM scheduled_task_runs WHERE task_name = $1 AND status = 'succeeded'",
+        REFRESH_TASK,
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub fn feed_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/feeds/google-merchant.xml", get(google_merchant_feed))
+        .route("/api/admin/feeds/google-merchant/regenerate", post(regenerate_feed))
+        .route("/api/admin/feeds/google-categories", get(list_category_mappings))
+        .route(
+            "/api/admin/feeds/google-categories/:category",
+            put(set_category_mapping).delete(delete_category_mapping),
+        )
+        .with_state(app_state)
+}
+
+async fn google_merchant_feed(State(app_state): State<Arc<AppState>>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    // Each replica keeps its own copy; one older than the last scheduled refresh is rebuilt
+    let refreshed_at = last_scheduled_refresh(&app_state.pool).await.map_err(db_error)?;
+    let feed = match app_state.feed_cache.get() {
+        Some(feed) if refreshed_at.is_none_or(|at| feed.generated_at >= at) => feed,
+        _ => refresh(&app_state).await.map_err(db_error)?,
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/xml; charset=utf-8".to_string()),
+            (header::LAST_MODIFIED, feed.generated_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()),
+        ],
+        feed.xml,
+    ))
+}
+
+// Rebuild the feed now instead of waiting for the next scheduled run
+async fn regenerate_feed(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<FeedStatus>, (StatusCode, String)> {
+    let feed = refresh(&app_state)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(FeedStatus {
+        generated_at: feed.generated_at,
+        item_count: feed.item_count,
+        skipped: feed.skipped,
+    }))
+}
+
+async fn list_category_mappings(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<GoogleCategoryMapping>>, (StatusCode, String)> {
+    let mappings = sqlx::query_as!(GoogleCategoryMapping, "SELECT category, google_product_category, updated_at FROM google_category_mappings ORDER BY category")
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(mappings))
+}
+
+async fn set_category_mapping(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(category): Path<String>,
+    Json(input): Json<GoogleCategoryInput>,
+) -> Result<Json<GoogleCategoryMapping>, (StatusCode, String)> {
+    let google_category = input.google_product_category.trim();
+    if category.trim().is_empty() || google_category.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "category and google_product_category are required".to_string()));
+    }
+    let mapping = sqlx::query_as!(
+        GoogleCategoryMapping,
+        "INSERT INTO google_category_mappings (category, google_product_category)
+         VALUES ($1, $2)
+         ON CONFLICT (category) DO UPDATE SET google_product_category = $2, updated_at = NOW()
+         RETURNING category, google_product_category, updated_at",
+        category.trim(),
+        google_category,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?;
+    Ok(Json(mapping))
+}
+
+async fn delete_category_mapping(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(category): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM google_category_mappings WHERE category = $1", category.trim())
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Mapping not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
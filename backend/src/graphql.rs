@@ -0,0 +1,560 @@
+// GraphQL Module - Read API for headless frontends and mobile apps
+// Exposes products, categories, cart pricing and orders at /graphql so clients fetch exactly
+// the fields they need in one round trip. Nested lookups are batched through dataloaders.
+
+use async_graphql::{
+    dataloader::{DataLoader, Loader},
+    http::GraphiQLSource,
+    Context, EmptyMutation, EmptySubscription, Guard, InputObject, Object, Result, Schema, SimpleObject,
+};
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+    routing::get,
+    Extension, Json, Router,
+};
+use sqlx::types::chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::admin_products::Product;
+use crate::customer_auth::AuthenticatedCustomer;
+use crate::pricing::{to_cents, PriceBook};
+use crate::repos::{order_query, product_query};
+use crate::webhooks::{Order, OrderItem};
+use crate::AppState;
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+const MAX_QUERY_DEPTH: usize = 10;
+const MAX_QUERY_COMPLEXITY: usize = 500;
+
+pub type StorefrontSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+// Who is making the request, taken from the bearer token
+#[derive(Clone, Copy)]
+enum Viewer {
+    Anonymous,
+    Customer(Uuid),
+    Admin,
+}
+
+// Active price rules, loaded at most once per request and only when a price is asked for
+#[derive(Default)]
+struct RequestPrices(OnceCell<PriceBook>);
+
+async fn price_book<'a>(ctx: &Context<'a>) -> Result<&'a PriceBook> {
+    let pool = ctx.data_unchecked::<sqlx::PgPool>();
+    let book = ctx
+        .data_unchecked::<RequestPrices>()
+        .0
+        .get_or_try_init(|| PriceBook::load_active(pool))
+        .await?;
+    Ok(book)
+}
+
+fn page(limit: Option<i64>, offset: Option<i64>) -> (i64, i64) {
+    (
+        limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE),
+        offset.unwrap_or(0).max(0),
+    )
+}
+
+// --- Guards ---
+
+struct AdminGuard;
+
+impl Guard for AdminGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        match ctx.data_unchecked::<Viewer>() {
+            Viewer::Admin => Ok(()),
+            _ => Err("Admin login required".into()),
+        }
+    }
+}
+
+struct SignedInGuard;
+
+impl Guard for SignedInGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        match ctx.data_unchecked::<Viewer>() {
+            Viewer::Anonymous => Err("Login required".into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+// --- Dataloaders ---
+
+// Products by id, any status (order items may point at unpublished products)
+pub struct ProductLoader(sqlx::PgPool);
+
+impl Loader<i32> for ProductLoader {
+    type Value = Product;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Product>, Self::Error> {
+        let products = product_query!("SELECT", "FROM products WHERE id = ANY($1)", keys)
+            .fetch_all(&self.0)
+            .await?;
+        Ok(products.into_iter().map(|p| (p.id, p)).collect())
+    }
+}
+
+// Line items grouped by order id
+pub struct OrderItemsLoader(sqlx::PgPool);
+
+impl Loader<Uuid> for OrderItemsLoader {
+    type Value = Vec<OrderItem>;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Vec<OrderItem>>, Self::Error> {
+        let items = sqlx::query_as!(
+            OrderItem,
+            r#"SELECT id, order_id, product_id, product_name, product_description, quantity, unit_price, total_price, created_at AS "created_at!" FROM order_items WHERE order_id = ANY($1) ORDER BY created_at, id"#,
+            keys,
+        )
+        .fetch_all(&self.0)
+        .await?;
+        let mut grouped: HashMap<Uuid, Vec<OrderItem>> = HashMap::new();
+        for item in items {
+            grouped.entry(item.order_id).or_default().push(item);
+        }
+        Ok(grouped)
+    }
+}
+
+// --- Types ---
+
+pub struct ProductNode(Product);
+
+#[Object(name = "Product")]
+impl ProductNode {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// URL slug; the storefront page is /product/{slug}
+    async fn slug(&self) -> &str {
+        &self.0.slug
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn sku(&self) -> Option<&str> {
+        self.0.sku.as_deref()
+    }
+
+    async fn image_url(&self) -> Option<&str> {
+        self.0.image_url.as_deref()
+    }
+
+    async fn brand(&self) -> Option<&str> {
+        self.0.brand.as_deref()
+    }
+
+    async fn category(&self) -> Option<&str> {
+        self.0.category.as_deref()
+    }
+
+    async fn inventory(&self) -> i32 {
+        self.0.inventory
+    }
+
+    async fn in_stock(&self) -> bool {
+        self.0.inventory > 0
+    }
+
+    /// Unit price in dollars with active sales and quantity breaks applied
+    async fn price(&self, ctx: &Context<'_>, #[graphql(default = 1)] quantity: i32) -> Result<f64> {
+        Ok(price_book(ctx).await?.unit_price(self.0.id, self.0.price, quantity))
+    }
+
+    /// List price before any sale
+    async fn original_price(&self) -> f64 {
+        self.0.price
+    }
+
+    async fn price_breaks(&self, ctx: &Context<'_>) -> Result<Vec<PriceBreakNode>> {
+        Ok(price_book(ctx)
+            .await?
+            .price_breaks(self.0.id, self.0.price)
+            .into_iter()
+            .map(|b| PriceBreakNode {
+                min_quantity: b.min_quantity,
+                price: b.price,
+            })
+            .collect())
+    }
+
+    async fn weight_oz(&self) -> Option<f64> {
+        self.0.weight_oz
+    }
+
+    async fn created_at(&self) -> NaiveDateTime {
+        self.0.created_at
+    }
+
+    #[graphql(guard = "AdminGuard")]
+    async fn status(&self) -> String {
+        serde_json::to_value(self.0.status)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default()
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "PriceBreak")]
+pub struct PriceBreakNode {
+    min_quantity: i32,
+    price: f64,
+}
+
+pub struct CategoryNode {
+    name: String,
+    product_count: i64,
+}
+
+#[Object(name = "Category")]
+impl CategoryNode {
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn product_count(&self) -> i64 {
+        self.product_count
+    }
+
+    async fn products(&self, ctx: &Context<'_>, limit: Option<i64>, offset: Option<i64>) -> Result<Vec<ProductNode>> {
+        let (limit, offset) = page(limit, offset);
+        let products = product_query!(
+            "SELECT",
+            "FROM products WHERE status = 'published' AND category = $1 ORDER BY id LIMIT $2 OFFSET $3",
+            &self.name,
+            limit,
+            offset,
+        )
+        .fetch_all(ctx.data_unchecked::<sqlx::PgPool>())
+        .await?;
+        Ok(products.into_iter().map(ProductNode).collect())
+    }
+}
+
+#[derive(InputObject)]
+pub struct CartLineInput {
+    product_id: i32,
+    quantity: i32,
+}
+
+// A cart priced server-side, the same way checkout charges it
+pub struct CartNode {
+    lines: Vec<CartLineNode>,
+}
+
+#[Object(name = "Cart")]
+impl CartNode {
+    async fn lines(&self) -> &[CartLineNode] {
+        &self.lines
+    }
+
+    async fn item_count(&self) -> i32 {
+        self.lines.iter().map(|l| l.quantity).sum()
+    }
+
+    async fn subtotal_cents(&self) -> i64 {
+        self.lines.iter().map(CartLineNode::total_cents).sum()
+    }
+}
+
+pub struct CartLineNode {
+    product: Product,
+    quantity: i32,
+    unit_price: f64,
+}
+
+impl CartLineNode {
+    fn total_cents(&self) -> i64 {
+        to_cents(self.unit_price) * self.quantity as i64
+    }
+}
+
+#[Object(name = "CartLine")]
+impl CartLineNode {
+    async fn product(&self) -> ProductNode {
+        ProductNode(self.product.clone())
+    }
+
+    async fn quantity(&self) -> i32 {
+        self.quantity
+    }
+
+    async fn unit_price(&self) -> f64 {
+        self.unit_price
+    }
+
+    async fn line_total_cents(&self) -> i64 {
+        self.total_cents()
+    }
+}
+
+pub struct OrderNode(Order);
+
+#[Object(name = "Order")]
+impl OrderNode {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn status(&self) -> String {
+        self.0.status.to_string()
+    }
+
+    async fn total_cents(&self) -> i64 {
+        self.0.total_amount
+    }
+
+    async fn currency(&self) -> &str {
+        &self.0.currency
+    }
+
+    async fn customer_email(&self) -> Option<&str> {
+        self.0.customer_email.as_deref()
+    }
+
+    async fn customer_name(&self) -> Option<&str> {
+        self.0.customer_name.as_deref()
+    }
+
+    async fn gift_wrap(&self) -> bool {
+        self.0.gift_wrap
+    }
+
+    async fn gift_message(&self) -> Option<&str> {
+        self.0.gift_message.as_deref()
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn items(&self, ctx: &Context<'_>) -> Result<Vec<OrderItemNode>> {
+        let items = ctx
+            .data_unchecked::<DataLoader<OrderItemsLoader>>()
+            .load_one(self.0.id)
+            .await?
+            .unwrap_or_default();
+        Ok(items.into_iter().map(OrderItemNode).collect())
+    }
+}
+
+pub struct OrderItemNode(OrderItem);
+
+#[Object(name = "OrderItem")]
+impl OrderItemNode {
+    async fn product_name(&self) -> &str {
+        &self.0.product_name
+    }
+
+    async fn quantity(&self) -> i32 {
+        self.0.quantity
+    }
+
+    async fn unit_price_cents(&self) -> i64 {
+        self.0.unit_price
+    }
+
+    async fn total_price_cents(&self) -> i64 {
+        self.0.total_price
+    }
+
+    /// The product as it is now; null once it has been deleted
+    async fn product(&self, ctx: &Context<'_>) -> Result<Option<ProductNode>> {
+        let Some(product_id) = self.0.product_id else {
+            return Ok(None);
+        };
+        let product = ctx.data_unchecked::<DataLoader<ProductLoader>>().load_one(product_id).await?;
+        Ok(product.map(ProductNode))
+    }
+}
+
+// --- Query root ---
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Published products, optionally filtered by category or a name search
+    async fn products(
+        &self,
+        ctx: &Context<'_>,
+        category: Option<String>,
+        search: Option<String>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<ProductNode>> {
+        let (limit, offset) = page(limit, offset);
+        let products = product_query!(
+            "SELECT",
+            "FROM products
+             WHERE status = 'published'
+               AND ($1::VARCHAR IS NULL OR category = $1)
+               AND ($2::TEXT IS NULL OR name ILIKE '%' || $2 || '%')
+             ORDER BY id LIMIT $3 OFFSET $4",
+            category,
+            search.as_deref().map(str::trim).filter(|s| !s.is_empty()),
+            limit,
+            offset,
+        )
+        .fetch_all(ctx.data_unchecked::<sqlx::PgPool>())
+        .await?;
+        Ok(products.into_iter().map(ProductNode).collect())
+    }
+
+    /// A single product; drafts are only visible to admins
+    async fn product(&self, ctx: &Context<'_>, id: i32) -> Result<Option<ProductNode>> {
+        let product = ctx.data_unchecked::<DataLoader<ProductLoader>>().load_one(id).await?;
+        let is_admin = matches!(ctx.data_unchecked::<Viewer>(), Viewer::Admin);
+        Ok(product
+            .filter(|p| is_admin || p.status == crate::admin_products::ProductStatus::Published)
+            .map(ProductNode))
+    }
+
+    /// Categories that have at least one published product
+    async fn categories(&self, ctx: &Context<'_>) -> Result<Vec<CategoryNode>> {
+        let rows = sqlx::query!(
+            r#"SELECT category AS "category!", COUNT(*) AS "count!" FROM products
+             WHERE status = 'published' AND category IS NOT NULL
+             GROUP BY category ORDER BY category"#,
+        )
+        .map(|r| (r.category, r.count))
+        .fetch_all(ctx.data_unchecked::<sqlx::PgPool>())
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(name, product_count)| CategoryNode { name, product_count })
+            .collect())
+    }
+
+    /// Price a client-side cart with current sale prices and quantity breaks
+    async fn cart(&self, ctx: &Context<'_>, lines: Vec<CartLineInput>) -> Result<CartNode> {
+        let loader = ctx.data_unchecked::<DataLoader<ProductLoader>>();
+        let products = loader.load_many(lines.iter().map(|l| l.product_id)).await?;
+        let book = price_book(ctx).await?;
+
+        let mut priced = Vec::with_capacity(lines.len());
+        for line in lines {
+            if line.quantity < 1 {
+                return Err(format!("Invalid quantity for product {}", line.product_id).into());
+            }
+            let product = products
+                .get(&line.product_id)
+                .filter(|p| p.status == crate::admin_products::ProductStatus::Published)
+                .ok_or_else(|| format!("Unknown product {}", line.product_id))?;
+            priced.push(CartLineNode {
+                unit_price: book.unit_price(product.id, product.price, line.quantity),
+                product: product.clone(),
+                quantity: line.quantity,
+            });
+        }
+        Ok(CartNode { lines: priced })
+    }
+
+    /// The signed-in customer's orders, or every order for admins
+    #[graphql(guard = "SignedInGuard")]
+    async fn orders(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<String>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<OrderNode>> {
+        let (limit, offset) = page(limit, offset);
+        let customer_id = match ctx.data_unchecked::<Viewer>() {
+            Viewer::Customer(id) => Some(*id),
+            _ => None,
+        };
+        let orders = order_query!(
+            "SELECT",
+            "FROM orders
+             WHERE ($1::UUID IS NULL OR customer_id = $1)
+               AND ($2::VARCHAR IS NULL OR status = $2)
+             ORDER BY created_at DESC LIMIT $3 OFFSET $4",
+            customer_id,
+            status,
+            limit,
+            offset,
+        )
+        .fetch_all(ctx.data_unchecked::<sqlx::PgPool>())
+        .await?;
+        Ok(orders.into_iter().map(OrderNode).collect())
+    }
+
+    /// A single order, visible to its customer and to admins
+    #[graphql(guard = "SignedInGuard")]
+    async fn order(&self, ctx: &Context<'_>, id: Uuid) -> Result<Option<OrderNode>> {
+        let order = order_query!("SELECT", "FROM orders WHERE id = $1", id)
+            .fetch_optional(ctx.data_unchecked::<sqlx::PgPool>())
+            .await?;
+        let viewer = *ctx.data_unchecked::<Viewer>();
+        Ok(order
+            .filter(|o| match viewer {
+                Viewer::Admin => true,
+                Viewer::Customer(customer_id) => o.customer_id == Some(customer_id),
+                Viewer::Anonymous => false,
+            })
+            .map(OrderNode))
+    }
+}
+
+pub fn build_schema(pool: sqlx::PgPool) -> StorefrontSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(pool)
+        .limit_depth(MAX_QUERY_DEPTH)
+        .limit_complexity(MAX_QUERY_COMPLEXITY)
+        .finish()
+}
+
+pub fn graphql_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    let schema = build_schema((*app_state.pool).clone());
+    Router::new()
+        .route("/graphql", get(graphiql).post(graphql_handler))
+        .layer(Extension(schema))
+        .with_state(app_state)
+}
+
+// In-browser query editor
+async fn graphiql() -> impl IntoResponse {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+// Admin and customer tokens are both optional; resolvers check the viewer through guards
+async fn graphql_handler(
+    State(app_state): State<Arc<AppState>>,
+    Extension(schema): Extension<StorefrontSchema>,
+    admin: Option<AuthenticatedAdmin>,
+    customer: Option<AuthenticatedCustomer>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    let viewer = match (admin, customer) {
+        (Some(_), _) => Viewer::Admin,
+        (None, Some(customer)) => Viewer::Customer(customer.customer_id),
+        (None, None) => Viewer::Anonymous,
+    };
+    let pool = (*app_state.pool).clone();
+    let request = request
+        .data(viewer)
+        .data(RequestPrices::default())
+        .data(DataLoader::new(ProductLoader(pool.clone()), tokio::spawn))
+        .data(DataLoader::new(OrderItemsLoader(pool), tokio::spawn));
+    Json(schema.execute(request).await)
+}
@@ -0,0 +1,498 @@
+// Integrations Module - Two-way sync with an external ERP and polling feeds for no-code tools
+// The ERP pushes stock levels and prices by SKU in bulk (authenticated with scoped API keys),
+// and gets a signed webhook for every new order through the job queue so failed deliveries retry.
+// Tools like Zapier poll /api/integrations/orders and /customers with a cursor for new records
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::{get, put},
+    Json, Router,
+};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::admin_products::publish_stock_events;
+use crate::api_keys::{
+    ApiKeyAuth, SCOPE_READ_CUSTOMERS, SCOPE_READ_ORDERS, SCOPE_READ_PRODUCTS, SCOPE_WRITE_INVENTORY, SCOPE_WRITE_PRICES,
+};
+use crate::app_config;
+use crate::customers::{customer_query, Customer};
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::jobs;
+use crate::pagination::{self, CursorKey};
+use crate::pricing;
+use crate::repos::{order_query, product_query};
+use crate::request_id::WithRequestId;
+use crate::webhooks::{Order, OrderItem};
+use crate::AppState;
+
+pub const ERP_ORDER_WEBHOOK_JOB: &str = "erp_order_webhook";
+const MAX_BATCH_SIZE: usize = 1000;
+const DEFAULT_POLL_LIMIT: i64 = 50;
+const MAX_POLL_LIMIT: i64 = 100;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Deserialize)]
+pub struct InventoryUpdate {
+    pub sku: String,
+    pub inventory: i32,
+}
+
+#[derive(Deserialize)]
+pub struct PriceUpdate {
+    pub sku: String,
+    pub price: f64,
+}
+
+// SKU catalog with current stock and price, for reconciling against the ERP
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ProductStock {
+    pub id: i32,
+    pub sku: String,
+    pub name: String,
+    pub price: f64,
+    pub inventory: i32,
+}
+
+// Per-item outcome of a bulk update; one bad row never blocks the rest
+#[derive(Serialize)]
+pub struct SyncSummary {
+    pub updated: usize,
+    pub errors: Vec<SyncError>,
+}
+
+#[derive(Serialize)]
+pub struct SyncError {
+    pub index: usize,
+    pub sku: String,
+    pub error: String,
+}
+
+impl SyncSummary {
+    fn new() -> Self {
+        Self { updated: 0, errors: Vec::new() }
+    }
+
+    fn fail(&mut self, index: usize, sku: &str, error: impl Into<String>) {
+        self.errors.push(SyncError {
+            index,
+            sku: sku.to_string(),
+            error: error.into(),
+        });
+    }
+}
+
+fn check_batch_size(len: usize) -> Result<(), (StatusCode, String)> {
+    if len > MAX_BATCH_SIZE {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("At most {} updates per request", MAX_BATCH_SIZE),
+        ));
+    }
+    Ok(())
+}
+
+pub fn integration_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/integrations/inventory", put(sync_inventory))
+        .route("/api/integrations/prices", put(sync_prices))
+        .route("/api/integrations/products", get(list_products))
+        .route("/api/integrations/orders", get(poll_orders))
+        .route("/api/integrations/customers", get(poll_customers))
+        .with_state(app_state)
+}
+
+async fn list_products(
+    key: ApiKeyAuth,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ProductStock>>, (StatusCode, String)> {
+    key.require(SCOPE_READ_PRODUCTS)?;
+    let products = sqlx::query_as!(
+        ProductStock,
+        r#"SELECT id, sku AS "sku!", name, price, inventory FROM products WHERE sku IS NOT NULL ORDER BY sku"#,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(products))
+}
+
+async fn sync_inventory(
+    key: ApiKeyAuth,
+    State(app_state): State<Arc<AppState>>,
+    Json(updates): Json<Vec<InventoryUpdate>>,
+) -> Result<Json<SyncSummary>, (StatusCode, String)> {
+    key.require(SCOPE_WRITE_INVENTORY)?;
+    check_batch_size(updates.len())?;
+    let mut summary = SyncSummary::new();
+
+    for (index, update) in updates.iter().enumerate() {
+        let sku = update.sku.trim();
+        if sku.is_empty() {
+            summary.fail(index, sku, "sku is required");
+            continue;
+        }
+        if update.inventory < 0 {
+            summary.fail(index, sku, "inventory must not be negative");
+            continue;
+        }
+
+        let previous_inventory: Option<i32> = match sqlx::query_scalar!(
+            "SELECT inventory FROM products WHERE sku = $1",
+            sku,
+        )
+        .fetch_optional(&*app_state.pool)
+        .await
+        {
+        Ok(previous) => previous,
+        Err(e) => {
+            summary.fail(index, sku, format!("DB error: {}", e));
+            continue;
+        }
+        };
+        let product = product_query!(
+            "UPDATE products SET inventory = $1 WHERE sku = $2 RETURNING",
+            "",
+            update.inventory,
+            sku,
+        )
+        .fetch_optional(&*app_state.pool)
+        .await;
+        match product {
+            Ok(Some(product)) => {
+                app_state.events.publish(DomainEvent::ProductUpdated { product_id: product.id });
+                publish_stock_events(&app_state.events, &product, previous_inventory);
+                summary.updated += 1;
+            }
+            Ok(None) => summary.fail(index, sku, "Unknown SKU"),
+            Err(e) => summary.fail(index, sku, format!("DB error: {}", e)),
+        }
+    }
+
+    Ok(Json(summary))
+}
+
+async fn sync_prices(
+    key: ApiKeyAuth,
+    State(app_state): State<Arc<AppState>>,
+    Json(updates): Json<Vec<PriceUpdate>>,
+) -> Result<Json<SyncSummary>, (StatusCode, String)> {
+    key.require(SCOPE_WRITE_PRICES)?;
+    check_batch_size(updates.len())?;
+    let mut summary = SyncSummary::new();
+
+    for (index, update) in updates.iter().enumerate() {
+        let sku = update.sku.trim();
+        if sku.is_empty() {
+            summary.fail(index, sku, "sku is required");
+            continue;
+        }
+        if !update.price.is_finite() || update.price <= 0.0 {
+            summary.fail(index, sku, "price must be greater than 0");
+            continue;
+        }
+
+        let result = sqlx::query!(
+            r#"UPDATE products p SET price = $1 FROM (SELECT id, price FROM products WHERE sku = $2 FOR UPDATE) old
+             WHERE p.id = old.id RETURNING p.id, p.name, old.price AS "previous_price""#,
+            update.price,
+            sku,
+        )
+        .fetch_optional(&*app_state.pool)
+        .await;
+        match result {
+            Ok(Some(row)) => {
+                app_state.events.publish(DomainEvent::ProductUpdated { product_id: row.id });
+                pricing::publish_price_drop(&app_state.events, row.id, &row.name, row.previous_price, update.price);
+                summary.updated += 1;
+            }
+            Ok(_) => summary.fail(index, sku, "Unknown SKU"),
+            Err(e) => summary.fail(index, sku, format!("DB error: {}", e)),
+        }
+    }
+
+    Ok(Json(summary))
+}
+
+// --- Polling feeds ---
+
+// Position after the last record seen: the id of the transaction that inserted it, then
+// (created_at, id). Cursors handed out before that id was kept carry none and sort among the
+// rows that existed then, which all share the lowest transaction id
+struct Cursor {
+    xid: Option<i64>,
+    created_at: DateTime<Utc>,
+    id: Uuid,
+}
+
+impl CursorKey for Cursor {
+    fn to_text(&self) -> String {
+        let key = (self.created_at, self.id).to_text();
+        match self.xid {
+            Some(xid) => format!("{}|{}", xid, key),
+            None => key,
+        }
+    }
+
+    fn from_text(text: &str) -> Option<Self> {
+        let (xid, key) = match text.split_once('|') {
+            Some((xid, key)) if xid.parse::<i64>().is_ok() => (xid.parse().ok(), key),
+            _ => (None, text),
+        };
+        let (created_at, id) = <(DateTime<Utc>, Uuid)>::from_text(key)?;
+        Some(Cursor { xid, created_at, id })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PollQuery {
+    pub since_cursor: Option<String>, // Omit to start from the oldest record
+    pub limit: Option<i64>,
+}
+
+impl PollQuery {
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_POLL_LIMIT).clamp(1, MAX_POLL_LIMIT)
+    }
+
+    fn position(&self) -> Result<Option<Cursor>, (StatusCode, String)> {
+        pagination::after(self.since_cursor.as_deref())
+    }
+}
+
+// Records oldest first; pass next_cursor back as since_cursor to get only newer ones.
+// With nothing new, next_cursor repeats the cursor that was sent
+#[derive(Serialize)]
+pub struct PollPage<T> {
+    pub data: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+// Both feeds page on (poll_xid, created_at, id) and leave out rows whose inserting transaction
+// isn't older than every one still running: a row that commits later could still land behind
+// them, and the next poll would skip it
+async fn poll_orders(
+    key: ApiKeyAuth,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<PollQuery>,
+) -> Result<Json<PollPage<serde_json::Value>>, (StatusCode, String)> {
+    key.require(SCOPE_READ_ORDERS)?;
+    let limit = query.limit();
+    let position = query.position()?;
+    let mut orders = order_query!(
+        "SELECT",
+        "FROM orders
+         WHERE livemode = $5 AND poll_xid < pg_snapshot_xmin(pg_current_snapshot())
+           AND ($2::TIMESTAMPTZ IS NULL
+                OR (poll_xid, created_at, id) >
+                   (COALESCE($1::BIGINT::TEXT::XID8, (SELECT MIN(poll_xid) FROM orders)), $2, $3))
+         ORDER BY poll_xid, created_at, id
+         LIMIT $4",
+        position.as_ref().and_then(|p| p.xid),
+        position.as_ref().map(|p| p.created_at),
+        position.as_ref().map(|p| p.id),
+        limit + 1,
+        app_state.mode.is_live(),
+    ) // ERPs only ever see the server's own mode
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    let has_more = orders.len() as i64 > limit;
+    orders.truncate(limit as usize);
+    let next_cursor = match orders.last() {
+        Some(last) => {
+            // The id of the transaction that inserted the row; it never changes afterwards
+            let xid = sqlx::query_scalar!(r#"SELECT poll_xid::TEXT::BIGINT AS "xid!" FROM orders WHERE id = $1"#, last.id)
+                .fetch_one(&*app_state.pool)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+            Some(pagination::encode_cursor(&Cursor { xid: Some(xid), created_at: last.created_at, id: last.id }))
+        }
+        None => query.since_cursor.clone(),
+    };
+    let data = order_payloads(&app_state.pool, orders)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(PollPage { data, next_cursor, has_more }))
+}
+
+async fn poll_customers(
+    key: ApiKeyAuth,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<PollQuery>,
+) -> Result<Json<PollPage<Customer>>, (StatusCode, String)> {
+    key.require(SCOPE_READ_CUSTOMERS)?;
+    let limit = query.limit();
+    let position = query.position()?;
+    let mut customers = customer_query!(
+        "SELECT",
+        "FROM customers
+         WHERE created_at IS NOT NULL AND poll_xid < pg_snapshot_xmin(pg_current_snapshot())
+           AND ($2::TIMESTAMPTZ IS NULL
+                OR (poll_xid, created_at, id) >
+                   (COALESCE($1::BIGINT::TEXT::XID8, (SELECT MIN(poll_xid) FROM customers)), $2, $3))
+         ORDER BY poll_xid, created_at, id
+         LIMIT $4",
+        position.as_ref().and_then(|p| p.xid),
+        position.as_ref().map(|p| p.created_at),
+        position.as_ref().map(|p| p.id),
+        limit + 1,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    let has_more = customers.len() as i64 > limit;
+    customers.truncate(limit as usize);
+    let next_cursor = match customers.last() {
+        Some(Customer { created_at: Some(created_at), id, .. }) => {
+            let xid = sqlx::query_scalar!(r#"SELECT poll_xid::TEXT::BIGINT AS "xid!" FROM customers WHERE id = $1"#, id)
+                .fetch_one(&*app_state.pool)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+            Some(pagination::encode_cursor(&Cursor { xid: Some(xid), created_at: *created_at, id: *id }))
+        }
+        _ => query.since_cursor.clone(),
+    };
+    Ok(Json(PollPage { data: customers, next_cursor, has_more }))
+}
+
+// --- Outbound order webhook ---
+
+// Queues an ERP webhook for every new order when ERP_WEBHOOK_URL is set
+pub struct ErpWebhookSubscriber {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl ErpWebhookSubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for ErpWebhookSubscriber {
+    fn name(&self) -> &'static str {
+        "erp_webhook"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let DomainEvent::OrderCreated { order_id, .. } = event else {
+            return Ok(());
+        };
+        if app_config::get().integrations.erp_webhook_url.is_none() {
+            return Ok(());
+        }
+        jobs::enqueue(&self.pool, ERP_ORDER_WEBHOOK_JOB, json!({ "order_id": order_id }), Utc::now())
+            .await
+            .map_err(|e| format!("Failed to queue ERP webhook for order {}: {}", order_id, e))?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct ErpWebhookPayload {
+    order_id: Uuid,
+}
+
+// Order with its items and SKUs, as sent to the ERP and to merchant webhooks
+pub async fn order_payload(pool: &sqlx::PgPool, order_id: Uuid) -> Result<serde_json::Value, String> {
+    let order = order_query!("SELECT", "FROM orders WHERE id = $1", order_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?
+        .ok_or_else(|| format!("Order {} not found", order_id))?;
+    Ok(order_payloads(pool, vec![order]).await?.remove(0))
+}
+
+// Same shape for a batch of orders, loading items and SKUs in one query each
+async fn order_payloads(pool: &sqlx::PgPool, orders: Vec<Order>) -> Result<Vec<serde_json::Value>, String> {
+    let items = sqlx::query_as!(
+        OrderItem,
+        r#"SELECT id, order_id, product_id, product_name, product_description, quantity, unit_price, total_price, created_at AS "created_at!" FROM order_items WHERE order_id = ANY($1) ORDER BY created_at, id"#,
+        &orders.iter().map(|order| order.id).collect::<Vec<Uuid>>(),
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))?;
+    let skus: HashMap<i32, String> = sqlx::query!(
+        r#"SELECT id, sku AS "sku!" FROM products WHERE id = ANY($1) AND sku IS NOT NULL"#,
+        &items.iter().filter_map(|item| item.product_id).collect::<Vec<i32>>(),
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))?
+    .into_iter()
+    .map(|row| (row.id, row.sku))
+    .collect();
+
+    Ok(orders
+        .iter()
+        .map(|order| {
+            json!({
+                "id": order.id,
+                "status": order.status,
+                "total_amount": order.total_amount,
+                "currency": order.currency,
+                "customer_email": order.customer_email,
+                "customer_name": order.customer_name,
+                "created_at": order.created_at,
+                "items": items.iter().filter(|item| item.order_id == order.id).map(|item| json!({
+                    "product_id": item.product_id,
+                    "sku": item.product_id.and_then(|id| skus.get(&id)),
+                    "name": item.product_name,
+                    "quantity": item.quantity,
+                    "unit_price": item.unit_price,
+                    "total_price": item.total_price,
+                })).collect::<Vec<_>>(),
+            })
+        })
+        .collect())
+}
+
+// "sha256=" + hex(HMAC-SHA256(body, secret)), sent as X-RCom-Signature
+pub fn sign_body(secret: &str, body: &str) -> Result<String, String> {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).map_err(|e| format!("HMAC error: {}", e))?;
+    mac.update(body.as_bytes());
+    Ok(format!("sha256={}", hex::encode(mac.finalize().into_bytes())))
+}
+
+// Job handler: POST the order to the ERP, signed with ERP_WEBHOOK_SECRET
+pub async fn run_erp_order_webhook(pool: &sqlx::PgPool, payload: &serde_json::Value) -> Result<(), String> {
+    let payload: ErpWebhookPayload =
+        serde_json::from_value(payload.clone()).map_err(|e| format!("Invalid ERP webhook payload: {}", e))?;
+    let integrations = &app_config::get().integrations;
+    let url = integrations.erp_webhook_url.as_ref().ok_or("ERP_WEBHOOK_URL not configured")?;
+
+    let body = json!({
+        "event": "order.created",
+        "order": order_payload(pool, payload.order_id).await?,
+    })
+    .to_string();
+
+    let mut request = reqwest::Client::new()
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(body.clone());
+    if let Some(secret) = &integrations.erp_webhook_secret {
+        request = request.header("X-RCom-Signature", sign_body(secret.expose(), &body)?);
+    }
+
+    let response = request.with_request_id().send().await.map_err(|e| format!("ERP webhook request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("ERP webhook returned {}", response.status()));
+    }
+    tracing::info!("Sent order {} to ERP", payload.order_id);
+    Ok(())
+}
@@ -0,0 +1,160 @@
+// Job Alerts Module - Operational alerts for the background job queue
+// A periodic check alerts a Slack/Discord webhook and/or an admin email address when the dead-letter queue
+// grows past a threshold or too many outbound webhook deliveries are failing
+
+use std::sync::Arc;
+
+use crate::app_config;
+use crate::branding::{escape_html, load_branding};
+use crate::lettre_email::EmailConfig;
+use crate::notifications::ops::post_webhook_message;
+
+const WEBHOOK_MIN_DELIVERIES: i64 = 10; // Don't judge the rate on a handful of deliveries
+const ALERT_COOLDOWN_MINUTES: i32 = 60; // A condition that persists is re-alerted at most this often
+
+const ALERT_DEAD_LETTER_DEPTH: &str = "dead_letter_depth";
+const ALERT_WEBHOOK_FAILURE_RATE: &str = "webhook_failure_rate";
+
+// Where alerts go; either destination may be left unset
+struct AlertConfig {
+    slack_webhook_url: Option<String>,
+    email: Option<String>,
+    dead_letter_threshold: i64,
+    webhook_failure_rate: f64,
+}
+
+impl AlertConfig {
+    fn from_env() -> Self {
+        let alerts = &app_config::get().alerts;
+        Self {
+            slack_webhook_url: alerts.slack_webhook_url.as_ref().map(|url| url.expose().to_string()),
+            email: alerts.email.clone(),
+            dead_letter_threshold: alerts.dead_letter_threshold,
+            webhook_failure_rate: alerts.webhook_failure_rate,
+        }
+    }
+}
+
+// Record that an alert is about to be sent; false if it already went out within the cooldown
+async fn claim_alert(pool: &sqlx::PgPool, alert: &str, value: f64) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query!(
+        "INSERT INTO job_alerts (alert, value) VALUES ($1, $2)
+         ON CONFLICT (alert) DO UPDATE SET value = EXCLUDED.value, sent_at = NOW()
+         WHERE job_alerts.sent_at < NOW() - make_interval(mins => $3)",
+        alert,
+        value,
+        ALERT_COOLDOWN_MINUTES,
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+// Sent directly rather than through the job queue, which may be what's failing
+pub(crate) async fn send_email_alert(pool: &sqlx::PgPool, to: &str, subject: &str, text: &str) -> Result<(), String> {
+    let config = EmailConfig::from_env().ok_or("Email not configured")?;
+    let html_body = load_branding(pool)
+        .await
+        .email_html(subject, &format!("<p>{}</p>", escape_html(text)));
+    crate::webhooks::stripe::send_html_email(pool, &config, to, subject, &html_body).await
+}
+
+async fn send_alert(pool: &sqlx::PgPool, config: &AlertConfig, subject: &str, text: &str) {
+    tracing::warn!("{}: {}", subject, text);
+    if let Some(url) = &config.slack_webhook_url {
+        if let Err(e) = post_webhook_message(url, &format!("*{}*\n{}", subject, text)).await {
+            tracing::error!("Failed to send Slack alert: {}", e);
+        }
+    }
+    if let Some(email) = &config.email {
+        if let Err(e) = send_email_alert(pool, email, subject, text).await {
+            tracing::error!("Failed to send alert email to {}: {}", email, e);
+        }
+    }
+}
+
+async fn check_dead_letter_depth(pool: &sqlx::PgPool, config: &AlertConfig) -> Result<(), sqlx::Error> {
+    let depth: i64 = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "depth!" FROM jobs WHERE status = 'dead'"#)
+        .fetch_one(pool)
+        .await?;
+    if depth < config.dead_letter_threshold || !claim_alert(pool, ALERT_DEAD_LETTER_DEPTH, depth as f64).await? {
+        return Ok(());
+    }
+
+    let by_type: Vec<(String, i64)> = sqlx::query!(
+        r#"SELECT job_type, COUNT(*) AS "count!" FROM jobs WHERE status = 'dead' GROUP BY job_type ORDER BY COUNT(*) DESC"#,
+    )
+    .map(|r| (r.job_type, r.count))
+    .fetch_all(pool)
+    .await?;
+    let breakdown = by_type
+        .iter()
+        .map(|(job_type, count)| format!("{} {}", count, job_type))
+        .collect::<Vec<_>>()
+        .join(", ");
+    send_alert(
+        pool,
+        config,
+        "Dead-letter queue is growing",
+        &format!(
+            "{} background jobs have failed permanently ({}), threshold {}. Inspect and requeue them at /api/admin/jobs/dead.",
+            depth, breakdown, config.dead_letter_threshold
+        ),
+    )
+    .await;
+    Ok(())
+}
+
+// Share of the last hour's outbound webhook deliveries (excluding pings) whose latest attempt failed
+async fn check_webhook_failure_rate(pool: &sqlx::PgPool, config: &AlertConfig) -> Result<(), sqlx::Error> {
+    let (attempted, failed): (i64, i64) = sqlx::query!(
+        r#"SELECT COUNT(*) AS "attempted!", COUNT(*) FILTER (WHERE status = 'failed') AS "failed!" FROM webhook_deliveries
+         WHERE event_type <> 'ping' AND attempts > 0 AND created_at > NOW() - INTERVAL '1 hour'"#,
+    )
+    .map(|r| (r.attempted, r.failed))
+    .fetch_one(pool)
+    .await?;
+    if attempted < WEBHOOK_MIN_DELIVERIES {
+        return Ok(());
+    }
+    let rate = failed as f64 / attempted as f64;
+    if rate < config.webhook_failure_rate || !claim_alert(pool, ALERT_WEBHOOK_FAILURE_RATE, rate).await? {
+        return Ok(());
+    }
+    send_alert(
+        pool,
+        config,
+        "Outbound webhook failures",
+        &format!(
+            "{} of {} webhook deliveries in the last hour are failing ({:.0}%, threshold {:.0}%).",
+            failed,
+            attempted,
+            rate * 100.0,
+            config.webhook_failure_rate * 100.0
+        ),
+    )
+    .await;
+    Ok(())
+}
+
+// Periodically check queue health (every ALERT_CHECK_INTERVAL_SECS); does nothing if no destination is set
+pub fn spawn_job_alert_monitor(pool: Arc<sqlx::PgPool>) {
+    let config = AlertConfig::from_env();
+    if config.slack_webhook_url.is_none() && config.email.is_none() {
+        return;
+    }
+    let interval_secs = app_config::get().schedules.alert_check_interval_secs;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            if let Err(e) = check_dead_letter_depth(&pool, &config).await {
+                tracing::error!("Failed to check dead-letter queue: {}", e);
+            }
+            if let Err(e) = check_webhook_failure_rate(&pool, &config).await {
+                tracing::error!("Failed to check webhook failure rate: {}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,321 @@
+// Jobs Module - Database-backed background job queue
+// Work is enqueued with a run_at time and picked up by a polling worker; failed
+// jobs are retried with exponential backoff until max_attempts is reached, then
+// move to the dead-letter queue where admins can inspect and requeue them
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::{analytics_forwarding, dropship, integrations, marketing_automation, meta_conversions, notifications, outbound_webhooks, AppState};
+
+const POLL_INTERVAL_SECS: u64 = 5;
+const BATCH_SIZE: i64 = 10;
+const STALE_LOCK_MINUTES: i32 = 15; // Running jobs older than this are assumed lost
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+// Database model for queued jobs
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Job {
+    pub id: Uuid,
+    pub job_type: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub run_at: DateTime<Utc>,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub last_error: Option<String>,
+    pub locked_at: Option<DateTime<Utc>>,
+    pub dead_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct DeadJobQuery {
+    pub job_type: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct DeadJobSummary {
+    pub job_type: String,
+    pub count: i64,
+    pub oldest_dead_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct RequeueResult {
+    pub requeued: u64,
+}
+
+// What a handler did with a job it ran without error
+pub enum JobOutcome {
+    Done,
+    // Not run yet (e.g. a provider rate limit); retried at this time without using an attempt
+    Deferred(DateTime<Utc>),
+}
+
+// Queue a job to run at (or after) run_at
+pub async fn enqueue(
+    pool: &sqlx::PgPool,
+    job_type: &str,
+    payload: serde_json::Value,
+    run_at: DateTime<Utc>,
+) -> Result<Uuid, sqlx::Error> {
+    sqlx::query_scalar!(
+        "INSERT INTO jobs (job_type, payload, run_at) VALUES ($1, $2, $3) RETURNING id",
+        job_type,
+        payload,
+        run_at,
+    )
+    .fetch_one(pool)
+    .await
+}
+
+// Route a job to its handler by type
+async fn run_job(pool: &sqlx::PgPool, job: &Job) -> Result<JobOutcome, String> {
+    match job.job_type.as_str() {
+        marketing_automation::MARKETING_EMAIL_JOB => marketing_automation::run_marketing_email(pool, &job.payload).await.map(|_| JobOutcome::Done),
+        integrations::ERP_ORDER_WEBHOOK_JOB => integrations::run_erp_order_webhook(pool, &job.payload).await.map(|_| JobOutcome::Done),
+        outbound_webhooks::WEBHOOK_DELIVERY_JOB => outbound_webhooks::run_webhook_delivery(pool, &job.payload).await.map(|_| JobOutcome::Done),
+        notifications::NOTIFICATION_EMAIL_JOB => notifications::run_notification_email(pool, &job.payload).await,
+        notifications::NOTIFICATION_SMS_JOB => notifications::run_notification_sms(pool, &job.payload).await,
+        analytics_forwarding::ANALYTICS_EVENT_JOB => analytics_forwarding::run_analytics_event(pool, &job.payload).await.map(|_| JobOutcome::Done),
+        meta_conversions::META_EVENT_JOB => meta_conversions::run_meta_event(pool, &job.payload).await.map(|_| JobOutcome::Done),
+        dropship::VENDOR_PURCHASE_ORDER_JOB => dropship::run_vendor_purchase_order(pool, &job.payload).await.map(|_| JobOutcome::Done),
+        other => Err(format!("Unknown job type '{}'", other)),
+    }
+}
+
+// Claim due jobs so concurrent workers never run the same job twice
+async fn claim_due_jobs(pool: &sqlx::PgPool) -> Result<Vec<Job>, sqlx::Error> {
+    sqlx::query!(
+        "UPDATE jobs SET status = 'pending', locked_at = NULL, updated_at = NOW()
+         WHERE status = 'running' AND locked_at < NOW() - make_interval(mins => $1)",
+        STALE_LOCK_MINUTES,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query_as!(
+        Job,
+        "UPDATE jobs SET status = 'running', locked_at = NOW(), attempts = attempts + 1, updated_at = NOW()
+         WHERE id IN (
+             SELECT id FROM jobs WHERE status = 'pending' AND run_at <= NOW()
+             ORDER BY run_at LIMIT $1 FOR UPDATE SKIP LOCKED
+         )
+         RETURNING id, job_type, payload, status, run_at, attempts, max_attempts, last_error, locked_at, dead_at, created_at, updated_at",
+        BATCH_SIZE,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+async fn finish_job(pool: &sqlx::PgPool, job: &Job, result: Result<JobOutcome, String>) -> Result<(), sqlx::Error> {
+    match result {
+        Ok(JobOutcome::Deferred(run_at)) => {
+            sqlx::query!(
+                "UPDATE jobs SET status = 'pending', locked_at = NULL, attempts = attempts - 1, run_at = $1, updated_at = NOW()
+                 WHERE id = $2",
+                run_at,
+                job.id,
+            )
+            .execute(pool)
+            .await?;
+        }
+        Ok(JobOutcome::Done) => {
+            sqlx::query!(
+                "UPDATE jobs SET status = 'completed', locked_at = NULL, last_error = NULL, updated_at = NOW() WHERE id = $1",
+                job.id,
+            )
+            .execute(pool)
+            .await?;
+        }
+        Err(e) if job.attempts < job.max_attempts => {
+            // Back off 1, 2, 4, 8... minutes between attempts
+            let delay_minutes = 1i32 << (job.attempts - 1).clamp(0, 10);
+            tracing::error!("Job {} ({}) failed, retrying in {} min: {}", job.id, job.job_type, delay_minutes, e);
+            sqlx::query!(
+                "UPDATE jobs SET status = 'pending', locked_at = NULL, last_error = $1,
+                     run_at = NOW() + make_interval(mins => $2), updated_at = NOW()
+                 WHERE id = $3",
+                &e,
+                delay_minutes,
+                job.id,
+            )
+            .execute(pool)
+            .await?;
+        }
+        Err(e) => {
+            tracing::error!("Job {} ({}) failed permanently, moved to dead-letter queue: {}", job.id, job.job_type, e);
+            sqlx::query!(
+                "UPDATE jobs SET status = 'dead', locked_at = NULL, last_error = $1, dead_at = NOW(), updated_at = NOW()
+                 WHERE id = $2",
+                &e,
+                job.id,
+            )
+            .execute(pool)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+// Background worker polling the queue for due jobs
+pub fn spawn_job_worker(pool: Arc<sqlx::PgPool>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(POLL_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let jobs = match claim_due_jobs(&pool).await {
+                Ok(jobs) => jobs,
+                Err(e) => {
+                    tracing::error!("Failed to claim jobs: {}", e);
+                    continue;
+                }
+            };
+            for job in &jobs {
+                let result = run_job(&pool, job).await;
+                if let Err(e) = finish_job(&pool, job, result).await {
+                    tracing::error!("Failed to record result of job {}: {}", job.id, e);
+                }
+            }
+        }
+    });
+}
+
+pub fn job_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/jobs/dead", get(list_dead_jobs))
+        .route("/api/admin/jobs/dead/summary", get(dead_job_summary))
+        .route("/api/admin/jobs/dead/requeue", post(requeue_dead_jobs))
+        .route("/api/admin/jobs/:id", get(get_job).delete(discard_dead_job))
+        .route("/api/admin/jobs/:id/requeue", post(requeue_job))
+        .with_state(app_state)
+}
+
+// Dead jobs, most recent first, optionally of one type
+async fn list_dead_jobs(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<DeadJobQuery>,
+) -> Result<Json<Vec<Job>>, (StatusCode, String)> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let jobs = sqlx::query_as!(
+        Job,
+        "SELECT id, job_type, payload, status, run_at, attempts, max_attempts, last_error, locked_at, dead_at, created_at, updated_at FROM jobs
+         WHERE status = 'dead' AND ($1::TEXT IS NULL OR job_type = $1)
+         ORDER BY dead_at DESC, id
+         LIMIT $2 OFFSET $3",
+        query.job_type.as_deref(),
+        limit,
+        offset,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(jobs))
+}
+
+// Dead-letter queue depth per job type
+async fn dead_job_summary(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<DeadJobSummary>>, (StatusCode, String)> {
+    let rows: Vec<(String, i64, Option<DateTime<Utc>>)> = sqlx::query!(
+        r#"SELECT job_type, COUNT(*) AS "count!", MIN(dead_at) AS oldest FROM jobs WHERE status = 'dead' GROUP BY job_type ORDER BY job_type"#,
+    )
+    .map(|r| (r.job_type, r.count, r.oldest))
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(
+        rows.into_iter()
+            .map(|(job_type, count, oldest_dead_at)| DeadJobSummary {
+                job_type,
+                count,
+                oldest_dead_at,
+            })
+            .collect(),
+    ))
+}
+
+async fn get_job(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Job>, (StatusCode, String)> {
+    let job = sqlx::query_as!(Job, "SELECT id, job_type, payload, status, run_at, attempts, max_attempts, last_error, locked_at, dead_at, created_at, updated_at FROM jobs WHERE id = $1", id)
+        .fetch_optional(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Job not found".to_string()))?;
+    Ok(Json(job))
+}
+
+// Run a dead job again now with a fresh set of attempts; last_error is kept until it succeeds
+async fn requeue_job(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Job>, (StatusCode, String)> {
+    let job = sqlx::query_as!(
+        Job,
+        "UPDATE jobs SET status = 'pending', attempts = 0, run_at = NOW(), dead_at = NULL, updated_at = NOW()
+         WHERE id = $1 AND status = 'dead'
+         RETURNING id, job_type, payload, status, run_at, attempts, max_attempts, last_error, locked_at, dead_at, created_at, updated_at",
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Dead job not found".to_string()))?;
+    Ok(Json(job))
+}
+
+// Requeue every dead job, or only those of ?job_type= (e.g. after fixing a provider outage)
+async fn requeue_dead_jobs(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<DeadJobQuery>,
+) -> Result<Json<RequeueResult>, (StatusCode, String)> {
+    let res = sqlx::query!(
+        "UPDATE jobs SET status = 'pending', attempts = 0, run_at = NOW(), dead_at = NULL, updated_at = NOW()
+         WHERE status = 'dead' AND ($1::TEXT IS NULL OR job_type = $1)",
+        query.job_type.as_deref(),
+    )
+    .execute(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(RequeueResult {
+        requeued: res.rows_affected(),
+    }))
+}
+
+// Drop a dead job that should never run
+async fn discard_dead_job(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM jobs WHERE id = $1 AND status = 'dead'", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Dead job not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,276 @@
+// JWT Keys Module - Rotating signing keys for admin and customer tokens
+// Tokens carry the id of the key that signed them (kid) in their header and are accepted while
+// that key isn't retired, so rotating the key doesn't log anyone out. New tokens are signed with
+// the newest active key. JWT_SECRET stays valid as key "env" (and for tokens issued without a kid)
+// until it is retired. Every instance reloads the keys from the database each JWT_KEY_REFRESH_SECS.
+// Secrets are stored encrypted like PII, so reading the database isn't enough to forge a token
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{delete, get, post},
+    Json, Router,
+};
+use jsonwebtoken::{decode, decode_header, encode, DecodingKey, EncodingKey, Header, Validation};
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::sync::{Arc, RwLock};
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::pii::Encrypted;
+use crate::AppState;
+
+pub const ENV_KID: &str = "env";
+
+#[derive(Clone, sqlx::FromRow)]
+struct SigningKey {
+    kid: String,
+    secret: Encrypted,
+    activates_at: DateTime<Utc>,
+}
+
+// Keys loaded from the database; JWT_SECRET is read from the config when needed
+struct Keyring {
+    keys: Vec<SigningKey>, // Unretired, newest activation first
+    env_retired: bool,
+}
+
+static KEYRING: RwLock<Keyring> = RwLock::new(Keyring {
+    keys: Vec::new(),
+    env_retired: false,
+});
+
+#[derive(Debug, Serialize)]
+pub struct JwtKeyInfo {
+    pub kid: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub activates_at: Option<DateTime<Utc>>,
+    pub retired_at: Option<DateTime<Utc>>,
+    pub signing: bool, // Signs new tokens on this instance
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RotateQuery {
+    #[serde(default)]
+    pub immediate: bool, // Sign with the new key right away instead of after JWT_KEY_REFRESH_SECS
+}
+
+fn env_secret() -> &'static str {
+    app_config::get().auth.jwt_secret.expose()
+}
+
+// The key new tokens are signed with: the newest activated key, else JWT_SECRET
+fn signing_key() -> (String, String) {
+    let keyring = KEYRING.read().unwrap();
+    let now = Utc::now();
+    keyring
+        .keys
+        .iter()
+        .find(|key| key.activates_at <= now)
+        .map(|key| (key.kid.clone(), key.secret.to_string()))
+        .unwrap_or_else(|| (ENV_KID.to_string(), env_secret().to_string()))
+}
+
+// The secret for a token's kid; tokens from before rotation support have none and use JWT_SECRET
+fn verification_secret(kid: Option<&str>) -> Option<String> {
+    let keyring = KEYRING.read().unwrap();
+    match kid {
+        None | Some(ENV_KID) => (!keyring.env_retired).then(|| env_secret().to_string()),
+        Some(kid) => keyring.keys.iter().find(|key| key.kid == kid).map(|key| key.secret.to_string()),
+    }
+}
+
+// Sign claims with the current key; `derive` turns the key's secret into the HMAC key
+pub fn sign<T: Serialize>(claims: &T, derive: fn(&str) -> Vec<u8>) -> Result<String, jsonwebtoken::errors::Error> {
+    let (kid, secret) = signing_key();
+    let header = Header {
+        kid: Some(kid),
+        ..Header::default()
+    };
+    encode(&header, claims, &EncodingKey::from_secret(&derive(&secret)))
+}
+
+// Check a token against the key named in its header
+pub fn verify<T: DeserializeOwned>(token: &str, derive: fn(&str) -> Vec<u8>) -> Result<T, jsonwebtoken::errors::Error> {
+    let header = decode_header(token)?;
+    let secret = verification_secret(header.kid.as_deref())
+        .ok_or(jsonwebtoken::errors::ErrorKind::InvalidToken)?;
+    decode::<T>(token, &DecodingKey::from_secret(&derive(&secret)), &Validation::default()).map(|data| data.claims)
+}
+
+// Reload the unretired keys from the database
+pub async fn load_keys(pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let keys = sqlx::query_as!(
+        SigningKey,
+        r#"SELECT kid, secret AS "secret: Encrypted", activates_at FROM jwt_signing_keys
+         WHERE retired_at IS NULL AND kid <> $1
+         ORDER BY activates_at DESC"#,
+        ENV_KID,
+    )
+    .fetch_all(pool)
+    .await?;
+    let env_retired: bool = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM jwt_signing_keys WHERE kid = $1 AND retired_at IS NOT NULL) AS "env_retired!""#,
+        ENV_KID,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let mut keyring = KEYRING.write().unwrap();
+    keyring.keys = keys;
+    keyring.env_retired = env_retired;
+    Ok(())
+}
+
+// Pick up keys rotated or retired by other instances (every JWT_KEY_REFRESH_SECS)
+pub fn spawn_key_refresh(pool: Arc<sqlx::PgPool>) {
+    let interval_secs = app_config::get().schedules.jwt_key_refresh_secs;
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            if let Err(e) = load_keys(&pool).await {
+                tracing::error!("Failed to reload JWT signing keys: {}", e);
+            }
+        }
+    });
+}
+
+pub fn jwt_key_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/auth/keys", get(list_keys))
+        .route("/api/admin/auth/keys/rotate", post(rotate_key))
+        .route("/api/admin/auth/keys/:kid", delete(retire_key))
+        .with_state(app_state)
+}
+
+fn db_err(e: sqlx::Error) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e))
+}
+
+async fn list_keys(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<JwtKeyInfo>>, (StatusCode, String)> {
+    list(&app_state.pool).await.map(Json).map_err(db_err)
+}
+
+// Every key, newest first, with "env" for JWT_SECRET; secrets are never returned
+pub async fn list(pool: &sqlx::PgPool) -> Result<Vec<JwtKeyInfo>, sqlx::Error> {
+    let mut keys = sqlx::query_as!(
+        JwtKeyInfo,
+        r#"SELECT kid, created_at AS "created_at?", activates_at AS "activates_at?", retired_at, FALSE AS "signing!"
+         FROM jwt_signing_keys
+         WHERE kid <> $1 ORDER BY activates_at DESC"#,
+        ENV_KID,
+    )
+    .fetch_all(pool)
+    .await?;
+    let env_retired_at: Option<DateTime<Utc>> =
+        sqlx::query_scalar!("SELECT retired_at FROM jwt_signing_keys WHERE kid = $1", ENV_KID)
+            .fetch_optional(pool)
+            .await?
+            .flatten();
+    keys.push(JwtKeyInfo {
+        kid: ENV_KID.to_string(),
+        created_at: None,
+        activates_at: None,
+        retired_at: env_retired_at,
+        signing: false,
+    });
+
+    let (signing_kid, _) = signing_key();
+    for key in &mut keys {
+        key.signing = key.kid == signing_kid;
+    }
+    Ok(keys)
+}
+
+async fn rotate_key(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<RotateQuery>,
+) -> Result<(StatusCode, Json<JwtKeyInfo>), (StatusCode, String)> {
+    let key = rotate(&app_state.pool, query.immediate).await.map_err(db_err)?;
+    Ok((StatusCode::CREATED, Json(key)))
+}
+
+// Add a new signing key. It is accepted at once but only signs after JWT_KEY_REFRESH_SECS,
+// so every instance knows it before tokens signed with it show up
+pub async fn rotate(pool: &sqlx::PgPool, immediate: bool) -> Result<JwtKeyInfo, sqlx::Error> {
+    let kid = hex::encode(rand::thread_rng().gen::<[u8; 8]>());
+    let secret = hex::encode(rand::thread_rng().gen::<[u8; 32]>());
+    let delay_secs = if immediate {
+        0
+    } else {
+        app_config::get().schedules.jwt_key_refresh_secs as i32
+    };
+    let mut key = sqlx::query_as!(
+        JwtKeyInfo,
+        r#"INSERT INTO jwt_signing_keys (kid, secret, activates_at)
+         VALUES ($1, $2, NOW() + make_interval(secs => $3))
+         RETURNING kid, created_at AS "created_at?", activates_at AS "activates_at?", retired_at, FALSE AS "signing!""#,
+        &kid,
+        Encrypted(secret) as _,
+        f64::from(delay_secs),
+    )
+    .fetch_one(pool)
+    .await?;
+    load_keys(pool).await?;
+    key.signing = signing_key().0 == kid;
+
+    tracing::info!("Rotated JWT signing key, new key {} signs from {:?}", kid, key.activates_at);
+    Ok(key)
+}
+
+async fn retire_key(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(kid): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    retire(&app_state.pool, &kid).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Stop accepting a key; tokens it signed are rejected from then on (other instances follow
+// within JWT_KEY_REFRESH_SECS). Another active key must be left to sign with
+pub async fn retire(pool: &sqlx::PgPool, kid: &str) -> Result<(), (StatusCode, String)> {
+    load_keys(pool).await.map_err(db_err)?;
+    let remaining_signer = {
+        let keyring = KEYRING.read().unwrap();
+        let now = Utc::now();
+        let known = if kid == ENV_KID {
+            !keyring.env_retired
+        } else {
+            keyring.keys.iter().any(|key| key.kid == kid)
+        };
+        if !known {
+            return Err((StatusCode::NOT_FOUND, "No active key with that id".to_string()));
+        }
+        keyring.keys.iter().any(|key| key.kid != kid && key.activates_at <= now)
+            || (kid != ENV_KID && !keyring.env_retired)
+    };
+    if !remaining_signer {
+        return Err((
+            StatusCode::CONFLICT,
+            "This is the only key that can sign tokens; rotate and wait for the new key to activate first".to_string(),
+        ));
+    }
+
+    // JWT_SECRET isn't stored, so retiring it records a row without a secret
+    sqlx::query!(
+        "INSERT INTO jwt_signing_keys (kid, secret, retired_at) VALUES ($1, '', NOW())
+         ON CONFLICT (kid) DO UPDATE SET retired_at = NOW()",
+        kid,
+    )
+    .execute(pool)
+    .await
+    .map_err(db_err)?;
+    load_keys(pool).await.map_err(db_err)?;
+
+    tracing::info!("Retired JWT signing key {}", kid);
+    Ok(())
+}
@@ -0,0 +1,86 @@
+// Letre Marketing Integration
+// Minimal client for Letre subscriber lists, used to sync customer segments
+// API base URL and key come from LETRE_API_URL / LETRE_API_KEY
+
+use reqwest::Client;
+use serde_json::json;
+
+use crate::app_config;
+use crate::request_id::WithRequestId;
+
+#[derive(Clone, Debug)]
+pub struct LetreConfig {
+    pub api_key: String,
+    pub api_url: String,
+}
+
+impl LetreConfig {
+    pub fn from_env() -> Option<Self> {
+        let letre = &app_config::get().letre;
+        Some(Self {
+            api_key: letre.api_key.as_ref()?.expose().to_string(),
+            api_url: letre.api_url.clone(),
+        })
+    }
+}
+
+pub struct LetreClient {
+    config: LetreConfig,
+    client: Client,
+}
+
+impl LetreClient {
+    pub fn new(config: LetreConfig) -> Self {
+        Self {
+            config,
+            client: Client::new(),
+        }
+    }
+
+    /// Add (or re-activate) a subscriber on a Letre list
+    pub async fn add_subscriber(&self, list_id: &str, email: &str, name: Option<&str>) -> Result<(), String> {
+        let url = format!("{}/v1/lists/{}/subscribers", self.config.api_url, list_id);
+        let response = self
+            .client
+            .post(&url)
+            .bearer_auth(&self.config.api_key)
+            .json(&json!({ "email": email, "name": name }))
+            .with_request_id()
+            .send()
+            .await
+            .map_err(|e| format!("Failed to add subscriber to Letre: {}", e))?;
+
+        let status = response.status();
+        if status.is_success() || status.as_u16() == 409 {
+            Ok(())
+        } else {
+            let body = response.text().await.unwrap_or_default();
+            Err(format!("Letre API returned error status {}: {}", status, body))
+        }
+    }
+
+    /// Remove a subscriber from a Letre list
+    pub async fn remove_subscriber(&self, list_id: &str, email: &str) -> Result<(), String> {
+        let mut url = reqwest::Url::parse(&format!("{}/v1/lists/{}/subscribers", self.config.api_url, list_id))
+            .map_err(|e| format!("Invalid LETRE_API_URL: {}", e))?;
+        url.path_segments_mut()
+            .map_err(|_| "Invalid LETRE_API_URL".to_string())?
+            .push(email); // Percent-encodes characters like '+' in the address
+        let response = self
+            .client
+            .delete(url)
+            .bearer_auth(&self.config.api_key)
+            .with_request_id()
+            .send()
+            .await
+            .map_err(|e| format!("Failed to remove subscriber from Letre: {}", e))?;
+
+        let status = response.status();
+        if status.is_success() || status.as_u16() == 404 {
+            Ok(())
+        } else {
+            let body = response.text().await.unwrap_or_default();
+            Err(format!("Letre API returned error status {}: {}", status, body))
+        }
+    }
+}
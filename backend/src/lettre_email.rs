@@ -5,7 +5,9 @@
 use axum::{Json, Router, routing::post, extract::State, http::StatusCode};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use crate::app_config;
 use crate::AppState;
+use crate::branding::{escape_html, load_branding};
 use lettre::{
     Message, SmtpTransport, Transport,
     message::{header::ContentType, Mailbox},
@@ -24,13 +26,14 @@ pub struct EmailConfig {
 
 impl EmailConfig {
     pub fn from_env() -> Option<Self> {
+        let smtp = &app_config::get().smtp;
         Some(Self {
-            smtp_host: std::env::var("SMTP_HOST").ok()?,
-            smtp_port: std::env::var("SMTP_PORT").ok()?.parse().ok()?,
-            smtp_username: std::env::var("SMTP_USERNAME").ok()?,
-            smtp_password: std::env::var("SMTP_PASSWORD").ok()?,
-            from_email: std::env::var("FROM_EMAIL").ok()?,
-            from_name: std::env::var("FROM_NAME").unwrap_or_else(|_| "R-Com Store".to_string()),
+            smtp_host: smtp.host.clone()?,
+            smtp_port: smtp.port?,
+            smtp_username: smtp.username.clone()?,
+            smtp_password: smtp.password.as_ref()?.expose().to_string(),
+            from_email: smtp.from_email.clone()?,
+            from_name: smtp.from_name.clone(),
         })
     }
 }
@@ -62,6 +65,7 @@ pub struct OrderItem {
 }
 
 #[derive(Deserialize)]
+#[allow(dead_code)]
 pub struct PasswordResetRequest {
     pub to: String,
     pub to_name: Option<String>,
@@ -188,37 +192,17 @@ async fn send_order_confirmation(
     for item in &payload.items {
         items_html.push_str(&format!(
             "<tr><td>{}</td><td>{}</td><td>${:.2}</td></tr>",
-            item.name, item.quantity, item.price
+            escape_html(&item.name), item.quantity, item.price
         ));
     }
 
-    let html_body = format!(
-        r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <style>
-        body {{ font-family: Arial, sans-serif; line-height: 1.6; color: #333; }}
-        .container {{ max-width: 600px; margin: 0 auto; padding: 20px; }}
-        .header {{ background: #1976d2; color: white; padding: 20px; text-align: center; }}
-        .content {{ padding: 20px; background: #f9f9f9; }}
-        .footer {{ text-align: center; padding: 20px; color: #666; font-size: 12px; }}
-        table {{ width: 100%; border-collapse: collapse; margin: 20px 0; }}
-        th, td {{ padding: 10px; text-align: left; border-bottom: 1px solid #ddd; }}
-        th {{ background: #f0f0f0; }}
-        .total {{ font-size: 18px; font-weight: bold; }}
-    </style>
-</head>
-<body>
-    <div class="container">
-        <div class="header">
-            <h1>Order Confirmation</h1>
-        </div>
-        <div class="content">
-            <p>Hi {},</p>
+    let branding = load_branding(&state.pool).await;
+    let html_body = branding.email_html(
+        "Order Confirmation",
+        &format!(
+            r#"<p>Hi {},</p>
             <p>Thank you for your order! Your order has been confirmed.</p>
             <p><strong>Order ID:</strong> {}</p>
-
             <table>
                 <thead>
                     <tr>
@@ -231,22 +215,13 @@ async fn send_order_confirmation(
                     {}
                 </tbody>
             </table>
-
             <p class="total">Total: ${:.2}</p>
-
-            <p>We'll send you a shipping confirmation email as soon as your order ships.</p>
-        </div>
-        <div class="footer">
-            <p>© 2025 R-Com Store. All rights reserved.</p>
-        </div>
-    </div>
-</body>
-</html>
-        "#,
-        payload.to_name.as_deref().unwrap_or("Customer"),
-        payload.order_id,
-        items_html,
-        payload.order_total
+            <p>We'll send you a shipping confirmation email as soon as your order ships.</p>"#,
+            escape_html(payload.to_name.as_deref().unwrap_or("Customer")),
+            escape_html(&payload.order_id),
+            items_html,
+            payload.order_total
+        ),
     );
 
     let email = Message::builder()
@@ -283,43 +258,20 @@ async fn send_password_reset(
     let to = parse_mailbox(&payload.to, payload.to_name.clone())
         .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
 
-    let html_body = format!(
-        r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <style>
-        body {{ font-family: Arial, sans-serif; line-height: 1.6; color: #333; }}
-        .container {{ max-width: 600px; margin: 0 auto; padding: 20px; }}
-        .header {{ background: #1976d2; color: white; padding: 20px; text-align: center; }}
-        .content {{ padding: 20px; background: #f9f9f9; }}
-        .button {{ display: inline-block; padding: 12px 24px; background: #1976d2; color: white; text-decoration: none; border-radius: 4px; margin: 20px 0; }}
-        .footer {{ text-align: center; padding: 20px; color: #666; font-size: 12px; }}
-    </style>
-</head>
-<body>
-    <div class="container">
-        <div class="header">
-            <h1>Password Reset Request</h1>
-        </div>
-        <div class="content">
-            <p>Hi {},</p>
+    let branding = load_branding(&state.pool).await;
+    let html_body = branding.email_html(
+        "Password Reset Request",
+        &format!(
+            r#"<p>Hi {},</p>
             <p>We received a request to reset your password. Click the button below to create a new password:</p>
             <p style="text-align: center;">
                 <a href="{}" class="button">Reset Password</a>
             </p>
             <p><strong>This link will expire in 24 hours.</strong></p>
-            <p>If you didn't request a password reset, please ignore this email.</p>
-        </div>
-        <div class="footer">
-            <p>© 2025 R-Com Store. All rights reserved.</p>
-        </div>
-    </div>
-</body>
-</html>
-        "#,
-        payload.to_name.as_deref().unwrap_or("there"),
-        payload.reset_url
+            <p>If you didn't request a password reset, please ignore this email.</p>"#,
+            escape_html(payload.to_name.as_deref().unwrap_or("there")),
+            escape_html(&payload.reset_url)
+        ),
     );
 
     let email = Message::builder()
@@ -356,44 +308,23 @@ async fn send_welcome(
     let to = parse_mailbox(&payload.to, payload.to_name.clone())
         .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
 
-    let html_body = format!(
-        r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <style>
-        body {{ font-family: Arial, sans-serif; line-height: 1.6; color: #333; }}
-        .container {{ max-width: 600px; margin: 0 auto; padding: 20px; }}
-        .header {{ background: #1976d2; color: white; padding: 20px; text-align: center; }}
-        .content {{ padding: 20px; background: #f9f9f9; }}
-        .footer {{ text-align: center; padding: 20px; color: #666; font-size: 12px; }}
-    </style>
-</head>
-<body>
-    <div class="container">
-        <div class="header">
-            <h1>Welcome to R-Com!</h1>
-        </div>
-        <div class="content">
-            <p>Hi {},</p>
-            <p>Welcome to R-Com Store! We're excited to have you as part of our community.</p>
+    let branding = load_branding(&state.pool).await;
+    let html_body = branding.email_html(
+        &format!("Welcome to {}!", branding.store_name),
+        &format!(
+            r#"<p>Hi {},</p>
+            <p>Welcome to {}! We're excited to have you as part of our community.</p>
             <p>Start exploring our products and enjoy shopping with us!</p>
-            <p>If you have any questions, feel free to reach out to our support team.</p>
-        </div>
-        <div class="footer">
-            <p>© 2025 R-Com Store. All rights reserved.</p>
-        </div>
-    </div>
-</body>
-</html>
-        "#,
-        payload.to_name.as_deref().unwrap_or("there")
+            <p>If you have any questions, feel free to reach out to our support team.</p>"#,
+            escape_html(payload.to_name.as_deref().unwrap_or("there")),
+            escape_html(&branding.store_name)
+        ),
     );
 
     let email = Message::builder()
         .from(from)
         .to(to)
-        .subject("Welcome to R-Com Store!")
+        .subject(format!("Welcome to {}!", branding.store_name))
         .header(ContentType::TEXT_HTML)
         .body(html_body)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to build email: {}", e)))?;
@@ -0,0 +1,845 @@
+// ============================================================================
+// AXUM VERSION MIGRATION NOTES (0.6 → 0.7.4)
+// ============================================================================
+// This code was originally written for Axum 0.6 but has been updated for 0.7.4
+// 
+// Key Breaking Changes in Axum 0.7+:
+// 1. axum::Server removed → Use axum::serve() with TcpListener
+// 2. .into_make_service() removed → Pass Router directly to axum::serve()
+// 3. Server binding pattern changed → Manual TcpListener creation required
+//
+// Migration Summary:
+// OLD: axum::Server::bind(&addr).serve(app.into_make_service()).await
+// NEW: axum::serve(TcpListener::bind(&addr).await?, app).await
+// ============================================================================
+
+// --- Imports ---
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::{net::SocketAddr, sync::Arc};
+use dotenv::dotenv;
+// AXUM 0.7.4 UPDATE: Added TcpListener import
+// In Axum 0.7+, axum::Server was removed and replaced with axum::serve()
+// which requires a tokio::net::TcpListener instead of direct SocketAddr binding
+use tokio::net::TcpListener;
+// Using stripe crate (renamed async-stripe v0.23.0 in Cargo.toml)
+use stripe::{Client as StripeClient, PaymentIntent, CreatePaymentIntent as PaymentIntentCreateParams, Currency};
+use sqlx::types::chrono::NaiveDateTime;
+// CORS support
+use tower_http::cors::{AllowOrigin, CorsLayer, Any};
+
+// Module declarations
+mod admin_auth;
+mod admin_products;
+mod square_payments;
+mod lettre_email;
+mod brevo_email;
+mod textbelt_sms;
+mod easypost_shipping;
+mod webhooks;
+mod events;
+mod pricing;
+mod warehouses;
+mod shipping_rules;
+mod delivery_estimates;
+mod order_shipments;
+mod admin_orders;
+mod customers;
+mod letre_marketing;
+mod segments;
+mod jobs;
+mod marketing_automation;
+mod reviews;
+mod customer_auth;
+mod coupons;
+mod referrals;
+mod store_credit;
+mod loyalty;
+mod content_blocks;
+mod pages;
+mod blog;
+mod feeds;
+mod graphql;
+mod admin_notifications;
+mod order_tracking;
+mod integrations;
+mod outbound_webhooks;
+mod api_keys;
+mod branding;
+mod email_log;
+mod mailchimp_marketing;
+mod marketing_providers;
+mod phone_verification;
+mod sms_log;
+mod notifications;
+mod job_alerts;
+mod app_config;
+mod jwt_keys;
+mod pii;
+mod saved_searches;
+mod audit_log;
+mod product_slugs;
+mod product_search;
+mod search_index;
+mod cart_pricing;
+mod payment_verification;
+mod checkout_carts;
+mod payment_capture;
+mod reconciliation;
+mod disputes;
+mod environment_mode;
+mod manual_orders;
+mod payment_links;
+mod quotes;
+mod purchase_orders;
+mod license_keys;
+mod tickets;
+mod purchase_limits;
+mod drops;
+mod stock_reservations;
+mod wishlists;
+mod experiments;
+mod product_images;
+mod static_files;
+mod compression;
+mod tls;
+mod request_id;
+mod unit_of_work;
+mod repos;
+mod pagination;
+mod bulk_insert;
+mod cart_math;
+mod db_health;
+mod export_stream;
+mod load_profile;
+mod provider_breaker;
+mod provider_retry;
+mod route_limits;
+mod activity;
+mod daily_digest;
+mod order_status;
+mod split_shipments;
+mod dropship;
+mod attribution;
+mod analytics_forwarding;
+mod meta_conversions;
+mod log_scrubbing;
+mod danger_zone;
+mod order_snapshots;
+mod seed;
+mod scheduler;
+pub mod cli;
+
+// --- What the integration tests in tests/ and the benches call; not a stable API ---
+#[doc(hidden)]
+pub mod testing {
+    use std::sync::Arc;
+
+    pub use crate::admin_auth::issue_admin_token;
+    pub use crate::admin_products::admin_product_routes;
+    pub use crate::cart_pricing::PricedLine;
+    pub use crate::repos::{OrderListFilter, OrderRepo, PgOrderRepo};
+    pub use crate::stock_reservations::reserve_for_cart;
+    pub use crate::unit_of_work::{run as run_unit_of_work, UnitOfWork, WorkError};
+    pub use crate::webhooks::Order;
+    pub use crate::AppState;
+
+    // Load settings from the environment (exits on invalid ones) and build the shared state
+    pub fn app_state(pool: sqlx::PgPool) -> Arc<AppState> {
+        let config = crate::app_config::init();
+        crate::build_app_state(Arc::new(pool), crate::StripeClient::new(config.stripe.secret_key.expose()))
+    }
+}
+
+// --- Shared application state for all handlers ---
+pub struct AppState {
+    pub pool: Arc<sqlx::PgPool>,          // Shared Postgres connection pool
+    pub stripe_client: StripeClient,      // Stripe API client
+    pub events: events::EventBus,         // Domain event bus for side effects
+    pub rate_cache: easypost_shipping::RateCache, // Cached EasyPost shipping rates
+    pub tracking_cache: easypost_shipping::TrackingCache, // Cached EasyPost tracking lookups
+    pub feed_cache: feeds::FeedCache,     // Last generated Google Merchant feed
+    pub admin_notifier: admin_notifications::AdminNotifier, // Live event feed for admin dashboards
+    pub order_status: order_tracking::OrderStatusNotifier,  // Wakes live order tracking streams
+    pub api_key_limiter: api_keys::ApiKeyRateLimiter,       // Per-key request counts for the current minute
+    pub ops: notifications::ops::OpsNotifier,               // Batched Slack/Discord operational messages
+    pub mode: environment_mode::EnvironmentMode,            // Live or test; tags orders and webhook events
+    pub search_index: Option<search_index::SearchIndex>,    // Meilisearch product index, when configured
+    pub repos: repos::Repos,                                // Product, order and webhook event queries
+    pub db_health: db_health::DbHealth,                     // Database circuit breaker, set by the health monitor
+}
+
+// --- Server entrypoint, called from main.rs (the library is shared with rcom-cli) ---
+pub async fn run() {
+    dotenv().ok();                        // Load .env file for secrets
+    let config = app_config::init();      // Validate settings, exit listing every problem
+    log_scrubbing::init(&config.auth.pii_scrub_allowed_fields); // Set up logging, with PII redacted
+    if config.profile == app_config::ServerProfile::Load {
+        tracing::warn!("load profile: external providers are disabled and payment intents are stubbed");
+    }
+
+    // --- Set up database pool ---
+    let pool = Arc::new(db_health::connect(&config.database).await); // Waits for Postgres to come up
+
+    // --- Set up Stripe client ---
+    // Initialize Stripe client with async-stripe v0.23.0 API
+    let stripe_client = StripeClient::new(config.stripe.secret_key.expose());
+
+    // --- Encrypt any customer PII still stored in plain text (and fill in lookup hashes) ---
+    if let Err(e) = pii::backfill(&pool).await {
+        tracing::error!("Failed to backfill encrypted PII: {}", e);
+    }
+
+    // --- Redact PII from webhook payloads stored before scrubbing ---
+    if let Err(e) = log_scrubbing::backfill(&pool).await {
+        tracing::error!("Failed to scrub stored webhook payloads: {}", e);
+    }
+
+    // --- `backend seed`: fill the database with a demo store instead of serving ---
+    if seed::requested() {
+        seed::run(&pool).await;
+        return;
+    }
+
+    // --- JWT signing keys (JWT_SECRET plus rotated keys from the database) ---
+    if let Err(e) = jwt_keys::load_keys(&pool).await {
+        tracing::error!("Failed to load JWT signing keys, only JWT_SECRET is accepted: {}", e);
+    }
+    jwt_keys::spawn_key_refresh(pool.clone());
+
+    // --- Scheduled tasks: add rows for tasks new in this version ---
+    if let Err(e) = scheduler::register_tasks(&pool).await {
+        tracing::error!("Failed to register scheduled tasks: {}", e);
+    }
+
+    // --- Shared app state ---
+    let app_state = build_app_state(pool.clone(), stripe_client);
+    db_health::spawn_monitor(pool.clone(), app_state.db_health.clone(), app_state.ops.clone());
+    let mode = app_state.mode;
+
+    // --- Background tasks: scheduled launches, job queue, payout reconciliation, dispute reminders, ticket holds, product images, and the scheduler (stock reservations, expiring authorizations, digests, segment sync, product feed, maintenance) ---
+    if config.features.background_jobs {
+        admin_products::spawn_publish_scheduler(app_state.clone());
+        jobs::spawn_job_worker(pool.clone());
+        job_alerts::spawn_job_alert_monitor(pool.clone());
+        reconciliation::spawn_reconciliation_sync(app_state.clone());
+        disputes::spawn_dispute_reminders(app_state.clone());
+        purchase_orders::spawn_invoice_reminders(app_state.clone());
+        tickets::spawn_hold_release(app_state.clone());
+        pricing::spawn_sale_announcer(app_state.clone());
+        search_index::spawn_search_sync(app_state.clone());
+        product_images::spawn_image_processor(pool.clone());
+        scheduler::spawn_scheduler(app_state.clone());
+    }
+
+    // --- Configure CORS: any origin unless CORS_ALLOWED_ORIGINS lists them ---
+    let allowed_origins = if config.server.cors_allowed_origins.is_empty() {
+        AllowOrigin::from(Any)
+    } else {
+        AllowOrigin::list(config.server.cors_allowed_origins.iter().filter_map(|origin| origin.parse().ok()))
+    };
+    let cors = CorsLayer::new()
+        .allow_origin(allowed_origins)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers([request_id::HEADER]);
+
+    // --- Build the Axum router with all routes and shared state ---
+    let mut app = Router::new()
+        .route("/health", get(health_check))                           // Health check endpoint
+        .route("/health/db", get(db_health::health_report))            // Database pool and circuit breaker state
+        .route("/api/products", get(get_products))                    // Public products endpoint
+        .route("/api/products/:slug", get(get_product))               // Product by slug (old slugs and ids redirect)
+        .merge(product_search::product_search_routes(app_state.clone())) // Storefront search with facet counts
+        .merge(search_index::search_index_routes(app_state.clone()))   // Meilisearch reindex (admin)
+        .route("/api/create-payment-intent", post(create_payment_intent)) // Stripe payment intent
+        .merge(admin_auth::admin_auth_routes(app_state.clone()))       // Admin authentication routes
+        .merge(jwt_keys::jwt_key_routes(app_state.clone()))            // JWT signing key rotation
+        .merge(admin_products::admin_product_routes(app_state.clone()))// Admin product management
+        .merge(product_images::product_image_routes(app_state.clone())) // Product image uploads and variants
+        .merge(saved_searches::saved_search_routes(app_state.clone())) // Saved product searches and digests
+        .merge(pricing::price_rule_routes(app_state.clone()))          // Admin sale / quantity-break pricing, bulk price changes
+        .merge(audit_log::audit_log_routes(app_state.clone()))         // Admin bulk change audit log
+        .merge(activity::activity_routes(app_state.clone()))          // Admin activity feed
+        .merge(daily_digest::daily_digest_routes(app_state.clone()))  // Daily summary email, opt-outs and on-demand sends
+        .merge(order_status::order_status_routes(app_state.clone()))   // Order status history (admin)
+        .merge(split_shipments::split_shipment_routes(app_state.clone())) // Shipping progress, manual tracking and customer shipments
+        .merge(dropship::dropship_routes(app_state.clone())) // Dropship vendors, purchase orders and the vendor portal
+        .merge(attribution::attribution_routes(app_state.clone())) // UTM attribution report
+        .merge(analytics_forwarding::analytics_forwarding_routes(app_state.clone())) // Server-side GA4 / pixel forwarding settings
+        .merge(meta_conversions::meta_conversions_routes(app_state.clone())) // Meta Conversions API settings and AddToCart
+        .merge(danger_zone::danger_zone_routes(app_state.clone()))    // Delete test orders, purge stale carts, scrub old PII
+        .merge(square_payments::square_payment_routes(app_state.clone())) // Square payment processing
+        .merge(lettre_email::lettre_email_routes(app_state.clone()))     // Lettre transactional emails
+        .merge(brevo_email::brevo_email_routes(app_state.clone()))       // Brevo email marketing
+        .merge(textbelt_sms::textbelt_sms_routes(app_state.clone()))    // Textbelt SMS notifications
+        .merge(easypost_shipping::easypost_shipping_routes(app_state.clone())) // EasyPost shipping
+        .merge(shipping_rules::shipping_rules_routes(app_state.clone())) // Zone-based shipping quotes
+        .merge(cart_pricing::cart_pricing_routes(app_state.clone()))   // Authoritative cart totals
+        .merge(payment_verification::payment_verification_routes(app_state.clone())) // Expected vs received payment amounts
+        .merge(payment_capture::payment_capture_routes(app_state.clone())) // Authorization capture and void
+        .merge(reconciliation::reconciliation_routes(app_state.clone()))  // Provider payouts matched against orders
+        .merge(disputes::dispute_routes(app_state.clone()))           // Chargeback evidence workspace
+        .merge(delivery_estimates::delivery_estimate_routes(app_state.clone())) // Delivery date estimates
+        .merge(warehouses::warehouse_routes(app_state.clone()))        // Warehouses, stock and fulfillments
+        .merge(order_shipments::order_shipment_routes(app_state.clone())) // Multi-address order shipments
+        .merge(admin_orders::admin_order_routes(app_state.clone()))   // Order notes, packing slips, invoices
+        .merge(order_snapshots::order_snapshot_routes(app_state.clone())) // Orders as sold, from purchase-time snapshots
+        .merge(manual_orders::manual_order_routes(app_state.clone())) // Staff-entered phone and offline orders
+        .merge(payment_links::payment_link_routes(app_state.clone())) // Stripe Payment Links for invoices and social selling
+        .merge(quotes::quote_routes(app_state.clone())) // B2B quotes: request, revise, send and accept
+        .merge(purchase_orders::purchase_order_routes(app_state.clone())) // Net terms checkout, PDF invoices and receivables
+        .merge(license_keys::license_key_routes(app_state.clone())) // License key pools, assignment and reissue
+        .merge(tickets::ticket_routes(app_state.clone()))             // Event tickets: capacity, QR codes and check-in
+        .merge(purchase_limits::purchase_limit_routes(app_state.clone())) // Max-per-customer limits for limited drops
+        .merge(drops::drop_routes(app_state.clone()))                 // Waiting room queues for high-demand drops
+        .merge(stock_reservations::checkout_hold_routes(app_state.clone())) // Checkout stock hold countdown
+        .merge(wishlists::wishlist_routes(app_state.clone()))         // Customer wishlists and price watches
+        .merge(experiments::experiment_routes(app_state.clone()))     // Catalog A/B tests and their results
+        .merge(customers::customer_routes(app_state.clone()))         // Customer management
+        .merge(segments::segment_routes(app_state.clone()))           // Customer segments and list sync
+        .merge(marketing_providers::marketing_provider_routes(app_state.clone())) // Newsletter signup and campaigns
+        .merge(marketing_automation::marketing_automation_routes(app_state.clone())) // Event-driven marketing emails
+        .merge(reviews::review_routes(app_state.clone()))             // One-click ratings and review moderation
+        .merge(customer_auth::customer_auth_routes(app_state.clone())) // Customer login
+        .merge(phone_verification::phone_verification_routes(app_state.clone())) // Phone verification codes
+        .merge(referrals::referral_routes(app_state.clone()))         // Referral links and stats
+        .merge(store_credit::store_credit_routes(app_state.clone()))  // Store credit ledger
+        .merge(loyalty::loyalty_routes(app_state.clone()))            // Loyalty points ledger
+        .merge(content_blocks::content_block_routes(app_state.clone())) // CMS banners and homepage sections
+        .merge(pages::page_routes(app_state.clone()))                 // Static pages (shipping, returns)
+        .merge(blog::blog_routes(app_state.clone()))                  // Blog posts and RSS feed
+        .merge(admin_notifications::admin_notification_routes(app_state.clone())) // Admin dashboard WebSocket
+        .merge(order_tracking::order_tracking_routes(app_state.clone()))  // Live order status (SSE)
+        .merge(integrations::integration_routes(app_state.clone()))   // ERP inventory and price sync
+        .merge(outbound_webhooks::outbound_webhook_routes(app_state.clone())) // Merchant webhook subscriptions
+        .merge(api_keys::api_key_routes(app_state.clone()))           // Scoped API key management
+        .merge(branding::branding_routes(app_state.clone()))          // Store branding for emails and documents
+        .merge(email_log::email_log_routes(app_state.clone()))        // Email delivery log and suppression list
+        .merge(sms_log::sms_log_routes(app_state.clone()))            // Inbound SMS log and opt-out list
+        .merge(notifications::notification_routes(app_state.clone()))  // Notification quiet hours, batching and rate limits
+        .merge(jobs::job_routes(app_state.clone()))                   // Dead-letter queue inspection and requeue
+        .merge(scheduler::scheduler_routes(app_state.clone()))        // Scheduled tasks, run history and manual runs
+        .merge(provider_breaker::provider_breaker_routes(app_state.clone())) // Stripe, EasyPost and Brevo circuit breaker state
+        .merge(app_config::config_routes(app_state.clone()))          // Redacted runtime configuration
+        .merge(webhooks::webhook_routes(app_state.clone()))            // Payment, tracking and inbound SMS webhooks (Stripe, Square, EasyPost, Twilio)
+        .merge(static_files::media_routes(&config.media.dir));         // Uploaded product images and their variants
+    if config.features.product_feed {
+        app = app.merge(feeds::feed_routes(app_state.clone()));       // Google Merchant product feed
+    }
+    if config.features.graphql {
+        app = app.merge(graphql::graphql_routes(app_state.clone()));  // GraphQL API for headless clients
+    }
+    match &config.server.static_dir {
+        Some(dir) => app = app.merge(static_files::storefront_routes(dir)), // Leptos storefront, with client-side routes
+        None => app = app.route("/", get(health_check)),              // Health check at the root when there's no storefront
+    }
+    let app = app
+        .layer(axum::middleware::from_fn(provider_retry::retry_budget)) // Provider call retries each request may spend
+        .layer(axum::middleware::from_fn_with_state(app_state.clone(), db_health::reject_while_down)) // 503 for API requests while the database is down
+        .layer(cors)                                                   // Add CORS middleware
+        .layer(axum::middleware::from_fn(request_id::assign_request_id)) // X-Request-Id in logs, error bodies and provider calls
+        .layer(compression::compression_layer())                      // gzip / brotli for JSON, feeds and other text
+        .with_state(app_state);                                       // Attach shared state, converts Router<Arc<AppState>> -> Router<()>
+
+    // --- Start the HTTP server using axum 0.7.4 API ---
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.server.port));
+    if let (Some(cert_path), Some(key_path)) = (&config.server.tls_cert_path, &config.server.tls_key_path) {
+        tracing::info!("Backend running at https://{} in {} mode", addr, mode);
+        tls::serve(app, addr, cert_path, key_path).await;                 // HTTPS with HTTP/2, no reverse proxy needed
+        return;
+    }
+    tracing::info!("Backend running at http://{} in {} mode", addr, mode);
+    
+    // AXUM 0.7.4 UPDATE: Server setup pattern changed
+    // OLD (Axum 0.6): axum::Server::bind(&addr).serve(app.into_make_service())
+    // NEW (Axum 0.7+): axum::serve(listener, app)
+    //
+    // Changes made:
+    // 1. axum::Server was removed - no longer exists in axum 0.7+
+    // 2. Must create TcpListener manually and pass to axum::serve()
+    // 3. Router<S>.with_state(S) returns Router<()>, which can be passed directly to axum::serve()
+    // 4. No need for into_make_service() or into_service() - pass Router<()> directly
+    let listener = TcpListener::bind(&addr).await.unwrap();
+    tracing::info!("Listening on {}", addr);
+
+    // Router<()> (after with_state) can be passed directly to axum::serve() in Axum 0.7
+    axum::serve(listener, app)
+        .await
+        .unwrap();
+}
+
+// --- Shared state for handlers, background tasks and rcom-cli: repos, caches and the event bus ---
+pub(crate) fn build_app_state(pool: Arc<sqlx::PgPool>, stripe_client: StripeClient) -> Arc<AppState> {
+    let repos = repos::Repos::postgres(pool.clone());
+    build_app_state_with_repos(pool, stripe_client, repos)
+}
+
+// The same state over other repos, e.g. the in-memory ones handler tests use
+pub(crate) fn build_app_state_with_repos(pool: Arc<sqlx::PgPool>, stripe_client: StripeClient, repos: repos::Repos) -> Arc<AppState> {
+    let admin_notifier = admin_notifications::AdminNotifier::new();
+    let order_status = order_tracking::OrderStatusNotifier::new();
+    let ops = notifications::ops::OpsNotifier::from_env();
+    let search_index = search_index::SearchIndex::from_config();
+    let capture_events = Arc::new(std::sync::OnceLock::new());
+    let app_state = Arc::new(AppState {
+        pool: pool.clone(),
+        stripe_client: stripe_client.clone(),
+        events: events::EventBus::with_default_subscribers(pool.clone())
+            .subscribe(marketing_automation::MarketingAutomationSubscriber::new(pool.clone()))
+            .subscribe(loyalty::LoyaltySubscriber::new(pool.clone()))
+            .subscribe(admin_notifications::AdminNotificationSubscriber::new(admin_notifier.clone()))
+            .subscribe(order_tracking::OrderStatusSubscriber::new(order_status.clone()))
+            .subscribe(integrations::ErpWebhookSubscriber::new(pool.clone()))
+            .subscribe(outbound_webhooks::OutboundWebhookSubscriber::new(pool.clone()))
+            .subscribe(notifications::ops::OpsSubscriber::new(ops.clone()))
+            .subscribe(license_keys::LicenseKeySubscriber::new(pool.clone(), ops.clone()))
+            .subscribe(tickets::TicketSubscriber::new(pool.clone(), ops.clone()))
+            .subscribe(purchase_limits::PurchaseLimitSubscriber::new(pool.clone(), stripe_client.clone(), ops.clone()))
+            .subscribe(wishlists::PriceWatchSubscriber::new(pool.clone()))
+            .subscribe(experiments::PurchaseSubscriber::new(pool.clone()))
+            .subscribe(search_index::SearchIndexSubscriber::new(pool.clone(), search_index.clone()))
+            .subscribe(payment_capture::CaptureSubscriber::new(
+                pool.clone(),
+                repos.orders.clone(),
+                stripe_client,
+                capture_events.clone(),
+            ))
+            .subscribe(activity::OrderActivitySubscriber::new(pool.clone()))
+            .subscribe(split_shipments::ShipmentEmailSubscriber::new(pool.clone()))
+            .subscribe(dropship::DropshipSubscriber::new(pool.clone()))
+            .subscribe(analytics_forwarding::AnalyticsForwardingSubscriber::new(pool.clone()))
+            .subscribe(meta_conversions::MetaConversionsSubscriber::new(pool.clone())),
+        rate_cache: easypost_shipping::RateCache::default(),
+        tracking_cache: easypost_shipping::TrackingCache::default(),
+        feed_cache: feeds::FeedCache::default(),
+        admin_notifier,
+        order_status,
+        api_key_limiter: api_keys::ApiKeyRateLimiter::default(),
+        ops,
+        mode: environment_mode::EnvironmentMode::from_config(),
+        search_index,
+        repos,
+        db_health: db_health::DbHealth::default(),
+    });
+    let _ = capture_events.set(app_state.events.clone());
+    app_state
+}
+
+// --- Health check endpoint ---
+async fn health_check() -> &'static str {
+    "OK"
+}
+
+// --- Data types for Product, PaymentIntent, etc. ---
+#[derive(Serialize, sqlx::FromRow)]
+struct Product {
+    id: i32,
+    name: String,
+    description: Option<String>,
+    price: f64,
+    inventory: i32,
+    created_at: NaiveDateTime,
+    slug: String,
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_price: Option<f64>, // Set when a sale price is active
+    #[sqlx(skip)]
+    price_breaks: Vec<pricing::PriceBreak>,
+    #[sqlx(skip)]
+    images: Vec<product_images::ResponsiveImage>, // Ready uploaded images, main image first
+}
+
+// The storefront's view of a catalog row; prices and images are filled in afterwards
+impl From<admin_products::Product> for Product {
+    fn from(p: admin_products::Product) -> Self {
+        Product {
+            id: p.id,
+            name: p.name,
+            description: p.description,
+            price: p.price,
+            inventory: p.inventory,
+            created_at: p.created_at,
+            slug: p.slug,
+            original_price: None,
+            price_breaks: Vec::new(),
+            images: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CreatePaymentIntentRequest {
+    amount: Option<i64>, // Total the customer was shown, in cents; rejected when it differs from the server's
+    currency: Option<String>, // Currency the customer was shown; rejected when it differs from the server's
+    #[serde(flatten)]
+    cart: cart_pricing::CartPriceRequest, // Items, ship-to addresses and coupon, priced server-side
+    #[serde(flatten)]
+    gift: webhooks::GiftOptions, // gift_wrap / gift_message, stored on the order
+    #[serde(flatten)]
+    utm: attribution::Utm, // utm_source / utm_medium / utm_campaign the shopper arrived with
+    ga_client_id: Option<String>, // GA client id for server-side tracking; falls back to the _ga cookie
+    #[serde(flatten)]
+    browser: meta_conversions::BrowserContext, // tracking_consent and Meta's fbp / fbc, for the Purchase event
+    referral_code: Option<String>, // Falls back to the referral attribution cookie
+    redeem_points: Option<i64>, // Loyalty points to spend; requires a customer login
+    email: Option<String>, // Receipt email; guests must give one to buy purchase-limited products
+    drop_token: Option<String>, // Admitted waiting room token, while a drop of a cart product runs
+}
+
+#[derive(Serialize)]
+struct CreatePaymentIntentResponse {
+    client_secret: String,
+    amount: i64, // Amount charged, in cents, after every discount
+    order_id: Option<uuid::Uuid>, // Id the order gets once the payment succeeds
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    shipments: Vec<order_shipments::PlannedShipment>, // Per-address totals for multi-address orders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    discount_amount: Option<i64>, // Coupon discount applied, in cents
+    #[serde(skip_serializing_if = "Option::is_none")]
+    loyalty_points_redeemed: Option<i64>, // Points spent on this payment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    loyalty_discount: Option<i64>, // Discount bought with those points, in cents
+    #[serde(skip_serializing_if = "Option::is_none")]
+    store_credit_applied: Option<i64>, // Store credit used, in cents
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hold_expires_at: Option<sqlx::types::chrono::DateTime<sqlx::types::chrono::Utc>>, // Reserved stock and seats go back after this
+}
+
+// --- Example: create-payment-intent handler ---
+// Accepts Stripe client and creates a PaymentIntent using the async-stripe v0.23.0 API
+async fn create_payment_intent(
+    State(state): State<Arc<AppState>>,
+    customer: Option<customer_auth::AuthenticatedCustomer>,
+    headers: axum::http::HeaderMap,
+    Json(payload): Json<CreatePaymentIntentRequest>,
+) -> Result<Json<CreatePaymentIntentResponse>, (axum::http::StatusCode, String)> {
+    if payload.gift.gift_message.as_ref().is_some_and(|m| m.chars().count() > webhooks::GiftOptions::MAX_MESSAGE_LEN) {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("gift_message must be at most {} characters", webhooks::GiftOptions::MAX_MESSAGE_LEN),
+        ));
+    }
+    // Don't hold stock or tickets for a checkout Stripe can't take right now
+    if provider_breaker::is_open(provider_breaker::Provider::Stripe) {
+        return Err((
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            "Payments are temporarily unavailable, please try again in a minute".to_string(),
+        ));
+    }
+
+    // Charge the server-computed cart total; the client never supplies the amount
+    let mut price = cart_pricing::price_cart(&state.pool, &payload.cart, customer.as_ref().map(|c| c.customer_id))
+        .await
+        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?;
+    // Pricing without an address leaves shipping at 0, which is only right when nothing ships
+    if price.requires_shipping && payload.cart.to_address.is_none() && payload.cart.address_book.is_empty() {
+        return Err((
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+            "A ship-to address (to_address or address_book) is required for this cart".to_string(),
+        ));
+    }
+    drops::require_admission(&state.pool, &price.lines, payload.drop_token.as_deref()).await?;
+    // The cart is priced and charged in the store currency, whatever the client asks for
+    if let Some(client_currency) = payload.currency.as_deref().map(str::trim).filter(|c| !c.eq_ignore_ascii_case(&price.currency)) {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("Currency mismatch: client sent {} but the order is priced in {}", client_currency, price.currency),
+        ));
+    }
+    let currency: Currency = price.currency.parse().map_err(|_| {
+        (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unsupported store currency: {}", price.currency),
+        )
+    })?;
+    let amount = price.total;
+    let shipments = std::mem::take(&mut price.shipments);
+    let discount_amount = (price.discount > 0).then_some(price.discount);
+
+    // The coupon is held with the checkout below and marked redeemed when the order is created
+    let mut metadata = payload.gift.to_metadata();
+    if let Some(code) = &price.coupon_code {
+        metadata.insert(coupons::METADATA_KEY.to_string(), code.clone());
+    }
+
+    // Redeem loyalty points at the configured conversion rate (held with the checkout below and
+    // deducted when the order is created)
+    let (amount, loyalty_points_redeemed, loyalty_discount) = match payload.redeem_points.filter(|p| *p > 0) {
+        Some(requested) => {
+            let customer = customer.as_ref().ok_or((
+                axum::http::StatusCode::UNAUTHORIZED,
+                "Log in to redeem loyalty points".to_string(),
+            ))?;
+            let (points, discount) = loyalty::quote_redemption(&state.pool, customer.customer_id, requested, amount)
+                .await
+                .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+            if points > 0 {
+                metadata.insert(loyalty::METADATA_KEY.to_string(), points.to_string());
+            }
+            (amount - discount, (points > 0).then_some(points), (points > 0).then_some(discount))
+        }
+        None => (amount, None, None),
+    };
+
+    // Logged-in customers automatically spend available store credit (held with the checkout
+    // below and debited when the order is created)
+    let (amount, store_credit_applied) = match &customer {
+        Some(customer) => {
+            let balance = store_credit::available(&*state.pool, customer.customer_id)
+                .await
+                .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+            let applied = balance.min(amount - coupons::MIN_CHARGE_CENTS.min(amount)).max(0);
+            metadata.insert(store_credit::CUSTOMER_METADATA_KEY.to_string(), customer.customer_id.to_string());
+            if applied > 0 {
+                metadata.insert(store_credit::METADATA_KEY.to_string(), applied.to_string());
+            }
+            (amount - applied, (applied > 0).then_some(applied))
+        }
+        None => (amount, None),
+    };
+
+    // Referral attribution travels with the payment so the webhook can reward the referrer
+    let referral_code = payload
+        .referral_code
+        .as_deref()
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .or_else(|| referrals::code_from_cookies(&headers));
+    if let Some(code) = referral_code {
+        metadata.insert(referrals::METADATA_KEY.to_string(), code.to_uppercase());
+    }
+
+    // A stale or tampered client total is refused rather than silently charged differently
+    if let Some(client_amount) = payload.amount.filter(|a| *a != amount) {
+        return Err((
+            axum::http::StatusCode::CONFLICT,
+            format!("Amount mismatch: client sent {} but the order totals {} cents", client_amount, amount),
+        ));
+    }
+
+    // Snapshot the cart so the webhook can create the order with its items, and hold its event
+    // seats, stock, coupon, loyalty points and store credit until the payment goes through or the
+    // hold expires. All of it is saved or none of it, so a sold out product or a purchase limit
+    // leaves no cart or seat holds behind
+    let customer_id = customer.as_ref().map(|c| c.customer_id);
+    let contact_email = payload.email.as_deref().map(str::trim).filter(|e| !e.is_empty()).map(str::to_string);
+    let visitor_id = experiments::visitor_id(customer.as_ref(), &headers);
+    let utm = payload.utm.normalized();
+    let ga_client_id = analytics_forwarding::client_id(payload.ga_client_id.as_deref(), &headers);
+    let tracking = payload.browser.resolve(&headers);
+    let points_hold = customer_id.zip(loyalty_points_redeemed);
+    let credit_hold = customer_id.zip(store_credit_applied);
+    let (checkout, hold_expires_at) = unit_of_work::run(&state, |uow| {
+        let (price, contact_email, visitor_id, utm, ga_client_id, tracking) =
+            (price.clone(), contact_email.clone(), visitor_id.clone(), utm.clone(), ga_client_id.clone(), tracking.clone());
+        Box::pin(async move {
+            let checkout = checkout_carts::save_cart(uow.conn(), customer_id, &price).await?;
+            purchase_limits::enforce(uow.conn(), checkout.cart_id, customer_id, contact_email.as_deref(), &price.lines).await?;
+            let Some(cart_id) = checkout.cart_id else {
+                return Ok((checkout, None));
+            };
+            tickets::hold_for_cart(uow.conn(), cart_id, &price.lines).await?;
+            stock_reservations::reserve_for_cart(uow, cart_id, &price.lines).await?;
+            if let Some((customer_id, points)) = points_hold {
+                loyalty::hold_for_cart(uow.conn(), cart_id, customer_id, points).await?;
+            }
+            if let Some((customer_id, amount)) = credit_hold {
+                store_credit::hold_for_cart(uow.conn(), cart_id, customer_id, amount).await?;
+            }
+            if let Some(code) = &price.coupon_code {
+                coupons::hold_for_cart(uow.conn(), cart_id, code).await?;
+            }
+            if let Some(email) = contact_email {
+                checkout_carts::set_contact_email(uow.conn(), cart_id, &email).await?;
+            }
+            // Experiment results credit the purchase to this visitor's variants
+            if let Some(visitor_id) = visitor_id {
+                checkout_carts::set_visitor(uow.conn(), cart_id, &visitor_id).await?;
+            }
+            // Marketing attribution goes on the order with the cart
+            if !utm.is_empty() {
+                attribution::set_on_cart(uow.conn(), cart_id, &utm).await?;
+            }
+            if let Some(client_id) = ga_client_id {
+                analytics_forwarding::set_client_id(uow.conn(), cart_id, &client_id).await?;
+            }
+            meta_conversions::set_on_cart(uow.conn(), cart_id, &tracking).await?;
+            let hold_expires_at = stock_reservations::hold_expiry(uow.conn(), cart_id).await?;
+            Ok((checkout, hold_expires_at))
+        })
+    })
+    .await?;
+    checkout.add_to_metadata(&mut metadata);
+
+    // Create the params with required parameters in constructor
+    let mut params = PaymentIntentCreateParams::new(amount, currency);
+    params.payment_method_types = Some(vec!["card".to_string()]);
+    params.capture_method = Some(payment_capture::capture_method()); // Manual: authorize now, capture on fulfillment
+    params.metadata = Some(metadata);
+    params.receipt_email = payload.email.as_deref().map(str::trim).filter(|e| !e.is_empty());
+
+    let created = match app_config::get().profile {
+        app_config::ServerProfile::Load => Ok(load_profile::payment_intent(params.amount, params.currency)),
+        app_config::ServerProfile::Standard => provider_breaker::stripe(PaymentIntent::create(&state.stripe_client, params)).await,
+    };
+    match created {
+        Ok(intent) => {
+            let recorded =
+                record_payment_intent(&state, checkout.cart_id, intent.id.as_str(), amount, &price.currency, &shipments).await;
+            if let Err(e) = recorded {
+                // The webhook can't check or ship a payment without these: cancel it so it can't go
+                // through, and give back what the checkout held rather than wait for the hold to expire
+                if let app_config::ServerProfile::Standard = app_config::get().profile {
+                    checkout_carts::cancel_unrecorded_payment(&state, intent.id.as_str()).await;
+                }
+                if let Some(cart_id) = checkout.cart_id {
+                    if let Err(e) = checkout_carts::release_holds(&state, cart_id).await {
+                        tracing::error!("Failed to release the holds of checkout {}: {}", cart_id, e);
+                    }
+                }
+                return Err((axum::http::StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)));
+            }
+            Ok(Json(CreatePaymentIntentResponse {
+                client_secret: intent.client_secret.unwrap_or_default(),
+                amount,
+                order_id: checkout.order_id,
+                shipments,
+                discount_amount,
+                loyalty_points_redeemed,
+                loyalty_discount,
+                store_credit_applied,
+                hold_expires_at,
+            }))
+        }
+        Err(e) => {
+            if let Some(cart_id) = checkout.cart_id {
+                if let Err(e) = checkout_carts::release_holds(&state, cart_id).await {
+                    tracing::error!("Failed to release the holds of checkout {}: {}", cart_id, e);
+                }
+            }
+            Err((e.status(axum::http::StatusCode::INTERNAL_SERVER_ERROR), format!("Stripe error: {e}")))
+        }
+    }
+}
+
+// Link the new payment intent to its cart, and store the amount the webhook checks it against
+// and any per-address shipments
+async fn record_payment_intent(
+    state: &AppState,
+    cart_id: Option<uuid::Uuid>,
+    payment_intent_id: &str,
+    amount: i64,
+    currency: &str,
+    shipments: &[order_shipments::PlannedShipment],
+) -> Result<(), sqlx::Error> {
+    if let Some(cart_id) = cart_id {
+        checkout_carts::attach_payment(&state.pool, cart_id, payment_intent_id).await?;
+    }
+    payment_verification::record_expected(&state.pool, payment_intent_id, amount, currency).await?;
+    if !shipments.is_empty() {
+        order_shipments::save_shipments(&state.pool, payment_intent_id, shipments).await?;
+    }
+    Ok(())
+}
+
+// --- Example: get_products handler ---
+// Fetches all products from the database with effective (sale / tiered) prices applied, or a
+// page of them in id order when `cursor` or `limit` is given
+async fn get_products(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<pagination::PageQuery>,
+) -> Result<Json<pagination::Listing<Product>>, (StatusCode, String)> {
+    let (rows, page_size) = if query.wants_page() {
+        let size = pagination::page_size(query.limit);
+        let after = pagination::after(query.cursor.as_deref())?;
+        (state.repos.products.list_after(true, after, size + 1).await, Some(size))
+    } else {
+        (state.repos.products.list_published().await, None)
+    };
+    let mut products: Vec<Product> = rows
+        .unwrap_or_default()
+        .into_iter()
+        .map(Product::from)
+        .collect();
+
+    let price_book = pricing::PriceBook::load_active(&state.pool)
+        .await
+        .unwrap_or_default();
+    for product in &mut products {
+        apply_effective_price(&price_book, product);
+    }
+    if let Err(e) = attach_images(&state.pool, &mut products).await {
+        tracing::error!("Failed to load product images: {}", e);
+    }
+    Ok(Json(match page_size {
+        Some(size) => pagination::Listing::Page(pagination::Page::new(products, size, |p| p.id)),
+        None => pagination::Listing::All(products),
+    }))
+}
+
+// Show the sale price, keeping the list price as original_price, plus quantity breaks
+fn apply_effective_price(price_book: &pricing::PriceBook, product: &mut Product) {
+    let base_price = product.price;
+    let effective = price_book.unit_price(product.id, base_price, 1);
+    product.price_breaks = price_book.price_breaks(product.id, base_price);
+    if effective < base_price {
+        product.original_price = Some(base_price);
+        product.price = effective;
+    }
+}
+
+// Fill in each product's responsive images
+async fn attach_images(pool: &sqlx::PgPool, products: &mut [Product]) -> Result<(), sqlx::Error> {
+    let ids: Vec<i32> = products.iter().map(|p| p.id).collect();
+    let mut images = product_images::load_responsive(pool, &ids).await?;
+    for product in products {
+        product.images = images.remove(&product.id).unwrap_or_default();
+    }
+    Ok(())
+}
+
+// --- get_product handler ---
+// A published product by its current slug. Old slugs from before a rename and numeric ids
+// answer 301 with the current URL, so links shared before slugs (or renames) keep working
+async fn get_product(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+) -> Result<Response, (StatusCode, String)> {
+    let id = match product_slugs::resolve(&state.pool, &slug)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    {
+        product_slugs::SlugLookup::Found(id) => id,
+        product_slugs::SlugLookup::Moved(current) => {
+            let location = format!("/api/products/{}", current);
+            return Ok((StatusCode::MOVED_PERMANENTLY, [(header::LOCATION, location)]).into_response());
+        }
+        product_slugs::SlugLookup::NotFound => {
+            return Err((StatusCode::NOT_FOUND, "Product not found".to_string()));
+        }
+    };
+
+    let mut product = state
+        .repos
+        .products
+        .find_published(id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .map(Product::from)
+        .ok_or((StatusCode::NOT_FOUND, "Product not found".to_string()))?;
+    let price_book = pricing::PriceBook::load_active(&state.pool)
+        .await
+        .unwrap_or_default();
+    apply_effective_price(&price_book, &mut product);
+    attach_images(&state.pool, std::slice::from_mut(&mut product))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(product).into_response())
+}
\ No newline at end of file
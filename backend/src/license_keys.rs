@@ -0,0 +1,718 @@
+// License Keys Module - Keys for software products
+// A license product draws keys from a pool uploaded by admins, or has them generated from a
+// pattern. Keys are assigned once an order is confirmed (keys_per_unit for each unit bought)
+// and listed in the confirmation email. Orders that find the pool empty are filled, and the
+// keys emailed, when more keys are uploaded. Admins can revoke or reissue a key; refunded
+// orders have their keys revoked
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post, put},
+    Json, Router,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::branding::{escape_html, load_branding};
+use crate::customer_auth::AuthenticatedCustomer;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::notifications::ops::OpsNotifier;
+use crate::notifications::{queue_email, EmailPriority};
+use crate::pii::Encrypted;
+use crate::AppState;
+
+const KEY_SOURCES: [&str; 2] = ["pool", "generated"];
+// Generated key characters, without the easily confused 0/O and 1/I
+const KEY_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const MIN_RANDOM_CHARS: usize = 12;
+const MAX_UPLOAD_KEYS: usize = 10_000;
+
+// Database model for license products
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct LicenseProduct {
+    pub product_id: i32,
+    pub source: String, // pool or generated
+    pub key_pattern: String,
+    pub keys_per_unit: i32,
+    pub low_pool_threshold: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// License product with its name and pool counts for the admin list
+#[derive(Debug, Serialize)]
+pub struct LicenseProductSummary {
+    #[serde(flatten)]
+    pub settings: LicenseProduct,
+    pub product_name: String,
+    pub available: i64,
+    pub assigned: i64,
+    pub revoked: i64,
+}
+
+// Database model for license keys
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct LicenseKey {
+    pub id: Uuid,
+    pub product_id: i32,
+    pub license_key: String,
+    pub status: String, // available, assigned, revoked
+    pub order_id: Option<Uuid>,
+    pub assigned_at: Option<DateTime<Utc>>,
+    pub replaces_id: Option<Uuid>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub revoked_by: Option<String>,
+    pub revoke_reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// An order's key as shown to the customer
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct OrderLicenseKey {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub product_id: i32,
+    pub product_name: String,
+    pub license_key: String,
+    pub assigned_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct LicenseProductInput {
+    pub source: String,
+    pub key_pattern: Option<String>,
+    pub keys_per_unit: Option<i32>,
+    pub low_pool_threshold: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct UploadKeysRequest {
+    pub keys: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct UploadKeysResult {
+    pub added: u64,
+    pub duplicates: u64,
+    pub orders_filled: usize, // Orders waiting on keys that got theirs
+}
+
+#[derive(Deserialize)]
+pub struct KeyListQuery {
+    pub status: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct RevokeKeyRequest {
+    pub reason: Option<String>,
+    #[serde(default = "default_notify")]
+    pub notify_customer: bool, // Reissues only: email the new key
+}
+
+fn default_notify() -> bool {
+    true
+}
+
+// Keys assigned to one product of an order by assign_missing
+struct ProductAssignment {
+    product_id: i32,
+    source: String,
+    low_pool_threshold: i32,
+    assigned: i64,
+    missing: i64, // Still owed because the pool ran out
+}
+
+pub fn license_key_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/license-products", get(list_license_products))
+        .route("/api/admin/products/:id/license", put(set_license_product).delete(remove_license_product))
+        .route("/api/admin/products/:id/license-keys", get(list_product_keys).post(upload_keys))
+        .route("/api/admin/orders/:id/license-keys", get(list_order_keys))
+        .route("/api/admin/license-keys/:id/revoke", post(revoke_key))
+        .route("/api/admin/license-keys/:id/reissue", post(reissue_key))
+        .route("/api/customers/me/license-keys", get(get_my_keys))
+        .with_state(app_state)
+}
+
+// A key from the pattern, each X replaced by a random character
+fn generate_key(pattern: &str) -> String {
+    let mut rng = rand::thread_rng();
+    pattern
+        .chars()
+        .map(|c| if c == 'X' { KEY_ALPHABET[rng.gen_range(0..KEY_ALPHABET.len())] as char } else { c })
+        .collect()
+}
+
+// Take up to `count` keys for the order: the oldest available pool keys, or newly generated ones
+async fn take_keys(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    settings: &LicenseProduct,
+    order_id: Uuid,
+    count: i64,
+    replaces_id: Option<Uuid>,
+) -> Result<i64, sqlx::Error> {
+    if settings.source == "pool" {
+        let res = sqlx::query!(
+            "UPDATE license_keys SET status = 'assigned', order_id = $2, assigned_at = NOW(), replaces_id = $4
+             WHERE id IN (
+                 SELECT id FROM license_keys WHERE product_id = $1 AND status = 'available'
+                 ORDER BY created_at LIMIT $3 FOR UPDATE SKIP LOCKED
+             )",
+            settings.product_id,
+            order_id,
+            count,
+            replaces_id,
+        )
+        .execute(&mut **tx)
+        .await?;
+        return Ok(res.rows_affected() as i64);
+    }
+
+    let mut taken = 0;
+    while taken < count {
+        // A collision with an existing key is skipped and retried
+        let res = sqlx::query!(
+            "INSERT INTO license_keys (product_id, license_key, status, order_id, assigned_at, replaces_id)
+             VALUES ($1, $2, 'assigned', $3, NOW(), $4)
+             ON CONFLICT (product_id, license_key) DO NOTHING",
+            settings.product_id,
+            generate_key(&settings.key_pattern),
+            order_id,
+            replaces_id,
+        )
+        .execute(&mut **tx)
+        .await?;
+        taken += res.rows_affected() as i64;
+    }
+    Ok(taken)
+}
+
+// Assign the keys the order is still owed. Safe to call again, or concurrently: each order is
+// only filled once, and reissued keys don't count towards what it is owed
+async fn assign_missing(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Vec<ProductAssignment>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!("SELECT pg_advisory_xact_lock(hashtext($1::UUID::TEXT))", order_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let owed: Vec<(i32, i64)> = sqlx::query!(
+        r#"SELECT i.product_id AS "product_id!", SUM(i.quantity)::BIGINT * lp.keys_per_unit
+             - (SELECT COUNT(*) FROM license_keys k
+                WHERE k.order_id = $1 AND k.product_id = i.product_id AND k.replaces_id IS NULL) AS "missing!"
+         FROM order_items i
+         JOIN orders o ON o.id = i.order_id
+         JOIN license_products lp ON lp.product_id = i.product_id
+         WHERE i.order_id = $1 AND o.status IN ('completed', 'authorized', 'awaiting_payment')
+         GROUP BY i.product_id, lp.keys_per_unit"#,
+        order_id,
+    )
+    .map(|r| (r.product_id, r.missing))
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut assignments = Vec::new();
+    for (product_id, missing) in owed.into_iter().filter(|(_, missing)| *missing > 0) {
+        let settings = sqlx::query_as!(
+            LicenseProduct,
+            "SELECT product_id, source, key_pattern, keys_per_unit, low_pool_threshold, created_at, updated_at FROM license_products WHERE product_id = $1",
+            product_id,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        let assigned = take_keys(&mut tx, &settings, order_id, missing, None).await?;
+        assignments.push(ProductAssignment {
+            product_id,
+            source: settings.source,
+            low_pool_threshold: settings.low_pool_threshold,
+            assigned,
+            missing: missing - assigned,
+        });
+    }
+    tx.commit().await?;
+    Ok(assignments)
+}
+
+// The order's current (not revoked) keys
+pub async fn order_keys(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Vec<OrderLicenseKey>, sqlx::Error> {
+    sqlx::query_as!(
+        OrderLicenseKey,
+        r#"SELECT k.id, k.order_id AS "order_id!", k.product_id, p.name AS product_name, k.license_key, k.assigned_at
+         FROM license_keys k JOIN products p ON p.id = k.product_id
+         WHERE k.order_id = $1 AND k.status = 'assigned'
+         ORDER BY p.name, k.assigned_at, k.license_key"#,
+        order_id,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Assign what the order is owed and return all its keys; used by the confirmation emails
+pub async fn assign_for_order(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Vec<OrderLicenseKey>, sqlx::Error> {
+    assign_missing(pool, order_id).await?;
+    order_keys(pool, order_id).await
+}
+
+// Keys section for order emails; empty when the order has none
+pub fn keys_html(keys: &[OrderLicenseKey]) -> String {
+    if keys.is_empty() {
+        return String::new();
+    }
+    let rows: String = keys
+        .iter()
+        .map(|k| {
+            format!(
+                "<tr><td>{}</td><td><code>{}</code></td></tr>",
+                escape_html(&k.product_name),
+                escape_html(&k.license_key)
+            )
+        })
+        .collect();
+    format!(
+        "<p><strong>Your license keys:</strong></p>\
+         <table><thead><tr><th>Product</th><th>License key</th></tr></thead><tbody>{}</tbody></table>",
+        rows
+    )
+}
+
+// Email keys delivered after the confirmation (backordered or reissued)
+async fn send_keys_email(pool: &sqlx::PgPool, order_id: Uuid, keys: &[OrderLicenseKey], intro: &str) -> Result<(), String> {
+    let email: Option<Option<Encrypted>> = sqlx::query_scalar!(
+        r#"SELECT customer_email AS "email: Encrypted" FROM orders WHERE id = $1"#,
+        order_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))?;
+    let Some(email) = email.flatten() else {
+        return Ok(());
+    };
+    let html_body = load_branding(pool).await.email_html(
+        "Your License Keys",
+        &format!("<p>{}</p><p>Order ID: {}</p>{}", escape_html(intro), order_id, keys_html(keys)),
+    );
+    queue_email(pool, &email, &format!("Your license keys - {}", order_id), &html_body, EmailPriority::Immediate).await
+}
+
+// Assigns keys once an order is confirmed (also for orders without an email) and revokes the
+// keys of refunded orders
+pub struct LicenseKeySubscriber {
+    pool: Arc<sqlx::PgPool>,
+    ops: OpsNotifier,
+}
+
+impl LicenseKeySubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>, ops: OpsNotifier) -> Self {
+        Self { pool, ops }
+    }
+
+    async fn alert_on_shortage(&self, order_id: Uuid, assignments: &[ProductAssignment]) -> Result<(), sqlx::Error> {
+        for assignment in assignments.iter().filter(|a| a.source == "pool") {
+            let (name, available): (String, i64) = sqlx::query!(
+                r#"SELECT p.name, (SELECT COUNT(*) FROM license_keys k WHERE k.product_id = p.id AND k.status = 'available') AS "available!"
+                 FROM products p WHERE p.id = $1"#,
+                assignment.product_id,
+            )
+            .map(|r| (r.name, r.available))
+            .fetch_one(&*self.pool)
+            .await?;
+            if assignment.missing > 0 {
+                self.ops.notify(format!(
+                    ":key: {} is out of license keys; order {} is waiting on {} (upload more to deliver them)",
+                    name, order_id, assignment.missing
+                ));
+            } else if available < assignment.low_pool_threshold as i64
+                && available + assignment.assigned >= assignment.low_pool_threshold as i64
+            {
+                self.ops.notify(format!(":key: Only {} license keys left for {}", available, name));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for LicenseKeySubscriber {
+    fn name(&self) -> &'static str {
+        "license_keys"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        match event {
+            DomainEvent::OrderCreated { order_id, .. } => {
+                let assignments = assign_missing(&self.pool, *order_id)
+                    .await
+                    .map_err(|e| format!("DB error: {}", e))?;
+                self.alert_on_shortage(*order_id, &assignments)
+                    .await
+                    .map_err(|e| format!("DB error: {}", e))?;
+            }
+            DomainEvent::OrderRefunded { order_id, .. } => {
+                // Partial refunds leave the keys alone
+                let res = sqlx::query!(
+                    "UPDATE license_keys SET status = 'revoked', revoked_at = NOW(), revoke_reason = 'Order refunded'
+                     WHERE order_id = $1 AND status = 'assigned'
+                       AND EXISTS (SELECT 1 FROM orders WHERE id = $1 AND status = 'refunded')",
+                    order_id,
+                )
+                .execute(&*self.pool)
+                .await
+                .map_err(|e| format!("DB error: {}", e))?;
+                if res.rows_affected() > 0 {
+                    tracing::info!("Revoked {} license keys of refunded order {}", res.rows_affected(), order_id);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+async fn list_license_products(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<LicenseProductSummary>>, (StatusCode, String)> {
+    let rows = sqlx::query!(
+        r#"SELECT lp.product_id, lp.source, lp.key_pattern, lp.keys_per_unit, lp.low_pool_threshold, lp.created_at,
+                lp.updated_at, p.name AS product_name,
+                COUNT(k.id) FILTER (WHERE k.status = 'available') AS "available!",
+                COUNT(k.id) FILTER (WHERE k.status = 'assigned') AS "assigned!",
+                COUNT(k.id) FILTER (WHERE k.status = 'revoked') AS "revoked!"
+         FROM license_products lp
+         JOIN products p ON p.id = lp.product_id
+         LEFT JOIN license_keys k ON k.product_id = lp.product_id
+         GROUP BY lp.product_id, p.name
+         ORDER BY p.name"#,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let products = rows
+        .into_iter()
+        .map(|r| LicenseProductSummary {
+            settings: LicenseProduct {
+                product_id: r.product_id,
+                source: r.source,
+                key_pattern: r.key_pattern,
+                keys_per_unit: r.keys_per_unit,
+                low_pool_threshold: r.low_pool_threshold,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+            },
+            product_name: r.product_name,
+            available: r.available,
+            assigned: r.assigned,
+            revoked: r.revoked,
+        })
+        .collect();
+    Ok(Json(products))
+}
+
+// Make the product a license product, or change how its keys are made
+async fn set_license_product(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+    Json(input): Json<LicenseProductInput>,
+) -> Result<Json<LicenseProduct>, (StatusCode, String)> {
+    let source = input.source.trim().to_lowercase();
+    if !KEY_SOURCES.contains(&source.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, "source must be pool or generated".to_string()));
+    }
+    let key_pattern = input.key_pattern.as_deref().map(str::trim).filter(|p| !p.is_empty());
+    if let Some(pattern) = key_pattern {
+        if pattern.chars().filter(|c| *c == 'X').count() < MIN_RANDOM_CHARS || pattern.len() > 100 {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("key_pattern needs at least {} X characters (max 100 characters)", MIN_RANDOM_CHARS),
+            ));
+        }
+    }
+    if input.keys_per_unit.is_some_and(|n| !(1..=100).contains(&n)) {
+        return Err((StatusCode::BAD_REQUEST, "keys_per_unit must be between 1 and 100".to_string()));
+    }
+    if input.low_pool_threshold.is_some_and(|n| n < 0) {
+        return Err((StatusCode::BAD_REQUEST, "low_pool_threshold must be at least 0".to_string()));
+    }
+
+    let settings = sqlx::query_as!(
+        LicenseProduct,
+        "INSERT INTO license_products (product_id, source, key_pattern, keys_per_unit, low_pool_threshold)
+         VALUES ($1, $2, COALESCE($3, 'XXXXX-XXXXX-XXXXX-XXXXX'), COALESCE($4, 1), COALESCE($5, 10))
+         ON CONFLICT (product_id) DO UPDATE SET
+             source = EXCLUDED.source,
+             key_pattern = COALESCE($3, license_products.key_pattern),
+             keys_per_unit = COALESCE($4, license_products.keys_per_unit),
+             low_pool_threshold = COALESCE($5, license_products.low_pool_threshold),
+             updated_at = NOW()
+         RETURNING product_id, source, key_pattern, keys_per_unit, low_pool_threshold, created_at, updated_at",
+        product_id,
+        &source,
+        key_pattern,
+        input.keys_per_unit,
+        input.low_pool_threshold,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+            (StatusCode::NOT_FOUND, "Product not found".to_string())
+        }
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)),
+    })?;
+    tracing::info!("{} set product {} to {} license keys", admin.username, product_id, source);
+    Ok(Json(settings))
+}
+
+// Stop issuing keys for the product; keys already issued stay valid
+async fn remove_license_product(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM license_products WHERE product_id = $1", product_id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Not a license product".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_product_keys(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+    Query(query): Query<KeyListQuery>,
+) -> Result<Json<Vec<LicenseKey>>, (StatusCode, String)> {
+    let keys = sqlx::query_as!(
+        LicenseKey,
+        "SELECT id, product_id, license_key, status, order_id, assigned_at, replaces_id, revoked_at, revoked_by, revoke_reason, created_at FROM license_keys WHERE product_id = $1 AND ($2::VARCHAR IS NULL OR status = $2)
+         ORDER BY created_at DESC",
+        product_id,
+        query.status,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(keys))
+}
+
+// Add keys to the product's pool, then deliver keys to orders that were waiting on them
+async fn upload_keys(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+    Json(request): Json<UploadKeysRequest>,
+) -> Result<Json<UploadKeysResult>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let source: Option<String> = sqlx::query_scalar!(
+        "SELECT source FROM license_products WHERE product_id = $1",
+        product_id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    match source.as_deref() {
+        Some("pool") => {}
+        Some(_) => return Err((StatusCode::CONFLICT, "Product generates its keys".to_string())),
+        None => return Err((StatusCode::NOT_FOUND, "Not a license product".to_string())),
+    }
+    let mut keys: Vec<&str> = request.keys.iter().map(|k| k.trim()).filter(|k| !k.is_empty()).collect();
+    if keys.is_empty() || keys.len() > MAX_UPLOAD_KEYS {
+        return Err((StatusCode::BAD_REQUEST, format!("Upload between 1 and {} keys", MAX_UPLOAD_KEYS)));
+    }
+    let submitted = keys.len() as u64;
+    keys.sort();
+    keys.dedup();
+
+    let res = sqlx::query!(
+        "INSERT INTO license_keys (product_id, license_key)
+         SELECT $1, key FROM UNNEST($2::TEXT[]) AS key
+         ON CONFLICT (product_id, license_key) DO NOTHING",
+        product_id,
+        &keys as _,
+    )
+    .execute(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    let added = res.rows_affected();
+
+    // Orders still owed keys for this product, oldest first
+    let waiting: Vec<Uuid> = sqlx::query_scalar!(
+        "SELECT i.order_id
+         FROM order_items i
+         JOIN orders o ON o.id = i.order_id
+         JOIN license_products lp ON lp.product_id = i.product_id
+         WHERE i.product_id = $1 AND o.status IN ('completed', 'authorized', 'awaiting_payment')
+         GROUP BY i.order_id, lp.keys_per_unit, o.created_at
+         HAVING SUM(i.quantity) * lp.keys_per_unit > (
+             SELECT COUNT(*) FROM license_keys k
+             WHERE k.order_id = i.order_id AND k.product_id = $1 AND k.replaces_id IS NULL
+         )
+         ORDER BY o.created_at",
+        product_id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    let mut orders_filled = 0;
+    for order_id in waiting {
+        let assignments = assign_missing(&app_state.pool, order_id).await.map_err(db_error)?;
+        if assignments.iter().all(|a| a.assigned == 0) {
+            break; // The pool ran out again
+        }
+        orders_filled += 1;
+        let keys = order_keys(&app_state.pool, order_id).await.map_err(db_error)?;
+        if let Err(e) = send_keys_email(&app_state.pool, order_id, &keys, "Your license keys are ready.").await {
+            tracing::error!("Failed to email license keys for order {}: {}", order_id, e);
+        }
+    }
+
+    tracing::info!(
+        "{} uploaded {} license keys for product {} ({} filled orders)",
+        admin.username, added, product_id, orders_filled
+    );
+    Ok(Json(UploadKeysResult {
+        added,
+        duplicates: submitted - added,
+        orders_filled,
+    }))
+}
+
+async fn list_order_keys(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<Vec<LicenseKey>>, (StatusCode, String)> {
+    let keys = sqlx::query_as!(
+        LicenseKey,
+        "SELECT id, product_id, license_key, status, order_id, assigned_at, replaces_id, revoked_at, revoked_by, revoke_reason, created_at FROM license_keys WHERE order_id = $1 ORDER BY product_id, assigned_at",
+        order_id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(keys))
+}
+
+// Revoke a key that isn't revoked yet, returning it; None when there is no such key
+async fn revoke(
+    executor: impl sqlx::PgExecutor<'_>,
+    key_id: Uuid,
+    admin: &str,
+    reason: Option<&str>,
+) -> Result<Option<LicenseKey>, sqlx::Error> {
+    sqlx::query_as!(
+        LicenseKey,
+        "UPDATE license_keys SET status = 'revoked', revoked_at = NOW(), revoked_by = $2, revoke_reason = $3
+         WHERE id = $1 AND status <> 'revoked'
+         RETURNING id, product_id, license_key, status, order_id, assigned_at, replaces_id, revoked_at, revoked_by, revoke_reason, created_at",
+        key_id,
+        admin,
+        reason,
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+async fn revoke_key(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+    body: Option<Json<RevokeKeyRequest>>,
+) -> Result<Json<LicenseKey>, (StatusCode, String)> {
+    let request = body.map(|Json(r)| r).unwrap_or_default();
+    let reason = request.reason.as_deref().map(str::trim).filter(|r| !r.is_empty());
+    let key = revoke(&*app_state.pool, key_id, &admin.username, reason)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::CONFLICT, "Key not found or already revoked".to_string()))?;
+    tracing::info!("{} revoked license key {}", admin.username, key_id);
+    Ok(Json(key))
+}
+
+// Revoke an order's key and give the order a new one for the same product
+async fn reissue_key(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+    body: Option<Json<RevokeKeyRequest>>,
+) -> Result<Json<Vec<OrderLicenseKey>>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let request = body.map(|Json(r)| r).unwrap_or_default();
+    let reason = request.reason.as_deref().map(str::trim).filter(|r| !r.is_empty()).unwrap_or("Reissued");
+
+    let mut tx = app_state.pool.begin().await.map_err(db_error)?;
+    let key = sqlx::query_as!(LicenseKey, "SELECT id, product_id, license_key, status, order_id, assigned_at, replaces_id, revoked_at, revoked_by, revoke_reason, created_at FROM license_keys WHERE id = $1 FOR UPDATE", key_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "License key not found".to_string()))?;
+    let order_id = key
+        .order_id
+        .ok_or((StatusCode::CONFLICT, "Only keys assigned to an order can be reissued".to_string()))?;
+    let replaced: bool = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM license_keys WHERE replaces_id = $1) AS "replaced!""#,
+        key_id,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    if replaced {
+        return Err((StatusCode::CONFLICT, "Key was already reissued".to_string()));
+    }
+    let settings = sqlx::query_as!(
+        LicenseProduct,
+        "SELECT product_id, source, key_pattern, keys_per_unit, low_pool_threshold, created_at, updated_at FROM license_products WHERE product_id = $1",
+        key.product_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error)?
+    .ok_or((StatusCode::CONFLICT, "Product no longer issues license keys".to_string()))?;
+
+    if key.status != "revoked" {
+        revoke(&mut *tx, key_id, &admin.username, Some(reason)).await.map_err(db_error)?;
+    }
+    if take_keys(&mut tx, &settings, order_id, 1, Some(key_id)).await.map_err(db_error)? == 0 {
+        return Err((StatusCode::CONFLICT, "No license keys left in the pool".to_string()));
+    }
+    tx.commit().await.map_err(db_error)?;
+
+    let keys = order_keys(&app_state.pool, order_id).await.map_err(db_error)?;
+    if request.notify_customer {
+        if let Err(e) = send_keys_email(&app_state.pool, order_id, &keys, "We've issued you a new license key. Your current keys are below.").await {
+            tracing::error!("Failed to email reissued license key for order {}: {}", order_id, e);
+        }
+    }
+    tracing::info!("{} reissued license key {} on order {}", admin.username, key_id, order_id);
+    Ok(Json(keys))
+}
+
+// Keys from the signed-in customer's orders
+async fn get_my_keys(
+    customer: AuthenticatedCustomer,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<OrderLicenseKey>>, (StatusCode, String)> {
+    let keys = sqlx::query_as!(
+        OrderLicenseKey,
+        r#"SELECT k.id, k.order_id AS "order_id!", k.product_id, p.name AS product_name, k.license_key, k.assigned_at
+         FROM license_keys k
+         JOIN orders o ON o.id = k.order_id
+         JOIN products p ON p.id = k.product_id
+         WHERE o.customer_id = $1 AND k.status = 'assigned'
+         ORDER BY k.assigned_at DESC"#,
+        customer.customer_id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(keys))
+}
@@ -0,0 +1,66 @@
+// Load Profile Module - `--profile load`: the server with every external provider switched off
+// For load testing the checkout path (backend/loadtest/checkout.js) against a local or staging
+// database without charging cards, buying postage or sending mail:
+// - Stripe isn't called. create-payment-intent prices the cart, applies discounts and saves
+//   the cart, seat holds and stock reservations as usual, then answers with a stub intent
+//   (pi_load_...) instead of creating one. STRIPE_SECRET_KEY isn't required, and a live key is refused
+// - EasyPost, Brevo, Mailchimp, Letre, SMTP, SMS, Square, Meilisearch, the ERP webhook and the
+//   ops and alert webhooks are treated as unconfigured, and shipping is quoted from the rules
+// - background jobs are off, so nothing queued during a run is sent, and no scheduler competes
+//   with the test for the database
+// Stub intents never get a webhook: their stock and seats go back when the holds expire
+
+use stripe::{Currency, PaymentIntent, PaymentIntentStatus};
+
+use crate::app_config::AppConfig;
+
+// Whether the command line asks for the load profile (`--profile load` or `--profile=load`)
+pub fn requested(args: impl IntoIterator<Item = String>) -> Result<bool, String> {
+    let mut args = args.into_iter();
+    let mut profile = None;
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            profile = Some(args.next().unwrap_or_default());
+        } else if let Some(value) = arg.strip_prefix("--profile=") {
+            profile = Some(value.to_string());
+        }
+    }
+    match profile.as_deref() {
+        None | Some("standard") => Ok(false),
+        Some("load") => Ok(true),
+        Some(other) => Err(format!("--profile: must be one of standard, load, got \"{}\"", other)),
+    }
+}
+
+// Clear every provider credential and webhook so nothing leaves the server
+pub fn disable_providers(config: &mut AppConfig) {
+    config.square.access_token = None;
+    config.smtp.host = None;
+    config.brevo.api_key = None;
+    config.mailchimp.api_key = None;
+    config.letre.api_key = None;
+    config.sms.textbelt_api_key = None;
+    config.twilio.account_sid = None;
+    config.twilio.auth_token = None;
+    config.easypost.api_key = None;
+    config.shipping.rate_provider = "rules".to_string();
+    config.integrations.erp_webhook_url = None;
+    config.search.meilisearch_url = None;
+    config.alerts.slack_webhook_url = None;
+    config.alerts.email = None;
+    config.ops.webhook_url = None;
+    config.features.background_jobs = false;
+}
+
+// Stands in for the intent Stripe would have created
+pub fn payment_intent(amount: i64, currency: Currency) -> PaymentIntent {
+    let id = format!("pi_load_{}", uuid::Uuid::new_v4().simple());
+    PaymentIntent {
+        client_secret: Some(format!("{}_secret_load", id)),
+        id: id.parse().expect("pi_ prefix is a valid payment intent id"),
+        amount,
+        currency,
+        status: PaymentIntentStatus::RequiresPaymentMethod,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,203 @@
+// Log Scrubbing Module - Redacts customer PII and credentials from logs and webhook payloads
+// Tracing output is written through ScrubbingWriter, which masks email addresses, phone numbers,
+// card numbers and tokens in each line, and the values of sensitive fields (`email=...`).
+// Webhook payloads are scrubbed the same way before they're stored in webhook_events, so both
+// can be shared with support. Fields named in PII_SCRUB_ALLOWED_FIELDS are left as they are
+
+use regex::{Captures, Regex};
+use serde_json::Value;
+use std::io::{self, Write};
+use std::sync::OnceLock;
+
+const REDACTED: &str = "[redacted]";
+const BACKFILL_BATCH: i64 = 200;
+
+// Fields whose values are always personal or secret
+const SENSITIVE_FIELDS: &[&str] = &[
+    "email", "phone", "name", "first_name", "last_name", "given_name", "family_name", "address", "line1", "line2",
+    "postal_code", "ip", "last4", "exp_month", "exp_year", "fingerprint", "cvc", "card_number", "iban", "account_number",
+    "routing_number", "token", "secret", "password", "authorization", "cookie", "api_key",
+];
+const SENSITIVE_SUFFIXES: &[&str] = &["_email", "_phone", "_phone_number", "_address", "_token", "_secret", "_password"];
+
+struct Scrubber {
+    allowed_fields: Vec<String>, // Lowercase
+    email: Regex,
+    digits: Regex, // Phone and card number candidates, checked in scrub_digits
+    token: Regex,
+    bearer: Regex,
+    field: Regex,
+}
+
+static SCRUBBER: OnceLock<Scrubber> = OnceLock::new();
+
+impl Scrubber {
+    fn new(allowed_fields: &[String]) -> Self {
+        // tracing colours field names, so `key=value` may have escape codes around the `=`
+        let ansi = r"(?:\x1b\[[0-9;]*m)*";
+        Self {
+            allowed_fields: allowed_fields.iter().map(|f| f.to_lowercase()).collect(),
+            email: Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}").expect("email pattern"),
+            digits: Regex::new(r"\+?\(?\d[\d ().-]{8,23}\d").expect("digits pattern"),
+            token: Regex::new(
+                r"\b(?:(?:sk|rk)_(?:live|test)_[A-Za-z0-9]+|whsec_[A-Za-z0-9]+|(?:pi|seti)_[A-Za-z0-9]+_secret_[A-Za-z0-9]+|eyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*)",
+            )
+            .expect("token pattern"),
+            bearer: Regex::new(r"(?i)\b(bearer|basic)\s+[A-Za-z0-9._~+/=-]+").expect("bearer pattern"),
+            field: Regex::new(&format!(r#"(\x1b\[[0-9;]*m|\b)([A-Za-z_][A-Za-z0-9_]*)({ansi}={ansi})(?:"(?:[^"\\]|\\.)*"|[^\s\x1b,]+)"#))
+                .expect("field pattern"),
+        }
+    }
+
+    fn is_sensitive(&self, field: &str) -> bool {
+        let field = field.to_lowercase();
+        if self.allowed_fields.contains(&field) {
+            return false;
+        }
+        SENSITIVE_FIELDS.contains(&field.as_str()) || SENSITIVE_SUFFIXES.iter().any(|suffix| field.ends_with(suffix))
+    }
+
+    fn scrub_text(&self, text: &str) -> String {
+        let text = self.field.replace_all(text, |caps: &Captures| {
+            if self.is_sensitive(&caps[2]) {
+                format!("{}{}{}{}", &caps[1], &caps[2], &caps[3], REDACTED)
+            } else {
+                caps[0].to_string()
+            }
+        });
+        let text = self.bearer.replace_all(&text, "$1 [token]");
+        let text = self.token.replace_all(&text, "[token]");
+        let text = self.email.replace_all(&text, "[email]");
+        self.scrub_digits(&text)
+    }
+
+    // A run of 10-15 digits is a phone number and 13-19 digits passing the Luhn check a card
+    // number. Runs touching letters, dashes or dots are ids, UUIDs, dates or amounts and are kept
+    fn scrub_digits(&self, text: &str) -> String {
+        self.digits
+            .replace_all(text, |caps: &Captures| {
+                let found = caps.get(0).expect("match");
+                let before = text[..found.start()].chars().next_back();
+                let after = text[found.end()..].chars().next();
+                let joined = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'));
+                let digits: Vec<u32> = found.as_str().chars().filter_map(|c| c.to_digit(10)).collect();
+                if joined(before) || joined(after) {
+                    found.as_str().to_string()
+                } else if (13..=19).contains(&digits.len()) && luhn_valid(&digits) {
+                    "[card]".to_string()
+                } else if (10..=15).contains(&digits.len()) {
+                    "[phone]".to_string()
+                } else {
+                    found.as_str().to_string()
+                }
+            })
+            .into_owned()
+    }
+
+    fn scrub_json(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if self.allowed_fields.contains(&key.to_lowercase()) {
+                        continue;
+                    }
+                    if self.is_sensitive(key) && !value.is_null() {
+                        *value = Value::String(REDACTED.to_string());
+                    } else {
+                        self.scrub_json(value);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.scrub_json(item)),
+            Value::String(text) => *text = self.scrub_text(text),
+            _ => {}
+        }
+    }
+}
+
+fn luhn_valid(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| if i % 2 == 1 { if d * 2 > 9 { d * 2 - 9 } else { d * 2 } } else { d })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+fn scrubber() -> &'static Scrubber {
+    SCRUBBER.get_or_init(|| Scrubber::new(&[]))
+}
+
+// Set up tracing with scrubbed output; call once, after the configuration is loaded
+pub fn init(allowed_fields: &[String]) {
+    SCRUBBER.get_or_init(|| Scrubber::new(allowed_fields));
+    tracing_subscriber::fmt().with_writer(|| ScrubbingWriter { stderr: false }).init();
+}
+
+// The same, logging to stderr so a command's own output on stdout stays clean (rcom-cli)
+pub fn init_stderr(allowed_fields: &[String]) {
+    SCRUBBER.get_or_init(|| Scrubber::new(allowed_fields));
+    tracing_subscriber::fmt().with_writer(|| ScrubbingWriter { stderr: true }).init();
+}
+
+// Writes tracing output to stdout (or stderr) with PII redacted. The formatter writes each event in one call
+pub struct ScrubbingWriter {
+    stderr: bool,
+}
+
+impl Write for ScrubbingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        let scrubbed = scrubber().scrub_text(&text);
+        if self.stderr {
+            io::stderr().lock().write_all(scrubbed.as_bytes())?;
+        } else {
+            io::stdout().lock().write_all(scrubbed.as_bytes())?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.stderr {
+            io::stderr().flush()
+        } else {
+            io::stdout().flush()
+        }
+    }
+}
+
+// A webhook payload with personal and secret values replaced, ready to store
+pub fn scrub_json(mut value: Value) -> Value {
+    scrubber().scrub_json(&mut value);
+    value
+}
+
+// Scrub webhook payloads stored before scrubbing existed
+pub async fn backfill(pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let mut scrubbed = 0;
+    loop {
+        let rows: Vec<(sqlx::types::Uuid, Value)> =
+            sqlx::query!("SELECT id, payload FROM webhook_events WHERE NOT payload_scrubbed LIMIT $1", BACKFILL_BATCH)
+                .map(|r| (r.id, r.payload))
+                .fetch_all(pool)
+                .await?;
+        if rows.is_empty() {
+            break;
+        }
+        scrubbed += rows.len();
+        for (id, payload) in rows {
+            sqlx::query!(
+                "UPDATE webhook_events SET payload = $1, payload_scrubbed = TRUE WHERE id = $2",
+                scrub_json(payload),
+                id,
+            )
+            .execute(pool)
+            .await?;
+        }
+    }
+    if scrubbed > 0 {
+        tracing::info!("Scrubbed PII from {} stored webhook payloads", scrubbed);
+    }
+    Ok(())
+}
@@ -0,0 +1,426 @@
+// Loyalty Module - Points earned on completed orders
+// Customers earn points per dollar spent, redeem them at checkout at a configurable
+// conversion rate (held while they pay), and lose the points earned on an order when it is
+// refunded
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::customer_auth::AuthenticatedCustomer;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::unit_of_work::WorkError;
+use crate::AppState;
+
+pub const METADATA_KEY: &str = "loyalty_points_redeemed";
+
+// Why a ledger entry was made
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
+pub enum PointsReason {
+    #[sqlx(rename = "earned")]
+    #[serde(rename = "earned")]
+    Earned,
+    #[sqlx(rename = "redeemed")]
+    #[serde(rename = "redeemed")]
+    Redeemed,
+    #[sqlx(rename = "reversed")]
+    #[serde(rename = "reversed")]
+    Reversed,
+    #[sqlx(rename = "adjustment")]
+    #[serde(rename = "adjustment")]
+    Adjustment,
+}
+
+// Database model for ledger entries
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct LoyaltyEntry {
+    pub id: Uuid,
+    pub customer_id: Uuid,
+    pub points: i32, // negative for redemptions and reversals
+    pub reason: PointsReason,
+    pub order_id: Option<Uuid>,
+    pub note: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoyaltyAccount {
+    pub balance: i64,
+    pub balance_value: i64, // what the balance is worth at checkout, in cents
+    pub points_per_dollar: i64,
+    pub points_per_dollar_off: i64,
+    pub entries: Vec<LoyaltyEntry>,
+}
+
+#[derive(Deserialize)]
+pub struct AdjustPointsRequest {
+    pub points: i32, // positive or negative
+    pub note: Option<String>,
+}
+
+// Earn and redemption rates, from LOYALTY_POINTS_PER_DOLLAR (default 1 point per
+// dollar spent) and LOYALTY_REDEEM_POINTS_PER_DOLLAR (default 100 points = $1 off)
+#[derive(Debug, Clone, Copy)]
+pub struct LoyaltyConfig {
+    pub points_per_dollar: i64,
+    pub points_per_dollar_off: i64,
+}
+
+impl LoyaltyConfig {
+    pub fn from_env() -> Self {
+        let rewards = &app_config::get().rewards;
+        Self {
+            points_per_dollar: rewards.loyalty_points_per_dollar,
+            points_per_dollar_off: rewards.loyalty_redeem_points_per_dollar,
+        }
+    }
+
+    // Points earned for an order total; partial dollars don't earn
+    pub fn points_for(&self, amount_cents: i64) -> i64 {
+        amount_cents / 100 * self.points_per_dollar
+    }
+
+    // Discount in cents for redeeming a number of points
+    pub fn value_of(&self, points: i64) -> i64 {
+        points * 100 / self.points_per_dollar_off
+    }
+
+    // Most points that can be redeemed for a discount of at most `cents`
+    pub fn points_for_discount(&self, cents: i64) -> i64 {
+        cents * self.points_per_dollar_off / 100
+    }
+}
+
+// Current balance for a customer
+pub async fn balance<'e, E>(executor: E, customer_id: Uuid) -> Result<i64, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    sqlx::query_scalar!(
+        r#"SELECT COALESCE(SUM(points), 0)::BIGINT AS "points!" FROM loyalty_points WHERE customer_id = $1"#,
+        customer_id,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+// Append a ledger entry
+pub async fn add_entry<'e, E>(
+    executor: E,
+    customer_id: Uuid,
+    points: i64,
+    reason: PointsReason,
+    order_id: Option<Uuid>,
+    note: Option<&str>,
+    created_by: Option<&str>,
+) -> Result<LoyaltyEntry, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    sqlx::query_as!(
+        LoyaltyEntry,
+        r#"INSERT INTO loyalty_points (customer_id, points, reason, order_id, note, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING id, customer_id, points, reason AS "reason: PointsReason", order_id, note, created_by, created_at"#,
+        customer_id,
+        points as i32,
+        reason as _,
+        order_id,
+        note,
+        created_by,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+// Points a customer can redeem at checkout: the balance less what open checkouts hold
+pub async fn available(executor: impl sqlx::PgExecutor<'_>, customer_id: Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT (COALESCE((SELECT SUM(points) FROM loyalty_points WHERE customer_id = $1), 0)
+                 - COALESCE((SELECT SUM(points) FROM loyalty_point_holds WHERE customer_id = $1 AND status = 'held'), 0)
+                )::BIGINT AS "available!""#,
+        customer_id,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+// Points to redeem for a checkout: the requested points, capped at the available balance and
+// at what can be taken off `amount` without going under Stripe's minimum charge.
+// Returns (points, discount in cents).
+pub async fn quote_redemption(
+    pool: &sqlx::PgPool,
+    customer_id: Uuid,
+    requested: i64,
+    amount: i64,
+) -> Result<(i64, i64), sqlx::Error> {
+    let config = LoyaltyConfig::from_env();
+    let available = available(pool, customer_id).await?;
+    let max_discount = (amount - crate::coupons::MIN_CHARGE_CENTS.min(amount)).max(0);
+    let points = requested
+        .min(available)
+        .min(config.points_for_discount(max_discount))
+        .max(0);
+    Ok((points, config.value_of(points)))
+}
+
+// Hold the points redeemed on a checkout until its order is created, as part of the checkout's
+// unit of work, the same way stock_reservations holds its items. Fails the checkout with 409
+// when another checkout spent the points since this one was priced
+pub async fn hold_for_cart(
+    conn: &mut sqlx::PgConnection,
+    cart_id: Uuid,
+    customer_id: Uuid,
+    points: i64,
+) -> Result<(), WorkError<(StatusCode, String)>> {
+    // Serialize concurrent checkouts of the same customer
+    sqlx::query!("SELECT id FROM customers WHERE id = $1 FOR UPDATE", customer_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    let available = available(&mut *conn, customer_id).await?;
+    if available < points {
+        return Err(WorkError::Aborted((
+            StatusCode::CONFLICT,
+            format!("Only {} loyalty points left; refresh the checkout", available.max(0)),
+        )));
+    }
+    sqlx::query!(
+        "INSERT INTO loyalty_point_holds (cart_id, customer_id, points) VALUES ($1, $2, $3)",
+        cart_id,
+        customer_id,
+        points,
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// Give the checkout's held points back to the customer
+pub async fn release_hold(executor: impl sqlx::PgExecutor<'_>, cart_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE loyalty_point_holds SET status = 'released', released_at = NOW()
+         WHERE cart_id = $1 AND status = 'held'",
+        cart_id,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// Redeem the points the checkout held once its order exists. None when nothing is held any
+// more: the hold was released, or another Stripe event already created the order
+pub async fn redeem_held(conn: &mut sqlx::PgConnection, cart_id: Uuid, order_id: Uuid) -> Result<Option<i64>, sqlx::Error> {
+    let held = sqlx::query!(
+        "UPDATE loyalty_point_holds SET status = 'converted'
+         WHERE cart_id = $1 AND status = 'held'
+         RETURNING customer_id, points",
+        cart_id,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(held) = held else {
+        return Ok(None);
+    };
+    add_entry(&mut *conn, held.customer_id, -held.points, PointsReason::Redeemed, Some(order_id), None, None).await?;
+    Ok(Some(held.points))
+}
+
+// Award points for a completed order (at most once per order)
+async fn award_for_order(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Option<i64>, sqlx::Error> {
+    let order: Option<(Option<Uuid>, i64)> =
+        sqlx::query!("SELECT customer_id, total_amount FROM orders WHERE id = $1 AND status = 'completed'", order_id)
+            .map(|r| (r.customer_id, r.total_amount))
+            .fetch_optional(pool)
+            .await?;
+    let (customer_id, total_amount) = match order {
+        Some((Some(customer_id), total_amount)) => (customer_id, total_amount),
+        _ => return Ok(None),
+    };
+    let points = LoyaltyConfig::from_env().points_for(total_amount);
+    if points <= 0 {
+        return Ok(None);
+    }
+    let res = sqlx::query!(
+        "INSERT INTO loyalty_points (customer_id, points, reason, order_id)
+         VALUES ($1, $2, 'earned', $3)
+         ON CONFLICT (order_id, reason) WHERE order_id IS NOT NULL AND reason IN ('earned', 'redeemed', 'reversed')
+         DO NOTHING",
+        customer_id,
+        points as i32,
+        order_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok((res.rows_affected() > 0).then_some(points))
+}
+
+// Undo a refunded order's points: take back what it earned and give back what was
+// redeemed on it. The balance may go negative if the earned points were already spent.
+async fn reverse_for_order(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Option<i64>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let entries: Vec<(Uuid, i64)> = sqlx::query!(
+        r#"SELECT customer_id, SUM(points)::BIGINT AS "net!" FROM loyalty_points
+         WHERE order_id = $1 AND reason IN ('earned', 'redeemed')
+         GROUP BY customer_id"#,
+        order_id,
+    )
+    .map(|r| (r.customer_id, r.net))
+    .fetch_all(&mut *tx)
+    .await?;
+    let (customer_id, net) = match entries.as_slice() {
+        [(customer_id, net)] if *net != 0 => (*customer_id, *net),
+        _ => return Ok(None),
+    };
+    let res = sqlx::query!(
+        "INSERT INTO loyalty_points (customer_id, points, reason, order_id, note)
+         VALUES ($1, $2, 'reversed', $3, 'Order refunded')
+         ON CONFLICT (order_id, reason) WHERE order_id IS NOT NULL AND reason IN ('earned', 'redeemed', 'reversed')
+         DO NOTHING",
+        customer_id,
+        -net as i32,
+        order_id,
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok((res.rows_affected() > 0).then_some(-net))
+}
+
+// Awards points on new orders and reverses them on refunds
+pub struct LoyaltySubscriber {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl LoyaltySubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for LoyaltySubscriber {
+    fn name(&self) -> &'static str {
+        "loyalty"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        match event {
+            DomainEvent::OrderCreated { order_id, .. } => {
+                if let Some(points) = award_for_order(&self.pool, *order_id)
+                    .await
+                    .map_err(|e| format!("DB error: {}", e))?
+                {
+                    tracing::info!("Awarded {} loyalty points for order {}", points, order_id);
+                }
+            }
+            DomainEvent::OrderRefunded { order_id, .. } => {
+                if let Some(points) = reverse_for_order(&self.pool, *order_id)
+                    .await
+                    .map_err(|e| format!("DB error: {}", e))?
+                {
+                    tracing::info!("Reversed loyalty points for refunded order {} ({:+})", order_id, points);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+async fn load_account(pool: &sqlx::PgPool, customer_id: Uuid) -> Result<LoyaltyAccount, sqlx::Error> {
+    let entries = sqlx::query_as!(
+        LoyaltyEntry,
+        r#"SELECT id, customer_id, points, reason AS "reason: PointsReason", order_id, note, created_by, created_at FROM loyalty_points WHERE customer_id = $1 ORDER BY created_at DESC"#,
+        customer_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    let config = LoyaltyConfig::from_env();
+    let balance: i64 = entries.iter().map(|e| e.points as i64).sum();
+    Ok(LoyaltyAccount {
+        balance,
+        balance_value: config.value_of(balance.max(0)),
+        points_per_dollar: config.points_per_dollar,
+        points_per_dollar_off: config.points_per_dollar_off,
+        entries,
+    })
+}
+
+pub fn loyalty_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/customers/:id/loyalty", get(get_customer_points).post(adjust_points))
+        .route("/api/customers/me/loyalty", get(get_my_points))
+        .with_state(app_state)
+}
+
+async fn get_customer_points(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<LoyaltyAccount>, (StatusCode, String)> {
+    load_account(&app_state.pool, id)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))
+}
+
+async fn adjust_points(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(input): Json<AdjustPointsRequest>,
+) -> Result<(StatusCode, Json<LoyaltyEntry>), (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    if input.points == 0 {
+        return Err((StatusCode::BAD_REQUEST, "points must be non-zero".to_string()));
+    }
+
+    let mut tx = app_state.pool.begin().await.map_err(db_err)?;
+    let exists: bool = sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM customers WHERE id = $1 FOR UPDATE) AS "exists!""#, id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_err)?;
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, "Customer not found".to_string()));
+    }
+    if input.points < 0 && balance(&mut *tx, id).await.map_err(db_err)? + (input.points as i64) < 0 {
+        return Err((StatusCode::BAD_REQUEST, "Adjustment would make the balance negative".to_string()));
+    }
+
+    let entry = add_entry(
+        &mut *tx,
+        id,
+        input.points as i64,
+        PointsReason::Adjustment,
+        None,
+        input.note.as_deref().map(str::trim).filter(|n| !n.is_empty()),
+        Some(&admin.username),
+    )
+    .await
+    .map_err(db_err)?;
+    tx.commit().await.map_err(db_err)?;
+
+    tracing::info!("{} adjusted loyalty points for customer {} by {}", admin.username, id, input.points);
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+async fn get_my_points(
+    customer: AuthenticatedCustomer,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<LoyaltyAccount>, (StatusCode, String)> {
+    load_account(&app_state.pool, customer.customer_id)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))
+}
@@ -0,0 +1,208 @@
+// Mailchimp Marketing Integration
+// Client for Mailchimp audiences, member tags and regular campaigns (Marketing API v3)
+// The API key ends in its datacenter ("...-us21"), which selects the API host
+
+use md5::{Digest, Md5};
+use reqwest::{Client, Response};
+use serde_json::{json, Value};
+
+use crate::app_config;
+use crate::request_id::WithRequestId;
+
+#[derive(Clone, Debug)]
+pub struct MailchimpConfig {
+    pub api_key: String,
+    pub api_base_url: String,
+    pub list_id: Option<String>,
+}
+
+impl MailchimpConfig {
+    pub fn from_env() -> Option<Self> {
+        let mailchimp = &app_config::get().mailchimp;
+        let api_key = mailchimp.api_key.as_ref()?.expose().to_string();
+        let api_base_url = match &mailchimp.api_base_url {
+            Some(url) => url.clone(),
+            None => {
+                let datacenter = api_key.rsplit_once('-')?.1;
+                format!("https://{}.api.mailchimp.com/3.0", datacenter)
+            }
+        };
+        Some(Self {
+            api_key,
+            api_base_url,
+            list_id: mailchimp.list_id.clone(),
+        })
+    }
+}
+
+// Members are addressed by the MD5 hash of their lowercased email
+fn subscriber_hash(email: &str) -> String {
+    hex::encode(Md5::digest(email.trim().to_lowercase().as_bytes()))
+}
+
+async fn error_for(response: Response) -> String {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    format!("Mailchimp API returned error status {}: {}", status, body)
+}
+
+pub struct MailchimpClient {
+    config: MailchimpConfig,
+    client: Client,
+}
+
+impl MailchimpClient {
+    pub fn new(config: MailchimpConfig) -> Self {
+        Self {
+            config,
+            client: Client::new(),
+        }
+    }
+
+    // Explicit audience id, or MAILCHIMP_LIST_ID
+    pub fn audience<'a>(&'a self, list_id: Option<&'a str>) -> Result<&'a str, String> {
+        list_id
+            .or(self.config.list_id.as_deref())
+            .ok_or_else(|| "No Mailchimp audience given. Set MAILCHIMP_LIST_ID in environment.".to_string())
+    }
+
+    fn member_url(&self, list_id: &str, email: &str) -> String {
+        format!("{}/lists/{}/members/{}", self.config.api_base_url, list_id, subscriber_hash(email))
+    }
+
+    /// Add or update an audience member, subscribing them if they are new
+    pub async fn upsert_member(&self, list_id: &str, email: &str, first_name: Option<&str>) -> Result<(), String> {
+        let mut body = json!({
+            "email_address": email,
+            "status_if_new": "subscribed",
+        });
+        if let Some(name) = first_name {
+            body["merge_fields"] = json!({ "FNAME": name });
+        }
+
+        let response = self
+            .client
+            .put(self.member_url(list_id, email))
+            .basic_auth("rcom", Some(&self.config.api_key))
+            .json(&body)
+            .with_request_id()
+            .send()
+            .await
+            .map_err(|e| format!("Failed to add member to Mailchimp: {}", e))?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(error_for(response).await)
+        }
+    }
+
+    /// Activate and deactivate tags on an audience member
+    pub async fn update_member_tags(&self, list_id: &str, email: &str, add: &[String], remove: &[String]) -> Result<(), String> {
+        let tags: Vec<Value> = add
+            .iter()
+            .map(|tag| json!({ "name": tag, "status": "active" }))
+            .chain(remove.iter().map(|tag| json!({ "name": tag, "status": "inactive" })))
+            .collect();
+
+        let response = self
+            .client
+            .post(format!("{}/tags", self.member_url(list_id, email)))
+            .basic_auth("rcom", Some(&self.config.api_key))
+            .json(&json!({ "tags": tags }))
+            .with_request_id()
+            .send()
+            .await
+            .map_err(|e| format!("Failed to update Mailchimp member tags: {}", e))?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(error_for(response).await)
+        }
+    }
+
+    /// Archive an audience member; members that are already gone count as removed
+    pub async fn archive_member(&self, list_id: &str, email: &str) -> Result<(), String> {
+        let response = self
+            .client
+            .delete(self.member_url(list_id, email))
+            .basic_auth("rcom", Some(&self.config.api_key))
+            .with_request_id()
+            .send()
+            .await
+            .map_err(|e| format!("Failed to remove member from Mailchimp: {}", e))?;
+        let status = response.status();
+        if status.is_success() || status.as_u16() == 404 || status.as_u16() == 405 {
+            Ok(())
+        } else {
+            Err(error_for(response).await)
+        }
+    }
+
+    /// Create a regular campaign to the whole audience, set its HTML and send it.
+    /// Returns the Mailchimp campaign id
+    pub async fn send_campaign(
+        &self,
+        list_id: &str,
+        title: &str,
+        subject: &str,
+        html: &str,
+        from_name: &str,
+        reply_to: &str,
+    ) -> Result<String, String> {
+        let response = self
+            .client
+            .post(format!("{}/campaigns", self.config.api_base_url))
+            .basic_auth("rcom", Some(&self.config.api_key))
+            .json(&json!({
+                "type": "regular",
+                "recipients": { "list_id": list_id },
+                "settings": {
+                    "title": title,
+                    "subject_line": subject,
+                    "from_name": from_name,
+                    "reply_to": reply_to,
+                },
+            }))
+            .with_request_id()
+            .send()
+            .await
+            .map_err(|e| format!("Failed to create Mailchimp campaign: {}", e))?;
+        if !response.status().is_success() {
+            return Err(error_for(response).await);
+        }
+        let campaign: Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Mailchimp response: {}", e))?;
+        let campaign_id = campaign["id"]
+            .as_str()
+            .ok_or("Mailchimp response is missing the campaign id")?
+            .to_string();
+
+        let response = self
+            .client
+            .put(format!("{}/campaigns/{}/content", self.config.api_base_url, campaign_id))
+            .basic_auth("rcom", Some(&self.config.api_key))
+            .json(&json!({ "html": html }))
+            .with_request_id()
+            .send()
+            .await
+            .map_err(|e| format!("Failed to set Mailchimp campaign content: {}", e))?;
+        if !response.status().is_success() {
+            return Err(error_for(response).await);
+        }
+
+        let response = self
+            .client
+            .post(format!("{}/campaigns/{}/actions/send", self.config.api_base_url, campaign_id))
+            .basic_auth("rcom", Some(&self.config.api_key))
+            .with_request_id()
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send Mailchimp campaign: {}", e))?;
+        if !response.status().is_success() {
+            return Err(error_for(response).await);
+        }
+        Ok(campaign_id)
+    }
+}
@@ -0,0 +1,486 @@
+// Manual Orders Module - Orders entered by staff for phone and offline sales
+// The cart is priced and saved exactly like a storefront checkout, so the order gets the same
+// items, totals and customer link. Orders paid offline start 'completed'; invoice orders stay
+// 'pending' until staff mark them paid or the customer pays the Stripe payment link emailed
+// to them. Either way OrderCreated is published once paid, which sends the confirmation and
+// starts fulfillment like any other order. The stock is reserved when the order is entered, as
+// at checkout; pending orders that are cancelled or not paid in time give it back
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::post,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use std::sync::Arc;
+use stripe::{
+    CheckoutSession, CheckoutSessionMode, CreateCheckoutSession, CreateCheckoutSessionLineItems,
+    CreateCheckoutSessionLineItemsPriceData, CreateCheckoutSessionLineItemsPriceDataProductData,
+    CreateCheckoutSessionPaymentIntentData, Currency,
+};
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::branding::{escape_html, load_branding};
+use crate::cart_pricing::{price_cart, CartPrice, CartPriceRequest};
+use crate::checkout_carts;
+use crate::coupons;
+use crate::events::DomainEvent;
+use crate::notifications::{queue_email, EmailPriority};
+use crate::order_status::{self, TransitionError};
+use crate::pii::Encrypted;
+use crate::provider_breaker;
+use crate::purchase_limits;
+use crate::repos::order_query;
+use crate::stock_reservations;
+use crate::unit_of_work::{self, WorkError};
+use crate::webhooks::{create_order, CreateOrder, GiftOptions, Order, OrderItem, OrderStatus, PaymentProvider};
+use crate::AppState;
+
+// Payment metadata naming the manual order a payment link settles
+pub const METADATA_KEY: &str = "manual_order_id";
+
+const PAYMENT_METHODS: [&str; 2] = ["offline", "invoice"];
+
+#[derive(Deserialize)]
+pub struct ManualOrderRequest {
+    #[serde(flatten)]
+    pub cart: CartPriceRequest, // items, coupon_code and shipping, priced like checkout
+    pub customer_email: Option<String>,
+    pub customer_name: Option<String>,
+    pub payment_method: String,            // "offline" (already paid) or "invoice" (pay later)
+    pub payment_reference: Option<String>, // Receipt, cheque or invoice number
+    #[serde(default)]
+    pub send_payment_link: bool, // Email a Stripe payment link; invoice orders only
+    pub note: Option<String>,    // Added as an order note
+}
+
+#[derive(Deserialize, Default)]
+pub struct MarkPaidRequest {
+    pub payment_reference: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ManualOrderResponse {
+    pub order: Order,
+    pub items: Vec<OrderItem>,
+    pub price: CartPrice,
+}
+
+pub fn manual_order_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/orders", post(create_manual_order))
+        .route("/api/admin/orders/:id/payment-link", post(resend_payment_link))
+        .route("/api/admin/orders/:id/mark-paid", post(mark_paid_handler))
+        .route("/api/admin/orders/:id/cancel", post(cancel_handler))
+        .with_state(app_state)
+}
+
+async fn load_manual_order(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Order, (StatusCode, String)> {
+    order_query!("SELECT", "FROM orders WHERE id = $1 AND payment_provider = 'manual'", order_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Manual order not found".to_string()))
+}
+
+// Mark a pending manual order paid and publish OrderCreated; None when it isn't pending
+pub async fn mark_paid(
+    state: &AppState,
+    order_id: Uuid,
+    payment_intent_id: Option<&str>,
+    payment_reference: Option<&str>,
+    changed_by: &str,
+) -> Result<Option<Order>, TransitionError> {
+    let mut tx = state.pool.begin().await?;
+    let manual: Option<Option<Uuid>> =
+        sqlx::query_scalar!(
+            "SELECT cart_id FROM orders WHERE id = $1 AND payment_provider = 'manual' FOR UPDATE",
+            order_id,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+    let Some(cart_id) = manual else {
+        return Ok(None);
+    };
+    let Some(change) =
+        order_status::transition_from(&mut tx, order_id, OrderStatus::Pending, OrderStatus::Completed, changed_by).await?
+    else {
+        return Ok(None);
+    };
+    // The stock held since the order was entered is sold
+    if let Some(cart_id) = cart_id {
+        stock_reservations::convert_cart(&mut *tx, cart_id).await?;
+    }
+    let order = order_query!(
+        "UPDATE orders SET payment_intent_id = COALESCE($2, payment_intent_id),
+                payment_id = COALESCE($3, payment_id)
+         WHERE id = $1
+         RETURNING",
+        "",
+        order_id,
+        payment_intent_id,
+        payment_reference,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    publish_created(state, &order);
+    state.events.publish(change.event());
+    Ok(Some(order))
+}
+
+fn publish_created(state: &AppState, order: &Order) {
+    state.events.publish(DomainEvent::OrderCreated {
+        order_id: order.id,
+        provider: PaymentProvider::Manual,
+        payment_id: order.payment_id.clone(),
+        customer_email: order.customer_email.clone().map(Encrypted::into_inner),
+        customer_name: order.customer_name.clone(),
+        customer_phone: None,
+        total_amount: order.total_amount,
+        currency: order.currency.clone(),
+    });
+}
+
+// Cancel a pending manual order and put its held stock back; None when it isn't pending.
+// Staff cancel from the admin, and the release_stock_reservations task once the hold expires
+pub async fn cancel(state: &AppState, order_id: Uuid, changed_by: &str) -> Result<Option<Order>, TransitionError> {
+    let cancelled = unit_of_work::run(state, |uow| {
+        let changed_by = changed_by.to_string();
+        Box::pin(async move {
+            let cart_id: Option<Option<Uuid>> =
+                sqlx::query_scalar!(
+                    "SELECT cart_id FROM orders WHERE id = $1 AND payment_provider = 'manual' FOR UPDATE",
+                    order_id,
+                )
+                .fetch_optional(uow.conn())
+                .await?;
+            let Some(cart_id) = cart_id else {
+                return Ok(None);
+            };
+            let change = order_status::transition_from(uow.conn(), order_id, OrderStatus::Pending, OrderStatus::Voided, &changed_by)
+                .await
+                .map_err(|e| match e {
+                    TransitionError::Db(e) => WorkError::Db(e),
+                    e => WorkError::Aborted(e),
+                })?;
+            let Some(change) = change else {
+                return Ok(None);
+            };
+            if let Some(cart_id) = cart_id {
+                stock_reservations::release_held(uow, cart_id).await?;
+            }
+            uow.publish(change.event());
+            let order = order_query!("SELECT", "FROM orders WHERE id = $1", order_id)
+                .fetch_one(uow.conn())
+                .await?;
+            Ok(Some(order))
+        })
+    })
+    .await;
+    cancelled.map_err(|e| match e {
+        WorkError::Db(e) => TransitionError::Db(e),
+        WorkError::Aborted(e) => e,
+    })
+}
+
+// Settle the manual order a Stripe payment was made for; false when the payment isn't for one
+pub async fn settle_payment(
+    state: &AppState,
+    metadata: &HashMap<String, String>,
+    payment_intent_id: Option<&str>,
+) -> Result<bool, String> {
+    let Some(order_id) = metadata.get(METADATA_KEY).and_then(|id| Uuid::parse_str(id).ok()) else {
+        return Ok(false);
+    };
+    // The payment intent and checkout session events both arrive; the first one settles it
+    match mark_paid(state, order_id, payment_intent_id, None, "stripe").await {
+        Ok(Some(_)) => tracing::info!("Manual order {} paid through its payment link", order_id),
+        Ok(None) => tracing::info!("Manual order {} already settled", order_id),
+        Err(e) => return Err(format!("Failed to settle manual order {}: {}", order_id, e)),
+    }
+    Ok(true)
+}
+
+// A staff-entered order for an already priced cart
+pub(crate) struct NewManualOrder<'a> {
+    pub customer_email: Option<String>,
+    pub customer_name: Option<String>,
+    pub payment_method: &'a str, // See initial_status
+    pub payment_reference: Option<&'a str>,
+    pub created_by: Option<&'a str>, // None for orders the customer placed
+    pub customer_id: Option<Uuid>,   // Signed-in customer placing the order: their own coupons, and purchase limits apply
+}
+
+// Offline orders are paid and purchase orders ship on net terms; anything else stays pending
+fn initial_status(payment_method: &str) -> OrderStatus {
+    match payment_method {
+        "offline" => OrderStatus::Completed,
+        "purchase_order" => OrderStatus::AwaitingPayment,
+        _ => OrderStatus::Pending,
+    }
+}
+
+// Save the cart, reserve its stock and create its manual order with the cart's items. Paid and
+// purchase orders publish OrderCreated right away; pending ones wait for mark_paid
+pub(crate) async fn create_from_price(
+    state: &AppState,
+    price: &CartPrice,
+    new_order: NewManualOrder<'_>,
+) -> Result<Order, (StatusCode, String)> {
+    let status = initial_status(new_order.payment_method);
+    let confirmed = !matches!(status, OrderStatus::Pending);
+    let reference = new_order.payment_reference.map(str::trim).filter(|r| !r.is_empty());
+    let livemode = state.mode.is_live();
+
+    // The cart, its stock and the order are saved together, so a sold out product leaves
+    // neither an order nor a hold behind
+    let customer_id = new_order.customer_id;
+    let order_id = unit_of_work::run(state, |uow| {
+        let (price, customer_email, customer_name) = (price.clone(), new_order.customer_email.clone(), new_order.customer_name.clone());
+        let (payment_method, created_by) = (new_order.payment_method.to_string(), new_order.created_by.map(str::to_string));
+        let reference = reference.map(str::to_string);
+        Box::pin(async move {
+            let checkout = checkout_carts::save_cart(uow.conn(), None, &price).await?;
+            // Limits are on what customers buy; staff entering an order aren't held to them
+            if customer_id.is_some() {
+                purchase_limits::enforce(uow.conn(), checkout.cart_id, customer_id, customer_email.as_deref(), &price.lines).await?;
+            }
+            if let Some(cart_id) = checkout.cart_id {
+                stock_reservations::reserve_for_cart(uow, cart_id, &price.lines).await?;
+                if !confirmed {
+                    stock_reservations::hold_until_paid(uow.conn(), cart_id).await?;
+                }
+            }
+            // Reserved by save_cart, so the payment id can name the order before it exists
+            let order_id = checkout.order_id.unwrap_or_else(Uuid::new_v4);
+            let order = CreateOrder {
+                payment_provider: PaymentProvider::Manual,
+                payment_id: reference.unwrap_or_else(|| format!("{}_{}", payment_method, order_id)),
+                payment_intent_id: None,
+                customer_email,
+                customer_name,
+                total_amount: price.total,
+                currency: price.currency.to_uppercase(),
+                status,
+                webhook_event_id: None,
+                gift: GiftOptions::default(),
+                checkout,
+                livemode,
+            };
+            let order_id = create_order(uow.conn(), order).await?;
+            sqlx::query!(
+                "UPDATE orders SET payment_method = $1, created_by = $2 WHERE id = $3",
+                &payment_method,
+                created_by.as_deref(),
+                order_id,
+            )
+            .execute(uow.conn())
+            .await?;
+            Ok(order_id)
+        })
+    })
+    .await?;
+
+    if let Some(code) = &price.coupon_code {
+        match coupons::redeem(&*state.pool, code, order_id, new_order.customer_id).await {
+            Ok(true) => tracing::info!("Redeemed coupon {} on order {}", code, order_id),
+            Ok(false) => tracing::error!("Coupon {} on order {} was already redeemed", code, order_id),
+            Err(e) => tracing::error!("Failed to redeem coupon {} for order {}: {}", code, order_id, e),
+        }
+    }
+
+    let order = load_manual_order(&state.pool, order_id).await?;
+    match new_order.created_by {
+        Some(admin) => tracing::info!("{} entered {} order {}", admin, new_order.payment_method, order_id),
+        None => tracing::info!("Customer placed {} order {}", new_order.payment_method, order_id),
+    }
+    if confirmed {
+        publish_created(state, &order);
+    }
+    Ok(order)
+}
+
+// Create a Stripe Checkout link for the order total and email it to the customer.
+// Checkout links expire after 24 hours; a new one can be sent at any time
+pub(crate) async fn send_payment_link(state: &AppState, order: &Order) -> Result<String, (StatusCode, String)> {
+    let email = order
+        .customer_email
+        .as_deref()
+        .ok_or((StatusCode::BAD_REQUEST, "A payment link needs a customer_email".to_string()))?;
+    let success_url = format!(
+        "{}/orders/{}",
+        app_config::get().server.storefront_url.trim_end_matches('/'),
+        order.id
+    );
+    let metadata = HashMap::from([(METADATA_KEY.to_string(), order.id.to_string())]);
+    let client_reference_id = order.id.to_string();
+
+    let mut params = CreateCheckoutSession::new(&success_url);
+    params.mode = Some(CheckoutSessionMode::Payment);
+    params.customer_email = Some(email);
+    params.client_reference_id = Some(&client_reference_id);
+    params.metadata = Some(metadata.clone());
+    params.payment_intent_data = Some(CreateCheckoutSessionPaymentIntentData {
+        metadata: metadata.clone(),
+        receipt_email: Some(email.to_string()),
+        ..Default::default()
+    });
+    params.line_items = Some(vec![CreateCheckoutSessionLineItems {
+        quantity: Some(1),
+        price_data: Some(CreateCheckoutSessionLineItemsPriceData {
+            currency: order.currency.to_lowercase().parse().unwrap_or(Currency::USD),
+            product_data: Some(CreateCheckoutSessionLineItemsPriceDataProductData {
+                name: format!("Order {}", order.id),
+                ..Default::default()
+            }),
+            unit_amount: Some(order.total_amount),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }]);
+
+    let session = provider_breaker::stripe(CheckoutSession::create(&state.stripe_client, params))
+        .await
+        .map_err(|e| (e.status(StatusCode::BAD_GATEWAY), format!("Stripe error: {}", e)))?;
+    let url = session
+        .url
+        .ok_or((StatusCode::BAD_GATEWAY, "Stripe returned no checkout url".to_string()))?;
+
+    sqlx::query!("UPDATE orders SET payment_link_url = $1, updated_at = NOW() WHERE id = $2", &url, order.id)
+        .execute(&*state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    let items = state.repos.orders.items(order.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let html_body = load_branding(&state.pool).await.email_html(
+        "Your Order Is Ready for Payment",
+        &format!(
+            r#"<p>Hi {},</p>
+            <p>Thanks for your order. You can pay for it securely online:</p>
+            {}
+            <p class="total">Amount Due: ${:.2}</p>
+            <p><a href="{}">Pay now</a></p>
+            <p>This link is valid for 24 hours. Reply to this email if you need a new one.</p>"#,
+            escape_html(order.customer_name.as_deref().unwrap_or("there")),
+            crate::webhooks::items_table_html(&items),
+            order.total_amount as f64 / 100.0,
+            escape_html(&url)
+        ),
+    );
+    queue_email(&state.pool, email, &format!("Payment for order {}", order.id), &html_body, EmailPriority::Immediate)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    tracing::info!("Payment link for order {} sent to {}", order.id, email);
+    Ok(url)
+}
+
+async fn create_manual_order(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<ManualOrderRequest>,
+) -> Result<(StatusCode, Json<ManualOrderResponse>), (StatusCode, String)> {
+    let payment_method = request.payment_method.trim().to_lowercase();
+    if !PAYMENT_METHODS.contains(&payment_method.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, "payment_method must be offline or invoice".to_string()));
+    }
+    let paid = payment_method == "offline";
+    let customer_email = request
+        .customer_email
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(str::to_string);
+    if request.send_payment_link && (paid || customer_email.is_none()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "send_payment_link needs an invoice order with a customer_email".to_string(),
+        ));
+    }
+
+    let price = price_cart(&app_state.pool, &request.cart, None)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let new_order = NewManualOrder {
+        customer_email,
+        customer_name: request.customer_name.clone(),
+        payment_method: &payment_method,
+        payment_reference: request.payment_reference.as_deref(),
+        created_by: Some(&admin.username),
+        customer_id: None,
+    };
+    let mut order = create_from_price(&app_state, &price, new_order).await?;
+    let order_id = order.id;
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+
+    if let Some(note) = request.note.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+        sqlx::query!(
+            "INSERT INTO order_notes (order_id, author, body) VALUES ($1, $2, $3)",
+            order_id,
+            &admin.username,
+            note,
+        )
+        .execute(&*app_state.pool)
+        .await
+        .map_err(db_error)?;
+    }
+    if request.send_payment_link {
+        order.payment_link_url = Some(send_payment_link(&app_state, &order).await?);
+    }
+
+    let items = app_state.repos.orders.items(order_id).await.map_err(db_error)?;
+    Ok((StatusCode::CREATED, Json(ManualOrderResponse { order, items, price })))
+}
+
+// Send a fresh payment link for a pending manual order
+async fn resend_payment_link(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<Order>, (StatusCode, String)> {
+    let mut order = load_manual_order(&app_state.pool, order_id).await?;
+    if order.status != OrderStatus::Pending {
+        return Err((StatusCode::CONFLICT, format!("Order is {}, not pending", order.status)));
+    }
+    order.payment_link_url = Some(send_payment_link(&app_state, &order).await?);
+    Ok(Json(order))
+}
+
+// Record payment received outside Stripe, e.g. a bank transfer for an invoice
+async fn mark_paid_handler(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+    body: Option<Json<MarkPaidRequest>>,
+) -> Result<Json<Order>, (StatusCode, String)> {
+    let order = load_manual_order(&app_state.pool, order_id).await?;
+    let request = body.map(|Json(r)| r).unwrap_or_default();
+    let reference = request.payment_reference.as_deref().map(str::trim).filter(|r| !r.is_empty());
+    let paid = mark_paid(&app_state, order_id, None, reference, &admin.username)
+        .await?
+        .ok_or((StatusCode::CONFLICT, format!("Order is {}, not pending", order.status)))?;
+    tracing::info!("{} marked order {} paid", admin.username, order_id);
+    Ok(Json(paid))
+}
+
+// Cancel an invoice or quote order that won't be paid, returning its stock
+async fn cancel_handler(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<Order>, (StatusCode, String)> {
+    let order = load_manual_order(&app_state.pool, order_id).await?;
+    let cancelled = cancel(&app_state, order_id, &admin.username)
+        .await?
+        .ok_or((StatusCode::CONFLICT, format!("Order is {}, not pending", order.status)))?;
+    tracing::info!("{} cancelled order {}", admin.username, order_id);
+    Ok(Json(cancelled))
+}
@@ -0,0 +1,403 @@
+// Marketing Automation Module - Event-driven marketing emails
+// Domain events (first purchase, shipment delivered, refund) are matched against the
+// marketing_automations table and each matching flow is queued as a delayed job
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use chrono::Duration;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::branding::load_branding;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::jobs;
+use crate::lettre_email::EmailConfig;
+use crate::notifications::{queue_email, EmailPriority};
+use crate::pii::{self, Encrypted};
+use crate::AppState;
+
+pub const MARKETING_EMAIL_JOB: &str = "marketing_email";
+
+// Domain event an automation reacts to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
+pub enum AutomationTrigger {
+    #[sqlx(rename = "first_purchase")]
+    #[serde(rename = "first_purchase")]
+    FirstPurchase,
+    #[sqlx(rename = "shipment_delivered")]
+    #[serde(rename = "shipment_delivered")]
+    ShipmentDelivered,
+    #[sqlx(rename = "order_refunded")]
+    #[serde(rename = "order_refunded")]
+    OrderRefunded,
+}
+
+// Database model for marketing automations
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct MarketingAutomation {
+    pub id: i32,
+    pub name: String,
+    pub trigger: AutomationTrigger,
+    pub delay_days: i32,
+    pub subject: String,
+    pub html_body: String,
+    pub active: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MarketingAutomationInput {
+    pub name: String,
+    pub trigger: AutomationTrigger,
+    #[serde(default)]
+    pub delay_days: i32,
+    pub subject: String,
+    pub html_body: String,
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+// Payload stored on queued marketing email jobs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketingEmailPayload {
+    pub automation_id: i32,
+    pub email: String,
+    pub name: Option<String>,
+    pub order_id: Option<Uuid>,
+    pub tracking_code: Option<String>,
+}
+
+// Queues marketing emails for every active automation matching an event
+pub struct MarketingAutomationSubscriber {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl MarketingAutomationSubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+
+    // An order is a first purchase when it is the customer's only completed order
+    async fn is_first_purchase(&self, email: &str) -> Result<bool, String> {
+        let completed: i64 = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "completed!" FROM orders WHERE customer_email_hash = $1 AND status = 'completed'"#,
+            pii::email_hash(email),
+        )
+        .fetch_one(&*self.pool)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+        Ok(completed == 1)
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for MarketingAutomationSubscriber {
+    fn name(&self) -> &'static str {
+        "marketing_automation"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let (trigger, payload) = match event {
+            DomainEvent::OrderCreated {
+                order_id,
+                customer_email: Some(email),
+                customer_name,
+                ..
+            } => {
+                if !self.is_first_purchase(email).await? {
+                    return Ok(());
+                }
+                (
+                    AutomationTrigger::FirstPurchase,
+                    MarketingEmailPayload {
+                        automation_id: 0,
+                        email: email.clone(),
+                        name: customer_name.clone(),
+                        order_id: Some(*order_id),
+                        tracking_code: None,
+                    },
+                )
+            }
+            DomainEvent::ShipmentDelivered {
+                order_id: Some(order_id),
+                tracking_code,
+                ..
+            } => {
+                let customer: Option<(Option<Encrypted>, Option<String>)> =
+                    sqlx::query!(r#"SELECT customer_email AS "customer_email: Encrypted", customer_name FROM orders WHERE id = $1"#, order_id)
+                        .map(|r| (r.customer_email, r.customer_name))
+                        .fetch_optional(&*self.pool)
+                        .await
+                        .map_err(|e| format!("DB error: {}", e))?;
+                let (email, name) = match customer {
+                    Some((Some(email), name)) => (email.into_inner(), name),
+                    _ => return Ok(()),
+                };
+                (
+                    AutomationTrigger::ShipmentDelivered,
+                    MarketingEmailPayload {
+                        automation_id: 0,
+                        email,
+                        name,
+                        order_id: Some(*order_id),
+                        tracking_code: Some(tracking_code.clone()),
+                    },
+                )
+            }
+            DomainEvent::OrderRefunded {
+                order_id,
+                customer_email: Some(email),
+                ..
+            } => {
+                let name: Option<String> = sqlx::query_scalar!(
+                    "SELECT customer_name FROM orders WHERE id = $1",
+                    order_id,
+                )
+                .fetch_optional(&*self.pool)
+                .await
+                .map_err(|e| format!("DB error: {}", e))?
+                .flatten();
+                (
+                    AutomationTrigger::OrderRefunded,
+                    MarketingEmailPayload {
+                        automation_id: 0,
+                        email: email.clone(),
+                        name,
+                        order_id: Some(*order_id),
+                        tracking_code: None,
+                    },
+                )
+            }
+            _ => return Ok(()),
+        };
+
+        let automations = sqlx::query_as!(
+            MarketingAutomation,
+            r#"SELECT id, name, trigger AS "trigger: AutomationTrigger", delay_days, subject, html_body, active, created_at, updated_at FROM marketing_automations WHERE trigger = $1 AND active = TRUE ORDER BY delay_days, id"#,
+            trigger as _,
+        )
+        .fetch_all(&*self.pool)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+
+        for automation in automations {
+            let run_at = Utc::now() + Duration::days(automation.delay_days as i64);
+            let job_payload = serde_json::to_value(MarketingEmailPayload {
+                automation_id: automation.id,
+                ..payload.clone()
+            })
+            .map_err(|e| format!("Failed to serialize payload: {}", e))?;
+            jobs::enqueue(&self.pool, MARKETING_EMAIL_JOB, job_payload, run_at)
+                .await
+                .map_err(|e| format!("Failed to queue automation '{}': {}", automation.name, e))?;
+            tracing::info!("Queued marketing automation '{}' for {} at {}", automation.name, payload.email, run_at);
+        }
+        Ok(())
+    }
+}
+
+// Job handler: render and send one queued marketing email
+pub async fn run_marketing_email(pool: &sqlx::PgPool, payload: &serde_json::Value) -> Result<(), String> {
+    let payload: MarketingEmailPayload =
+        serde_json::from_value(payload.clone()).map_err(|e| format!("Invalid marketing email payload: {}", e))?;
+
+    // Automations edited or deactivated after queueing take effect for pending jobs
+    let automation = sqlx::query_as!(
+        MarketingAutomation,
+        r#"SELECT id, name, trigger AS "trigger: AutomationTrigger", delay_days, subject, html_body, active, created_at, updated_at FROM marketing_automations WHERE id = $1"#,
+        payload.automation_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))?;
+    let automation = match automation {
+        Some(a) if a.active => a,
+        _ => {
+            tracing::info!("Skipping marketing email for {}: automation {} inactive or deleted", payload.email, payload.automation_id);
+            return Ok(());
+        }
+    };
+
+    let disabled: Option<bool> = sqlx::query_scalar!(
+        "SELECT disabled FROM customers WHERE email_hash = $1",
+        pii::email_hash(&payload.email),
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))?;
+    if disabled == Some(true) {
+        tracing::info!("Skipping marketing email for {}: customer disabled", payload.email);
+        return Ok(());
+    }
+
+    EmailConfig::from_env().ok_or("Email not configured")?;
+    let subject = render_template(&automation.subject, &payload, false);
+    let mut html_body = render_template(&automation.html_body, &payload, true);
+    if html_body.contains("{{rating_links}}") {
+        let links = match payload.order_id {
+            Some(order_id) => crate::reviews::rating_links_html(order_id)?,
+            None => String::new(),
+        };
+        html_body = html_body.replace("{{rating_links}}", &links);
+    }
+    let html_body = load_branding(pool).await.email_html(&subject, &html_body);
+    // Non-urgent, so it goes out with the next email batch
+    queue_email(pool, &payload.email, &subject, &html_body, EmailPriority::Batched).await?;
+
+    tracing::info!("Marketing email '{}' queued for {}", automation.name, payload.email);
+    Ok(())
+}
+
+// Fill {{name}}, {{email}}, {{order_id}} and {{tracking_code}} placeholders
+// ({{rating_links}} is filled by the caller since it needs a signed token)
+fn render_template(template: &str, payload: &MarketingEmailPayload, html: bool) -> String {
+    let escape = |value: &str| {
+        if html {
+            value
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+                .replace('\'', "&#39;")
+        } else {
+            value.to_string()
+        }
+    };
+    template
+        .replace("{{name}}", &escape(payload.name.as_deref().unwrap_or("there")))
+        .replace("{{email}}", &escape(&payload.email))
+        .replace("{{order_id}}", &payload.order_id.map(|id| id.to_string()).unwrap_or_default())
+        .replace("{{tracking_code}}", &escape(payload.tracking_code.as_deref().unwrap_or_default()))
+}
+
+fn validate_input(input: &MarketingAutomationInput) -> Result<(), String> {
+    if input.name.trim().is_empty() {
+        return Err("name is required".to_string());
+    }
+    if input.subject.trim().is_empty() {
+        return Err("subject is required".to_string());
+    }
+    if input.html_body.trim().is_empty() {
+        return Err("html_body is required".to_string());
+    }
+    if input.delay_days < 0 {
+        return Err("delay_days must not be negative".to_string());
+    }
+    Ok(())
+}
+
+pub fn marketing_automation_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/marketing/automations", get(list_automations).post(create_automation))
+        .route(
+            "/api/admin/marketing/automations/:id",
+            get(get_automation).put(update_automation).delete(delete_automation),
+        )
+        .with_state(app_state)
+}
+
+async fn list_automations(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<MarketingAutomation>>, (StatusCode, String)> {
+    let automations = sqlx::query_as!(
+        MarketingAutomation,
+        r#"SELECT id, name, trigger AS "trigger: AutomationTrigger", delay_days, subject, html_body, active, created_at, updated_at FROM marketing_automations ORDER BY trigger, delay_days, id"#,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(automations))
+}
+
+async fn get_automation(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<MarketingAutomation>, (StatusCode, String)> {
+    sqlx::query_as!(MarketingAutomation, r#"SELECT id, name, trigger AS "trigger: AutomationTrigger", delay_days, subject, html_body, active, created_at, updated_at FROM marketing_automations WHERE id = $1"#, id)
+        .fetch_optional(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "Automation not found".to_string()))
+}
+
+async fn create_automation(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<MarketingAutomationInput>,
+) -> Result<(StatusCode, Json<MarketingAutomation>), (StatusCode, String)> {
+    validate_input(&input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let automation = sqlx::query_as!(
+        MarketingAutomation,
+        r#"INSERT INTO marketing_automations (name, trigger, delay_days, subject, html_body, active)
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING id, name, trigger AS "trigger: AutomationTrigger", delay_days, subject, html_body, active, created_at, updated_at"#,
+        input.name.trim(),
+        input.trigger as _,
+        input.delay_days,
+        input.subject.trim(),
+        &input.html_body,
+        input.active,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?;
+    Ok((StatusCode::CREATED, Json(automation)))
+}
+
+async fn update_automation(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(input): Json<MarketingAutomationInput>,
+) -> Result<Json<MarketingAutomation>, (StatusCode, String)> {
+    validate_input(&input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    sqlx::query_as!(
+        MarketingAutomation,
+        r#"UPDATE marketing_automations
+         SET name = $1, trigger = $2, delay_days = $3, subject = $4, html_body = $5, active = $6, updated_at = NOW()
+         WHERE id = $7 RETURNING id, name, trigger AS "trigger: AutomationTrigger", delay_days, subject, html_body, active, created_at, updated_at"#,
+        input.name.trim(),
+        input.trigger as _,
+        input.delay_days,
+        input.subject.trim(),
+        &input.html_body,
+        input.active,
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?
+    .map(Json)
+    .ok_or((StatusCode::NOT_FOUND, "Automation not found".to_string()))
+}
+
+async fn delete_automation(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM marketing_automations WHERE id = $1", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Automation not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
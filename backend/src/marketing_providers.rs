@@ -0,0 +1,351 @@
+// Marketing Providers Module - One interface over the email marketing platforms
+// Brevo, Mailchimp and Letre implement `MarketingProvider`; newsletter signups, order-time
+// contact sync, segment list sync and campaigns all go through it, so switching platforms
+// is a MARKETING_PROVIDER change rather than a code change
+
+use async_trait::async_trait;
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::branding::load_branding;
+use crate::brevo_email::{BrevoClient, BrevoConfig};
+use crate::letre_marketing::{LetreClient, LetreConfig};
+use crate::mailchimp_marketing::{MailchimpClient, MailchimpConfig};
+use crate::AppState;
+
+const BREVO_REMOVE_BATCH: usize = 150;
+// Contact attribute Brevo tags are kept in (a text attribute created in the Brevo account)
+const BREVO_TAGS_ATTRIBUTE: &str = "TAGS";
+
+// Everything a provider needs to send a one-off campaign to a list
+#[derive(Debug, Clone)]
+pub struct Campaign {
+    pub name: String,
+    pub subject: String,
+    pub html: String,
+    pub from_name: String,
+    pub reply_to: String,
+}
+
+// `list_id` is the provider's list or audience; None means the provider default
+// (no list for Brevo contacts, MAILCHIMP_LIST_ID for Mailchimp)
+#[async_trait]
+pub trait MarketingProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Add or update a contact, subscribing them to the list
+    async fn add_contact(&self, list_id: Option<&str>, email: &str, name: Option<&str>) -> Result<(), String>;
+
+    async fn update_tags(&self, list_id: Option<&str>, email: &str, add: &[String], remove: &[String]) -> Result<(), String>;
+
+    // Contacts that are already gone count as removed
+    async fn remove_contact(&self, list_id: Option<&str>, email: &str) -> Result<(), String>;
+
+    // Providers with a bulk endpoint override this
+    async fn remove_contacts(&self, list_id: Option<&str>, emails: &[String]) -> Result<(), String> {
+        for email in emails {
+            self.remove_contact(list_id, email).await?;
+        }
+        Ok(())
+    }
+
+    // Send a campaign to everyone on the list; returns the provider's campaign id
+    async fn send_campaign(&self, list_id: Option<&str>, campaign: &Campaign) -> Result<String, String>;
+}
+
+// Provider by name ("brevo", "mailchimp" or "letre"), if it is configured
+pub fn provider(name: &str) -> Result<Box<dyn MarketingProvider>, String> {
+    match name {
+        "brevo" => BrevoConfig::from_env()
+            .map(|config| Box::new(BrevoMarketing(BrevoClient::new(config))) as Box<dyn MarketingProvider>)
+            .ok_or_else(|| "Brevo not configured. Set BREVO_API_KEY in environment.".to_string()),
+        "mailchimp" => MailchimpConfig::from_env()
+            .map(|config| Box::new(MailchimpMarketing(MailchimpClient::new(config))) as Box<dyn MarketingProvider>)
+            .ok_or_else(|| "Mailchimp not configured. Set MAILCHIMP_API_KEY (key-datacenter) in environment.".to_string()),
+        "letre" => LetreConfig::from_env()
+            .map(|config| Box::new(LetreMarketing(LetreClient::new(config))) as Box<dyn MarketingProvider>)
+            .ok_or_else(|| "Letre not configured. Set LETRE_API_KEY in environment.".to_string()),
+        other => Err(format!("Unknown marketing provider '{}'", other)),
+    }
+}
+
+// The store's marketing provider (MARKETING_PROVIDER, defaults to brevo)
+pub fn provider_from_env() -> Result<Box<dyn MarketingProvider>, String> {
+    provider(&app_config::get().marketing.provider)
+}
+
+fn require_list<'a>(provider: &str, list_id: Option<&'a str>) -> Result<&'a str, String> {
+    list_id.ok_or_else(|| format!("{} needs a list id", provider))
+}
+
+// ============================================================================
+// Brevo
+// ============================================================================
+
+pub struct BrevoMarketing(BrevoClient);
+
+fn brevo_list_id(list_id: &str) -> Result<i64, String> {
+    list_id
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid Brevo list id '{}'", list_id))
+}
+
+#[async_trait]
+impl MarketingProvider for BrevoMarketing {
+    fn name(&self) -> &'static str {
+        "brevo"
+    }
+
+    async fn add_contact(&self, list_id: Option<&str>, email: &str, name: Option<&str>) -> Result<(), String> {
+        let list_ids = list_id.map(brevo_list_id).transpose()?.map(|id| vec![id]);
+        let attributes = name.map(|n| json!({ "FIRSTNAME": n }));
+        self.0.add_contact(email, attributes, list_ids).await?;
+        Ok(())
+    }
+
+    // Brevo has no contact tags, so they live in a comma separated TAGS attribute
+    async fn update_tags(&self, _list_id: Option<&str>, email: &str, add: &[String], remove: &[String]) -> Result<(), String> {
+        let contact = self
+            .0
+            .get_contact(email)
+            .await?
+            .ok_or_else(|| format!("{} is not a Brevo contact", email))?;
+        let mut tags: Vec<String> = contact["attributes"][BREVO_TAGS_ATTRIBUTE]
+            .as_str()
+            .unwrap_or_default()
+            .split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty() && !remove.contains(tag))
+            .collect();
+        for tag in add {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        self.0
+            .update_contact_attributes(email, json!({ (BREVO_TAGS_ATTRIBUTE): tags.join(",") }))
+            .await
+    }
+
+    // Without a list the contact is deleted from Brevo altogether
+    async fn remove_contact(&self, list_id: Option<&str>, email: &str) -> Result<(), String> {
+        match list_id {
+            Some(list_id) => self.remove_contacts(Some(list_id), &[email.to_string()]).await,
+            None => self.0.delete_contact(email).await,
+        }
+    }
+
+    async fn remove_contacts(&self, list_id: Option<&str>, emails: &[String]) -> Result<(), String> {
+        let Some(list_id) = list_id else {
+            for email in emails {
+                self.0.delete_contact(email).await?;
+            }
+            return Ok(());
+        };
+        let list_id = brevo_list_id(list_id)?;
+        for batch in emails.chunks(BREVO_REMOVE_BATCH) {
+            self.0.remove_contacts_from_list(list_id, batch).await?;
+        }
+        Ok(())
+    }
+
+    async fn send_campaign(&self, list_id: Option<&str>, campaign: &Campaign) -> Result<String, String> {
+        let list_id = brevo_list_id(require_list("Brevo", list_id)?)?;
+        self.0
+            .send_email_campaign(list_id, &campaign.name, &campaign.subject, &campaign.html, &campaign.reply_to)
+            .await
+            .map(|id| id.to_string())
+    }
+}
+
+// ============================================================================
+// Mailchimp
+// ============================================================================
+
+pub struct MailchimpMarketing(MailchimpClient);
+
+#[async_trait]
+impl MarketingProvider for MailchimpMarketing {
+    fn name(&self) -> &'static str {
+        "mailchimp"
+    }
+
+    async fn add_contact(&self, list_id: Option<&str>, email: &str, name: Option<&str>) -> Result<(), String> {
+        self.0.upsert_member(self.0.audience(list_id)?, email, name).await
+    }
+
+    async fn update_tags(&self, list_id: Option<&str>, email: &str, add: &[String], remove: &[String]) -> Result<(), String> {
+        self.0.update_member_tags(self.0.audience(list_id)?, email, add, remove).await
+    }
+
+    async fn remove_contact(&self, list_id: Option<&str>, email: &str) -> Result<(), String> {
+        self.0.archive_member(self.0.audience(list_id)?, email).await
+    }
+
+    async fn send_campaign(&self, list_id: Option<&str>, campaign: &Campaign) -> Result<String, String> {
+        self.0
+            .send_campaign(
+                self.0.audience(list_id)?,
+                &campaign.name,
+                &campaign.subject,
+                &campaign.html,
+                &campaign.from_name,
+                &campaign.reply_to,
+            )
+            .await
+    }
+}
+
+// ============================================================================
+// Letre
+// ============================================================================
+
+pub struct LetreMarketing(LetreClient);
+
+#[async_trait]
+impl MarketingProvider for LetreMarketing {
+    fn name(&self) -> &'static str {
+        "letre"
+    }
+
+    async fn add_contact(&self, list_id: Option<&str>, email: &str, name: Option<&str>) -> Result<(), String> {
+        self.0.add_subscriber(require_list("Letre", list_id)?, email, name).await
+    }
+
+    async fn update_tags(&self, _list_id: Option<&str>, _email: &str, _add: &[String], _remove: &[String]) -> Result<(), String> {
+        Err("Letre does not support contact tags".to_string())
+    }
+
+    async fn remove_contact(&self, list_id: Option<&str>, email: &str) -> Result<(), String> {
+        self.0.remove_subscriber(require_list("Letre", list_id)?, email).await
+    }
+
+    async fn send_campaign(&self, _list_id: Option<&str>, _campaign: &Campaign) -> Result<String, String> {
+        Err("Letre campaigns are not supported".to_string())
+    }
+}
+
+// ============================================================================
+// Routes
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct SubscribeRequest {
+    pub email: String,
+    pub first_name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnsubscribeRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CampaignRequest {
+    pub name: Option<String>,
+    pub subject: String,
+    pub html: String,
+    pub list_id: Option<String>,
+    pub reply_to: Option<String>,
+}
+
+fn unavailable(e: String) -> (StatusCode, String) {
+    (StatusCode::SERVICE_UNAVAILABLE, e)
+}
+
+pub fn marketing_provider_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/email/subscribe", post(subscribe))
+        .route("/api/email/unsubscribe", post(unsubscribe))
+        .route("/api/admin/marketing/campaigns", post(send_campaign))
+        .with_state(app_state)
+}
+
+// Newsletter signup into the configured provider's default list
+async fn subscribe(Json(request): Json<SubscribeRequest>) -> Result<Json<Value>, (StatusCode, String)> {
+    let email = request.email.trim();
+    if !email.contains('@') {
+        return Err((StatusCode::BAD_REQUEST, "A valid email is required".to_string()));
+    }
+    let provider = provider_from_env().map_err(unavailable)?;
+    let name = request.first_name.as_deref().map(str::trim).filter(|n| !n.is_empty());
+    provider
+        .add_contact(None, email, name)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+
+    let tags: Vec<String> = request
+        .tags
+        .iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    if !tags.is_empty() {
+        provider
+            .update_tags(None, email, &tags, &[])
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "message": format!("Successfully subscribed {}", email),
+        "provider": provider.name(),
+    })))
+}
+
+async fn unsubscribe(Json(request): Json<UnsubscribeRequest>) -> Result<Json<Value>, (StatusCode, String)> {
+    let provider = provider_from_env().map_err(unavailable)?;
+    provider
+        .remove_contact(None, request.email.trim())
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+    Ok(Json(json!({ "success": true, "provider": provider.name() })))
+}
+
+// Send a branded one-off campaign to a provider list
+async fn send_campaign(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<CampaignRequest>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let subject = request.subject.trim();
+    if subject.is_empty() || request.html.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "subject and html are required".to_string()));
+    }
+    let provider = provider_from_env().map_err(unavailable)?;
+    let branding = load_branding(&app_state.pool).await;
+    let reply_to = request
+        .reply_to
+        .or_else(|| branding.support_email.clone())
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            "reply_to is required when the store branding has no support email".to_string(),
+        ))?;
+
+    let campaign = Campaign {
+        name: request.name.unwrap_or_else(|| subject.to_string()),
+        subject: subject.to_string(),
+        html: branding.email_html(subject, &request.html),
+        from_name: branding.store_name.clone(),
+        reply_to,
+    };
+    let campaign_id = provider
+        .send_campaign(request.list_id.as_deref().map(str::trim), &campaign)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+
+    tracing::info!("Campaign '{}' sent via {}", campaign.name, provider.name());
+    Ok(Json(json!({
+        "success": true,
+        "provider": provider.name(),
+        "campaign_id": campaign_id,
+    })))
+}
@@ -0,0 +1,519 @@
+// Meta Conversions Module - Purchase and AddToCart events for Meta's Conversions API
+// Events are sent from the backend through the job queue with SHA-256 hashed customer identifiers,
+// and carry the same event_id as the browser pixel so Meta counts each conversion once: the
+// order id for Purchase, and the id the storefront generated for AddToCart. With require_consent
+// on, only shoppers who accepted ad tracking (tracking_consent at checkout / add to cart) are sent
+
+use async_trait::async_trait;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::customer_auth::AuthenticatedCustomer;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::jobs;
+use crate::pii::Encrypted;
+use crate::pricing::{self, CartLine};
+use crate::repos::order_query;
+use crate::request_id::WithRequestId;
+use crate::webhooks::OrderItem;
+use crate::AppState;
+
+pub const META_EVENT_JOB: &str = "meta_conversion_event";
+const GRAPH_API_URL: &str = "https://graph.facebook.com/v18.0";
+const CURRENCY: &str = "USD"; // Storefront carts are priced in USD
+const MAX_EVENT_ID_LEN: usize = 100;
+const MAX_BROWSER_ID_LEN: usize = 255; // Column width
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+// Database model for the meta_conversions row; the access token stays server-side
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct MetaSettings {
+    pub enabled: bool,
+    pub pixel_id: Option<String>,
+    #[serde(skip_serializing)]
+    pub access_token: Option<String>,
+    pub test_event_code: Option<String>,
+    pub require_consent: bool,
+    pub forward_test_orders: bool,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Default for MetaSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            pixel_id: None,
+            access_token: None,
+            test_event_code: None,
+            require_consent: true,
+            forward_test_orders: false,
+            updated_at: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SettingsResponse {
+    #[serde(flatten)]
+    pub settings: MetaSettings,
+    pub access_token_set: bool,
+}
+
+#[derive(Deserialize)]
+pub struct SettingsInput {
+    pub enabled: bool,
+    pub pixel_id: Option<String>,
+    pub access_token: Option<String>, // Omit to keep the current token
+    pub test_event_code: Option<String>,
+    pub require_consent: Option<bool>, // Defaults to true
+    #[serde(default)]
+    pub forward_test_orders: bool,
+}
+
+// Ad tracking consent and Meta browser ids sent by the storefront; the ids fall back to the
+// _fbp / _fbc cookies
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BrowserContext {
+    #[serde(default)]
+    pub tracking_consent: Option<bool>,
+    #[serde(default)]
+    pub fbp: Option<String>,
+    #[serde(default)]
+    pub fbc: Option<String>,
+}
+
+// A browser context resolved against the request's cookies and headers
+#[derive(Debug, Clone, Default, sqlx::FromRow)]
+pub struct TrackingContext {
+    pub tracking_consent: Option<bool>,
+    pub fbp: Option<String>,
+    pub fbc: Option<String>,
+    pub client_ip: Option<String>,
+    pub client_user_agent: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AddToCartRequest {
+    pub product_id: i32,
+    pub quantity: i32,
+    pub event_id: String, // Also passed as eventID to the browser pixel's AddToCart
+    pub event_source_url: Option<String>,
+    #[serde(flatten)]
+    pub browser: BrowserContext,
+}
+
+#[derive(Serialize)]
+pub struct AddToCartResponse {
+    pub queued: bool, // false while Conversions API forwarding is off or without consent
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MetaEventPayload {
+    event: serde_json::Value, // A Conversions API event, identifiers already hashed
+}
+
+fn cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(cookie_name, _)| *cookie_name == name)
+        .map(|(_, value)| value.trim().to_string())
+}
+
+impl BrowserContext {
+    pub fn resolve(&self, headers: &HeaderMap) -> TrackingContext {
+        let id = |sent: &Option<String>, cookie_name: &str| {
+            sent.as_deref()
+                .map(str::trim)
+                .map(str::to_string)
+                .or_else(|| cookie(headers, cookie_name))
+                .filter(|v| !v.is_empty() && v.len() <= MAX_BROWSER_ID_LEN)
+        };
+        let header_value = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        TrackingContext {
+            tracking_consent: self.tracking_consent,
+            fbp: id(&self.fbp, "_fbp"),
+            fbc: id(&self.fbc, "_fbc"),
+            // The first hop is the shopper when the API runs behind a proxy
+            client_ip: header_value("x-forwarded-for")
+                .and_then(|v| v.split(',').next().map(|ip| ip.trim().to_string()))
+                .filter(|ip| !ip.is_empty() && ip.len() <= 64),
+            client_user_agent: header_value(header::USER_AGENT.as_str()),
+        }
+    }
+}
+
+// Remember the checkout's consent and browser ids until its order is created
+pub async fn set_on_cart(executor: impl sqlx::PgExecutor<'_>, cart_id: Uuid, context: &TrackingContext) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE checkout_carts SET tracking_consent = $1, fbp = $2, fbc = $3, client_ip = $4, client_user_agent = $5
+         WHERE id = $6",
+        context.tracking_consent,
+        context.fbp.as_deref(),
+        context.fbc.as_deref(),
+        context.client_ip.as_deref(),
+        context.client_user_agent.as_deref(),
+        cart_id,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// Current settings; the defaults until an admin saves them
+pub async fn load_settings(pool: &sqlx::PgPool) -> Result<MetaSettings, sqlx::Error> {
+    Ok(sqlx::query_as!(
+        MetaSettings,
+        "SELECT enabled, pixel_id, access_token, test_event_code, require_consent, forward_test_orders, updated_at
+         FROM meta_conversions WHERE id = 1",
+    )
+    .fetch_optional(pool)
+    .await?
+    .unwrap_or_default())
+}
+
+// SHA-256 of a normalized identifier, as Meta expects; None when nothing is left to hash
+fn hashed(value: Option<&str>, normalize: fn(&str) -> String) -> Option<String> {
+    let normalized = normalize(value?);
+    (!normalized.is_empty()).then(|| hex::encode(Sha256::digest(normalized.as_bytes())))
+}
+
+fn normalize_text(value: &str) -> String {
+    value.trim().to_lowercase()
+}
+
+// Phone numbers are hashed as digits only, country code included
+fn normalize_phone(value: &str) -> String {
+    value.chars().filter(char::is_ascii_digit).collect()
+}
+
+// Hashed identifiers plus the unhashed browser ids Meta matches on
+fn user_data(
+    email: Option<&str>,
+    phone: Option<&str>,
+    name: Option<&str>,
+    customer_id: Option<Uuid>,
+    context: &TrackingContext,
+) -> serde_json::Value {
+    let (first_name, last_name) = match name.map(str::trim).and_then(|n| n.split_once(' ')) {
+        Some((first, last)) => (Some(first), Some(last)),
+        None => (name, None),
+    };
+    let customer_id = customer_id.map(|id| id.to_string());
+    let mut data = serde_json::Map::new();
+    for (key, value) in [
+        ("em", hashed(email, normalize_text)),
+        ("ph", hashed(phone, normalize_phone)),
+        ("fn", hashed(first_name, normalize_text)),
+        ("ln", hashed(last_name, normalize_text)),
+        ("external_id", hashed(customer_id.as_deref(), normalize_text)),
+    ] {
+        if let Some(value) = value {
+            data.insert(key.to_string(), json!([value]));
+        }
+    }
+    for (key, value) in [
+        ("fbp", &context.fbp),
+        ("fbc", &context.fbc),
+        ("client_ip_address", &context.client_ip),
+        ("client_user_agent", &context.client_user_agent),
+    ] {
+        if let Some(value) = value {
+            data.insert(key.to_string(), json!(value));
+        }
+    }
+    serde_json::Value::Object(data)
+}
+
+fn major_units(cents: i64) -> f64 {
+    cents as f64 / 100.0
+}
+
+async fn queue_event(pool: &sqlx::PgPool, event: serde_json::Value) -> Result<Uuid, String> {
+    let payload = serde_json::to_value(MetaEventPayload { event }).map_err(|e| e.to_string())?;
+    jobs::enqueue(pool, META_EVENT_JOB, payload, Utc::now())
+        .await
+        .map_err(|e| format!("Failed to queue Conversions API event: {}", e))
+}
+
+// Build and queue the Purchase event of a new order, if forwarding is on and the shopper agreed
+pub async fn queue_purchase(pool: &sqlx::PgPool, order_id: Uuid, phone: Option<&str>) -> Result<bool, String> {
+    let db_err = |e: sqlx::Error| format!("DB error: {}", e);
+    let settings = load_settings(pool).await.map_err(db_err)?;
+    if !settings.enabled {
+        return Ok(false);
+    }
+    let order = order_query!("SELECT", "FROM orders WHERE id = $1", order_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(db_err)?
+        .ok_or_else(|| format!("Order {} not found", order_id))?;
+    if !order.livemode && !settings.forward_test_orders {
+        return Ok(false);
+    }
+    let context = sqlx::query_as!(
+        TrackingContext,
+        "SELECT tracking_consent, fbp, fbc, client_ip, client_user_agent FROM checkout_carts WHERE id = $1",
+        order.cart_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(db_err)?
+    .unwrap_or_default();
+    // Orders without a checkout (e.g. manual orders) have no consent on record
+    if settings.require_consent && context.tracking_consent != Some(true) {
+        return Ok(false);
+    }
+    let items = sqlx::query_as!(
+        OrderItem,
+        r#"SELECT id, order_id, product_id, product_name, product_description, quantity, unit_price, total_price, created_at AS "created_at!" FROM order_items WHERE order_id = $1 ORDER BY created_at, id"#,
+        order.id,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(db_err)?;
+
+    let contents: Vec<serde_json::Value> = items
+        .iter()
+        .filter_map(|item| {
+            let id = item.product_id?;
+            Some(json!({ "id": id.to_string(), "quantity": item.quantity, "item_price": major_units(item.unit_price) }))
+        })
+        .collect();
+    let event = json!({
+        "event_name": "Purchase",
+        "event_time": order.created_at.timestamp(),
+        "event_id": order.id,
+        "action_source": "website",
+        "user_data": user_data(
+            order.customer_email.as_deref(),
+            phone,
+            order.customer_name.as_deref(),
+            order.customer_id,
+            &context,
+        ),
+        "custom_data": {
+            "value": major_units(order.total_amount),
+            "currency": order.currency.to_uppercase(),
+            "order_id": order.id,
+            "content_type": "product",
+            "contents": contents,
+        },
+    });
+    queue_event(pool, event).await?;
+    Ok(true)
+}
+
+// Job handler: send one event, unless forwarding was turned off since it was queued
+pub async fn run_meta_event(pool: &sqlx::PgPool, payload: &serde_json::Value) -> Result<(), String> {
+    let payload: MetaEventPayload =
+        serde_json::from_value(payload.clone()).map_err(|e| format!("Invalid Conversions API payload: {}", e))?;
+    let settings = load_settings(pool).await.map_err(|e| format!("DB error: {}", e))?;
+    if !settings.enabled {
+        return Ok(());
+    }
+    let (Some(pixel_id), Some(access_token)) = (&settings.pixel_id, &settings.access_token) else {
+        return Err("Meta pixel id or access token not configured".to_string());
+    };
+    let mut body = json!({ "data": [payload.event] });
+    if let Some(code) = &settings.test_event_code {
+        body["test_event_code"] = json!(code);
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| format!("HTTP client error: {}", e))?;
+    let response = client
+        .post(format!("{}/{}/events", GRAPH_API_URL, pixel_id))
+        .query(&[("access_token", access_token)])
+        .json(&body)
+        .with_request_id()
+        .send()
+        .await
+        .map_err(|e| format!("Conversions API request failed: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let detail = response.text().await.unwrap_or_default();
+        return Err(format!("Conversions API returned {}: {}", status, detail));
+    }
+    tracing::info!(
+        "Sent {} event {} to Meta",
+        payload.event["event_name"].as_str().unwrap_or("?"),
+        payload.event["event_id"]
+    );
+    Ok(())
+}
+
+// Queues the Purchase event of every new order
+pub struct MetaConversionsSubscriber {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl MetaConversionsSubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for MetaConversionsSubscriber {
+    fn name(&self) -> &'static str {
+        "meta_conversions"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let DomainEvent::OrderCreated { order_id, customer_phone, .. } = event else {
+            return Ok(());
+        };
+        queue_purchase(&self.pool, *order_id, customer_phone.as_deref()).await?;
+        Ok(())
+    }
+}
+
+pub fn meta_conversions_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/tracking/add-to-cart", post(track_add_to_cart))
+        .route("/api/admin/meta-conversions", get(get_settings).put(update_settings))
+        .with_state(app_state)
+}
+
+// AddToCart from the storefront, priced on the server. Logged-in customers are matched by their
+// hashed email and id
+async fn track_add_to_cart(
+    State(app_state): State<Arc<AppState>>,
+    customer: Option<AuthenticatedCustomer>,
+    headers: HeaderMap,
+    Json(request): Json<AddToCartRequest>,
+) -> Result<Json<AddToCartResponse>, (StatusCode, String)> {
+    let event_id = request.event_id.trim();
+    if event_id.is_empty() || event_id.len() > MAX_EVENT_ID_LEN {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("event_id is required and at most {} characters", MAX_EVENT_ID_LEN),
+        ));
+    }
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let settings = load_settings(&app_state.pool).await.map_err(db_err)?;
+    let context = request.browser.resolve(&headers);
+    if !settings.enabled || (settings.require_consent && context.tracking_consent != Some(true)) {
+        return Ok(Json(AddToCartResponse { queued: false }));
+    }
+    let line = CartLine {
+        product_id: request.product_id,
+        quantity: request.quantity,
+        ship_to: None,
+    };
+    let value = pricing::cart_total_cents(&app_state.pool, &[line])
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let email: Option<String> = match &customer {
+        Some(customer) => sqlx::query_scalar!(r#"SELECT email AS "email: Encrypted" FROM customers WHERE id = $1"#, customer.customer_id)
+            .fetch_optional(&*app_state.pool)
+            .await
+            .map_err(db_err)?
+            .map(Encrypted::into_inner),
+        None => None,
+    };
+    let mut event = json!({
+        "event_name": "AddToCart",
+        "event_time": Utc::now().timestamp(),
+        "event_id": event_id,
+        "action_source": "website",
+        "user_data": user_data(email.as_deref(), None, None, customer.as_ref().map(|c| c.customer_id), &context),
+        "custom_data": {
+            "value": major_units(value),
+            "currency": CURRENCY,
+            "content_type": "product",
+            "contents": [{ "id": request.product_id.to_string(), "quantity": request.quantity }],
+        },
+    });
+    if let Some(url) = request.event_source_url.as_deref().map(str::trim).filter(|u| !u.is_empty()) {
+        event["event_source_url"] = json!(url);
+    }
+    queue_event(&app_state.pool, event)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(AddToCartResponse { queued: true }))
+}
+
+fn optional(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+fn settings_response(settings: MetaSettings) -> SettingsResponse {
+    SettingsResponse {
+        access_token_set: settings.access_token.is_some(),
+        settings,
+    }
+}
+
+async fn get_settings(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<SettingsResponse>, (StatusCode, String)> {
+    let settings = load_settings(&app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(settings_response(settings)))
+}
+
+// Sending can only be enabled with a pixel id and access token
+async fn update_settings(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<SettingsInput>,
+) -> Result<Json<SettingsResponse>, (StatusCode, String)> {
+    let pixel_id = optional(input.pixel_id);
+    if pixel_id.as_deref().is_some_and(|id| !id.chars().all(|c| c.is_ascii_digit())) {
+        return Err((StatusCode::BAD_REQUEST, "pixel_id must be numeric".to_string()));
+    }
+    let current = load_settings(&app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let access_token = optional(input.access_token).or(current.access_token);
+    if input.enabled && (pixel_id.is_none() || access_token.is_none()) {
+        return Err((StatusCode::BAD_REQUEST, "pixel_id and access_token are required to enable sending".to_string()));
+    }
+
+    let settings = sqlx::query_as!(
+        MetaSettings,
+        "INSERT INTO meta_conversions (id, enabled, pixel_id, access_token, test_event_code, require_consent, forward_test_orders)
+         VALUES (1, $1, $2, $3, $4, $5, $6)
+         ON CONFLICT (id) DO UPDATE SET
+             enabled = EXCLUDED.enabled,
+             pixel_id = EXCLUDED.pixel_id,
+             access_token = EXCLUDED.access_token,
+             test_event_code = EXCLUDED.test_event_code,
+             require_consent = EXCLUDED.require_consent,
+             forward_test_orders = EXCLUDED.forward_test_orders,
+             updated_at = NOW()
+         RETURNING enabled, pixel_id, access_token, test_event_code, require_consent, forward_test_orders, updated_at",
+        input.enabled,
+        pixel_id,
+        access_token,
+        optional(input.test_event_code),
+        input.require_consent.unwrap_or(true),
+        input.forward_test_orders,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    Ok(Json(settings_response(settings)))
+}
@@ -0,0 +1,371 @@
+// Notifications Module - Scheduling and throttling for outbound SMS and email
+// Notification SMS wait out quiet hours in the customer's timezone, non-urgent emails go out
+// in batches, and sends over a provider's rate limit (or after it ran out of quota) are pushed
+// back in the job queue instead of failing
+
+pub mod ops;
+
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use chrono::{Duration, NaiveTime, TimeZone, Timelike};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::customer_auth::AuthenticatedCustomer;
+use crate::jobs::{self, JobOutcome};
+use crate::lettre_email::EmailConfig;
+use crate::pii;
+use crate::sms_log::is_opted_out;
+use crate::textbelt_sms::{send_sms_via_provider, SmsConfig};
+use crate::AppState;
+
+pub const NOTIFICATION_EMAIL_JOB: &str = "notification_email";
+pub const NOTIFICATION_SMS_JOB: &str = "notification_sms";
+
+pub const CHANNEL_SMS: &str = "sms";
+pub const CHANNEL_EMAIL: &str = "email";
+
+const QUOTA_PAUSE_MINUTES: i64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmailPriority {
+    // Sent as soon as the rate limit allows
+    Immediate,
+    // Held until the next batch window (EMAIL_BATCH_INTERVAL_MINUTES)
+    Batched,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailNotification {
+    pub to: String,
+    pub subject: String,
+    pub html: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SmsNotification {
+    pub phone: String, // E.164
+    pub message: String,
+    pub timezone: String, // Recipient's timezone, for quiet hours
+}
+
+#[derive(Serialize)]
+pub struct ChannelStatus {
+    pub channel: String,
+    pub queued: i64,
+    pub sent_last_minute: i64,
+    pub limit_per_minute: i64,
+    pub paused_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct NotificationSettingsInput {
+    pub timezone: Option<String>,
+    pub price_drop_emails: Option<bool>, // Unchanged when omitted
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct NotificationSettings {
+    pub timezone: Option<String>,
+    pub price_drop_emails: bool, // Emails when a wishlisted product's price drops
+}
+
+// Per-minute send limit for a channel (SMS_RATE_LIMIT_PER_MINUTE / EMAIL_RATE_LIMIT_PER_MINUTE)
+fn rate_limit(channel: &str) -> i64 {
+    let limits = &app_config::get().limits;
+    match channel {
+        CHANNEL_SMS => limits.sms_per_minute,
+        _ => limits.email_per_minute,
+    }
+}
+
+// Timezone used for customers who haven't set one (validated at startup)
+fn default_timezone() -> Tz {
+    app_config::get().notifications.store_timezone.parse().unwrap_or(Tz::UTC)
+}
+
+// Local hours during which notification SMS are held (QUIET_HOURS_START to QUIET_HOURS_END)
+fn quiet_hours() -> (u32, u32) {
+    let notifications = &app_config::get().notifications;
+    (notifications.quiet_hours_start, notifications.quiet_hours_end)
+}
+
+// Earliest time at or after `at` that falls outside quiet hours in `tz`
+fn outside_quiet_hours(at: DateTime<Utc>, tz: Tz) -> DateTime<Utc> {
+    let (start, end) = quiet_hours();
+    let local = at.with_timezone(&tz);
+    let hour = local.hour();
+    let quiet = match start.cmp(&end) {
+        std::cmp::Ordering::Equal => false,
+        std::cmp::Ordering::Greater => hour >= start || hour < end, // Spans midnight
+        std::cmp::Ordering::Less => hour >= start && hour < end,
+    };
+    if !quiet {
+        return at;
+    }
+
+    let mut date = local.date_naive();
+    if hour >= end {
+        date = date.succ_opt().unwrap_or(date);
+    }
+    let resume = date.and_time(NaiveTime::from_hms_opt(end, 0, 0).unwrap_or_default());
+    // A DST gap can skip the hour entirely; resume an hour later then
+    tz.from_local_datetime(&resume)
+        .earliest()
+        .or_else(|| tz.from_local_datetime(&(resume + Duration::hours(1))).earliest())
+        .map(|resume| resume.with_timezone(&Utc))
+        .unwrap_or(at)
+}
+
+// Start of the next batch window for non-urgent email
+fn next_batch_window(now: DateTime<Utc>) -> DateTime<Utc> {
+    let window = app_config::get().notifications.email_batch_interval_minutes * 60;
+    let next = (now.timestamp() / window + 1) * window;
+    Utc.timestamp_opt(next, 0).single().unwrap_or(now)
+}
+
+async fn customer_timezone(pool: &sqlx::PgPool, phone: &str) -> Result<Tz, sqlx::Error> {
+    let timezone: Option<String> = sqlx::query_scalar!(
+        r#"SELECT timezone AS "timezone!" FROM customers WHERE phone_hash = $1 AND timezone IS NOT NULL ORDER BY phone_verified_at DESC NULLS LAST LIMIT 1"#,
+        pii::lookup_hash(phone),
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(timezone.and_then(|tz| tz.parse().ok()).unwrap_or_else(default_timezone))
+}
+
+// When a send may go out on this channel: None now, or the time to retry
+async fn throttle(pool: &sqlx::PgPool, channel: &str) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    let paused_until: Option<DateTime<Utc>> =
+        sqlx::query_scalar!(
+            "SELECT paused_until FROM notification_pauses WHERE channel = $1 AND paused_until > NOW()",
+            channel,
+        )
+        .fetch_optional(pool)
+        .await?;
+    if paused_until.is_some() {
+        return Ok(paused_until);
+    }
+
+    let (sent, oldest): (i64, Option<DateTime<Utc>>) = sqlx::query!(
+        r#"SELECT COUNT(*) AS "sent!", MIN(created_at) AS oldest FROM notification_sends
+         WHERE channel = $1 AND created_at > NOW() - INTERVAL '1 minute'"#,
+        channel,
+    )
+    .map(|r| (r.sent, r.oldest))
+    .fetch_one(pool)
+    .await?;
+    if sent >= rate_limit(channel) {
+        // A slot frees up once the oldest send in the window ages out
+        return Ok(Some(oldest.unwrap_or_else(Utc::now) + Duration::minutes(1)));
+    }
+    Ok(None)
+}
+
+// Count a send against the channel's rate limit; every SMS and email send path calls this
+pub async fn record_send(pool: &sqlx::PgPool, channel: &str) {
+    let recorded = sqlx::query!(
+        "WITH pruned AS (DELETE FROM notification_sends WHERE created_at < NOW() - INTERVAL '1 hour')
+         INSERT INTO notification_sends (channel) VALUES ($1)",
+        channel,
+    )
+    .execute(pool)
+    .await;
+    if let Err(e) = recorded {
+        tracing::error!("Failed to record {} send: {}", channel, e);
+    }
+}
+
+// Hold a channel's queued sends, e.g. after the provider reported no quota left
+pub async fn pause_channel(pool: &sqlx::PgPool, channel: &str, reason: &str) {
+    let paused = sqlx::query!(
+        "INSERT INTO notification_pauses (channel, paused_until, reason)
+         VALUES ($1, NOW() + make_interval(mins => $2), $3)
+         ON CONFLICT (channel) DO UPDATE SET paused_until = EXCLUDED.paused_until, reason = EXCLUDED.reason, created_at = NOW()",
+        channel,
+        QUOTA_PAUSE_MINUTES as i32,
+        reason,
+    )
+    .execute(pool)
+    .await;
+    match paused {
+        Ok(_) => tracing::error!("{} notifications paused for {} min: {}", channel, QUOTA_PAUSE_MINUTES, reason),
+        Err(e) => tracing::error!("Failed to pause {} notifications: {}", channel, e),
+    }
+}
+
+// Queue an email; the job worker sends it within the email rate limit
+pub async fn queue_email(
+    pool: &sqlx::PgPool,
+    to: &str,
+    subject: &str,
+    html: &str,
+    priority: EmailPriority,
+) -> Result<(), String> {
+    let run_at = match priority {
+        EmailPriority::Immediate => Utc::now(),
+        EmailPriority::Batched => next_batch_window(Utc::now()),
+    };
+    let payload = serde_json::to_value(EmailNotification {
+        to: to.to_string(),
+        subject: subject.to_string(),
+        html: html.to_string(),
+    })
+    .map_err(|e| format!("Failed to serialize email: {}", e))?;
+    jobs::enqueue(pool, NOTIFICATION_EMAIL_JOB, payload, run_at)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+    Ok(())
+}
+
+// Queue a notification SMS to an E.164 number, held until quiet hours end for the recipient
+pub async fn queue_sms(pool: &sqlx::PgPool, phone: &str, message: &str) -> Result<(), String> {
+    let tz = customer_timezone(pool, phone).await.map_err(|e| format!("DB error: {}", e))?;
+    let run_at = outside_quiet_hours(Utc::now(), tz);
+    if run_at > Utc::now() {
+        tracing::info!("SMS to {} held for quiet hours until {}", phone, run_at);
+    }
+    let payload = serde_json::to_value(SmsNotification {
+        phone: phone.to_string(),
+        message: message.to_string(),
+        timezone: tz.name().to_string(),
+    })
+    .map_err(|e| format!("Failed to serialize SMS: {}", e))?;
+    jobs::enqueue(pool, NOTIFICATION_SMS_JOB, payload, run_at)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+    Ok(())
+}
+
+pub async fn run_notification_email(pool: &sqlx::PgPool, payload: &serde_json::Value) -> Result<JobOutcome, String> {
+    let email: EmailNotification =
+        serde_json::from_value(payload.clone()).map_err(|e| format!("Invalid email payload: {}", e))?;
+    if let Some(retry_at) = throttle(pool, CHANNEL_EMAIL).await.map_err(|e| format!("DB error: {}", e))? {
+        return Ok(JobOutcome::Deferred(retry_at));
+    }
+    let config = EmailConfig::from_env().ok_or("Email not configured")?;
+    crate::webhooks::stripe::send_html_email(pool, &config, &email.to, &email.subject, &email.html).await?;
+    tracing::info!("Email '{}' sent to {}", email.subject, email.to);
+    Ok(JobOutcome::Done)
+}
+
+pub async fn run_notification_sms(pool: &sqlx::PgPool, payload: &serde_json::Value) -> Result<JobOutcome, String> {
+    let sms: SmsNotification = serde_json::from_value(payload.clone()).map_err(|e| format!("Invalid SMS payload: {}", e))?;
+    if is_opted_out(pool, &sms.phone).await.map_err(|e| format!("DB error: {}", e))? {
+        tracing::info!("Dropping queued SMS to {}: opted out", sms.phone);
+        return Ok(JobOutcome::Done);
+    }
+
+    // Retries and throttling can push a send into quiet hours
+    let tz: Tz = sms.timezone.parse().unwrap_or_else(|_| default_timezone());
+    let now = Utc::now();
+    let allowed_at = outside_quiet_hours(now, tz);
+    if allowed_at > now {
+        return Ok(JobOutcome::Deferred(allowed_at));
+    }
+    if let Some(retry_at) = throttle(pool, CHANNEL_SMS).await.map_err(|e| format!("DB error: {}", e))? {
+        return Ok(JobOutcome::Deferred(outside_quiet_hours(retry_at, tz)));
+    }
+
+    let config = SmsConfig::from_env().ok_or("SMS not configured")?;
+    match send_sms_via_provider(pool, &config, &sms.phone, &sms.message).await {
+        Ok((_, quota_remaining)) => {
+            if quota_remaining == Some(0) {
+                pause_channel(pool, CHANNEL_SMS, "Textbelt quota used up").await;
+            }
+            Ok(JobOutcome::Done)
+        }
+        // Out of quota: wait for it to be topped up rather than burning retries
+        Err(e) if e.to_lowercase().contains("quota") => {
+            pause_channel(pool, CHANNEL_SMS, &e).await;
+            Ok(JobOutcome::Deferred(Utc::now() + Duration::minutes(QUOTA_PAUSE_MINUTES)))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub fn notification_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/notifications/status", get(get_status))
+        .route(
+            "/api/customers/me/notification-settings",
+            get(get_my_settings).put(update_my_settings),
+        )
+        .with_state(app_state)
+}
+
+// Queue depth, current send rate and any quota pause for each channel
+async fn get_status(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ChannelStatus>>, (StatusCode, String)> {
+    let mut statuses = Vec::new();
+    for (channel, job_type) in [(CHANNEL_SMS, NOTIFICATION_SMS_JOB), (CHANNEL_EMAIL, NOTIFICATION_EMAIL_JOB)] {
+        let (queued, sent_last_minute, paused_until): (i64, i64, Option<DateTime<Utc>>) = sqlx::query!(
+            r#"SELECT
+                 (SELECT COUNT(*) FROM jobs WHERE job_type = $1 AND status IN ('pending', 'running')) AS "queued!",
+                 (SELECT COUNT(*) FROM notification_sends WHERE channel = $2 AND created_at > NOW() - INTERVAL '1 minute') AS "sent_last_minute!",
+                 (SELECT paused_until FROM notification_pauses WHERE channel = $2 AND paused_until > NOW()) AS paused_until"#,
+            job_type,
+            channel,
+        )
+        .map(|r| (r.queued, r.sent_last_minute, r.paused_until))
+        .fetch_one(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+        statuses.push(ChannelStatus {
+            channel: channel.to_string(),
+            queued,
+            sent_last_minute,
+            limit_per_minute: rate_limit(channel),
+            paused_until,
+        });
+    }
+    Ok(Json(statuses))
+}
+
+async fn get_my_settings(
+    customer: AuthenticatedCustomer,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<NotificationSettings>, (StatusCode, String)> {
+    let settings = sqlx::query_as!(
+        NotificationSettings,
+        "SELECT timezone, price_drop_emails FROM customers WHERE id = $1",
+        customer.customer_id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let settings = settings.ok_or((StatusCode::NOT_FOUND, "Customer not found".to_string()))?;
+    Ok(Json(settings))
+}
+
+// Timezone is an IANA name like "America/New_York"; null falls back to the store timezone.
+// price_drop_emails is left as it is when omitted
+async fn update_my_settings(
+    customer: AuthenticatedCustomer,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<NotificationSettingsInput>,
+) -> Result<Json<NotificationSettings>, (StatusCode, String)> {
+    let timezone = input.timezone.map(|tz| tz.trim().to_string()).filter(|tz| !tz.is_empty());
+    if let Some(tz) = &timezone {
+        tz.parse::<Tz>()
+            .map_err(|_| (StatusCode::BAD_REQUEST, format!("Unknown timezone '{}'", tz)))?;
+    }
+    let settings = sqlx::query_as!(
+        NotificationSettings,
+        "UPDATE customers SET timezone = $1, price_drop_emails = COALESCE($2, price_drop_emails), updated_at = NOW()
+         WHERE id = $3 RETURNING timezone, price_drop_emails",
+        timezone.as_deref(),
+        input.price_drop_emails,
+        customer.customer_id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let settings = settings.ok_or((StatusCode::NOT_FOUND, "Customer not found".to_string()))?;
+    Ok(Json(settings))
+}
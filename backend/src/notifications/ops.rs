@@ -0,0 +1,238 @@
+// Ops Notifications - Operational messages for the team's Slack or Discord channel
+// Large orders, payment failure spikes, new disputes, low stock and rejected webhook signatures are
+// collected and posted as one message per batch interval, so a burst never floods the channel
+
+use async_trait::async_trait;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::app_config;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::request_id::WithRequestId;
+
+const PAYMENT_FAILURE_WINDOW: Duration = Duration::from_secs(10 * 60);
+const MAX_LINES_PER_MESSAGE: usize = 20;
+const MAX_PENDING_LINES: usize = 500; // Beyond this, lines are only counted
+
+// Post a message to a Slack or Discord incoming webhook (Discord URLs are detected by host)
+pub async fn post_webhook_message(webhook_url: &str, text: &str) -> Result<(), String> {
+    let body = if webhook_url.contains("discord.com/") || webhook_url.contains("discordapp.com/") {
+        json!({ "content": text })
+    } else {
+        json!({ "text": text })
+    };
+    let response = reqwest::Client::new()
+        .post(webhook_url)
+        .json(&body)
+        .with_request_id()
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach chat webhook: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Chat webhook returned {}", response.status()));
+    }
+    Ok(())
+}
+
+struct OpsConfig {
+    webhook_url: String,
+    batch_interval: Duration,
+    large_order_cents: i64,
+    payment_failure_spike: usize,
+}
+
+impl OpsConfig {
+    fn from_env() -> Option<Self> {
+        let ops = &app_config::get().ops;
+        Some(Self {
+            webhook_url: ops.webhook_url.as_ref()?.expose().to_string(),
+            batch_interval: Duration::from_secs(ops.batch_interval_secs),
+            large_order_cents: (ops.large_order_amount * 100.0) as i64,
+            payment_failure_spike: ops.payment_failure_spike,
+        })
+    }
+}
+
+// Handle for queueing ops messages; a no-op when OPS_WEBHOOK_URL is not set
+#[derive(Clone, Default)]
+pub struct OpsNotifier {
+    sender: Option<mpsc::UnboundedSender<String>>,
+    large_order_cents: i64,
+    payment_failure_spike: usize,
+    payment_failures: Arc<Mutex<VecDeque<Instant>>>,
+}
+
+impl OpsNotifier {
+    // Reads the OPS_* settings and starts the batching task
+    pub fn from_env() -> Self {
+        let Some(config) = OpsConfig::from_env() else {
+            return Self::default();
+        };
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let notifier = Self {
+            sender: Some(sender),
+            large_order_cents: config.large_order_cents,
+            payment_failure_spike: config.payment_failure_spike,
+            payment_failures: Arc::default(),
+        };
+        tokio::spawn(run_batcher(config, receiver));
+        notifier
+    }
+
+    pub fn notify(&self, line: String) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(line);
+        }
+    }
+
+    // Called by the webhook handlers whenever a request fails signature verification
+    pub fn webhook_signature_failed(&self, source: &str) {
+        self.notify(format!(":warning: Rejected {} webhook with an invalid signature", source));
+    }
+
+    // The failure count once failures within the window reach the spike threshold; the window then starts over
+    fn record_payment_failure(&self) -> Option<usize> {
+        let mut failures = self.payment_failures.lock().unwrap();
+        let now = Instant::now();
+        while failures.front().is_some_and(|at| now.duration_since(*at) > PAYMENT_FAILURE_WINDOW) {
+            failures.pop_front();
+        }
+        failures.push_back(now);
+        if failures.len() < self.payment_failure_spike {
+            return None;
+        }
+        let count = failures.len();
+        failures.clear();
+        Some(count)
+    }
+}
+
+// Collects lines and posts them as one message every batch interval
+async fn run_batcher(config: OpsConfig, mut receiver: mpsc::UnboundedReceiver<String>) {
+    let mut pending: Vec<String> = Vec::new();
+    let mut dropped = 0usize;
+    let mut interval = tokio::time::interval(config.batch_interval);
+    loop {
+        tokio::select! {
+            line = receiver.recv() => match line {
+                Some(line) if pending.len() < MAX_PENDING_LINES => pending.push(line),
+                Some(_) => dropped += 1,
+                None => break,
+            },
+            _ = interval.tick() => {
+                if pending.is_empty() {
+                    continue;
+                }
+                // Repeats (e.g. a run of rejected webhooks) collapse into one line with a count
+                let mut lines: Vec<(String, usize)> = Vec::new();
+                for line in pending.drain(..) {
+                    match lines.iter_mut().find(|(seen, _)| *seen == line) {
+                        Some((_, count)) => *count += 1,
+                        None => lines.push((line, 1)),
+                    }
+                }
+                let more = lines.len().saturating_sub(MAX_LINES_PER_MESSAGE) + dropped;
+                let mut text = lines
+                    .iter()
+                    .take(MAX_LINES_PER_MESSAGE)
+                    .map(|(line, count)| if *count > 1 { format!("{} (×{})", line, count) } else { line.clone() })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                if more > 0 {
+                    text.push_str(&format!("\n…and {} more", more));
+                }
+                if let Err(e) = post_webhook_message(&config.webhook_url, &text).await {
+                    tracing::error!("Failed to post ops notification: {}", e);
+                }
+                dropped = 0;
+            }
+        }
+    }
+}
+
+// Turns domain events into ops messages
+pub struct OpsSubscriber {
+    notifier: OpsNotifier,
+}
+
+impl OpsSubscriber {
+    pub fn new(notifier: OpsNotifier) -> Self {
+        Self { notifier }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for OpsSubscriber {
+    fn name(&self) -> &'static str {
+        "ops_notifications"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        if self.notifier.sender.is_none() {
+            return Ok(());
+        }
+        match event {
+            DomainEvent::OrderCreated {
+                order_id,
+                total_amount,
+                currency,
+                customer_email,
+                ..
+            } if *total_amount >= self.notifier.large_order_cents => {
+                self.notifier.notify(format!(
+                    ":moneybag: Large order {} for {:.2} {}{}",
+                    order_id,
+                    *total_amount as f64 / 100.0,
+                    currency.to_uppercase(),
+                    customer_email.as_ref().map(|e| format!(" from {}", e)).unwrap_or_default()
+                ));
+            }
+            DomainEvent::PaymentFailed { provider, reason, .. } => {
+                if let Some(count) = self.notifier.record_payment_failure() {
+                    self.notifier.notify(format!(
+                        ":rotating_light: {} failed payments in the last {} minutes (latest via {}{})",
+                        count,
+                        PAYMENT_FAILURE_WINDOW.as_secs() / 60,
+                        provider,
+                        reason.as_ref().map(|r| format!(": {}", r)).unwrap_or_default()
+                    ));
+                }
+            }
+            DomainEvent::DisputeOpened {
+                order_id,
+                amount,
+                currency,
+                reason,
+                evidence_due_by,
+                ..
+            } => {
+                self.notifier.notify(format!(
+                    ":rotating_light: New {:.2} {} dispute ({}){}{}",
+                    *amount as f64 / 100.0,
+                    currency,
+                    reason,
+                    order_id.map(|id| format!(" on order {}", id)).unwrap_or_default(),
+                    evidence_due_by
+                        .map(|d| format!(", evidence due {}", d.format("%Y-%m-%d")))
+                        .unwrap_or_default()
+                ));
+            }
+            DomainEvent::LowStock {
+                product_id,
+                product_name,
+                inventory,
+                threshold,
+            } => {
+                self.notifier.notify(format!(
+                    ":package: Low stock: {} (#{}) has {} left (threshold {})",
+                    product_name, product_id, inventory, threshold
+                ));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,428 @@
+// Order Shipments Module - "Ship to multiple addresses" orders
+// Checkout assigns each cart line to an address book entry; every address becomes
+// a child shipment with its own subtotal, shipping charge, fulfillments and label
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::easypost_shipping::{
+    pack_parcels, packable_items, purchase_cheapest_label, Address, ShipmentForm, MAX_PARCEL_WEIGHT_OZ,
+};
+use crate::events::DomainEvent;
+use crate::pii::Encrypted;
+use crate::pricing::{cart_total_cents, to_cents, CartLine};
+use crate::route_limits::{self, RouteGroup};
+use crate::shipping_rules::quote_rules;
+use crate::split_shipments;
+use crate::warehouses::{plan_fulfillment, record_fulfillments, OrderFulfillment, Warehouse};
+use crate::AppState;
+
+// Named ship-to address supplied at checkout and referenced by items[].ship_to
+#[derive(Deserialize, Clone)]
+pub struct AddressBookEntry {
+    pub key: String,
+    pub address: Address,
+    pub shipping_method_id: Option<i32>, // Shipping rules method; cheapest when omitted
+}
+
+// One ship-to address with its lines and charges, as priced at checkout
+#[derive(Serialize, Clone)]
+pub struct PlannedShipment {
+    pub address_key: String,
+    pub address: Address,
+    pub items: Vec<CartLine>,
+    pub shipping_method: String,
+    pub subtotal_amount: i64, // in cents
+    pub shipping_amount: i64, // in cents
+}
+
+// Database model for order shipments
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct OrderShipment {
+    pub id: Uuid,
+    pub payment_intent_id: String,
+    pub order_id: Option<Uuid>,
+    pub address_key: String,
+    pub recipient_name: Option<Encrypted>,
+    pub street1: Encrypted,
+    pub street2: Option<Encrypted>,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    pub country: String,
+    pub phone: Option<Encrypted>,
+    pub shipping_method: String,
+    pub subtotal_amount: i64,
+    pub shipping_amount: i64,
+    pub status: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl OrderShipment {
+    // Destination address for fulfillment planning and labels
+    pub fn address(&self) -> Address {
+        Address {
+            name: self.recipient_name.clone().map(Encrypted::into_inner),
+            street1: self.street1.clone().into_inner(),
+            street2: self.street2.clone().map(Encrypted::into_inner),
+            city: self.city.clone(),
+            state: self.state.clone(),
+            zip: self.zip.clone(),
+            country: Some(self.country.clone()),
+            phone: self.phone.clone().map(Encrypted::into_inner),
+            email: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct OrderShipmentItem {
+    pub product_id: Option<i32>,
+    pub quantity: i32,
+}
+
+#[derive(Serialize)]
+pub struct OrderShipmentDetail {
+    #[serde(flatten)]
+    pub shipment: OrderShipment,
+    pub items: Vec<OrderShipmentItem>,
+    pub fulfillments: Vec<OrderFulfillment>,
+}
+
+#[derive(Deserialize)]
+pub struct FulfillmentLabelRequest {
+    pub to_address: Option<Address>, // Required when the fulfillment isn't tied to an order shipment
+}
+
+#[derive(Serialize)]
+pub struct FulfillmentLabelResponse {
+    pub success: bool,
+    pub fulfillment: OrderFulfillment,
+    pub service: String,
+    pub rate: String,
+    pub label_url: String,
+    pub forms: Vec<ShipmentForm>,
+}
+
+// Group cart lines by their ship_to key and price each address separately:
+// items at current prices plus the chosen (or cheapest) shipping rules method
+pub async fn plan_shipments(
+    pool: &sqlx::PgPool,
+    address_book: &[AddressBookEntry],
+    items: &[CartLine],
+) -> Result<Vec<PlannedShipment>, String> {
+    for (index, entry) in address_book.iter().enumerate() {
+        if address_book[..index].iter().any(|e| e.key == entry.key) {
+            return Err(format!("Duplicate address book key '{}'", entry.key));
+        }
+    }
+    for item in items {
+        let key = item
+            .ship_to
+            .as_deref()
+            .ok_or_else(|| format!("Product {} has no ship_to address", item.product_id))?;
+        if !address_book.iter().any(|e| e.key == key) {
+            return Err(format!("Unknown ship_to address '{}'", key));
+        }
+    }
+
+    let mut shipments = Vec::new();
+    for entry in address_book {
+        let lines: Vec<CartLine> = items
+            .iter()
+            .filter(|i| i.ship_to.as_deref() == Some(entry.key.as_str()))
+            .cloned()
+            .collect();
+        if lines.is_empty() {
+            continue;
+        }
+
+        let subtotal_amount = cart_total_cents(pool, &lines).await?;
+        let quote = quote_rules(pool, &entry.address, &lines).await?;
+        let option = match entry.shipping_method_id {
+            Some(method_id) => quote
+                .options
+                .iter()
+                .find(|o| o.method_id == Some(method_id))
+                .ok_or_else(|| format!("Shipping method {} is not available for '{}'", method_id, entry.key))?,
+            None => quote
+                .options
+                .first()
+                .ok_or_else(|| format!("No shipping available for '{}'", entry.key))?,
+        };
+        let rate: f64 = option.rate.parse().map_err(|_| format!("Invalid shipping rate for {}", option.service))?;
+
+        shipments.push(PlannedShipment {
+            address_key: entry.key.clone(),
+            address: entry.address.clone(),
+            items: lines,
+            shipping_method: option.service.clone(),
+            subtotal_amount,
+            shipping_amount: to_cents(rate),
+        });
+    }
+    Ok(shipments)
+}
+
+// Persist planned shipments against the payment intent that will pay for them
+pub async fn save_shipments(
+    pool: &sqlx::PgPool,
+    payment_intent_id: &str,
+    shipments: &[PlannedShipment],
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for shipment in shipments {
+        let address = &shipment.address;
+        let shipment_id: Uuid = sqlx::query_scalar!(
+            "INSERT INTO order_shipments
+                (payment_intent_id, address_key, recipient_name, street1, street2, city, state, zip, country,
+                 phone, shipping_method, subtotal_amount, shipping_amount)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) RETURNING id",
+            payment_intent_id,
+            &shipment.address_key,
+            address.name.as_deref().map(Encrypted::from) as _,
+            Encrypted::from(address.street1.as_str()) as _,
+            address.street2.as_deref().map(Encrypted::from) as _,
+            &address.city,
+            &address.state,
+            &address.zip,
+            address.country.as_deref().unwrap_or("US").to_uppercase(),
+            address.phone.as_deref().map(Encrypted::from) as _,
+            &shipment.shipping_method,
+            shipment.subtotal_amount,
+            shipment.shipping_amount,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        for line in &shipment.items {
+            sqlx::query!(
+                "INSERT INTO order_shipment_items (shipment_id, product_id, quantity) VALUES ($1, $2, $3)",
+                shipment_id,
+                line.product_id,
+                line.quantity,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    tx.commit().await
+}
+
+// Link the shipments created at checkout to the order once payment succeeds
+pub async fn attach_to_order(executor: impl sqlx::PgExecutor<'_>, payment_intent_id: &str, order_id: Uuid) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query!(
+        "UPDATE order_shipments SET order_id = $1 WHERE payment_intent_id = $2 AND order_id IS NULL",
+        order_id,
+        payment_intent_id,
+    )
+    .execute(executor)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+// Admin routes for per-address shipments and their labels
+pub fn order_shipment_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/orders/:id/shipments", get(list_order_shipments))
+        .route("/api/admin/orders/:id/shipments/fulfill", post(fulfill_order_shipments))
+        .route("/api/admin/fulfillments/:id/label", post(buy_fulfillment_label).layer(route_limits::limit(RouteGroup::Labels)))
+        .with_state(app_state)
+}
+
+async fn shipment_items(pool: &sqlx::PgPool, shipment_id: Uuid) -> Result<Vec<OrderShipmentItem>, sqlx::Error> {
+    sqlx::query_as!(
+        OrderShipmentItem,
+        "SELECT product_id, quantity FROM order_shipment_items WHERE shipment_id = $1",
+        shipment_id,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+async fn list_order_shipments(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<Vec<OrderShipmentDetail>>, (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let shipments = sqlx::query_as!(
+        OrderShipment,
+        r#"SELECT id, payment_intent_id, order_id, address_key, recipient_name AS "recipient_name: Encrypted", street1 AS "street1: Encrypted", street2 AS "street2: Encrypted", city, state, zip, country, phone AS "phone: Encrypted", shipping_method, subtotal_amount, shipping_amount, status, created_at FROM order_shipments WHERE order_id = $1 ORDER BY created_at, address_key"#,
+        order_id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(db_err)?;
+
+    let mut details = Vec::new();
+    for shipment in shipments {
+        let items = shipment_items(&app_state.pool, shipment.id).await.map_err(db_err)?;
+        let fulfillments = sqlx::query_as!(
+            OrderFulfillment,
+            "SELECT id, order_id, warehouse_id, status, carrier, tracking_code, shipment_id, created_at, updated_at, order_shipment_id, shipped_at, delivered_at FROM order_fulfillments WHERE order_shipment_id = $1 ORDER BY created_at",
+            shipment.id,
+        )
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(db_err)?;
+        details.push(OrderShipmentDetail { shipment, items, fulfillments });
+    }
+    Ok(Json(details))
+}
+
+// Plan and record warehouse fulfillments for every pending ship-to address
+async fn fulfill_order_shipments(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<Vec<OrderFulfillment>>), (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let shipments = sqlx::query_as!(
+        OrderShipment,
+        r#"SELECT id, payment_intent_id, order_id, address_key, recipient_name AS "recipient_name: Encrypted", street1 AS "street1: Encrypted", street2 AS "street2: Encrypted", city, state, zip, country, phone AS "phone: Encrypted", shipping_method, subtotal_amount, shipping_amount, status, created_at FROM order_shipments WHERE order_id = $1 AND status = 'pending' ORDER BY created_at, address_key"#,
+        order_id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(db_err)?;
+    if shipments.is_empty() {
+        return Err((StatusCode::NOT_FOUND, "No pending shipments for this order".to_string()));
+    }
+
+    // Plan everything before writing so one unshippable address doesn't leave a partial result
+    let mut plans = Vec::new();
+    for shipment in &shipments {
+        let lines: Vec<CartLine> = shipment_items(&app_state.pool, shipment.id)
+            .await
+            .map_err(db_err)?
+            .into_iter()
+            .filter_map(|i| i.product_id.map(|product_id| CartLine { product_id, quantity: i.quantity, ship_to: None }))
+            .collect();
+        let groups = plan_fulfillment(&app_state.pool, &shipment.address(), &lines)
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("{}: {}", shipment.address_key, e)))?;
+        plans.push((shipment.id, groups));
+    }
+
+    let mut tx = app_state.pool.begin().await.map_err(db_err)?;
+    let mut created = Vec::new();
+    for (shipment_id, groups) in &plans {
+        created.extend(record_fulfillments(&mut tx, order_id, Some(*shipment_id), groups).await?);
+        sqlx::query!("UPDATE order_shipments SET status = 'fulfilling' WHERE id = $1", shipment_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_err)?;
+    }
+    tx.commit().await.map_err(db_err)?;
+    app_state.events.publish(DomainEvent::OrderFulfilled { order_id });
+
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+// Buy the cheapest label from a fulfillment's warehouse to its ship-to address
+async fn buy_fulfillment_label(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(fulfillment_id): Path<Uuid>,
+    body: Option<Json<FulfillmentLabelRequest>>,
+) -> Result<Json<FulfillmentLabelResponse>, (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let fulfillment = sqlx::query_as!(
+        OrderFulfillment,
+        "SELECT id, order_id, warehouse_id, status, carrier, tracking_code, shipment_id, created_at, updated_at, order_shipment_id, shipped_at, delivered_at FROM order_fulfillments WHERE id = $1",
+        fulfillment_id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(db_err)?
+    .ok_or((StatusCode::NOT_FOUND, "Fulfillment not found".to_string()))?;
+    if fulfillment.tracking_code.is_some() {
+        return Err((StatusCode::CONFLICT, "Fulfillment already has a label".to_string()));
+    }
+
+    let warehouse = sqlx::query_as!(Warehouse, "SELECT id, name, street1, street2, city, state, zip, country, phone, active, handling_days, created_at FROM warehouses WHERE id = $1", fulfillment.warehouse_id)
+        .fetch_optional(&*app_state.pool)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::BAD_REQUEST, "Fulfillment has no origin warehouse".to_string()))?;
+
+    let requested = body.and_then(|Json(b)| b.to_address);
+    let to_address = match (requested, fulfillment.order_shipment_id) {
+        (Some(address), _) => address,
+        (None, Some(shipment_id)) => sqlx::query_as!(
+            OrderShipment,
+            r#"SELECT id, payment_intent_id, order_id, address_key, recipient_name AS "recipient_name: Encrypted", street1 AS "street1: Encrypted", street2 AS "street2: Encrypted", city, state, zip, country, phone AS "phone: Encrypted", shipping_method, subtotal_amount, shipping_amount, status, created_at FROM order_shipments WHERE id = $1"#,
+            shipment_id,
+        )
+        .fetch_one(&*app_state.pool)
+        .await
+        .map_err(db_err)?
+        .address(),
+        (None, None) => return Err((StatusCode::BAD_REQUEST, "to_address is required".to_string())),
+    };
+
+    let lines: Vec<CartLine> = sqlx::query_as!(
+        OrderShipmentItem,
+        "SELECT product_id, quantity FROM order_fulfillment_items WHERE fulfillment_id = $1",
+        fulfillment.id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(db_err)?
+    .into_iter()
+    .filter_map(|i| i.product_id.map(|product_id| CartLine { product_id, quantity: i.quantity, ship_to: None }))
+    .collect();
+
+    let items = packable_items(&app_state.pool, &lines)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let parcels = pack_parcels(&items, MAX_PARCEL_WEIGHT_OZ);
+    let parcel = match parcels.as_slice() {
+        [parcel] => parcel,
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Fulfillment packs into {} parcels; split it before buying a label", parcels.len()),
+            ))
+        }
+    };
+
+    let label = purchase_cheapest_label(&app_state, &warehouse.address(), &to_address, parcel, &lines).await?;
+
+    let mut tx = app_state.pool.begin().await.map_err(db_err)?;
+    let fulfillment = split_shipments::mark_shipped(
+        &mut tx,
+        fulfillment.id,
+        &label.carrier,
+        Some(&label.tracking_code),
+        Some(&label.shipment_id),
+    )
+    .await
+    .map_err(db_err)?;
+    tx.commit().await.map_err(db_err)?;
+    app_state.events.publish(DomainEvent::OrderShipped {
+        order_id: fulfillment.order_id,
+        fulfillment_id: fulfillment.id,
+        carrier: fulfillment.carrier.clone(),
+        tracking_code: fulfillment.tracking_code.clone(),
+    });
+
+    Ok(Json(FulfillmentLabelResponse {
+        success: true,
+        fulfillment,
+        service: label.service,
+        rate: label.rate,
+        label_url: label.label_url,
+        forms: label.forms,
+    }))
+}
@@ -0,0 +1,161 @@
+// Order Snapshots Module - Orders exactly as they were sold
+// Products, prices, tax settings and coupons keep changing after a sale, so order items record
+// the terms they were sold under (see checkout_carts::convert_to_order) and the checkout cart
+// keeps the charged totals. The as-sold view is put back together from those alone, never from
+// the live catalog, for invoices and dispute evidence
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::AppState;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SoldItem {
+    pub product_id: Option<i32>, // None once the product is deleted
+    pub product_name: String,
+    pub product_description: Option<String>,
+    pub sku: Option<String>,
+    pub quantity: i32,
+    pub list_price: Option<i64>, // in cents, the catalog price at the time
+    pub unit_price: i64,         // in cents, after sales and quantity breaks
+    pub line_total: i64,
+    pub discount_amount: Option<i64>, // The line's share of the order discount
+    pub tax_rate: Option<f64>,
+    pub tax_amount: Option<i64>, // The line's share of the order tax
+}
+
+#[derive(Debug, Serialize)]
+pub struct SoldCoupon {
+    pub code: String,
+    pub amount_off: Option<i64>, // in cents
+}
+
+// Amounts in cents. discount, tax and shipping are None for orders placed without a checkout cart
+#[derive(Debug, Serialize)]
+pub struct OrderAsSold {
+    pub order_id: Uuid,
+    pub sold_at: DateTime<Utc>,
+    pub currency: String,
+    pub items: Vec<SoldItem>,
+    pub coupon: Option<SoldCoupon>,
+    pub tax_rate: Option<f64>,
+    pub subtotal: i64,
+    pub discount: Option<i64>,
+    pub tax: Option<i64>,
+    pub shipping: Option<i64>,
+    pub total: i64,
+    pub complete: bool, // false when the order predates snapshots and lacks tax and coupon terms
+}
+
+#[derive(sqlx::FromRow)]
+struct SoldOrder {
+    id: Uuid,
+    created_at: Option<DateTime<Utc>>,
+    currency: String,
+    total_amount: i64,
+    subtotal_amount: Option<i64>, // Checkout cart totals; None without a cart
+    discount_amount: Option<i64>,
+    tax_amount: Option<i64>,
+    shipping_amount: Option<i64>,
+    tax_rate: Option<f64>,
+    coupon_code: Option<String>,
+    coupon_amount_off: Option<i64>,
+}
+
+impl OrderAsSold {
+    // Invoice lines between the items and the total: (label, cents)
+    pub fn adjustments(&self) -> Vec<(String, i64)> {
+        let mut lines = vec![("Subtotal".to_string(), self.subtotal)];
+        if let Some(discount) = self.discount.filter(|d| *d > 0) {
+            let label = match &self.coupon {
+                Some(coupon) => format!("Discount ({})", coupon.code),
+                None => "Discount".to_string(),
+            };
+            lines.push((label, -discount));
+        }
+        if let Some(tax) = self.tax {
+            let label = match self.tax_rate {
+                Some(rate) => format!("Tax ({}%)", (rate * 10000.0).round() / 100.0),
+                None => "Tax".to_string(),
+            };
+            lines.push((label, tax));
+        }
+        if let Some(shipping) = self.shipping {
+            lines.push(("Shipping".to_string(), shipping));
+        }
+        lines
+    }
+}
+
+pub fn order_snapshot_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/orders/:id/as-sold", get(get_order_as_sold))
+        .with_state(app_state)
+}
+
+pub async fn load_as_sold(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Option<OrderAsSold>, sqlx::Error> {
+    let Some(order) = sqlx::query_as!(
+        SoldOrder,
+        r#"SELECT o.id, o.created_at, o.currency, o.total_amount,
+                c.subtotal_amount AS "subtotal_amount?", c.discount_amount AS "discount_amount?", c.tax_amount AS "tax_amount?", c.shipping_amount AS "shipping_amount?",
+                c.tax_rate, c.coupon_code, c.coupon_amount_off
+         FROM orders o LEFT JOIN checkout_carts c ON c.id = o.cart_id
+         WHERE o.id = $1"#,
+        order_id,
+    )
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let items = sqlx::query_as!(
+        SoldItem,
+        "SELECT product_id, product_name, product_description, sku, quantity, list_price, unit_price,
+                total_price AS line_total, discount_amount, tax_rate, tax_amount
+         FROM order_items WHERE order_id = $1 ORDER BY created_at, id",
+        order_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let has_cart = order.subtotal_amount.is_some();
+    let complete = has_cart && order.tax_rate.is_some() && items.iter().all(|i| i.tax_rate.is_some());
+    Ok(Some(OrderAsSold {
+        order_id: order.id,
+        sold_at: order.created_at.unwrap_or_else(Utc::now),
+        currency: order.currency,
+        subtotal: order.subtotal_amount.unwrap_or_else(|| items.iter().map(|i| i.line_total).sum()),
+        items,
+        coupon: order.coupon_code.map(|code| SoldCoupon {
+            code,
+            amount_off: order.coupon_amount_off,
+        }),
+        tax_rate: order.tax_rate,
+        discount: order.discount_amount,
+        tax: order.tax_amount,
+        shipping: order.shipping_amount,
+        total: order.total_amount,
+        complete,
+    }))
+}
+
+async fn get_order_as_sold(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<OrderAsSold>, (StatusCode, String)> {
+    load_as_sold(&app_state.pool, order_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "Order not found".to_string()))
+}
@@ -0,0 +1,230 @@
+// Order Status Module - The order lifecycle as a state machine
+// An order can only move along these transitions:
+//   pending          -> completed (paid), failed, voided
+//   authorized       -> completed (captured), voided (hold released)
+//   awaiting_payment -> completed (invoice paid), voided
+//   completed        -> refunded
+//   failed, refunded and voided are final
+// Every status change goes through `transition`, in the caller's transaction: it locks the order,
+// refuses a change the table doesn't allow (e.g. refunded -> pending) and records it in
+// order_status_history, where create_order also records the first status. Callers publish the
+// change's OrderStatusChanged event once their transaction has committed
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use sqlx::PgConnection;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::events::DomainEvent;
+use crate::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    #[sqlx(rename = "pending")]
+    Pending,
+    #[sqlx(rename = "authorized")]
+    Authorized, // Payment held, captured on fulfillment
+    #[sqlx(rename = "awaiting_payment")]
+    AwaitingPayment, // Purchase order on net terms, paid against its invoice later
+    #[sqlx(rename = "completed")]
+    Completed,
+    #[sqlx(rename = "failed")]
+    Failed,
+    #[sqlx(rename = "refunded")]
+    Refunded,
+    #[sqlx(rename = "voided")]
+    Voided, // Authorization cancelled without capture
+}
+
+impl OrderStatus {
+    // Statuses an order in this one may move to
+    pub fn next(self) -> &'static [OrderStatus] {
+        use OrderStatus::*;
+        match self {
+            Pending => &[Completed, Failed, Voided],
+            Authorized => &[Completed, Voided],
+            AwaitingPayment => &[Completed, Voided],
+            Completed => &[Refunded],
+            Failed | Refunded | Voided => &[],
+        }
+    }
+
+    pub fn can_become(self, to: OrderStatus) -> bool {
+        self.next().contains(&to)
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderStatus::Pending => write!(f, "pending"),
+            OrderStatus::Authorized => write!(f, "authorized"),
+            OrderStatus::AwaitingPayment => write!(f, "awaiting_payment"),
+            OrderStatus::Completed => write!(f, "completed"),
+            OrderStatus::Failed => write!(f, "failed"),
+            OrderStatus::Refunded => write!(f, "refunded"),
+            OrderStatus::Voided => write!(f, "voided"),
+        }
+    }
+}
+
+// A status change that was made, for the caller to publish
+#[derive(Debug, Clone, Copy)]
+pub struct StatusChange {
+    pub order_id: Uuid,
+    pub from: OrderStatus,
+    pub to: OrderStatus,
+}
+
+impl StatusChange {
+    pub fn event(&self) -> DomainEvent {
+        DomainEvent::OrderStatusChanged {
+            order_id: self.order_id,
+            from: self.from,
+            to: self.to,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TransitionError {
+    NotFound,
+    NotAllowed { from: OrderStatus, to: OrderStatus },
+    Db(sqlx::Error),
+}
+
+impl From<sqlx::Error> for TransitionError {
+    fn from(e: sqlx::Error) -> Self {
+        TransitionError::Db(e)
+    }
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransitionError::NotFound => f.write_str("Order not found"),
+            TransitionError::NotAllowed { from, to } => write!(f, "Order is {} and can't become {}", from, to),
+            TransitionError::Db(e) => write!(f, "DB error: {}", e),
+        }
+    }
+}
+
+impl From<TransitionError> for (StatusCode, String) {
+    fn from(e: TransitionError) -> Self {
+        let status = match e {
+            TransitionError::NotFound => StatusCode::NOT_FOUND,
+            TransitionError::NotAllowed { .. } => StatusCode::CONFLICT,
+            TransitionError::Db(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, e.to_string())
+    }
+}
+
+// Move an order to `to` if its current status allows it. `changed_by` is the admin, or the
+// webhook or background task making the change
+pub async fn transition(
+    conn: &mut PgConnection,
+    order_id: Uuid,
+    to: OrderStatus,
+    changed_by: &str,
+) -> Result<StatusChange, TransitionError> {
+    let from: OrderStatus = sqlx::query_scalar!(r#"SELECT status AS "status: OrderStatus" FROM orders WHERE id = $1 FOR UPDATE"#, order_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(TransitionError::NotFound)?;
+    if !from.can_become(to) {
+        return Err(TransitionError::NotAllowed { from, to });
+    }
+    sqlx::query!("UPDATE orders SET status = $2, updated_at = NOW() WHERE id = $1", order_id, to as _)
+        .execute(&mut *conn)
+        .await?;
+    record(&mut *conn, order_id, Some(from), to, changed_by).await?;
+    Ok(StatusChange { order_id, from, to })
+}
+
+// Like transition, for changes that only apply to an order in `from` (e.g. capturing an
+// authorization): None when the order is missing or in another status
+pub async fn transition_from(
+    conn: &mut PgConnection,
+    order_id: Uuid,
+    from: OrderStatus,
+    to: OrderStatus,
+    changed_by: &str,
+) -> Result<Option<StatusChange>, TransitionError> {
+    let current: Option<OrderStatus> = sqlx::query_scalar!(
+        r#"SELECT status AS "current: OrderStatus" FROM orders WHERE id = $1 FOR UPDATE"#,
+        order_id,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    if current != Some(from) {
+        return Ok(None);
+    }
+    transition(conn, order_id, to, changed_by).await.map(Some)
+}
+
+// Add a history entry; `from` is None for the status an order is created with
+pub async fn record(
+    executor: impl sqlx::PgExecutor<'_>,
+    order_id: Uuid,
+    from: Option<OrderStatus>,
+    to: OrderStatus,
+    changed_by: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO order_status_history (order_id, from_status, to_status, changed_by) VALUES ($1, $2, $3, $4)",
+        order_id,
+        from as _,
+        to as _,
+        changed_by,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct StatusHistoryEntry {
+    pub from_status: Option<OrderStatus>,
+    pub to_status: OrderStatus,
+    pub changed_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub fn order_status_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/orders/:id/status-history", get(status_history))
+        .with_state(app_state)
+}
+
+// An order's statuses, oldest first
+async fn status_history(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<Vec<StatusHistoryEntry>>, (StatusCode, String)> {
+    let entries = sqlx::query_as!(
+        StatusHistoryEntry,
+        r#"SELECT from_status AS "from_status: OrderStatus", to_status AS "to_status: OrderStatus", changed_by, created_at FROM order_status_history
+         WHERE order_id = $1 ORDER BY id"#,
+        order_id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if entries.is_empty() {
+        return Err((StatusCode::NOT_FOUND, "Order not found".to_string()));
+    }
+    Ok(Json(entries))
+}
@@ -0,0 +1,237 @@
+// Order Tracking Module - Live order status for the storefront tracking page
+// Status is derived from the order and its fulfillments; domain events about an order wake
+// the open /api/orders/:id/events streams, which re-read the status and push it when it moves
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+    Router,
+};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::admin_auth::verify_admin_token;
+use crate::customer_auth::verify_customer_token;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::pii::Encrypted;
+use crate::AppState;
+
+const CHANNEL_CAPACITY: usize = 1024;
+
+// Where an order is in its lifecycle, as shown to the customer
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderTrackingStatus {
+    Pending,
+    AwaitingPayment, // Purchase order confirmed; the invoice is paid on terms
+    Paid,
+    Fulfilled,
+    PartiallyShipped, // Some items are on their way, the rest follow in later shipments
+    Shipped,
+    Delivered,
+    Refunded,
+    Voided,
+    Failed,
+}
+
+#[derive(Serialize)]
+struct StatusUpdate {
+    order_id: Uuid,
+    status: OrderTrackingStatus,
+}
+
+// Current tracking status, or None when the order doesn't exist. The order has shipped once the
+// shipped fulfillments hold every ordered item (or, for orders without copied items, once every
+// fulfillment has shipped); until then it's partially shipped
+pub async fn tracking_status(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Option<OrderTrackingStatus>, sqlx::Error> {
+    let row: Option<(String, i64, i64, i64, i64, i64)> = sqlx::query!(
+        r#"SELECT o.status,
+                COUNT(f.id) AS "fulfillments!",
+                COUNT(f.id) FILTER (WHERE f.status IN ('shipped', 'delivered')) AS "shipped!",
+                COUNT(f.id) FILTER (WHERE f.status = 'delivered') AS "delivered!",
+                COALESCE(SUM(f.quantity) FILTER (WHERE f.status IN ('shipped', 'delivered')), 0)::BIGINT AS "shipped_items!",
+                (SELECT COALESCE(SUM(quantity), 0) FROM order_items WHERE order_id = o.id)::BIGINT AS "ordered_items!"
+         FROM orders o
+         LEFT JOIN (
+             SELECT f.id, f.order_id, f.status,
+                    (SELECT COALESCE(SUM(quantity), 0) FROM order_fulfillment_items WHERE fulfillment_id = f.id) AS quantity
+             FROM order_fulfillments f
+             WHERE f.order_id = $1 AND f.status <> 'cancelled'
+         ) f ON f.order_id = o.id
+         WHERE o.id = $1
+         GROUP BY o.id"#,
+        order_id,
+    )
+    .map(|r| (r.status, r.fulfillments, r.shipped, r.delivered, r.shipped_items, r.ordered_items))
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|(status, fulfillments, shipped, delivered, shipped_items, ordered_items)| {
+        let all_shipped = if ordered_items > 0 { shipped_items >= ordered_items } else { shipped == fulfillments };
+        match status.as_str() {
+            "refunded" => OrderTrackingStatus::Refunded,
+            "voided" => OrderTrackingStatus::Voided,
+            "failed" => OrderTrackingStatus::Failed,
+            "awaiting_payment" if fulfillments == 0 => OrderTrackingStatus::AwaitingPayment,
+            // An authorized order is paid as far as the customer is concerned; capture happens on fulfillment.
+            // Purchase orders ship before their invoice is paid
+            "completed" | "authorized" if fulfillments == 0 => OrderTrackingStatus::Paid,
+            "completed" | "authorized" | "awaiting_payment" if all_shipped && delivered == fulfillments => OrderTrackingStatus::Delivered,
+            "completed" | "authorized" | "awaiting_payment" if all_shipped && shipped > 0 => OrderTrackingStatus::Shipped,
+            "completed" | "authorized" | "awaiting_payment" if shipped > 0 => OrderTrackingStatus::PartiallyShipped,
+            "completed" | "authorized" | "awaiting_payment" => OrderTrackingStatus::Fulfilled,
+            _ => OrderTrackingStatus::Pending,
+        }
+    }))
+}
+
+// Ids of orders that just had something happen, shared by every open stream
+#[derive(Clone)]
+pub struct OrderStatusNotifier {
+    sender: broadcast::Sender<Uuid>,
+}
+
+impl OrderStatusNotifier {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+}
+
+pub struct OrderStatusSubscriber {
+    notifier: OrderStatusNotifier,
+}
+
+impl OrderStatusSubscriber {
+    pub fn new(notifier: OrderStatusNotifier) -> Self {
+        Self { notifier }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for OrderStatusSubscriber {
+    fn name(&self) -> &'static str {
+        "order_status"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let order_id = match event {
+            DomainEvent::OrderCreated { order_id, .. }
+            | DomainEvent::OrderRefunded { order_id, .. }
+            | DomainEvent::OrderFulfilled { order_id }
+            | DomainEvent::OrderVoided { order_id }
+            | DomainEvent::OrderShipped { order_id, .. }
+            | DomainEvent::ShipmentDelivered { order_id: Some(order_id), .. } => *order_id,
+            _ => return Ok(()),
+        };
+        let _ = self.notifier.sender.send(order_id);
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OrderEventsQuery {
+    pub token: Option<String>, // Customer or admin JWT; EventSource can't send headers
+    pub email: Option<String>, // Guest orders: the email used at checkout
+}
+
+pub fn order_tracking_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/orders/:id/events", get(order_events))
+        .with_state(app_state)
+}
+
+// The order's customer, an admin, or a guest who knows the checkout email may follow it
+pub(crate) async fn authorize(
+    pool: &sqlx::PgPool,
+    order_id: Uuid,
+    token: Option<&str>,
+    email: Option<&str>,
+) -> Result<(), (StatusCode, String)> {
+    let order: Option<(Option<Uuid>, Option<Encrypted>)> =
+        sqlx::query!(r#"SELECT customer_id, customer_email AS "customer_email: Encrypted" FROM orders WHERE id = $1"#, order_id)
+            .map(|r| (r.customer_id, r.customer_email))
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let not_found = || (StatusCode::NOT_FOUND, "Order not found".to_string());
+    let (customer_id, customer_email) = order.ok_or_else(not_found)?;
+
+    if let Some(token) = token {
+        if verify_admin_token(token).is_ok() {
+            return Ok(());
+        }
+        let customer = verify_customer_token(token)?;
+        return if customer_id == Some(customer.customer_id) { Ok(()) } else { Err(not_found()) };
+    }
+    match (email, customer_email) {
+        (Some(email), Some(customer_email)) if email.trim().eq_ignore_ascii_case(&customer_email) => Ok(()),
+        (Some(_), _) => Err(not_found()),
+        (None, _) => Err((StatusCode::UNAUTHORIZED, "Login or order email required".to_string())),
+    }
+}
+
+fn status_event(order_id: Uuid, status: OrderTrackingStatus) -> Result<Event, Infallible> {
+    Ok(Event::default()
+        .event("status")
+        .json_data(StatusUpdate { order_id, status })
+        .unwrap_or_default())
+}
+
+// Send the current status right away, then every change until the client disconnects
+async fn order_events(
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+    Query(query): Query<OrderEventsQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, String)> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string)
+        .or(query.token);
+    authorize(&app_state.pool, order_id, token.as_deref(), query.email.as_deref()).await?;
+
+    // Subscribe before reading the status so a change in between isn't lost
+    let updates = app_state.order_status.sender.subscribe();
+    let pool = (*app_state.pool).clone();
+    let current = tracking_status(&pool, order_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Order not found".to_string()))?;
+
+    let changes = stream::unfold((updates, current), move |(mut updates, mut last)| {
+        let pool = pool.clone();
+        async move {
+            loop {
+                let touched = match updates.recv().await {
+                    Ok(id) => id == order_id,
+                    Err(broadcast::error::RecvError::Lagged(_)) => true, // May have missed ours; re-check
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                };
+                if !touched {
+                    continue;
+                }
+                match tracking_status(&pool, order_id).await {
+                    Ok(Some(status)) if status != last => {
+                        last = status;
+                        return Some((status_event(order_id, status), (updates, last)));
+                    }
+                    Ok(_) => continue,
+                    Err(e) => tracing::error!("Failed to load tracking status for order {}: {}", order_id, e),
+                }
+            }
+        }
+    });
+
+    Ok(Sse::new(stream::once(async move { status_event(order_id, current) }).chain(changes))
+        .keep_alive(KeepAlive::default()))
+}
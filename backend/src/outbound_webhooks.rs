@@ -0,0 +1,446 @@
+// Outbound Webhooks Module - Merchant-registered endpoints for store events
+// Subscriptions pick the events they want; each event becomes a logged delivery that is
+// POSTed with an HMAC signature through the job queue, so failures retry with backoff
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::integrations::{order_payload, sign_body};
+use crate::jobs;
+use crate::repos::product_query;
+use crate::request_id::WithRequestId;
+use crate::AppState;
+
+pub const WEBHOOK_DELIVERY_JOB: &str = "webhook_delivery";
+pub const EVENT_TYPES: [&str; 4] = ["order.created", "order.shipped", "order.status_changed", "product.updated"];
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+const MAX_RESPONSE_BODY: usize = 1000;
+const DELIVERY_LOG_LIMIT: i64 = 50;
+
+// Database model for webhook subscriptions
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct WebhookSubscription {
+    pub id: i32,
+    pub url: String,
+    pub description: Option<String>,
+    pub events: Vec<String>,
+    pub secret: String,
+    pub active: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookSubscriptionInput {
+    pub url: String,
+    pub description: Option<String>,
+    pub events: Vec<String>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+// Database model for delivery attempts
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub subscription_id: i32,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub response_body: Option<String>,
+    pub last_error: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+fn generate_secret() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    hex::encode(bytes)
+}
+
+// Trim the URL, and dedupe the event list keeping the order it was given in
+fn validate_input(input: &mut WebhookSubscriptionInput) -> Result<(), String> {
+    input.url = input.url.trim().to_string();
+    if !(input.url.starts_with("https://") || input.url.starts_with("http://")) {
+        return Err("url must start with https:// or http://".to_string());
+    }
+    let mut events: Vec<String> = Vec::new();
+    for event in &input.events {
+        let event = event.trim();
+        if !EVENT_TYPES.contains(&event) {
+            return Err(format!("Unknown event '{}'; expected one of {}", event, EVENT_TYPES.join(", ")));
+        }
+        if !events.iter().any(|e| e == event) {
+            events.push(event.to_string());
+        }
+    }
+    if events.is_empty() {
+        return Err("events must not be empty".to_string());
+    }
+    input.events = events;
+    Ok(())
+}
+
+// Record a delivery and queue it for sending
+async fn queue_delivery(
+    pool: &sqlx::PgPool,
+    subscription_id: i32,
+    event_type: &str,
+    payload: &serde_json::Value,
+) -> Result<Uuid, sqlx::Error> {
+    let delivery_id: Uuid = sqlx::query_scalar!(
+        "INSERT INTO webhook_deliveries (subscription_id, event_type, payload) VALUES ($1, $2, $3) RETURNING id",
+        subscription_id,
+        event_type,
+        payload,
+    )
+    .fetch_one(pool)
+    .await?;
+    jobs::enqueue(pool, WEBHOOK_DELIVERY_JOB, json!({ "delivery_id": delivery_id }), Utc::now()).await?;
+    Ok(delivery_id)
+}
+
+// POST a delivery once and record the outcome on its row
+async fn attempt_delivery(pool: &sqlx::PgPool, delivery_id: Uuid) -> Result<WebhookDelivery, String> {
+    let db_err = |e: sqlx::Error| format!("DB error: {}", e);
+    let delivery = sqlx::query_as!(WebhookDelivery, "SELECT id, subscription_id, event_type, payload, status, attempts, response_status, response_body, last_error, created_at, delivered_at FROM webhook_deliveries WHERE id = $1", delivery_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(db_err)?
+        .ok_or_else(|| format!("Webhook delivery {} not found", delivery_id))?;
+    let subscription = sqlx::query_as!(
+        WebhookSubscription,
+        "SELECT id, url, description, events, secret, active, created_at, updated_at FROM webhook_subscriptions WHERE id = $1",
+        delivery.subscription_id,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(db_err)?;
+
+    let body = delivery.payload.to_string();
+    let result = async {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .build()
+            .map_err(|e| format!("HTTP client error: {}", e))?;
+        client
+            .post(&subscription.url)
+            .header("Content-Type", "application/json")
+            .header("X-RCom-Event", &delivery.event_type)
+            .header("X-RCom-Delivery", delivery.id.to_string())
+            .header("X-RCom-Signature", sign_body(&subscription.secret, &body)?)
+            .body(body)
+            .with_request_id()
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))
+    }
+    .await;
+
+    let (status, response_status, response_body, error) = match result {
+        Ok(response) => {
+            let code = response.status();
+            let text: String = response.text().await.unwrap_or_default().chars().take(MAX_RESPONSE_BODY).collect();
+            if code.is_success() {
+                ("delivered", Some(code.as_u16() as i32), Some(text), None)
+            } else {
+                ("failed", Some(code.as_u16() as i32), Some(text), Some(format!("Endpoint returned {}", code)))
+            }
+        }
+        Err(e) => ("failed", None, None, Some(e)),
+    };
+
+    sqlx::query_as!(
+        WebhookDelivery,
+        "UPDATE webhook_deliveries
+         SET status = $1::TEXT, attempts = attempts + 1, response_status = $2, response_body = $3, last_error = $4,
+             delivered_at = CASE WHEN $1::TEXT = 'delivered' THEN NOW() ELSE delivered_at END
+         WHERE id = $5 RETURNING id, subscription_id, event_type, payload, status, attempts, response_status, response_body, last_error, created_at, delivered_at",
+        status,
+        response_status,
+        response_body,
+        error,
+        delivery.id,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(db_err)
+}
+
+#[derive(Deserialize)]
+struct DeliveryJobPayload {
+    delivery_id: Uuid,
+}
+
+// Job handler: a failed attempt returns an error so the queue retries it
+pub async fn run_webhook_delivery(pool: &sqlx::PgPool, payload: &serde_json::Value) -> Result<(), String> {
+    let payload: DeliveryJobPayload =
+        serde_json::from_value(payload.clone()).map_err(|e| format!("Invalid webhook delivery payload: {}", e))?;
+    let delivery = attempt_delivery(pool, payload.delivery_id).await?;
+    match delivery.status.as_str() {
+        "delivered" => Ok(()),
+        _ => Err(delivery.last_error.unwrap_or_else(|| "Delivery failed".to_string())),
+    }
+}
+
+// Turns domain events into deliveries for every active subscription that wants them
+pub struct OutboundWebhookSubscriber {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl OutboundWebhookSubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for OutboundWebhookSubscriber {
+    fn name(&self) -> &'static str {
+        "outbound_webhooks"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let event_type = match event {
+            DomainEvent::OrderCreated { .. } => "order.created",
+            DomainEvent::OrderShipped { .. } => "order.shipped",
+            DomainEvent::OrderStatusChanged { .. } => "order.status_changed",
+            DomainEvent::ProductUpdated { .. } => "product.updated",
+            _ => return Ok(()),
+        };
+        let subscription_ids: Vec<i32> =
+            sqlx::query_scalar!(
+                "SELECT id FROM webhook_subscriptions WHERE active = TRUE AND $1 = ANY(events) ORDER BY id",
+                event_type,
+            )
+            .fetch_all(&*self.pool)
+            .await
+            .map_err(|e| format!("DB error: {}", e))?;
+        if subscription_ids.is_empty() {
+            return Ok(());
+        }
+
+        // Build the payload once, at event time, so every subscriber gets the same snapshot
+        let data = match event {
+            DomainEvent::OrderCreated { order_id, .. } => order_payload(&self.pool, *order_id).await?,
+            DomainEvent::OrderShipped { order_id, fulfillment_id, carrier, tracking_code } => json!({
+                "order": order_payload(&self.pool, *order_id).await?,
+                "fulfillment_id": fulfillment_id,
+                "carrier": carrier,
+                "tracking_code": tracking_code,
+            }),
+            DomainEvent::OrderStatusChanged { order_id, from, to } => json!({
+                "order": order_payload(&self.pool, *order_id).await?,
+                "from": from,
+                "to": to,
+            }),
+            DomainEvent::ProductUpdated { product_id } => {
+                let product = product_query!("SELECT", "FROM products WHERE id = $1", product_id)
+                    .fetch_one(&*self.pool)
+                    .await
+                    .map_err(|e| format!("DB error: {}", e))?;
+                serde_json::to_value(product).map_err(|e| format!("Failed to serialize product: {}", e))?
+            }
+            _ => return Ok(()),
+        };
+        let payload = json!({
+            "id": Uuid::new_v4(),
+            "event": event_type,
+            "created_at": Utc::now(),
+            "data": data,
+        });
+
+        for subscription_id in subscription_ids {
+            queue_delivery(&self.pool, subscription_id, event_type, &payload)
+                .await
+                .map_err(|e| format!("Failed to queue webhook for subscription {}: {}", subscription_id, e))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn outbound_webhook_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/webhook-subscriptions", get(list_subscriptions).post(create_subscription))
+        .route(
+            "/api/admin/webhook-subscriptions/:id",
+            get(get_subscription).put(update_subscription).delete(delete_subscription),
+        )
+        .route("/api/admin/webhook-subscriptions/:id/deliveries", get(list_deliveries))
+        .route("/api/admin/webhook-subscriptions/:id/ping", post(ping_subscription))
+        .route("/api/admin/webhook-deliveries/:id/retry", post(retry_delivery))
+        .with_state(app_state)
+}
+
+async fn list_subscriptions(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<WebhookSubscription>>, (StatusCode, String)> {
+    let subscriptions = sqlx::query_as!(WebhookSubscription, "SELECT id, url, description, events, secret, active, created_at, updated_at FROM webhook_subscriptions ORDER BY id")
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(subscriptions))
+}
+
+async fn get_subscription(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<WebhookSubscription>, (StatusCode, String)> {
+    sqlx::query_as!(WebhookSubscription, "SELECT id, url, description, events, secret, active, created_at, updated_at FROM webhook_subscriptions WHERE id = $1", id)
+        .fetch_optional(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "Webhook subscription not found".to_string()))
+}
+
+// The signing secret is generated here and returned with the subscription
+async fn create_subscription(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(mut input): Json<WebhookSubscriptionInput>,
+) -> Result<(StatusCode, Json<WebhookSubscription>), (StatusCode, String)> {
+    validate_input(&mut input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let subscription = sqlx::query_as!(
+        WebhookSubscription,
+        "INSERT INTO webhook_subscriptions (url, description, events, secret, active)
+         VALUES ($1, $2, $3, $4, $5) RETURNING id, url, description, events, secret, active, created_at, updated_at",
+        &input.url,
+        input.description.as_deref().map(str::trim).filter(|d| !d.is_empty()),
+        &input.events,
+        generate_secret(),
+        input.active,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?;
+    Ok((StatusCode::CREATED, Json(subscription)))
+}
+
+async fn update_subscription(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(mut input): Json<WebhookSubscriptionInput>,
+) -> Result<Json<WebhookSubscription>, (StatusCode, String)> {
+    validate_input(&mut input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    sqlx::query_as!(
+        WebhookSubscription,
+        "UPDATE webhook_subscriptions
+         SET url = $1, description = $2, events = $3, active = $4, updated_at = NOW()
+         WHERE id = $5 RETURNING id, url, description, events, secret, active, created_at, updated_at",
+        &input.url,
+        input.description.as_deref().map(str::trim).filter(|d| !d.is_empty()),
+        &input.events,
+        input.active,
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?
+    .map(Json)
+    .ok_or((StatusCode::NOT_FOUND, "Webhook subscription not found".to_string()))
+}
+
+async fn delete_subscription(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM webhook_subscriptions WHERE id = $1", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Webhook subscription not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Most recent deliveries first
+async fn list_deliveries(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<WebhookDelivery>>, (StatusCode, String)> {
+    let deliveries = sqlx::query_as!(
+        WebhookDelivery,
+        "SELECT id, subscription_id, event_type, payload, status, attempts, response_status, response_body, last_error, created_at, delivered_at FROM webhook_deliveries WHERE subscription_id = $1 ORDER BY created_at DESC LIMIT $2",
+        id,
+        DELIVERY_LOG_LIMIT,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(deliveries))
+}
+
+// Send a "ping" event right away and return the attempt, so merchants can check their endpoint
+async fn ping_subscription(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<WebhookDelivery>, (StatusCode, String)> {
+    let payload = json!({
+        "id": Uuid::new_v4(),
+        "event": "ping",
+        "created_at": Utc::now(),
+        "data": {},
+    });
+    let delivery_id: Uuid = sqlx::query_scalar!(
+        "INSERT INTO webhook_deliveries (subscription_id, event_type, payload)
+         SELECT id, 'ping', $2 FROM webhook_subscriptions WHERE id = $1
+         RETURNING id",
+        id,
+        &payload,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Webhook subscription not found".to_string()))?;
+
+    let delivery = attempt_delivery(&app_state.pool, delivery_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(delivery))
+}
+
+// Queue another attempt of a delivery with the same body
+async fn retry_delivery(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("UPDATE webhook_deliveries SET status = 'pending' WHERE id = $1", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Webhook delivery not found".to_string()));
+    }
+    jobs::enqueue(&app_state.pool, WEBHOOK_DELIVERY_JOB, json!({ "delivery_id": id }), Utc::now())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(StatusCode::ACCEPTED)
+}
@@ -0,0 +1,189 @@
+// Pages Module - Admin-editable static pages (shipping info, returns policy, ...)
+// Pages are written in markdown and served as sanitized HTML by slug
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::content_blocks::markdown_to_html;
+use crate::AppState;
+
+// Database model for pages
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Page {
+    pub id: i32,
+    pub slug: String,
+    pub title: String,
+    pub body_markdown: String,
+    pub meta_description: Option<String>,
+    pub published: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PageInput {
+    pub slug: String,
+    pub title: String,
+    pub body_markdown: String,
+    pub meta_description: Option<String>,
+    #[serde(default = "default_published")]
+    pub published: bool,
+}
+
+fn default_published() -> bool {
+    true
+}
+
+// What the storefront receives
+#[derive(Debug, Serialize)]
+pub struct PublicPage {
+    pub slug: String,
+    pub title: String,
+    pub html: String, // Rendered from markdown, scripts and event handlers stripped
+    pub meta_description: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl From<Page> for PublicPage {
+    fn from(page: Page) -> Self {
+        Self {
+            html: ammonia::clean(&markdown_to_html(&page.body_markdown)),
+            slug: page.slug,
+            title: page.title,
+            meta_description: page.meta_description,
+            updated_at: page.updated_at,
+        }
+    }
+}
+
+fn validate_input(input: &PageInput) -> Result<(), String> {
+    let slug = input.slug.trim();
+    if slug.is_empty() || slug.len() > 100 {
+        return Err("slug must be 1-100 characters".to_string());
+    }
+    if !slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+        return Err("slug may only contain lowercase letters, digits and '-'".to_string());
+    }
+    if input.title.trim().is_empty() {
+        return Err("title is required".to_string());
+    }
+    if input.body_markdown.trim().is_empty() {
+        return Err("body_markdown is required".to_string());
+    }
+    Ok(())
+}
+
+pub fn page_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/pages/:slug", get(get_published_page))
+        .route("/api/admin/pages", get(list_pages).post(create_page))
+        .route("/api/admin/pages/:id", get(get_page).put(update_page).delete(delete_page))
+        .with_state(app_state)
+}
+
+async fn get_published_page(
+    State(app_state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<PublicPage>, (StatusCode, String)> {
+    sqlx::query_as!(Page, "SELECT id, slug, title, body_markdown, meta_description, published, created_at, updated_at FROM pages WHERE slug = $1 AND published = TRUE", slug)
+        .fetch_optional(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .map(|page| Json(page.into()))
+        .ok_or((StatusCode::NOT_FOUND, "Page not found".to_string()))
+}
+
+async fn list_pages(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Page>>, (StatusCode, String)> {
+    let pages = sqlx::query_as!(Page, "SELECT id, slug, title, body_markdown, meta_description, published, created_at, updated_at FROM pages ORDER BY slug")
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(pages))
+}
+
+async fn get_page(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Page>, (StatusCode, String)> {
+    sqlx::query_as!(Page, "SELECT id, slug, title, body_markdown, meta_description, published, created_at, updated_at FROM pages WHERE id = $1", id)
+        .fetch_optional(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "Page not found".to_string()))
+}
+
+async fn create_page(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<PageInput>,
+) -> Result<(StatusCode, Json<Page>), (StatusCode, String)> {
+    validate_input(&input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let page = sqlx::query_as!(
+        Page,
+        "INSERT INTO pages (slug, title, body_markdown, meta_description, published)
+         VALUES ($1, $2, $3, $4, $5) RETURNING id, slug, title, body_markdown, meta_description, published, created_at, updated_at",
+        input.slug.trim(),
+        input.title.trim(),
+        &input.body_markdown,
+        input.meta_description.as_deref().map(str::trim).filter(|d| !d.is_empty()),
+        input.published,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?;
+    Ok((StatusCode::CREATED, Json(page)))
+}
+
+async fn update_page(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(input): Json<PageInput>,
+) -> Result<Json<Page>, (StatusCode, String)> {
+    validate_input(&input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    sqlx::query_as!(
+        Page,
+        "UPDATE pages
+         SET slug = $1, title = $2, body_markdown = $3, meta_description = $4, published = $5, updated_at = NOW()
+         WHERE id = $6 RETURNING id, slug, title, body_markdown, meta_description, published, created_at, updated_at",
+        input.slug.trim(),
+        input.title.trim(),
+        &input.body_markdown,
+        input.meta_description.as_deref().map(str::trim).filter(|d| !d.is_empty()),
+        input.published,
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?
+    .map(Json)
+    .ok_or((StatusCode::NOT_FOUND, "Page not found".to_string()))
+}
+
+async fn delete_page(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM pages WHERE id = $1", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Page not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
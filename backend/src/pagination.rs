@@ -0,0 +1,127 @@
+// Pagination Module - Keyset (cursor) pagination shared by list endpoints
+// An OFFSET page reads and throws away every row before it, so deep pages of big tables get
+// slow, and rows added while someone pages shift what the next page shows. A keyset page
+// continues after the last row seen instead: each list orders by a unique key (an id, or
+// created_at then id) and the cursor carries that key, so the next query seeks straight to it
+// through an index. Cursors are opaque to clients: URL-safe base64 of the key's fields joined
+// by '|'. Pass `next_cursor` back as `cursor` for the next page; it is null on the last one
+
+use axum::http::StatusCode;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::SecondsFormat;
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+// A row's position in a list's order
+pub trait CursorKey: Sized {
+    fn to_text(&self) -> String;
+    fn from_text(text: &str) -> Option<Self>;
+}
+
+impl CursorKey for i32 {
+    fn to_text(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_text(text: &str) -> Option<Self> {
+        text.parse().ok()
+    }
+}
+
+// Microseconds, as Postgres stores them, so the row itself isn't skipped or repeated
+impl CursorKey for (DateTime<Utc>, Uuid) {
+    fn to_text(&self) -> String {
+        format!("{}|{}", self.0.to_rfc3339_opts(SecondsFormat::Micros, true), self.1)
+    }
+
+    fn from_text(text: &str) -> Option<Self> {
+        let (created_at, id) = text.split_once('|')?;
+        let created_at = DateTime::parse_from_rfc3339(created_at).ok()?;
+        Some((created_at.with_timezone(&Utc), Uuid::parse_str(id).ok()?))
+    }
+}
+
+// A timestamp and a text key, for lists merged from several tables (see activity)
+impl CursorKey for (DateTime<Utc>, String) {
+    fn to_text(&self) -> String {
+        format!("{}|{}", self.0.to_rfc3339_opts(SecondsFormat::Micros, true), self.1)
+    }
+
+    fn from_text(text: &str) -> Option<Self> {
+        let (at, key) = text.split_once('|')?;
+        let at = DateTime::parse_from_rfc3339(at).ok()?;
+        Some((at.with_timezone(&Utc), key.to_string()))
+    }
+}
+
+pub fn encode_cursor<K: CursorKey>(key: &K) -> String {
+    URL_SAFE_NO_PAD.encode(key.to_text())
+}
+
+pub fn decode_cursor<K: CursorKey>(cursor: &str) -> Result<K, (StatusCode, String)> {
+    let invalid = || (StatusCode::BAD_REQUEST, "Invalid cursor".to_string());
+    let decoded = URL_SAFE_NO_PAD.decode(cursor.trim()).map_err(|_| invalid())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+    K::from_text(&decoded).ok_or_else(invalid)
+}
+
+// Query of a list with no other filters. Lists with filters declare `cursor` and `limit`
+// themselves, since flattening this into their query would break parsing `limit`
+#[derive(Deserialize)]
+pub struct PageQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl PageQuery {
+    pub fn wants_page(&self) -> bool {
+        self.cursor.is_some() || self.limit.is_some()
+    }
+}
+
+// The position a `cursor` query parameter asks to continue after; None for the first page
+pub fn after<K: CursorKey>(cursor: Option<&str>) -> Result<Option<K>, (StatusCode, String)> {
+    cursor
+        .filter(|cursor| !cursor.trim().is_empty())
+        .map(decode_cursor)
+        .transpose()
+}
+
+// The `limit` query parameter, defaulted and capped
+pub fn page_size(limit: Option<i64>) -> i64 {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+impl<T> Page<T> {
+    // `rows` come from a query with LIMIT size + 1: the extra row only tells there are more
+    pub fn new<K: CursorKey>(mut rows: Vec<T>, size: i64, key: impl Fn(&T) -> K) -> Self {
+        let has_more = rows.len() as i64 > size;
+        rows.truncate(size as usize);
+        let next_cursor = match rows.last() {
+            Some(last) if has_more => Some(encode_cursor(&key(last))),
+            _ => None,
+        };
+        Page { data: rows, next_cursor, has_more }
+    }
+}
+
+// Response of a list that returned every row before it was paginated: without `cursor` or
+// `limit` (see PageQuery::wants_page) it still does, so existing clients keep working
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Listing<T> {
+    All(Vec<T>),
+    Page(Page<T>),
+}
@@ -0,0 +1,361 @@
+// Payment Capture Module - Authorize at checkout, capture on fulfillment
+// With PAYMENT_CAPTURE_METHOD=manual the card is only authorized at checkout and the order
+// starts as 'authorized'. The hold is captured once every item is fulfilled (or by an admin,
+// in full or in part). Authorizations older than AUTHORIZATION_WINDOW_HOURS are settled
+// before the card network drops them: the fulfilled share is captured, or the hold is voided
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use sqlx::types::Uuid;
+use std::sync::{Arc, OnceLock};
+use stripe::{
+    CancelPaymentIntent, CapturePaymentIntent, Client as StripeClient, PaymentIntent, PaymentIntentCancellationReason,
+    PaymentIntentCaptureMethod,
+};
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::events::{DomainEvent, EventBus, EventSubscriber};
+use crate::provider_breaker;
+use crate::repos::{order_query, OrderRepo};
+use crate::order_status::{self, OrderStatus, StatusChange, TransitionError};
+use crate::webhooks::Order;
+use crate::AppState;
+
+#[derive(Deserialize, Default)]
+pub struct CaptureRequest {
+    pub amount: Option<i64>, // in cents; the rest of the authorization is released. Defaults to the full amount
+}
+
+// Capture method for new PaymentIntents
+pub fn capture_method() -> PaymentIntentCaptureMethod {
+    if app_config::get().checkout.capture_method == "manual" {
+        PaymentIntentCaptureMethod::Manual
+    } else {
+        PaymentIntentCaptureMethod::Automatic
+    }
+}
+
+// Start the authorization window for a newly authorized order
+pub async fn record_authorization(executor: impl sqlx::PgExecutor<'_>, order_id: Uuid) -> Result<(), sqlx::Error> {
+    let window_hours = app_config::get().checkout.authorization_window_hours as i32;
+    sqlx::query!(
+        "UPDATE orders SET authorization_expires_at = NOW() + make_interval(hours => $2::INT)
+         WHERE id = $1 AND status = 'authorized'",
+        order_id,
+        window_hours,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// The order paid with this payment intent, locked until the caller's transaction ends
+async fn lock_by_payment_intent(conn: &mut sqlx::PgConnection, payment_intent_id: &str) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT id FROM orders WHERE payment_intent_id = $1 ORDER BY created_at LIMIT 1 FOR UPDATE",
+        payment_intent_id,
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+// Mark an authorized order captured; None when no authorized order uses this payment intent
+pub async fn record_capture(
+    pool: &sqlx::PgPool,
+    payment_intent_id: &str,
+    amount_captured: i64,
+    changed_by: &str,
+) -> Result<Option<StatusChange>, TransitionError> {
+    let mut tx = pool.begin().await?;
+    let Some(order_id) = lock_by_payment_intent(&mut tx, payment_intent_id).await? else {
+        return Ok(None);
+    };
+    let Some(change) =
+        order_status::transition_from(&mut tx, order_id, OrderStatus::Authorized, OrderStatus::Completed, changed_by).await?
+    else {
+        return Ok(None);
+    };
+    sqlx::query!(
+        "UPDATE orders SET amount_captured = $2, captured_at = NOW() WHERE id = $1",
+        order_id,
+        amount_captured,
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Some(change))
+}
+
+// Mark an authorized order voided; None when no authorized order uses this payment intent
+pub async fn record_void(
+    pool: &sqlx::PgPool,
+    payment_intent_id: &str,
+    changed_by: &str,
+) -> Result<Option<StatusChange>, TransitionError> {
+    let mut tx = pool.begin().await?;
+    let Some(order_id) = lock_by_payment_intent(&mut tx, payment_intent_id).await? else {
+        return Ok(None);
+    };
+    let Some(change) =
+        order_status::transition_from(&mut tx, order_id, OrderStatus::Authorized, OrderStatus::Voided, changed_by).await?
+    else {
+        return Ok(None);
+    };
+    tx.commit().await?;
+    Ok(Some(change))
+}
+
+// Value of the items covered by active fulfillments and of all items, in cents
+async fn fulfilled_value(pool: &sqlx::PgPool, order_id: Uuid) -> Result<(i64, i64), sqlx::Error> {
+    sqlx::query!(
+        r#"WITH shipped AS (
+             SELECT fi.product_id, SUM(fi.quantity) AS quantity
+             FROM order_fulfillment_items fi
+             JOIN order_fulfillments f ON f.id = fi.fulfillment_id
+             WHERE f.order_id = $1 AND f.status <> 'cancelled'
+             GROUP BY fi.product_id
+         ), ordered AS (
+             SELECT product_id, SUM(quantity) AS quantity, SUM(total_price) AS total
+             FROM order_items WHERE order_id = $1
+             GROUP BY product_id
+         )
+         SELECT COALESCE(SUM(o.total * LEAST(COALESCE(s.quantity, 0), o.quantity) / o.quantity), 0)::BIGINT AS "fulfilled!",
+                COALESCE(SUM(o.total), 0)::BIGINT AS "total!"
+         FROM ordered o LEFT JOIN shipped s ON s.product_id = o.product_id"#,
+        order_id,
+    )
+    .map(|r| (r.fulfilled, r.total))
+    .fetch_one(pool)
+    .await
+}
+
+// Capture an authorized order's payment, in full or for `amount`, publishing the status change
+pub async fn capture_order(
+    pool: &sqlx::PgPool,
+    stripe_client: &StripeClient,
+    events: &EventBus,
+    order: &Order,
+    amount: Option<i64>,
+    changed_by: &str,
+) -> Result<i64, (StatusCode, String)> {
+    if order.status != OrderStatus::Authorized {
+        return Err((StatusCode::CONFLICT, format!("Order is {}, not authorized", order.status)));
+    }
+    let payment_intent_id = order
+        .payment_intent_id
+        .as_deref()
+        .ok_or((StatusCode::CONFLICT, "Order has no payment intent".to_string()))?;
+    if let Some(amount) = amount {
+        if amount <= 0 || amount > order.total_amount {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("amount must be between 1 and {} cents", order.total_amount),
+            ));
+        }
+    }
+
+    let params = CapturePaymentIntent {
+        amount_to_capture: amount.map(|a| a as u64),
+        application_fee_amount: None,
+    };
+    let intent = provider_breaker::stripe(PaymentIntent::capture(stripe_client, payment_intent_id, params))
+        .await
+        .map_err(|e| (e.status(StatusCode::BAD_GATEWAY), format!("Stripe error: {}", e)))?;
+
+    // The payment_intent.succeeded webhook records this too; whichever runs first wins
+    if let Some(change) = record_capture(pool, payment_intent_id, intent.amount_received, changed_by).await? {
+        events.publish(change.event());
+    }
+    Ok(intent.amount_received)
+}
+
+// Release an authorized order's hold without charging, publishing OrderVoided and the status change
+pub async fn void_order(state: &AppState, order: &Order, changed_by: &str) -> Result<(), (StatusCode, String)> {
+    if order.status != OrderStatus::Authorized {
+        return Err((StatusCode::CONFLICT, format!("Order is {}, not authorized", order.status)));
+    }
+    let payment_intent_id = order
+        .payment_intent_id
+        .as_deref()
+        .ok_or((StatusCode::CONFLICT, "Order has no payment intent".to_string()))?;
+
+    let params = CancelPaymentIntent {
+        cancellation_reason: Some(PaymentIntentCancellationReason::Abandoned),
+    };
+    provider_breaker::stripe(PaymentIntent::cancel(&state.stripe_client, payment_intent_id, params))
+        .await
+        .map_err(|e| (e.status(StatusCode::BAD_GATEWAY), format!("Stripe error: {}", e)))?;
+
+    if let Some(change) = record_void(&state.pool, payment_intent_id, changed_by).await? {
+        state.events.publish(DomainEvent::OrderVoided { order_id: change.order_id });
+        state.events.publish(change.event());
+    }
+    Ok(())
+}
+
+async fn load_order(orders: &dyn OrderRepo, order_id: Uuid) -> Result<Order, (StatusCode, String)> {
+    orders
+        .find(order_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Order not found".to_string()))
+}
+
+// Captures an authorized order once all of its items are fulfilled. `events` is the bus this
+// subscriber is registered on, set once it's built, for publishing the status change
+pub struct CaptureSubscriber {
+    pool: Arc<sqlx::PgPool>,
+    orders: Arc<dyn OrderRepo>,
+    stripe_client: StripeClient,
+    events: Arc<OnceLock<EventBus>>,
+}
+
+impl CaptureSubscriber {
+    pub fn new(
+        pool: Arc<sqlx::PgPool>,
+        orders: Arc<dyn OrderRepo>,
+        stripe_client: StripeClient,
+        events: Arc<OnceLock<EventBus>>,
+    ) -> Self {
+        Self { pool, orders, stripe_client, events }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for CaptureSubscriber {
+    fn name(&self) -> &'static str {
+        "payment_capture"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let DomainEvent::OrderFulfilled { order_id } = event else {
+            return Ok(());
+        };
+        let order = load_order(&*self.orders, *order_id).await.map_err(|(_, e)| e)?;
+        if order.status != OrderStatus::Authorized {
+            return Ok(());
+        }
+        let (fulfilled, total) = fulfilled_value(&self.pool, order.id)
+            .await
+            .map_err(|e| format!("DB error: {}", e))?;
+        if fulfilled < total {
+            tracing::info!("Order {} is partly fulfilled, capture waits for the rest", order.id);
+            return Ok(());
+        }
+        let events = self.events.get().ok_or("Event bus not set")?;
+        let captured = capture_order(&self.pool, &self.stripe_client, events, &order, None, "fulfillment")
+            .await
+            .map_err(|(_, e)| e)?;
+        tracing::info!("Captured {} cents for fulfilled order {}", captured, order.id);
+        Ok(())
+    }
+}
+
+// Settle an authorization about to expire: capture the fulfilled share, or void it
+async fn settle_expiring(state: &AppState, order: &Order) -> Result<(), String> {
+    let (fulfilled, total) = fulfilled_value(&state.pool, order.id)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+    if fulfilled > 0 && total > 0 {
+        let amount = (order.total_amount as i128 * fulfilled as i128 / total as i128) as i64;
+        let captured = capture_order(
+            &state.pool,
+            &state.stripe_client,
+            &state.events,
+            order,
+            Some(amount.max(1)),
+            "authorization expiry",
+        )
+        .await
+            .map_err(|(_, e)| e)?;
+        tracing::info!("Captured {} of {} cents for partly fulfilled order {}", captured, order.total_amount, order.id);
+    } else {
+        void_order(state, order, "authorization expiry").await.map_err(|(_, e)| e)?;
+        tracing::info!("Voided expired authorization for order {}", order.id);
+    }
+    Ok(())
+}
+
+// Settle every expired authorization (the settle_expired_authorizations scheduled task)
+pub async fn settle_expired(state: &AppState) -> Result<String, String> {
+    let orders = order_query!(
+        "SELECT",
+        "FROM orders WHERE status = 'authorized' AND authorization_expires_at <= NOW()
+         ORDER BY authorization_expires_at",
+    )
+    .fetch_all(&*state.pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))?;
+    let mut failed = 0;
+    for order in &orders {
+        if let Err(e) = settle_expiring(state, order).await {
+            failed += 1;
+            tracing::error!("Failed to settle authorization for order {}: {}", order.id, e);
+            state.ops.notify(format!(":warning: Could not settle expiring authorization for order {}: {}", order.id, e));
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{} of {} expired authorizations could not be settled", failed, orders.len()));
+    }
+    Ok(format!("Settled {} expired authorizations", orders.len()))
+}
+
+pub fn payment_capture_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/payments/authorizations", get(list_authorizations))
+        .route("/api/admin/orders/:id/capture", post(capture))
+        .route("/api/admin/orders/:id/void", post(void))
+        .with_state(app_state)
+}
+
+// Authorized orders awaiting capture, soonest to expire first
+async fn list_authorizations(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Order>>, (StatusCode, String)> {
+    let orders = order_query!(
+        "SELECT",
+        "FROM orders WHERE status = 'authorized' ORDER BY authorization_expires_at NULLS LAST, created_at",
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(orders))
+}
+
+async fn capture(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+    body: Option<Json<CaptureRequest>>,
+) -> Result<Json<Order>, (StatusCode, String)> {
+    let order = load_order(&*app_state.repos.orders, order_id).await?;
+    let Json(request) = body.unwrap_or_default();
+    capture_order(
+        &app_state.pool,
+        &app_state.stripe_client,
+        &app_state.events,
+        &order,
+        request.amount,
+        &admin.username,
+    )
+    .await?;
+    Ok(Json(load_order(&*app_state.repos.orders, order_id).await?))
+}
+
+async fn void(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<Order>, (StatusCode, String)> {
+    let order = load_order(&*app_state.repos.orders, order_id).await?;
+    void_order(&app_state, &order, &admin.username).await?;
+    Ok(Json(load_order(&*app_state.repos.orders, order_id).await?))
+}
@@ -0,0 +1,401 @@
+// Payment Links Module - Shareable Stripe Payment Links for invoices and social selling
+// A link sells one product (at its current price) or an ad-hoc amount. Our link id rides in
+// the link and payment intent metadata; checkout.session.completed creates the order, links it
+// back to the payment link and adds to the link's sales totals. The payment intent and charge
+// events of a link payment are skipped so the order is only created once
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use std::sync::Arc;
+use stripe::{CheckoutSession, CreatePrice, CreatePriceProductData, Currency, PaymentLinkId, Price, UpdatePaymentLink};
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::events::DomainEvent;
+use crate::pricing::{to_cents, PriceBook};
+use crate::provider_breaker::{self, GuardedSend, Provider};
+use crate::repos::order_query;
+use crate::request_id::WithRequestId;
+use crate::unit_of_work::{self, WorkError};
+use crate::webhooks::{create_order, CreateOrder, GiftOptions, Order, OrderStatus, PaymentProvider};
+use crate::AppState;
+
+// Metadata naming our payment link record, on the Stripe link and its payment intents
+pub const METADATA_KEY: &str = "payment_link_id";
+
+const STRIPE_PAYMENT_LINKS_URL: &str = "https://api.stripe.com/v1/payment_links";
+const MAX_QUANTITY: i32 = 100;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct PaymentLinkRecord {
+    pub id: Uuid,
+    pub stripe_payment_link_id: String,
+    pub stripe_price_id: String,
+    pub url: String,
+    pub product_id: Option<i32>,
+    pub description: String,
+    pub reference: Option<String>,
+    pub unit_amount: i64, // in cents
+    pub quantity: i32,
+    pub currency: String,
+    pub active: bool,
+    pub livemode: bool,
+    pub created_by: Option<String>,
+    pub sales_count: i32,
+    pub revenue: i64, // in cents
+    pub last_sale_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct CreatePaymentLinkRequest {
+    pub product_id: Option<i32>,     // Sell a product at its current price...
+    pub amount: Option<i64>,         // ...or an ad-hoc amount in cents
+    pub description: Option<String>, // Required for ad-hoc amounts; defaults to the product name
+    pub quantity: Option<i32>,       // Defaults to 1
+    pub currency: Option<String>,    // Defaults to usd
+    pub reference: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PaymentLinkDetail {
+    #[serde(flatten)]
+    pub link: PaymentLinkRecord,
+    pub orders: Vec<Order>,
+}
+
+// The parts of Stripe's payment link response we keep
+#[derive(Deserialize)]
+struct StripePaymentLink {
+    id: String,
+    url: String,
+}
+
+pub fn payment_link_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/payment-links", get(list_payment_links).post(create_payment_link))
+        .route("/api/admin/payment-links/:id", get(get_payment_link))
+        .route("/api/admin/payment-links/:id/deactivate", post(deactivate_payment_link))
+        .with_state(app_state)
+}
+
+fn link_id(metadata: &HashMap<String, String>) -> Option<Uuid> {
+    metadata.get(METADATA_KEY).and_then(|id| Uuid::parse_str(id).ok())
+}
+
+// True for payments made through one of our payment links; their order comes from the checkout session
+pub fn is_link_payment(metadata: &HashMap<String, String>) -> bool {
+    link_id(metadata).is_some()
+}
+
+async fn load_link(pool: &sqlx::PgPool, id: Uuid) -> Result<PaymentLinkRecord, (StatusCode, String)> {
+    sqlx::query_as!(PaymentLinkRecord, "SELECT id, stripe_payment_link_id, stripe_price_id, url, product_id, description, reference, unit_amount, quantity, currency, active, livemode, created_by, sales_count, revenue, last_sale_at, created_at, updated_at FROM payment_links WHERE id = $1", id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Payment link not found".to_string()))
+}
+
+// Create the Stripe payment link. Called directly because async-stripe has no payment intent
+// metadata on payment links
+async fn create_stripe_link(id: Uuid, price_id: &str, quantity: i32) -> Result<StripePaymentLink, String> {
+    let form = [
+        ("line_items[0][price]".to_string(), price_id.to_string()),
+        ("line_items[0][quantity]".to_string(), quantity.to_string()),
+        (format!("metadata[{}]", METADATA_KEY), id.to_string()),
+        (format!("payment_intent_data[metadata][{}]", METADATA_KEY), id.to_string()),
+    ];
+    let response = reqwest::Client::new()
+        .post(STRIPE_PAYMENT_LINKS_URL)
+        .bearer_auth(app_config::get().stripe.secret_key.expose())
+        .form(&form)
+        .with_request_id()
+        .send_guarded(Provider::Stripe)
+        .await
+        .map_err(|e| format!("Stripe request failed: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!("Stripe returned {}: {}", status, text));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Stripe response: {}", e))
+}
+
+async fn create_payment_link(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<CreatePaymentLinkRequest>,
+) -> Result<(StatusCode, Json<PaymentLinkRecord>), (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let quantity = request.quantity.unwrap_or(1);
+    if !(1..=MAX_QUANTITY).contains(&quantity) {
+        return Err((StatusCode::BAD_REQUEST, format!("quantity must be between 1 and {}", MAX_QUANTITY)));
+    }
+    let currency_code = request.currency.as_deref().unwrap_or("usd").trim().to_lowercase();
+    let currency: Currency = currency_code
+        .parse()
+        .map_err(|_| (StatusCode::BAD_REQUEST, format!("Unknown currency {}", currency_code)))?;
+    let description = request.description.as_deref().map(str::trim).filter(|d| !d.is_empty());
+
+    let (product_id, unit_amount, description) = match (request.product_id, request.amount) {
+        (Some(product_id), None) => {
+            let (name, base_price): (String, f64) =
+                sqlx::query!("SELECT name, price FROM products WHERE id = $1 AND status = 'published'", product_id)
+                    .map(|r| (r.name, r.price))
+                    .fetch_optional(&*app_state.pool)
+                    .await
+                    .map_err(db_error)?
+                    .ok_or((StatusCode::BAD_REQUEST, format!("Unknown product {}", product_id)))?;
+            // The link keeps the price in effect now, sales and quantity breaks included
+            let book = PriceBook::load_active(&app_state.pool).await.map_err(db_error)?;
+            let unit_amount = to_cents(book.unit_price(product_id, base_price, quantity));
+            (Some(product_id), unit_amount, description.map(str::to_string).unwrap_or(name))
+        }
+        (None, Some(amount)) => {
+            if amount <= 0 {
+                return Err((StatusCode::BAD_REQUEST, "amount must be positive".to_string()));
+            }
+            let description = description
+                .ok_or((StatusCode::BAD_REQUEST, "description is required for an ad-hoc amount".to_string()))?;
+            (None, amount, description.to_string())
+        }
+        _ => {
+            return Err((StatusCode::BAD_REQUEST, "Give either product_id or amount".to_string()));
+        }
+    };
+
+    let mut params = CreatePrice::new(currency);
+    params.unit_amount = Some(unit_amount);
+    params.product_data = Some(CreatePriceProductData {
+        name: description.clone(),
+        ..Default::default()
+    });
+    let price = provider_breaker::stripe(Price::create(&app_state.stripe_client, params))
+        .await
+        .map_err(|e| (e.status(StatusCode::BAD_GATEWAY), format!("Stripe error: {}", e)))?;
+
+    let id = Uuid::new_v4();
+    let stripe_link = create_stripe_link(id, price.id.as_str(), quantity)
+        .await
+        .map_err(|e| (provider_breaker::degraded_status(Provider::Stripe, StatusCode::BAD_GATEWAY), e))?;
+
+    let link = sqlx::query_as!(
+        PaymentLinkRecord,
+        "INSERT INTO payment_links
+            (id, stripe_payment_link_id, stripe_price_id, url, product_id, description, reference,
+             unit_amount, quantity, currency, livemode, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+         RETURNING id, stripe_payment_link_id, stripe_price_id, url, product_id, description, reference, unit_amount, quantity, currency, active, livemode, created_by, sales_count, revenue, last_sale_at, created_at, updated_at",
+        id,
+        &stripe_link.id,
+        price.id.as_str(),
+        &stripe_link.url,
+        product_id,
+        &description,
+        request.reference.as_deref().map(str::trim).filter(|r| !r.is_empty()),
+        unit_amount,
+        quantity,
+        currency_code.to_uppercase(),
+        app_state.mode.is_live(),
+        &admin.username,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+
+    tracing::info!("{} created payment link {} ({})", admin.username, link.id, link.url);
+    Ok((StatusCode::CREATED, Json(link)))
+}
+
+async fn list_payment_links(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<PaymentLinkRecord>>, (StatusCode, String)> {
+    let links = sqlx::query_as!(
+        PaymentLinkRecord,
+        "SELECT id, stripe_payment_link_id, stripe_price_id, url, product_id, description, reference, unit_amount, quantity, currency, active, livemode, created_by, sales_count, revenue, last_sale_at, created_at, updated_at FROM payment_links WHERE livemode = $1 ORDER BY created_at DESC",
+        app_state.mode.is_live(),
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(links))
+}
+
+async fn get_payment_link(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<PaymentLinkDetail>, (StatusCode, String)> {
+    let link = load_link(&app_state.pool, id).await?;
+    let orders = order_query!(
+        "SELECT",
+        "FROM orders WHERE payment_link_id = $1 ORDER BY created_at DESC",
+        id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(PaymentLinkDetail { link, orders }))
+}
+
+// Stop the link from taking new payments; Stripe shows customers a "no longer available" page
+async fn deactivate_payment_link(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<PaymentLinkRecord>, (StatusCode, String)> {
+    let link = load_link(&app_state.pool, id).await?;
+    let stripe_id: PaymentLinkId = link
+        .stripe_payment_link_id
+        .parse()
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Invalid Stripe payment link id".to_string()))?;
+    let mut params = UpdatePaymentLink::new();
+    params.active = Some(false);
+    provider_breaker::stripe(stripe::PaymentLink::update(&app_state.stripe_client, &stripe_id, params))
+        .await
+        .map_err(|e| (e.status(StatusCode::BAD_GATEWAY), format!("Stripe error: {}", e)))?;
+
+    let link = sqlx::query_as!(
+        PaymentLinkRecord,
+        "UPDATE payment_links SET active = FALSE, updated_at = NOW() WHERE id = $1 RETURNING id, stripe_payment_link_id, stripe_price_id, url, product_id, description, reference, unit_amount, quantity, currency, active, livemode, created_by, sales_count, revenue, last_sale_at, created_at, updated_at",
+        id,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(link))
+}
+
+// Record a completed checkout of one of our payment links: create (or adopt) its order and
+// add it to the link's totals. false when the session isn't for one of our links
+pub async fn record_sale(state: &AppState, session: &CheckoutSession, webhook_id: Uuid) -> Result<bool, String> {
+    let db_error = |e: sqlx::Error| format!("Database error: {}", e);
+    let link = match (link_id(&session.metadata), &session.payment_link) {
+        (Some(id), _) => sqlx::query_as!(PaymentLinkRecord, "SELECT id, stripe_payment_link_id, stripe_price_id, url, product_id, description, reference, unit_amount, quantity, currency, active, livemode, created_by, sales_count, revenue, last_sale_at, created_at, updated_at FROM payment_links WHERE id = $1", id)
+            .fetch_optional(&*state.pool)
+            .await
+            .map_err(db_error)?,
+        (None, Some(stripe_link)) => {
+            let stripe_link_id = stripe_link.id();
+            sqlx::query_as!(
+                PaymentLinkRecord,
+                "SELECT id, stripe_payment_link_id, stripe_price_id, url, product_id, description, reference, unit_amount, quantity, currency, active, livemode, created_by, sales_count, revenue, last_sale_at, created_at, updated_at FROM payment_links WHERE stripe_payment_link_id = $1",
+                stripe_link_id.as_str(),
+            )
+            .fetch_optional(&*state.pool)
+            .await
+            .map_err(db_error)?
+        }
+        (None, None) => None,
+    };
+    // Links made in the Stripe dashboard go through the regular checkout flow
+    let Some(link) = link else {
+        return Ok(false);
+    };
+
+    let payment_intent_id = session.payment_intent.as_ref().map(|pi| pi.id().to_string());
+    let total_amount = session.amount_total.unwrap_or(0);
+    let currency = session
+        .currency
+        .as_ref()
+        .map(|c| c.to_string().to_uppercase())
+        .unwrap_or_else(|| link.currency.clone());
+
+    let details = session.customer_details.as_ref();
+    let customer_email = details.and_then(|d| d.email.clone()).or_else(|| session.customer_email.clone());
+    let customer_name = details.and_then(|d| d.name.clone());
+    let order = CreateOrder {
+        payment_provider: PaymentProvider::Stripe,
+        payment_id: session.id.to_string(),
+        payment_intent_id: payment_intent_id.clone(),
+        customer_email,
+        customer_name,
+        total_amount,
+        currency,
+        status: OrderStatus::Completed,
+        webhook_event_id: Some(webhook_id),
+        gift: GiftOptions::default(),
+        checkout: Default::default(),
+        livemode: state.mode.is_live(),
+    };
+
+    // The order, its item and the link's totals are written together
+    unit_of_work::run(state, |uow| {
+        let (link, order) = (link.clone(), order.clone());
+        Box::pin(async move {
+            let existing: Option<Uuid> = match &order.payment_intent_id {
+                Some(pi) => sqlx::query_scalar!("SELECT id FROM orders WHERE payment_intent_id = $1", pi)
+                    .fetch_optional(uow.conn())
+                    .await?,
+                None => None,
+            };
+
+            match existing {
+                // Already created from the charge, e.g. when Stripe didn't copy the metadata to it
+                Some(order_id) => {
+                    sqlx::query!("UPDATE orders SET payment_link_id = $1 WHERE id = $2", link.id, order_id)
+                        .execute(uow.conn())
+                        .await?;
+                    tracing::info!("Linked order {} to payment link {}", order_id, link.id);
+                }
+                None => {
+                    let order_id = create_order(uow.conn(), order.clone()).await?;
+                    sqlx::query!("UPDATE orders SET payment_link_id = $1 WHERE id = $2", link.id, order_id)
+                        .execute(uow.conn())
+                        .await?;
+                    sqlx::query!(
+                        "INSERT INTO order_items (order_id, product_id, product_name, quantity, unit_price, total_price)
+                         VALUES ($1, $2, $3, $4, $5, $6)",
+                        order_id,
+                        link.product_id,
+                        &link.description,
+                        link.quantity,
+                        link.unit_amount,
+                        link.unit_amount * link.quantity as i64,
+                    )
+                    .execute(uow.conn())
+                    .await?;
+                    tracing::info!("Created order {} from payment link {}", order_id, link.id);
+
+                    uow.publish(DomainEvent::OrderCreated {
+                        order_id,
+                        provider: PaymentProvider::Stripe,
+                        payment_id: order.payment_id,
+                        customer_email: order.customer_email,
+                        customer_name: order.customer_name,
+                        customer_phone: None,
+                        total_amount: order.total_amount,
+                        currency: order.currency,
+                    });
+                }
+            }
+
+            sqlx::query!(
+                "UPDATE payment_links
+                 SET sales_count = sales_count + 1, revenue = revenue + $2, last_sale_at = NOW(), updated_at = NOW()
+                 WHERE id = $1",
+                link.id,
+                order.total_amount,
+            )
+            .execute(uow.conn())
+            .await?;
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|e: WorkError<String>| e.to_string())?;
+    Ok(true)
+}
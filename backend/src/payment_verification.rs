@@ -0,0 +1,109 @@
+// Payment Verification Module - Expected vs received payment amounts
+// Checkout records the amount it computed for each payment intent. When the payment
+// succeeds the webhook records what was actually received; a difference flags the row
+// for review instead of silently fulfilling an order that was under- or over-charged
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::AppState;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct PaymentExpectation {
+    pub payment_intent_id: String,
+    pub expected_amount: i64,
+    pub currency: String,
+    pub order_id: Option<Uuid>,
+    pub received_amount: Option<i64>,
+    pub mismatch: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub verified_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct DiscrepancyQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+// Remember the amount checkout asked the provider to charge
+pub async fn record_expected(
+    pool: &sqlx::PgPool,
+    payment_intent_id: &str,
+    expected_amount: i64,
+    currency: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO payment_expectations (payment_intent_id, expected_amount, currency) VALUES ($1, $2, $3)
+         ON CONFLICT (payment_intent_id) DO UPDATE SET expected_amount = EXCLUDED.expected_amount, currency = EXCLUDED.currency",
+        payment_intent_id,
+        expected_amount,
+        currency.to_uppercase(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Record what a payment intent actually collected. Returns the expectation when one was
+// recorded at checkout (payments created elsewhere, e.g. the Stripe dashboard, have none)
+pub async fn verify_received(
+    executor: impl sqlx::PgExecutor<'_>,
+    payment_intent_id: &str,
+    order_id: Uuid,
+    received_amount: i64,
+    currency: &str,
+) -> Result<Option<PaymentExpectation>, sqlx::Error> {
+    sqlx::query_as!(
+        PaymentExpectation,
+        "UPDATE payment_expectations
+         SET order_id = $2, received_amount = $3, verified_at = NOW(),
+             mismatch = expected_amount <> $3 OR currency <> $4
+         WHERE payment_intent_id = $1
+         RETURNING payment_intent_id, expected_amount, currency, order_id, received_amount, mismatch, created_at, verified_at",
+        payment_intent_id,
+        order_id,
+        received_amount,
+        currency.to_uppercase(),
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+pub fn payment_verification_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/payments/discrepancies", get(list_discrepancies))
+        .with_state(app_state)
+}
+
+// Payments whose received amount differed from checkout's, most recent first
+async fn list_discrepancies(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<DiscrepancyQuery>,
+) -> Result<Json<Vec<PaymentExpectation>>, (StatusCode, String)> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let rows = sqlx::query_as!(
+        PaymentExpectation,
+        "SELECT payment_intent_id, expected_amount, currency, order_id, received_amount, mismatch, created_at, verified_at FROM payment_expectations WHERE mismatch ORDER BY verified_at DESC LIMIT $1 OFFSET $2",
+        limit,
+        offset,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(rows))
+}
@@ -0,0 +1,316 @@
+// Phone Verification Module - Confirms a customer controls a phone number before texting it
+// Codes go out through Twilio Verify when it is configured, falling back to a six-digit code
+// of our own sent with Textbelt. Verified numbers are recorded and SMS notifications skip the rest
+
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::api_keys::constant_time_eq;
+use crate::app_config;
+use crate::customer_auth::AuthenticatedCustomer;
+use crate::pii::{self, Encrypted};
+use crate::request_id::WithRequestId;
+use crate::textbelt_sms::{format_phone_number, send_sms_via_provider, SmsConfig, SmsProvider};
+use crate::AppState;
+
+const CODE_TTL_MINUTES: i64 = 10;
+const RESEND_COOLDOWN_SECS: i64 = 30;
+const MAX_SENDS_PER_HOUR: i64 = 5;
+const MAX_ATTEMPTS: i32 = 5;
+
+const PROVIDER_TWILIO_VERIFY: &str = "twilio_verify";
+const PROVIDER_TEXTBELT: &str = "textbelt";
+
+#[derive(Debug, sqlx::FromRow)]
+struct PhoneVerification {
+    id: i32,
+    customer_id: Option<sqlx::types::Uuid>,
+    provider: String,
+    code_hash: Option<String>,
+    attempts: i32,
+}
+
+#[derive(Deserialize)]
+pub struct SendCodeRequest {
+    pub phone: String,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyCodeRequest {
+    pub phone: String,
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub struct SendCodeResponse {
+    pub phone: String,
+    pub provider: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct VerifyCodeResponse {
+    pub phone: String,
+    pub verified: bool,
+}
+
+// Twilio Verify service credentials
+struct TwilioVerifyConfig {
+    account_sid: String,
+    auth_token: String,
+    service_sid: String,
+    api_base_url: String,
+}
+
+impl TwilioVerifyConfig {
+    fn from_env() -> Option<Self> {
+        let twilio = &app_config::get().twilio;
+        Some(Self {
+            account_sid: twilio.account_sid.clone()?,
+            auth_token: twilio.auth_token.as_ref()?.expose().to_string(),
+            service_sid: twilio.verify_service_sid.clone()?,
+            api_base_url: twilio.verify_api_url.clone(),
+        })
+    }
+
+    async fn post(&self, path: &str, params: &[(&str, &str)]) -> Result<serde_json::Value, String> {
+        let url = format!("{}/Services/{}/{}", self.api_base_url, self.service_sid, path);
+        let response = reqwest::Client::new()
+            .post(&url)
+            .basic_auth(&self.account_sid, Some(&self.auth_token))
+            .form(params)
+            .with_request_id()
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach Twilio Verify: {}", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("Twilio Verify API error ({}): {}", status, body));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Twilio Verify response: {}", e))
+    }
+
+    async fn send_code(&self, phone: &str) -> Result<(), String> {
+        self.post("Verifications", &[("To", phone), ("Channel", "sms")]).await?;
+        Ok(())
+    }
+
+    // Twilio answers 404 once the verification expired or was already approved
+    async fn check_code(&self, phone: &str, code: &str) -> Result<bool, String> {
+        let check = self
+            .post("VerificationCheck", &[("To", phone), ("Code", code)])
+            .await?;
+        Ok(check["status"].as_str() == Some("approved"))
+    }
+}
+
+fn hash_code(code: &str) -> String {
+    hex::encode(Sha256::digest(code.as_bytes()))
+}
+
+fn generate_code() -> String {
+    format!("{:06}", rand::thread_rng().gen_range(0..1_000_000))
+}
+
+// Whether SMS notifications may be sent to this number (any format; compared as E.164)
+pub async fn is_phone_verified(pool: &sqlx::PgPool, phone: &str) -> Result<bool, String> {
+    let phone = format_phone_number(phone)?;
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM verified_phones WHERE phone_hash = $1) AS "exists!""#,
+        pii::lookup_hash(&phone),
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))
+}
+
+// Send the code with Textbelt, whatever SMS_PROVIDER is set to
+async fn send_textbelt_code(pool: &sqlx::PgPool, phone: &str, code: &str) -> Result<(), String> {
+    let config = SmsConfig {
+        provider: SmsProvider::Textbelt,
+        ..SmsConfig::from_env().ok_or("SMS is not configured")?
+    };
+    let message = format!(
+        "Your R-Com verification code is {}. It expires in {} minutes.",
+        code, CODE_TTL_MINUTES
+    );
+    send_sms_via_provider(pool, &config, phone, &message).await?;
+    Ok(())
+}
+
+pub fn phone_verification_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/auth/phone/send-code", post(send_code))
+        .route("/api/auth/phone/verify-code", post(verify_code))
+        .with_state(app_state)
+}
+
+// Text a verification code. Logged-in customers get the number saved on their account once verified
+async fn send_code(
+    State(app_state): State<Arc<AppState>>,
+    customer: Option<AuthenticatedCustomer>,
+    Json(request): Json<SendCodeRequest>,
+) -> Result<(StatusCode, Json<SendCodeResponse>), (StatusCode, String)> {
+    let phone = format_phone_number(&request.phone).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let (recent, last_sent_at): (i64, Option<DateTime<Utc>>) = sqlx::query!(
+        r#"SELECT COUNT(*) AS "recent!", MAX(created_at) AS last_sent_at FROM phone_verifications
+         WHERE phone_hash = $1 AND created_at > NOW() - INTERVAL '1 hour'"#,
+        pii::lookup_hash(&phone),
+    )
+    .map(|r| (r.recent, r.last_sent_at))
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if recent >= MAX_SENDS_PER_HOUR {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many codes sent to this number, try again later".to_string(),
+        ));
+    }
+    if last_sent_at.is_some_and(|at| Utc::now() - at < chrono::Duration::seconds(RESEND_COOLDOWN_SECS)) {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            format!("Wait {} seconds before requesting another code", RESEND_COOLDOWN_SECS),
+        ));
+    }
+
+    // Prefer Twilio Verify; fall back to our own code over Textbelt if it is missing or fails
+    let mut sent = None;
+    if let Some(twilio) = TwilioVerifyConfig::from_env() {
+        match twilio.send_code(&phone).await {
+            Ok(()) => sent = Some((PROVIDER_TWILIO_VERIFY, None)),
+            Err(e) => tracing::error!("Twilio Verify failed for {}, falling back to Textbelt: {}", phone, e),
+        }
+    }
+    let (provider, code_hash) = match sent {
+        Some(sent) => sent,
+        None => {
+            let code = generate_code();
+            send_textbelt_code(&app_state.pool, &phone, &code)
+                .await
+                .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Failed to send verification code: {}", e)))?;
+            (PROVIDER_TEXTBELT, Some(hash_code(&code)))
+        }
+    };
+
+    let expires_at = Utc::now() + chrono::Duration::minutes(CODE_TTL_MINUTES);
+    sqlx::query!(
+        "INSERT INTO phone_verifications (phone, phone_hash, customer_id, provider, code_hash, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6)",
+        Encrypted::from(phone.as_str()) as _,
+        pii::lookup_hash(&phone),
+        customer.map(|c| c.customer_id),
+        provider,
+        code_hash,
+        expires_at,
+    )
+    .execute(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(SendCodeResponse {
+            phone,
+            provider: provider.to_string(),
+            expires_at,
+        }),
+    ))
+}
+
+// Check the code from the latest unexpired verification for the number
+async fn verify_code(
+    State(app_state): State<Arc<AppState>>,
+    customer: Option<AuthenticatedCustomer>,
+    Json(request): Json<VerifyCodeRequest>,
+) -> Result<Json<VerifyCodeResponse>, (StatusCode, String)> {
+    let phone = format_phone_number(&request.phone).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let code = request.code.trim();
+
+    let verification = sqlx::query_as!(
+        PhoneVerification,
+        "SELECT id, customer_id, provider, code_hash, attempts FROM phone_verifications
+         WHERE phone_hash = $1 AND verified_at IS NULL AND expires_at > NOW()
+         ORDER BY created_at DESC LIMIT 1",
+        pii::lookup_hash(&phone),
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "No pending verification for this number".to_string()))?;
+
+    if verification.attempts >= MAX_ATTEMPTS {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many attempts, request a new code".to_string(),
+        ));
+    }
+    sqlx::query!("UPDATE phone_verifications SET attempts = attempts + 1 WHERE id = $1", verification.id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    let approved = if verification.provider == PROVIDER_TWILIO_VERIFY {
+        let twilio = TwilioVerifyConfig::from_env().ok_or((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Twilio Verify is no longer configured, request a new code".to_string(),
+        ))?;
+        twilio
+            .check_code(&phone, code)
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e))?
+    } else {
+        verification
+            .code_hash
+            .as_deref()
+            .is_some_and(|expected| constant_time_eq(hash_code(code).as_bytes(), expected.as_bytes()))
+    };
+    if !approved {
+        return Err((StatusCode::BAD_REQUEST, "Invalid verification code".to_string()));
+    }
+
+    let mut tx = app_state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    sqlx::query!("UPDATE phone_verifications SET verified_at = NOW() WHERE id = $1", verification.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    sqlx::query!(
+        "INSERT INTO verified_phones (phone, phone_hash) VALUES ($1, $2)
+         ON CONFLICT (phone_hash) DO UPDATE SET verified_at = NOW()",
+        Encrypted::from(phone.as_str()) as _,
+        pii::lookup_hash(&phone),
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if let Some(customer_id) = customer.map(|c| c.customer_id).or(verification.customer_id) {
+        sqlx::query!(
+            "UPDATE customers SET phone = $1, phone_hash = $2, phone_verified_at = NOW(), updated_at = NOW() WHERE id = $3",
+            Encrypted::from(phone.as_str()) as _,
+            pii::lookup_hash(&phone),
+            customer_id,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    }
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    Ok(Json(VerifyCodeResponse { phone, verified: true }))
+}
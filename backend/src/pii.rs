@@ -0,0 +1,302 @@
+// PII Module - Application-level encryption for customer contact details
+// Customer emails and phone numbers wherever they are kept (customers, orders, reviews, quotes,
+// carts, segment lists, opt-out lists, SMS logs), the street address, name and phone of order
+// shipments, and JWT signing secrets are stored AES-256-GCM encrypted with PII_ENCRYPTION_KEY.
+// Queries bind and read them as `Encrypted`, which encrypts on the way in and decrypts on the way
+// out. Exact-match lookups
+// (login by email, opt-out checks by phone) go through keyed HMAC-SHA256 hashes kept next to them
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef};
+use sqlx::{Decode, Encode, Postgres, Row, Type};
+use std::sync::OnceLock;
+
+use crate::app_config;
+
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+const BACKFILL_BATCH: i64 = 500;
+// Hashes still need a key when encryption is off (development); they are redone once a key is set
+const UNENCRYPTED_HASH_KEY: &[u8] = b"r-com unencrypted pii lookups";
+
+struct Keys {
+    cipher: Option<Aes256Gcm>,
+    hash_key: Vec<u8>,
+}
+
+static KEYS: OnceLock<Keys> = OnceLock::new();
+
+// PII_ENCRYPTION_KEY must decode to exactly 32 bytes
+pub fn parse_key(encoded: &str) -> Option<[u8; 32]> {
+    STANDARD.decode(encoded.trim()).ok()?.try_into().ok()
+}
+
+fn keys() -> &'static Keys {
+    KEYS.get_or_init(|| {
+        match app_config::get().auth.pii_encryption_key.as_ref().and_then(|key| parse_key(key.expose())) {
+            Some(key) => {
+                // The lookup hash key is derived so one secret covers both
+                let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&key).expect("HMAC accepts any key length");
+                mac.update(b"pii-lookup-hash");
+                Keys {
+                    cipher: Some(Aes256Gcm::new(&key.into())),
+                    hash_key: mac.finalize().into_bytes().to_vec(),
+                }
+            }
+            None => Keys {
+                cipher: None,
+                hash_key: UNENCRYPTED_HASH_KEY.to_vec(),
+            },
+        }
+    })
+}
+
+pub fn encryption_enabled() -> bool {
+    keys().cipher.is_some()
+}
+
+// Encrypt for storage; plain text when no key is configured
+pub fn encrypt(plaintext: &str) -> String {
+    let Some(cipher) = &keys().cipher else {
+        return plaintext.to_string();
+    };
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .expect("AES-GCM encryption of in-memory data cannot fail");
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+    format!("{}{}", PREFIX, STANDARD.encode(sealed))
+}
+
+// Decrypt a stored value; values written before encryption was enabled are returned as they are
+pub fn decrypt(stored: &str) -> Result<String, String> {
+    let Some(encoded) = stored.strip_prefix(PREFIX) else {
+        return Ok(stored.to_string());
+    };
+    let cipher = keys()
+        .cipher
+        .as_ref()
+        .ok_or("Encrypted PII found but PII_ENCRYPTION_KEY is not set")?;
+    let sealed = STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("Invalid encrypted PII: {}", e))?;
+    if sealed.len() < NONCE_LEN {
+        return Err("Invalid encrypted PII: too short".to_string());
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Failed to decrypt PII; was PII_ENCRYPTION_KEY changed?".to_string())?;
+    String::from_utf8(plaintext).map_err(|e| format!("Invalid encrypted PII: {}", e))
+}
+
+// Deterministic keyed hash for exact-match lookups; callers normalize first
+pub fn lookup_hash(value: &str) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&keys().hash_key).expect("HMAC accepts any key length");
+    mac.update(value.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// Emails are matched case-insensitively
+pub fn email_hash(email: &str) -> String {
+    lookup_hash(&email.trim().to_lowercase())
+}
+
+// A PII value: plain text in memory and in JSON, encrypted in the database
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Encrypted(pub String);
+
+impl Encrypted {
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl std::ops::Deref for Encrypted {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Encrypted {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Encrypted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<String> for Encrypted {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Encrypted {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl Type<Postgres> for Encrypted {
+    fn type_info() -> PgTypeInfo {
+        <String as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <String as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl Encode<'_, Postgres> for Encrypted {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        <String as Encode<Postgres>>::encode(encrypt(&self.0), buf)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for Encrypted {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let stored = <&str as Decode<Postgres>>::decode(value)?;
+        Ok(Self(decrypt(stored)?))
+    }
+}
+
+type HashFn = fn(&str) -> String;
+
+// An encrypted column, with the hash column kept for lookups on it (if any)
+struct PiiColumn {
+    column: &'static str,
+    hash: Option<(&'static str, HashFn)>,
+}
+
+const fn plain(column: &'static str) -> PiiColumn {
+    PiiColumn { column, hash: None }
+}
+
+const fn email(column: &'static str, hash_column: &'static str) -> PiiColumn {
+    PiiColumn { column, hash: Some((hash_column, email_hash)) }
+}
+
+const fn phone(column: &'static str, hash_column: &'static str) -> PiiColumn {
+    PiiColumn { column, hash: Some((hash_column, lookup_hash)) }
+}
+
+// Every table holding encrypted PII (and the JWT signing secrets, kept the same way)
+const PII_TABLES: &[(&str, &[PiiColumn])] = &[
+    ("customers", &[email("email", "email_hash"), phone("phone", "phone_hash")]),
+    (
+        "order_shipments",
+        &[plain("recipient_name"), plain("street1"), plain("street2"), plain("phone")],
+    ),
+    ("orders", &[email("customer_email", "customer_email_hash")]),
+    ("reviews", &[plain("customer_email")]),
+    ("quotes", &[plain("customer_email")]),
+    ("checkout_carts", &[plain("contact_email")]),
+    ("customer_segment_members", &[email("email", "email_hash")]),
+    ("purchase_limit_overrides", &[email("customer_email", "customer_email_hash")]),
+    ("daily_digest_opt_outs", &[email("email", "email_hash")]),
+    ("phone_verifications", &[phone("phone", "phone_hash")]),
+    ("verified_phones", &[phone("phone", "phone_hash")]),
+    ("sms_opt_outs", &[phone("phone", "phone_hash")]),
+    ("sms_inbound_messages", &[phone("from_phone", "from_phone_hash")]),
+    ("jwt_signing_keys", &[plain("secret")]),
+];
+
+// SQL condition for rows with any of these columns still in plain text
+fn needs_backfill_filter(columns: &[&str]) -> String {
+    columns
+        .iter()
+        .map(|column| format!("({} IS NOT NULL AND {} NOT LIKE '{}%')", column, column, PREFIX))
+        .collect::<Vec<_>>()
+        .join(" OR ")
+}
+
+// SQL condition for rows with a value but no lookup hash yet
+fn missing_hash_filter(columns: &[PiiColumn]) -> Option<String> {
+    let conditions: Vec<String> = columns
+        .iter()
+        .filter_map(|c| c.hash.map(|(hash_column, _)| format!("({} IS NOT NULL AND {} IS NULL)", c.column, hash_column)))
+        .collect();
+    (!conditions.is_empty()).then(|| conditions.join(" OR "))
+}
+
+// Encrypt and hash one table's plain-text rows in batches; returns how many were updated.
+// Rows are addressed by ctid so tables without a single key column work the same way
+async fn backfill_table(pool: &sqlx::PgPool, table: &str, columns: &[PiiColumn]) -> Result<usize, sqlx::Error> {
+    let names: Vec<&str> = columns.iter().map(|c| c.column).collect();
+    let filter = match (encryption_enabled(), missing_hash_filter(columns)) {
+        (true, Some(missing)) => format!("{} OR {}", missing, needs_backfill_filter(&names)),
+        (true, None) => needs_backfill_filter(&names),
+        (false, Some(missing)) => missing,
+        (false, None) => return Ok(0),
+    };
+    let select = format!("SELECT ctid::TEXT, {} FROM {} WHERE {} LIMIT $1", names.join(", "), table, filter);
+    let mut assignments = Vec::new();
+    for c in columns {
+        assignments.push(format!("{} = ${}", c.column, assignments.len() + 1));
+        if let Some((hash_column, _)) = c.hash {
+            assignments.push(format!("{} = ${}", hash_column, assignments.len() + 1));
+        }
+    }
+    let update = format!(
+        "UPDATE {} SET {} WHERE ctid = ${}::TID",
+        table,
+        assignments.join(", "),
+        assignments.len() + 1
+    );
+
+    let mut updated = 0;
+    loop {
+        let rows = sqlx::query(&select).bind(BACKFILL_BATCH).fetch_all(pool).await?;
+        if rows.is_empty() {
+            break;
+        }
+        for row in &rows {
+            let mut query = sqlx::query(&update);
+            for (i, c) in columns.iter().enumerate() {
+                let value: Option<Encrypted> = row.try_get(i + 1)?;
+                if let Some((_, hash)) = c.hash {
+                    let hashed = value.as_deref().map(hash);
+                    query = query.bind(value).bind(hashed);
+                } else {
+                    query = query.bind(value);
+                }
+            }
+            query.bind(row.try_get::<String, _>(0)?).execute(pool).await?;
+        }
+        updated += rows.len();
+    }
+    Ok(updated)
+}
+
+// Encrypt existing plain-text rows and fill in lookup hashes. Runs at startup before requests are
+// served; with no key it only fills in missing hashes
+pub async fn backfill(pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let mut updated = Vec::new();
+    for (table, columns) in PII_TABLES {
+        let rows = backfill_table(pool, table, columns).await?;
+        if rows > 0 {
+            updated.push(format!("{} {}", rows, table));
+        }
+    }
+    if !updated.is_empty() {
+        tracing::info!("PII backfill updated {}", updated.join(", "));
+    }
+    Ok(())
+}
@@ -0,0 +1,471 @@
+// Pricing Module - Scheduled sales, quantity-break pricing and bulk price changes
+// Effective prices are computed at request time from active price_rules so the
+// storefront and checkout always agree on what a product costs. Bulk percentage
+// changes to base prices are previewed first and recorded in the audit log for undo
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::audit_log;
+use crate::cart_math;
+use crate::events::{DomainEvent, EventBus};
+use crate::AppState;
+
+pub const BULK_PRICE_ACTION: &str = "bulk_price_update";
+
+// Kind of price rule
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
+pub enum PriceRuleType {
+    #[sqlx(rename = "sale")]
+    #[serde(rename = "sale")]
+    Sale,
+    #[sqlx(rename = "quantity_break")]
+    #[serde(rename = "quantity_break")]
+    QuantityBreak,
+}
+
+// Database model for price rules
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct PriceRule {
+    pub id: i32,
+    pub product_id: i32,
+    pub rule_type: PriceRuleType,
+    pub price: f64,
+    pub min_quantity: i32,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct PriceRuleInput {
+    pub rule_type: PriceRuleType,
+    pub price: f64,
+    pub min_quantity: Option<i32>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+// Quantity tier shown to shoppers ("buy 10+ for $4.50 each")
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceBreak {
+    pub min_quantity: i32,
+    pub price: f64,
+}
+
+// How bulk-changed prices are rounded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum RoundingPolicy {
+    #[default]
+    #[serde(rename = "nearest_cent")]
+    NearestCent,
+    #[serde(rename = "nearest_dollar")]
+    NearestDollar,
+    #[serde(rename = "ends_in_99")]
+    EndsIn99, // Nearest dollar less a cent, e.g. 24.99
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkPriceRequest {
+    pub percentage: f64, // Negative to lower prices
+    pub category: Option<String>, // Exact, case-insensitive; all products when unset
+    #[serde(default)]
+    pub rounding: RoundingPolicy,
+    #[serde(default)]
+    pub apply: bool, // Dry run unless set
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkPriceChange {
+    pub product_id: i32,
+    pub name: String,
+    pub old_price: f64,
+    pub new_price: f64,
+}
+
+#[derive(Serialize)]
+pub struct BulkPriceResult {
+    pub applied: bool,
+    pub audit_id: Option<i32>, // Undo with POST /api/admin/products/bulk-price/{audit_id}/undo
+    pub changes: Vec<BulkPriceChange>,
+    pub unchanged: usize, // Matched, but the rounded price came out the same
+}
+
+#[derive(Serialize)]
+pub struct BulkPriceUndoResult {
+    pub restored: Vec<i32>,
+    pub skipped: Vec<i32>, // Price was changed again since, so it was left alone
+}
+
+// One line of a cart submitted for server-side pricing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CartLine {
+    pub product_id: i32,
+    pub quantity: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ship_to: Option<String>, // Address book key for multi-address checkout
+}
+
+// All currently active price rules, grouped by product
+#[derive(Default)]
+pub struct PriceBook {
+    rules: HashMap<i32, Vec<PriceRule>>,
+}
+
+impl PriceBook {
+    // Load every rule whose schedule window contains the current time
+    pub async fn load_active(pool: &sqlx::PgPool) -> Result<Self, sqlx::Error> {
+        let rules = sqlx::query_as!(
+            PriceRule,
+            r#"SELECT id, product_id, rule_type AS "rule_type: PriceRuleType", price, min_quantity, starts_at, ends_at, created_at FROM price_rules
+             WHERE (starts_at IS NULL OR starts_at <= NOW())
+               AND (ends_at IS NULL OR ends_at > NOW())"#,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut book = Self::default();
+        for rule in rules {
+            book.rules.entry(rule.product_id).or_default().push(rule);
+        }
+        Ok(book)
+    }
+
+    // Lowest unit price available for a product at the given line quantity
+    pub fn unit_price(&self, product_id: i32, base_price: f64, quantity: i32) -> f64 {
+        let rules = self.rules.get(&product_id).into_iter().flatten();
+        cart_math::tiered_price(base_price, rules.map(|r| (r.min_quantity, r.price)), quantity)
+    }
+
+    // Quantity tiers above a single unit, cheapest price per tier
+    pub fn price_breaks(&self, product_id: i32, base_price: f64) -> Vec<PriceBreak> {
+        let mut tiers: Vec<i32> = self
+            .rules
+            .get(&product_id)
+            .into_iter()
+            .flatten()
+            .filter(|r| r.rule_type == PriceRuleType::QuantityBreak && r.min_quantity > 1)
+            .map(|r| r.min_quantity)
+            .collect();
+        tiers.sort_unstable();
+        tiers.dedup();
+
+        tiers
+            .into_iter()
+            .map(|min_quantity| PriceBreak {
+                min_quantity,
+                price: self.unit_price(product_id, base_price, min_quantity),
+            })
+            .collect()
+    }
+}
+
+pub use crate::cart_math::to_cents;
+
+// Announce a lowered price so wishlist price watches get checked
+pub fn publish_price_drop(events: &EventBus, product_id: i32, product_name: &str, previous_price: f64, price: f64) {
+    if to_cents(price) < to_cents(previous_price) {
+        events.publish(DomainEvent::PriceDropped {
+            product_id,
+            product_name: product_name.to_string(),
+            previous_price: to_cents(previous_price),
+            price: to_cents(price),
+        });
+    }
+}
+
+// Announce sale rules that have started as price drops, once each. Sales starting in the
+// future are picked up by the background task below when their window opens
+async fn announce_started_sales(app_state: &AppState) -> Result<(), sqlx::Error> {
+    let started: Vec<(i32, String, f64, f64)> = sqlx::query!(
+        "UPDATE price_rules r SET announced_at = NOW()
+         FROM products p
+         WHERE p.id = r.product_id AND r.rule_type = 'sale' AND r.announced_at IS NULL
+           AND (r.starts_at IS NULL OR r.starts_at <= NOW())
+           AND (r.ends_at IS NULL OR r.ends_at > NOW())
+         RETURNING p.id, p.name, p.price, r.price AS sale_price",
+    )
+    .map(|r| (r.id, r.name, r.price, r.sale_price))
+    .fetch_all(&*app_state.pool)
+    .await?;
+    for (product_id, name, base_price, sale_price) in started {
+        publish_price_drop(&app_state.events, product_id, &name, base_price, sale_price);
+    }
+    Ok(())
+}
+
+// Background task that announces sales as they start (checked every minute)
+pub fn spawn_sale_announcer(app_state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            if let Err(e) = announce_started_sales(&app_state).await {
+                tracing::error!("Failed to announce started sales: {}", e);
+            }
+        }
+    });
+}
+
+fn round_price(price: f64, rounding: RoundingPolicy) -> f64 {
+    match rounding {
+        RoundingPolicy::NearestCent => (price * 100.0).round() / 100.0,
+        RoundingPolicy::NearestDollar => price.round(),
+        RoundingPolicy::EndsIn99 => ((price.round() * 100.0 - 1.0) / 100.0).max(0.99),
+    }
+}
+
+// Compute the cart total in cents from server-side prices and active rules
+pub async fn cart_total_cents(pool: &sqlx::PgPool, lines: &[CartLine]) -> Result<i64, String> {
+    let ids: Vec<i32> = lines.iter().map(|l| l.product_id).collect();
+    let products: Vec<(i32, f64)> = sqlx::query!(
+        "SELECT id, price FROM products WHERE id = ANY($1) AND status = 'published'",
+        &ids,
+    )
+    .map(|r| (r.id, r.price))
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Database error: {}", e))?;
+    let base_prices: HashMap<i32, f64> = products.into_iter().collect();
+
+    let book = PriceBook::load_active(pool)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    let mut total = 0i64;
+    for line in lines {
+        if line.quantity < 1 {
+            return Err(format!("Invalid quantity for product {}", line.product_id));
+        }
+        let base = base_prices
+            .get(&line.product_id)
+            .ok_or_else(|| format!("Unknown product {}", line.product_id))?;
+        let (_, line_total) = cart_math::line_amounts(book.unit_price(line.product_id, *base, line.quantity), line.quantity);
+        total += line_total;
+    }
+    Ok(total)
+}
+
+// Admin price rule routes
+pub fn price_rule_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/products/:id/price-rules", get(list_price_rules).post(create_price_rule))
+        .route("/api/admin/price-rules/:id", delete(delete_price_rule))
+        .route("/api/admin/products/bulk-price", post(bulk_price_update))
+        .route("/api/admin/products/bulk-price/:audit_id/undo", post(undo_bulk_price_update))
+        .with_state(app_state)
+}
+
+async fn list_price_rules(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+) -> Result<Json<Vec<PriceRule>>, (StatusCode, String)> {
+    let rules = sqlx::query_as!(
+        PriceRule,
+        r#"SELECT id, product_id, rule_type AS "rule_type: PriceRuleType", price, min_quantity, starts_at, ends_at, created_at FROM price_rules WHERE product_id = $1 ORDER BY min_quantity, starts_at NULLS FIRST"#,
+        product_id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(rules))
+}
+
+async fn create_price_rule(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+    Json(input): Json<PriceRuleInput>,
+) -> Result<(StatusCode, Json<PriceRule>), (StatusCode, String)> {
+    if input.price < 0.0 {
+        return Err((StatusCode::BAD_REQUEST, "Price must not be negative".to_string()));
+    }
+    if let (Some(start), Some(end)) = (input.starts_at, input.ends_at) {
+        if end <= start {
+            return Err((StatusCode::BAD_REQUEST, "ends_at must be after starts_at".to_string()));
+        }
+    }
+
+    let rec = sqlx::query_as!(
+        PriceRule,
+        r#"INSERT INTO price_rules (product_id, rule_type, price, min_quantity, starts_at, ends_at)
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING id, product_id, rule_type AS "rule_type: PriceRuleType", price, min_quantity, starts_at, ends_at, created_at"#,
+        product_id,
+        input.rule_type as _,
+        input.price,
+        input.min_quantity.unwrap_or(1).max(1),
+        input.starts_at,
+        input.ends_at,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?;
+    // A sale that is already running is announced now rather than on the next tick
+    if let Err(e) = announce_started_sales(&app_state).await {
+        tracing::error!("Failed to announce started sales: {}", e);
+    }
+    Ok((StatusCode::CREATED, Json(rec)))
+}
+
+async fn delete_price_rule(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<bool>, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM price_rules WHERE id = $1", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(res.rows_affected() > 0))
+}
+
+// Preview a percentage change to base prices, or apply it in one transaction with an audit log entry
+async fn bulk_price_update(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<BulkPriceRequest>,
+) -> Result<Json<BulkPriceResult>, (StatusCode, String)> {
+    if !request.percentage.is_finite() || request.percentage <= -100.0 || request.percentage == 0.0 {
+        return Err((StatusCode::BAD_REQUEST, "percentage must be above -100 and not 0".to_string()));
+    }
+    let category = request.category.as_deref().map(str::trim).filter(|c| !c.is_empty());
+
+    let mut tx = app_state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    // Lock the rows when applying, so the diff is exactly what gets written
+    let products: Vec<(i32, String, f64)> = if request.apply {
+        sqlx::query!(
+            "SELECT id, name, price FROM products WHERE ($1::TEXT IS NULL OR LOWER(category) = LOWER($1)) ORDER BY id FOR UPDATE",
+            category,
+        )
+        .map(|r| (r.id, r.name, r.price))
+        .fetch_all(&mut *tx)
+        .await
+    } else {
+        sqlx::query!(
+            "SELECT id, name, price FROM products WHERE ($1::TEXT IS NULL OR LOWER(category) = LOWER($1)) ORDER BY id",
+            category,
+        )
+        .map(|r| (r.id, r.name, r.price))
+        .fetch_all(&mut *tx)
+        .await
+    }
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    let factor = 1.0 + request.percentage / 100.0;
+    let mut changes = Vec::new();
+    for (product_id, name, old_price) in products.iter().cloned() {
+        let new_price = round_price(old_price * factor, request.rounding);
+        if new_price <= 0.0 {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Product {} ({}) would cost {:.2}", product_id, name, new_price),
+            ));
+        }
+        if new_price != old_price {
+            changes.push(BulkPriceChange { product_id, name, old_price, new_price });
+        }
+    }
+    let unchanged = products.len() - changes.len();
+    if !request.apply || changes.is_empty() {
+        return Ok(Json(BulkPriceResult { applied: false, audit_id: None, changes, unchanged }));
+    }
+
+    let ids: Vec<i32> = changes.iter().map(|c| c.product_id).collect();
+    let prices: Vec<f64> = changes.iter().map(|c| c.new_price).collect();
+    sqlx::query!(
+        "UPDATE products p SET price = u.price FROM UNNEST($1::INT[], $2::FLOAT8[]) AS u(id, price) WHERE p.id = u.id",
+        &ids,
+        &prices,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let summary = format!(
+        "Changed {} prices by {}%{}",
+        changes.len(),
+        request.percentage,
+        category.map(|c| format!(" in {}", c)).unwrap_or_default()
+    );
+    let audit_id = audit_log::record(
+        &mut tx,
+        &admin.username,
+        BULK_PRICE_ACTION,
+        &summary,
+        serde_json::to_value(&request).unwrap_or_default(),
+        Some(serde_json::to_value(&changes).unwrap_or_default()),
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    for change in &changes {
+        app_state.events.publish(DomainEvent::ProductUpdated { product_id: change.product_id });
+        publish_price_drop(&app_state.events, change.product_id, &change.name, change.old_price, change.new_price);
+    }
+    tracing::info!("{} by {} (audit entry {})", summary, admin.username, audit_id);
+    Ok(Json(BulkPriceResult { applied: true, audit_id: Some(audit_id), changes, unchanged }))
+}
+
+// Put back the prices a bulk update changed; products repriced since are skipped
+async fn undo_bulk_price_update(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(audit_id): Path<i32>,
+) -> Result<Json<BulkPriceUndoResult>, (StatusCode, String)> {
+    let mut tx = app_state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let entry = audit_log::claim_for_undo(&mut tx, audit_id, BULK_PRICE_ACTION).await?;
+    let changes: Vec<BulkPriceChange> = serde_json::from_value(entry.undo.unwrap_or_default())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Invalid undo record: {}", e)))?;
+
+    let ids: Vec<i32> = changes.iter().map(|c| c.product_id).collect();
+    let old_prices: Vec<f64> = changes.iter().map(|c| c.old_price).collect();
+    let new_prices: Vec<f64> = changes.iter().map(|c| c.new_price).collect();
+    let restored: Vec<i32> = sqlx::query_scalar!(
+        "UPDATE products p SET price = u.old_price
+         FROM UNNEST($1::INT[], $2::FLOAT8[], $3::FLOAT8[]) AS u(id, old_price, new_price)
+         WHERE p.id = u.id AND p.price = u.new_price
+         RETURNING p.id",
+        &ids,
+        &old_prices,
+        &new_prices,
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    audit_log::mark_undone(&mut tx, audit_id, &admin.username)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    for id in &restored {
+        app_state.events.publish(DomainEvent::ProductUpdated { product_id: *id });
+    }
+    for change in changes.iter().filter(|c| restored.contains(&c.product_id)) {
+        publish_price_drop(&app_state.events, change.product_id, &change.name, change.new_price, change.old_price);
+    }
+    let skipped = ids.into_iter().filter(|id| !restored.contains(id)).collect();
+    Ok(Json(BulkPriceUndoResult { restored, skipped }))
+}
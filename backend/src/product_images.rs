@@ -0,0 +1,612 @@
+// Product Images Module - Uploaded product photos and their responsive variants
+// Admins upload an image file per request; the original is stored under MEDIA_DIR and a
+// background worker renders it at several widths in the original's format (JPEG, or PNG with transparency),
+// WebP and AVIF. Storefront product responses carry each ready image as srcset URLs, so
+// browsers download the smallest size and best format they support. The first ready image
+// (by position) becomes the product's image_url, used by the Google feed and order emails
+
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::StatusCode,
+    routing::{get, put},
+    Json, Router,
+};
+use image::{codecs::avif::AvifEncoder, codecs::jpeg::JpegEncoder, DynamicImage, ImageEncoder, ImageFormat};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::AppState;
+
+const VARIANT_WIDTHS: [u32; 5] = [160, 320, 640, 1024, 1600]; // Plus the original's width when smaller
+const FALLBACK_WIDTH: u32 = 640; // `src` for browsers without srcset support
+const MAIN_IMAGE_WIDTH: u32 = 1024; // Width used for the product's image_url
+const MAX_PIXELS: u64 = 40_000_000;
+const JPEG_QUALITY: u8 = 82;
+const WEBP_QUALITY: f32 = 80.0;
+const AVIF_QUALITY: u8 = 60;
+const AVIF_SPEED: u8 = 8; // 1 (smallest files) to 10 (fastest)
+const POLL_INTERVAL_SECS: u64 = 5;
+const STALE_LOCK_MINUTES: i32 = 30; // Images locked longer than this are assumed abandoned
+
+// Database model for an uploaded image, with its variants for the admin
+#[derive(Debug, Serialize)]
+pub struct ProductImage {
+    pub id: i32,
+    pub product_id: i32,
+    pub position: i32,
+    pub alt_text: Option<String>,
+    pub content_type: String,
+    pub width: i32,
+    pub height: i32,
+    pub bytes: i64,
+    pub status: String, // processing, ready or failed
+    pub error: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub processed_at: Option<DateTime<Utc>>,
+    pub variants: Vec<ImageVariant>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImageVariant {
+    #[serde(skip)]
+    pub image_id: i32,
+    pub format: String, // jpeg, png, webp or avif
+    pub width: i32,
+    pub height: i32,
+    pub bytes: i64,
+    #[serde(skip)]
+    pub path: String,
+    pub url: String,
+}
+
+// A product_images row as query_as! reads it; the variants are loaded separately
+struct ProductImageRow {
+    id: i32,
+    product_id: i32,
+    position: i32,
+    alt_text: Option<String>,
+    content_type: String,
+    width: i32,
+    height: i32,
+    bytes: i64,
+    status: String,
+    error: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+    processed_at: Option<DateTime<Utc>>,
+}
+
+impl From<ProductImageRow> for ProductImage {
+    fn from(row: ProductImageRow) -> Self {
+        ProductImage {
+            id: row.id,
+            product_id: row.product_id,
+            position: row.position,
+            alt_text: row.alt_text,
+            content_type: row.content_type,
+            width: row.width,
+            height: row.height,
+            bytes: row.bytes,
+            status: row.status,
+            error: row.error,
+            created_at: row.created_at,
+            processed_at: row.processed_at,
+            variants: Vec::new(),
+        }
+    }
+}
+
+// A product_image_variants row as query_as! reads it; the URL comes from the path
+struct ImageVariantRow {
+    image_id: i32,
+    format: String,
+    width: i32,
+    height: i32,
+    bytes: i64,
+    path: String,
+}
+
+impl From<ImageVariantRow> for ImageVariant {
+    fn from(row: ImageVariantRow) -> Self {
+        ImageVariant {
+            url: media_url(&row.path),
+            image_id: row.image_id,
+            format: row.format,
+            width: row.width,
+            height: row.height,
+            bytes: row.bytes,
+            path: row.path,
+        }
+    }
+}
+
+// A ready image as the storefront renders it: <picture> sources plus the <img> fallback
+#[derive(Debug, Clone, Serialize)]
+pub struct ResponsiveImage {
+    pub id: i32,
+    pub alt: String,
+    pub width: i32,
+    pub height: i32,
+    pub src: String,
+    pub srcset: String, // Original format, e.g. "https://.../320.jpg 320w, https://.../640.jpg 640w"
+    pub sources: Vec<ImageSource>, // AVIF then WebP
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageSource {
+    #[serde(rename = "type")]
+    pub mime_type: &'static str,
+    pub srcset: String,
+}
+
+#[derive(Deserialize)]
+pub struct UploadQuery {
+    pub alt: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ImageUpdate {
+    pub alt_text: Option<String>,
+    pub position: Option<i32>,
+}
+
+// A rendered file, before it is recorded
+struct RenderedVariant {
+    format: &'static str,
+    width: u32,
+    height: u32,
+    bytes: usize,
+    path: String,
+}
+
+pub fn product_image_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    let max_upload_bytes = app_config::get().media.max_image_upload_mb * 1024 * 1024;
+    Router::new()
+        .route(
+            "/api/admin/products/:id/images",
+            get(list_images).post(upload_image).layer(DefaultBodyLimit::max(max_upload_bytes)),
+        )
+        .route("/api/admin/products/:id/images/:image_id", put(update_image).delete(delete_image))
+        .with_state(app_state)
+}
+
+fn media_url(path: &str) -> String {
+    let config = app_config::get();
+    format!("{}/{}", config.media.public_url(&config.server), path)
+}
+
+fn media_path(path: &str) -> std::path::PathBuf {
+    std::path::Path::new(&app_config::get().media.dir).join(path)
+}
+
+fn srcset(variants: &[&ImageVariant]) -> String {
+    variants
+        .iter()
+        .map(|v| format!("{} {}w", media_url(&v.path), v.width))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Ready images for each product, main image first
+pub async fn load_responsive(pool: &sqlx::PgPool, product_ids: &[i32]) -> Result<HashMap<i32, Vec<ResponsiveImage>>, sqlx::Error> {
+    let images = sqlx::query_as!(
+        ProductImageRow,
+        "SELECT id, product_id, position, alt_text, content_type, width, height, bytes, status, error, created_at, processed_at FROM product_images WHERE product_id = ANY($1) AND status = 'ready' ORDER BY product_id, position, id",
+        product_ids,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(ProductImage::from)
+    .collect::<Vec<_>>();
+    let ids: Vec<i32> = images.iter().map(|i| i.id).collect();
+    let variants = sqlx::query_as!(
+        ImageVariantRow,
+        "SELECT image_id, format, width, height, bytes, path FROM product_image_variants
+         WHERE image_id = ANY($1) ORDER BY width",
+        &ids,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(ImageVariant::from)
+    .collect::<Vec<_>>();
+
+    let mut by_product: HashMap<i32, Vec<ResponsiveImage>> = HashMap::new();
+    for image in images {
+        let of_format = |format: &str| -> Vec<&ImageVariant> {
+            variants.iter().filter(|v| v.image_id == image.id && v.format == format).collect()
+        };
+        let fallback = match of_format("png") {
+            png if !png.is_empty() => png,
+            _ => of_format("jpeg"),
+        };
+        let Some(src) = fallback
+            .iter()
+            .rev()
+            .find(|v| v.width as u32 <= FALLBACK_WIDTH)
+            .or(fallback.first())
+        else {
+            continue;
+        };
+        let sources = [("avif", "image/avif"), ("webp", "image/webp")]
+            .into_iter()
+            .filter_map(|(format, mime_type)| {
+                let of = of_format(format);
+                (!of.is_empty()).then(|| ImageSource { mime_type, srcset: srcset(&of) })
+            })
+            .collect();
+        by_product.entry(image.product_id).or_default().push(ResponsiveImage {
+            id: image.id,
+            alt: image.alt_text.clone().unwrap_or_default(),
+            width: image.width,
+            height: image.height,
+            src: media_url(&src.path),
+            srcset: srcset(&fallback),
+            sources,
+        });
+    }
+    Ok(by_product)
+}
+
+// Point the product's image_url at its main image, unless an admin set an outside URL
+async fn refresh_main_image(pool: &sqlx::PgPool, product_id: i32) -> Result<(), sqlx::Error> {
+    let path: Option<String> = sqlx::query_scalar!(
+        "SELECT v.path FROM product_images i
+         JOIN product_image_variants v ON v.image_id = i.id AND v.format IN ('jpeg', 'png')
+         WHERE i.product_id = $1 AND i.status = 'ready'
+         ORDER BY i.position, i.id, v.width <= $2 DESC, ABS(v.width - $2)
+         LIMIT 1",
+        product_id,
+        MAIN_IMAGE_WIDTH as i32,
+    )
+    .fetch_optional(pool)
+    .await?;
+    let config = app_config::get();
+    sqlx::query!(
+        "UPDATE products SET image_url = $2
+         WHERE id = $1 AND (image_url IS NULL OR image_url LIKE $3 || '/%')",
+        product_id,
+        path.as_deref().map(media_url),
+        config.media.public_url(&config.server),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn load_images(pool: &sqlx::PgPool, product_id: i32) -> Result<Vec<ProductImage>, sqlx::Error> {
+    let mut images = sqlx::query_as!(
+        ProductImageRow,
+        "SELECT id, product_id, position, alt_text, content_type, width, height, bytes, status, error, created_at, processed_at FROM product_images WHERE product_id = $1 ORDER BY position, id",
+        product_id,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(ProductImage::from)
+    .collect::<Vec<_>>();
+    let ids: Vec<i32> = images.iter().map(|i| i.id).collect();
+    let variants = sqlx::query_as!(
+        ImageVariantRow,
+        "SELECT image_id, format, width, height, bytes, path FROM product_image_variants
+         WHERE image_id = ANY($1) ORDER BY format, width",
+        &ids,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(ImageVariant::from)
+    .collect::<Vec<_>>();
+    for variant in variants {
+        if let Some(image) = images.iter_mut().find(|i| i.id == variant.image_id) {
+            image.variants.push(variant);
+        }
+    }
+    Ok(images)
+}
+
+async fn list_images(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+) -> Result<Json<Vec<ProductImage>>, (StatusCode, String)> {
+    let images = load_images(&app_state.pool, product_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(images))
+}
+
+// Store an uploaded image (the raw file as the request body); the worker renders its variants
+async fn upload_image(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+    Query(query): Query<UploadQuery>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<ProductImage>), (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let exists: bool = sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM products WHERE id = $1) AS "exists!""#, product_id)
+        .fetch_one(&*app_state.pool)
+        .await
+        .map_err(db_error)?;
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, "Product not found".to_string()));
+    }
+
+    let image = add_image(&app_state.pool, product_id, &body, query.alt.as_deref()).await?;
+    Ok((StatusCode::ACCEPTED, Json(image)))
+}
+
+// Store an uploaded original for the image processor; the product must exist
+pub async fn add_image(
+    pool: &sqlx::PgPool,
+    product_id: i32,
+    body: &[u8],
+    alt: Option<&str>,
+) -> Result<ProductImage, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let format = image::guess_format(body)
+        .ok()
+        .filter(|f| matches!(f, ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::Gif | ImageFormat::WebP))
+        .ok_or((StatusCode::UNSUPPORTED_MEDIA_TYPE, "Upload a JPEG, PNG, GIF or WebP image".to_string()))?;
+    let (width, height) = image::ImageReader::with_format(Cursor::new(body), format)
+        .into_dimensions()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Unreadable image: {}", e)))?;
+    if width as u64 * height as u64 > MAX_PIXELS {
+        return Err((StatusCode::BAD_REQUEST, format!("Image is too large (over {} megapixels)", MAX_PIXELS / 1_000_000)));
+    }
+
+    let dir = format!("products/{}/{}", product_id, Uuid::new_v4());
+    let original_path = format!("{}/original.{}", dir, format.extensions_str()[0]);
+    let file_error = |e: std::io::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to store image: {}", e));
+    tokio::fs::create_dir_all(media_path(&dir)).await.map_err(file_error)?;
+    tokio::fs::write(media_path(&original_path), body).await.map_err(file_error)?;
+
+    let image = sqlx::query_as!(
+        ProductImageRow,
+        "INSERT INTO product_images (product_id, position, alt_text, original_path, content_type, width, height, bytes)
+         VALUES ($1, (SELECT COALESCE(MAX(position) + 1, 0) FROM product_images WHERE product_id = $1),
+                 $2, $3, $4, $5, $6, $7)
+         RETURNING id, product_id, position, alt_text, content_type, width, height, bytes, status, error, created_at, processed_at",
+        product_id,
+        alt.map(str::trim).filter(|a| !a.is_empty()),
+        &original_path,
+        format.to_mime_type(),
+        width as i32,
+        height as i32,
+        body.len() as i64,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(db_error)?;
+    Ok(image.into())
+}
+
+// Change the alt text or move the image; position 0 makes it the main image
+async fn update_image(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path((product_id, image_id)): Path<(i32, i32)>,
+    Json(input): Json<ImageUpdate>,
+) -> Result<Json<Vec<ProductImage>>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let res = sqlx::query!(
+        "UPDATE product_images SET alt_text = COALESCE($3, alt_text), position = COALESCE($4, position)
+         WHERE id = $1 AND product_id = $2",
+        image_id,
+        product_id,
+        input.alt_text.as_deref().map(str::trim),
+        input.position,
+    )
+    .execute(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Image not found".to_string()));
+    }
+    if let Some(position) = input.position {
+        // Shift the others down so the moved image takes the spot
+        sqlx::query!(
+            "UPDATE product_images SET position = position + 1
+             WHERE product_id = $1 AND id <> $2 AND position >= $3",
+            product_id,
+            image_id,
+            position,
+        )
+        .execute(&*app_state.pool)
+        .await
+        .map_err(db_error)?;
+        refresh_main_image(&app_state.pool, product_id).await.map_err(db_error)?;
+    }
+    let images = load_images(&app_state.pool, product_id).await.map_err(db_error)?;
+    Ok(Json(images))
+}
+
+async fn delete_image(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path((product_id, image_id)): Path<(i32, i32)>,
+) -> Result<Json<bool>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let original_path: Option<String> = sqlx::query_scalar!(
+        "DELETE FROM product_images WHERE id = $1 AND product_id = $2 RETURNING original_path",
+        image_id,
+        product_id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    let Some(original_path) = original_path else {
+        return Ok(Json(false));
+    };
+    if let Some(dir) = std::path::Path::new(&original_path).parent() {
+        if let Err(e) = tokio::fs::remove_dir_all(media_path(&dir.to_string_lossy())).await {
+            tracing::error!("Failed to remove files of image {}: {}", image_id, e);
+        }
+    }
+    refresh_main_image(&app_state.pool, product_id).await.map_err(db_error)?;
+    Ok(Json(true))
+}
+
+fn encode(image: &DynamicImage, format: &str) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+    let (width, height) = (image.width(), image.height());
+    match format {
+        "jpeg" => JpegEncoder::new_with_quality(&mut buf, JPEG_QUALITY)
+            .encode_image(&image.to_rgb8())
+            .map_err(|e| e.to_string())?,
+        "png" => image
+            .write_to(&mut Cursor::new(&mut buf), ImageFormat::Png)
+            .map_err(|e| e.to_string())?,
+        "webp" if image.color().has_alpha() => {
+            buf = webp::Encoder::from_rgba(&image.to_rgba8(), width, height).encode(WEBP_QUALITY).to_vec()
+        }
+        "webp" => buf = webp::Encoder::from_rgb(&image.to_rgb8(), width, height).encode(WEBP_QUALITY).to_vec(),
+        "avif" if image.color().has_alpha() => AvifEncoder::new_with_speed_quality(&mut buf, AVIF_SPEED, AVIF_QUALITY)
+            .write_image(&image.to_rgba8(), width, height, image::ExtendedColorType::Rgba8)
+            .map_err(|e| e.to_string())?,
+        "avif" => AvifEncoder::new_with_speed_quality(&mut buf, AVIF_SPEED, AVIF_QUALITY)
+            .write_image(&image.to_rgb8(), width, height, image::ExtendedColorType::Rgb8)
+            .map_err(|e| e.to_string())?,
+        other => return Err(format!("Unknown format {}", other)),
+    }
+    Ok(buf)
+}
+
+// Decode the original and write every width and format next to it (blocking; CPU heavy)
+fn render_variants(original_path: &str) -> Result<Vec<RenderedVariant>, String> {
+    let original = image::open(media_path(original_path)).map_err(|e| format!("Unreadable image: {}", e))?;
+    let dir = std::path::Path::new(original_path)
+        .parent()
+        .map(|d| d.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let fallback = if original.color().has_alpha() { "png" } else { "jpeg" };
+    let largest = original.width().min(VARIANT_WIDTHS[VARIANT_WIDTHS.len() - 1]);
+    let mut widths: Vec<u32> = VARIANT_WIDTHS.iter().copied().filter(|w| *w < largest).collect();
+    widths.push(largest);
+
+    let mut rendered = Vec::new();
+    for width in widths {
+        let resized = if width == original.width() {
+            original.clone()
+        } else {
+            original.resize(width, u32::MAX, image::imageops::FilterType::Lanczos3)
+        };
+        for format in [fallback, "webp", "avif"] {
+            let bytes = encode(&resized, format)?;
+            let extension = if format == "jpeg" { "jpg" } else { format };
+            let path = format!("{}/{}.{}", dir, width, extension);
+            std::fs::write(media_path(&path), &bytes).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+            rendered.push(RenderedVariant { format, width, height: resized.height(), bytes: bytes.len(), path });
+        }
+    }
+    Ok(rendered)
+}
+
+// Renders uploaded images one at a time, outside the job queue so a large batch of
+// uploads doesn't hold up emails and webhooks
+pub fn spawn_image_processor(pool: Arc<sqlx::PgPool>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(POLL_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            loop {
+                match process_next(&pool).await {
+                    Ok(true) => continue,
+                    Ok(false) => break,
+                    Err(e) => {
+                        tracing::error!("Failed to process product image: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+// Claim the oldest image still processing and render it; false when there is none. An image
+// that can't be decoded or written is marked failed rather than retried
+async fn process_next(pool: &sqlx::PgPool) -> Result<bool, String> {
+    let db_error = |e: sqlx::Error| format!("DB error: {}", e);
+    let claimed: Option<(i32, i32, String)> = sqlx::query!(
+        "UPDATE product_images SET locked_at = NOW()
+         WHERE id = (
+             SELECT id FROM product_images
+             WHERE status = 'processing'
+               AND (locked_at IS NULL OR locked_at < NOW() - make_interval(mins => $1))
+             ORDER BY created_at LIMIT 1
+             FOR UPDATE SKIP LOCKED
+         )
+         RETURNING id, product_id, original_path",
+        STALE_LOCK_MINUTES,
+    )
+    .map(|r| (r.id, r.product_id, r.original_path))
+    .fetch_optional(pool)
+    .await
+    .map_err(db_error)?;
+    let Some((image_id, product_id, original_path)) = claimed else {
+        return Ok(false);
+    };
+
+    let rendered = match tokio::task::spawn_blocking(move || render_variants(&original_path)).await {
+        Ok(result) => result,
+        Err(e) => Err(format!("Image processing panicked: {}", e)),
+    };
+    let rendered = match rendered {
+        Ok(rendered) => rendered,
+        Err(e) => {
+            sqlx::query!(
+                "UPDATE product_images SET status = 'failed', error = $2, locked_at = NULL, processed_at = NOW()
+                 WHERE id = $1",
+                image_id,
+                &e,
+            )
+            .execute(pool)
+            .await
+            .map_err(db_error)?;
+            tracing::error!("Failed to process image {} of product {}: {}", image_id, product_id, e);
+            return Ok(true);
+        }
+    };
+
+    let mut tx = pool.begin().await.map_err(db_error)?;
+    sqlx::query!("DELETE FROM product_image_variants WHERE image_id = $1", image_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    for variant in &rendered {
+        sqlx::query!(
+            "INSERT INTO product_image_variants (image_id, format, width, height, bytes, path)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+            image_id,
+            variant.format,
+            variant.width as i32,
+            variant.height as i32,
+            variant.bytes as i64,
+            &variant.path,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    }
+    sqlx::query!(
+        "UPDATE product_images SET status = 'ready', error = NULL, locked_at = NULL, processed_at = NOW()
+         WHERE id = $1",
+        image_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    tx.commit().await.map_err(db_error)?;
+
+    refresh_main_image(pool, product_id).await.map_err(db_error)?;
+    tracing::info!("Processed image {} of product {} ({} variants)", image_id, product_id, rendered.len());
+    Ok(true)
+}
@@ -0,0 +1,311 @@
+// Product Search Module - Storefront product search with facet counts
+// Filters published products by text, category, price range and stock, and returns the counts
+// the catalog's filter UI shows next to each option. Each facet is counted with every other
+// filter applied but not its own, so picking a category still shows how many products the
+// other categories have. Searches go to Meilisearch when it is configured (see search_index);
+// otherwise, or when it fails, text is matched with Postgres full-text search and the facets
+// are computed in SQL on effective (sale) prices
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgArguments, Postgres};
+use sqlx::query::QueryAs;
+use std::sync::Arc;
+
+use crate::pricing::PriceBook;
+use crate::repos::product_query;
+use crate::search_index::IndexedSearch;
+use crate::{apply_effective_price, attach_images, AppState, Product};
+
+const DEFAULT_PAGE_SIZE: i64 = 24;
+const MAX_PAGE_SIZE: i64 = 100;
+// Price facet bucket edges in dollars: under $25, $25-50, ..., $500 and up
+pub const PRICE_BUCKET_EDGES: [f64; 5] = [25.0, 50.0, 100.0, 250.0, 500.0];
+
+// Published products with their current single-unit price (as PriceBook::unit_price) and
+// whether they pass each filter. Text matches whole words (stemmed) through the search_vector
+// column, or part of the name while the shopper is still typing. Every query below selects from
+// it, so all take the same binds
+const MATCHED_PRODUCTS: &str = "
+    WITH priced AS (
+        SELECT p.*, LEAST(p.price, COALESCE((
+                   SELECT MIN(r.price) FROM price_rules r
+                   WHERE r.product_id = p.id AND r.min_quantity <= 1
+                     AND (r.starts_at IS NULL OR r.starts_at <= NOW())
+                     AND (r.ends_at IS NULL OR r.ends_at > NOW())
+               ), p.price)) AS effective_price
+        FROM products p WHERE p.status = 'published'
+    ), matched AS (
+        SELECT *,
+               ($1::TEXT IS NULL OR search_vector @@ websearch_to_tsquery('english', $1)
+                   OR name ILIKE '%' || $1 || '%') AS match_text,
+               ($2::TEXT IS NULL OR LOWER(category) = LOWER($2)) AS match_category,
+               (($3::FLOAT8 IS NULL OR effective_price >= $3)
+                   AND ($4::FLOAT8 IS NULL OR effective_price < $4)) AS match_price,
+               (NOT $5::BOOL OR inventory > 0) AS match_stock,
+               width_bucket(effective_price, $6::FLOAT8[]) AS price_bucket
+        FROM priced
+    )";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchParams {
+    pub q: Option<String>,
+    pub category: Option<String>, // Exact, case-insensitive
+    pub min_price: Option<f64>,   // Inclusive
+    pub max_price: Option<f64>,   // Exclusive, so price buckets don't overlap
+    #[serde(default)]
+    pub in_stock: bool,
+    pub sort: Option<String>, // relevance (default with q), newest (default without), price_asc, price_desc, name_asc or name_desc
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CategoryFacet {
+    pub value: String,
+    pub count: i64,
+    pub selected: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PriceFacet {
+    pub min: f64,
+    pub max: Option<f64>, // None for the top bucket
+    pub count: i64,
+    pub selected: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AvailabilityFacet {
+    pub in_stock: i64,
+    pub out_of_stock: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchFacets {
+    pub categories: Vec<CategoryFacet>,
+    pub price: Vec<PriceFacet>,
+    pub availability: AvailabilityFacet,
+}
+
+#[derive(Serialize)]
+pub struct SearchResults {
+    pub products: Vec<Product>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    pub facets: SearchFacets,
+}
+
+#[derive(sqlx::FromRow)]
+struct Totals {
+    total: i64,
+    in_stock: i64,
+    out_of_stock: i64,
+}
+
+pub fn product_search_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/products/search", get(search_products))
+        .with_state(app_state)
+}
+
+// Empty strings count as unset
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+const SORTS: [&str; 6] = ["relevance", "newest", "price_asc", "price_desc", "name_asc", "name_desc"];
+
+// ORDER BY for a validated sort; relevance without a search term falls back to newest
+fn order_by(sort: &str, has_text: bool) -> &'static str {
+    match sort {
+        "relevance" if has_text => "ts_rank(search_vector, websearch_to_tsquery('english', $1)) DESC, created_at DESC, id DESC",
+        "price_asc" => "effective_price, id",
+        "price_desc" => "effective_price DESC, id",
+        "name_asc" => "name, id",
+        "name_desc" => "name DESC, id",
+        _ => "created_at DESC, id DESC",
+    }
+}
+
+// Bind the filters shared by every query, in MATCHED_PRODUCTS order
+fn bind_filters<'q, O>(
+    query: QueryAs<'q, Postgres, O, PgArguments>,
+    params: &'q SearchParams,
+) -> QueryAs<'q, Postgres, O, PgArguments> {
+    query
+        .bind(non_empty(&params.q))
+        .bind(non_empty(&params.category))
+        .bind(params.min_price)
+        .bind(params.max_price)
+        .bind(params.in_stock)
+        .bind(PRICE_BUCKET_EDGES.to_vec())
+}
+
+// Categories with their counts, marking the one the request filtered on
+fn category_facets(params: &SearchParams, counts: Vec<(String, i64)>) -> Vec<CategoryFacet> {
+    let selected = non_empty(&params.category).map(str::to_lowercase);
+    counts
+        .into_iter()
+        .map(|(value, count)| CategoryFacet {
+            selected: selected.as_deref() == Some(value.to_lowercase().as_str()),
+            value,
+            count,
+        })
+        .collect()
+}
+
+// Price buckets in order, including empty ones so the UI can show them disabled
+fn price_facets(params: &SearchParams, counts: &[(i32, i64)]) -> Vec<PriceFacet> {
+    (0..=PRICE_BUCKET_EDGES.len())
+        .map(|bucket| {
+            let min = if bucket == 0 { 0.0 } else { PRICE_BUCKET_EDGES[bucket - 1] };
+            let max = PRICE_BUCKET_EDGES.get(bucket).copied();
+            let count = counts
+                .iter()
+                .find(|(b, _)| *b as usize == bucket)
+                .map(|(_, c)| *c)
+                .unwrap_or(0);
+            let selected = params.min_price.unwrap_or(0.0) == min && params.max_price == max;
+            PriceFacet { min, max, count, selected }
+        })
+        .collect()
+}
+
+async fn search_products(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<SearchResults>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let has_text = non_empty(&params.q).is_some();
+    let sort = non_empty(&params.sort).unwrap_or(if has_text { "relevance" } else { "newest" });
+    if !SORTS.contains(&sort) {
+        return Err((StatusCode::BAD_REQUEST, format!("sort must be one of {}", SORTS.join(", "))));
+    }
+    if params.min_price.is_some_and(|p| p < 0.0) || params.max_price.is_some_and(|p| p < 0.0) {
+        return Err((StatusCode::BAD_REQUEST, "Prices can't be negative".to_string()));
+    }
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = params.offset.unwrap_or(0).max(0);
+    let pool = &*app_state.pool;
+
+    let found = match &app_state.search_index {
+        Some(index) => match index.search(&params, sort, limit, offset).await {
+            Ok(found) => Some(found),
+            Err(e) => {
+                tracing::error!("Meilisearch search failed, using Postgres: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    let found = match found {
+        Some(found) => found,
+        None => search_postgres(pool, &params, order_by(sort, has_text), limit, offset)
+            .await
+            .map_err(db_error)?,
+    };
+
+    // Products come from Postgres in the order the search ranked them
+    let mut products = product_query!(
+        "SELECT",
+        "FROM products WHERE id = ANY($1) AND status = 'published'",
+        &found.ids,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(db_error)?
+    .into_iter()
+    .map(Product::from)
+    .collect::<Vec<_>>();
+    products.sort_by_key(|p| found.ids.iter().position(|id| *id == p.id));
+    let price_book = PriceBook::load_active(pool).await.map_err(db_error)?;
+    for product in &mut products {
+        apply_effective_price(&price_book, product);
+    }
+    attach_images(pool, &mut products).await.map_err(db_error)?;
+
+    Ok(Json(SearchResults {
+        products,
+        total: found.total,
+        limit,
+        offset,
+        facets: SearchFacets {
+            categories: category_facets(&params, found.categories),
+            price: price_facets(&params, &found.price_counts),
+            availability: AvailabilityFacet { in_stock: found.in_stock, out_of_stock: found.out_of_stock },
+        },
+    }))
+}
+
+// The same search computed in SQL
+async fn search_postgres(
+    pool: &sqlx::PgPool,
+    params: &SearchParams,
+    order: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<IndexedSearch, sqlx::Error> {
+    let ids_sql = format!(
+        "{} SELECT id FROM matched WHERE match_text AND match_category AND match_price AND match_stock
+         ORDER BY {} LIMIT $7 OFFSET $8",
+        MATCHED_PRODUCTS, order
+    );
+    let ids = bind_filters(sqlx::query_as::<_, (i32,)>(&ids_sql), params)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|(id,)| id)
+        .collect();
+
+    // Match count, and in/out of stock counts with every filter but availability
+    let totals_sql = format!(
+        "{} SELECT COUNT(*) FILTER (WHERE match_text AND match_category AND match_price AND match_stock) AS total,
+                COUNT(*) FILTER (WHERE match_text AND match_category AND match_price AND inventory > 0) AS in_stock,
+                COUNT(*) FILTER (WHERE match_text AND match_category AND match_price AND inventory <= 0) AS out_of_stock
+         FROM matched",
+        MATCHED_PRODUCTS
+    );
+    let totals = bind_filters(sqlx::query_as::<_, Totals>(&totals_sql), params)
+        .fetch_one(pool)
+        .await?;
+
+    // Every category in the catalog, counted with every filter but category
+    let categories_sql = format!(
+        "{} SELECT category, COUNT(*) FILTER (WHERE match_text AND match_price AND match_stock)
+         FROM matched WHERE category IS NOT NULL AND category <> ''
+         GROUP BY category ORDER BY category",
+        MATCHED_PRODUCTS
+    );
+    let categories = bind_filters(sqlx::query_as(&categories_sql), params)
+        .fetch_all(pool)
+        .await?;
+
+    // Price buckets, counted with every filter but price
+    let prices_sql = format!(
+        "{} SELECT price_bucket, COUNT(*) FROM matched
+         WHERE match_text AND match_category AND match_stock
+         GROUP BY price_bucket",
+        MATCHED_PRODUCTS
+    );
+    let price_counts = bind_filters(sqlx::query_as(&prices_sql), params)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(IndexedSearch {
+        ids,
+        total: totals.total,
+        categories,
+        price_counts,
+        in_stock: totals.in_stock,
+        out_of_stock: totals.out_of_stock,
+    })
+}
@@ -0,0 +1,175 @@
+// Product Slugs Module - URL slugs for product pages
+// Every product gets a unique slug generated from its name. Renaming a product gives it a
+// new slug and keeps the old one in product_slug_history, so old links redirect instead of breaking
+
+use std::collections::HashSet;
+
+const MAX_SLUG_LEN: usize = 150; // Leaves room in the column for a "-n" suffix
+const FALLBACK_SLUG: &str = "product";
+const RESERVED_SLUGS: &[&str] = &["search"]; // Taken by other /api/products/... routes
+
+// The slug a product ended up with, and the one it replaced when the name changed
+pub struct AssignedSlug {
+    pub slug: String,
+    pub replaced: Option<String>,
+}
+
+// What a slug (or legacy numeric id) in a product URL refers to
+pub enum SlugLookup {
+    Found(i32),
+    Moved(String), // The product's current slug
+    NotFound,
+}
+
+// Lowercase ASCII letters and digits, everything else collapsed into single dashes
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(MAX_SLUG_LEN);
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        FALLBACK_SLUG.to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+// A product to give a slug in assign_slugs: the name it's being saved under, and for a saved
+// product its id and current name and slug
+pub struct SlugRequest<'a> {
+    pub name: &'a str,
+    pub saved: Option<(i32, &'a str, &'a str)>,
+}
+
+// `base`, or `base-2`, `base-3`... whichever isn't `taken`
+fn first_free(base: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(base) && !RESERVED_SLUGS.contains(&base) {
+        return base.to_string();
+    }
+    let suffix = (2..)
+        .find(|n| !taken.contains(&format!("{}-{}", base, n)))
+        .unwrap_or_default();
+    format!("{}-{}", base, suffix)
+}
+
+// Slug for a product being created (no id) or saved under `name`; it only changes when the name does
+pub async fn assign_slug(pool: &sqlx::PgPool, name: &str, product_id: Option<i32>) -> Result<AssignedSlug, sqlx::Error> {
+    let current: Option<(String, String)> = match product_id {
+        Some(id) => sqlx::query!("SELECT name, slug FROM products WHERE id = $1", id)
+            .map(|r| (r.name, r.slug))
+            .fetch_optional(pool)
+            .await?,
+        None => None,
+    };
+    let saved = product_id.zip(current.as_ref()).map(|(id, (name, slug))| (id, name.as_str(), slug.as_str()));
+    let mut assigned = assign_slugs(pool, &[SlugRequest { name, saved }]).await?;
+    Ok(assigned.remove(0))
+}
+
+// assign_slug for several products saved together (an import), reading the slugs in use in one
+// query: a new slug isn't used by another product, now or in the past, nor given to an earlier
+// product in `requests`
+pub async fn assign_slugs(pool: &sqlx::PgPool, requests: &[SlugRequest<'_>]) -> Result<Vec<AssignedSlug>, sqlx::Error> {
+    let renamed = |request: &SlugRequest| !matches!(request.saved, Some((_, name, _)) if name.trim() == request.name.trim());
+    let bases: Vec<String> = requests.iter().map(|r| slugify(r.name)).collect();
+    let taken = sqlx::query!(
+        r#"SELECT b.ord AS "ord!", t.slug AS "slug!"
+         FROM UNNEST($1::TEXT[], $2::INT4[], $3::BOOL[]) WITH ORDINALITY AS b(base, product_id, renamed, ord)
+         CROSS JOIN LATERAL (
+             SELECT slug FROM products
+             WHERE (slug = b.base OR slug LIKE b.base || '-%') AND id IS DISTINCT FROM b.product_id
+             UNION
+             SELECT slug FROM product_slug_history
+             WHERE (slug = b.base OR slug LIKE b.base || '-%') AND product_id IS DISTINCT FROM b.product_id
+         ) t
+         WHERE b.renamed"#,
+        &bases,
+        &requests.iter().map(|r| r.saved.map(|(id, _, _)| id)).collect::<Vec<_>>() as _,
+        &requests.iter().map(renamed).collect::<Vec<_>>(),
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut taken_by: Vec<HashSet<String>> = vec![HashSet::new(); requests.len()];
+    for row in taken {
+        taken_by[row.ord as usize - 1].insert(row.slug);
+    }
+
+    let mut claimed: HashSet<String> = HashSet::new();
+    let mut assigned = Vec::with_capacity(requests.len());
+    for ((request, base), mut taken) in requests.iter().zip(&bases).zip(taken_by) {
+        let slug = match request.saved {
+            Some((_, _, slug)) if !renamed(request) => slug.to_string(),
+            _ => {
+                taken.extend(claimed.iter().cloned());
+                first_free(base, &taken)
+            }
+        };
+        let replaced = request.saved.map(|(_, _, old)| old.to_string()).filter(|old| *old != slug);
+        claimed.insert(slug.clone());
+        assigned.push(AssignedSlug { slug, replaced });
+    }
+    Ok(assigned)
+}
+
+// After a rename is saved: send the old slug to the product, and drop the new one from history
+// if the product is taking back a slug it had before
+pub async fn keep_old_slug(pool: &sqlx::PgPool, product_id: i32, assigned: &AssignedSlug) -> Result<(), sqlx::Error> {
+    keep_old_slugs(pool, &[(product_id, assigned)]).await
+}
+
+// keep_old_slug for several products at once, in two statements
+pub async fn keep_old_slugs(pool: &sqlx::PgPool, saved: &[(i32, &AssignedSlug)]) -> Result<(), sqlx::Error> {
+    let renamed: Vec<_> = saved.iter().filter_map(|(id, assigned)| Some((*id, assigned.replaced.clone()?, assigned.slug.clone()))).collect();
+    if renamed.is_empty() {
+        return Ok(());
+    }
+    sqlx::query!(
+        "INSERT INTO product_slug_history (slug, product_id) SELECT * FROM UNNEST($1::TEXT[], $2::INT4[])
+         ON CONFLICT (slug) DO UPDATE SET product_id = EXCLUDED.product_id, created_at = NOW()",
+        &renamed.iter().map(|(_, old, _)| old.clone()).collect::<Vec<_>>(),
+        &renamed.iter().map(|(id, _, _)| *id).collect::<Vec<_>>(),
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM product_slug_history WHERE slug = ANY($1)",
+        &renamed.iter().map(|(_, _, new)| new.clone()).collect::<Vec<_>>(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Resolve a product URL key: a current slug, an old slug, or a numeric id from before slugs existed
+pub async fn resolve(pool: &sqlx::PgPool, key: &str) -> Result<SlugLookup, sqlx::Error> {
+    let id: Option<i32> = sqlx::query_scalar!("SELECT id FROM products WHERE slug = $1", key)
+        .fetch_optional(pool)
+        .await?;
+    if let Some(id) = id {
+        return Ok(SlugLookup::Found(id));
+    }
+
+    let current: Option<String> = sqlx::query_scalar!(
+        "SELECT p.slug FROM product_slug_history h JOIN products p ON p.id = h.product_id WHERE h.slug = $1",
+        key,
+    )
+    .fetch_optional(pool)
+    .await?;
+    if let Some(slug) = current {
+        return Ok(SlugLookup::Moved(slug));
+    }
+
+    let Ok(id) = key.parse::<i32>() else {
+        return Ok(SlugLookup::NotFound);
+    };
+    let slug: Option<String> = sqlx::query_scalar!("SELECT slug FROM products WHERE id = $1", id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(slug.map(SlugLookup::Moved).unwrap_or(SlugLookup::NotFound))
+}
@@ -0,0 +1,314 @@
+// Provider Breaker Module - Timeouts and circuit breakers for calls to Stripe, EasyPost and Brevo
+// When a provider is down every call to it used to wait out its full timeout (or hang, since the
+// clients had none), so one outage tied up every request that touched it. Calls now go through
+// a breaker per provider:
+// - closed: calls go through, each limited to PROVIDER_TIMEOUT_SECS. Timeouts, connection
+//   errors and 5xx responses count as failures; anything else resets the count
+// - open: after PROVIDER_BREAKER_FAILURES failures in a row, calls fail at once with
+//   `Guarded::Open` for PROVIDER_BREAKER_COOLDOWN_SECS. Handlers answer 503 (`Guarded::status`)
+// - half-open: after the cooldown one call is let through as a probe. Success closes the
+//   breaker, failure opens it for another cooldown; other calls keep failing fast meanwhile
+// Request errors such as a declined card or an invalid address are the caller's problem, not
+// an outage, and don't count. GET /api/admin/provider-breakers shows each breaker's state and counters
+
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    Stripe,
+    EasyPost,
+    Brevo,
+}
+
+impl Provider {
+    const ALL: [Provider; 3] = [Provider::Stripe, Provider::EasyPost, Provider::Brevo];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Provider::Stripe => "Stripe",
+            Provider::EasyPost => "EasyPost",
+            Provider::Brevo => "Brevo",
+        }
+    }
+}
+
+// Outcome of a guarded call that didn't succeed
+#[derive(Debug)]
+pub enum Guarded<E> {
+    Open { retry_after: Duration }, // Not attempted: the provider's breaker is open
+    TimedOut(Duration),
+    Failed(E),
+}
+
+impl<E> Guarded<E> {
+    // 503 while the provider is known to be down, `otherwise` for the call's own failures
+    pub fn status(&self, otherwise: StatusCode) -> StatusCode {
+        match self {
+            Guarded::Open { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            Guarded::TimedOut(_) => StatusCode::GATEWAY_TIMEOUT,
+            Guarded::Failed(_) => otherwise,
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Guarded<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Guarded::Open { retry_after } => write!(
+                f,
+                "temporarily unavailable after repeated failures, try again in {}s",
+                retry_after.as_secs().max(1)
+            ),
+            Guarded::TimedOut(timeout) => write!(f, "no response within {}s", timeout.as_secs()),
+            Guarded::Failed(e) => e.fmt(f),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { since: Instant }, // A probe is in flight
+}
+
+struct Breaker {
+    state: Mutex<BreakerState>,
+    calls: AtomicU64,
+    failures: AtomicU64,
+    rejected: AtomicU64,
+    times_opened: AtomicU64,
+}
+
+impl Breaker {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+            calls: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            times_opened: AtomicU64::new(0),
+        }
+    }
+
+    // Whether a call may go ahead; if not, how long until the next probe
+    fn admit(&self, cooldown: Duration) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let admitted = match *state {
+            BreakerState::Closed { .. } => Ok(()),
+            BreakerState::Open { until } if now >= until => {
+                *state = BreakerState::HalfOpen { since: now };
+                Ok(())
+            }
+            BreakerState::Open { until } => Err(until - now),
+            // A probe whose request was dropped never reports back, so don't wait on it forever
+            BreakerState::HalfOpen { since } if now - since >= cooldown => {
+                *state = BreakerState::HalfOpen { since: now };
+                Ok(())
+            }
+            BreakerState::HalfOpen { since } => Err(cooldown - (now - since)),
+        };
+        match admitted {
+            Ok(()) => self.calls.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self.rejected.fetch_add(1, Ordering::Relaxed),
+        };
+        admitted
+    }
+
+    fn record_success(&self, provider: Provider) {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            BreakerState::HalfOpen { .. } => {
+                tracing::info!(provider = provider.name(), "provider reachable again, circuit breaker closed");
+                *state = BreakerState::Closed { failures: 0 };
+            }
+            BreakerState::Closed { .. } => *state = BreakerState::Closed { failures: 0 },
+            // A call let through before the breaker opened doesn't close it; the probe decides
+            BreakerState::Open { .. } => {}
+        }
+    }
+
+    fn record_failure(&self, provider: Provider, threshold: u32, cooldown: Duration) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        let mut state = self.state.lock().unwrap();
+        let open = match *state {
+            BreakerState::Closed { failures } if failures + 1 >= threshold => true,
+            BreakerState::Closed { failures } => {
+                *state = BreakerState::Closed { failures: failures + 1 };
+                false
+            }
+            BreakerState::HalfOpen { .. } => true,
+            BreakerState::Open { .. } => false,
+        };
+        if open {
+            *state = BreakerState::Open { until: Instant::now() + cooldown };
+            self.times_opened.fetch_add(1, Ordering::Relaxed);
+            tracing::error!(
+                provider = provider.name(),
+                cooldown_secs = cooldown.as_secs(),
+                "provider failing, circuit breaker open"
+            );
+        }
+    }
+}
+
+fn breaker(provider: Provider) -> &'static Breaker {
+    static BREAKERS: OnceLock<[Breaker; 3]> = OnceLock::new();
+    let breakers = BREAKERS.get_or_init(|| [Breaker::new(), Breaker::new(), Breaker::new()]);
+    &breakers[provider as usize]
+}
+
+// Whether calls to the provider are failing fast right now
+pub fn is_open(provider: Provider) -> bool {
+    matches!(*breaker(provider).state.lock().unwrap(), BreakerState::Open { until } if Instant::now() < until)
+}
+
+// 503 while the provider's breaker is open, else `otherwise`. For handlers whose provider
+// errors arrive as strings
+pub fn degraded_status(provider: Provider, otherwise: StatusCode) -> StatusCode {
+    if is_open(provider) {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        otherwise
+    }
+}
+
+// Run one call to `provider` through its breaker. `is_outage` tells which of the call's own
+// errors mean the provider is down rather than that the request was bad
+pub async fn call<T, E>(
+    provider: Provider,
+    is_outage: impl Fn(&E) -> bool,
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, Guarded<E>> {
+    guard(provider, |result| result.as_ref().is_err_and(&is_outage), call)
+        .await
+        .map_err(widen)?
+        .map_err(Guarded::Failed)
+}
+
+// `call`, but judging the whole result, so a successful call can still count as a failure
+async fn guard<R>(
+    provider: Provider,
+    is_outage: impl Fn(&R) -> bool,
+    call: impl Future<Output = R>,
+) -> Result<R, Guarded<Infallible>> {
+    let settings = &app_config::get().providers;
+    let cooldown = Duration::from_secs(settings.breaker_cooldown_secs);
+    let breaker = breaker(provider);
+    breaker.admit(cooldown).map_err(|retry_after| Guarded::Open { retry_after })?;
+
+    let timeout = Duration::from_secs(settings.timeout_secs);
+    match tokio::time::timeout(timeout, call).await {
+        Ok(result) => {
+            if is_outage(&result) {
+                breaker.record_failure(provider, settings.breaker_failures, cooldown);
+            } else {
+                breaker.record_success(provider);
+            }
+            Ok(result)
+        }
+        Err(_) => {
+            breaker.record_failure(provider, settings.breaker_failures, cooldown);
+            Err(Guarded::TimedOut(timeout))
+        }
+    }
+}
+
+fn widen<E>(guarded: Guarded<Infallible>) -> Guarded<E> {
+    match guarded {
+        Guarded::Open { retry_after } => Guarded::Open { retry_after },
+        Guarded::TimedOut(timeout) => Guarded::TimedOut(timeout),
+        Guarded::Failed(never) => match never {},
+    }
+}
+
+// A Stripe SDK call. Stripe's 5xx answers and failures to reach it are outages
+pub async fn stripe<T>(call: impl Future<Output = Result<T, stripe::StripeError>>) -> Result<T, Guarded<stripe::StripeError>> {
+    self::call(
+        Provider::Stripe,
+        |e| match e {
+            stripe::StripeError::Stripe(e) => e.http_status >= 500,
+            stripe::StripeError::ClientError(_) | stripe::StripeError::Timeout => true,
+            _ => false,
+        },
+        call,
+    )
+    .await
+}
+
+// Sends HTTP requests to a provider through its breaker. A 5xx response still comes back for
+// the caller to read, but counts as a failure
+pub trait GuardedSend {
+    fn send_guarded(self, provider: Provider) -> impl Future<Output = Result<reqwest::Response, Guarded<reqwest::Error>>> + Send;
+}
+
+impl GuardedSend for reqwest::RequestBuilder {
+    async fn send_guarded(self, provider: Provider) -> Result<reqwest::Response, Guarded<reqwest::Error>> {
+        let is_outage = |result: &Result<reqwest::Response, reqwest::Error>| match result {
+            Ok(response) => response.status().is_server_error(),
+            Err(e) => !e.is_builder(),
+        };
+        guard(provider, is_outage, self.send())
+            .await
+            .map_err(widen)?
+            .map_err(Guarded::Failed)
+    }
+}
+
+pub fn provider_breaker_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/provider-breakers", get(breaker_report))
+        .with_state(app_state)
+}
+
+#[derive(Serialize)]
+pub struct BreakerReport {
+    pub provider: &'static str,
+    pub state: &'static str, // "closed", "open" or "half_open"
+    pub consecutive_failures: u32,
+    pub retry_after_secs: Option<u64>, // While open
+    // Since startup
+    pub calls: u64,
+    pub failures: u64,
+    pub rejected: u64, // Failed fast while open
+    pub times_opened: u64,
+}
+
+async fn breaker_report(_admin: AuthenticatedAdmin, State(_state): State<Arc<AppState>>) -> Json<Vec<BreakerReport>> {
+    let now = Instant::now();
+    let report = Provider::ALL
+        .into_iter()
+        .map(|provider| {
+            let breaker = breaker(provider);
+            let (state, consecutive_failures, retry_after_secs) = match *breaker.state.lock().unwrap() {
+                BreakerState::Closed { failures } => ("closed", failures, None),
+                BreakerState::Open { until } if now < until => ("open", 0, Some((until - now).as_secs().max(1))),
+                BreakerState::Open { .. } => ("half_open", 0, None), // The next call is the probe
+                BreakerState::HalfOpen { .. } => ("half_open", 0, None),
+            };
+            BreakerReport {
+                provider: provider.name(),
+                state,
+                consecutive_failures,
+                retry_after_secs,
+                calls: breaker.calls.load(Ordering::Relaxed),
+                failures: breaker.failures.load(Ordering::Relaxed),
+                rejected: breaker.rejected.load(Ordering::Relaxed),
+                times_opened: breaker.times_opened.load(Ordering::Relaxed),
+            }
+        })
+        .collect();
+    Json(report)
+}
@@ -0,0 +1,107 @@
+// Provider Retry Module - Retrying idempotent calls to Stripe, EasyPost and Brevo
+// Reads that are safe to repeat (EasyPost trackers and rate quotes, Brevo contact and list
+// fetches) are sent with `send_retrying` instead of `send_guarded`. A call is tried up to
+// PROVIDER_RETRY_ATTEMPTS times when it times out, can't connect, or gets 429 or a 5xx:
+// - the wait is the provider's Retry-After when it sends one (seconds or an HTTP date). One
+//   longer than PROVIDER_MAX_RETRY_AFTER_SECS isn't waited out; the response goes back as is
+// - otherwise PROVIDER_RETRY_BASE_DELAY_MS doubling per attempt, plus up to as much again of
+//   random jitter so callers that failed together don't retry together
+// - each API request has a budget of PROVIDER_RETRY_BUDGET retries shared by all its provider
+//   calls, so a request making several calls can't multiply its latency. Calls made outside a
+//   request (jobs, schedulers) are limited by the attempts alone
+// Every attempt goes through the provider's circuit breaker, so retries stop once it opens
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::app_config::{self, ProviderSettings};
+use crate::provider_breaker::{Guarded, GuardedSend, Provider};
+
+tokio::task_local! {
+    static BUDGET: Arc<AtomicU32>; // Retries the current request has left
+}
+
+// Give each API request its retry budget
+pub async fn retry_budget(request: Request, next: Next) -> Response {
+    let budget = Arc::new(AtomicU32::new(app_config::get().providers.retry_budget));
+    BUDGET.scope(budget, next.run(request)).await
+}
+
+fn spend_retry() -> bool {
+    BUDGET
+        .try_with(|budget| budget.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| left.checked_sub(1)).is_ok())
+        .unwrap_or(true)
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+// Retry-After as delay-seconds or an HTTP date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((at - Utc::now()).to_std().unwrap_or_default())
+}
+
+fn backoff(settings: &ProviderSettings, attempt: u32) -> Duration {
+    let delay = settings.retry_base_delay_ms.saturating_mul(1 << (attempt - 1).min(10));
+    let jitter = rand::random::<u64>() % (delay + 1);
+    Duration::from_millis(delay + jitter)
+}
+
+// How long to wait before trying again, or None when the outcome should go back to the caller
+fn retry_wait(settings: &ProviderSettings, attempt: u32, result: &Result<reqwest::Response, Guarded<reqwest::Error>>) -> Option<Duration> {
+    match result {
+        Ok(response) if is_retryable_status(response.status()) => match retry_after(response.headers()) {
+            Some(wait) if wait > Duration::from_secs(settings.max_retry_after_secs) => None,
+            Some(wait) => Some(wait),
+            None => Some(backoff(settings, attempt)),
+        },
+        Ok(_) => None,
+        Err(Guarded::TimedOut(_)) => Some(backoff(settings, attempt)),
+        Err(Guarded::Failed(e)) if e.is_connect() || e.is_timeout() => Some(backoff(settings, attempt)),
+        Err(_) => None,
+    }
+}
+
+// Sends an idempotent request to a provider, retrying as described above
+pub trait RetrySend {
+    fn send_retrying(self, provider: Provider) -> impl Future<Output = Result<reqwest::Response, Guarded<reqwest::Error>>> + Send;
+}
+
+impl RetrySend for reqwest::RequestBuilder {
+    async fn send_retrying(self, provider: Provider) -> Result<reqwest::Response, Guarded<reqwest::Error>> {
+        let settings = &app_config::get().providers;
+        let mut attempt = 1;
+        loop {
+            // A streamed body can't be sent twice
+            let Some(request) = self.try_clone() else {
+                return self.send_guarded(provider).await;
+            };
+            let result = request.send_guarded(provider).await;
+            match retry_wait(settings, attempt, &result) {
+                Some(wait) if attempt < settings.retry_attempts && spend_retry() => {
+                    tracing::warn!(
+                        provider = provider.name(),
+                        attempt,
+                        wait_ms = wait.as_millis() as u64,
+                        "provider call failed, retrying"
+                    );
+                    tokio::time::sleep(wait).await;
+                    attempt += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+}
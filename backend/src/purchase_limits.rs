@@ -0,0 +1,498 @@
+// Purchase Limits Module - Max-per-customer rules for limited drops
+// A limited product can be bought at most max_per_customer times per buyer, counted over the
+// last window_days (or all time). Checkout counts the buyer's earlier orders by customer
+// account, email, and the card fingerprints of those orders, plus the buyer's other open
+// checkouts; admins can give a single email a different limit. Card fingerprints are only
+// known once a Stripe payment goes through, so they are recorded on the order and a buyer who
+// got past the limit with a new email on the same card is flagged to the ops channel
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use stripe::{Client as StripeClient, PaymentIntent, PaymentIntentId};
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::cart_pricing::PricedLine;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::notifications::ops::OpsNotifier;
+use crate::pii::{self, Encrypted};
+use crate::provider_breaker;
+use crate::unit_of_work::WorkError;
+use crate::AppState;
+
+// Database model for purchase limits
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct PurchaseLimit {
+    pub product_id: i32,
+    pub max_per_customer: i32,
+    pub window_days: Option<i32>, // None counts every earlier order
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// Limit with its product name and override count for the admin list
+#[derive(Debug, Serialize)]
+pub struct PurchaseLimitSummary {
+    #[serde(flatten)]
+    pub limit: PurchaseLimit,
+    pub product_name: String,
+    pub overrides: i64,
+}
+
+// Database model for per-customer overrides
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct PurchaseLimitOverride {
+    pub id: Uuid,
+    pub product_id: i32,
+    pub customer_email: Encrypted,
+    pub max_per_customer: i32,
+    pub note: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct PurchaseLimitInput {
+    pub max_per_customer: i32,
+    pub window_days: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct OverrideInput {
+    pub customer_email: String,
+    pub max_per_customer: i32,
+    pub note: Option<String>,
+}
+
+// A limited product in the cart with the limit that applies to this buyer
+#[derive(sqlx::FromRow)]
+struct BuyerLimit {
+    product_id: i32,
+    name: String,
+    max_per_customer: i32,
+    window_days: Option<i32>,
+}
+
+// Who is buying: earlier orders matching any of these count towards the limit
+struct Buyer<'a> {
+    customer_id: Option<Uuid>,
+    email: &'a str,
+    fingerprint: Option<&'a str>,
+}
+
+// An order with limited products, as checked once it is created
+#[derive(sqlx::FromRow)]
+struct LimitedOrder {
+    customer_id: Option<Uuid>,
+    customer_email: Option<Encrypted>,
+    payment_intent_id: Option<String>,
+    product_ids: Vec<i32>,
+}
+
+pub fn purchase_limit_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/purchase-limits", get(list_limits))
+        .route("/api/admin/products/:id/purchase-limit", put(set_limit).delete(remove_limit))
+        .route("/api/admin/products/:id/purchase-limit/overrides", get(list_overrides).post(set_override))
+        .route("/api/admin/purchase-limit-overrides/:id", delete(remove_override))
+        .with_state(app_state)
+}
+
+// Limits on these products for the email, with its override applied. In a stable order, as
+// checkout takes a lock per limited product in this order
+async fn buyer_limits(
+    executor: impl sqlx::PgExecutor<'_>,
+    product_ids: &[i32],
+    email: &str,
+) -> Result<Vec<BuyerLimit>, sqlx::Error> {
+    sqlx::query_as!(
+        BuyerLimit,
+        r#"SELECT l.product_id, p.name, COALESCE(o.max_per_customer, l.max_per_customer) AS "max_per_customer!", l.window_days
+         FROM purchase_limits l
+         JOIN products p ON p.id = l.product_id
+         LEFT JOIN purchase_limit_overrides o ON o.product_id = l.product_id AND o.customer_email_hash = $2
+         WHERE l.product_id = ANY($1)
+         ORDER BY p.name, l.product_id"#,
+        product_ids,
+        pii::email_hash(email),
+    )
+    .fetch_all(executor)
+    .await
+}
+
+// Units of the product the buyer ordered within the window. Orders by the same account or
+// email count, and so do orders paid with a card any of those orders was paid with
+async fn purchased(executor: impl sqlx::PgExecutor<'_>, limit: &BuyerLimit, buyer: &Buyer<'_>) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"WITH cards AS (
+             SELECT DISTINCT payment_fingerprint FROM orders
+             WHERE payment_fingerprint IS NOT NULL AND (customer_id = $2 OR customer_email_hash = $3)
+         )
+         SELECT COALESCE(SUM(i.quantity), 0)::BIGINT AS "quantity!"
+         FROM order_items i JOIN orders o ON o.id = i.order_id
+         WHERE i.product_id = $1
+           AND o.status NOT IN ('failed', 'refunded', 'voided')
+           AND ($5::INT IS NULL OR o.created_at >= NOW() - make_interval(days => $5::INT))
+           AND (o.customer_id = $2 OR o.customer_email_hash = $3
+                OR o.payment_fingerprint = $4 OR o.payment_fingerprint IN (SELECT payment_fingerprint FROM cards))"#,
+        limit.product_id,
+        buyer.customer_id,
+        Some(buyer.email.trim()).filter(|e| !e.is_empty()).map(pii::email_hash),
+        buyer.fingerprint,
+        limit.window_days,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+// Units of the product in the buyer's other open checkouts: carts by the same account or email
+// that still hold their stock or seats and haven't become an order yet
+async fn held(
+    executor: impl sqlx::PgExecutor<'_>,
+    limit: &BuyerLimit,
+    buyer: &Buyer<'_>,
+    cart_id: Option<Uuid>,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT COALESCE(SUM(i.quantity), 0)::BIGINT AS "quantity!"
+         FROM checkout_cart_items i JOIN checkout_carts c ON c.id = i.cart_id
+         WHERE i.product_id = $1
+           AND c.converted_at IS NULL
+           AND c.id IS DISTINCT FROM $4
+           AND (c.customer_id = $2 OR c.buyer_email_hash = $3)
+           AND (EXISTS (SELECT 1 FROM stock_reservations r WHERE r.cart_id = c.id AND r.product_id = $1 AND r.status = 'held')
+                OR EXISTS (SELECT 1 FROM ticket_holds h WHERE h.cart_id = c.id AND h.product_id = $1 AND h.status = 'held'))"#,
+        limit.product_id,
+        buyer.customer_id,
+        pii::email_hash(buyer.email),
+        cart_id,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+// Refuse a checkout that would take the buyer over a product's limit, as part of the
+// checkout's unit of work. Guests must give an email to buy limited products; signed-in
+// customers default to their account email. The buyer's other open checkouts count too, and
+// each limited product is locked per buyer email until the checkout commits, so parallel
+// checkouts by the same buyer see each other's holds
+pub async fn enforce(
+    conn: &mut sqlx::PgConnection,
+    cart_id: Option<Uuid>,
+    customer_id: Option<Uuid>,
+    email: Option<&str>,
+    lines: &[PricedLine],
+) -> Result<(), WorkError<(StatusCode, String)>> {
+    let mut quantities: BTreeMap<i32, i64> = BTreeMap::new();
+    for line in lines {
+        *quantities.entry(line.product_id).or_insert(0) += line.quantity as i64;
+    }
+    let ids: Vec<i32> = quantities.keys().copied().collect();
+    let limited: i64 = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "limited!" FROM purchase_limits WHERE product_id = ANY($1)"#, &ids)
+        .fetch_one(&mut *conn)
+        .await?;
+    if limited == 0 {
+        return Ok(());
+    }
+
+    let account_email = match customer_id {
+        Some(customer_id) => sqlx::query_scalar!(r#"SELECT email AS "email: Encrypted" FROM customers WHERE id = $1"#, customer_id)
+            .fetch_optional(&mut *conn)
+            .await?
+            .map(Encrypted::into_inner),
+        None => None,
+    };
+    let email = email
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .or(account_email.as_deref())
+        .ok_or(WorkError::Aborted((StatusCode::BAD_REQUEST, "An email address is required to buy limited items".to_string())))?;
+    let buyer = Buyer { customer_id, email, fingerprint: None };
+    let email_hash = pii::email_hash(email);
+    // Later checkouts by this email count the cart while it is open
+    if let Some(cart_id) = cart_id {
+        sqlx::query!("UPDATE checkout_carts SET buyer_email_hash = $2 WHERE id = $1", cart_id, &email_hash)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    for limit in buyer_limits(&mut *conn, &ids, email).await? {
+        sqlx::query!(
+            "SELECT pg_advisory_xact_lock(hashtext('purchase_limit:' || $1::INT || ':' || $2::TEXT))",
+            limit.product_id,
+            &email_hash,
+        )
+        .execute(&mut *conn)
+        .await?;
+        let bought = purchased(&mut *conn, &limit, &buyer).await? + held(&mut *conn, &limit, &buyer, cart_id).await?;
+        let left = (limit.max_per_customer as i64 - bought).max(0);
+        if quantities[&limit.product_id] > left {
+            return Err(WorkError::Aborted((
+                StatusCode::CONFLICT,
+                if left == 0 {
+                    format!("{} is limited to {} per customer and you have reached the limit", limit.name, limit.max_per_customer)
+                } else {
+                    format!("{} is limited to {} per customer; you can buy {} more", limit.name, limit.max_per_customer, left)
+                },
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Records the card fingerprint of Stripe orders with limited products and flags orders that
+// took a buyer over a limit (e.g. the same card under a new email)
+pub struct PurchaseLimitSubscriber {
+    pool: Arc<sqlx::PgPool>,
+    stripe_client: StripeClient,
+    ops: OpsNotifier,
+}
+
+impl PurchaseLimitSubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>, stripe_client: StripeClient, ops: OpsNotifier) -> Self {
+        Self { pool, stripe_client, ops }
+    }
+
+    async fn card_fingerprint(&self, payment_intent_id: &str) -> Option<String> {
+        let id = payment_intent_id.parse::<PaymentIntentId>().ok()?;
+        match provider_breaker::stripe(PaymentIntent::retrieve(&self.stripe_client, &id, &["payment_method"])).await {
+            Ok(intent) => intent
+                .payment_method
+                .as_ref()
+                .and_then(|pm| pm.as_object())
+                .and_then(|pm| pm.card.as_ref())
+                .and_then(|card| card.fingerprint.clone()),
+            Err(e) => {
+                tracing::error!("Failed to look up the card of payment {}: {}", payment_intent_id, e);
+                None
+            }
+        }
+    }
+
+    async fn check_order(&self, order_id: Uuid) -> Result<(), sqlx::Error> {
+        let order = sqlx::query_as!(
+            LimitedOrder,
+            r#"SELECT o.customer_id, o.customer_email AS "customer_email: Encrypted", o.payment_intent_id, ARRAY_AGG(DISTINCT i.product_id) AS "product_ids!"
+             FROM orders o
+             JOIN order_items i ON i.order_id = o.id
+             JOIN purchase_limits l ON l.product_id = i.product_id
+             WHERE o.id = $1
+             GROUP BY o.id"#,
+            order_id,
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+        let Some(order) = order else {
+            return Ok(());
+        };
+
+        let fingerprint = match &order.payment_intent_id {
+            Some(payment_intent_id) => self.card_fingerprint(payment_intent_id).await,
+            None => None,
+        };
+        if let Some(fingerprint) = &fingerprint {
+            sqlx::query!("UPDATE orders SET payment_fingerprint = $2 WHERE id = $1", order_id, fingerprint)
+                .execute(&*self.pool)
+                .await?;
+        }
+
+        let email = order.customer_email.map(Encrypted::into_inner).unwrap_or_default();
+        let buyer = Buyer { customer_id: order.customer_id, email: &email, fingerprint: fingerprint.as_deref() };
+        for limit in buyer_limits(&*self.pool, &order.product_ids, &email).await? {
+            let bought = purchased(&*self.pool, &limit, &buyer).await?;
+            if bought > limit.max_per_customer as i64 {
+                self.ops.notify(format!(
+                    ":no_entry: Order {} goes over the purchase limit for {}: {} bought by this buyer, limit {}",
+                    order_id, limit.name, bought, limit.max_per_customer
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for PurchaseLimitSubscriber {
+    fn name(&self) -> &'static str {
+        "purchase_limits"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        if let DomainEvent::OrderCreated { order_id, .. } = event {
+            self.check_order(*order_id).await.map_err(|e| format!("DB error: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+async fn list_limits(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<PurchaseLimitSummary>>, (StatusCode, String)> {
+    let limits = sqlx::query!(
+        r#"SELECT l.product_id, l.max_per_customer, l.window_days, l.created_at, l.updated_at, p.name AS product_name,
+                (SELECT COUNT(*) FROM purchase_limit_overrides o WHERE o.product_id = l.product_id) AS "overrides!"
+         FROM purchase_limits l JOIN products p ON p.id = l.product_id
+         ORDER BY p.name"#,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .into_iter()
+    .map(|r| PurchaseLimitSummary {
+        limit: PurchaseLimit {
+            product_id: r.product_id,
+            max_per_customer: r.max_per_customer,
+            window_days: r.window_days,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+        },
+        product_name: r.product_name,
+        overrides: r.overrides,
+    })
+    .collect();
+    Ok(Json(limits))
+}
+
+// Limit how many of the product each buyer can order
+async fn set_limit(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+    Json(input): Json<PurchaseLimitInput>,
+) -> Result<Json<PurchaseLimit>, (StatusCode, String)> {
+    if input.max_per_customer < 0 {
+        return Err((StatusCode::BAD_REQUEST, "max_per_customer must be at least 0".to_string()));
+    }
+    if input.window_days.is_some_and(|d| d < 1) {
+        return Err((StatusCode::BAD_REQUEST, "window_days must be at least 1".to_string()));
+    }
+
+    let limit = sqlx::query_as!(
+        PurchaseLimit,
+        "INSERT INTO purchase_limits (product_id, max_per_customer, window_days)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (product_id) DO UPDATE SET
+             max_per_customer = EXCLUDED.max_per_customer,
+             window_days = EXCLUDED.window_days,
+             updated_at = NOW()
+         RETURNING product_id, max_per_customer, window_days, created_at, updated_at",
+        product_id,
+        input.max_per_customer,
+        input.window_days,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+            (StatusCode::NOT_FOUND, "Product not found".to_string())
+        }
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)),
+    })?;
+    tracing::info!("{} limited product {} to {} per customer", admin.username, product_id, limit.max_per_customer);
+    Ok(Json(limit))
+}
+
+// Remove the limit and its overrides
+async fn remove_limit(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM purchase_limits WHERE product_id = $1", product_id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Product has no purchase limit".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_overrides(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+) -> Result<Json<Vec<PurchaseLimitOverride>>, (StatusCode, String)> {
+    let overrides = sqlx::query_as!(
+        PurchaseLimitOverride,
+        r#"SELECT id, product_id, customer_email AS "customer_email: Encrypted", max_per_customer, note, created_by, created_at FROM purchase_limit_overrides WHERE product_id = $1 ORDER BY created_at DESC"#,
+        product_id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(overrides))
+}
+
+// Give one customer email its own limit for the product (higher or lower); replaces any earlier one
+async fn set_override(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+    Json(input): Json<OverrideInput>,
+) -> Result<(StatusCode, Json<PurchaseLimitOverride>), (StatusCode, String)> {
+    let email = input.customer_email.trim().to_lowercase();
+    if !email.contains('@') || email.len() > 255 {
+        return Err((StatusCode::BAD_REQUEST, "A valid customer_email is required".to_string()));
+    }
+    if input.max_per_customer < 0 {
+        return Err((StatusCode::BAD_REQUEST, "max_per_customer must be at least 0".to_string()));
+    }
+    let note = input.note.as_deref().map(str::trim).filter(|n| !n.is_empty());
+
+    let entry = sqlx::query_as!(
+        PurchaseLimitOverride,
+        r#"INSERT INTO purchase_limit_overrides (product_id, customer_email, customer_email_hash, max_per_customer, note, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (product_id, customer_email_hash) DO UPDATE SET
+             max_per_customer = EXCLUDED.max_per_customer,
+             note = EXCLUDED.note,
+             created_by = EXCLUDED.created_by,
+             created_at = NOW()
+         RETURNING id, product_id, customer_email AS "customer_email: Encrypted", max_per_customer, note, created_by, created_at"#,
+        product_id,
+        Encrypted::from(email.as_str()) as _,
+        pii::email_hash(&email),
+        input.max_per_customer,
+        note,
+        &admin.username,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+            (StatusCode::NOT_FOUND, "Product has no purchase limit".to_string())
+        }
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)),
+    })?;
+    tracing::info!(
+        "{} set the purchase limit of product {} to {} for {}",
+        admin.username, product_id, entry.max_per_customer, email
+    );
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+async fn remove_override(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM purchase_limit_overrides WHERE id = $1", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Override not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
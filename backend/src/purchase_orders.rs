@@ -0,0 +1,686 @@
+// Purchase Orders Module - Net terms checkout for wholesale buyers
+// Customers tagged PURCHASE_ORDER_CUSTOMER_TAG can check out with a PO number instead of a card.
+// The order is created 'awaiting_payment', confirmed and fulfilled like any other, and invoiced
+// with NET_TERMS_DAYS terms (PDF invoice). Staff record the payments received against the
+// invoice; once they cover the total the order is 'completed'. Overdue invoices get a reminder
+// every INVOICE_REMINDER_INTERVAL_DAYS
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use printpdf::{BuiltinFont, IndirectFontRef, Line, Mm, PdfDocument, PdfLayerReference, Point};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::branding::{escape_html, load_branding, Branding};
+use crate::cart_pricing::{price_cart, CartPrice, CartPriceRequest};
+use crate::customer_auth::AuthenticatedCustomer;
+use crate::manual_orders::{self, NewManualOrder};
+use crate::notifications::{queue_email, EmailPriority};
+use crate::order_tracking;
+use crate::pii::Encrypted;
+use crate::repos::{order_items, order_query};
+use crate::route_limits::{self, RouteGroup};
+use crate::order_status::{self, OrderStatus};
+use crate::webhooks::{Order, OrderItem};
+use crate::AppState;
+
+const PAYMENT_METHOD: &str = "purchase_order";
+const RECEIPT_METHODS: [&str; 5] = ["check", "ach", "wire", "card", "other"];
+
+#[derive(Deserialize)]
+pub struct PurchaseOrderCheckout {
+    #[serde(flatten)]
+    pub cart: CartPriceRequest, // items, coupon_code and shipping, priced like checkout
+    pub po_number: String,
+}
+
+#[derive(Serialize)]
+pub struct PurchaseOrderResponse {
+    pub order: Order,
+    pub price: CartPrice,
+}
+
+// A payment received against a purchase order invoice
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct InvoicePayment {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub amount: i64, // in cents
+    pub method: String,
+    pub reference: Option<String>,
+    pub received_at: DateTime<Utc>,
+    pub recorded_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct RecordPaymentRequest {
+    pub amount: Option<i64>, // in cents; defaults to the open balance
+    pub method: String,      // check, ach, wire, card or other
+    pub reference: Option<String>,
+    pub received_at: Option<DateTime<Utc>>, // Defaults to now
+}
+
+// An invoice with what has been paid against it
+#[derive(Serialize)]
+pub struct InvoiceStatus {
+    #[serde(flatten)]
+    pub order: Order,
+    pub amount_paid: i64, // in cents
+    pub balance: i64,     // in cents
+    pub overdue: bool,
+    pub payments: Vec<InvoicePayment>,
+}
+
+// Row of the receivables list
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Receivable {
+    pub order_id: Uuid,
+    pub po_number: Option<String>,
+    pub customer_email: Option<Encrypted>,
+    pub customer_name: Option<String>,
+    pub status: String,
+    pub total_amount: i64,
+    pub amount_paid: i64,
+    pub balance: i64,
+    pub currency: String,
+    pub payment_due_at: Option<DateTime<Utc>>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub payment_reminder_sent_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ReceivablesQuery {
+    pub status: Option<String>, // open (default), overdue, paid or all
+}
+
+#[derive(Deserialize)]
+pub struct InvoiceQuery {
+    pub token: Option<String>, // Customer or admin JWT, for links opened outside the app
+    pub email: Option<String>, // The order email, as in the invoice email's link
+}
+
+// Checkout amounts behind the order's total, as priced at checkout
+#[derive(Debug, Default, sqlx::FromRow)]
+struct InvoiceTotals {
+    subtotal_amount: i64,
+    discount_amount: i64,
+    tax_amount: i64,
+    shipping_amount: i64,
+}
+
+pub fn purchase_order_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/purchase-orders", post(place_purchase_order))
+        .route("/api/orders/:id/invoice.pdf", get(invoice_pdf).layer(route_limits::limit(RouteGroup::Pdfs)))
+        .route("/api/admin/purchase-orders", get(list_receivables))
+        .route("/api/admin/orders/:id/payments", get(get_invoice_status).post(record_payment))
+        .route("/api/admin/orders/:id/payment-reminder", post(send_reminder_handler))
+        .with_state(app_state)
+}
+
+async fn load_purchase_order(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Order, (StatusCode, String)> {
+    order_query!("SELECT", "FROM orders WHERE id = $1 AND payment_method = $2", order_id, PAYMENT_METHOD)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Purchase order not found".to_string()))
+}
+
+async fn amount_paid(pool: &sqlx::PgPool, order_id: Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(r#"SELECT COALESCE(SUM(amount), 0)::BIGINT AS "amount!" FROM invoice_payments WHERE order_id = $1"#, order_id)
+        .fetch_one(pool)
+        .await
+}
+
+async fn invoice_status(pool: &sqlx::PgPool, order: Order) -> Result<InvoiceStatus, sqlx::Error> {
+    let payments = sqlx::query_as!(
+        InvoicePayment,
+        "SELECT id, order_id, amount, method, reference, received_at, recorded_by, created_at FROM invoice_payments WHERE order_id = $1 ORDER BY received_at, created_at",
+        order.id,
+    )
+    .fetch_all(pool)
+    .await?;
+    let amount_paid: i64 = payments.iter().map(|p| p.amount).sum();
+    let overdue = order.status == OrderStatus::AwaitingPayment && order.payment_due_at.is_some_and(|due| due < Utc::now());
+    Ok(InvoiceStatus {
+        balance: (order.total_amount - amount_paid).max(0),
+        amount_paid,
+        overdue,
+        payments,
+        order,
+    })
+}
+
+// Link to the order's PDF invoice that opens for whoever has the order email
+fn invoice_url(order: &Order) -> String {
+    let api_url = app_config::get().server.public_api_url.trim_end_matches('/').to_string();
+    let base = format!("{}/api/orders/{}/invoice.pdf", api_url, order.id);
+    match &order.customer_email {
+        Some(email) => reqwest::Url::parse_with_params(&base, &[("email", email)])
+            .map(|url| url.to_string())
+            .unwrap_or(base),
+        None => base,
+    }
+}
+
+fn format_money(cents: i64) -> String {
+    format!("${:.2}", cents as f64 / 100.0)
+}
+
+fn format_due_date(order: &Order) -> String {
+    order.payment_due_at.map(|d| d.format("%B %-d, %Y").to_string()).unwrap_or_default()
+}
+
+// Check out with a PO number; only customers with the purchase order tag may
+async fn place_purchase_order(
+    customer: AuthenticatedCustomer,
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<PurchaseOrderCheckout>,
+) -> Result<(StatusCode, Json<PurchaseOrderResponse>), (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let po_number = request.po_number.trim();
+    if po_number.is_empty() || po_number.len() > 100 {
+        return Err((StatusCode::BAD_REQUEST, "po_number is required (max 100 characters)".to_string()));
+    }
+
+    let account: Option<(Encrypted, Option<String>, Vec<String>, bool)> =
+        sqlx::query!(r#"SELECT email AS "email: Encrypted", name, tags, disabled FROM customers WHERE id = $1"#, customer.customer_id)
+            .map(|r| (r.email, r.name, r.tags, r.disabled))
+            .fetch_optional(&*app_state.pool)
+            .await
+            .map_err(db_error)?;
+    let (email, name, tags, disabled) = account.ok_or((StatusCode::NOT_FOUND, "Customer not found".to_string()))?;
+    let config = app_config::get();
+    if disabled || !tags.contains(&config.checkout.purchase_order_tag) {
+        return Err((StatusCode::FORBIDDEN, "Purchase order checkout is not enabled for this account".to_string()));
+    }
+
+    let price = price_cart(&app_state.pool, &request.cart, Some(customer.customer_id))
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let new_order = NewManualOrder {
+        customer_email: Some(email.into_inner()),
+        customer_name: name,
+        payment_method: PAYMENT_METHOD,
+        payment_reference: None,
+        created_by: None,
+        customer_id: Some(customer.customer_id),
+    };
+    let order = manual_orders::create_from_price(&app_state, &price, new_order).await?;
+    let order = order_query!(
+        "UPDATE orders SET po_number = $2, payment_due_at = created_at + make_interval(days => $3::INT), updated_at = NOW()
+         WHERE id = $1
+         RETURNING",
+        "",
+        order.id,
+        po_number,
+        config.checkout.net_terms_days as i32,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+
+    if let Err(e) = send_invoice_email(&app_state.pool, &order).await {
+        tracing::error!("Failed to email the invoice for order {}: {}", order.id, e);
+    }
+    app_state.ops.notify(format!(
+        "Purchase order {} from {}: order {} ({}, due {})",
+        po_number,
+        order.customer_email.as_deref().unwrap_or("unknown"),
+        order.id,
+        format_money(order.total_amount),
+        format_due_date(&order)
+    ));
+    Ok((StatusCode::CREATED, Json(PurchaseOrderResponse { order, price })))
+}
+
+async fn send_invoice_email(pool: &sqlx::PgPool, order: &Order) -> Result<(), String> {
+    let Some(email) = &order.customer_email else {
+        return Ok(());
+    };
+    let items = order_items(pool, order.id).await.map_err(|e| format!("DB error: {}", e))?;
+    let html_body = load_branding(pool).await.email_html(
+        "Invoice",
+        &format!(
+            r#"<p>Thank you for your purchase order {}.</p>
+            <p>Order ID: {}</p>
+            {}
+            <p class="total">Amount due: {}</p>
+            <p>Payment terms: Net {}. Please pay by {}.</p>
+            <p><a href="{}">Download the invoice (PDF)</a></p>"#,
+            escape_html(order.po_number.as_deref().unwrap_or_default()),
+            order.id,
+            crate::webhooks::items_table_html(&items),
+            format_money(order.total_amount),
+            app_config::get().checkout.net_terms_days,
+            format_due_date(order),
+            escape_html(&invoice_url(order))
+        ),
+    );
+    let subject = format!("Invoice for PO {}", order.po_number.as_deref().unwrap_or_default());
+    queue_email(pool, email, &subject, &html_body, EmailPriority::Immediate).await
+}
+
+// PDF invoice for the order's customer, an admin, or whoever has the order email
+async fn invoice_pdf(
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+    Query(query): Query<InvoiceQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string)
+        .or(query.token);
+    order_tracking::authorize(&app_state.pool, order_id, token.as_deref(), query.email.as_deref()).await?;
+
+    let order = app_state
+        .repos
+        .orders
+        .find(order_id)
+        .await
+        .map_err(db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Order not found".to_string()))?;
+    let items = app_state.repos.orders.items(order_id).await.map_err(db_error)?;
+    let totals = sqlx::query_as!(
+        InvoiceTotals,
+        "SELECT subtotal_amount, discount_amount, tax_amount, shipping_amount FROM checkout_carts WHERE id = $1",
+        order.cart_id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    let paid = amount_paid(&app_state.pool, order_id).await.map_err(db_error)?;
+    let branding = load_branding(&app_state.pool).await;
+
+    let pdf = render_invoice_pdf(&branding, &order, &items, totals, paid)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to render invoice: {}", e)))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (header::CONTENT_DISPOSITION, format!("inline; filename=\"invoice-{}.pdf\"", order.id)),
+        ],
+        pdf,
+    ))
+}
+
+// Letter-size invoice with the store letterhead, terms and the items; long orders continue on
+// further pages
+fn render_invoice_pdf(
+    branding: &Branding,
+    order: &Order,
+    items: &[OrderItem],
+    totals: Option<InvoiceTotals>,
+    amount_paid: i64,
+) -> Result<Vec<u8>, String> {
+    const PAGE_WIDTH: f32 = 215.9;
+    const PAGE_HEIGHT: f32 = 279.4;
+    const MARGIN: f32 = 20.0;
+    const ROW: f32 = 6.0;
+
+    let (doc, page, layer) = PdfDocument::new(format!("Invoice {}", order.id), Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Invoice");
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| e.to_string())?;
+    let mut layer = doc.get_page(page).get_layer(layer);
+    let text = |layer: &PdfLayerReference, s: &str, size: f32, x: f32, y: f32, font: &IndirectFontRef| {
+        layer.use_text(s, size, Mm(x), Mm(y), font);
+    };
+    let rule = |layer: &PdfLayerReference, y: f32| {
+        layer.add_line(Line {
+            points: vec![(Point::new(Mm(MARGIN), Mm(y)), false), (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(y)), false)],
+            is_closed: false,
+        });
+    };
+
+    // Letterhead and invoice details
+    let mut y = PAGE_HEIGHT - MARGIN;
+    text(&layer, &branding.store_name, 18.0, MARGIN, y, &bold);
+    text(&layer, "INVOICE", 18.0, PAGE_WIDTH - MARGIN - 35.0, y, &bold);
+    y -= ROW;
+    for line in branding.footer_address.iter().chain(branding.support_email.iter()) {
+        text(&layer, line, 9.0, MARGIN, y, &regular);
+        y -= 5.0;
+    }
+    y -= ROW;
+
+    let terms = match (order.payment_method.as_deref(), order.payment_due_at) {
+        (Some(PAYMENT_METHOD), Some(due)) => {
+            let days = (due - order.created_at).num_days();
+            vec![
+                ("Terms", format!("Net {}", days)),
+                ("Due date", due.format("%B %-d, %Y").to_string()),
+            ]
+        }
+        _ => vec![("Terms", "Paid at checkout".to_string())],
+    };
+    let mut details = vec![
+        ("Invoice number", order.id.to_string()),
+        ("Invoice date", order.created_at.format("%B %-d, %Y").to_string()),
+    ];
+    if let Some(po_number) = &order.po_number {
+        details.push(("PO number", po_number.clone()));
+    }
+    details.extend(terms);
+    let bill_to: Vec<&str> = [order.customer_name.as_deref(), order.customer_email.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect();
+
+    text(&layer, "Bill to", 10.0, MARGIN, y, &bold);
+    let details_y = y;
+    for (i, line) in bill_to.iter().enumerate() {
+        text(&layer, line, 10.0, MARGIN, y - ROW * (i as f32 + 1.0), &regular);
+    }
+    for (i, (label, value)) in details.iter().enumerate() {
+        let row_y = details_y - ROW * i as f32;
+        text(&layer, label, 10.0, 110.0, row_y, &bold);
+        text(&layer, value, 10.0, 145.0, row_y, &regular);
+    }
+    y = details_y - ROW * (details.len().max(bill_to.len() + 1) as f32) - ROW;
+
+    // Items
+    let header = |layer: &PdfLayerReference, y: f32| {
+        text(layer, "Item", 10.0, MARGIN, y, &bold);
+        text(layer, "Qty", 10.0, 125.0, y, &bold);
+        text(layer, "Unit price", 10.0, 145.0, y, &bold);
+        text(layer, "Total", 10.0, 175.0, y, &bold);
+        rule(layer, y - 2.0);
+    };
+    header(&layer, y);
+    y -= ROW + 1.0;
+    for item in items {
+        if y < MARGIN + ROW {
+            let (next_page, next_layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Invoice");
+            layer = doc.get_page(next_page).get_layer(next_layer);
+            y = PAGE_HEIGHT - MARGIN;
+            header(&layer, y);
+            y -= ROW + 1.0;
+        }
+        let name: String = if item.product_name.chars().count() > 55 {
+            format!("{}...", item.product_name.chars().take(52).collect::<String>())
+        } else {
+            item.product_name.clone()
+        };
+        text(&layer, &name, 10.0, MARGIN, y, &regular);
+        text(&layer, &item.quantity.to_string(), 10.0, 125.0, y, &regular);
+        text(&layer, &format_money(item.unit_price), 10.0, 145.0, y, &regular);
+        text(&layer, &format_money(item.total_price), 10.0, 175.0, y, &regular);
+        y -= ROW;
+    }
+
+    // Totals
+    let mut summary = Vec::new();
+    if let Some(totals) = totals {
+        summary.push(("Subtotal", totals.subtotal_amount));
+        if totals.discount_amount > 0 {
+            summary.push(("Discount", -totals.discount_amount));
+        }
+        summary.push(("Tax", totals.tax_amount));
+        summary.push(("Shipping", totals.shipping_amount));
+    }
+    summary.push(("Total", order.total_amount));
+    if amount_paid > 0 {
+        summary.push(("Paid", -amount_paid));
+    }
+    let balance = if order.status == OrderStatus::AwaitingPayment { (order.total_amount - amount_paid).max(0) } else { 0 };
+    summary.push(("Balance due", balance));
+    if y < MARGIN + ROW * (summary.len() as f32 + 2.0) {
+        let (next_page, next_layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Invoice");
+        layer = doc.get_page(next_page).get_layer(next_layer);
+        y = PAGE_HEIGHT - MARGIN;
+    }
+    rule(&layer, y + 2.0);
+    y -= ROW / 2.0;
+    for (label, cents) in summary {
+        let font = if label == "Total" || label == "Balance due" { &bold } else { &regular };
+        text(&layer, label, 10.0, 145.0, y, font);
+        text(&layer, &format_money(cents), 10.0, 175.0, y, font);
+        y -= ROW;
+    }
+    if let (Some(PAYMENT_METHOD), Some(po_number)) = (order.payment_method.as_deref(), &order.po_number) {
+        y -= ROW;
+        text(&layer, &format!("Please reference PO {} and invoice {} with your payment.", po_number, order.id), 9.0, MARGIN, y, &regular);
+    }
+
+    doc.save_to_bytes().map_err(|e| e.to_string())
+}
+
+// Open, overdue or paid purchase order invoices with their balances, oldest due first
+async fn list_receivables(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ReceivablesQuery>,
+) -> Result<Json<Vec<Receivable>>, (StatusCode, String)> {
+    let status = query.status.as_deref().unwrap_or("open");
+    if !["open", "overdue", "paid", "all"].contains(&status) {
+        return Err((StatusCode::BAD_REQUEST, format!("status must be open, overdue, paid or all, got {}", status)));
+    }
+    let receivables = sqlx::query_as!(
+        Receivable,
+        r#"SELECT o.id AS order_id, o.po_number, o.customer_email AS "customer_email: Encrypted", o.customer_name, o.status,
+                o.total_amount, COALESCE(p.paid, 0)::BIGINT AS "amount_paid!",
+                GREATEST(o.total_amount - COALESCE(p.paid, 0), 0)::BIGINT AS "balance!",
+                o.currency, o.payment_due_at, o.paid_at, o.payment_reminder_sent_at, o.created_at AS "created_at!"
+         FROM orders o
+         LEFT JOIN (SELECT order_id, SUM(amount) AS paid FROM invoice_payments GROUP BY order_id) p ON p.order_id = o.id
+         WHERE o.payment_method = $1 AND o.livemode = $2
+           AND CASE $3::TEXT
+                   WHEN 'open' THEN o.status = 'awaiting_payment'
+                   WHEN 'overdue' THEN o.status = 'awaiting_payment' AND o.payment_due_at < NOW()
+                   WHEN 'paid' THEN o.status = 'completed'
+                   ELSE TRUE
+               END
+         ORDER BY o.payment_due_at, o.created_at"#,
+        PAYMENT_METHOD,
+        app_state.mode.is_live(),
+        status,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(receivables))
+}
+
+async fn get_invoice_status(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<InvoiceStatus>, (StatusCode, String)> {
+    let order = load_purchase_order(&app_state.pool, order_id).await?;
+    let status = invoice_status(&app_state.pool, order)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(status))
+}
+
+// Record a payment received against the invoice; the order completes once it is paid in full
+async fn record_payment(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+    Json(request): Json<RecordPaymentRequest>,
+) -> Result<Json<InvoiceStatus>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let method = request.method.trim().to_lowercase();
+    if !RECEIPT_METHODS.contains(&method.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("method must be one of {}", RECEIPT_METHODS.join(", "))));
+    }
+
+    let mut tx = app_state.pool.begin().await.map_err(db_error)?;
+    // Lock the order so two payments recorded at once can't both complete it
+    let order = order_query!(
+        "SELECT",
+        "FROM orders WHERE id = $1 AND payment_method = $2 FOR UPDATE",
+        order_id,
+        PAYMENT_METHOD,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error)?
+    .ok_or((StatusCode::NOT_FOUND, "Purchase order not found".to_string()))?;
+    if order.status != OrderStatus::AwaitingPayment {
+        return Err((StatusCode::CONFLICT, format!("Order is {}", order.status)));
+    }
+    let paid: i64 = sqlx::query_scalar!(
+        r#"SELECT COALESCE(SUM(amount), 0)::BIGINT AS "paid!" FROM invoice_payments WHERE order_id = $1"#,
+        order_id,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    let balance = order.total_amount - paid;
+    let amount = request.amount.unwrap_or(balance);
+    if amount <= 0 || amount > balance {
+        return Err((StatusCode::BAD_REQUEST, format!("amount must be between 1 and the open balance of {} cents", balance)));
+    }
+
+    sqlx::query!(
+        "INSERT INTO invoice_payments (order_id, amount, method, reference, received_at, recorded_by)
+         VALUES ($1, $2, $3, $4, COALESCE($5, NOW()), $6)",
+        order_id,
+        amount,
+        &method,
+        request.reference.as_deref().map(str::trim).filter(|r| !r.is_empty()),
+        request.received_at,
+        &admin.username,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    let settled = amount == balance;
+    let (order, change) = if settled {
+        let change = order_status::transition(&mut tx, order_id, OrderStatus::Completed, &admin.username).await?;
+        let order = order_query!("UPDATE orders SET paid_at = NOW() WHERE id = $1 RETURNING", "", order_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        (order, Some(change))
+    } else {
+        (order, None)
+    };
+    tx.commit().await.map_err(db_error)?;
+    if let Some(change) = change {
+        app_state.events.publish(change.event());
+    }
+
+    tracing::info!(
+        "{} recorded {} {} payment on order {}{}",
+        admin.username,
+        format_money(amount),
+        method,
+        order_id,
+        if settled { " (paid in full)" } else { "" }
+    );
+    let status = invoice_status(&app_state.pool, order).await.map_err(db_error)?;
+    Ok(Json(status))
+}
+
+// Email the customer that the invoice is overdue and note when it was sent
+async fn send_overdue_reminder(state: &AppState, order: &Order) -> Result<(), String> {
+    let email = order.customer_email.as_deref().ok_or("Order has no customer email")?;
+    let paid = amount_paid(&state.pool, order.id).await.map_err(|e| format!("DB error: {}", e))?;
+    let balance = (order.total_amount - paid).max(0);
+    let html_body = load_branding(&state.pool).await.email_html(
+        "Payment Reminder",
+        &format!(
+            r#"<p>Hi {},</p>
+            <p>Our invoice for your purchase order {} was due on {} and still has an open balance.</p>
+            <p>Order ID: {}<br>Invoice total: {}<br>Paid: {}</p>
+            <p class="total">Balance due: {}</p>
+            <p><a href="{}">Download the invoice (PDF)</a></p>
+            <p>If you have already sent payment, thank you, and please disregard this reminder.</p>"#,
+            escape_html(order.customer_name.as_deref().unwrap_or("there")),
+            escape_html(order.po_number.as_deref().unwrap_or_default()),
+            format_due_date(order),
+            order.id,
+            format_money(order.total_amount),
+            format_money(paid),
+            format_money(balance),
+            escape_html(&invoice_url(order))
+        ),
+    );
+    let subject = format!("Payment reminder: PO {} is overdue", order.po_number.as_deref().unwrap_or_default());
+    queue_email(&state.pool, email, &subject, &html_body, EmailPriority::Immediate).await?;
+    sqlx::query!("UPDATE orders SET payment_reminder_sent_at = NOW() WHERE id = $1", order.id)
+        .execute(&*state.pool)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+    Ok(())
+}
+
+async fn send_reminder_handler(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let order = load_purchase_order(&app_state.pool, order_id).await?;
+    if order.status != OrderStatus::AwaitingPayment {
+        return Err((StatusCode::CONFLICT, format!("Order is {}", order.status)));
+    }
+    send_overdue_reminder(&app_state, &order)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    tracing::info!("{} sent a payment reminder for order {}", admin.username, order_id);
+    Ok(StatusCode::ACCEPTED)
+}
+
+// Remind customers about overdue invoices, again every INVOICE_REMINDER_INTERVAL_DAYS
+async fn send_due_reminders(state: &AppState) -> Result<(), sqlx::Error> {
+    let interval_days = app_config::get().checkout.invoice_reminder_interval_days as i32;
+    let overdue = order_query!(
+        "SELECT",
+        "FROM orders
+         WHERE status = 'awaiting_payment' AND payment_method = $1 AND livemode = $2
+           AND payment_due_at < NOW() AND customer_email IS NOT NULL
+           AND (payment_reminder_sent_at IS NULL OR payment_reminder_sent_at <= NOW() - make_interval(days => $3::INT))",
+        PAYMENT_METHOD,
+        state.mode.is_live(),
+        interval_days,
+    )
+    .fetch_all(&*state.pool)
+    .await?;
+
+    for order in overdue {
+        match send_overdue_reminder(state, &order).await {
+            Ok(()) => state.ops.notify(format!(
+                ":receipt: Invoice for PO {} (order {}, {}) was due {}; reminder sent to {}",
+                order.po_number.as_deref().unwrap_or_default(),
+                order.id,
+                format_money(order.total_amount),
+                format_due_date(&order),
+                order.customer_email.as_deref().unwrap_or_default()
+            )),
+            Err(e) => tracing::error!("Failed to send payment reminder for order {}: {}", order.id, e),
+        }
+    }
+    Ok(())
+}
+
+// Background task that sends overdue invoice reminders (checked every ALERT_CHECK_INTERVAL_SECS)
+pub fn spawn_invoice_reminders(state: Arc<AppState>) {
+    let interval_secs = app_config::get().schedules.alert_check_interval_secs;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            if let Err(e) = send_due_reminders(&state).await {
+                tracing::error!("Failed to check overdue invoices: {}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,656 @@
+// Quotes Module - Quote/estimate workflow for B2B customers
+// A customer requests a quote for a cart, priced like checkout. Admins adjust quantities and
+// prices (each change is kept as a revision) and send it; the email carries a view link and
+// an accept link valid for QUOTE_VALID_DAYS. Accepting converts the quote into a pending
+// manual order and redirects the customer to its Stripe payment link
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Redirect,
+    routing::{get, post},
+    Json, Router,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::branding::{escape_html, load_branding};
+use crate::cart_pricing::{price_cart, CartPrice, CartPriceRequest, PricedLine};
+use crate::customer_auth::AuthenticatedCustomer;
+use crate::manual_orders::{self, NewManualOrder};
+use crate::notifications::{queue_email, EmailPriority};
+use crate::pii::Encrypted;
+use crate::pricing::CartLine;
+use crate::AppState;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Quote {
+    pub id: Uuid,
+    pub customer_id: Option<Uuid>,
+    pub customer_email: Encrypted,
+    pub customer_name: Option<String>,
+    pub company: Option<String>,
+    pub message: Option<String>,
+    pub status: String, // requested, sent, accepted, expired, cancelled
+    pub revision: i32,
+    pub subtotal: i64,
+    pub discount: i64,
+    pub tax: i64,
+    pub shipping: i64,
+    pub total: i64,
+    pub currency: String,
+    #[serde(skip_serializing)]
+    pub accept_token_hash: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub sent_by: Option<String>,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub order_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct QuoteItem {
+    pub product_id: Option<i32>,
+    pub product_name: String,
+    pub quantity: i32,
+    pub list_price: i64, // Store price when requested, in cents
+    pub unit_price: i64, // Quoted price, in cents
+    pub line_total: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct QuoteRevision {
+    pub revision: i32,
+    pub items: serde_json::Value,
+    pub subtotal: i64,
+    pub discount: i64,
+    pub tax: i64,
+    pub shipping: i64,
+    pub total: i64,
+    pub changed_by: Option<String>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct QuoteDetail {
+    #[serde(flatten)]
+    pub quote: Quote,
+    pub items: Vec<QuoteItem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revisions: Option<Vec<QuoteRevision>>, // Admins only
+}
+
+#[derive(Deserialize)]
+pub struct QuoteRequest {
+    #[serde(flatten)]
+    pub cart: CartPriceRequest,
+    pub customer_email: String,
+    pub customer_name: Option<String>,
+    pub company: Option<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct QuoteLineInput {
+    pub product_id: i32,
+    pub quantity: i32,
+    pub unit_price: Option<i64>, // in cents; defaults to the current store price
+}
+
+#[derive(Deserialize)]
+pub struct QuoteUpdate {
+    pub items: Vec<QuoteLineInput>,
+    pub discount: Option<i64>, // in cents; kept when omitted
+    pub shipping: Option<i64>, // in cents; kept when omitted
+    pub note: Option<String>,  // Why the quote changed, kept with the revision
+}
+
+#[derive(Deserialize, Default)]
+pub struct SendQuoteRequest {
+    pub valid_days: Option<i64>, // Defaults to QUOTE_VALID_DAYS
+}
+
+#[derive(Deserialize)]
+pub struct QuoteListQuery {
+    pub status: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct QuoteTokenQuery {
+    pub token: String,
+}
+
+pub fn quote_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/quotes", post(request_quote))
+        .route("/api/quotes/:id", get(view_quote))
+        .route("/api/quotes/:id/accept", get(accept_quote))
+        .route("/api/admin/quotes", get(list_quotes))
+        .route("/api/admin/quotes/:id", get(get_quote).put(update_quote))
+        .route("/api/admin/quotes/:id/send", post(send_quote))
+        .route("/api/admin/quotes/:id/cancel", post(cancel_quote))
+        .with_state(app_state)
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// Sent quotes past their expiry can no longer be accepted
+async fn expire_quotes(pool: &sqlx::PgPool) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query!(
+        "UPDATE quotes SET status = 'expired', updated_at = NOW() WHERE status = 'sent' AND expires_at <= NOW()",
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+async fn load_quote(pool: &sqlx::PgPool, id: Uuid) -> Result<Quote, (StatusCode, String)> {
+    sqlx::query_as!(Quote, r#"SELECT id, customer_id, customer_email AS "customer_email: Encrypted", customer_name, company, message, status, revision, subtotal, discount, tax, shipping, total, currency, expires_at, sent_by, sent_at, accepted_at, order_id, created_at, updated_at, accept_token_hash FROM quotes WHERE id = $1"#, id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Quote not found".to_string()))
+}
+
+async fn quote_items(pool: &sqlx::PgPool, quote_id: Uuid) -> Result<Vec<QuoteItem>, sqlx::Error> {
+    sqlx::query_as!(
+        QuoteItem,
+        "SELECT product_id, product_name, quantity, list_price, unit_price, line_total
+         FROM quote_items WHERE quote_id = $1 ORDER BY id",
+        quote_id,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// The quote an emailed token opens. Links from before the last revision stop working
+async fn load_with_token(pool: &sqlx::PgPool, id: Uuid, token: &str) -> Result<Quote, (StatusCode, String)> {
+    let quote = load_quote(pool, id).await?;
+    match &quote.accept_token_hash {
+        Some(hash) if *hash == hash_token(token) => Ok(quote),
+        _ => Err((StatusCode::NOT_FOUND, "Quote not found".to_string())),
+    }
+}
+
+// Replace the quote's lines and totals, and record them as a new revision
+async fn write_revision(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    quote: &Quote,
+    items: &[QuoteItem],
+    changed_by: Option<&str>,
+    note: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM quote_items WHERE quote_id = $1", quote.id)
+        .execute(&mut **tx)
+        .await?;
+    for item in items {
+        sqlx::query!(
+            "INSERT INTO quote_items (quote_id, product_id, product_name, quantity, list_price, unit_price, line_total)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            quote.id,
+            item.product_id,
+            &item.product_name,
+            item.quantity,
+            item.list_price,
+            item.unit_price,
+            item.line_total,
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+    sqlx::query!(
+        "INSERT INTO quote_revisions (quote_id, revision, items, subtotal, discount, tax, shipping, total, changed_by, note)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+        quote.id,
+        quote.revision,
+        serde_json::to_value(items).unwrap_or_default(),
+        quote.subtotal,
+        quote.discount,
+        quote.tax,
+        quote.shipping,
+        quote.total,
+        changed_by,
+        note,
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+// Customer asks for a quote on a cart; prices start at what checkout would charge
+async fn request_quote(
+    customer: Option<AuthenticatedCustomer>,
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<QuoteRequest>,
+) -> Result<(StatusCode, Json<QuoteDetail>), (StatusCode, String)> {
+    let email = request.customer_email.trim().to_lowercase();
+    if !email.contains('@') {
+        return Err((StatusCode::BAD_REQUEST, "A valid customer_email is required".to_string()));
+    }
+    let price = price_cart(&app_state.pool, &request.cart, customer.as_ref().map(|c| c.customer_id))
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let items: Vec<QuoteItem> = price
+        .lines
+        .iter()
+        .map(|line| QuoteItem {
+            product_id: Some(line.product_id),
+            product_name: line.name.clone(),
+            quantity: line.quantity,
+            list_price: line.unit_price,
+            unit_price: line.unit_price,
+            line_total: line.line_total,
+        })
+        .collect();
+
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let mut tx = app_state.pool.begin().await.map_err(db_error)?;
+    let quote = sqlx::query_as!(
+        Quote,
+        r#"INSERT INTO quotes (customer_id, customer_email, customer_name, company, message,
+                             subtotal, discount, tax, shipping, total, currency)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+         RETURNING id, customer_id, customer_email AS "customer_email: Encrypted", customer_name, company, message, status, revision, subtotal, discount, tax, shipping, total, currency, expires_at, sent_by, sent_at, accepted_at, order_id, created_at, updated_at, accept_token_hash"#,
+        customer.map(|c| c.customer_id),
+        Encrypted::from(email.as_str()) as _,
+        request.customer_name.as_deref().map(str::trim).filter(|n| !n.is_empty()),
+        request.company.as_deref().map(str::trim).filter(|c| !c.is_empty()),
+        request.message.as_deref().map(str::trim).filter(|m| !m.is_empty()),
+        price.subtotal,
+        price.discount,
+        price.tax,
+        price.shipping,
+        price.total,
+        price.currency.to_uppercase(),
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    write_revision(&mut tx, &quote, &items, None, None).await.map_err(db_error)?;
+    tx.commit().await.map_err(db_error)?;
+
+    app_state.ops.notify(format!(
+        "New quote request {} from {}{} ({} items, ${:.2})",
+        quote.id,
+        email,
+        quote.company.as_deref().map(|c| format!(" at {}", c)).unwrap_or_default(),
+        items.len(),
+        quote.total as f64 / 100.0
+    ));
+    Ok((StatusCode::CREATED, Json(QuoteDetail { quote, items, revisions: None })))
+}
+
+// The quote behind an emailed link
+async fn view_quote(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<QuoteTokenQuery>,
+) -> Result<Json<QuoteDetail>, (StatusCode, String)> {
+    expire_quotes(&app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let quote = load_with_token(&app_state.pool, id, &query.token).await?;
+    let items = quote_items(&app_state.pool, id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(QuoteDetail { quote, items, revisions: None }))
+}
+
+// Accept link: convert the quote into a pending order and send the customer to pay it.
+// Opening the link again goes back to the same order
+async fn accept_quote(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<QuoteTokenQuery>,
+) -> Result<Redirect, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    expire_quotes(&app_state.pool).await.map_err(db_error)?;
+    let quote = load_with_token(&app_state.pool, id, &query.token).await?;
+    let storefront_url = app_config::get().server.storefront_url.trim_end_matches('/').to_string();
+
+    if quote.status == "accepted" {
+        let order_id = quote.order_id.ok_or((StatusCode::CONFLICT, "Quote has no order".to_string()))?;
+        let order: Option<(String, Option<String>)> =
+            sqlx::query!("SELECT status, payment_link_url FROM orders WHERE id = $1", order_id)
+                .map(|r| (r.status, r.payment_link_url))
+                .fetch_optional(&*app_state.pool)
+                .await
+                .map_err(db_error)?;
+        return Ok(match order {
+            Some((status, Some(url))) if status == "pending" => Redirect::to(&url),
+            _ => Redirect::to(&format!("{}/orders/{}", storefront_url, order_id)),
+        });
+    }
+    if quote.status != "sent" {
+        return Err((StatusCode::GONE, format!("This quote is {}", quote.status)));
+    }
+
+    // Claim the quote first so a double click can't create two orders
+    let claimed = sqlx::query!(
+        "UPDATE quotes SET status = 'accepted', accepted_at = NOW(), updated_at = NOW() WHERE id = $1 AND status = 'sent'",
+        id,
+    )
+    .execute(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    if claimed.rows_affected() == 0 {
+        return Err((StatusCode::CONFLICT, "Quote is already being accepted".to_string()));
+    }
+
+    match convert_to_order(&app_state, &quote).await {
+        Ok(url) => Ok(Redirect::to(&url)),
+        Err(e) => {
+            sqlx::query!(
+                "UPDATE quotes SET status = 'sent', accepted_at = NULL WHERE id = $1 AND order_id IS NULL",
+                id,
+            )
+            .execute(&*app_state.pool)
+            .await
+            .ok();
+            Err(e)
+        }
+    }
+}
+
+// Create the quote's pending order at the quoted prices and email its payment link
+async fn convert_to_order(state: &AppState, quote: &Quote) -> Result<String, (StatusCode, String)> {
+    let items = quote_items(&state.pool, quote.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let mut lines = Vec::new();
+    for item in &items {
+        let product_id = item.product_id.ok_or((
+            StatusCode::CONFLICT,
+            format!("{} is no longer available; ask us for a new quote", item.product_name),
+        ))?;
+        lines.push(PricedLine {
+            product_id,
+            name: item.product_name.clone(),
+            quantity: item.quantity,
+            unit_price: item.unit_price,
+            line_total: item.line_total,
+            ship_to: None,
+        });
+    }
+    let price = CartPrice {
+        lines,
+        subtotal: quote.subtotal,
+        discount: quote.discount,
+        coupon_code: None,
+        coupon_amount_off: None,
+        tax_rate: app_config::get().checkout.tax_rate,
+        tax: quote.tax,
+        shipping: quote.shipping,
+        shipping_method: None,
+        shipments: Vec::new(),
+        requires_shipping: true, // Shipping was agreed on the quote
+        total: quote.total,
+        currency: quote.currency.to_lowercase(),
+    };
+
+    let new_order = NewManualOrder {
+        customer_email: Some(quote.customer_email.to_string()),
+        customer_name: quote.customer_name.clone(),
+        payment_method: "quote",
+        payment_reference: None,
+        created_by: quote.sent_by.as_deref(),
+        customer_id: None,
+    };
+    let order = manual_orders::create_from_price(state, &price, new_order).await?;
+    sqlx::query!("UPDATE quotes SET order_id = $1, updated_at = NOW() WHERE id = $2", order.id, quote.id)
+        .execute(&*state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    state.ops.notify(format!(
+        "Quote {} accepted by {}: order {} (${:.2})",
+        quote.id,
+        quote.customer_email,
+        order.id,
+        quote.total as f64 / 100.0
+    ));
+    manual_orders::send_payment_link(state, &order).await
+}
+
+async fn list_quotes(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<QuoteListQuery>,
+) -> Result<Json<Vec<Quote>>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    expire_quotes(&app_state.pool).await.map_err(db_error)?;
+    let quotes = sqlx::query_as!(
+        Quote,
+        r#"SELECT id, customer_id, customer_email AS "customer_email: Encrypted", customer_name, company, message, status, revision, subtotal, discount, tax, shipping, total, currency, expires_at, sent_by, sent_at, accepted_at, order_id, created_at, updated_at, accept_token_hash FROM quotes WHERE ($1::VARCHAR IS NULL OR status = $1) ORDER BY created_at DESC"#,
+        query.status,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    Ok(Json(quotes))
+}
+
+async fn get_quote(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<QuoteDetail>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    expire_quotes(&app_state.pool).await.map_err(db_error)?;
+    let quote = load_quote(&app_state.pool, id).await?;
+    let items = quote_items(&app_state.pool, id).await.map_err(db_error)?;
+    let revisions = sqlx::query_as!(
+        QuoteRevision,
+        "SELECT revision, items, subtotal, discount, tax, shipping, total, changed_by, note, created_at
+         FROM quote_revisions WHERE quote_id = $1 ORDER BY revision",
+        id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    Ok(Json(QuoteDetail { quote, items, revisions: Some(revisions) }))
+}
+
+// Adjust lines and prices. A sent quote goes back to 'requested' and its links stop working
+// until it is sent again
+async fn update_quote(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(update): Json<QuoteUpdate>,
+) -> Result<Json<QuoteDetail>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    expire_quotes(&app_state.pool).await.map_err(db_error)?;
+    let quote = load_quote(&app_state.pool, id).await?;
+    if !["requested", "sent", "expired"].contains(&quote.status.as_str()) {
+        return Err((StatusCode::CONFLICT, format!("Quote is {}", quote.status)));
+    }
+    if update.items.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "A quote needs at least one item".to_string()));
+    }
+
+    // List prices come from what checkout would charge today
+    let cart = CartPriceRequest {
+        items: update
+            .items
+            .iter()
+            .map(|i| CartLine {
+                product_id: i.product_id,
+                quantity: i.quantity,
+                ship_to: None,
+            })
+            .collect(),
+        address_book: Vec::new(),
+        to_address: None,
+        shipping_method_id: None,
+        coupon_code: None,
+    };
+    let list = price_cart(&app_state.pool, &cart, None)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let mut items = Vec::new();
+    for (input, line) in update.items.iter().zip(&list.lines) {
+        let unit_price = input.unit_price.unwrap_or(line.unit_price);
+        if unit_price < 0 {
+            return Err((StatusCode::BAD_REQUEST, format!("Invalid unit_price for product {}", input.product_id)));
+        }
+        items.push(QuoteItem {
+            product_id: Some(line.product_id),
+            product_name: line.name.clone(),
+            quantity: line.quantity,
+            list_price: line.unit_price,
+            unit_price,
+            line_total: unit_price * line.quantity as i64,
+        });
+    }
+
+    let subtotal: i64 = items.iter().map(|i| i.line_total).sum();
+    let discount = update.discount.unwrap_or(quote.discount);
+    let shipping = update.shipping.unwrap_or(quote.shipping);
+    if discount < 0 || discount > subtotal || shipping < 0 {
+        return Err((StatusCode::BAD_REQUEST, "discount must be between 0 and the subtotal, shipping at least 0".to_string()));
+    }
+    let tax = ((subtotal - discount) as f64 * app_config::get().checkout.tax_rate).round() as i64;
+    let total = subtotal - discount + tax + shipping;
+
+    let mut tx = app_state.pool.begin().await.map_err(db_error)?;
+    let quote = sqlx::query_as!(
+        Quote,
+        r#"UPDATE quotes
+         SET revision = revision + 1, subtotal = $2, discount = $3, tax = $4, shipping = $5, total = $6,
+             status = 'requested', accept_token_hash = NULL, expires_at = NULL, updated_at = NOW()
+         WHERE id = $1
+         RETURNING id, customer_id, customer_email AS "customer_email: Encrypted", customer_name, company, message, status, revision, subtotal, discount, tax, shipping, total, currency, expires_at, sent_by, sent_at, accepted_at, order_id, created_at, updated_at, accept_token_hash"#,
+        id,
+        subtotal,
+        discount,
+        tax,
+        shipping,
+        total,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    let note = update.note.as_deref().map(str::trim).filter(|n| !n.is_empty());
+    write_revision(&mut tx, &quote, &items, Some(&admin.username), note)
+        .await
+        .map_err(db_error)?;
+    tx.commit().await.map_err(db_error)?;
+
+    get_quote(admin, State(app_state), Path(id)).await
+}
+
+// Email the quote with fresh view and accept links
+async fn send_quote(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    body: Option<Json<SendQuoteRequest>>,
+) -> Result<Json<Quote>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    expire_quotes(&app_state.pool).await.map_err(db_error)?;
+    let quote = load_quote(&app_state.pool, id).await?;
+    if !["requested", "sent", "expired"].contains(&quote.status.as_str()) {
+        return Err((StatusCode::CONFLICT, format!("Quote is {}", quote.status)));
+    }
+    let request = body.map(|Json(r)| r).unwrap_or_default();
+    let valid_days = request
+        .valid_days
+        .unwrap_or(app_config::get().checkout.quote_valid_days as i64);
+    if !(1..=365).contains(&valid_days) {
+        return Err((StatusCode::BAD_REQUEST, "valid_days must be between 1 and 365".to_string()));
+    }
+
+    let token_bytes: [u8; 32] = rand::thread_rng().gen();
+    let token = hex::encode(token_bytes);
+    let quote = sqlx::query_as!(
+        Quote,
+        r#"UPDATE quotes
+         SET status = 'sent', accept_token_hash = $2, expires_at = NOW() + make_interval(days => $3::INT),
+             sent_by = $4, sent_at = NOW(), updated_at = NOW()
+         WHERE id = $1
+         RETURNING id, customer_id, customer_email AS "customer_email: Encrypted", customer_name, company, message, status, revision, subtotal, discount, tax, shipping, total, currency, expires_at, sent_by, sent_at, accepted_at, order_id, created_at, updated_at, accept_token_hash"#,
+        id,
+        hash_token(&token),
+        valid_days as i32,
+        &admin.username,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    let items = quote_items(&app_state.pool, id).await.map_err(db_error)?;
+
+    let api_url = app_config::get().server.public_api_url.trim_end_matches('/').to_string();
+    let accept_url = format!("{}/api/quotes/{}/accept?token={}", api_url, quote.id, token);
+    let rows: String = items
+        .iter()
+        .map(|item| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>${:.2}</td><td>${:.2}</td></tr>",
+                escape_html(&item.product_name),
+                item.quantity,
+                item.unit_price as f64 / 100.0,
+                item.line_total as f64 / 100.0
+            )
+        })
+        .collect();
+    let expires = quote.expires_at.map(|d| d.format("%B %-d, %Y").to_string()).unwrap_or_default();
+    let html_body = load_branding(&app_state.pool).await.email_html(
+        "Your Quote",
+        &format!(
+            r#"<p>Hi {},</p>
+            <p>Here is your quote (revision {}):</p>
+            <table><thead><tr><th>Item</th><th>Quantity</th><th>Unit price</th><th>Total</th></tr></thead><tbody>{}</tbody></table>
+            <p>Subtotal: ${:.2}<br>Discount: ${:.2}<br>Tax: ${:.2}<br>Shipping: ${:.2}</p>
+            <p class="total">Total: ${:.2}</p>
+            <p><a href="{}">Accept and pay</a></p>
+            <p>This quote is valid until {}.</p>"#,
+            escape_html(quote.customer_name.as_deref().unwrap_or("there")),
+            quote.revision,
+            rows,
+            quote.subtotal as f64 / 100.0,
+            quote.discount as f64 / 100.0,
+            quote.tax as f64 / 100.0,
+            quote.shipping as f64 / 100.0,
+            quote.total as f64 / 100.0,
+            escape_html(&accept_url),
+            expires
+        ),
+    );
+    queue_email(&app_state.pool, &quote.customer_email, "Your quote", &html_body, EmailPriority::Immediate)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    tracing::info!("{} sent quote {} to {}", admin.username, quote.id, quote.customer_email);
+    Ok(Json(quote))
+}
+
+async fn cancel_quote(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Quote>, (StatusCode, String)> {
+    sqlx::query_as!(
+        Quote,
+        r#"UPDATE quotes SET status = 'cancelled', updated_at = NOW()
+         WHERE id = $1 AND status IN ('requested', 'sent', 'expired')
+         RETURNING id, customer_id, customer_email AS "customer_email: Encrypted", customer_name, company, message, status, revision, subtotal, discount, tax, shipping, total, currency, expires_at, sent_by, sent_at, accepted_at, order_id, created_at, updated_at, accept_token_hash"#,
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::CONFLICT, "Only open quotes can be cancelled".to_string()))
+    .map(Json)
+}
@@ -0,0 +1,673 @@
+// Reconciliation Module - Provider payouts matched against orders
+// Pulls Stripe payouts with their balance transactions and Square payouts with their entries,
+// stores what each movement grossed, cost in fees and netted, and links it to the order it
+// paid for. The admin report and CSV export surface provider payments no order accounts for
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use chrono::Duration;
+use sqlx::types::chrono::{DateTime, NaiveDate, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+use stripe::{
+    BalanceTransaction, BalanceTransactionSourceUnion, Expandable, ListBalanceTransactions, ListPayouts, Payout,
+    RangeQuery,
+};
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::environment_mode;
+use crate::provider_breaker;
+use crate::request_id::WithRequestId;
+use crate::route_limits::{self, RouteGroup};
+use crate::square_payments::AmountMoney;
+use crate::AppState;
+
+const DEFAULT_SYNC_DAYS: i64 = 30;
+const DEFAULT_REPORT_DAYS: i64 = 30;
+const STRIPE_PAGE_SIZE: u64 = 100;
+const SQUARE_API_VERSION: &str = "2025-05-21";
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ProviderTransaction {
+    pub provider: String,
+    pub transaction_id: String,
+    pub payout_id: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub payment_id: Option<String>,
+    pub gross_amount: i64,
+    pub fee_amount: i64,
+    pub net_amount: i64,
+    pub currency: String,
+    pub order_id: Option<Uuid>,
+    pub occurred_at: Option<DateTime<Utc>>,
+}
+
+// A payout with the totals of the movements it settled
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct PayoutSummary {
+    pub provider: String,
+    pub payout_id: String,
+    pub amount: i64,
+    pub currency: String,
+    pub status: String,
+    pub arrival_date: Option<DateTime<Utc>>,
+    pub gross_amount: i64,
+    pub fee_amount: i64,
+    pub net_amount: i64,
+    pub transactions: i64,
+    pub unmatched: i64,
+}
+
+// A paid order with no provider movement settling it yet
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct UnsettledOrder {
+    pub id: Uuid,
+    pub payment_provider: String,
+    pub payment_id: String,
+    pub payment_intent_id: Option<String>,
+    pub total_amount: i64,
+    pub currency: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReconciliationTotals {
+    pub payout_amount: i64,
+    pub gross_amount: i64,
+    pub fee_amount: i64,
+    pub net_amount: i64,
+}
+
+#[derive(Serialize)]
+pub struct ReconciliationReport {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub totals: ReconciliationTotals,
+    pub payouts: Vec<PayoutSummary>,
+    pub unmatched_payments: Vec<ProviderTransaction>,
+    pub unsettled_orders: Vec<UnsettledOrder>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SyncSummary {
+    pub payouts: usize,
+    pub transactions: usize,
+    pub matched: u64,
+}
+
+#[derive(Deserialize)]
+pub struct ReportQuery {
+    pub from: Option<DateTime<Utc>>, // Payout arrival window; defaults to the last 30 days
+    pub to: Option<DateTime<Utc>>,
+    pub provider: Option<String>,    // "stripe" or "square"; both when omitted
+    pub mode: Option<String>,        // live, test or all unsettled orders; defaults to the server's mode
+}
+
+#[derive(Deserialize)]
+pub struct SyncQuery {
+    pub days: Option<i64>, // How far back to pull payouts (defaults to 30)
+}
+
+// --- Square payout API shapes ---
+
+#[derive(Deserialize)]
+struct SquarePayoutList {
+    payouts: Option<Vec<SquarePayout>>,
+    cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SquarePayout {
+    id: String,
+    status: String,
+    amount_money: AmountMoney,
+    arrival_date: Option<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+struct SquarePayoutEntryList {
+    payout_entries: Option<Vec<SquarePayoutEntry>>,
+    cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SquarePayoutEntry {
+    id: String,
+    #[serde(rename = "type")]
+    type_: String,
+    effective_at: Option<DateTime<Utc>>,
+    gross_amount_money: Option<AmountMoney>,
+    net_amount_money: AmountMoney,
+    type_charge_details: Option<SquarePaymentRef>,
+    type_refund_details: Option<SquarePaymentRef>,
+}
+
+#[derive(Deserialize)]
+struct SquarePaymentRef {
+    payment_id: Option<String>,
+}
+
+struct NewPayout<'a> {
+    provider: &'a str,
+    payout_id: &'a str,
+    amount: i64,
+    currency: &'a str,
+    status: &'a str,
+    arrival_date: Option<DateTime<Utc>>,
+}
+
+struct NewTransaction<'a> {
+    provider: &'a str,
+    transaction_id: &'a str,
+    payout_id: &'a str,
+    type_: &'a str,
+    payment_id: Option<String>,
+    gross_amount: i64,
+    fee_amount: i64,
+    net_amount: i64,
+    currency: &'a str,
+    occurred_at: Option<DateTime<Utc>>,
+}
+
+async fn upsert_payout(pool: &sqlx::PgPool, payout: NewPayout<'_>) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO provider_payouts (provider, payout_id, amount, currency, status, arrival_date)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (provider, payout_id) DO UPDATE
+         SET amount = EXCLUDED.amount, status = EXCLUDED.status, arrival_date = EXCLUDED.arrival_date, synced_at = NOW()",
+        payout.provider,
+        payout.payout_id,
+        payout.amount,
+        payout.currency.to_uppercase(),
+        payout.status,
+        payout.arrival_date,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn upsert_transaction(pool: &sqlx::PgPool, tx: NewTransaction<'_>) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO provider_transactions
+            (provider, transaction_id, payout_id, type, payment_id, gross_amount, fee_amount, net_amount, currency, occurred_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+         ON CONFLICT (provider, transaction_id) DO UPDATE
+         SET payout_id = EXCLUDED.payout_id, payment_id = EXCLUDED.payment_id, gross_amount = EXCLUDED.gross_amount,
+             fee_amount = EXCLUDED.fee_amount, net_amount = EXCLUDED.net_amount, synced_at = NOW()",
+        tx.provider,
+        tx.transaction_id,
+        tx.payout_id,
+        tx.type_,
+        tx.payment_id,
+        tx.gross_amount,
+        tx.fee_amount,
+        tx.net_amount,
+        tx.currency.to_uppercase(),
+        tx.occurred_at,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// The payment a balance transaction belongs to: its charge's payment intent (orders store
+// that), else the charge itself
+fn stripe_payment_id(source: Option<&Expandable<BalanceTransactionSourceUnion>>) -> Option<String> {
+    let Expandable::Object(source) = source? else {
+        return None;
+    };
+    match source.as_ref() {
+        BalanceTransactionSourceUnion::Charge(charge) => Some(
+            charge
+                .payment_intent
+                .as_ref()
+                .map(|pi| pi.id().to_string())
+                .unwrap_or_else(|| charge.id.to_string()),
+        ),
+        BalanceTransactionSourceUnion::Refund(refund) => refund
+            .payment_intent
+            .as_ref()
+            .map(|pi| pi.id().to_string())
+            .or_else(|| refund.charge.as_ref().map(|c| c.id().to_string())),
+        BalanceTransactionSourceUnion::Dispute(dispute) => Some(
+            dispute
+                .payment_intent
+                .as_ref()
+                .map(|pi| pi.id().to_string())
+                .unwrap_or_else(|| dispute.charge.id().to_string()),
+        ),
+        _ => None,
+    }
+}
+
+fn from_timestamp(ts: i64) -> Option<DateTime<Utc>> {
+    DateTime::<Utc>::from_timestamp(ts, 0)
+}
+
+async fn sync_stripe(state: &AppState, since: DateTime<Utc>, summary: &mut SyncSummary) -> Result<(), String> {
+    let mut payouts = Vec::new();
+    let mut params = ListPayouts::new();
+    params.created = Some(RangeQuery::gte(since.timestamp()));
+    params.limit = Some(STRIPE_PAGE_SIZE);
+    loop {
+        let page = provider_breaker::stripe(Payout::list(&state.stripe_client, &params))
+            .await
+            .map_err(|e| format!("Stripe error: {}", e))?;
+        params.starting_after = page.data.last().map(|p| p.id.clone());
+        let has_more = page.has_more;
+        payouts.extend(page.data);
+        if !has_more {
+            break;
+        }
+    }
+
+    for payout in &payouts {
+        upsert_payout(
+            &state.pool,
+            NewPayout {
+                provider: "stripe",
+                payout_id: payout.id.as_str(),
+                amount: payout.amount,
+                currency: &payout.currency.to_string(),
+                status: &payout.status,
+                arrival_date: from_timestamp(payout.arrival_date),
+            },
+        )
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+        summary.payouts += 1;
+
+        let expand = ["data.source"];
+        let mut params = ListBalanceTransactions::new();
+        params.payout = Some(payout.id.clone());
+        params.expand = &expand;
+        params.limit = Some(STRIPE_PAGE_SIZE);
+        loop {
+            let page = provider_breaker::stripe(BalanceTransaction::list(&state.stripe_client, &params))
+                .await
+                .map_err(|e| format!("Stripe error: {}", e))?;
+            for bt in &page.data {
+                let type_ = bt.type_.as_str();
+                if type_ == "payout" {
+                    continue; // The payout itself; its amount is on the payout row
+                }
+                upsert_transaction(
+                    &state.pool,
+                    NewTransaction {
+                        provider: "stripe",
+                        transaction_id: bt.id.as_str(),
+                        payout_id: payout.id.as_str(),
+                        type_,
+                        payment_id: stripe_payment_id(bt.source.as_ref()),
+                        gross_amount: bt.amount,
+                        fee_amount: bt.fee,
+                        net_amount: bt.net,
+                        currency: &bt.currency.to_string(),
+                        occurred_at: from_timestamp(bt.created),
+                    },
+                )
+                .await
+                .map_err(|e| format!("DB error: {}", e))?;
+                summary.transactions += 1;
+            }
+            params.starting_after = page.data.last().map(|bt| bt.id.clone());
+            if !page.has_more {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn square_get<T: serde::de::DeserializeOwned>(
+    square: &crate::square_payments::SquareClient,
+    path: &str,
+    query: &[(&str, String)],
+) -> Result<T, String> {
+    let response = square
+        .client
+        .get(format!("{}{}", square.base_url, path))
+        .header("Authorization", format!("Bearer {}", square.access_token))
+        .header("Square-Version", SQUARE_API_VERSION)
+        .query(query)
+        .with_request_id()
+        .send()
+        .await
+        .map_err(|e| format!("Square API request failed: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("Square API error {}: {}", status, body));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Square response: {}", e))
+}
+
+async fn sync_square(state: &AppState, since: DateTime<Utc>, summary: &mut SyncSummary) -> Result<(), String> {
+    let Some(square) = state.square_client() else {
+        return Ok(()); // Square not configured
+    };
+
+    let mut payouts = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut query = vec![
+            ("location_id", state.square_location_id()),
+            ("begin_time", since.to_rfc3339()),
+        ];
+        if let Some(c) = &cursor {
+            query.push(("cursor", c.clone()));
+        }
+        let page: SquarePayoutList = square_get(&square, "/v2/payouts", &query).await?;
+        payouts.extend(page.payouts.unwrap_or_default());
+        cursor = page.cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+
+    for payout in &payouts {
+        upsert_payout(
+            &state.pool,
+            NewPayout {
+                provider: "square",
+                payout_id: &payout.id,
+                amount: payout.amount_money.amount,
+                currency: &payout.amount_money.currency,
+                status: &payout.status.to_lowercase(),
+                arrival_date: payout.arrival_date.and_then(|d| d.and_hms_opt(0, 0, 0)).map(|d| d.and_utc()),
+            },
+        )
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+        summary.payouts += 1;
+
+        let path = format!("/v2/payouts/{}/payout-entries", payout.id);
+        let mut cursor: Option<String> = None;
+        loop {
+            let query: Vec<(&str, String)> = cursor.iter().map(|c| ("cursor", c.clone())).collect();
+            let page: SquarePayoutEntryList = square_get(&square, &path, &query).await?;
+            for entry in page.payout_entries.unwrap_or_default() {
+                let net = entry.net_amount_money.amount;
+                let gross = entry.gross_amount_money.as_ref().map(|m| m.amount).unwrap_or(net);
+                let payment_id = entry
+                    .type_charge_details
+                    .or(entry.type_refund_details)
+                    .and_then(|d| d.payment_id);
+                upsert_transaction(
+                    &state.pool,
+                    NewTransaction {
+                        provider: "square",
+                        transaction_id: &entry.id,
+                        payout_id: &payout.id,
+                        type_: &entry.type_.to_lowercase(),
+                        payment_id,
+                        gross_amount: gross,
+                        fee_amount: gross - net, // Square reports fees with varying signs; derive them
+                        net_amount: net,
+                        currency: &entry.net_amount_money.currency,
+                        occurred_at: entry.effective_at,
+                    },
+                )
+                .await
+                .map_err(|e| format!("DB error: {}", e))?;
+                summary.transactions += 1;
+            }
+            cursor = page.cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Link unmatched movements to the orders their payments created
+pub async fn match_orders(pool: &sqlx::PgPool) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query!(
+        "UPDATE provider_transactions t SET order_id = o.id
+         FROM orders o
+         WHERE t.order_id IS NULL AND t.payment_id IS NOT NULL
+           AND (o.payment_provider = t.provider OR (o.payment_provider = 'manual' AND t.provider = 'stripe'))
+           AND (o.payment_intent_id = t.payment_id OR o.payment_id = t.payment_id)",
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+// Pull payouts from the last `days` days from every configured provider, then match them
+pub async fn sync(state: &AppState, days: i64) -> Result<SyncSummary, String> {
+    let since = Utc::now() - Duration::days(days);
+    let mut summary = SyncSummary::default();
+    sync_stripe(state, since, &mut summary).await?;
+    sync_square(state, since, &mut summary).await?;
+    summary.matched = match_orders(&state.pool)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+    Ok(summary)
+}
+
+// Background task that pulls recent payouts (every RECONCILIATION_SYNC_INTERVAL_SECS)
+pub fn spawn_reconciliation_sync(state: Arc<AppState>) {
+    let interval_secs = app_config::get().schedules.reconciliation_sync_interval_secs;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            match sync(&state, DEFAULT_SYNC_DAYS).await {
+                Ok(summary) => tracing::info!(
+                    "Reconciliation sync: {} payout(s), {} transaction(s), {} newly matched",
+                    summary.payouts, summary.transactions, summary.matched
+                ),
+                Err(e) => tracing::error!("Reconciliation sync failed: {}", e),
+            }
+        }
+    });
+}
+
+pub fn reconciliation_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/reconciliation", get(report).layer(route_limits::limit(RouteGroup::Reports)))
+        .route("/api/admin/reconciliation/export", get(export_csv).layer(route_limits::limit(RouteGroup::Reports)))
+        .route("/api/admin/reconciliation/sync", post(sync_now))
+        .with_state(app_state)
+}
+
+fn report_window(query: &ReportQuery) -> Result<(DateTime<Utc>, DateTime<Utc>), (StatusCode, String)> {
+    if let Some(provider) = &query.provider {
+        if provider != "stripe" && provider != "square" {
+            return Err((StatusCode::BAD_REQUEST, "provider must be stripe or square".to_string()));
+        }
+    }
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - Duration::days(DEFAULT_REPORT_DAYS));
+    if from > to {
+        return Err((StatusCode::BAD_REQUEST, "from must be before to".to_string()));
+    }
+    Ok((from, to))
+}
+
+// Movements of payouts arriving in the window, unmatched payments first
+async fn window_transactions(
+    pool: &sqlx::PgPool,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    provider: Option<&str>,
+    unmatched_only: bool,
+) -> Result<Vec<ProviderTransaction>, sqlx::Error> {
+    sqlx::query_as!(
+        ProviderTransaction,
+        "SELECT t.provider, t.transaction_id, t.payout_id, t.type AS type_, t.payment_id, t.gross_amount, t.fee_amount, t.net_amount, t.currency, t.order_id, t.occurred_at FROM provider_transactions t
+         JOIN provider_payouts p ON p.provider = t.provider AND p.payout_id = t.payout_id
+         WHERE p.arrival_date >= $1 AND p.arrival_date < $2
+           AND ($3::VARCHAR IS NULL OR t.provider = $3)
+           AND (NOT $4 OR (t.order_id IS NULL AND t.gross_amount > 0))
+         ORDER BY (t.order_id IS NULL AND t.gross_amount > 0) DESC, t.occurred_at",
+        from,
+        to,
+        provider,
+        unmatched_only,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+async fn report(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Json<ReconciliationReport>, (StatusCode, String)> {
+    let (from, to) = report_window(&query)?;
+    let provider = query.provider.as_deref();
+    let livemode = environment_mode::livemode_filter(query.mode.as_deref(), app_state.mode)?;
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+
+    let payouts = sqlx::query_as!(
+        PayoutSummary,
+        r#"SELECT p.provider, p.payout_id, p.amount, p.currency, p.status, p.arrival_date,
+                COALESCE(SUM(t.gross_amount), 0)::BIGINT AS "gross_amount!",
+                COALESCE(SUM(t.fee_amount), 0)::BIGINT AS "fee_amount!",
+                COALESCE(SUM(t.net_amount), 0)::BIGINT AS "net_amount!",
+                COUNT(t.transaction_id) AS "transactions!",
+                COUNT(t.transaction_id) FILTER (WHERE t.order_id IS NULL AND t.gross_amount > 0) AS "unmatched!"
+         FROM provider_payouts p
+         LEFT JOIN provider_transactions t ON t.provider = p.provider AND t.payout_id = p.payout_id
+         WHERE p.arrival_date >= $1 AND p.arrival_date < $2 AND ($3::VARCHAR IS NULL OR p.provider = $3)
+         GROUP BY p.provider, p.payout_id
+         ORDER BY p.arrival_date DESC"#,
+        from,
+        to,
+        provider,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+
+    let unmatched_payments = window_transactions(&app_state.pool, from, to, provider, true)
+        .await
+        .map_err(db_error)?;
+
+    // Recent orders may simply not have been paid out yet
+    let unsettled_orders = sqlx::query_as!(
+        UnsettledOrder,
+        "SELECT o.id, o.payment_provider, o.payment_id, o.payment_intent_id, o.total_amount, o.currency, o.created_at
+         FROM orders o
+         WHERE o.status IN ('completed', 'refunded') AND o.created_at >= $1 AND o.created_at < $2
+           AND ($3::VARCHAR IS NULL OR o.payment_provider = $3)
+           AND ($4::BOOLEAN IS NULL OR o.livemode = $4)
+           AND NOT EXISTS (SELECT 1 FROM provider_transactions t WHERE t.order_id = o.id)
+         ORDER BY o.created_at",
+        from,
+        to,
+        provider,
+        livemode,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+
+    let totals = payouts.iter().fold(ReconciliationTotals::default(), |mut totals, p| {
+        totals.payout_amount += p.amount;
+        totals.gross_amount += p.gross_amount;
+        totals.fee_amount += p.fee_amount;
+        totals.net_amount += p.net_amount;
+        totals
+    });
+
+    Ok(Json(ReconciliationReport {
+        from,
+        to,
+        totals,
+        payouts,
+        unmatched_payments,
+        unsettled_orders,
+    }))
+}
+
+// One row of the reconciliation CSV export
+#[derive(Serialize)]
+struct ReconciliationCsvRow {
+    matched: bool,
+    provider: String,
+    payout_id: String,
+    transaction_id: String,
+    #[serde(rename = "type")]
+    type_: String,
+    payment_id: Option<String>,
+    order_id: Option<Uuid>,
+    gross_amount: i64,
+    fee_amount: i64,
+    net_amount: i64,
+    currency: String,
+    occurred_at: Option<DateTime<Utc>>,
+}
+
+// Every movement in the window as CSV, unmatched payments first
+async fn export_csv(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ReportQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let (from, to) = report_window(&query)?;
+    let transactions = window_transactions(&app_state.pool, from, to, query.provider.as_deref(), false)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for t in transactions {
+        writer
+            .serialize(ReconciliationCsvRow {
+                // Fees, payouts and adjustments have no order to match
+                matched: t.order_id.is_some() || t.gross_amount <= 0,
+                provider: t.provider,
+                payout_id: t.payout_id,
+                transaction_id: t.transaction_id,
+                type_: t.type_,
+                payment_id: t.payment_id,
+                order_id: t.order_id,
+                gross_amount: t.gross_amount,
+                fee_amount: t.fee_amount,
+                net_amount: t.net_amount,
+                currency: t.currency,
+                occurred_at: t.occurred_at,
+            })
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("CSV error: {}", e)))?;
+    }
+    let body = writer
+        .into_inner()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("CSV error: {}", e)))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"reconciliation.csv\""),
+        ],
+        body,
+    ))
+}
+
+// Pull payouts now instead of waiting for the background sync
+async fn sync_now(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<SyncQuery>,
+) -> Result<Json<SyncSummary>, (StatusCode, String)> {
+    let days = query.days.unwrap_or(DEFAULT_SYNC_DAYS).clamp(1, 365);
+    sync(&app_state, days)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e))
+}
@@ -0,0 +1,342 @@
+// Referrals Module - Customer referral program
+// Each customer gets a referral code; /r/:code records the visit, sets an attribution
+// cookie and sends the friend to the storefront. The code travels with the payment
+// intent, and the friend's first completed order earns the referrer store credit or a coupon.
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::app_config;
+use crate::branding::load_branding;
+use crate::coupons;
+use crate::customer_auth::AuthenticatedCustomer;
+use crate::lettre_email::EmailConfig;
+use crate::notifications::{queue_email, EmailPriority};
+use crate::pii::Encrypted;
+use crate::store_credit;
+use crate::AppState;
+
+pub const REFERRAL_COOKIE: &str = "rcom_ref";
+pub const METADATA_KEY: &str = "referral_code";
+const COOKIE_MAX_AGE_SECS: i64 = 30 * 24 * 3600;
+const REWARD_COUPON_VALID_DAYS: i64 = 180;
+
+// How referrers are rewarded (REFERRAL_REWARD_TYPE)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RewardType {
+    StoreCredit,
+    Coupon,
+}
+
+impl RewardType {
+    fn from_env() -> Self {
+        match app_config::get().rewards.referral_reward_type.as_str() {
+            "store_credit" => RewardType::StoreCredit,
+            _ => RewardType::Coupon,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            RewardType::StoreCredit => "store_credit",
+            RewardType::Coupon => "coupon",
+        }
+    }
+}
+
+fn reward_amount() -> i64 {
+    app_config::get().rewards.referral_reward_amount
+}
+
+// A referred friend as shown to the referrer (no personal details)
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ReferralReward {
+    pub status: String,
+    pub reward_type: Option<String>,
+    pub reward_amount: Option<i64>, // in cents
+    pub coupon_code: Option<String>,
+    pub coupon_redeemed: bool,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReferralStats {
+    pub referral_code: String,
+    pub referral_link: String,
+    pub visits: i64,
+    pub successful_referrals: i64,
+    pub total_rewards: i64, // in cents
+    pub rewards: Vec<ReferralReward>,
+}
+
+// Get the customer's referral code, generating one on first use
+pub async fn ensure_referral_code(pool: &sqlx::PgPool, customer_id: Uuid) -> Result<String, sqlx::Error> {
+    let existing: Option<String> = sqlx::query_scalar!(
+        "SELECT referral_code FROM customers WHERE id = $1",
+        customer_id,
+    )
+    .fetch_one(pool)
+    .await?;
+    if let Some(code) = existing {
+        return Ok(code);
+    }
+
+    // Retry on the (unlikely) unique collision
+    let mut attempts = 0;
+    loop {
+        let candidate = coupons::generate_code("R").replace('-', "");
+        let res = sqlx::query_scalar!(
+            r#"UPDATE customers SET referral_code = COALESCE(referral_code, $1), updated_at = NOW()
+             WHERE id = $2 RETURNING referral_code AS "referral_code!""#,
+            &candidate,
+            customer_id,
+        )
+        .fetch_one(pool)
+        .await;
+        match res {
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() && attempts < 5 => attempts += 1,
+            other => return other,
+        }
+    }
+}
+
+// Referral code from the attribution cookie set by /r/:code
+pub fn code_from_cookies(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == REFERRAL_COOKIE)
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+// Record a referred order and reward the referrer if it's the friend's first order.
+// Called by the payment webhooks once the order exists.
+pub async fn record_referred_order(pool: &sqlx::PgPool, order_id: Uuid, code: &str) -> Result<(), String> {
+    let db_err = |e: sqlx::Error| format!("DB error: {}", e);
+
+    let referrer: Option<(Uuid, Encrypted, Option<String>)> = sqlx::query!(
+        r#"SELECT id, email AS "email: Encrypted", name FROM customers WHERE referral_code = UPPER($1) AND disabled = FALSE"#,
+        code.trim(),
+    )
+    .map(|r| (r.id, r.email, r.name))
+    .fetch_optional(pool)
+    .await
+    .map_err(db_err)?;
+    let (referrer_id, referrer_email, referrer_name) = match referrer {
+        Some(r) => r,
+        None => return Ok(()),
+    };
+
+    let customer_id: Option<Uuid> = sqlx::query_scalar!("SELECT customer_id FROM orders WHERE id = $1", order_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(db_err)?
+        .flatten();
+    let customer_id = match customer_id {
+        Some(id) if id != referrer_id => id, // No self-referrals
+        _ => return Ok(()),
+    };
+
+    let completed_orders: i64 =
+        sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "completed_orders!" FROM orders WHERE customer_id = $1 AND status = 'completed'"#,
+            customer_id,
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(db_err)?;
+    if completed_orders > 1 {
+        sqlx::query!(
+            "INSERT INTO referrals (referrer_id, referred_customer_id, order_id, status)
+             VALUES ($1, $2, $3, 'ineligible') ON CONFLICT (referred_customer_id) DO NOTHING",
+            referrer_id,
+            customer_id,
+            order_id,
+        )
+        .execute(pool)
+        .await
+        .map_err(db_err)?;
+        return Ok(());
+    }
+
+    let reward_type = RewardType::from_env();
+    let amount = reward_amount();
+    let mut tx = pool.begin().await.map_err(db_err)?;
+    let inserted: Option<Uuid> = sqlx::query_scalar!(
+        "INSERT INTO referrals (referrer_id, referred_customer_id, order_id, status, reward_type, reward_amount, rewarded_at)
+         VALUES ($1, $2, $3, 'rewarded', $4, $5, NOW())
+         ON CONFLICT (referred_customer_id) DO NOTHING RETURNING id",
+        referrer_id,
+        customer_id,
+        order_id,
+        reward_type.as_str(),
+        amount,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_err)?;
+    let referral_id = match inserted {
+        Some(id) => id,
+        None => return Ok(()), // Friend was already referred
+    };
+
+    let coupon = match reward_type {
+        RewardType::Coupon => {
+            let expires_at = Utc::now() + chrono::Duration::days(REWARD_COUPON_VALID_DAYS);
+            let coupon = coupons::issue_coupon(&mut *tx, "REF", amount, Some(referrer_id), "referral", Some(expires_at))
+                .await
+                .map_err(db_err)?;
+            sqlx::query!("UPDATE referrals SET coupon_id = $1 WHERE id = $2", coupon.id, referral_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_err)?;
+            Some(coupon)
+        }
+        RewardType::StoreCredit => {
+            store_credit::add_entry(
+                &mut *tx,
+                referrer_id,
+                amount,
+                store_credit::CreditReason::Referral,
+                Some("Referral reward"),
+                Some(order_id),
+                None,
+            )
+            .await
+            .map_err(db_err)?;
+            None
+        }
+    };
+    tx.commit().await.map_err(db_err)?;
+
+    tracing::info!(
+        "Referral reward ({}, {} cents) issued to customer {} for order {}",
+        reward_type.as_str(),
+        amount,
+        referrer_id,
+        order_id
+    );
+
+    // Let the referrer know; the reward stands even if the email fails
+    if EmailConfig::from_env().is_some() {
+        let reward_html = match &coupon {
+            Some(c) => format!("Here's your ${:.2} coupon for your next order: <strong>{}</strong>", amount as f64 / 100.0, c.code),
+            None => format!("We've added ${:.2} in store credit to your account.", amount as f64 / 100.0),
+        };
+        let html_body = load_branding(pool).await.email_html(
+            "You earned a referral reward!",
+            &format!(
+                "<p>Hi{},</p><p>A friend you referred just placed their first order. Thank you!</p><p>{}</p>",
+                referrer_name.as_ref().map(|n| format!(" {}", n)).unwrap_or_default(),
+                reward_html
+            ),
+        );
+        if let Err(e) = queue_email(pool, &referrer_email, "You earned a referral reward!", &html_body, EmailPriority::Batched).await {
+            tracing::error!("Failed to queue referral reward email to {}: {}", referrer_email, e);
+        }
+    }
+    Ok(())
+}
+
+pub fn referral_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/r/:code", get(track_referral))
+        .route("/api/customers/me/referrals", get(get_referral_stats))
+        .with_state(app_state)
+}
+
+// Referral link: count the visit, remember the code in a cookie and go to the storefront
+async fn track_referral(
+    State(app_state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let redirect = Redirect::to(app_config::get().server.storefront_url.trim_end_matches('/'));
+
+    let referrer_id: Option<Uuid> =
+        sqlx::query_scalar!(
+            "SELECT id FROM customers WHERE referral_code = UPPER($1) AND disabled = FALSE",
+            code.trim(),
+        )
+        .fetch_optional(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    // Unknown codes still land on the storefront, just without attribution
+    let referrer_id = match referrer_id {
+        Some(id) => id,
+        None => return Ok((HeaderMap::new(), redirect)),
+    };
+
+    sqlx::query!("INSERT INTO referral_visits (referrer_id) VALUES ($1)", referrer_id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    let cookie = format!(
+        "{}={}; Max-Age={}; Path=/; HttpOnly; SameSite=Lax",
+        REFERRAL_COOKIE,
+        code.trim().to_uppercase(),
+        COOKIE_MAX_AGE_SECS
+    );
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::SET_COOKIE,
+        cookie.parse().map_err(|_| (StatusCode::BAD_REQUEST, "Invalid referral code".to_string()))?,
+    );
+    Ok((headers, redirect))
+}
+
+async fn get_referral_stats(
+    customer: AuthenticatedCustomer,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<ReferralStats>, (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let referral_code = ensure_referral_code(&app_state.pool, customer.customer_id)
+        .await
+        .map_err(db_err)?;
+
+    let visits: i64 = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "visits!" FROM referral_visits WHERE referrer_id = $1"#,
+        customer.customer_id,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(db_err)?;
+
+    let rewards = sqlx::query_as!(
+        ReferralReward,
+        r#"SELECT r.status, r.reward_type, r.reward_amount, c.code AS "coupon_code?",
+                (c.redeemed_at IS NOT NULL) AS "coupon_redeemed!", r.created_at
+         FROM referrals r
+         LEFT JOIN coupons c ON c.id = r.coupon_id
+         WHERE r.referrer_id = $1
+         ORDER BY r.created_at DESC"#,
+        customer.customer_id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(db_err)?;
+
+    let api_url = &app_config::get().server.public_api_url;
+    Ok(Json(ReferralStats {
+        referral_link: format!("{}/r/{}", api_url.trim_end_matches('/'), referral_code),
+        referral_code,
+        visits,
+        successful_referrals: rewards.iter().filter(|r| r.status == "rewarded").count() as i64,
+        total_rewards: rewards.iter().filter_map(|r| r.reward_amount).sum(),
+        rewards,
+    }))
+}
@@ -0,0 +1,75 @@
+// In-memory repos for handler tests: the rows a test puts in are all there is, and no query
+// reaches the database
+
+use async_trait::async_trait;
+use futures_util::stream::{self, BoxStream};
+use sqlx::types::Uuid;
+use std::sync::Mutex;
+
+use crate::admin_orders::OrderDetail;
+use crate::webhooks::{Order, OrderItem};
+
+use super::{OrderExportFilter, OrderListFilter, OrderRepo};
+
+#[derive(Default)]
+pub struct MemoryOrderRepo {
+    orders: Mutex<Vec<Order>>,
+    items: Mutex<Vec<OrderItem>>,
+}
+
+impl MemoryOrderRepo {
+    pub fn insert(&self, order: Order, items: Vec<OrderItem>) {
+        self.orders.lock().unwrap().push(order);
+        self.items.lock().unwrap().extend(items);
+    }
+
+    fn items_of(&self, order_id: Uuid) -> Vec<OrderItem> {
+        self.items.lock().unwrap().iter().filter(|i| i.order_id == order_id).cloned().collect()
+    }
+}
+
+#[async_trait]
+impl OrderRepo for MemoryOrderRepo {
+    async fn find(&self, id: Uuid) -> Result<Option<Order>, sqlx::Error> {
+        Ok(self.orders.lock().unwrap().iter().find(|o| o.id == id).cloned())
+    }
+
+    async fn items(&self, order_id: Uuid) -> Result<Vec<OrderItem>, sqlx::Error> {
+        Ok(self.items_of(order_id))
+    }
+
+    async fn list_with_items(&self, filter: &OrderListFilter) -> Result<Vec<OrderDetail>, sqlx::Error> {
+        let mut orders: Vec<Order> = self
+            .orders
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|o| filter.customer_id.is_none() || o.customer_id == filter.customer_id)
+            .filter(|o| filter.status.as_deref().is_none_or(|s| o.status.to_string() == s))
+            .filter(|o| filter.before.is_none_or(|before| (o.created_at, o.id) < before))
+            .cloned()
+            .collect();
+        orders.sort_by_key(|o| std::cmp::Reverse((o.created_at, o.id)));
+        orders.truncate(filter.limit.map_or(usize::MAX, |l| l as usize));
+        Ok(orders
+            .into_iter()
+            .map(|order| OrderDetail { items: self.items_of(order.id), order })
+            .collect())
+    }
+
+    fn stream<'a>(&'a self, filter: &'a OrderExportFilter) -> BoxStream<'a, Result<Order, sqlx::Error>> {
+        let mut orders: Vec<Order> = self
+            .orders
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|o| filter.customer_id.is_none() || o.customer_id == filter.customer_id)
+            .filter(|o| filter.status.as_deref().is_none_or(|s| o.status.to_string() == s))
+            .filter(|o| filter.from.is_none_or(|from| o.created_at >= from))
+            .filter(|o| filter.to.is_none_or(|to| o.created_at < to))
+            .cloned()
+            .collect();
+        orders.sort_by_key(|o| (o.created_at, o.id));
+        Box::pin(stream::iter(orders.into_iter().map(Ok)))
+    }
+}
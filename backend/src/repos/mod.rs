@@ -0,0 +1,37 @@
+// Repos Module - Typed queries for products, orders and webhook events behind traits
+// Handlers reach these tables through `AppState::repos` instead of writing SQL inline, so each
+// query lives in one place to tune and a handler can be exercised against an in-memory
+// implementation of the trait. The Postgres implementations run on the shared pool; writes
+// that must commit together with others still take an executor or run in a unit of work
+// (see unit_of_work), since a repo call is its own statement
+
+use std::sync::Arc;
+
+mod orders;
+mod products;
+mod webhook_events;
+#[cfg(test)]
+pub mod memory;
+
+pub(crate) use orders::order_query;
+pub use orders::{order_items, OrderExportFilter, OrderListFilter, OrderRepo, PgOrderRepo};
+pub(crate) use products::product_query;
+pub use products::{NewProduct, PgProductRepo, ProductRepo, ProductUpdate};
+pub use webhook_events::{PgWebhookRepo, WebhookEventFilter, WebhookRepo};
+
+#[derive(Clone)]
+pub struct Repos {
+    pub products: Arc<dyn ProductRepo>,
+    pub orders: Arc<dyn OrderRepo>,
+    pub webhook_events: Arc<dyn WebhookRepo>,
+}
+
+impl Repos {
+    pub fn postgres(pool: Arc<sqlx::PgPool>) -> Self {
+        Self {
+            products: Arc::new(PgProductRepo::new(pool.clone())),
+            orders: Arc::new(PgOrderRepo::new(pool.clone())),
+            webhook_events: Arc::new(PgWebhookRepo::new(pool)),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+// Order queries: an order and its items, as shown to admins and on invoices
+// Lists load each order's items in the same query, aggregated to JSON, rather than one items
+// query per order
+
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use serde::Deserialize;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::{Json, Uuid};
+use std::sync::Arc;
+
+use crate::admin_orders::OrderDetail;
+use crate::pii::Encrypted;
+use crate::webhooks::{Order, OrderItem, OrderStatus};
+
+// query_as! for Order rows, like product_query!: the columns go between `$before` and `$after`,
+// with customer_email decrypted, status as OrderStatus, and created_at and updated_at (nullable
+// in the schema but always filled from their defaults) as plain timestamps
+macro_rules! order_query {
+    ($before:literal, $after:literal $(, $arg:expr)* $(,)?) => {
+        sqlx::query_as!(
+            $crate::webhooks::Order,
+            $before
+                + " id, payment_provider, payment_id, payment_intent_id,
+                    customer_email AS \"customer_email: crate::pii::Encrypted\", customer_name,
+                    total_amount, currency, status AS \"status: crate::webhooks::OrderStatus\", webhook_event_id,
+                    created_at AS \"created_at!\", updated_at AS \"updated_at!\",
+                    gift_wrap, gift_message, customer_id, cart_id,
+                    authorization_expires_at, amount_captured, captured_at, livemode,
+                    payment_method, created_by, payment_link_url, payment_link_id,
+                    po_number, payment_due_at, paid_at, payment_reminder_sent_at "
+                + $after
+            $(, $arg)*
+        )
+    };
+}
+pub(crate) use order_query;
+
+// Which orders to list, newest first (by created_at, then id). Unset fields don't filter
+#[derive(Debug, Default)]
+pub struct OrderListFilter {
+    pub customer_id: Option<Uuid>,
+    pub status: Option<String>,
+    pub before: Option<(DateTime<Utc>, Uuid)>, // Only orders after this one in the list (a page cursor)
+    pub limit: Option<i64>, // All matching orders when unset
+}
+
+// Which orders to export, oldest first. Unset fields don't filter
+#[derive(Debug, Default, Deserialize)]
+pub struct OrderExportFilter {
+    pub status: Option<String>,
+    pub customer_id: Option<Uuid>,
+    pub from: Option<DateTime<Utc>>, // created_at at or after
+    pub to: Option<DateTime<Utc>>,   // created_at before
+}
+
+#[async_trait]
+pub trait OrderRepo: Send + Sync {
+    async fn find(&self, id: Uuid) -> Result<Option<Order>, sqlx::Error>;
+
+    // In the order they were added
+    async fn items(&self, order_id: Uuid) -> Result<Vec<OrderItem>, sqlx::Error>;
+
+    // Orders with their items, in one round trip
+    async fn list_with_items(&self, filter: &OrderListFilter) -> Result<Vec<OrderDetail>, sqlx::Error>;
+
+    // Matching orders without items, read as the stream is polled (for exports)
+    fn stream<'a>(&'a self, filter: &'a OrderExportFilter) -> BoxStream<'a, Result<Order, sqlx::Error>>;
+}
+
+
+pub struct PgOrderRepo {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl PgOrderRepo {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl OrderRepo for PgOrderRepo {
+    async fn find(&self, id: Uuid) -> Result<Option<Order>, sqlx::Error> {
+        order_query!("SELECT", "FROM orders WHERE id = $1", id)
+            .fetch_optional(&*self.pool)
+            .await
+    }
+
+    async fn items(&self, order_id: Uuid) -> Result<Vec<OrderItem>, sqlx::Error> {
+        order_items(&self.pool, order_id).await
+    }
+
+    fn stream<'a>(&'a self, filter: &'a OrderExportFilter) -> BoxStream<'a, Result<Order, sqlx::Error>> {
+        order_query!(
+            "SELECT",
+            "FROM orders
+             WHERE ($1::TEXT IS NULL OR status = $1)
+               AND ($2::UUID IS NULL OR customer_id = $2)
+               AND ($3::TIMESTAMPTZ IS NULL OR created_at >= $3)
+               AND ($4::TIMESTAMPTZ IS NULL OR created_at < $4)
+             ORDER BY created_at, id",
+            filter.status,
+            filter.customer_id,
+            filter.from,
+            filter.to,
+        )
+        .fetch(&*self.pool)
+    }
+
+    async fn list_with_items(&self, filter: &OrderListFilter) -> Result<Vec<OrderDetail>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT o.id, o.payment_provider, o.payment_id, o.payment_intent_id, o.customer_email AS "customer_email: Encrypted", o.customer_name,
+                   o.total_amount, o.currency, o.status AS "status: OrderStatus", o.webhook_event_id,
+                   o.created_at AS "created_at!", o.updated_at AS "updated_at!",
+                   o.gift_wrap, o.gift_message, o.customer_id, o.cart_id,
+                   o.authorization_expires_at, o.amount_captured, o.captured_at, o.livemode,
+                   o.payment_method, o.created_by, o.payment_link_url, o.payment_link_id,
+                   o.po_number, o.payment_due_at, o.paid_at, o.payment_reminder_sent_at,
+                   COALESCE(
+                       (SELECT json_agg(oi ORDER BY oi.created_at, oi.id) FROM order_items oi WHERE oi.order_id = o.id),
+                       '[]'
+                   ) AS "items!: Json<Vec<OrderItem>>"
+            FROM orders o
+            WHERE ($1::UUID IS NULL OR o.customer_id = $1)
+              AND ($2::TEXT IS NULL OR o.status = $2)
+              AND ($3::TIMESTAMPTZ IS NULL OR (o.created_at, o.id) < ($3, $4))
+            ORDER BY o.created_at DESC, o.id DESC
+            LIMIT $5
+            "#,
+            filter.customer_id,
+            filter.status,
+            filter.before.map(|(created_at, _)| created_at),
+            filter.before.map(|(_, id)| id),
+            filter.limit,
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| OrderDetail {
+                order: Order {
+                    id: r.id,
+                    payment_provider: r.payment_provider,
+                    payment_id: r.payment_id,
+                    payment_intent_id: r.payment_intent_id,
+                    customer_email: r.customer_email,
+                    customer_name: r.customer_name,
+                    total_amount: r.total_amount,
+                    currency: r.currency,
+                    status: r.status,
+                    webhook_event_id: r.webhook_event_id,
+                    created_at: r.created_at,
+                    updated_at: r.updated_at,
+                    gift_wrap: r.gift_wrap,
+                    gift_message: r.gift_message,
+                    customer_id: r.customer_id,
+                    cart_id: r.cart_id,
+                    authorization_expires_at: r.authorization_expires_at,
+                    amount_captured: r.amount_captured,
+                    captured_at: r.captured_at,
+                    livemode: r.livemode,
+                    payment_method: r.payment_method,
+                    created_by: r.created_by,
+                    payment_link_url: r.payment_link_url,
+                    payment_link_id: r.payment_link_id,
+                    po_number: r.po_number,
+                    payment_due_at: r.payment_due_at,
+                    paid_at: r.paid_at,
+                    payment_reminder_sent_at: r.payment_reminder_sent_at,
+                },
+                items: r.items.0,
+            })
+            .collect())
+    }
+}
+
+// The items query, for the order emails sent from code that holds only a pool
+pub async fn order_items(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Vec<OrderItem>, sqlx::Error> {
+    sqlx::query_as!(
+        OrderItem,
+        r#"
+        SELECT id, order_id, product_id, product_name, product_description,
+               quantity, unit_price, total_price, created_at AS "created_at!"
+        FROM order_items WHERE order_id = $1 ORDER BY created_at, id
+        "#,
+        order_id,
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,343 @@
+// Product queries: catalog reads for the storefront and admin, and admin writes
+
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use sqlx::types::chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::admin_products::{Product, ProductInput, ProductStatus};
+
+// query_as! for Product rows, for queries here and ones that must run in a transaction. The
+// column list is spelled out between `$before` and `$after` (query! takes no `*` with overrides):
+// status decodes as ProductStatus, and created_at, which the schema leaves nullable but always
+// fills from its default, as a plain timestamp
+macro_rules! product_query {
+    ($before:literal, $after:literal $(, $arg:expr)* $(,)?) => {
+        sqlx::query_as!(
+            $crate::admin_products::Product,
+            $before
+                + " id, name, description, price, inventory, created_at AS \"created_at!\",
+                    status AS \"status: crate::admin_products::ProductStatus\", publish_at, sku, barcode,
+                    weight_oz, length_in, width_in, height_in, hs_code, country_of_origin, domestic_only,
+                    image_url, brand, category, slug "
+                + $after
+            $(, $arg)*
+        )
+    };
+}
+pub(crate) use product_query;
+
+#[async_trait]
+pub trait ProductRepo: Send + Sync {
+    // Every product whatever its status, in id order
+    async fn list(&self) -> Result<Vec<Product>, sqlx::Error>;
+
+    // Every product in id order, read as the stream is polled (for exports)
+    fn stream_all(&self) -> BoxStream<'_, Result<Product, sqlx::Error>>;
+
+    // Products the storefront shows, in id order
+    async fn list_published(&self) -> Result<Vec<Product>, sqlx::Error>;
+
+    // A page in id order: at most `limit` products with ids above `after`, only published ones
+    // for the storefront
+    async fn list_after(&self, published_only: bool, after: Option<i32>, limit: i64) -> Result<Vec<Product>, sqlx::Error>;
+
+    async fn find(&self, id: i32) -> Result<Option<Product>, sqlx::Error>;
+
+    // Exact match on the stored (trimmed) code
+    async fn find_by_sku(&self, sku: &str) -> Result<Option<Product>, sqlx::Error>;
+
+    async fn find_by_barcode(&self, barcode: &str) -> Result<Option<Product>, sqlx::Error>;
+
+    // Products with any of these SKUs or ids, read in one query (imports)
+    async fn find_by_skus_or_ids(&self, skus: &[String], ids: &[i32]) -> Result<Vec<Product>, sqlx::Error>;
+
+    // None for drafts and scheduled products as well as missing ones
+    async fn find_published(&self, id: i32) -> Result<Option<Product>, sqlx::Error>;
+
+    // `input` is stored as given; callers normalize it (see ProductInput::normalized)
+    async fn create(&self, input: &ProductInput, slug: &str) -> Result<Product, sqlx::Error>;
+
+    // Create several products in one statement (see bulk_insert). All or none are created
+    async fn create_many(&self, products: &[NewProduct]) -> Result<Vec<Product>, sqlx::Error>;
+
+    // Leaves status, publish_at and domestic_only alone when the input omits them
+    async fn update(&self, id: i32, input: &ProductInput, slug: &str) -> Result<Option<Product>, sqlx::Error>;
+
+    // `update` for several products in one statement (see bulk_insert). All or none are updated;
+    // products that no longer exist are left out of the result
+    async fn update_many(&self, products: &[ProductUpdate]) -> Result<Vec<Product>, sqlx::Error>;
+
+    async fn set_status(
+        &self,
+        id: i32,
+        status: ProductStatus,
+        publish_at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Product>, sqlx::Error>;
+
+    // Whether the product existed
+    async fn delete(&self, id: i32) -> Result<bool, sqlx::Error>;
+}
+
+// A product to create with its slug, already made unique
+pub struct NewProduct {
+    pub input: ProductInput,
+    pub slug: String,
+}
+
+// A saved product to overwrite with `input` and its slug, already made unique
+pub struct ProductUpdate {
+    pub id: i32,
+    pub input: ProductInput,
+    pub slug: String,
+}
+
+pub struct PgProductRepo {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl PgProductRepo {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ProductRepo for PgProductRepo {
+    async fn list(&self) -> Result<Vec<Product>, sqlx::Error> {
+        product_query!("SELECT", "FROM products ORDER BY id")
+            .fetch_all(&*self.pool)
+            .await
+    }
+
+    fn stream_all(&self) -> BoxStream<'_, Result<Product, sqlx::Error>> {
+        product_query!("SELECT", "FROM products ORDER BY id").fetch(&*self.pool)
+    }
+
+    async fn list_published(&self) -> Result<Vec<Product>, sqlx::Error> {
+        product_query!("SELECT", "FROM products WHERE status = 'published' ORDER BY id")
+            .fetch_all(&*self.pool)
+            .await
+    }
+
+    async fn list_after(&self, published_only: bool, after: Option<i32>, limit: i64) -> Result<Vec<Product>, sqlx::Error> {
+        product_query!(
+            "SELECT",
+            "FROM products
+             WHERE (NOT $1 OR status = 'published') AND ($2::INT4 IS NULL OR id > $2)
+             ORDER BY id
+             LIMIT $3",
+            published_only,
+            after,
+            limit,
+        )
+        .fetch_all(&*self.pool)
+        .await
+    }
+
+    async fn find(&self, id: i32) -> Result<Option<Product>, sqlx::Error> {
+        product_query!("SELECT", "FROM products WHERE id = $1", id)
+            .fetch_optional(&*self.pool)
+            .await
+    }
+
+    async fn find_by_sku(&self, sku: &str) -> Result<Option<Product>, sqlx::Error> {
+        product_query!("SELECT", "FROM products WHERE sku = $1", sku)
+            .fetch_optional(&*self.pool)
+            .await
+    }
+
+    async fn find_by_barcode(&self, barcode: &str) -> Result<Option<Product>, sqlx::Error> {
+        product_query!("SELECT", "FROM products WHERE barcode = $1", barcode)
+            .fetch_optional(&*self.pool)
+            .await
+    }
+
+    async fn find_by_skus_or_ids(&self, skus: &[String], ids: &[i32]) -> Result<Vec<Product>, sqlx::Error> {
+        product_query!("SELECT", "FROM products WHERE sku = ANY($1) OR id = ANY($2) ORDER BY id", skus, ids)
+            .fetch_all(&*self.pool)
+            .await
+    }
+
+    async fn find_published(&self, id: i32) -> Result<Option<Product>, sqlx::Error> {
+        product_query!("SELECT", "FROM products WHERE id = $1 AND status = 'published'", id)
+            .fetch_optional(&*self.pool)
+            .await
+    }
+
+    async fn create(&self, input: &ProductInput, slug: &str) -> Result<Product, sqlx::Error> {
+        product_query!(
+            "INSERT INTO products (name, description, price, inventory, status, publish_at, sku, barcode,
+                                   weight_oz, length_in, width_in, height_in, hs_code, country_of_origin, domestic_only,
+                                   image_url, brand, category, slug)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19) RETURNING",
+            "",
+            input.name,
+            input.description,
+            input.price,
+            input.inventory,
+            input.status.unwrap_or(ProductStatus::Published) as _,
+            input.publish_at,
+            input.sku,
+            input.barcode,
+            input.weight_oz,
+            input.length_in,
+            input.width_in,
+            input.height_in,
+            input.hs_code,
+            input.country_of_origin,
+            input.domestic_only.unwrap_or(false),
+            input.image_url,
+            input.brand,
+            input.category,
+            slug,
+        )
+        .fetch_one(&*self.pool)
+        .await
+    }
+
+    async fn create_many(&self, products: &[NewProduct]) -> Result<Vec<Product>, sqlx::Error> {
+        // One array per column; the macro only checks arrays without NULLs, so nullable ones are `as _`
+        let column = |f: fn(&NewProduct) -> Option<String>| products.iter().map(f).collect::<Vec<_>>();
+        let measure = |f: fn(&ProductInput) -> Option<f64>| products.iter().map(|p| f(&p.input)).collect::<Vec<_>>();
+        product_query!(
+            "INSERT INTO products (name, description, price, inventory, status, publish_at, sku, barcode,
+                                   weight_oz, length_in, width_in, height_in, hs_code, country_of_origin, domestic_only,
+                                   image_url, brand, category, slug)
+             SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[], $3::FLOAT8[], $4::INT4[], $5::VARCHAR[], $6::TIMESTAMPTZ[],
+                                  $7::VARCHAR[], $8::VARCHAR[], $9::FLOAT8[], $10::FLOAT8[], $11::FLOAT8[], $12::FLOAT8[],
+                                  $13::VARCHAR[], $14::VARCHAR[], $15::BOOL[], $16::TEXT[], $17::VARCHAR[], $18::VARCHAR[],
+                                  $19::VARCHAR[])
+             RETURNING",
+            "",
+            &products.iter().map(|p| p.input.name.clone()).collect::<Vec<_>>(),
+            &column(|p| p.input.description.clone()) as _,
+            &products.iter().map(|p| p.input.price).collect::<Vec<_>>(),
+            &products.iter().map(|p| p.input.inventory).collect::<Vec<_>>(),
+            &products.iter().map(|p| p.input.status.unwrap_or(ProductStatus::Published)).collect::<Vec<_>>() as _,
+            &products.iter().map(|p| p.input.publish_at).collect::<Vec<_>>() as _,
+            &column(|p| p.input.sku.clone()) as _,
+            &column(|p| p.input.barcode.clone()) as _,
+            &measure(|i| i.weight_oz) as _,
+            &measure(|i| i.length_in) as _,
+            &measure(|i| i.width_in) as _,
+            &measure(|i| i.height_in) as _,
+            &column(|p| p.input.hs_code.clone()) as _,
+            &column(|p| p.input.country_of_origin.clone()) as _,
+            &products.iter().map(|p| p.input.domestic_only.unwrap_or(false)).collect::<Vec<_>>(),
+            &column(|p| p.input.image_url.clone()) as _,
+            &column(|p| p.input.brand.clone()) as _,
+            &column(|p| p.input.category.clone()) as _,
+            &products.iter().map(|p| p.slug.clone()).collect::<Vec<_>>(),
+        )
+        .fetch_all(&*self.pool)
+        .await
+    }
+
+    async fn update(&self, id: i32, input: &ProductInput, slug: &str) -> Result<Option<Product>, sqlx::Error> {
+        product_query!(
+            "UPDATE products SET name = $1, description = $2, price = $3, inventory = $4,
+             status = COALESCE($5, status), publish_at = COALESCE($6, publish_at),
+             sku = $7, barcode = $8,
+             weight_oz = $9, length_in = $10, width_in = $11, height_in = $12,
+             hs_code = $13, country_of_origin = $14, domestic_only = COALESCE($15, domestic_only),
+             image_url = $16, brand = $17, category = $18, slug = $19
+             WHERE id = $20 RETURNING",
+            "",
+            input.name,
+            input.description,
+            input.price,
+            input.inventory,
+            input.status as _,
+            input.publish_at,
+            input.sku,
+            input.barcode,
+            input.weight_oz,
+            input.length_in,
+            input.width_in,
+            input.height_in,
+            input.hs_code,
+            input.country_of_origin,
+            input.domestic_only,
+            input.image_url,
+            input.brand,
+            input.category,
+            slug,
+            id,
+        )
+        .fetch_optional(&*self.pool)
+        .await
+    }
+
+    async fn update_many(&self, products: &[ProductUpdate]) -> Result<Vec<Product>, sqlx::Error> {
+        // As in create_many; the new values are prefixed so RETURNING only sees the products' columns
+        let column = |f: fn(&ProductInput) -> Option<String>| products.iter().map(|p| f(&p.input)).collect::<Vec<_>>();
+        let measure = |f: fn(&ProductInput) -> Option<f64>| products.iter().map(|p| f(&p.input)).collect::<Vec<_>>();
+        product_query!(
+            "UPDATE products SET name = new_name, description = new_description, price = new_price,
+             inventory = new_inventory, status = COALESCE(new_status, status),
+             publish_at = COALESCE(new_publish_at, publish_at), sku = new_sku, barcode = new_barcode,
+             weight_oz = new_weight_oz, length_in = new_length_in, width_in = new_width_in,
+             height_in = new_height_in, hs_code = new_hs_code, country_of_origin = new_country_of_origin,
+             domestic_only = COALESCE(new_domestic_only, domestic_only), image_url = new_image_url,
+             brand = new_brand, category = new_category, slug = new_slug
+             FROM UNNEST($1::INT4[], $2::TEXT[], $3::TEXT[], $4::FLOAT8[], $5::INT4[], $6::VARCHAR[],
+                         $7::TIMESTAMPTZ[], $8::VARCHAR[], $9::VARCHAR[], $10::FLOAT8[], $11::FLOAT8[],
+                         $12::FLOAT8[], $13::FLOAT8[], $14::VARCHAR[], $15::VARCHAR[], $16::BOOL[], $17::TEXT[],
+                         $18::VARCHAR[], $19::VARCHAR[], $20::VARCHAR[])
+                  AS u(product_id, new_name, new_description, new_price, new_inventory, new_status,
+                       new_publish_at, new_sku, new_barcode, new_weight_oz, new_length_in, new_width_in,
+                       new_height_in, new_hs_code, new_country_of_origin, new_domestic_only, new_image_url,
+                       new_brand, new_category, new_slug)
+             WHERE id = product_id
+             RETURNING",
+            "",
+            &products.iter().map(|p| p.id).collect::<Vec<_>>(),
+            &products.iter().map(|p| p.input.name.clone()).collect::<Vec<_>>(),
+            &column(|i| i.description.clone()) as _,
+            &products.iter().map(|p| p.input.price).collect::<Vec<_>>(),
+            &products.iter().map(|p| p.input.inventory).collect::<Vec<_>>(),
+            &products.iter().map(|p| p.input.status).collect::<Vec<_>>() as _,
+            &products.iter().map(|p| p.input.publish_at).collect::<Vec<_>>() as _,
+            &column(|i| i.sku.clone()) as _,
+            &column(|i| i.barcode.clone()) as _,
+            &measure(|i| i.weight_oz) as _,
+            &measure(|i| i.length_in) as _,
+            &measure(|i| i.width_in) as _,
+            &measure(|i| i.height_in) as _,
+            &column(|i| i.hs_code.clone()) as _,
+            &column(|i| i.country_of_origin.clone()) as _,
+            &products.iter().map(|p| p.input.domestic_only).collect::<Vec<_>>() as _,
+            &column(|i| i.image_url.clone()) as _,
+            &column(|i| i.brand.clone()) as _,
+            &column(|i| i.category.clone()) as _,
+            &products.iter().map(|p| p.slug.clone()).collect::<Vec<_>>(),
+        )
+        .fetch_all(&*self.pool)
+        .await
+    }
+
+    async fn set_status(
+        &self,
+        id: i32,
+        status: ProductStatus,
+        publish_at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Product>, sqlx::Error> {
+        product_query!(
+            "UPDATE products SET status = $1, publish_at = $2 WHERE id = $3 RETURNING",
+            "",
+            status as _,
+            publish_at,
+            id,
+        )
+        .fetch_optional(&*self.pool)
+        .await
+    }
+
+    async fn delete(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!("DELETE FROM products WHERE id = $1", id)
+            .execute(&*self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+}
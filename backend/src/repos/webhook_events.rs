@@ -0,0 +1,106 @@
+// Webhook event log: every provider event received, and whether handling it succeeded
+
+use async_trait::async_trait;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::log_scrubbing;
+use crate::webhooks::{CreateWebhookEvent, WebhookEvent};
+
+// Which events to list, newest first (by created_at, then id). Unset fields don't filter
+#[derive(Debug, Default)]
+pub struct WebhookEventFilter {
+    pub provider: Option<String>,
+    pub processed: Option<bool>,
+    pub before: Option<(DateTime<Utc>, Uuid)>, // Only events after this one in the list (a page cursor)
+    pub limit: i64,
+}
+
+#[async_trait]
+pub trait WebhookRepo: Send + Sync {
+    // Record a received event. None when the provider already sent it (idempotency): the insert
+    // is the check, so concurrent deliveries can't both pass
+    async fn log(&self, event: CreateWebhookEvent) -> Result<Option<Uuid>, sqlx::Error>;
+
+    async fn mark_processed(&self, id: Uuid, success: bool, error_message: Option<String>) -> Result<(), sqlx::Error>;
+
+    async fn list(&self, filter: &WebhookEventFilter) -> Result<Vec<WebhookEvent>, sqlx::Error>;
+}
+
+pub struct PgWebhookRepo {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl PgWebhookRepo {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl WebhookRepo for PgWebhookRepo {
+    async fn log(&self, event: CreateWebhookEvent) -> Result<Option<Uuid>, sqlx::Error> {
+        let provider_str = event.provider.to_string();
+        // Stored for support to read, so customer details and secrets are redacted first
+        let payload = log_scrubbing::scrub_json(event.payload);
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO webhook_events (provider, event_type, event_id, payload, processed, livemode, payload_scrubbed)
+            VALUES ($1, $2, $3, $4, FALSE, $5, TRUE)
+            ON CONFLICT (provider, event_id) DO NOTHING
+            RETURNING id
+            "#,
+            provider_str,
+            event.event_type,
+            event.event_id,
+            payload,
+            event.livemode,
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(result.map(|r| r.id))
+    }
+
+    async fn mark_processed(&self, id: Uuid, success: bool, error_message: Option<String>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE webhook_events
+            SET processed = $1, processed_at = NOW(), error_message = $2
+            WHERE id = $3
+            "#,
+            success,
+            error_message,
+            id,
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn list(&self, filter: &WebhookEventFilter) -> Result<Vec<WebhookEvent>, sqlx::Error> {
+        sqlx::query_as!(
+            WebhookEvent,
+            r#"
+            SELECT id, provider, event_type, event_id, payload, processed, processed_at, error_message,
+                   created_at AS "created_at!", livemode
+            FROM webhook_events
+            WHERE ($1::TEXT IS NULL OR provider = $1)
+              AND ($2::BOOLEAN IS NULL OR processed = $2)
+              AND ($3::TIMESTAMPTZ IS NULL OR (created_at, id) < ($3, $4))
+            ORDER BY created_at DESC, id DESC
+            LIMIT $5
+            "#,
+            filter.provider,
+            filter.processed,
+            filter.before.map(|(created_at, _)| created_at),
+            filter.before.map(|(_, id)| id),
+            filter.limit,
+        )
+        .fetch_all(&*self.pool)
+        .await
+    }
+}
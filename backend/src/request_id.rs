@@ -0,0 +1,143 @@
+// Request ID Module - Correlates a request across logs, error responses and provider calls
+// Every request gets an id: the caller's X-Request-Id when it sends a usable one (so a proxy
+// or client can choose it), otherwise a new UUID. The id is
+// - a field of the request's tracing span, so every log event while handling it carries it
+// - returned in the X-Request-Id response header, and as `request_id` in error bodies
+// - sent as X-Request-Id on provider calls made while handling it, including from the event
+//   subscribers it triggers
+// so support can find the logs behind an error a customer reports
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::json;
+use std::future::Future;
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub const HEADER: HeaderName = HeaderName::from_static("x-request-id");
+const MAX_LEN: usize = 128;
+const MAX_ERROR_BODY_BYTES: usize = 1024 * 1024;
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+// Id of the request being handled, if any (background jobs have none)
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|id| id.clone()).ok()
+}
+
+// Keep the current request's id and span in a task spawned while handling it
+pub fn propagate<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let id = current();
+    let future = future.instrument(tracing::Span::current());
+    async move {
+        match id {
+            Some(id) => CURRENT.scope(id, future).await,
+            None => future.await,
+        }
+    }
+}
+
+// Adds the current request id to outbound provider calls
+pub trait WithRequestId {
+    fn with_request_id(self) -> Self;
+}
+
+impl WithRequestId for reqwest::RequestBuilder {
+    fn with_request_id(self) -> Self {
+        match current() {
+            Some(id) => self.header(HEADER.as_str(), id),
+            None => self,
+        }
+    }
+}
+
+// Ids from callers are kept when short and free of anything that could forge log lines
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_LEN
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+pub async fn assign_request_id(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(&HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| is_valid(v))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let header_value = HeaderValue::from_str(&id).expect("request ids are visible ASCII");
+    // Handlers that read the header see the id the response will carry
+    request.headers_mut().insert(HEADER, header_value.clone());
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    );
+    let started = std::time::Instant::now();
+    let response = CURRENT.scope(id.clone(), next.run(request)).instrument(span.clone()).await;
+    let status = response.status();
+    span.in_scope(|| {
+        if status.is_server_error() {
+            tracing::error!(status = status.as_u16(), latency_ms = started.elapsed().as_millis() as u64, "request failed");
+        } else {
+            tracing::info!(status = status.as_u16(), latency_ms = started.elapsed().as_millis() as u64, "request finished");
+        }
+    });
+
+    let mut response = if status.is_client_error() || status.is_server_error() {
+        with_id_in_body(response, &id).await
+    } else {
+        response
+    };
+    response.headers_mut().insert(HEADER, header_value);
+    response
+}
+
+// Add the id to an error body. JSON objects get a `request_id` field; plain text (what the
+// handlers' `(StatusCode, String)` errors produce) and empty bodies become
+// `{"error": "...", "request_id": "..."}`. Other bodies are left alone
+async fn with_id_in_body(response: Response, id: &str) -> Response {
+    let (mut parts, body) = response.into_parts();
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let is_json = content_type.starts_with("application/json");
+    let is_text = content_type.is_empty() || content_type.starts_with("text/plain");
+    if (!is_json && !is_text) || parts.headers.contains_key(header::CONTENT_ENCODING) {
+        return Response::from_parts(parts, body);
+    }
+
+    let bytes = axum::body::to_bytes(body, MAX_ERROR_BODY_BYTES).await.unwrap_or_default();
+    let error_body = if is_json {
+        match serde_json::from_slice::<serde_json::Value>(&bytes) {
+            Ok(serde_json::Value::Object(mut object)) => {
+                object.insert("request_id".to_string(), json!(id));
+                serde_json::Value::Object(object)
+            }
+            _ => return Response::from_parts(parts, Body::from(bytes)),
+        }
+    } else {
+        let text = String::from_utf8_lossy(&bytes);
+        let message = match text.trim() {
+            "" => parts.status.canonical_reason().unwrap_or("Error"),
+            message => message,
+        };
+        json!({ "error": message, "request_id": id })
+    };
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(error_body.to_string()))
+}
@@ -0,0 +1,311 @@
+// Reviews Module - Order reviews collected from review request emails
+// Emails carry signed one-click rating links; clicking one records a draft review
+// and sends the customer to the storefront to optionally add text
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Redirect,
+    routing::{get, post, put},
+    Json, Router,
+};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::pii::Encrypted;
+use crate::AppState;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const REVIEW_TOKEN_TTL_DAYS: i64 = 30;
+const MAX_REVIEW_LEN: usize = 5000;
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+// Database model for reviews
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Review {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub customer_id: Option<Uuid>,
+    pub customer_email: Option<Encrypted>,
+    pub rating: i32,
+    pub body: Option<String>,
+    pub status: String,
+    pub submitted_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// What the storefront review page shows for a token
+#[derive(Debug, Serialize)]
+pub struct ReviewDraft {
+    pub order_id: Uuid,
+    pub rating: Option<i32>,
+    pub body: Option<String>,
+    pub status: Option<String>,
+    pub items: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct QuickRatingQuery {
+    pub token: String,
+    pub rating: i32,
+}
+
+#[derive(Deserialize)]
+pub struct ReviewTokenQuery {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct SubmitReviewRequest {
+    pub token: String,
+    pub rating: i32,
+    pub body: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ReviewListQuery {
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct ReviewStatusRequest {
+    pub status: String,
+}
+
+// Review links are signed with REVIEW_TOKEN_SECRET, falling back to JWT_SECRET
+fn token_secret() -> &'static str {
+    let auth = &app_config::get().auth;
+    auth.review_token_secret.as_ref().unwrap_or(&auth.jwt_secret).expose()
+}
+
+fn token_signature(order_id: Uuid, expires: i64) -> Result<HmacSha256, String> {
+    let mut mac = HmacSha256::new_from_slice(token_secret().as_bytes())
+        .map_err(|e| format!("Failed to create HMAC: {}", e))?;
+    mac.update(format!("review:{}:{}", order_id, expires).as_bytes());
+    Ok(mac)
+}
+
+// Token format: "<order_id>.<expires unix ts>.<hex HMAC-SHA256>"
+pub fn sign_review_token(order_id: Uuid) -> Result<String, String> {
+    let expires = (Utc::now() + chrono::Duration::days(REVIEW_TOKEN_TTL_DAYS)).timestamp();
+    let signature = token_signature(order_id, expires)?.finalize().into_bytes();
+    Ok(format!("{}.{}.{}", order_id, expires, hex::encode(signature)))
+}
+
+fn verify_review_token(token: &str) -> Result<Uuid, (StatusCode, String)> {
+    let invalid = || (StatusCode::UNAUTHORIZED, "Invalid or expired review link".to_string());
+    let mut parts = token.splitn(3, '.');
+    let (order_id, expires, signature) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(o), Some(e), Some(s)) => (o, e, s),
+        _ => return Err(invalid()),
+    };
+    let order_id = Uuid::parse_str(order_id).map_err(|_| invalid())?;
+    let expires: i64 = expires.parse().map_err(|_| invalid())?;
+    let signature = hex::decode(signature).map_err(|_| invalid())?;
+
+    token_signature(order_id, expires)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .verify_slice(&signature)
+        .map_err(|_| invalid())?;
+    if expires < Utc::now().timestamp() {
+        return Err(invalid());
+    }
+    Ok(order_id)
+}
+
+// HTML row of 1-5 star links for review request emails ({{rating_links}})
+pub fn rating_links_html(order_id: Uuid) -> Result<String, String> {
+    let token = sign_review_token(order_id)?;
+    let api_url = &app_config::get().server.public_api_url;
+    let links: Vec<String> = (1..=5)
+        .map(|rating| {
+            format!(
+                "<a href=\"{}/api/reviews/rate?token={}&amp;rating={}\" style=\"font-size:24px;text-decoration:none;color:#f5a623\">{}</a>",
+                api_url.trim_end_matches('/'),
+                token,
+                rating,
+                "★".repeat(rating)
+            )
+        })
+        .collect();
+    Ok(format!("<p>{}</p>", links.join("&nbsp;&nbsp;")))
+}
+
+fn validate_rating(rating: i32) -> Result<(), (StatusCode, String)> {
+    if !(1..=5).contains(&rating) {
+        return Err((StatusCode::BAD_REQUEST, "rating must be between 1 and 5".to_string()));
+    }
+    Ok(())
+}
+
+pub fn review_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/reviews/rate", get(quick_rate))
+        .route("/api/reviews/draft", get(get_review_draft))
+        .route("/api/reviews/submit", post(submit_review))
+        .route("/api/admin/reviews", get(list_reviews))
+        .route("/api/admin/reviews/:id/status", put(set_review_status))
+        .with_state(app_state)
+}
+
+// Record (or change) the rating as a draft, then hand over to the storefront review page.
+// Published and hidden reviews are left alone so old links can't undo moderation.
+// The order's email is copied as stored, still encrypted.
+async fn upsert_rating(pool: &sqlx::PgPool, order_id: Uuid, rating: i32) -> Result<(), (StatusCode, String)> {
+    let res = sqlx::query!(
+        "INSERT INTO reviews (order_id, customer_id, customer_email, rating)
+         SELECT id, customer_id, customer_email, $2 FROM orders WHERE id = $1
+         ON CONFLICT (order_id) DO UPDATE
+         SET rating = EXCLUDED.rating, updated_at = NOW()
+         WHERE reviews.status IN ('draft', 'submitted')",
+        order_id,
+        rating,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        let exists: bool = sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM orders WHERE id = $1) AS "exists!""#, order_id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+        if !exists {
+            return Err((StatusCode::NOT_FOUND, "Order not found".to_string()));
+        }
+    }
+    Ok(())
+}
+
+async fn quick_rate(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<QuickRatingQuery>,
+) -> Result<Redirect, (StatusCode, String)> {
+    let order_id = verify_review_token(&query.token)?;
+    validate_rating(query.rating)?;
+    upsert_rating(&app_state.pool, order_id, query.rating).await?;
+
+    tracing::info!("Recorded {}-star rating for order {}", query.rating, order_id);
+    let storefront_url = &app_config::get().server.storefront_url;
+    Ok(Redirect::to(&format!(
+        "{}/review?token={}&rating={}",
+        storefront_url.trim_end_matches('/'),
+        query.token,
+        query.rating
+    )))
+}
+
+async fn get_review_draft(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ReviewTokenQuery>,
+) -> Result<Json<ReviewDraft>, (StatusCode, String)> {
+    let order_id = verify_review_token(&query.token)?;
+    let review = sqlx::query_as!(Review, r#"SELECT id, order_id, customer_id, customer_email AS "customer_email: Encrypted", rating, body, status, submitted_at, created_at, updated_at FROM reviews WHERE order_id = $1"#, order_id)
+        .fetch_optional(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let items: Vec<String> =
+        sqlx::query_scalar!("SELECT product_name FROM order_items WHERE order_id = $1 ORDER BY created_at", order_id)
+            .fetch_all(&*app_state.pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    Ok(Json(ReviewDraft {
+        order_id,
+        rating: review.as_ref().map(|r| r.rating),
+        body: review.as_ref().and_then(|r| r.body.clone()),
+        status: review.map(|r| r.status),
+        items,
+    }))
+}
+
+// Finalize the review with optional text; edits to a published review go back to moderation
+async fn submit_review(
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<SubmitReviewRequest>,
+) -> Result<Json<Review>, (StatusCode, String)> {
+    let order_id = verify_review_token(&input.token)?;
+    validate_rating(input.rating)?;
+    let body = input.body.as_deref().map(str::trim).filter(|b| !b.is_empty());
+    if body.is_some_and(|b| b.chars().count() > MAX_REVIEW_LEN) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Review must be at most {} characters", MAX_REVIEW_LEN),
+        ));
+    }
+
+    upsert_rating(&app_state.pool, order_id, input.rating).await?;
+    sqlx::query_as!(
+        Review,
+        r#"UPDATE reviews
+         SET rating = $2, body = $3,
+             status = CASE WHEN status = 'hidden' THEN 'hidden' ELSE 'submitted' END,
+             submitted_at = NOW(), updated_at = NOW()
+         WHERE order_id = $1 RETURNING id, order_id, customer_id, customer_email AS "customer_email: Encrypted", rating, body, status, submitted_at, created_at, updated_at"#,
+        order_id,
+        input.rating,
+        body,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map(Json)
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))
+}
+
+async fn list_reviews(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ReviewListQuery>,
+) -> Result<Json<Vec<Review>>, (StatusCode, String)> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let reviews = sqlx::query_as!(
+        Review,
+        r#"SELECT id, order_id, customer_id, customer_email AS "customer_email: Encrypted", rating, body, status, submitted_at, created_at, updated_at FROM reviews
+         WHERE ($1::TEXT IS NULL OR status = $1)
+         ORDER BY COALESCE(submitted_at, created_at) DESC
+         LIMIT $2 OFFSET $3"#,
+        query.status,
+        limit,
+        offset,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(reviews))
+}
+
+async fn set_review_status(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(input): Json<ReviewStatusRequest>,
+) -> Result<Json<Review>, (StatusCode, String)> {
+    if !matches!(input.status.as_str(), "published" | "hidden" | "submitted") {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "status must be one of: published, hidden, submitted".to_string(),
+        ));
+    }
+    sqlx::query_as!(
+        Review,
+        r#"UPDATE reviews SET status = $1, updated_at = NOW() WHERE id = $2 RETURNING id, order_id, customer_id, customer_email AS "customer_email: Encrypted", rating, body, status, submitted_at, created_at, updated_at"#,
+        &input.status,
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .map(Json)
+    .ok_or((StatusCode::NOT_FOUND, "Review not found".to_string()))
+}
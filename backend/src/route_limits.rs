@@ -0,0 +1,214 @@
+// Route Limits Module - Concurrency limits for expensive route groups
+// Buying labels waits on EasyPost, CSV imports and reports scan whole tables, and PDFs are
+// rendered on the request's thread, so a burst of any of them can hold the runtime and the
+// database pool while cheap requests queue behind. Each group below shares one semaphore
+// across its routes. A request arriving while all of a group's permits are taken isn't queued:
+// it's shed at once with 503 and Retry-After.
+// The permit goes into the response body and is only given back when the body has been sent or
+// dropped: an export streams rows for as long as the client reads, well after the handler has
+// returned (tower's concurrency limit would release it then)
+//
+// Attach with `.route(path, post(handler).layer(route_limits::limit(RouteGroup::Labels)))`
+
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::Request,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::future::BoxFuture;
+use http_body::{Frame, SizeHint};
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tower::{Layer, Service};
+
+use crate::app_config;
+
+#[derive(Debug, Clone, Copy)]
+pub enum RouteGroup {
+    Labels,  // Postage label purchases (LABEL_PURCHASE_CONCURRENCY)
+    Imports, // CSV product imports (CSV_IMPORT_CONCURRENCY)
+    Pdfs,    // Invoice and ticket PDFs (PDF_CONCURRENCY)
+    Reports, // Reconciliation, experiment results and CSV exports (REPORT_CONCURRENCY)
+}
+
+impl RouteGroup {
+    fn semaphore(self) -> Arc<Semaphore> {
+        static SEMAPHORES: OnceLock<[Arc<Semaphore>; 4]> = OnceLock::new();
+        let semaphores = SEMAPHORES.get_or_init(|| {
+            let limits = &app_config::get().limits;
+            [
+                limits.label_purchase_concurrency,
+                limits.csv_import_concurrency,
+                limits.pdf_concurrency,
+                limits.report_concurrency,
+            ]
+            .map(|permits| Arc::new(Semaphore::new(permits)))
+        });
+        semaphores[self as usize].clone()
+    }
+}
+
+// Layer for a route of `group`
+#[derive(Clone)]
+pub struct RouteLimit {
+    semaphore: Arc<Semaphore>,
+}
+
+pub fn limit(group: RouteGroup) -> RouteLimit {
+    RouteLimit { semaphore: group.semaphore() }
+}
+
+impl<S> Layer<S> for RouteLimit {
+    type Service = Limited<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Limited { inner, semaphore: self.semaphore.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct Limited<S> {
+    inner: S,
+    semaphore: Arc<Semaphore>,
+}
+
+impl<S> Service<Request> for Limited<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Response, Infallible>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let Ok(permit) = self.semaphore.clone().try_acquire_owned() else {
+            return Box::pin(std::future::ready(Ok(overloaded())));
+        };
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            Ok(response.map(|body| Body::new(PermittedBody { body, _permit: permit })))
+        })
+    }
+}
+
+// A response body holding its route's permit until it's done with
+struct PermittedBody {
+    body: Body,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl HttpBody for PermittedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        Pin::new(&mut self.body).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+fn overloaded() -> Response {
+    let retry_after = app_config::get().limits.overload_retry_after_secs.to_string();
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, retry_after)],
+        "Too many of these requests are being handled, try again shortly",
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export_stream::{self, ExportFormat};
+    use axum::{routing::get, Router};
+    use serde::Serialize;
+    use tokio::sync::Notify;
+    use tower::ServiceExt;
+
+    #[derive(Serialize)]
+    struct Row {
+        id: i32,
+    }
+
+    fn load_config() {
+        for (key, value) in [
+            ("DATABASE_URL", "postgres://localhost/unused"),
+            ("STRIPE_SECRET_KEY", "sk_test_route_limits"),
+            ("STRIPE_WEBHOOK_SECRET", "whsec_route_limits"),
+            ("JWT_SECRET", "route-limits-test"),
+        ] {
+            if std::env::var(key).is_err() {
+                std::env::set_var(key, value);
+            }
+        }
+        crate::app_config::init();
+    }
+
+    // One permit, and an export that sends its row only once `release` is notified
+    fn export_app(release: Arc<Notify>) -> Router {
+        let export = move || {
+            let release = release.clone();
+            async move {
+                export_stream::respond(ExportFormat::Csv, "rows", move |mut out| async move {
+                    release.notified().await;
+                    out.write(&Row { id: 1 }).await?;
+                    Ok(out)
+                })
+            }
+        };
+        Router::new().route("/export", get(export).layer(RouteLimit { semaphore: Arc::new(Semaphore::new(1)) }))
+    }
+
+    async fn get_export(app: &Router) -> Response {
+        let request = Request::builder().uri("/export").body(Body::empty()).unwrap();
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_streaming_export_holds_its_permit_until_the_body_ends() {
+        load_config();
+        let release = Arc::new(Notify::new());
+        let app = export_app(release.clone());
+
+        // The handler has returned, but the body is still being written
+        let first = get_export(&app).await;
+        assert_eq!(first.status(), StatusCode::OK);
+        let shed = get_export(&app).await;
+        assert_eq!(shed.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(shed.headers().contains_key(header::RETRY_AFTER));
+
+        release.notify_one();
+        let body = axum::body::to_bytes(first.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"id\n1\n");
+        assert_eq!(get_export(&app).await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn dropping_an_unread_export_gives_its_permit_back() {
+        load_config();
+        let app = export_app(Arc::new(Notify::new()));
+
+        let first = get_export(&app).await;
+        assert_eq!(get_export(&app).await.status(), StatusCode::SERVICE_UNAVAILABLE);
+        drop(first);
+        assert_eq!(get_export(&app).await.status(), StatusCode::OK);
+    }
+}
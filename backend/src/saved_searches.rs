@@ -0,0 +1,507 @@
+// Saved Searches Module - Stored admin product queries with scheduled email digests
+// A saved search filters products by name, category, brand, status, stock and price
+// (e.g. "under 5 in stock in Apparel"). Searches with a frequency are evaluated on a schedule
+// and their matches emailed to the recipients, so merchandisers don't have to check by hand
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::admin_products::ProductStatus;
+use crate::branding::{escape_html, load_branding};
+use crate::notifications::{queue_email, EmailPriority};
+use crate::AppState;
+
+const PREVIEW_SAMPLE_SIZE: usize = 20;
+const DIGEST_MAX_ROWS: usize = 100;
+
+// How often a saved search is emailed
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
+pub enum DigestFrequency {
+    #[sqlx(rename = "daily")]
+    #[serde(rename = "daily")]
+    Daily,
+    #[sqlx(rename = "weekly")]
+    #[serde(rename = "weekly")]
+    Weekly,
+}
+
+// Product filters; None means "don't filter on this"
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProductSearchFilters {
+    pub name_contains: Option<String>,
+    pub category: Option<String>, // Exact, case-insensitive
+    pub brand: Option<String>,    // Exact, case-insensitive
+    pub status: Option<ProductStatus>,
+    pub min_inventory: Option<i32>,
+    pub max_inventory: Option<i32>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+}
+
+// Database model for saved searches
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedSearch {
+    pub id: i32,
+    pub name: String,
+    #[serde(flatten)]
+    pub filters: ProductSearchFilters,
+    pub frequency: Option<DigestFrequency>,
+    pub recipients: Vec<String>,
+    pub send_when_empty: bool,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_match_count: Option<i32>,
+    pub last_sent_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// A saved_searches row as query_as! reads it (it can't fill the flattened filters)
+struct SavedSearchRow {
+    id: i32,
+    name: String,
+    name_contains: Option<String>,
+    category: Option<String>,
+    brand: Option<String>,
+    status: Option<ProductStatus>,
+    min_inventory: Option<i32>,
+    max_inventory: Option<i32>,
+    min_price: Option<f64>,
+    max_price: Option<f64>,
+    frequency: Option<DigestFrequency>,
+    recipients: Vec<String>,
+    send_when_empty: bool,
+    last_run_at: Option<DateTime<Utc>>,
+    last_match_count: Option<i32>,
+    last_sent_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+impl From<SavedSearchRow> for SavedSearch {
+    fn from(row: SavedSearchRow) -> Self {
+        SavedSearch {
+            id: row.id,
+            name: row.name,
+            filters: ProductSearchFilters {
+                name_contains: row.name_contains,
+                category: row.category,
+                brand: row.brand,
+                status: row.status,
+                min_inventory: row.min_inventory,
+                max_inventory: row.max_inventory,
+                min_price: row.min_price,
+                max_price: row.max_price,
+            },
+            frequency: row.frequency,
+            recipients: row.recipients,
+            send_when_empty: row.send_when_empty,
+            last_run_at: row.last_run_at,
+            last_match_count: row.last_match_count,
+            last_sent_at: row.last_sent_at,
+            last_error: row.last_error,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+// query_as! for saved_searches rows, with the columns between `$before` and `$after` (see product_query!)
+macro_rules! saved_search_query {
+    ($before:literal, $after:literal $(, $arg:expr)* $(,)?) => {
+        sqlx::query_as!(
+            SavedSearchRow,
+            $before
+                + " id, name, name_contains, category, brand, status AS \"status: ProductStatus\",
+                    min_inventory, max_inventory, min_price, max_price, frequency AS \"frequency: DigestFrequency\",
+                    recipients, send_when_empty, last_run_at, last_match_count, last_sent_at, last_error,
+                    created_at, updated_at "
+                + $after
+            $(, $arg)*
+        )
+    };
+}
+
+#[derive(Deserialize)]
+pub struct SavedSearchInput {
+    pub name: String,
+    #[serde(flatten)]
+    pub filters: ProductSearchFilters,
+    pub frequency: Option<DigestFrequency>,
+    #[serde(default)]
+    pub recipients: Vec<String>,
+    #[serde(default)]
+    pub send_when_empty: bool,
+}
+
+// Product matching a saved search
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct SearchMatch {
+    pub id: i32,
+    pub name: String,
+    pub sku: Option<String>,
+    pub category: Option<String>,
+    pub brand: Option<String>,
+    pub status: ProductStatus,
+    pub price: f64,
+    pub inventory: i32,
+}
+
+#[derive(Serialize)]
+pub struct SearchPreview {
+    pub match_count: usize,
+    pub sample: Vec<SearchMatch>,
+}
+
+#[derive(Serialize)]
+pub struct DigestResult {
+    pub match_count: usize,
+    pub sent_to: Vec<String>, // Empty when nothing matched and send_when_empty is off
+}
+
+// Trimmed, lowercase, de-duplicated addresses
+fn normalize_recipients(recipients: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = recipients
+        .iter()
+        .map(|r| r.trim().to_lowercase())
+        .filter(|r| !r.is_empty())
+        .collect();
+    normalized.sort();
+    normalized.dedup();
+    normalized
+}
+
+// Empty strings count as unset
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn validate_input(input: &SavedSearchInput) -> Result<(), String> {
+    if input.name.trim().is_empty() {
+        return Err("name is required".to_string());
+    }
+    let recipients = normalize_recipients(&input.recipients);
+    if let Some(invalid) = recipients.iter().find(|r| !r.contains('@')) {
+        return Err(format!("Invalid recipient email: {}", invalid));
+    }
+    if input.frequency.is_some() && recipients.is_empty() {
+        return Err("recipients are required when a frequency is set".to_string());
+    }
+    Ok(())
+}
+
+// Products matching the filters, lowest stock first
+pub async fn matching_products(pool: &sqlx::PgPool, filters: &ProductSearchFilters) -> Result<Vec<SearchMatch>, sqlx::Error> {
+    sqlx::query_as!(
+        SearchMatch,
+        r#"SELECT id, name, sku, category, brand, status AS "status: ProductStatus", price, inventory FROM products
+         WHERE ($1::TEXT IS NULL OR name ILIKE '%' || $1 || '%')
+           AND ($2::TEXT IS NULL OR LOWER(category) = LOWER($2))
+           AND ($3::TEXT IS NULL OR LOWER(brand) = LOWER($3))
+           AND ($4::VARCHAR IS NULL OR status = $4)
+           AND ($5::INT IS NULL OR inventory >= $5)
+           AND ($6::INT IS NULL OR inventory <= $6)
+           AND ($7::FLOAT8 IS NULL OR price >= $7)
+           AND ($8::FLOAT8 IS NULL OR price <= $8)
+         ORDER BY inventory, id"#,
+        non_empty(&filters.name_contains),
+        non_empty(&filters.category),
+        non_empty(&filters.brand),
+        filters.status as _,
+        filters.min_inventory,
+        filters.max_inventory,
+        filters.min_price,
+        filters.max_price,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+fn digest_html(search: &SavedSearch, matches: &[SearchMatch]) -> String {
+    if matches.is_empty() {
+        return "<p>No products match this search right now.</p>".to_string();
+    }
+    let rows = matches
+        .iter()
+        .take(DIGEST_MAX_ROWS)
+        .map(|m| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>${:.2}</td></tr>",
+                escape_html(&m.name),
+                escape_html(m.sku.as_deref().unwrap_or("")),
+                escape_html(m.category.as_deref().unwrap_or("")),
+                m.inventory,
+                m.price
+            )
+        })
+        .collect::<String>();
+    let more = matches.len().saturating_sub(DIGEST_MAX_ROWS);
+    format!(
+        "<p>{} product(s) match <strong>{}</strong>.</p>
+         <table><tr><th>Product</th><th>SKU</th><th>Category</th><th>Stock</th><th>Price</th></tr>{}</table>{}",
+        matches.len(),
+        escape_html(&search.name),
+        rows,
+        if more > 0 { format!("<p>…and {} more. See /api/admin/saved-searches/{}/results.</p>", more, search.id) } else { String::new() }
+    )
+}
+
+// Evaluate a saved search and queue its digest to every recipient, recording the outcome.
+// Nothing is sent when there are no matches unless send_when_empty is set
+pub async fn send_digest(pool: &sqlx::PgPool, search: &SavedSearch) -> Result<DigestResult, String> {
+    let matches = matching_products(pool, &search.filters)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+    let mut sent_to = Vec::new();
+    let mut error = None;
+    if !matches.is_empty() || search.send_when_empty {
+        let subject = format!("Saved search: {} ({} products)", search.name, matches.len());
+        let html_body = load_branding(pool).await.email_html(&search.name, &digest_html(search, &matches));
+        for recipient in &search.recipients {
+            match queue_email(pool, recipient, &subject, &html_body, EmailPriority::Immediate).await {
+                Ok(()) => sent_to.push(recipient.clone()),
+                Err(e) => error = Some(format!("Failed to queue digest for {}: {}", recipient, e)),
+            }
+        }
+    }
+
+    sqlx::query!(
+        "UPDATE saved_searches
+         SET last_match_count = $1, last_error = $2,
+             last_sent_at = CASE WHEN $3 THEN NOW() ELSE last_sent_at END
+         WHERE id = $4",
+        matches.len() as i32,
+        error.as_deref(),
+        !sent_to.is_empty(),
+        search.id,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))?;
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(DigestResult {
+            match_count: matches.len(),
+            sent_to,
+        }),
+    }
+}
+
+// Claim the searches whose digest is due; stamping last_run_at keeps other instances from sending it too
+async fn claim_due_searches(pool: &sqlx::PgPool) -> Result<Vec<SavedSearch>, sqlx::Error> {
+    let rows = saved_search_query!(
+        "UPDATE saved_searches SET last_run_at = NOW()
+         WHERE frequency IS NOT NULL
+           AND (last_run_at IS NULL
+                OR last_run_at <= NOW() - CASE frequency WHEN 'weekly' THEN INTERVAL '7 days' ELSE INTERVAL '1 day' END)
+         RETURNING",
+        "",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(SavedSearch::from).collect())
+}
+
+// Email every digest that's due (the send_saved_search_digests scheduled task)
+pub async fn send_due_digests(pool: &sqlx::PgPool) -> Result<String, String> {
+    let searches = claim_due_searches(pool)
+        .await
+        .map_err(|e| format!("Failed to load due saved searches: {}", e))?;
+    let mut failed = 0;
+    for search in &searches {
+        match send_digest(pool, search).await {
+            Ok(r) if !r.sent_to.is_empty() => tracing::info!(
+                "Saved search '{}' digest queued: {} matches to {} recipient(s)",
+                search.name,
+                r.match_count,
+                r.sent_to.len()
+            ),
+            Ok(_) => {}
+            Err(e) => {
+                failed += 1;
+                tracing::error!("Saved search '{}' digest failed: {}", search.name, e);
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{} of {} due saved search digests failed", failed, searches.len()));
+    }
+    Ok(format!("Checked {} due saved searches", searches.len()))
+}
+
+// Admin saved search routes
+pub fn saved_search_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/saved-searches", get(list_saved_searches).post(create_saved_search))
+        .route("/api/admin/saved-searches/preview", post(preview_saved_search))
+        .route(
+            "/api/admin/saved-searches/:id",
+            get(get_saved_search).put(update_saved_search).delete(delete_saved_search),
+        )
+        .route("/api/admin/saved-searches/:id/results", get(saved_search_results))
+        .route("/api/admin/saved-searches/:id/send", post(send_saved_search_now))
+        .with_state(app_state)
+}
+
+async fn load_saved_search(pool: &sqlx::PgPool, id: i32) -> Result<SavedSearch, (StatusCode, String)> {
+    saved_search_query!("SELECT", "FROM saved_searches WHERE id = $1", id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .map(SavedSearch::from)
+        .ok_or((StatusCode::NOT_FOUND, "Saved search not found".to_string()))
+}
+
+async fn list_saved_searches(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<SavedSearch>>, (StatusCode, String)> {
+    let searches = saved_search_query!("SELECT", "FROM saved_searches ORDER BY name")
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(searches.into_iter().map(SavedSearch::from).collect()))
+}
+
+async fn get_saved_search(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<SavedSearch>, (StatusCode, String)> {
+    Ok(Json(load_saved_search(&app_state.pool, id).await?))
+}
+
+// Count and sample the products a set of filters would select, without saving
+async fn preview_saved_search(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(filters): Json<ProductSearchFilters>,
+) -> Result<Json<SearchPreview>, (StatusCode, String)> {
+    let mut matches = matching_products(&app_state.pool, &filters)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let match_count = matches.len();
+    matches.truncate(PREVIEW_SAMPLE_SIZE);
+    Ok(Json(SearchPreview { match_count, sample: matches }))
+}
+
+async fn create_saved_search(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<SavedSearchInput>,
+) -> Result<(StatusCode, Json<SavedSearch>), (StatusCode, String)> {
+    validate_input(&input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let search = saved_search_query!(
+        "INSERT INTO saved_searches
+            (name, name_contains, category, brand, status, min_inventory, max_inventory, min_price, max_price,
+             frequency, recipients, send_when_empty)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) RETURNING",
+        "",
+        input.name.trim(),
+        non_empty(&input.filters.name_contains),
+        non_empty(&input.filters.category),
+        non_empty(&input.filters.brand),
+        input.filters.status as _,
+        input.filters.min_inventory,
+        input.filters.max_inventory,
+        input.filters.min_price,
+        input.filters.max_price,
+        input.frequency as _,
+        &normalize_recipients(&input.recipients),
+        input.send_when_empty,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?;
+    Ok((StatusCode::CREATED, Json(search.into())))
+}
+
+async fn update_saved_search(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(input): Json<SavedSearchInput>,
+) -> Result<Json<SavedSearch>, (StatusCode, String)> {
+    validate_input(&input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let search = saved_search_query!(
+        "UPDATE saved_searches
+         SET name = $1, name_contains = $2, category = $3, brand = $4, status = $5,
+             min_inventory = $6, max_inventory = $7, min_price = $8, max_price = $9,
+             frequency = $10, recipients = $11, send_when_empty = $12, updated_at = NOW()
+         WHERE id = $13 RETURNING",
+        "",
+        input.name.trim(),
+        non_empty(&input.filters.name_contains),
+        non_empty(&input.filters.category),
+        non_empty(&input.filters.brand),
+        input.filters.status as _,
+        input.filters.min_inventory,
+        input.filters.max_inventory,
+        input.filters.min_price,
+        input.filters.max_price,
+        input.frequency as _,
+        &normalize_recipients(&input.recipients),
+        input.send_when_empty,
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Saved search not found".to_string()))?;
+    Ok(Json(search.into()))
+}
+
+async fn delete_saved_search(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM saved_searches WHERE id = $1", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Saved search not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Every product currently matching a saved search
+async fn saved_search_results(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<SearchMatch>>, (StatusCode, String)> {
+    let search = load_saved_search(&app_state.pool, id).await?;
+    let matches = matching_products(&app_state.pool, &search.filters)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(matches))
+}
+
+// Email the digest now instead of waiting for the schedule
+async fn send_saved_search_now(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<DigestResult>, (StatusCode, String)> {
+    let search = load_saved_search(&app_state.pool, id).await?;
+    if search.recipients.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Saved search has no recipients".to_string()));
+    }
+    let result = send_digest(&app_state.pool, &search)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(result))
+}
@@ -0,0 +1,627 @@
+// Scheduler Module - Recurring background tasks on cron schedules
+// Tasks are defined in TASKS and get a scheduled_tasks row on startup, where admins change the
+// cron expression (five fields, in STORE_TIMEZONE), disable a task or ask for a run right away.
+// With several replicas only the holder of the scheduler lease starts runs; when it stops
+// renewing, another replica takes over once the lease expires. Runs missed while no server was
+// up happen once, on the next tick. Every run is kept in scheduled_task_runs
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use croner::Cron;
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::{
+    app_config, audit_log, daily_digest, danger_zone, feeds, payment_capture, saved_searches, segments, stock_reservations,
+    AppState,
+};
+
+const LEASE_NAME: &str = "scheduler";
+const SCHEDULED: &str = "schedule"; // triggered_by of runs started by the schedule
+const AUDIT_ACTOR: &str = "scheduler"; // admin_username of audit entries for scheduled changes
+const RUN_TIMEOUT_SECS: u64 = 3600; // Runs taking longer are stopped and count as failed
+const RUN_RETENTION_DAYS: i64 = 90;
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+type TaskFuture = Pin<Box<dyn Future<Output = Result<String, String>> + Send>>;
+
+// A task the scheduler can run; Ok is a one-line summary of what it did
+struct TaskDefinition {
+    name: &'static str,
+    description: &'static str,
+    cron: &'static str, // Schedule until an admin changes it
+    enabled: bool,      // Whether the task starts out enabled
+    run: fn(Arc<AppState>) -> TaskFuture,
+}
+
+const TASKS: &[TaskDefinition] = &[
+    TaskDefinition {
+        name: "purge_stale_carts",
+        description: "Delete anonymous checkout carts older than STALE_CART_DAYS that never became an order",
+        cron: "15 3 * * *",
+        enabled: true,
+        run: |state| Box::pin(purge_stale_carts(state)),
+    },
+    TaskDefinition {
+        name: "scrub_order_pii",
+        description: "Remove customer details from finished orders older than ORDER_PII_RETENTION_DAYS",
+        cron: "45 3 * * *",
+        enabled: false, // Irreversible; enabled by an admin once the retention period is agreed
+        run: |state| Box::pin(scrub_order_pii(state)),
+    },
+    TaskDefinition {
+        name: "prune_task_runs",
+        description: "Delete scheduled task runs older than 90 days",
+        cron: "30 4 * * 0",
+        enabled: true,
+        run: |state| Box::pin(prune_task_runs(state)),
+    },
+    TaskDefinition {
+        name: "release_stock_reservations",
+        description: "Put the stock of checkouts whose hold expired unpaid back into inventory",
+        cron: "*/5 * * * *",
+        enabled: true,
+        run: |state| Box::pin(release_stock_reservations(state)),
+    },
+    TaskDefinition {
+        name: "settle_expired_authorizations",
+        description: "Capture the fulfilled share of authorizations older than AUTHORIZATION_WINDOW_HOURS and void the rest",
+        cron: "*/15 * * * *",
+        enabled: true,
+        run: |state| Box::pin(async move { payment_capture::settle_expired(&state).await }),
+    },
+    TaskDefinition {
+        name: "send_daily_digest",
+        description: "Email yesterday's summary to DAILY_DIGEST_RECIPIENTS once DAILY_DIGEST_HOUR has passed",
+        cron: "*/10 * * * *",
+        enabled: true,
+        run: |state| Box::pin(async move { daily_digest::send_if_due(&state.pool).await }),
+    },
+    TaskDefinition {
+        name: "send_saved_search_digests",
+        description: "Email the digests of daily and weekly saved searches that are due",
+        cron: "*/15 * * * *",
+        enabled: true,
+        run: |state| Box::pin(async move { saved_searches::send_due_digests(&state.pool).await }),
+    },
+    TaskDefinition {
+        name: "refresh_segments",
+        description: "Re-materialize customer segments and sync them to their marketing provider lists",
+        cron: "0 * * * *",
+        enabled: true,
+        run: |state| Box::pin(async move { segments::refresh_all(&state.pool).await }),
+    },
+    TaskDefinition {
+        name: feeds::REFRESH_TASK,
+        description: "Regenerate the Google Merchant feed (does nothing unless ENABLE_PRODUCT_FEED is on)",
+        cron: "0 * * * *",
+        enabled: true,
+        run: |state| Box::pin(async move { feeds::scheduled_refresh(&state).await }),
+    },
+];
+
+#[derive(Debug, Serialize)]
+pub struct ScheduledTask {
+    pub name: String,
+    pub description: &'static str,
+    pub cron: String,
+    pub enabled: bool,
+    pub next_run_at: DateTime<Utc>,
+    pub run_requested_at: Option<DateTime<Utc>>,
+    pub run_requested_by: Option<String>,
+    pub running_since: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_status: Option<String>,
+    pub last_error: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// A scheduled_tasks row as query_as! reads it; the description comes from the task's definition
+struct ScheduledTaskRow {
+    name: String,
+    cron: String,
+    enabled: bool,
+    next_run_at: DateTime<Utc>,
+    run_requested_at: Option<DateTime<Utc>>,
+    run_requested_by: Option<String>,
+    running_since: Option<DateTime<Utc>>,
+    last_run_at: Option<DateTime<Utc>>,
+    last_status: Option<String>,
+    last_error: Option<String>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+impl ScheduledTaskRow {
+    fn with_description(self, description: &'static str) -> ScheduledTask {
+        ScheduledTask {
+            name: self.name,
+            description,
+            cron: self.cron,
+            enabled: self.enabled,
+            next_run_at: self.next_run_at,
+            run_requested_at: self.run_requested_at,
+            run_requested_by: self.run_requested_by,
+            running_since: self.running_since,
+            last_run_at: self.last_run_at,
+            last_status: self.last_status,
+            last_error: self.last_error,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+// query_as! for scheduled_tasks rows, with the columns between `$before` and `$after` (see product_query!)
+macro_rules! task_query {
+    ($before:literal, $after:literal $(, $arg:expr)* $(,)?) => {
+        sqlx::query_as!(
+            ScheduledTaskRow,
+            $before
+                + " name, cron, enabled, next_run_at, run_requested_at, run_requested_by, running_since, last_run_at,
+                    last_status, last_error, updated_at "
+                + $after
+            $(, $arg)*
+        )
+    };
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct TaskRun {
+    pub id: i64,
+    pub task_name: String,
+    pub triggered_by: String,
+    pub instance_id: String,
+    pub status: String,
+    pub summary: Option<String>,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SchedulerLeader {
+    pub holder: String,
+    pub acquired_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct SchedulerStatus {
+    pub timezone: String,
+    pub leader: Option<SchedulerLeader>, // None when no replica holds an unexpired lease
+    pub tasks: Vec<ScheduledTask>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateTaskRequest {
+    pub enabled: Option<bool>,
+    pub cron: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TaskRunQuery {
+    pub limit: Option<i64>,
+}
+
+fn find_task(name: &str) -> Option<&'static TaskDefinition> {
+    TASKS.iter().find(|task| task.name == name)
+}
+
+fn parse_cron(expression: &str) -> Result<Cron, String> {
+    Cron::new(expression.trim())
+        .parse()
+        .map_err(|e| format!("Invalid cron expression \"{}\": {}", expression, e))
+}
+
+// The first time the expression matches after `after`, in the store's timezone
+fn next_run(expression: &str, after: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    let cron = parse_cron(expression)?;
+    cron.find_next_occurrence(&after.with_timezone(&daily_digest::store_timezone()), false)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| format!("No next run for \"{}\": {}", expression, e))
+}
+
+// Identifies this server in the lease and run history
+fn instance_id() -> String {
+    let host = &app_config::get().server.hostname;
+    format!("{}-{}", host, &uuid::Uuid::new_v4().simple().to_string()[..8])
+}
+
+// Add rows for tasks this version knows; existing rows keep their admin changes
+pub async fn register_tasks(pool: &sqlx::PgPool) -> Result<(), String> {
+    for task in TASKS {
+        let next_run_at = next_run(task.cron, Utc::now())?;
+        sqlx::query!(
+            "INSERT INTO scheduled_tasks (name, cron, enabled, next_run_at) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (name) DO NOTHING",
+            task.name,
+            task.cron,
+            task.enabled,
+            next_run_at,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+    }
+    Ok(())
+}
+
+// Take or renew the lease; true while this instance holds it
+async fn renew_lease(pool: &sqlx::PgPool, instance: &str, lease_secs: u64) -> Result<bool, sqlx::Error> {
+    let holder: Option<String> = sqlx::query_scalar!(
+        "INSERT INTO scheduler_leases (name, holder, acquired_at, expires_at)
+         VALUES ($1, $2, NOW(), NOW() + make_interval(secs => $3))
+         ON CONFLICT (name) DO UPDATE SET
+             holder = EXCLUDED.holder,
+             acquired_at = CASE WHEN scheduler_leases.holder = EXCLUDED.holder THEN scheduler_leases.acquired_at ELSE NOW() END,
+             expires_at = EXCLUDED.expires_at
+         WHERE scheduler_leases.holder = EXCLUDED.holder OR scheduler_leases.expires_at < NOW()
+         RETURNING holder",
+        LEASE_NAME,
+        instance,
+        lease_secs as f64,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(holder.is_some())
+}
+
+// Mark tasks that are due (or were asked for) as running, moving scheduled ones to their next time.
+// The conditional update means a task is claimed once even if two replicas briefly both lead
+async fn claim_due_tasks(pool: &sqlx::PgPool) -> Result<Vec<(ScheduledTask, String)>, sqlx::Error> {
+    let candidates = task_query!(
+        "SELECT",
+        "FROM scheduled_tasks
+         WHERE ((enabled AND next_run_at <= NOW()) OR run_requested_at IS NOT NULL)
+           AND (running_since IS NULL OR running_since < NOW() - make_interval(secs => $1))",
+        (RUN_TIMEOUT_SECS * 2) as f64,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut claimed = Vec::new();
+    for task in candidates {
+        let Some(definition) = find_task(&task.name) else {
+            continue; // Defined by another version of the server
+        };
+        let scheduled = task.enabled && task.next_run_at <= Utc::now();
+        let next_run_at = if scheduled {
+            match next_run(&task.cron, Utc::now()) {
+                Ok(next) => next,
+                Err(e) => {
+                    tracing::error!("Scheduled task {}: {}", task.name, e);
+                    continue;
+                }
+            }
+        } else {
+            task.next_run_at
+        };
+        let row = task_query!(
+            "UPDATE scheduled_tasks SET running_since = NOW(), next_run_at = $2,
+                    run_requested_at = NULL, run_requested_by = NULL, updated_at = NOW()
+             WHERE name = $1 AND running_since IS NOT DISTINCT FROM $3
+             RETURNING",
+            "",
+            &task.name,
+            next_run_at,
+            task.running_since,
+        )
+        .fetch_optional(pool)
+        .await?;
+        let Some(row) = row else {
+            continue;
+        };
+        if task.running_since.is_some() {
+            // The replica running it went away; close its run
+            sqlx::query!(
+                "UPDATE scheduled_task_runs SET status = 'failed', error = 'interrupted', finished_at = NOW()
+                 WHERE task_name = $1 AND status = 'running'",
+                &task.name,
+            )
+            .execute(pool)
+            .await?;
+        }
+        let triggered_by = match (scheduled, task.run_requested_by) {
+            (false, Some(admin)) => admin,
+            _ => SCHEDULED.to_string(),
+        };
+        claimed.push((row.with_description(definition.description), triggered_by));
+    }
+    Ok(claimed)
+}
+
+// Run one claimed task and record the outcome
+async fn run_task(state: Arc<AppState>, task: ScheduledTask, triggered_by: String, instance: String) {
+    let Some(definition) = find_task(&task.name) else {
+        return;
+    };
+    let run_id: i64 = match sqlx::query_scalar!(
+        "INSERT INTO scheduled_task_runs (task_name, triggered_by, instance_id) VALUES ($1, $2, $3) RETURNING id",
+        &task.name,
+        &triggered_by,
+        &instance,
+    )
+    .fetch_one(&*state.pool)
+    .await
+    {
+        Ok(id) => id,
+        Err(e) => {
+            tracing::error!("Failed to record a run of {}: {}", task.name, e);
+            release_task(&state.pool, &task.name).await;
+            return;
+        }
+    };
+
+    let result = match tokio::time::timeout(Duration::from_secs(RUN_TIMEOUT_SECS), (definition.run)(state.clone())).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timed out after {} seconds", RUN_TIMEOUT_SECS)),
+    };
+    match &result {
+        Ok(summary) => tracing::info!("Scheduled task {}: {}", task.name, summary),
+        Err(e) => tracing::error!("Scheduled task {} failed: {}", task.name, e),
+    }
+    let (status, summary, error) = match result {
+        Ok(summary) => ("succeeded", Some(summary), None),
+        Err(e) => ("failed", None, Some(e)),
+    };
+    let recorded = async {
+        sqlx::query!(
+            "UPDATE scheduled_task_runs SET status = $2, summary = $3, error = $4, finished_at = NOW() WHERE id = $1",
+            run_id,
+            status,
+            summary.as_deref(),
+            error.as_deref(),
+        )
+        .execute(&*state.pool)
+        .await?;
+        sqlx::query!(
+            "UPDATE scheduled_tasks SET running_since = NULL, last_run_at = NOW(), last_status = $2, last_error = $3,
+                    updated_at = NOW()
+             WHERE name = $1",
+            &task.name,
+            status,
+            error.as_deref(),
+        )
+        .execute(&*state.pool)
+        .await
+    };
+    if let Err(e) = recorded.await {
+        tracing::error!("Failed to record the result of {}: {}", task.name, e);
+    }
+}
+
+// Clear the running flag of a task that couldn't start
+async fn release_task(pool: &sqlx::PgPool, name: &str) {
+    if let Err(e) = sqlx::query!("UPDATE scheduled_tasks SET running_since = NULL WHERE name = $1", name)
+        .execute(pool)
+        .await
+    {
+        tracing::error!("Failed to release scheduled task {}: {}", name, e);
+    }
+}
+
+// Background task: hold the lease and start due tasks, each in its own task so a long run
+// doesn't hold up the others or the lease renewal
+pub fn spawn_scheduler(state: Arc<AppState>) {
+    let settings = &app_config::get().schedules;
+    let (tick_secs, lease_secs) = (settings.scheduler_tick_secs, settings.scheduler_lease_secs);
+    let instance = instance_id();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(tick_secs));
+        let mut leading = false;
+        loop {
+            interval.tick().await;
+            let leader = match renew_lease(&state.pool, &instance, lease_secs).await {
+                Ok(leader) => leader,
+                Err(e) => {
+                    tracing::error!("Failed to renew the scheduler lease: {}", e);
+                    false
+                }
+            };
+            if leader != leading {
+                if leader {
+                    tracing::info!("Scheduler: {} is now running scheduled tasks", instance);
+                } else {
+                    tracing::info!("Scheduler: {} is no longer running scheduled tasks", instance);
+                }
+                leading = leader;
+            }
+            if !leader {
+                continue;
+            }
+            match claim_due_tasks(&state.pool).await {
+                Ok(tasks) => {
+                    for (task, triggered_by) in tasks {
+                        tokio::spawn(run_task(state.clone(), task, triggered_by, instance.clone()));
+                    }
+                }
+                Err(e) => tracing::error!("Failed to claim scheduled tasks: {}", e),
+            }
+        }
+    });
+}
+
+async fn purge_stale_carts(state: Arc<AppState>) -> Result<String, String> {
+    let db_err = |e: sqlx::Error| format!("DB error: {}", e);
+    let days = app_config::get().schedules.stale_cart_days;
+    let cutoff = Utc::now() - chrono::Duration::days(days);
+    let mut tx = state.pool.begin().await.map_err(db_err)?;
+    let purged = danger_zone::purge_stale_carts(&mut tx, cutoff).await.map_err(db_err)?;
+    let summary = format!("Purged {} anonymous checkout carts created before {}", purged, cutoff.date_naive());
+    if purged > 0 {
+        let details = serde_json::json!({ "cutoff": cutoff, "older_than_days": days, "scheduled": true });
+        audit_log::record(&mut tx, AUDIT_ACTOR, danger_zone::PURGE_CARTS_ACTION, &summary, details, None)
+            .await
+            .map_err(db_err)?;
+    }
+    tx.commit().await.map_err(db_err)?;
+    Ok(summary)
+}
+
+async fn scrub_order_pii(state: Arc<AppState>) -> Result<String, String> {
+    let db_err = |e: sqlx::Error| format!("DB error: {}", e);
+    let days = app_config::get().schedules.order_pii_retention_days;
+    let cutoff = Utc::now() - chrono::Duration::days(days);
+    let mut tx = state.pool.begin().await.map_err(db_err)?;
+    let ids = danger_zone::scrub_orders_before(&mut tx, cutoff).await.map_err(db_err)?;
+    let summary = format!("Scrubbed personal data from {} orders placed before {}", ids.len(), cutoff.date_naive());
+    if !ids.is_empty() {
+        let details = serde_json::json!({ "cutoff": cutoff, "older_than_days": days, "scheduled": true });
+        audit_log::record(&mut tx, AUDIT_ACTOR, danger_zone::SCRUB_ORDERS_ACTION, &summary, details, None)
+            .await
+            .map_err(db_err)?;
+    }
+    tx.commit().await.map_err(db_err)?;
+    Ok(summary)
+}
+
+async fn release_stock_reservations(state: Arc<AppState>) -> Result<String, String> {
+    let released = stock_reservations::release_expired(&state).await.map_err(|e| format!("DB error: {}", e))?;
+    Ok(format!("Released the stock of {} expired checkouts", released))
+}
+
+async fn prune_task_runs(state: Arc<AppState>) -> Result<String, String> {
+    let deleted = sqlx::query!(
+        "DELETE FROM scheduled_task_runs WHERE status <> 'running' AND started_at < NOW() - make_interval(days => $1)",
+        RUN_RETENTION_DAYS as i32,
+    )
+    .execute(&*state.pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))?
+    .rows_affected();
+    Ok(format!("Deleted {} runs older than {} days", deleted, RUN_RETENTION_DAYS))
+}
+
+pub fn scheduler_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/scheduled-tasks", get(list_tasks))
+        .route("/api/admin/scheduled-tasks/:name", get(get_task).put(update_task))
+        .route("/api/admin/scheduled-tasks/:name/run", post(request_run))
+        .route("/api/admin/scheduled-tasks/:name/runs", get(list_runs))
+        .with_state(app_state)
+}
+
+async fn load_task(pool: &sqlx::PgPool, name: &str) -> Result<ScheduledTask, (StatusCode, String)> {
+    let definition = find_task(name).ok_or((StatusCode::NOT_FOUND, "Scheduled task not found".to_string()))?;
+    let task = task_query!("SELECT", "FROM scheduled_tasks WHERE name = $1", name)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Scheduled task not found".to_string()))?;
+    Ok(task.with_description(definition.description))
+}
+
+// Every task with its schedule and latest outcome, and the replica currently running them
+async fn list_tasks(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<SchedulerStatus>, (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let rows = task_query!("SELECT", "FROM scheduled_tasks ORDER BY name")
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(db_err)?;
+    let tasks = rows
+        .into_iter()
+        .filter_map(|task| {
+            let description = find_task(&task.name)?.description;
+            Some(task.with_description(description))
+        })
+        .collect();
+    let leader = sqlx::query_as!(
+        SchedulerLeader,
+        "SELECT holder, acquired_at, expires_at FROM scheduler_leases WHERE name = $1 AND expires_at > NOW()",
+        LEASE_NAME,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(db_err)?;
+    Ok(Json(SchedulerStatus {
+        timezone: app_config::get().notifications.store_timezone.clone(),
+        leader,
+        tasks,
+    }))
+}
+
+async fn get_task(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Json<ScheduledTask>, (StatusCode, String)> {
+    load_task(&app_state.pool, &name).await.map(Json)
+}
+
+// Enable or disable a task, or change its schedule; the next run is worked out again from now
+async fn update_task(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(request): Json<UpdateTaskRequest>,
+) -> Result<Json<ScheduledTask>, (StatusCode, String)> {
+    let task = load_task(&app_state.pool, &name).await?;
+    let cron = request.cron.map(|cron| cron.trim().to_string()).unwrap_or(task.cron);
+    let next_run_at = next_run(&cron, Utc::now()).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    sqlx::query!(
+        "UPDATE scheduled_tasks SET cron = $2, enabled = COALESCE($3, enabled), next_run_at = $4, updated_at = NOW()
+         WHERE name = $1",
+        &name,
+        &cron,
+        request.enabled,
+        next_run_at,
+    )
+    .execute(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    load_task(&app_state.pool, &name).await.map(Json)
+}
+
+// Run a task on the scheduler's next tick, whether or not it's enabled. A task that is running
+// now runs again once it finishes
+async fn request_run(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<(StatusCode, Json<ScheduledTask>), (StatusCode, String)> {
+    load_task(&app_state.pool, &name).await?;
+    sqlx::query!(
+        "UPDATE scheduled_tasks SET run_requested_at = NOW(), run_requested_by = $2, updated_at = NOW() WHERE name = $1",
+        &name,
+        &admin.username,
+    )
+    .execute(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let task = load_task(&app_state.pool, &name).await?;
+    Ok((StatusCode::ACCEPTED, Json(task)))
+}
+
+// Run history of a task, most recent first
+async fn list_runs(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(query): Query<TaskRunQuery>,
+) -> Result<Json<Vec<TaskRun>>, (StatusCode, String)> {
+    load_task(&app_state.pool, &name).await?;
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let runs = sqlx::query_as!(
+        TaskRun,
+        "SELECT id, task_name, triggered_by, instance_id, status, summary, error, started_at, finished_at FROM scheduled_task_runs WHERE task_name = $1 ORDER BY started_at DESC, id DESC LIMIT $2",
+        &name,
+        limit,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(runs))
+}
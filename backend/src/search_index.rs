@@ -0,0 +1,429 @@
+// Search Index Module - Optional Meilisearch index for storefront product search
+// With MEILISEARCH_URL set, published products are mirrored into a Meilisearch index: product
+// events upsert or remove single documents, and a background full sync (at startup and every
+// SEARCH_REINDEX_INTERVAL_SECS) repairs anything the events missed, like sales ending.
+// /api/products/search then queries Meilisearch for typo-tolerant matching and facet counts,
+// falling back to Postgres full-text search when the engine is unset or unreachable
+
+use async_trait::async_trait;
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::pricing::PriceBook;
+use crate::product_search::{SearchParams, PRICE_BUCKET_EDGES};
+use crate::request_id::WithRequestId;
+use crate::AppState;
+
+const SYNC_BATCH_SIZE: usize = 1000;
+
+// What the index stores per published product; prices are effective (sale) prices
+#[derive(Debug, Serialize)]
+pub struct SearchDocument {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub brand: Option<String>,
+    pub category: Option<String>,
+    pub slug: String,
+    pub price: f64,
+    pub in_stock: bool,
+    #[serde(rename = "created_at")]
+    pub created_ts: i64, // Unix seconds, sortable
+}
+
+// Matches from the index: product ids in result order, plus the facet counts
+pub struct IndexedSearch {
+    pub ids: Vec<i32>,
+    pub total: i64,
+    pub categories: Vec<(String, i64)>,
+    pub price_counts: Vec<(i32, i64)>, // (bucket, count), buckets as in PRICE_BUCKET_EDGES
+    pub in_stock: i64,
+    pub out_of_stock: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReindexResult {
+    pub indexed: usize,
+    pub removed: usize,
+}
+
+#[derive(Deserialize)]
+struct MultiSearchResponse {
+    results: Vec<SearchResult>,
+}
+
+#[derive(Deserialize)]
+struct SearchResult {
+    #[serde(default)]
+    hits: Vec<Hit>,
+    #[serde(rename = "totalHits")]
+    total_hits: Option<i64>,
+    #[serde(rename = "facetDistribution", default)]
+    facet_distribution: HashMap<String, HashMap<String, i64>>,
+}
+
+#[derive(Deserialize)]
+struct Hit {
+    id: i32,
+}
+
+#[derive(Deserialize)]
+struct DocumentPage {
+    results: Vec<Hit>,
+    total: usize,
+}
+
+// The filter a facet's own counts leave out
+#[derive(Clone, Copy, PartialEq)]
+enum Facet {
+    None,
+    Category,
+    Price,
+    Stock,
+}
+
+#[derive(Clone)]
+pub struct SearchIndex {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+    index: String,
+}
+
+impl SearchIndex {
+    // The configured Meilisearch index, or None to search with Postgres
+    pub fn from_config() -> Option<Self> {
+        let settings = &app_config::get().search;
+        let url = settings.meilisearch_url.as_ref()?;
+        Some(Self {
+            client: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            api_key: settings.meilisearch_api_key.as_ref().map(|k| k.expose().to_string()),
+            index: settings.meilisearch_index.clone(),
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, format!("{}{}", self.url, path));
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, String> {
+        let response = request.with_request_id().send().await.map_err(|e| format!("Meilisearch request failed: {}", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("Meilisearch returned {}: {}", status, body));
+        }
+        Ok(response)
+    }
+
+    // Searchable, filterable and sortable attributes (creates the index if needed)
+    async fn configure(&self) -> Result<(), String> {
+        let settings = json!({
+            "searchableAttributes": ["name", "brand", "category", "description"],
+            "filterableAttributes": ["category", "price", "in_stock"],
+            "sortableAttributes": ["price", "name", "created_at"],
+        });
+        let path = format!("/indexes/{}/settings", self.index);
+        self.send(self.request(reqwest::Method::PATCH, &path).json(&settings)).await?;
+        Ok(())
+    }
+
+    async fn upsert(&self, documents: &[SearchDocument]) -> Result<(), String> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+        let path = format!("/indexes/{}/documents?primaryKey=id", self.index);
+        self.send(self.request(reqwest::Method::POST, &path).json(documents)).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, ids: &[i32]) -> Result<(), String> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let path = format!("/indexes/{}/documents/delete-batch", self.index);
+        self.send(self.request(reqwest::Method::POST, &path).json(ids)).await?;
+        Ok(())
+    }
+
+    // Ids of every document in the index
+    async fn indexed_ids(&self) -> Result<Vec<i32>, String> {
+        let mut ids = Vec::new();
+        loop {
+            let path = format!(
+                "/indexes/{}/documents?fields=id&limit={}&offset={}",
+                self.index,
+                SYNC_BATCH_SIZE,
+                ids.len()
+            );
+            let response = self.request(reqwest::Method::GET, &path).with_request_id().send().await
+                .map_err(|e| format!("Meilisearch request failed: {}", e))?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(ids); // Index not created yet
+            }
+            if !response.status().is_success() {
+                return Err(format!("Meilisearch returned {}", response.status()));
+            }
+            let page: DocumentPage = response
+                .json()
+                .await
+                .map_err(|e| format!("Invalid Meilisearch response: {}", e))?;
+            let fetched = page.results.len();
+            ids.extend(page.results.into_iter().map(|hit| hit.id));
+            if fetched == 0 || ids.len() >= page.total {
+                return Ok(ids);
+            }
+        }
+    }
+
+    // Re-send every published product and drop documents for products that no longer are
+    pub async fn reindex(&self, pool: &sqlx::PgPool) -> Result<ReindexResult, String> {
+        self.configure().await?;
+        let documents = load_documents(pool, None).await.map_err(|e| format!("DB error: {}", e))?;
+        for batch in documents.chunks(SYNC_BATCH_SIZE) {
+            self.upsert(batch).await?;
+        }
+        let published: HashSet<i32> = documents.iter().map(|d| d.id).collect();
+        let stale: Vec<i32> = self
+            .indexed_ids()
+            .await?
+            .into_iter()
+            .filter(|id| !published.contains(id))
+            .collect();
+        for batch in stale.chunks(SYNC_BATCH_SIZE) {
+            self.delete(batch).await?;
+        }
+        Ok(ReindexResult { indexed: documents.len(), removed: stale.len() })
+    }
+
+    // Bring one product's document up to date, removing it when the product isn't published
+    async fn sync_product(&self, pool: &sqlx::PgPool, product_id: i32) -> Result<(), String> {
+        let documents = load_documents(pool, Some(&[product_id]))
+            .await
+            .map_err(|e| format!("DB error: {}", e))?;
+        if documents.is_empty() {
+            self.delete(&[product_id]).await
+        } else {
+            self.upsert(&documents).await
+        }
+    }
+
+    // Run a storefront search: one multi-search request for the page of matches and every facet
+    pub async fn search(&self, params: &SearchParams, sort: &str, limit: i64, offset: i64) -> Result<IndexedSearch, String> {
+        let q = params.q.as_deref().map(str::trim).unwrap_or("");
+        let facet_query = |skip: Facet, extra: Option<String>, facets: &[&str]| {
+            let mut filter = self.filters(params, skip);
+            filter.extend(extra);
+            json!({
+                "indexUid": self.index,
+                "q": q,
+                "filter": filter,
+                "facets": facets,
+                "hitsPerPage": 0,
+                "page": 1,
+            })
+        };
+
+        let sort: Vec<&str> = match sort {
+            "price_asc" => vec!["price:asc"],
+            "price_desc" => vec!["price:desc"],
+            "name_asc" => vec!["name:asc"],
+            "name_desc" => vec!["name:desc"],
+            "relevance" if !q.is_empty() => vec![],
+            _ => vec!["created_at:desc"],
+        };
+        let mut queries = vec![
+            json!({
+                "indexUid": self.index,
+                "q": q,
+                "filter": self.filters(params, Facet::None),
+                "sort": sort,
+                "limit": limit,
+                "offset": offset,
+                "attributesToRetrieve": ["id"],
+            }),
+            facet_query(Facet::Stock, None, &["in_stock"]),
+            facet_query(Facet::Category, None, &["category"]),
+            // Every category in the index, so empty ones are listed too
+            json!({ "indexUid": self.index, "q": "", "facets": ["category"], "hitsPerPage": 0, "page": 1 }),
+        ];
+        for bucket in 0..=PRICE_BUCKET_EDGES.len() {
+            let mut range = Vec::new();
+            if bucket > 0 {
+                range.push(format!("price >= {}", PRICE_BUCKET_EDGES[bucket - 1]));
+            }
+            if let Some(max) = PRICE_BUCKET_EDGES.get(bucket) {
+                range.push(format!("price < {}", max));
+            }
+            queries.push(facet_query(Facet::Price, Some(range.join(" AND ")), &[]));
+        }
+
+        let response: MultiSearchResponse = self
+            .send(self.request(reqwest::Method::POST, "/multi-search").json(&json!({ "queries": queries })))
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid Meilisearch response: {}", e))?;
+        let mut results = response.results.into_iter();
+        let mut next = || results.next().ok_or("Meilisearch returned too few results".to_string());
+
+        let ids = next()?.hits.into_iter().map(|hit| hit.id).collect();
+        let stock = next()?;
+        let count = |result: &SearchResult, facet: &str, value: &str| {
+            result
+                .facet_distribution
+                .get(facet)
+                .and_then(|values| values.get(value))
+                .copied()
+                .unwrap_or(0)
+        };
+        let in_stock = count(&stock, "in_stock", "true");
+        let out_of_stock = count(&stock, "in_stock", "false");
+        let total = if params.in_stock { in_stock } else { stock.total_hits.unwrap_or(0) };
+
+        let filtered = next()?;
+        let all = next()?;
+        let mut categories: Vec<(String, i64)> = all
+            .facet_distribution
+            .get("category")
+            .map(|values| values.keys().map(|c| (c.clone(), count(&filtered, "category", c))).collect())
+            .unwrap_or_default();
+        categories.sort();
+
+        let mut price_counts = Vec::new();
+        for bucket in 0..=PRICE_BUCKET_EDGES.len() {
+            price_counts.push((bucket as i32, next()?.total_hits.unwrap_or(0)));
+        }
+
+        Ok(IndexedSearch { ids, total, categories, price_counts, in_stock, out_of_stock })
+    }
+
+    // Meilisearch filter expressions for the request, leaving out one facet's own filter
+    fn filters(&self, params: &SearchParams, skip: Facet) -> Vec<String> {
+        let mut filters = Vec::new();
+        if skip != Facet::Category {
+            if let Some(category) = params.category.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+                filters.push(format!("category = \"{}\"", category.replace('\\', "\\\\").replace('"', "\\\"")));
+            }
+        }
+        if skip != Facet::Price {
+            if let Some(min) = params.min_price {
+                filters.push(format!("price >= {}", min));
+            }
+            if let Some(max) = params.max_price {
+                filters.push(format!("price < {}", max));
+            }
+        }
+        if skip != Facet::Stock && params.in_stock {
+            filters.push("in_stock = true".to_string());
+        }
+        filters
+    }
+}
+
+// Index documents for published products (all of them, or just `ids`)
+async fn load_documents(pool: &sqlx::PgPool, ids: Option<&[i32]>) -> Result<Vec<SearchDocument>, sqlx::Error> {
+    let mut documents = sqlx::query_as!(
+        SearchDocument,
+        r#"SELECT id, name, description, brand, category, slug, price, inventory > 0 AS "in_stock!",
+                EXTRACT(EPOCH FROM created_at)::BIGINT AS "created_ts!"
+         FROM products
+         WHERE status = 'published' AND ($1::INT[] IS NULL OR id = ANY($1))
+         ORDER BY id"#,
+        ids,
+    )
+    .fetch_all(pool)
+    .await?;
+    let book = PriceBook::load_active(pool).await?;
+    for document in &mut documents {
+        document.price = book.unit_price(document.id, document.price, 1);
+    }
+    Ok(documents)
+}
+
+pub fn search_index_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/search/reindex", post(reindex))
+        .with_state(app_state)
+}
+
+// Rebuild the index now, e.g. after pointing MEILISEARCH_URL at a new server
+async fn reindex(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<ReindexResult>, (StatusCode, String)> {
+    let index = app_state
+        .search_index
+        .as_ref()
+        .ok_or((StatusCode::BAD_REQUEST, "Meilisearch is not configured (MEILISEARCH_URL)".to_string()))?;
+    index
+        .reindex(&app_state.pool)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e))
+}
+
+// Background task that fully re-syncs the index at startup and every SEARCH_REINDEX_INTERVAL_SECS
+pub fn spawn_search_sync(state: Arc<AppState>) {
+    let Some(index) = state.search_index.clone() else {
+        return;
+    };
+    let interval_secs = app_config::get().search.reindex_interval_secs;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            match index.reindex(&state.pool).await {
+                Ok(result) => tracing::info!("Search index synced ({} products, {} removed)", result.indexed, result.removed),
+                Err(e) => tracing::error!("Failed to sync search index: {}", e),
+            }
+        }
+    });
+}
+
+// Keeps single products in the index up to date as they change
+pub struct SearchIndexSubscriber {
+    pool: Arc<sqlx::PgPool>,
+    index: Option<SearchIndex>,
+}
+
+impl SearchIndexSubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>, index: Option<SearchIndex>) -> Self {
+        Self { pool, index }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for SearchIndexSubscriber {
+    fn name(&self) -> &'static str {
+        "search_index"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let Some(index) = &self.index else {
+            return Ok(());
+        };
+        match event {
+            DomainEvent::ProductDeleted { product_id } => index.delete(&[*product_id]).await,
+            DomainEvent::ProductCreated { product_id }
+            | DomainEvent::ProductUpdated { product_id }
+            | DomainEvent::ProductBackInStock { product_id, .. }
+            | DomainEvent::LowStock { product_id, .. }
+            | DomainEvent::PriceDropped { product_id, .. } => index.sync_product(&self.pool, *product_id).await,
+            _ => Ok(()),
+        }
+    }
+}
+
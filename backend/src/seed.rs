@@ -0,0 +1,479 @@
+// Seed Module - `backend seed`: fills a development or demo database with a believable store
+// The store is described in a JSON spec (seed/demo_store.json, built in; `--file` for another):
+// categories and their products, customer names to combine, and how many historical orders
+// to generate over how many days. Products get a generated image in their category's color,
+// queued for the image worker like an upload, so the storefront, reports and admin UI have
+// something to show. Seeding is safe to repeat: products are matched by SKU and customers by
+// email and kept as they are, and orders are only generated while there are no seeded ones.
+// Generation is driven by `random_seed`, so every database seeded from one spec looks the same
+
+use chrono::{Duration, Utc};
+use rand::distributions::WeightedIndex;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use sqlx::types::Uuid;
+use std::collections::HashSet;
+use std::io::Cursor;
+
+use crate::pii::{self, Encrypted};
+use crate::{app_config, customers, product_images, product_slugs};
+
+const DEMO_STORE: &str = include_str!("../seed/demo_store.json");
+const SEEDED_BY: &str = "seed"; // orders.created_by and order_status_history.changed_by
+const IMAGE_SIZE: u32 = 800;
+
+#[derive(Debug, Deserialize)]
+pub struct SeedSpec {
+    pub random_seed: u64,
+    pub categories: Vec<CategorySpec>,
+    pub customers: CustomerSpec,
+    pub orders: OrderSpec,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CategorySpec {
+    pub name: String,
+    pub color: String, // #rrggbb, the background of the category's product images
+    pub products: Vec<ProductSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProductSpec {
+    pub name: String,
+    pub sku: String,
+    pub price: f64, // in dollars, like products.price
+    pub description: Option<String>,
+    pub brand: Option<String>,
+    pub inventory: i32,
+    pub weight_oz: Option<f64>,
+    #[serde(default = "default_popularity")]
+    pub popularity: u32, // Relative share of order lines
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CustomerSpec {
+    pub count: usize,
+    pub email_domain: String,
+    pub first_names: Vec<String>,
+    pub last_names: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrderSpec {
+    pub count: usize,
+    pub days: i64, // Orders are spread over this many days before today, busier towards today
+    pub max_lines: usize,
+    pub max_quantity: i32,
+    #[serde(default)]
+    pub refunded_percent: f64,
+    pub currency: String,
+    #[serde(default)]
+    pub campaigns: Vec<CampaignSpec>,
+}
+
+// UTM attribution for `percent` of orders, for the attribution report
+#[derive(Debug, Deserialize)]
+pub struct CampaignSpec {
+    pub source: String,
+    pub medium: String,
+    pub campaign: String,
+    pub percent: f64,
+}
+
+fn default_popularity() -> u32 {
+    1
+}
+
+// `backend seed [--file PATH] [--no-images] [--no-orders]`
+struct SeedArgs {
+    file: Option<String>,
+    images: bool,
+    orders: bool,
+}
+
+impl SeedArgs {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = SeedArgs { file: None, images: true, orders: true };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--file" => parsed.file = Some(args.next().ok_or("--file: expected a path")?),
+                "--no-images" => parsed.images = false,
+                "--no-orders" => parsed.orders = false,
+                "--profile" => {
+                    args.next(); // Read by app_config
+                }
+                other if other.starts_with("--profile=") => {}
+                other => return Err(format!("seed: unknown argument \"{}\"", other)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+// Whether the server was started as `backend seed ...`
+pub fn requested() -> bool {
+    std::env::args().nth(1).as_deref() == Some("seed")
+}
+
+// Seed the database from the command line's spec and exit; failures exit with status 1
+pub async fn run(pool: &sqlx::PgPool) {
+    if let Err(e) = seed_from_args(pool).await {
+        eprintln!("✗ Seeding failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn seed_from_args(pool: &sqlx::PgPool) -> Result<(), String> {
+    if app_config::get().is_live() {
+        return Err("refusing to seed a live store (live Stripe key or ENVIRONMENT_MODE=live)".to_string());
+    }
+    let args = SeedArgs::parse(std::env::args().skip(2))?;
+    let text = match &args.file {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?,
+        None => DEMO_STORE.to_string(),
+    };
+    let spec: SeedSpec = serde_json::from_str(&text).map_err(|e| format!("invalid seed spec: {}", e))?;
+    let problems = spec.validate();
+    if !problems.is_empty() {
+        return Err(format!("invalid seed spec:\n  - {}", problems.join("\n  - ")));
+    }
+    seed(pool, &spec, args.images, args.orders).await
+}
+
+impl SeedSpec {
+    // Every problem with the spec, so they can all be fixed at once
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut skus = HashSet::new();
+        for category in &self.categories {
+            if parse_color(&category.color).is_none() {
+                problems.push(format!("{}: color must be #rrggbb, got \"{}\"", category.name, category.color));
+            }
+            for product in &category.products {
+                if !skus.insert(product.sku.as_str()) {
+                    problems.push(format!("{}: duplicate sku", product.sku));
+                }
+                if product.price <= 0.0 {
+                    problems.push(format!("{}: price must be positive", product.sku));
+                }
+                if product.inventory < 0 {
+                    problems.push(format!("{}: inventory can't be negative", product.sku));
+                }
+            }
+        }
+        if self.customers.count > 0 && (self.customers.first_names.is_empty() || self.customers.last_names.is_empty()) {
+            problems.push("customers: first_names and last_names are required".to_string());
+        }
+        if self.orders.count > 0 {
+            if skus.is_empty() || self.customers.count == 0 {
+                problems.push("orders: need at least one product and one customer".to_string());
+            }
+            if self.orders.days < 1 || self.orders.max_lines < 1 || self.orders.max_quantity < 1 {
+                problems.push("orders: days, max_lines and max_quantity must be at least 1".to_string());
+            }
+            if self.orders.campaigns.iter().map(|c| c.percent).sum::<f64>() > 100.0 {
+                problems.push("orders: campaign percents add up to more than 100".to_string());
+            }
+        }
+        problems
+    }
+}
+
+fn parse_color(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.strip_prefix('#').filter(|h| h.len() == 6)?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+// A seeded product: what orders need to know about it
+struct SeededProduct {
+    id: i32,
+    name: String,
+    description: Option<String>,
+    sku: String,
+    price: i64, // in cents
+    popularity: u32,
+}
+
+struct SeededCustomer {
+    id: Uuid,
+    name: String,
+    email: String,
+}
+
+pub async fn seed(pool: &sqlx::PgPool, spec: &SeedSpec, images: bool, orders: bool) -> Result<(), String> {
+    let mut rng = StdRng::seed_from_u64(spec.random_seed);
+    let db_error = |e: sqlx::Error| format!("DB error: {}", e);
+
+    let mut products = Vec::new();
+    let (mut created, mut queued) = (0, 0);
+    for category in &spec.categories {
+        let color = parse_color(&category.color).unwrap_or([128, 128, 128]);
+        for (index, product) in category.products.iter().enumerate() {
+            let (seeded, is_new) = seed_product(pool, spec.orders.days, category, product).await.map_err(db_error)?;
+            if is_new {
+                created += 1;
+            }
+            if images && !has_images(pool, seeded.id).await.map_err(db_error)? {
+                let png = product_image(color, index);
+                product_images::add_image(pool, seeded.id, &png, Some(&product.name))
+                    .await
+                    .map_err(|(_, e)| e)?;
+                queued += 1;
+            }
+            products.push(seeded);
+        }
+    }
+    println!("✓ Seeded {} products ({} new) in {} categories", products.len(), created, spec.categories.len());
+    if queued > 0 {
+        println!("✓ Queued {} product images; the server's image worker renders them", queued);
+    }
+
+    let customers = seed_customers(pool, &spec.customers, &mut rng).await.map_err(db_error)?;
+    println!("✓ Seeded {} customers", customers.len());
+
+    if orders && spec.orders.count > 0 {
+        let already: bool = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM orders WHERE created_by = $1) AS "already!""#,
+            SEEDED_BY,
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(db_error)?;
+        if already {
+            println!("• Seeded orders already exist, not generating more");
+        } else {
+            let total = seed_orders(pool, &spec.orders, &products, &customers, &mut rng).await.map_err(db_error)?;
+            println!(
+                "✓ Seeded {} orders over {} days ({:.2} {} revenue)",
+                spec.orders.count,
+                spec.orders.days,
+                total as f64 / 100.0,
+                spec.orders.currency
+            );
+        }
+    }
+    Ok(())
+}
+
+// Insert the product unless its SKU exists; true when it was inserted
+async fn seed_product(
+    pool: &sqlx::PgPool,
+    days: i64,
+    category: &CategorySpec,
+    product: &ProductSpec,
+) -> Result<(SeededProduct, bool), sqlx::Error> {
+    let existing: Option<(i32, String, Option<String>, f64)> =
+        sqlx::query!("SELECT id, name, description, price FROM products WHERE sku = $1", &product.sku)
+            .map(|r| (r.id, r.name, r.description, r.price))
+            .fetch_optional(pool)
+            .await?;
+    let is_new = existing.is_none();
+    let (id, name, description, price) = match existing {
+        Some(existing) => existing,
+        None => {
+            let slug = product_slugs::assign_slug(pool, &product.name, None).await?.slug;
+            // Listed a month before the first order, so reports have a full history
+            let id: i32 = sqlx::query_scalar!(
+                "INSERT INTO products (name, description, price, inventory, sku, brand, category, weight_oz, slug, created_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW() - make_interval(days => $10))
+                 RETURNING id",
+                &product.name,
+                product.description.as_deref(),
+                product.price,
+                product.inventory,
+                &product.sku,
+                product.brand.as_deref(),
+                &category.name,
+                product.weight_oz,
+                &slug,
+                days as i32 + 30,
+            )
+            .fetch_one(pool)
+            .await?;
+            (id, product.name.clone(), product.description.clone(), product.price)
+        }
+    };
+    let seeded = SeededProduct {
+        id,
+        name,
+        description,
+        sku: product.sku.clone(),
+        price: (price * 100.0).round() as i64,
+        popularity: product.popularity.max(1),
+    };
+    Ok((seeded, is_new))
+}
+
+async fn has_images(pool: &sqlx::PgPool, product_id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM product_images WHERE product_id = $1) AS "exists!""#, product_id)
+        .fetch_one(pool)
+        .await
+}
+
+// A square PNG in the category color: a diagonal shade with a lighter disc, offset per product
+fn product_image(color: [u8; 3], index: usize) -> Vec<u8> {
+    let size = IMAGE_SIZE as f32;
+    let (cx, cy) = (size * (0.35 + 0.1 * (index % 4) as f32), size * (0.4 + 0.07 * (index % 3) as f32));
+    let radius = size * 0.22;
+    let image = image::RgbImage::from_fn(IMAGE_SIZE, IMAGE_SIZE, |x, y| {
+        let (x, y) = (x as f32, y as f32);
+        let shade = 0.8 + 0.3 * (x + y) / (2.0 * size);
+        let inside = (x - cx).powi(2) + (y - cy).powi(2) < radius.powi(2);
+        image::Rgb(color.map(|c| {
+            let c = c as f32 * shade;
+            (if inside { c + (255.0 - c) * 0.55 } else { c }).min(255.0) as u8
+        }))
+    });
+    let mut png = Vec::new();
+    image::DynamicImage::ImageRgb8(image)
+        .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .expect("encoding a PNG in memory");
+    png
+}
+
+// Distinct first/last name pairs with example emails, found or created by email
+async fn seed_customers(
+    pool: &sqlx::PgPool,
+    spec: &CustomerSpec,
+    rng: &mut StdRng,
+) -> Result<Vec<SeededCustomer>, sqlx::Error> {
+    let mut names: Vec<(&String, &String)> = spec
+        .first_names
+        .iter()
+        .flat_map(|first| spec.last_names.iter().map(move |last| (first, last)))
+        .collect();
+    names.shuffle(rng);
+    let mut seeded = Vec::new();
+    for (index, (first, last)) in names.iter().cycle().take(spec.count).enumerate() {
+        let round = index / names.len();
+        let local = format!("{}.{}", first, last).to_lowercase();
+        let email = match round {
+            0 => format!("{}@{}", local, spec.email_domain),
+            n => format!("{}{}@{}", local, n + 1, spec.email_domain),
+        };
+        let name = format!("{} {}", first, last);
+        let id = customers::upsert_customer(pool, &email, Some(&name)).await?;
+        seeded.push(SeededCustomer { id, name, email });
+    }
+    Ok(seeded)
+}
+
+// Completed (or later refunded) offline orders, all in one transaction; returns their total
+async fn seed_orders(
+    pool: &sqlx::PgPool,
+    spec: &OrderSpec,
+    products: &[SeededProduct],
+    customers: &[SeededCustomer],
+    rng: &mut StdRng,
+) -> Result<i64, sqlx::Error> {
+    let popularity = WeightedIndex::new(products.iter().map(|p| p.popularity)).expect("products have popularity");
+    let now = Utc::now();
+    let mut revenue = 0;
+    let mut tx = pool.begin().await?;
+    for _ in 0..spec.count {
+        // More recent days are busier, like a growing store
+        let days_ago = spec.days as f64 * (1.0 - rng.gen::<f64>().sqrt());
+        let placed_at = now - Duration::seconds((days_ago * 86_400.0) as i64);
+        // A few regulars place most of the orders
+        let customer = &customers[(rng.gen::<f64>().powf(1.5) * customers.len() as f64) as usize];
+
+        let lines = rng.gen_range(1..=spec.max_lines.min(products.len()));
+        let mut chosen: Vec<usize> = Vec::new();
+        while chosen.len() < lines {
+            let index = rng.sample(&popularity);
+            if !chosen.contains(&index) {
+                chosen.push(index);
+            }
+        }
+        let items: Vec<(&SeededProduct, i32)> = chosen
+            .iter()
+            .map(|&i| (&products[i], 1 + rng.gen_range(0..spec.max_quantity).min(rng.gen_range(0..spec.max_quantity))))
+            .collect();
+        let total: i64 = items.iter().map(|(p, quantity)| p.price * *quantity as i64).sum();
+
+        let mut roll = rng.gen::<f64>() * 100.0;
+        let campaign = spec.campaigns.iter().find(|c| {
+            roll -= c.percent;
+            roll < 0.0
+        });
+        let refunded = rng.gen::<f64>() * 100.0 < spec.refunded_percent;
+        let status = if refunded { "refunded" } else { "completed" };
+
+        let order_id: Uuid = sqlx::query_scalar!(
+            "INSERT INTO orders (payment_provider, payment_id, payment_method, created_by, customer_id, customer_email,
+                                 customer_email_hash, customer_name, total_amount, currency, status, livemode, utm_source,
+                                 utm_medium, utm_campaign, paid_at, created_at, updated_at)
+             VALUES ('manual', $1, 'offline', $2, $3, $4, $13, $5, $6, $7, $8, FALSE, $9, $10, $11, $12, $12, $12)
+             RETURNING id",
+            format!("seed_{}", Uuid::new_v4().simple()),
+            SEEDED_BY,
+            customer.id,
+            Encrypted::from(customer.email.as_str()) as _,
+            &customer.name,
+            total,
+            &spec.currency,
+            status,
+            campaign.map(|c| &c.source),
+            campaign.map(|c| &c.medium),
+            campaign.map(|c| &c.campaign),
+            placed_at,
+            pii::email_hash(&customer.email),
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        for (product, quantity) in &items {
+            sqlx::query!(
+                "INSERT INTO order_items (order_id, product_id, product_name, product_description, sku, quantity,
+                                          list_price, unit_price, total_price, discount_amount, created_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $7, $8, 0, $9)",
+                order_id,
+                product.id,
+                &product.name,
+                product.description.as_deref(),
+                &product.sku,
+                quantity,
+                product.price,
+                product.price * *quantity as i64,
+                placed_at,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let mut history = vec![(None, "completed", placed_at)];
+        if refunded {
+            let refunded_at = (placed_at + Duration::days(rng.gen_range(1..=10))).min(now);
+            history.push((Some("completed"), "refunded", refunded_at));
+        } else {
+            revenue += total;
+        }
+        for (from, to, at) in history {
+            sqlx::query!(
+                "INSERT INTO order_status_history (order_id, from_status, to_status, changed_by, created_at)
+                 VALUES ($1, $2, $3, $4, $5)",
+                order_id,
+                from,
+                to,
+                SEEDED_BY,
+                at,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    // Customers signed up no later than their first order
+    sqlx::query!(
+        "UPDATE customers c SET created_at = first.placed_at
+         FROM (SELECT customer_id, MIN(created_at) AS placed_at FROM orders WHERE created_by = $1 GROUP BY customer_id) first
+         WHERE c.id = first.customer_id AND c.created_at > first.placed_at",
+        SEEDED_BY,
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(revenue)
+}
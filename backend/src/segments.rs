@@ -0,0 +1,647 @@
+// Customer Segments Module - Segment builder over real purchase data
+// Segments filter customers by order count, total spend, last order date and tags,
+// are materialized into member rows and synced to Brevo, Mailchimp or Letre lists on a schedule
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::environment_mode::EnvironmentMode;
+use crate::marketing_providers;
+use crate::pii::{self, Encrypted};
+use crate::AppState;
+
+const REMOVE_BATCH: usize = 150;
+const PREVIEW_SAMPLE_SIZE: usize = 20;
+
+// Marketing provider a segment's membership is pushed to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
+pub enum SegmentProvider {
+    #[sqlx(rename = "brevo")]
+    #[serde(rename = "brevo")]
+    Brevo,
+    #[sqlx(rename = "mailchimp")]
+    #[serde(rename = "mailchimp")]
+    Mailchimp,
+    #[sqlx(rename = "letre")]
+    #[serde(rename = "letre")]
+    Letre,
+}
+
+impl SegmentProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SegmentProvider::Brevo => "brevo",
+            SegmentProvider::Mailchimp => "mailchimp",
+            SegmentProvider::Letre => "letre",
+        }
+    }
+}
+
+// Segment filters; None means "don't filter on this"
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SegmentFilters {
+    pub min_order_count: Option<i32>,
+    pub max_order_count: Option<i32>,
+    pub min_total_spend: Option<i64>, // in cents
+    pub max_total_spend: Option<i64>, // in cents
+    pub min_days_since_last_order: Option<i32>,
+    pub max_days_since_last_order: Option<i32>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+// Database model for customer segments
+#[derive(Debug, Clone, Serialize)]
+pub struct CustomerSegment {
+    pub id: i32,
+    pub name: String,
+    #[serde(flatten)]
+    pub filters: SegmentFilters,
+    pub provider: Option<SegmentProvider>,
+    pub provider_list_id: Option<String>,
+    pub member_count: i32,
+    pub materialized_at: Option<DateTime<Utc>>,
+    pub synced_at: Option<DateTime<Utc>>,
+    pub last_sync_error: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// A customer_segments row as query_as! reads it (it can't fill the flattened filters)
+struct SegmentRow {
+    id: i32,
+    name: String,
+    min_order_count: Option<i32>,
+    max_order_count: Option<i32>,
+    min_total_spend: Option<i64>,
+    max_total_spend: Option<i64>,
+    min_days_since_last_order: Option<i32>,
+    max_days_since_last_order: Option<i32>,
+    tags: Vec<String>,
+    provider: Option<SegmentProvider>,
+    provider_list_id: Option<String>,
+    member_count: i32,
+    materialized_at: Option<DateTime<Utc>>,
+    synced_at: Option<DateTime<Utc>>,
+    last_sync_error: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+impl From<SegmentRow> for CustomerSegment {
+    fn from(row: SegmentRow) -> Self {
+        CustomerSegment {
+            id: row.id,
+            name: row.name,
+            filters: SegmentFilters {
+                min_order_count: row.min_order_count,
+                max_order_count: row.max_order_count,
+                min_total_spend: row.min_total_spend,
+                max_total_spend: row.max_total_spend,
+                min_days_since_last_order: row.min_days_since_last_order,
+                max_days_since_last_order: row.max_days_since_last_order,
+                tags: row.tags,
+            },
+            provider: row.provider,
+            provider_list_id: row.provider_list_id,
+            member_count: row.member_count,
+            materialized_at: row.materialized_at,
+            synced_at: row.synced_at,
+            last_sync_error: row.last_sync_error,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+// query_as! for customer_segments rows, with the columns between `$before` and `$after` (see product_query!)
+macro_rules! segment_query {
+    ($before:literal, $after:literal $(, $arg:expr)* $(,)?) => {
+        sqlx::query_as!(
+            SegmentRow,
+            $before
+                + " id, name, min_order_count, max_order_count, min_total_spend, max_total_spend,
+                    min_days_since_last_order, max_days_since_last_order, tags,
+                    provider AS \"provider: SegmentProvider\", provider_list_id, member_count,
+                    materialized_at, synced_at, last_sync_error, created_at, updated_at "
+                + $after
+            $(, $arg)*
+        )
+    };
+}
+
+#[derive(Deserialize)]
+pub struct CustomerSegmentInput {
+    pub name: String,
+    #[serde(flatten)]
+    pub filters: SegmentFilters,
+    pub provider: Option<SegmentProvider>,
+    pub provider_list_id: Option<String>,
+}
+
+// Customer matching a segment, with the stats the filters were applied to
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct SegmentMatch {
+    pub customer_id: Uuid,
+    pub email: Encrypted,
+    pub name: Option<String>,
+    pub order_count: i64,
+    pub total_spend: i64, // in cents
+    pub last_order_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct SegmentPreview {
+    pub member_count: usize,
+    pub sample: Vec<SegmentMatch>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SegmentMember {
+    pub customer_id: Uuid,
+    pub email: Encrypted,
+    pub added_at: Option<DateTime<Utc>>,
+    pub synced_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SegmentRefreshResult {
+    pub member_count: i32,
+    pub added: usize,
+    pub removed: usize,
+    pub synced_added: usize,
+    pub synced_removed: usize,
+    pub sync_error: Option<String>,
+}
+
+// Trimmed, lowercase, de-duplicated tags (same normalization as customer tags)
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = tags
+        .iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    normalized.sort();
+    normalized.dedup();
+    normalized
+}
+
+fn validate_input(input: &CustomerSegmentInput) -> Result<(), String> {
+    if input.name.trim().is_empty() {
+        return Err("name is required".to_string());
+    }
+    if input.provider.is_some() && input.provider_list_id.as_deref().is_none_or(|l| l.trim().is_empty()) {
+        return Err("provider_list_id is required when a provider is set".to_string());
+    }
+    if input.provider == Some(SegmentProvider::Brevo)
+        && input.provider_list_id.as_deref().is_some_and(|l| l.trim().parse::<i64>().is_err())
+    {
+        return Err("Brevo provider_list_id must be a numeric list id".to_string());
+    }
+    Ok(())
+}
+
+// Active (not disabled) customers matching the filters, biggest spenders first
+pub async fn matching_customers(pool: &sqlx::PgPool, filters: &SegmentFilters) -> Result<Vec<SegmentMatch>, sqlx::Error> {
+    sqlx::query_as!(
+        SegmentMatch,
+        r#"WITH stats AS (
+             SELECT c.id AS customer_id, c.email, c.name,
+                    COUNT(o.id) AS order_count,
+                    COALESCE(SUM(o.total_amount), 0)::BIGINT AS total_spend,
+                    MAX(o.created_at) AS last_order_at
+             FROM customers c
+             LEFT JOIN orders o ON o.customer_id = c.id AND o.status = 'completed' AND o.livemode = $8
+             WHERE c.disabled = FALSE AND c.tags @> $1
+             GROUP BY c.id
+         )
+         SELECT customer_id, email AS "email: Encrypted", name, order_count AS "order_count!", total_spend AS "total_spend!", last_order_at FROM stats
+         WHERE ($2::INT IS NULL OR order_count >= $2)
+           AND ($3::INT IS NULL OR order_count <= $3)
+           AND ($4::BIGINT IS NULL OR total_spend >= $4)
+           AND ($5::BIGINT IS NULL OR total_spend <= $5)
+           AND ($6::INT IS NULL OR last_order_at <= NOW() - make_interval(days => $6))
+           AND ($7::INT IS NULL OR last_order_at >= NOW() - make_interval(days => $7))
+         ORDER BY total_spend DESC, customer_id"#,
+        &normalize_tags(&filters.tags),
+        filters.min_order_count,
+        filters.max_order_count,
+        filters.min_total_spend,
+        filters.max_total_spend,
+        filters.min_days_since_last_order,
+        filters.max_days_since_last_order,
+        EnvironmentMode::from_config().is_live(),
+    ) // Test orders never count towards a segment
+    .fetch_all(pool)
+    .await
+}
+
+// Recompute a segment's membership; leavers stay flagged until removed from the provider list
+pub async fn materialize_segment(pool: &sqlx::PgPool, segment: &CustomerSegment) -> Result<SegmentRefreshResult, sqlx::Error> {
+    let matches = matching_customers(pool, &segment.filters).await?;
+    let ids: Vec<Uuid> = matches.iter().map(|m| m.customer_id).collect();
+    // Arrays are bound as plain TEXT[], so the emails are encrypted here
+    let emails: Vec<String> = matches.iter().map(|m| pii::encrypt(&m.email)).collect();
+    let email_hashes: Vec<String> = matches.iter().map(|m| pii::email_hash(&m.email)).collect();
+
+    let mut tx = pool.begin().await?;
+    let removed = sqlx::query!(
+        "UPDATE customer_segment_members SET removed_at = NOW()
+         WHERE segment_id = $1 AND removed_at IS NULL AND customer_id <> ALL($2)",
+        segment.id,
+        &ids,
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected() as usize;
+
+    let added: i64 = sqlx::query_scalar!(
+        r#"WITH upserted AS (
+             INSERT INTO customer_segment_members (segment_id, customer_id, email, email_hash)
+             SELECT $1, * FROM UNNEST($2::UUID[], $3::TEXT[], $4::TEXT[])
+             ON CONFLICT (segment_id, customer_id) DO UPDATE
+                 SET email = EXCLUDED.email,
+                     email_hash = EXCLUDED.email_hash,
+                     removed_at = NULL,
+                     synced_at = CASE WHEN customer_segment_members.removed_at IS NULL
+                                           AND customer_segment_members.email_hash = EXCLUDED.email_hash
+                                      THEN customer_segment_members.synced_at END
+             RETURNING (xmax = 0) AS inserted
+         )
+         SELECT COUNT(*) AS "added!" FROM upserted WHERE inserted"#,
+        segment.id,
+        &ids,
+        &emails,
+        &email_hashes,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // Without a provider there is no list to clean up
+    if segment.provider.is_none() {
+        sqlx::query!(
+            "DELETE FROM customer_segment_members WHERE segment_id = $1 AND removed_at IS NOT NULL",
+            segment.id,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query!(
+        "UPDATE customer_segments SET member_count = $1, materialized_at = NOW() WHERE id = $2",
+        matches.len() as i32,
+        segment.id,
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(SegmentRefreshResult {
+        member_count: matches.len() as i32,
+        added: added as usize,
+        removed,
+        ..Default::default()
+    })
+}
+
+// Push pending membership changes to the segment's provider list.
+// Returns (added, removed) counts; partial progress is kept on failure.
+pub async fn sync_segment(pool: &sqlx::PgPool, segment: &CustomerSegment) -> Result<(usize, usize), String> {
+    let (provider, list_id) = match (segment.provider, segment.provider_list_id.as_deref()) {
+        (Some(provider), Some(list_id)) => (provider, list_id.trim()),
+        _ => return Ok((0, 0)),
+    };
+    let db_err = |e: sqlx::Error| format!("Database error: {}", e);
+
+    let to_add: Vec<(Uuid, Encrypted)> = sqlx::query!(
+        r#"SELECT customer_id, email AS "email: Encrypted" FROM customer_segment_members
+         WHERE segment_id = $1 AND removed_at IS NULL AND synced_at IS NULL"#,
+        segment.id,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(db_err)?
+    .into_iter()
+    .map(|r| (r.customer_id, r.email))
+    .collect();
+    let to_remove: Vec<(Uuid, Encrypted)> = sqlx::query!(
+        r#"SELECT customer_id, email AS "email: Encrypted" FROM customer_segment_members
+         WHERE segment_id = $1 AND removed_at IS NOT NULL"#,
+        segment.id,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(db_err)?
+    .into_iter()
+    .map(|r| (r.customer_id, r.email))
+    .collect();
+
+    let client = marketing_providers::provider(provider.as_str())?;
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let result = async {
+        for (customer_id, email) in &to_add {
+            client.add_contact(Some(list_id), email, None).await?;
+            added.push(*customer_id);
+        }
+        for batch in to_remove.chunks(REMOVE_BATCH) {
+            let emails: Vec<String> = batch.iter().map(|(_, email)| email.to_string()).collect();
+            client.remove_contacts(Some(list_id), &emails).await?;
+            removed.extend(batch.iter().map(|(customer_id, _)| *customer_id));
+        }
+        Ok::<(), String>(())
+    }
+    .await;
+
+    // Record whatever made it to the provider, even if a later call failed
+    sqlx::query!(
+        "UPDATE customer_segment_members SET synced_at = NOW() WHERE segment_id = $1 AND customer_id = ANY($2)",
+        segment.id,
+        &added,
+    )
+    .execute(pool)
+    .await
+    .map_err(db_err)?;
+    sqlx::query!(
+        "DELETE FROM customer_segment_members WHERE segment_id = $1 AND customer_id = ANY($2) AND removed_at IS NOT NULL",
+        segment.id,
+        &removed,
+    )
+    .execute(pool)
+    .await
+    .map_err(db_err)?;
+
+    result.map(|_| (added.len(), removed.len()))
+}
+
+// Materialize and sync one segment, recording the sync outcome on the segment
+pub async fn refresh_segment(pool: &sqlx::PgPool, segment: &CustomerSegment) -> Result<SegmentRefreshResult, sqlx::Error> {
+    let mut result = materialize_segment(pool, segment).await?;
+    if segment.provider.is_none() {
+        return Ok(result);
+    }
+
+    let sync_error = match sync_segment(pool, segment).await {
+        Ok((synced_added, synced_removed)) => {
+            result.synced_added = synced_added;
+            result.synced_removed = synced_removed;
+            None
+        }
+        Err(e) => Some(e),
+    };
+    sqlx::query!(
+        "UPDATE customer_segments
+         SET synced_at = CASE WHEN $1::TEXT IS NULL THEN NOW() ELSE synced_at END, last_sync_error = $1
+         WHERE id = $2",
+        sync_error.as_deref(),
+        segment.id,
+    )
+    .execute(pool)
+    .await?;
+    result.sync_error = sync_error;
+    Ok(result)
+}
+
+// Every segment, oldest first
+pub async fn all_segments(pool: &sqlx::PgPool) -> Result<Vec<CustomerSegment>, sqlx::Error> {
+    let rows = segment_query!("SELECT", "FROM customer_segments ORDER BY id").fetch_all(pool).await?;
+    Ok(rows.into_iter().map(CustomerSegment::from).collect())
+}
+
+// Refresh every segment and sync it to its provider list (the refresh_segments scheduled task)
+pub async fn refresh_all(pool: &sqlx::PgPool) -> Result<String, String> {
+    let segments = all_segments(pool)
+        .await
+        .map_err(|e| format!("Failed to load customer segments: {}", e))?;
+    let mut failed = 0;
+    for segment in &segments {
+        match refresh_segment(pool, segment).await {
+            Ok(SegmentRefreshResult { sync_error: Some(e), .. }) => {
+                failed += 1;
+                tracing::error!("Segment '{}' sync failed: {}", segment.name, e)
+            }
+            Ok(r) if r.added + r.removed > 0 => tracing::info!(
+                "Segment '{}' refreshed: {} members (+{} / -{})",
+                segment.name, r.member_count, r.added, r.removed
+            ),
+            Ok(_) => {}
+            Err(e) => {
+                failed += 1;
+                tracing::error!("Failed to refresh segment '{}': {}", segment.name, e)
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{} of {} segments failed to refresh or sync", failed, segments.len()));
+    }
+    Ok(format!("Refreshed {} segments", segments.len()))
+}
+
+// Admin segment builder routes
+pub fn segment_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/segments", get(list_segments).post(create_segment))
+        .route("/api/admin/segments/preview", post(preview_segment))
+        .route("/api/admin/segments/:id", get(get_segment).put(update_segment).delete(delete_segment))
+        .route("/api/admin/segments/:id/members", get(list_segment_members))
+        .route("/api/admin/segments/:id/refresh", post(refresh_segment_now))
+        .with_state(app_state)
+}
+
+async fn load_segment(pool: &sqlx::PgPool, id: i32) -> Result<CustomerSegment, (StatusCode, String)> {
+    segment_query!("SELECT", "FROM customer_segments WHERE id = $1", id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .map(CustomerSegment::from)
+        .ok_or((StatusCode::NOT_FOUND, "Segment not found".to_string()))
+}
+
+async fn list_segments(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<CustomerSegment>>, (StatusCode, String)> {
+    let segments = segment_query!("SELECT", "FROM customer_segments ORDER BY name")
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(segments.into_iter().map(CustomerSegment::from).collect()))
+}
+
+async fn get_segment(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<CustomerSegment>, (StatusCode, String)> {
+    Ok(Json(load_segment(&app_state.pool, id).await?))
+}
+
+// Count and sample the customers a set of filters would select, without saving
+async fn preview_segment(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(filters): Json<SegmentFilters>,
+) -> Result<Json<SegmentPreview>, (StatusCode, String)> {
+    let mut matches = matching_customers(&app_state.pool, &filters)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let member_count = matches.len();
+    matches.truncate(PREVIEW_SAMPLE_SIZE);
+    Ok(Json(SegmentPreview { member_count, sample: matches }))
+}
+
+async fn create_segment(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<CustomerSegmentInput>,
+) -> Result<(StatusCode, Json<CustomerSegment>), (StatusCode, String)> {
+    validate_input(&input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let segment: CustomerSegment = segment_query!(
+        "INSERT INTO customer_segments
+            (name, min_order_count, max_order_count, min_total_spend, max_total_spend,
+             min_days_since_last_order, max_days_since_last_order, tags, provider, provider_list_id)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING",
+        "",
+        input.name.trim(),
+        input.filters.min_order_count,
+        input.filters.max_order_count,
+        input.filters.min_total_spend,
+        input.filters.max_total_spend,
+        input.filters.min_days_since_last_order,
+        input.filters.max_days_since_last_order,
+        &normalize_tags(&input.filters.tags),
+        input.provider as _,
+        input.provider_list_id.as_deref().map(str::trim),
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?
+    .into();
+
+    // Materialize right away so the admin sees the member count; the scheduler syncs it
+    let segment = match materialize_segment(&app_state.pool, &segment).await {
+        Ok(_) => load_segment(&app_state.pool, segment.id).await?,
+        Err(e) => {
+            tracing::error!("Failed to materialize segment '{}': {}", segment.name, e);
+            segment
+        }
+    };
+    Ok((StatusCode::CREATED, Json(segment)))
+}
+
+async fn update_segment(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(input): Json<CustomerSegmentInput>,
+) -> Result<Json<CustomerSegment>, (StatusCode, String)> {
+    validate_input(&input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let existing = load_segment(&app_state.pool, id).await?;
+
+    let mut tx = app_state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    let segment: CustomerSegment = segment_query!(
+        "UPDATE customer_segments
+         SET name = $1, min_order_count = $2, max_order_count = $3, min_total_spend = $4, max_total_spend = $5,
+             min_days_since_last_order = $6, max_days_since_last_order = $7, tags = $8,
+             provider = $9, provider_list_id = $10, updated_at = NOW()
+         WHERE id = $11 RETURNING",
+        "",
+        input.name.trim(),
+        input.filters.min_order_count,
+        input.filters.max_order_count,
+        input.filters.min_total_spend,
+        input.filters.max_total_spend,
+        input.filters.min_days_since_last_order,
+        input.filters.max_days_since_last_order,
+        &normalize_tags(&input.filters.tags),
+        input.provider as _,
+        input.provider_list_id.as_deref().map(str::trim),
+        id,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?
+    .into();
+
+    // A new destination list starts empty: push every member again and forget pending removals
+    if existing.provider != segment.provider || existing.provider_list_id != segment.provider_list_id {
+        sqlx::query!("DELETE FROM customer_segment_members WHERE segment_id = $1 AND removed_at IS NOT NULL", id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+        sqlx::query!("UPDATE customer_segment_members SET synced_at = NULL WHERE segment_id = $1", id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    }
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    materialize_segment(&app_state.pool, &segment)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(load_segment(&app_state.pool, id).await?))
+}
+
+async fn delete_segment(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM customer_segments WHERE id = $1", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if res.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Segment not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_segment_members(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<SegmentMember>>, (StatusCode, String)> {
+    load_segment(&app_state.pool, id).await?;
+    let mut members = sqlx::query_as!(
+        SegmentMember,
+        r#"SELECT customer_id, email AS "email: Encrypted", added_at, synced_at FROM customer_segment_members
+         WHERE segment_id = $1 AND removed_at IS NULL"#,
+        id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    // Encrypted emails don't sort in SQL
+    members.sort_by(|a, b| a.email.0.cmp(&b.email.0));
+    Ok(Json(members))
+}
+
+// Materialize and sync a segment immediately instead of waiting for the scheduler
+async fn refresh_segment_now(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<SegmentRefreshResult>, (StatusCode, String)> {
+    let segment = load_segment(&app_state.pool, id).await?;
+    let result = refresh_segment(&app_state.pool, &segment)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(result))
+}
@@ -0,0 +1,484 @@
+// Shipping Rules Module - Zone-based shipping rates
+// Zones match a destination by country, state and postal code prefix; each zone
+// offers flat-rate methods with per-item surcharges and free-shipping thresholds.
+// This is the default quoting backend; EasyPost live rates are optional.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::easypost_shipping::{
+    ensure_ships_internationally, is_international, live_cart_rates, Address, CartRatesRequest, RateSelection,
+};
+use crate::pricing::{cart_total_cents, CartLine};
+use crate::AppState;
+
+// Quoting configuration
+pub struct ShippingRulesConfig {
+    pub provider: String,         // "rules" (default) or "easypost"
+    pub fallback_flat_rate: f64,  // Charged when no zone matches the destination
+}
+
+impl ShippingRulesConfig {
+    pub fn from_env() -> Self {
+        let shipping = &app_config::get().shipping;
+        Self {
+            provider: shipping.rate_provider.clone(),
+            fallback_flat_rate: shipping.fallback_flat_rate,
+        }
+    }
+}
+
+// Database model for shipping zones
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ShippingZone {
+    pub id: i32,
+    pub name: String,
+    pub countries: Vec<String>,
+    pub states: Vec<String>,
+    pub zip_prefixes: Vec<String>,
+    pub priority: i32,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct ShippingZoneInput {
+    pub name: String,
+    #[serde(default)]
+    pub countries: Vec<String>,
+    #[serde(default)]
+    pub states: Vec<String>,
+    #[serde(default)]
+    pub zip_prefixes: Vec<String>,
+    pub priority: Option<i32>,
+}
+
+// Database model for shipping methods offered within a zone
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ShippingMethod {
+    pub id: i32,
+    pub zone_id: i32,
+    pub name: String,
+    pub base_rate: f64,
+    pub per_item_surcharge: f64,
+    pub free_over: Option<f64>,
+    pub min_delivery_days: Option<i32>,
+    pub max_delivery_days: Option<i32>,
+    pub active: bool,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct ShippingMethodInput {
+    pub name: String,
+    pub base_rate: f64,
+    pub per_item_surcharge: Option<f64>,
+    pub free_over: Option<f64>,
+    pub min_delivery_days: Option<i32>,
+    pub max_delivery_days: Option<i32>,
+    pub active: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct ShippingQuoteRequest {
+    pub to_address: Address,
+    pub items: Vec<CartLine>,
+    pub provider: Option<String>, // Overrides SHIPPING_RATE_PROVIDER for this quote
+    #[serde(flatten)]
+    pub selection: RateSelection, // Carrier accounts / services for EasyPost quotes
+}
+
+// A shipping choice presented at checkout, regardless of which backend priced it
+#[derive(Debug, Clone, Serialize)]
+pub struct ShippingOption {
+    pub provider: String,
+    pub method_id: Option<i32>,
+    pub carrier: Option<String>,
+    pub service: String,
+    pub rate: String,
+    pub currency: String,
+    pub min_delivery_days: Option<i32>,
+    pub max_delivery_days: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct ShippingQuoteResponse {
+    pub success: bool,
+    pub provider: String,
+    pub zone: Option<String>,
+    pub subtotal: String,
+    pub options: Vec<ShippingOption>,
+}
+
+// Normalize a list of region codes for case-insensitive matching
+fn normalize_codes(codes: Vec<String>) -> Vec<String> {
+    codes
+        .into_iter()
+        .map(|c| c.trim().to_uppercase())
+        .filter(|c| !c.is_empty())
+        .collect()
+}
+
+impl ShippingZone {
+    // Empty criteria match any destination
+    pub fn matches(&self, destination: &Address) -> bool {
+        let country = destination.country.as_deref().unwrap_or("US").trim().to_uppercase();
+        let state = destination.state.trim().to_uppercase();
+        let zip = destination.zip.trim().to_uppercase();
+
+        (self.countries.is_empty() || self.countries.contains(&country))
+            && (self.states.is_empty() || self.states.contains(&state))
+            && (self.zip_prefixes.is_empty() || self.zip_prefixes.iter().any(|p| zip.starts_with(p.as_str())))
+    }
+
+    // More specific zones win ties on priority
+    fn specificity(&self) -> usize {
+        self.countries.len().min(1) + self.states.len().min(1) * 2 + self.zip_prefixes.len().min(1) * 4
+    }
+}
+
+impl ShippingMethod {
+    // Price for a cart with the given subtotal (dollars) and unit count
+    pub fn price(&self, subtotal: f64, item_count: i32) -> f64 {
+        if self.free_over.is_some_and(|threshold| subtotal >= threshold) {
+            return 0.0;
+        }
+        self.base_rate + self.per_item_surcharge * item_count.max(0) as f64
+    }
+}
+
+// Find the highest priority zone covering a destination
+pub async fn find_zone(pool: &sqlx::PgPool, destination: &Address) -> Result<Option<ShippingZone>, sqlx::Error> {
+    let zones = sqlx::query_as!(ShippingZone, "SELECT id, name, countries, states, zip_prefixes, priority, created_at FROM shipping_zones ORDER BY priority DESC, id")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(zones
+        .into_iter()
+        .filter(|z| z.matches(destination))
+        .max_by(|a, b| {
+            a.priority
+                .cmp(&b.priority)
+                .then(a.specificity().cmp(&b.specificity()))
+                .then(b.id.cmp(&a.id))
+        }))
+}
+
+// Quote a cart against the configured zones, falling back to a flat rate
+pub async fn quote_rules(
+    pool: &sqlx::PgPool,
+    destination: &Address,
+    lines: &[CartLine],
+) -> Result<ShippingQuoteResponse, String> {
+    let config = ShippingRulesConfig::from_env();
+    let subtotal = cart_total_cents(pool, lines).await? as f64 / 100.0;
+    let item_count: i32 = lines.iter().map(|l| l.quantity).sum();
+
+    let zone = find_zone(pool, destination)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    let mut options = Vec::new();
+    if let Some(zone) = &zone {
+        let methods = sqlx::query_as!(
+            ShippingMethod,
+            "SELECT id, zone_id, name, base_rate, per_item_surcharge, free_over, min_delivery_days, max_delivery_days, active, created_at FROM shipping_methods WHERE zone_id = $1 AND active = TRUE ORDER BY base_rate, id",
+            zone.id,
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+
+        options = methods
+            .iter()
+            .map(|m| ShippingOption {
+                provider: "rules".to_string(),
+                method_id: Some(m.id),
+                carrier: None,
+                service: m.name.clone(),
+                rate: format!("{:.2}", m.price(subtotal, item_count)),
+                currency: "USD".to_string(),
+                min_delivery_days: m.min_delivery_days,
+                max_delivery_days: m.max_delivery_days,
+            })
+            .collect();
+    }
+
+    // No zone or no active methods: offer the flat-rate fallback
+    if options.is_empty() {
+        options.push(ShippingOption {
+            provider: "rules".to_string(),
+            method_id: None,
+            carrier: None,
+            service: "Standard".to_string(),
+            rate: format!("{:.2}", config.fallback_flat_rate),
+            currency: "USD".to_string(),
+            min_delivery_days: None,
+            max_delivery_days: None,
+        });
+    }
+
+    options.sort_by(|a, b| {
+        let a: f64 = a.rate.parse().unwrap_or(f64::MAX);
+        let b: f64 = b.rate.parse().unwrap_or(f64::MAX);
+        a.total_cmp(&b)
+    });
+
+    Ok(ShippingQuoteResponse {
+        success: true,
+        provider: "rules".to_string(),
+        zone: zone.map(|z| z.name),
+        subtotal: format!("{:.2}", subtotal),
+        options,
+    })
+}
+
+// Public quote route and admin zone/method routes
+pub fn shipping_rules_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/shipping/quote", post(quote_shipping))
+        .route("/api/admin/shipping/zones", get(list_zones).post(create_zone))
+        .route("/api/admin/shipping/zones/:id", put(update_zone).delete(delete_zone))
+        .route("/api/admin/shipping/zones/:id/methods", get(list_methods).post(create_method))
+        .route("/api/admin/shipping/methods/:id", put(update_method).delete(delete_method))
+        .with_state(app_state)
+}
+
+// Quote shipping for a cart using the rules engine, or EasyPost when selected
+async fn quote_shipping(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ShippingQuoteRequest>,
+) -> Result<Json<ShippingQuoteResponse>, (StatusCode, String)> {
+    if payload.items.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Cart is empty".to_string()));
+    }
+    if is_international(None, &payload.to_address) {
+        ensure_ships_internationally(&state.pool, &payload.items)
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+
+    let provider = payload
+        .provider
+        .clone()
+        .map(|p| p.to_lowercase())
+        .unwrap_or_else(|| ShippingRulesConfig::from_env().provider);
+
+    if provider == "easypost" && state.shipping_config().is_some() {
+        let request = CartRatesRequest {
+            from_address: None,
+            to_address: payload.to_address.clone(),
+            items: payload.items.clone(),
+            selection: payload.selection.clone(),
+        };
+        match live_cart_rates(&state, &request).await {
+            Ok(live) if !live.rates.is_empty() => {
+                let subtotal = cart_total_cents(&state.pool, &payload.items)
+                    .await
+                    .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+                let options = live
+                    .rates
+                    .into_iter()
+                    .map(|r| ShippingOption {
+                        provider: "easypost".to_string(),
+                        method_id: None,
+                        carrier: Some(r.carrier),
+                        service: r.service,
+                        rate: r.rate,
+                        currency: r.currency,
+                        min_delivery_days: r.delivery_days,
+                        max_delivery_days: r.delivery_days,
+                    })
+                    .collect();
+                return Ok(Json(ShippingQuoteResponse {
+                    success: true,
+                    provider: "easypost".to_string(),
+                    zone: None,
+                    subtotal: format!("{:.2}", subtotal as f64 / 100.0),
+                    options,
+                }));
+            }
+            Ok(_) => tracing::error!("EasyPost returned no rates, using shipping rules"),
+            Err((_, e)) => tracing::error!("EasyPost quote failed, using shipping rules: {}", e),
+        }
+    }
+
+    quote_rules(&state.pool, &payload.to_address, &payload.items)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+async fn list_zones(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ShippingZone>>, (StatusCode, String)> {
+    let zones = sqlx::query_as!(ShippingZone, "SELECT id, name, countries, states, zip_prefixes, priority, created_at FROM shipping_zones ORDER BY priority DESC, id")
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(zones))
+}
+
+async fn create_zone(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<ShippingZoneInput>,
+) -> Result<(StatusCode, Json<ShippingZone>), (StatusCode, String)> {
+    let rec = sqlx::query_as!(
+        ShippingZone,
+        "INSERT INTO shipping_zones (name, countries, states, zip_prefixes, priority)
+         VALUES ($1, $2, $3, $4, $5) RETURNING id, name, countries, states, zip_prefixes, priority, created_at",
+        &input.name,
+        &normalize_codes(input.countries),
+        &normalize_codes(input.states),
+        &normalize_codes(input.zip_prefixes),
+        input.priority.unwrap_or(0),
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?;
+    Ok((StatusCode::CREATED, Json(rec)))
+}
+
+async fn update_zone(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(input): Json<ShippingZoneInput>,
+) -> Result<Json<ShippingZone>, (StatusCode, String)> {
+    let rec = sqlx::query_as!(
+        ShippingZone,
+        "UPDATE shipping_zones SET name = $1, countries = $2, states = $3, zip_prefixes = $4, priority = $5
+         WHERE id = $6 RETURNING id, name, countries, states, zip_prefixes, priority, created_at",
+        &input.name,
+        &normalize_codes(input.countries),
+        &normalize_codes(input.states),
+        &normalize_codes(input.zip_prefixes),
+        input.priority.unwrap_or(0),
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Shipping zone not found".to_string()))?;
+    Ok(Json(rec))
+}
+
+async fn delete_zone(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<bool>, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM shipping_zones WHERE id = $1", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(res.rows_affected() > 0))
+}
+
+// Reject rates and delivery windows that can't be quoted
+fn validate_method(input: &ShippingMethodInput) -> Result<(), (StatusCode, String)> {
+    if input.base_rate < 0.0 || input.per_item_surcharge.unwrap_or(0.0) < 0.0 {
+        return Err((StatusCode::BAD_REQUEST, "Rates must not be negative".to_string()));
+    }
+    if let (Some(min), Some(max)) = (input.min_delivery_days, input.max_delivery_days) {
+        if max < min {
+            return Err((StatusCode::BAD_REQUEST, "max_delivery_days must not be less than min_delivery_days".to_string()));
+        }
+    }
+    Ok(())
+}
+
+async fn list_methods(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(zone_id): Path<i32>,
+) -> Result<Json<Vec<ShippingMethod>>, (StatusCode, String)> {
+    let methods = sqlx::query_as!(
+        ShippingMethod,
+        "SELECT id, zone_id, name, base_rate, per_item_surcharge, free_over, min_delivery_days, max_delivery_days, active, created_at FROM shipping_methods WHERE zone_id = $1 ORDER BY base_rate, id",
+        zone_id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(methods))
+}
+
+async fn create_method(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(zone_id): Path<i32>,
+    Json(input): Json<ShippingMethodInput>,
+) -> Result<(StatusCode, Json<ShippingMethod>), (StatusCode, String)> {
+    validate_method(&input)?;
+
+    let rec = sqlx::query_as!(
+        ShippingMethod,
+        "INSERT INTO shipping_methods
+            (zone_id, name, base_rate, per_item_surcharge, free_over, min_delivery_days, max_delivery_days, active)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id, zone_id, name, base_rate, per_item_surcharge, free_over, min_delivery_days, max_delivery_days, active, created_at",
+        zone_id,
+        &input.name,
+        input.base_rate,
+        input.per_item_surcharge.unwrap_or(0.0),
+        input.free_over,
+        input.min_delivery_days,
+        input.max_delivery_days,
+        input.active.unwrap_or(true),
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?;
+    Ok((StatusCode::CREATED, Json(rec)))
+}
+
+async fn update_method(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(input): Json<ShippingMethodInput>,
+) -> Result<Json<ShippingMethod>, (StatusCode, String)> {
+    validate_method(&input)?;
+
+    let rec = sqlx::query_as!(
+        ShippingMethod,
+        "UPDATE shipping_methods SET name = $1, base_rate = $2, per_item_surcharge = $3, free_over = $4,
+            min_delivery_days = $5, max_delivery_days = $6, active = $7
+         WHERE id = $8 RETURNING id, zone_id, name, base_rate, per_item_surcharge, free_over, min_delivery_days, max_delivery_days, active, created_at",
+        &input.name,
+        input.base_rate,
+        input.per_item_surcharge.unwrap_or(0.0),
+        input.free_over,
+        input.min_delivery_days,
+        input.max_delivery_days,
+        input.active.unwrap_or(true),
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Shipping method not found".to_string()))?;
+    Ok(Json(rec))
+}
+
+async fn delete_method(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<bool>, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM shipping_methods WHERE id = $1", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(res.rows_affected() > 0))
+}
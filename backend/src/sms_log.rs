@@ -0,0 +1,148 @@
+// SMS Log Module - Inbound texts and the SMS opt-out list
+// Inbound messages are stored for support; STOP keywords add the number to an opt-out list
+// that every outgoing SMS is checked against, and START removes it again
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::pii::{self, Encrypted};
+use crate::textbelt_sms::format_phone_number;
+use crate::AppState;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+// Database model for received texts
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SmsInboundMessage {
+    pub id: i32,
+    pub message_sid: String,
+    pub from_phone: Encrypted,
+    pub to_phone: Option<String>,
+    pub body: String,
+    pub keyword: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SmsOptOut {
+    pub phone: Encrypted,
+    pub keyword: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct InboundQuery {
+    pub phone: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+// `phone` must already be E.164 (see textbelt_sms::format_phone_number)
+pub async fn is_opted_out(pool: &sqlx::PgPool, phone: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM sms_opt_outs WHERE phone_hash = $1) AS "exists!""#, pii::lookup_hash(phone))
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn opt_out(pool: &sqlx::PgPool, phone: &str, keyword: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO sms_opt_outs (phone, phone_hash, keyword) VALUES ($1, $2, $3) ON CONFLICT (phone_hash) DO NOTHING",
+        Encrypted::from(phone) as _,
+        pii::lookup_hash(phone),
+        keyword,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn opt_in(pool: &sqlx::PgPool, phone: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM sms_opt_outs WHERE phone_hash = $1", pii::lookup_hash(phone))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// Store an inbound text; false if this message was already recorded (a retried webhook)
+pub async fn record_inbound(
+    pool: &sqlx::PgPool,
+    message_sid: &str,
+    from_phone: &str,
+    to_phone: Option<&str>,
+    body: &str,
+    keyword: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query!(
+        "INSERT INTO sms_inbound_messages (message_sid, from_phone, from_phone_hash, to_phone, body, keyword)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (message_sid) DO NOTHING",
+        message_sid,
+        Encrypted::from(from_phone) as _,
+        pii::lookup_hash(from_phone),
+        to_phone,
+        body,
+        keyword,
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub fn sms_log_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/sms/inbound", get(list_inbound))
+        .route("/api/admin/sms/opt-outs", get(list_opt_outs))
+        .with_state(app_state)
+}
+
+// Newest first, optionally for one sender
+async fn list_inbound(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<InboundQuery>,
+) -> Result<Json<Vec<SmsInboundMessage>>, (StatusCode, String)> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+    // Senders are stored in E.164, so the filter is normalized the same way before hashing
+    let phone = query
+        .phone
+        .as_deref()
+        .map(format_phone_number)
+        .transpose()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let messages = sqlx::query_as!(
+        SmsInboundMessage,
+        r#"SELECT id, message_sid, from_phone AS "from_phone: Encrypted", to_phone, body, keyword, created_at FROM sms_inbound_messages
+         WHERE ($1::TEXT IS NULL OR from_phone_hash = $1)
+         ORDER BY created_at DESC, id DESC
+         LIMIT $2 OFFSET $3"#,
+        phone.as_deref().map(pii::lookup_hash),
+        limit,
+        offset,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(messages))
+}
+
+// Only the customer can lift an opt-out (by texting START), so this list is read-only
+async fn list_opt_outs(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<SmsOptOut>>, (StatusCode, String)> {
+    let opt_outs = sqlx::query_as!(SmsOptOut, r#"SELECT phone AS "phone: Encrypted", keyword, created_at FROM sms_opt_outs ORDER BY created_at DESC"#)
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(opt_outs))
+}
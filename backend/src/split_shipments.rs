@@ -0,0 +1,389 @@
+// Split Shipments Module - Orders fulfilled and shipped in parts
+// Each fulfillment is one shipment of some of the order's items, with its own carrier and
+// tracking. Fulfillments can't add up to more than was ordered, the order's tracking status
+// reads partially_shipped until every item has left, and the customer is emailed for each
+// shipment with what's in it and what's still to come
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::branding::{escape_html, load_branding};
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::notifications::{queue_email, EmailPriority};
+use crate::pii::Encrypted;
+use crate::order_tracking::{self, OrderTrackingStatus};
+use crate::unit_of_work::WorkError;
+use crate::warehouses::OrderFulfillment;
+use crate::AppState;
+
+// How much of one product has been fulfilled, shipped and delivered
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ItemProgress {
+    pub product_id: Option<i32>,
+    pub product_name: String,
+    pub ordered: i64,
+    pub fulfilled: i64, // In active fulfillments, shipped or not
+    pub shipped: i64,
+    pub delivered: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ShipmentLine {
+    pub product_id: Option<i32>,
+    pub product_name: Option<String>,
+    pub quantity: i32,
+}
+
+// One fulfillment as the customer sees it
+#[derive(Debug, Serialize)]
+pub struct Shipment {
+    pub id: Uuid,
+    pub status: String, // 'pending', 'shipped' or 'delivered'
+    pub carrier: Option<String>,
+    pub tracking_code: Option<String>,
+    pub shipped_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub items: Vec<ShipmentLine>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShippingProgress {
+    pub order_id: Uuid,
+    pub status: OrderTrackingStatus,
+    pub items: Vec<ItemProgress>,
+    pub shipments: Vec<Shipment>,
+}
+
+#[derive(Deserialize)]
+pub struct ShipFulfillmentRequest {
+    pub carrier: String,
+    pub tracking_code: Option<String>, // Optional for e.g. local courier deliveries
+}
+
+#[derive(Deserialize)]
+pub struct ShipmentsQuery {
+    pub token: Option<String>, // Customer or admin JWT, instead of the Authorization header
+    pub email: Option<String>, // Guest orders: the email used at checkout
+}
+
+// Ordered, fulfilled, shipped and delivered quantities of each of the order's products
+pub async fn item_progress(executor: impl sqlx::PgExecutor<'_>, order_id: Uuid) -> Result<Vec<ItemProgress>, sqlx::Error> {
+    sqlx::query_as!(
+        ItemProgress,
+        r#"WITH ordered AS (
+             SELECT product_id, MIN(product_name) AS product_name, SUM(quantity)::BIGINT AS quantity
+             FROM order_items WHERE order_id = $1
+             GROUP BY product_id
+         ), sent AS (
+             SELECT fi.product_id,
+                    SUM(fi.quantity)::BIGINT AS fulfilled,
+                    COALESCE(SUM(fi.quantity) FILTER (WHERE f.status IN ('shipped', 'delivered')), 0)::BIGINT AS shipped,
+                    COALESCE(SUM(fi.quantity) FILTER (WHERE f.status = 'delivered'), 0)::BIGINT AS delivered
+             FROM order_fulfillment_items fi
+             JOIN order_fulfillments f ON f.id = fi.fulfillment_id
+             WHERE f.order_id = $1 AND f.status <> 'cancelled'
+             GROUP BY fi.product_id
+         )
+         SELECT o.product_id, o.product_name AS "product_name!", o.quantity AS "ordered!",
+                COALESCE(s.fulfilled, 0) AS "fulfilled!", COALESCE(s.shipped, 0) AS "shipped!",
+                COALESCE(s.delivered, 0) AS "delivered!"
+         FROM ordered o LEFT JOIN sent s ON s.product_id = o.product_id
+         ORDER BY o.product_name"#,
+        order_id,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+// Refuse fulfillment lines that go beyond what's left to fulfill of each product. Locks the
+// order so two fulfillments created at once can't both take the last units. Orders without
+// copied items (placed before items were kept) aren't checked
+pub async fn check_remaining(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    order_id: Uuid,
+    lines: &[(i32, i32)], // (product_id, quantity)
+) -> Result<(), WorkError<(StatusCode, String)>> {
+    let found: Option<Uuid> = sqlx::query_scalar!("SELECT id FROM orders WHERE id = $1 FOR UPDATE", order_id)
+        .fetch_optional(&mut **tx)
+        .await?;
+    if found.is_none() {
+        return Err(WorkError::Aborted((StatusCode::NOT_FOUND, "Order not found".to_string())));
+    }
+    let progress = item_progress(&mut **tx, order_id).await?;
+    if progress.is_empty() {
+        return Ok(());
+    }
+
+    let mut requested: HashMap<i32, i64> = HashMap::new();
+    for &(product_id, quantity) in lines {
+        *requested.entry(product_id).or_default() += quantity as i64;
+    }
+    for (product_id, quantity) in requested {
+        let Some(item) = progress.iter().find(|i| i.product_id == Some(product_id)) else {
+            return Err(WorkError::Aborted((
+                StatusCode::BAD_REQUEST,
+                format!("Product {} isn't in this order", product_id),
+            )));
+        };
+        let remaining = (item.ordered - item.fulfilled).max(0);
+        if quantity > remaining {
+            return Err(WorkError::Aborted((
+                StatusCode::CONFLICT,
+                format!("Product {} has {} left to fulfill, {} requested", product_id, remaining, quantity),
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Mark a fulfillment shipped with its tracking, and its ship-to address shipped once every one
+// of the address's fulfillments is
+pub async fn mark_shipped(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    fulfillment_id: Uuid,
+    carrier: &str,
+    tracking_code: Option<&str>,
+    easypost_shipment_id: Option<&str>,
+) -> Result<OrderFulfillment, sqlx::Error> {
+    let fulfillment = sqlx::query_as!(
+        OrderFulfillment,
+        "UPDATE order_fulfillments SET carrier = $1, tracking_code = $2, shipment_id = COALESCE($3, shipment_id),
+                status = 'shipped', shipped_at = NOW(), updated_at = NOW()
+         WHERE id = $4 RETURNING id, order_id, warehouse_id, status, carrier, tracking_code, shipment_id, created_at, updated_at, order_shipment_id, shipped_at, delivered_at",
+        carrier,
+        tracking_code,
+        easypost_shipment_id,
+        fulfillment_id,
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    if let Some(shipment_id) = fulfillment.order_shipment_id {
+        sqlx::query!(
+            "UPDATE order_shipments SET status = 'shipped'
+             WHERE id = $1 AND NOT EXISTS (
+                 SELECT 1 FROM order_fulfillments WHERE order_shipment_id = $1 AND status NOT IN ('shipped', 'delivered', 'cancelled')
+             )",
+            shipment_id,
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(fulfillment)
+}
+
+pub(crate) async fn shipment_lines(pool: &sqlx::PgPool, fulfillment_id: Uuid) -> Result<Vec<ShipmentLine>, sqlx::Error> {
+    sqlx::query_as!(
+        ShipmentLine,
+        r#"SELECT fi.product_id, p.name AS "product_name?", fi.quantity
+         FROM order_fulfillment_items fi LEFT JOIN products p ON p.id = fi.product_id
+         WHERE fi.fulfillment_id = $1 ORDER BY p.name"#,
+        fulfillment_id,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Tracking status, item quantities and shipments of an order; None when it doesn't exist
+pub async fn shipping_progress(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Option<ShippingProgress>, sqlx::Error> {
+    let Some(status) = order_tracking::tracking_status(pool, order_id).await? else {
+        return Ok(None);
+    };
+    let items = item_progress(pool, order_id).await?;
+    let fulfillments = sqlx::query_as!(
+        OrderFulfillment,
+        "SELECT id, order_id, warehouse_id, status, carrier, tracking_code, shipment_id, created_at, updated_at, order_shipment_id, shipped_at, delivered_at FROM order_fulfillments WHERE order_id = $1 AND status <> 'cancelled' ORDER BY created_at",
+        order_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut shipments = Vec::new();
+    for fulfillment in fulfillments {
+        shipments.push(Shipment {
+            items: shipment_lines(pool, fulfillment.id).await?,
+            id: fulfillment.id,
+            status: fulfillment.status,
+            carrier: fulfillment.carrier,
+            tracking_code: fulfillment.tracking_code,
+            shipped_at: fulfillment.shipped_at,
+            delivered_at: fulfillment.delivered_at,
+        });
+    }
+    Ok(Some(ShippingProgress { order_id, status, items, shipments }))
+}
+
+pub fn split_shipment_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/orders/:id/shipping-progress", get(admin_shipping_progress))
+        .route("/api/admin/fulfillments/:id/ship", post(ship_fulfillment))
+        .route("/api/orders/:id/shipments", get(customer_shipments))
+        .with_state(app_state)
+}
+
+async fn admin_shipping_progress(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<ShippingProgress>, (StatusCode, String)> {
+    shipping_progress(&app_state.pool, order_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "Order not found".to_string()))
+}
+
+// Record a fulfillment shipped outside EasyPost labels, with the carrier's tracking number
+async fn ship_fulfillment(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(fulfillment_id): Path<Uuid>,
+    Json(request): Json<ShipFulfillmentRequest>,
+) -> Result<Json<OrderFulfillment>, (StatusCode, String)> {
+    let carrier = request.carrier.trim();
+    if carrier.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "carrier is required".to_string()));
+    }
+    let tracking_code = request.tracking_code.as_deref().map(str::trim).filter(|t| !t.is_empty());
+
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let mut tx = app_state.pool.begin().await.map_err(db_err)?;
+    let status: String = sqlx::query_scalar!(
+        "SELECT status FROM order_fulfillments WHERE id = $1 FOR UPDATE",
+        fulfillment_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_err)?
+    .ok_or((StatusCode::NOT_FOUND, "Fulfillment not found".to_string()))?;
+    if status != "pending" {
+        return Err((StatusCode::CONFLICT, format!("Fulfillment is {}", status)));
+    }
+    let fulfillment = mark_shipped(&mut tx, fulfillment_id, carrier, tracking_code, None)
+        .await
+        .map_err(db_err)?;
+    tx.commit().await.map_err(db_err)?;
+
+    tracing::info!("{} marked fulfillment {} of order {} shipped", admin.username, fulfillment.id, fulfillment.order_id);
+    app_state.events.publish(DomainEvent::OrderShipped {
+        order_id: fulfillment.order_id,
+        fulfillment_id: fulfillment.id,
+        carrier: fulfillment.carrier.clone(),
+        tracking_code: fulfillment.tracking_code.clone(),
+    });
+    Ok(Json(fulfillment))
+}
+
+// The order's shipments for its customer, an admin, or a guest who knows the checkout email
+async fn customer_shipments(
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+    Query(query): Query<ShipmentsQuery>,
+    headers: HeaderMap,
+) -> Result<Json<ShippingProgress>, (StatusCode, String)> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string)
+        .or(query.token);
+    order_tracking::authorize(&app_state.pool, order_id, token.as_deref(), query.email.as_deref()).await?;
+    shipping_progress(&app_state.pool, order_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "Order not found".to_string()))
+}
+
+fn lines_html(lines: impl Iterator<Item = (String, i64)>) -> String {
+    let items: String = lines
+        .map(|(name, quantity)| format!("<li>{} &times; {}</li>", quantity, escape_html(&name)))
+        .collect();
+    format!("<ul>{}</ul>", items)
+}
+
+// Emails the customer for every shipment: its items and tracking, and what's still to ship
+pub struct ShipmentEmailSubscriber {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl ShipmentEmailSubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for ShipmentEmailSubscriber {
+    fn name(&self) -> &'static str {
+        "shipment_email"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        let DomainEvent::OrderShipped { order_id, fulfillment_id, carrier, tracking_code } = event else {
+            return Ok(());
+        };
+        let db_err = |e: sqlx::Error| format!("DB error: {}", e);
+        let customer: Option<(Option<Encrypted>, Option<String>)> =
+            sqlx::query!(r#"SELECT customer_email AS "customer_email: Encrypted", customer_name FROM orders WHERE id = $1"#, order_id)
+                .map(|r| (r.customer_email, r.customer_name))
+                .fetch_optional(&*self.pool)
+                .await
+                .map_err(db_err)?;
+        let Some((Some(email), name)) = customer else {
+            return Ok(());
+        };
+
+        let lines = shipment_lines(&self.pool, *fulfillment_id).await.map_err(db_err)?;
+        let progress = item_progress(&*self.pool, *order_id).await.map_err(db_err)?;
+        let to_ship: Vec<(String, i64)> = progress
+            .into_iter()
+            .filter(|i| i.shipped < i.ordered)
+            .map(|i| (i.product_name, i.ordered - i.shipped))
+            .collect();
+        let (subject, intro) = if to_ship.is_empty() {
+            ("Your order has shipped", "Your order is on its way.")
+        } else {
+            ("Part of your order has shipped", "Part of your order is on its way. The rest will follow in a separate shipment.")
+        };
+
+        let tracking = match (carrier, tracking_code) {
+            (Some(carrier), Some(code)) => format!("Carrier: {}<br>Tracking number: {}", escape_html(carrier), escape_html(code)),
+            (Some(carrier), None) => format!("Carrier: {}", escape_html(carrier)),
+            (None, Some(code)) => format!("Tracking number: {}", escape_html(code)),
+            (None, None) => String::new(),
+        };
+        let still_to_ship = if to_ship.is_empty() {
+            String::new()
+        } else {
+            format!("<p>Still to ship:</p>{}", lines_html(to_ship.into_iter()))
+        };
+        let html_body = load_branding(&self.pool).await.email_html(
+            subject,
+            &format!(
+                "<p>Hi {},</p><p>{}</p><p>Order ID: {}<br>{}</p><p>In this shipment:</p>{}{}",
+                escape_html(name.as_deref().unwrap_or("there")),
+                intro,
+                order_id,
+                tracking,
+                lines_html(lines.into_iter().map(|l| {
+                    let name = l.product_name.unwrap_or_else(|| "Item".to_string());
+                    (name, l.quantity as i64)
+                })),
+                still_to_ship,
+            ),
+        );
+        queue_email(&self.pool, &email, &format!("{} - {}", subject, order_id), &html_body, EmailPriority::Immediate).await
+    }
+}
@@ -0,0 +1,84 @@
+// Static Files Module - Serves the built storefront and uploaded media
+// Bundle files carry a content hash in their names (trunk writes e.g. `main-<hash>.css` and
+// `frontend-leptos-<hash>_bg.wasm`) and media files are never rewritten in place, so both are
+// cached for a year as immutable. HTML is revalidated on every load so a deploy shows up at
+// once, and other files (favicon, robots.txt) are cached for an hour. A `.br` or `.gz` file next
+// to a bundle file is sent instead to browsers that accept it. Any other path outside /api gets
+// index.html, so client-side routes like /product/tote-bag load the app
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use std::sync::Arc;
+use tower_http::services::{ServeDir, ServeFile};
+
+use crate::AppState;
+
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const REVALIDATE: &str = "no-cache";
+const SHORT_LIVED: &str = "public, max-age=3600";
+const MIN_HASH_LEN: usize = 16; // trunk's hashes are 16 hex digits
+
+// Uploaded product images and their variants, under /media
+pub fn media_routes(dir: &str) -> Router<Arc<AppState>> {
+    Router::new()
+        .nest_service("/media", ServeDir::new(dir))
+        .layer(middleware::from_fn(media_headers))
+}
+
+// The storefront bundle, answering every path no other route matched
+pub fn storefront_routes(dir: &str) -> Router<Arc<AppState>> {
+    let index = std::path::Path::new(dir).join("index.html");
+    let files = ServeDir::new(dir)
+        .precompressed_br()
+        .precompressed_gzip()
+        .fallback(ServeFile::new(index).precompressed_br().precompressed_gzip());
+    Router::new()
+        .fallback_service(files)
+        .layer(middleware::from_fn(storefront_headers))
+}
+
+async fn media_headers(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    if response.status().is_success() {
+        response.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static(IMMUTABLE));
+    }
+    response
+}
+
+async fn storefront_headers(request: Request, next: Next) -> Response {
+    // Unknown API paths get a plain 404 rather than the app
+    if request.uri().path().starts_with("/api/") {
+        return (StatusCode::NOT_FOUND, "Not found").into_response();
+    }
+    let hashed = is_hashed(request.uri().path());
+    let mut response = next.run(request).await;
+    if response.status().is_success() {
+        let is_html = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/html"));
+        let cache_control = match (is_html, hashed) {
+            (true, _) => REVALIDATE,
+            (false, true) => IMMUTABLE,
+            (false, false) => SHORT_LIVED,
+        };
+        let headers = response.headers_mut();
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(cache_control));
+        // Caches must keep the brotli, gzip and plain copies apart
+        headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    }
+    response
+}
+
+// Whether a file name includes a content hash, so its contents never change
+fn is_hashed(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or_default();
+    name.split(['-', '_', '.'])
+        .any(|part| part.len() >= MIN_HASH_LEN && part.chars().all(|c| c.is_ascii_hexdigit()))
+}
@@ -0,0 +1,302 @@
+// Stock Reservations Module - Inventory held by unpaid checkouts
+// create-payment-intent takes the cart's items out of product inventory for CART_HOLD_MINUTES,
+// so two shoppers can't both pay for the last unit. The reservation becomes the sale when the
+// order is created (checkout_carts::convert_to_order). Abandoned checkouts have their payment
+// cancelled and the stock put back, and the customer is emailed that their hold expired.
+// Orders entered by staff (manual, quote and purchase orders) reserve the same way; pending
+// ones keep the hold until they are paid, cancelled or MANUAL_ORDER_HOLD_DAYS pass.
+// Ticketed event products are held by the tickets module instead
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use crate::admin_products::stock_events;
+use crate::app_config;
+use crate::cart_pricing::PricedLine;
+use crate::checkout_carts;
+use crate::coupons;
+use crate::loyalty;
+use crate::manual_orders;
+use crate::repos::product_query;
+use crate::store_credit;
+use crate::unit_of_work::{self, UnitOfWork, WorkError};
+use crate::AppState;
+
+// What the checkout page shows while the customer pays
+#[derive(Debug, Serialize)]
+pub struct CheckoutHold {
+    pub order_id: Uuid,
+    pub status: String, // held, expired, released, converted or none (nothing needed holding)
+    pub expires_at: Option<DateTime<Utc>>,
+    pub seconds_remaining: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct StockLevel {
+    name: String,
+    inventory: i32,
+    ticketed: bool,
+}
+
+pub fn checkout_hold_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/checkout/:order_id/hold", get(get_hold))
+        .with_state(app_state)
+}
+
+// Quantity of each product in the cart, in id order so concurrent checkouts lock rows alike
+fn quantities(lines: &[PricedLine]) -> BTreeMap<i32, i32> {
+    let mut quantities = BTreeMap::new();
+    for line in lines {
+        *quantities.entry(line.product_id).or_insert(0) += line.quantity;
+    }
+    quantities
+}
+
+// Take the cart's items out of inventory until the payment goes through or CART_HOLD_MINUTES pass,
+// as part of the checkout's unit of work. A product without enough stock left fails the checkout with 409
+pub async fn reserve_for_cart(
+    uow: &mut UnitOfWork,
+    cart_id: Uuid,
+    lines: &[PricedLine],
+) -> Result<(), WorkError<(StatusCode, String)>> {
+    for (product_id, quantity) in quantities(lines) {
+        // NO KEY so the lock doesn't wait on the key-share lock the cart's own items already hold
+        // through their foreign key, which deadlocked concurrent checkouts of the same product
+        let stock = sqlx::query_as!(
+            StockLevel,
+            r#"SELECT p.name, p.inventory,
+                      EXISTS (SELECT 1 FROM ticket_events e WHERE e.product_id = p.id) AS "ticketed!"
+               FROM products p WHERE p.id = $1 FOR NO KEY UPDATE"#,
+            product_id,
+        )
+        .fetch_optional(uow.conn())
+        .await?;
+        let Some(stock) = stock.filter(|s| !s.ticketed) else {
+            continue;
+        };
+        if stock.inventory < quantity {
+            let message = match stock.inventory {
+                n if n <= 0 => format!("{} is out of stock", stock.name),
+                n => format!("Only {} left of {}", n, stock.name),
+            };
+            return Err(WorkError::Aborted((StatusCode::CONFLICT, message)));
+        }
+
+        // The row is locked, so the guard can't fail here; it keeps the decrement from ever taking
+        // inventory below zero should the lock above be dropped
+        let product = product_query!(
+            "UPDATE products SET inventory = inventory - $2, updated_at = NOW()
+             WHERE id = $1 AND inventory >= $2 RETURNING",
+            "",
+            product_id,
+            quantity,
+        )
+        .fetch_optional(uow.conn())
+        .await?
+        .ok_or_else(|| WorkError::Aborted((StatusCode::CONFLICT, format!("{} just sold out", stock.name))))?;
+        sqlx::query!(
+            "INSERT INTO stock_reservations (cart_id, product_id, quantity, expires_at)
+             VALUES ($1, $2, $3, NOW() + make_interval(mins => $4::INT))",
+            cart_id,
+            product_id,
+            quantity,
+            app_config::get().checkout.cart_hold_minutes as i32,
+        )
+        .execute(uow.conn())
+        .await?;
+        for event in stock_events(&product, Some(stock.inventory)) {
+            uow.publish(event);
+        }
+    }
+    Ok(())
+}
+
+// Put the cart's reserved stock back into inventory
+pub async fn release_cart(state: &AppState, cart_id: Uuid) -> Result<(), sqlx::Error> {
+    unit_of_work::run(state, |uow| Box::pin(async move { Ok::<_, WorkError<Infallible>>(release_held(uow, cart_id).await?) }))
+        .await
+        .map_err(|e| match e {
+            WorkError::Db(e) => e,
+            WorkError::Aborted(never) => match never {},
+        })
+}
+
+// Put the cart's held stock back into inventory, free its coupon, and put its held points and
+// store credit back on the customer's balances, as part of the caller's unit of work
+pub async fn release_held(uow: &mut UnitOfWork, cart_id: Uuid) -> Result<(), sqlx::Error> {
+    let released = sqlx::query!(
+        "UPDATE stock_reservations SET status = 'released', released_at = NOW()
+         WHERE cart_id = $1 AND status = 'held'
+         RETURNING product_id, quantity",
+        cart_id,
+    )
+    .fetch_all(uow.conn())
+    .await?;
+
+    for held in released {
+        let quantity = held.quantity;
+        let product = product_query!(
+            "UPDATE products SET inventory = inventory + $2, updated_at = NOW() WHERE id = $1 RETURNING",
+            "",
+            held.product_id,
+            quantity,
+        )
+        .fetch_optional(uow.conn())
+        .await?;
+        if let Some(product) = product {
+            for event in stock_events(&product, Some(product.inventory - quantity)) {
+                uow.publish(event);
+            }
+        }
+    }
+    // The coupon, points and store credit the checkout held go back with its stock
+    coupons::release_hold(uow.conn(), cart_id).await?;
+    loyalty::release_hold(uow.conn(), cart_id).await?;
+    store_credit::release_hold(uow.conn(), cart_id).await?;
+    Ok(())
+}
+
+// The cart's held stock now belongs to its order
+pub async fn convert_cart(executor: impl sqlx::PgExecutor<'_>, cart_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE stock_reservations SET status = 'converted' WHERE cart_id = $1 AND status = 'held'",
+        cart_id,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// Keep the stock of an order entered by staff held for MANUAL_ORDER_HOLD_DAYS instead of
+// CART_HOLD_MINUTES, while the customer pays the invoice or payment link
+pub async fn hold_until_paid(executor: impl sqlx::PgExecutor<'_>, cart_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE stock_reservations SET expires_at = NOW() + make_interval(days => $2::INT)
+         WHERE cart_id = $1 AND status = 'held'",
+        cart_id,
+        app_config::get().checkout.manual_order_hold_days as i32,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// When the cart's earliest stock reservation or ticket hold runs out
+pub async fn hold_expiry(executor: impl sqlx::PgExecutor<'_>, cart_id: Uuid) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT MIN(expires_at) FROM (
+             SELECT expires_at FROM stock_reservations WHERE cart_id = $1 AND status = 'held'
+             UNION ALL
+             SELECT expires_at FROM ticket_holds WHERE cart_id = $1 AND status = 'held'
+         ) h",
+        cart_id,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+// Give back the stock of checkouts whose reservation expired. The payment is cancelled first
+// so it can't go through afterwards; reservations whose payment already succeeded (or is still
+// processing) are left for the order to take over. Returns how many checkouts and unpaid
+// manual orders were released
+// (run by the release_stock_reservations scheduled task)
+pub async fn release_expired(state: &AppState) -> Result<usize, sqlx::Error> {
+    let carts = sqlx::query!(
+        "SELECT DISTINCT c.id, c.payment_intent_id
+         FROM stock_reservations r JOIN checkout_carts c ON c.id = r.cart_id
+         WHERE r.status = 'held' AND r.expires_at <= NOW() AND c.converted_at IS NULL",
+    )
+    .fetch_all(&*state.pool)
+    .await?;
+
+    let mut released = 0;
+    for cart in carts {
+        let (cart_id, payment_intent_id) = (cart.id, cart.payment_intent_id);
+        if let Some(payment_intent_id) = payment_intent_id {
+            if !checkout_carts::cancel_abandoned_payment(state, &payment_intent_id).await {
+                continue;
+            }
+        }
+        release_cart(state, cart_id).await?;
+        released += 1;
+        tracing::info!("Released expired stock reservation of checkout {}", cart_id);
+        if let Err(e) = checkout_carts::notify_hold_expired(&state.pool, cart_id).await {
+            tracing::error!("Failed to email expired hold notice for checkout {}: {}", cart_id, e);
+        }
+    }
+
+    // Manual and quote orders still waiting to be paid are cancelled with their hold
+    let orders = sqlx::query_scalar!(
+        "SELECT DISTINCT o.id
+         FROM stock_reservations r JOIN orders o ON o.cart_id = r.cart_id
+         WHERE r.status = 'held' AND r.expires_at <= NOW() AND o.status = 'pending'",
+    )
+    .fetch_all(&*state.pool)
+    .await?;
+    for order_id in orders {
+        match manual_orders::cancel(state, order_id, "system").await {
+            Ok(Some(_)) => {
+                released += 1;
+                tracing::info!("Cancelled unpaid order {}; its stock hold expired", order_id);
+            }
+            Ok(None) => {}
+            Err(e) => tracing::error!("Failed to cancel unpaid order {}: {}", order_id, e),
+        }
+    }
+    Ok(released)
+}
+
+// Hold countdown for the checkout page, looked up by the order id create-payment-intent returned
+async fn get_hold(
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<CheckoutHold>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let cart = sqlx::query!(
+        r#"SELECT id, converted_at IS NOT NULL AS "converted!" FROM checkout_carts WHERE order_id = $1"#,
+        order_id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(db_error)?
+    .ok_or((StatusCode::NOT_FOUND, "Checkout not found".to_string()))?;
+    let (cart_id, converted) = (cart.id, cart.converted);
+
+    let expires_at = hold_expiry(&*app_state.pool, cart_id).await.map_err(db_error)?;
+    let seconds_remaining = expires_at
+        .map(|at| (at - Utc::now()).num_seconds().max(0))
+        .unwrap_or(0);
+    let status = if converted {
+        "converted"
+    } else if expires_at.is_some() {
+        if seconds_remaining > 0 { "held" } else { "expired" }
+    } else {
+        let released = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM stock_reservations WHERE cart_id = $1 AND status = 'released')
+                   OR EXISTS (SELECT 1 FROM ticket_holds WHERE cart_id = $1 AND status = 'released') AS "released!""#,
+            cart_id,
+        )
+        .fetch_one(&*app_state.pool)
+        .await
+        .map_err(db_error)?;
+        if released { "released" } else { "none" }
+    };
+
+    Ok(Json(CheckoutHold {
+        order_id,
+        status: status.to_string(),
+        expires_at: if converted { None } else { expires_at },
+        seconds_remaining: if converted { 0 } else { seconds_remaining },
+    }))
+}
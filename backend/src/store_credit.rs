@@ -0,0 +1,365 @@
+// Store Credit Module - Per-customer store credit ledger
+// Credits come from refunds, referral rewards and admin goodwill grants; available
+// credit is applied automatically at checkout, held while the customer pays and debited
+// when the order is created
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::customer_auth::AuthenticatedCustomer;
+use crate::events::DomainEvent;
+use crate::order_status::{self, OrderStatus};
+use crate::pii::Encrypted;
+use crate::repos::order_query;
+use crate::unit_of_work::WorkError;
+use crate::webhooks::PaymentProvider;
+use crate::AppState;
+
+pub const METADATA_KEY: &str = "store_credit_applied";
+pub const CUSTOMER_METADATA_KEY: &str = "customer_id";
+
+// Why a ledger entry was made
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
+pub enum CreditReason {
+    #[sqlx(rename = "refund")]
+    #[serde(rename = "refund")]
+    Refund,
+    #[sqlx(rename = "referral")]
+    #[serde(rename = "referral")]
+    Referral,
+    #[sqlx(rename = "goodwill")]
+    #[serde(rename = "goodwill")]
+    Goodwill,
+    #[sqlx(rename = "checkout")]
+    #[serde(rename = "checkout")]
+    Checkout,
+    #[sqlx(rename = "adjustment")]
+    #[serde(rename = "adjustment")]
+    Adjustment,
+}
+
+// Database model for ledger entries
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct StoreCreditEntry {
+    pub id: Uuid,
+    pub customer_id: Uuid,
+    pub amount: i64, // in cents; negative for debits
+    pub reason: CreditReason,
+    pub note: Option<String>,
+    pub order_id: Option<Uuid>,
+    pub created_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StoreCreditAccount {
+    pub balance: i64, // in cents
+    pub entries: Vec<StoreCreditEntry>,
+}
+
+#[derive(Deserialize)]
+pub struct GrantCreditRequest {
+    pub amount: i64, // in cents; only adjustments may be negative
+    pub reason: CreditReason,
+    pub note: Option<String>,
+    pub order_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+pub struct RefundToCreditRequest {
+    pub amount: Option<i64>, // in cents; defaults to whatever hasn't been credited yet
+    pub note: Option<String>,
+}
+
+// Current balance for a customer
+pub async fn balance<'e, E>(executor: E, customer_id: Uuid) -> Result<i64, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    sqlx::query_scalar!(
+        r#"SELECT COALESCE(SUM(amount), 0)::BIGINT AS "amount!" FROM store_credits WHERE customer_id = $1"#,
+        customer_id,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+// Append a ledger entry
+pub async fn add_entry<'e, E>(
+    executor: E,
+    customer_id: Uuid,
+    amount: i64,
+    reason: CreditReason,
+    note: Option<&str>,
+    order_id: Option<Uuid>,
+    created_by: Option<&str>,
+) -> Result<StoreCreditEntry, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    sqlx::query_as!(
+        StoreCreditEntry,
+        r#"INSERT INTO store_credits (customer_id, amount, reason, note, order_id, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING id, customer_id, amount, reason AS "reason: CreditReason", note, order_id, created_by, created_at"#,
+        customer_id,
+        amount,
+        reason as _,
+        note,
+        order_id,
+        created_by,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+// Credit a customer can spend at checkout: the balance less what open checkouts hold
+pub async fn available(executor: impl sqlx::PgExecutor<'_>, customer_id: Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT (COALESCE((SELECT SUM(amount) FROM store_credits WHERE customer_id = $1), 0)
+                 - COALESCE((SELECT SUM(amount) FROM store_credit_holds WHERE customer_id = $1 AND status = 'held'), 0)
+                )::BIGINT AS "available!""#,
+        customer_id,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+// Hold the credit taken off a checkout until its order is created, as part of the checkout's
+// unit of work. Fails the checkout with 409 when another checkout spent the credit since this
+// one was priced
+pub async fn hold_for_cart(
+    conn: &mut sqlx::PgConnection,
+    cart_id: Uuid,
+    customer_id: Uuid,
+    amount: i64,
+) -> Result<(), WorkError<(StatusCode, String)>> {
+    // Serialize concurrent checkouts of the same customer
+    sqlx::query!("SELECT id FROM customers WHERE id = $1 FOR UPDATE", customer_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    let available = available(&mut *conn, customer_id).await?;
+    if available < amount {
+        return Err(WorkError::Aborted((
+            StatusCode::CONFLICT,
+            format!("Only {} cents of store credit left; refresh the checkout", available.max(0)),
+        )));
+    }
+    sqlx::query!(
+        "INSERT INTO store_credit_holds (cart_id, customer_id, amount) VALUES ($1, $2, $3)",
+        cart_id,
+        customer_id,
+        amount,
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// Give the checkout's held credit back to the customer
+pub async fn release_hold(executor: impl sqlx::PgExecutor<'_>, cart_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE store_credit_holds SET status = 'released', released_at = NOW()
+         WHERE cart_id = $1 AND status = 'held'",
+        cart_id,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// Debit the credit the checkout held once its order exists. None when nothing is held any
+// more: the hold was released, or another Stripe event already created the order
+pub async fn debit_held(conn: &mut sqlx::PgConnection, cart_id: Uuid, order_id: Uuid) -> Result<Option<i64>, sqlx::Error> {
+    let held = sqlx::query!(
+        "UPDATE store_credit_holds SET status = 'converted'
+         WHERE cart_id = $1 AND status = 'held'
+         RETURNING customer_id, amount",
+        cart_id,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(held) = held else {
+        return Ok(None);
+    };
+    add_entry(&mut *conn, held.customer_id, -held.amount, CreditReason::Checkout, None, Some(order_id), None).await?;
+    Ok(Some(held.amount))
+}
+
+async fn load_account(pool: &sqlx::PgPool, customer_id: Uuid) -> Result<StoreCreditAccount, sqlx::Error> {
+    let entries = sqlx::query_as!(
+        StoreCreditEntry,
+        r#"SELECT id, customer_id, amount, reason AS "reason: CreditReason", note, order_id, created_by, created_at FROM store_credits WHERE customer_id = $1 ORDER BY created_at DESC"#,
+        customer_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(StoreCreditAccount {
+        balance: entries.iter().map(|e| e.amount).sum(),
+        entries,
+    })
+}
+
+pub fn store_credit_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/customers/:id/store-credit", get(get_customer_credit).post(grant_credit))
+        .route("/api/admin/orders/:id/refund-to-credit", post(refund_to_credit))
+        .route("/api/customers/me/store-credit", get(get_my_credit))
+        .with_state(app_state)
+}
+
+async fn get_customer_credit(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<StoreCreditAccount>, (StatusCode, String)> {
+    load_account(&app_state.pool, id)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))
+}
+
+async fn grant_credit(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(input): Json<GrantCreditRequest>,
+) -> Result<(StatusCode, Json<StoreCreditEntry>), (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    match input.reason {
+        CreditReason::Goodwill | CreditReason::Refund if input.amount > 0 => {}
+        CreditReason::Adjustment if input.amount != 0 => {}
+        CreditReason::Goodwill | CreditReason::Refund | CreditReason::Adjustment => {
+            return Err((StatusCode::BAD_REQUEST, "amount must be positive (or non-zero for adjustments)".to_string()));
+        }
+        CreditReason::Referral | CreditReason::Checkout => {
+            return Err((StatusCode::BAD_REQUEST, "reason must be goodwill, refund or adjustment".to_string()));
+        }
+    }
+
+    let mut tx = app_state.pool.begin().await.map_err(db_err)?;
+    let exists: bool = sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM customers WHERE id = $1 FOR UPDATE) AS "exists!""#, id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_err)?;
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, "Customer not found".to_string()));
+    }
+    if input.amount < 0 && balance(&mut *tx, id).await.map_err(db_err)? + input.amount < 0 {
+        return Err((StatusCode::BAD_REQUEST, "Adjustment would make the balance negative".to_string()));
+    }
+
+    let entry = add_entry(
+        &mut *tx,
+        id,
+        input.amount,
+        input.reason,
+        input.note.as_deref().map(str::trim).filter(|n| !n.is_empty()),
+        input.order_id,
+        Some(&admin.username),
+    )
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?;
+    tx.commit().await.map_err(db_err)?;
+
+    tracing::info!("{} granted {} cents store credit ({:?}) to customer {}", admin.username, input.amount, input.reason, id);
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+// Refund an order as store credit instead of back to the card.
+// Crediting the full order total marks it refunded.
+async fn refund_to_credit(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(input): Json<RefundToCreditRequest>,
+) -> Result<(StatusCode, Json<StoreCreditEntry>), (StatusCode, String)> {
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let mut tx = app_state.pool.begin().await.map_err(db_err)?;
+    let order = order_query!("SELECT", "FROM orders WHERE id = $1 FOR UPDATE", id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::NOT_FOUND, "Order not found".to_string()))?;
+    let customer_id = order
+        .customer_id
+        .ok_or((StatusCode::CONFLICT, "Order has no customer to credit".to_string()))?;
+    if order.status != OrderStatus::Completed {
+        return Err((StatusCode::CONFLICT, format!("Cannot refund an order with status '{}'", order.status)));
+    }
+
+    let already_credited: i64 = sqlx::query_scalar!(
+        r#"SELECT COALESCE(SUM(amount), 0)::BIGINT AS "already_credited!" FROM store_credits WHERE order_id = $1 AND reason = 'refund'"#,
+        id,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_err)?;
+    let remaining = order.total_amount - already_credited;
+    let amount = input.amount.unwrap_or(remaining);
+    if amount <= 0 || amount > remaining {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("amount must be between 1 and {} cents", remaining.max(0)),
+        ));
+    }
+
+    let note = input
+        .note
+        .as_deref()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("Refund for order {}", id));
+    let entry = add_entry(&mut *tx, customer_id, amount, CreditReason::Refund, Some(&note), Some(id), Some(&admin.username))
+        .await
+        .map_err(db_err)?;
+
+    let fully_refunded = amount == remaining;
+    let change = if fully_refunded {
+        Some(order_status::transition(&mut tx, id, OrderStatus::Refunded, &admin.username).await?)
+    } else {
+        None
+    };
+    tx.commit().await.map_err(db_err)?;
+
+    tracing::info!("Refunded {} cents of order {} to store credit", amount, id);
+    if fully_refunded {
+        let provider = match order.payment_provider.as_str() {
+            "square" => PaymentProvider::Square,
+            "manual" => PaymentProvider::Manual,
+            _ => PaymentProvider::Stripe,
+        };
+        app_state.events.publish(DomainEvent::OrderRefunded {
+            order_id: id,
+            provider,
+            payment_id: order.payment_id,
+            customer_email: order.customer_email.map(Encrypted::into_inner),
+            amount_refunded: order.total_amount,
+            currency: order.currency,
+        });
+    }
+    if let Some(change) = change {
+        app_state.events.publish(change.event());
+    }
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+async fn get_my_credit(
+    customer: AuthenticatedCustomer,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<StoreCreditAccount>, (StatusCode, String)> {
+    load_account(&app_state.pool, customer.customer_id)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))
+}
@@ -5,8 +5,12 @@
 use axum::{Json, Router, routing::post, extract::State, http::StatusCode};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use crate::app_config;
+use crate::notifications::{record_send, CHANNEL_SMS};
+use crate::phone_verification::is_phone_verified;
+use crate::request_id::WithRequestId;
+use crate::sms_log::is_opted_out;
 use crate::AppState;
-use reqwest;
 
 // SMS Provider enum
 #[derive(Debug, Clone)]
@@ -29,23 +33,19 @@ pub struct SmsConfig {
 
 impl SmsConfig {
     pub fn from_env() -> Option<Self> {
-        let provider_str = std::env::var("SMS_PROVIDER")
-            .unwrap_or_else(|_| "textbelt".to_string())
-            .to_lowercase();
-
-        let provider = match provider_str.as_str() {
+        let config = app_config::get();
+        let provider = match config.sms.provider.as_str() {
             "twilio" => SmsProvider::Twilio,
             _ => SmsProvider::Textbelt,
         };
 
         Some(Self {
             provider,
-            twilio_account_sid: std::env::var("TWILIO_ACCOUNT_SID").ok(),
-            twilio_auth_token: std::env::var("TWILIO_AUTH_TOKEN").ok(),
-            twilio_from_phone: std::env::var("TWILIO_FROM_PHONE").ok(),
-            textbelt_api_key: std::env::var("TEXTBELT_API_KEY").ok(),
-            textbelt_api_url: std::env::var("TEXTBELT_API_URL")
-                .unwrap_or_else(|_| "https://textbelt.com/text".to_string()),
+            twilio_account_sid: config.twilio.account_sid.clone(),
+            twilio_auth_token: config.twilio.auth_token.as_ref().map(|t| t.expose().to_string()),
+            twilio_from_phone: config.twilio.from_phone.clone(),
+            textbelt_api_key: config.sms.textbelt_api_key.as_ref().map(|k| k.expose().to_string()),
+            textbelt_api_url: config.sms.textbelt_api_url.clone(),
         })
     }
 }
@@ -95,6 +95,7 @@ pub struct SmsResponse {
 
 // Textbelt API response structure
 #[derive(Deserialize)]
+#[allow(dead_code)]
 struct TextbeltResponse {
     pub success: bool,
     #[serde(rename = "quotaRemaining")]
@@ -105,6 +106,7 @@ struct TextbeltResponse {
 
 // Twilio API response structure
 #[derive(Deserialize)]
+#[allow(dead_code)]
 struct TwilioResponse {
     pub sid: Option<String>,
     pub status: Option<String>,
@@ -142,6 +144,7 @@ async fn send_textbelt_sms(
     let response = client
         .post(&config.textbelt_api_url)
         .form(&params)
+        .with_request_id()
         .send()
         .await
         .map_err(|e| format!("Failed to send SMS: {}", e))?;
@@ -194,6 +197,7 @@ async fn send_twilio_sms(
         .post(&url)
         .basic_auth(account_sid.as_str(), Some(auth_token.as_str()))
         .form(&params)
+        .with_request_id()
         .send()
         .await
         .map_err(|e| format!("Failed to send SMS via Twilio: {}", e))?;
@@ -215,26 +219,33 @@ async fn send_twilio_sms(
     Ok(twilio_response)
 }
 
-// Unified SMS sending function that routes to the correct provider
-async fn send_sms_via_provider(
+// Unified SMS sending function that routes to the correct provider.
+// Numbers on the opt-out list (they texted STOP) are never sent to
+pub(crate) async fn send_sms_via_provider(
+    pool: &sqlx::PgPool,
     config: &SmsConfig,
     phone: &str,
     message: &str,
 ) -> Result<(bool, Option<i32>), String> {
-    match config.provider {
+    if is_opted_out(pool, phone).await.map_err(|e| format!("DB error: {}", e))? {
+        return Err(format!("{} has opted out of SMS", phone));
+    }
+    let sent = match config.provider {
         SmsProvider::Twilio => {
             send_twilio_sms(config, phone, message).await?;
-            Ok((true, None)) // Twilio doesn't return quota info
+            (true, None) // Twilio doesn't return quota info
         }
         SmsProvider::Textbelt => {
             let response = send_textbelt_sms(config, phone, message).await?;
-            Ok((response.success, response.quota_remaining))
+            (response.success, response.quota_remaining)
         }
-    }
+    };
+    record_send(pool, CHANNEL_SMS).await;
+    Ok(sent)
 }
 
 // Helper function to validate and format phone number
-fn format_phone_number(phone: &str) -> Result<String, String> {
+pub(crate) fn format_phone_number(phone: &str) -> Result<String, String> {
     // Remove all non-digit characters
     let digits: String = phone.chars().filter(|c| c.is_ascii_digit()).collect();
 
@@ -253,6 +264,18 @@ fn format_phone_number(phone: &str) -> Result<String, String> {
     }
 }
 
+// Order notifications only go to numbers confirmed through phone verification
+async fn require_verified_phone(state: &AppState, phone: &str) -> Result<(), (StatusCode, String)> {
+    let verified = is_phone_verified(&state.pool, phone)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    if verified {
+        Ok(())
+    } else {
+        Err((StatusCode::UNPROCESSABLE_ENTITY, format!("{} has not been verified", phone)))
+    }
+}
+
 // Send generic SMS
 async fn send_sms(
     State(state): State<Arc<AppState>>,
@@ -264,7 +287,7 @@ async fn send_sms(
     let formatted_phone = format_phone_number(&payload.phone)
         .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
 
-    let (success, quota_remaining) = send_sms_via_provider(&config, &formatted_phone, &payload.message)
+    let (success, quota_remaining) = send_sms_via_provider(&state.pool, &config, &formatted_phone, &payload.message)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("SMS error: {}", e)))?;
 
@@ -285,6 +308,7 @@ async fn send_order_confirmation(
 
     let formatted_phone = format_phone_number(&payload.phone)
         .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    require_verified_phone(&state, &formatted_phone).await?;
 
     let message = format!(
         "R-Com Order Confirmed! Order #{} - Total: ${:.2}. Thank you for your purchase! Track your order at rcom.store/orders/{}",
@@ -293,7 +317,7 @@ async fn send_order_confirmation(
         payload.order_id
     );
 
-    let (success, quota_remaining) = send_sms_via_provider(&config, &formatted_phone, &message)
+    let (success, quota_remaining) = send_sms_via_provider(&state.pool, &config, &formatted_phone, &message)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("SMS error: {}", e)))?;
 
@@ -314,6 +338,7 @@ async fn send_shipping_update(
 
     let formatted_phone = format_phone_number(&payload.phone)
         .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    require_verified_phone(&state, &formatted_phone).await?;
 
     let message = format!(
         "R-Com Shipping Update! Order #{} has shipped via {}. Tracking: {}. Estimated delivery 3-5 business days.",
@@ -322,7 +347,7 @@ async fn send_shipping_update(
         payload.tracking_number
     );
 
-    let (success, quota_remaining) = send_sms_via_provider(&config, &formatted_phone, &message)
+    let (success, quota_remaining) = send_sms_via_provider(&state.pool, &config, &formatted_phone, &message)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("SMS error: {}", e)))?;
 
@@ -343,13 +368,14 @@ async fn send_delivery_notification(
 
     let formatted_phone = format_phone_number(&payload.phone)
         .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    require_verified_phone(&state, &formatted_phone).await?;
 
     let message = format!(
         "R-Com Delivery Complete! Your order #{} has been delivered. Enjoy your purchase! Questions? Contact support@rcom.store",
         payload.order_id
     );
 
-    let (success, quota_remaining) = send_sms_via_provider(&config, &formatted_phone, &message)
+    let (success, quota_remaining) = send_sms_via_provider(&state.pool, &config, &formatted_phone, &message)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("SMS error: {}", e)))?;
 
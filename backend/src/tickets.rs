@@ -0,0 +1,877 @@
+// Event Tickets Module - Dated, limited-capacity products
+// A ticketed product has a start date and a capacity. Checkout holds the cart's seats for
+// TICKET_HOLD_MINUTES; the hold turns into sold seats when the order is created, and unpaid
+// holds are released (and their payment cancelled) once they expire, so capacity can't be
+// oversold. Each seat bought gets a ticket with a QR code, emailed to the customer and
+// available as PNG or PDF, which staff scan at the door to check the holder in. Refunded
+// orders have their tickets voided and the seats returned
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::{get, post, put},
+    Json, Router,
+};
+use printpdf::{BuiltinFont, Mm, PdfDocument, Rect};
+use qrcode::QrCode;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::app_config;
+use crate::branding::{escape_html, load_branding, Branding};
+use crate::cart_pricing::PricedLine;
+use crate::checkout_carts;
+use crate::coupons;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::loyalty;
+use crate::notifications::ops::OpsNotifier;
+use crate::notifications::{queue_email, EmailPriority};
+use crate::pii::Encrypted;
+use crate::route_limits::{self, RouteGroup};
+use crate::store_credit;
+use crate::unit_of_work::WorkError;
+use crate::AppState;
+
+const QR_MODULE_PX: usize = 8;
+const QR_QUIET_ZONE: usize = 4; // Modules of white border scanners need around the code
+
+// Database model for ticketed products
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct TicketEvent {
+    pub product_id: i32,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub venue: Option<String>,
+    pub capacity: i32,
+    pub sold: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// Upcoming event on the storefront
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct EventListing {
+    pub product_id: i32,
+    pub name: String,
+    pub price: f64,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub venue: Option<String>,
+    pub capacity: i32,
+    pub remaining: i64, // Capacity less sold and held seats
+}
+
+// Database model for tickets
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Ticket {
+    pub id: Uuid,
+    pub code: String,
+    pub product_id: i32,
+    pub order_id: Uuid,
+    pub status: String, // valid, void
+    pub checked_in_at: Option<DateTime<Utc>>,
+    pub checked_in_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// A ticket with its event details, for emails, the PDF and check-in
+#[derive(Debug, Clone, Serialize)]
+pub struct TicketDetails {
+    #[serde(flatten)]
+    pub ticket: Ticket,
+    pub event_name: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub venue: Option<String>,
+}
+
+// A ticket joined with its event as query_as! reads it (it can't fill the flattened ticket)
+struct TicketDetailsRow {
+    id: Uuid,
+    code: String,
+    product_id: i32,
+    order_id: Uuid,
+    status: String,
+    checked_in_at: Option<DateTime<Utc>>,
+    checked_in_by: Option<String>,
+    created_at: DateTime<Utc>,
+    event_name: String,
+    starts_at: DateTime<Utc>,
+    ends_at: Option<DateTime<Utc>>,
+    venue: Option<String>,
+}
+
+impl From<TicketDetailsRow> for TicketDetails {
+    fn from(row: TicketDetailsRow) -> Self {
+        TicketDetails {
+            ticket: Ticket {
+                id: row.id,
+                code: row.code,
+                product_id: row.product_id,
+                order_id: row.order_id,
+                status: row.status,
+                checked_in_at: row.checked_in_at,
+                checked_in_by: row.checked_in_by,
+                created_at: row.created_at,
+            },
+            event_name: row.event_name,
+            starts_at: row.starts_at,
+            ends_at: row.ends_at,
+            venue: row.venue,
+        }
+    }
+}
+
+// query_as! for tickets joined with their events (`t` and `e`) and products (`p`); the
+// columns go between `$before` and `$after` (see product_query!)
+macro_rules! ticket_details_query {
+    ($before:literal, $after:literal $(, $arg:expr)* $(,)?) => {
+        sqlx::query_as!(
+            TicketDetailsRow,
+            $before
+                + " t.id, t.code, t.product_id, t.order_id, t.status, t.checked_in_at, t.checked_in_by, t.created_at,
+                    p.name AS event_name, e.starts_at, e.ends_at, e.venue "
+                + $after
+            $(, $arg)*
+        )
+    };
+}
+
+#[derive(Deserialize)]
+pub struct EventInput {
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub venue: Option<String>,
+    pub capacity: i32,
+}
+
+#[derive(Serialize)]
+pub struct EventTickets {
+    pub event: TicketEvent,
+    pub held: i64, // Seats in unpaid checkouts
+    pub checked_in: i64,
+    pub tickets: Vec<Ticket>,
+}
+
+#[derive(Deserialize)]
+pub struct ValidateTicketRequest {
+    pub code: String,
+    #[serde(default = "default_check_in")]
+    pub check_in: bool, // false only looks the ticket up
+}
+
+fn default_check_in() -> bool {
+    true
+}
+
+// Seats left for one event, as checked at checkout
+#[derive(sqlx::FromRow)]
+struct Availability {
+    product_id: i32,
+    name: String,
+    starts_at: DateTime<Utc>,
+    remaining: i64,
+}
+
+pub fn ticket_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/events", get(list_events))
+        .route("/api/tickets/:code/qr.png", get(ticket_qr_png))
+        .route("/api/tickets/:code/ticket.pdf", get(ticket_pdf).layer(route_limits::limit(RouteGroup::Pdfs)))
+        .route("/api/admin/products/:id/event", put(set_event))
+        .route("/api/admin/products/:id/tickets", get(list_event_tickets))
+        .route("/api/admin/tickets/validate", post(validate_ticket))
+        .with_state(app_state)
+}
+
+// Quantity of each ticketed product in the cart (other products are ignored by the queries)
+fn quantities(lines: &[PricedLine]) -> BTreeMap<i32, i64> {
+    let mut quantities = BTreeMap::new();
+    for line in lines {
+        *quantities.entry(line.product_id).or_insert(0) += line.quantity as i64;
+    }
+    quantities
+}
+
+// Seats left for the cart's events; locked for update while holds are placed
+async fn load_availability(
+    executor: impl sqlx::PgExecutor<'_>,
+    product_ids: &[i32],
+    lock: bool,
+) -> Result<Vec<Availability>, sqlx::Error> {
+    if lock {
+        sqlx::query_as!(
+            Availability,
+            r#"SELECT e.product_id, p.name, e.starts_at,
+                    (e.capacity - e.sold - COALESCE((SELECT SUM(h.quantity) FROM ticket_holds h
+                                                      WHERE h.product_id = e.product_id AND h.status = 'held'), 0))::BIGINT AS "remaining!"
+             FROM ticket_events e JOIN products p ON p.id = e.product_id
+             WHERE e.product_id = ANY($1)
+             ORDER BY e.product_id FOR UPDATE OF e"#,
+            product_ids,
+        )
+        .fetch_all(executor)
+        .await
+    } else {
+        sqlx::query_as!(
+            Availability,
+            r#"SELECT e.product_id, p.name, e.starts_at,
+                    (e.capacity - e.sold - COALESCE((SELECT SUM(h.quantity) FROM ticket_holds h
+                                                      WHERE h.product_id = e.product_id AND h.status = 'held'), 0))::BIGINT AS "remaining!"
+             FROM ticket_events e JOIN products p ON p.id = e.product_id
+             WHERE e.product_id = ANY($1)
+             ORDER BY e.product_id"#,
+            product_ids,
+        )
+        .fetch_all(executor)
+        .await
+    }
+}
+
+// Err is a message for the customer when an event has started or hasn't enough seats left
+fn check_seats(availability: &[Availability], quantities: &BTreeMap<i32, i64>) -> Result<(), String> {
+    for event in availability {
+        let wanted = quantities.get(&event.product_id).copied().unwrap_or(0);
+        if event.starts_at <= Utc::now() {
+            return Err(format!("{} has already started", event.name));
+        }
+        if wanted > event.remaining {
+            return Err(if event.remaining <= 0 {
+                format!("{} is sold out", event.name)
+            } else {
+                format!("Only {} tickets left for {}", event.remaining, event.name)
+            });
+        }
+    }
+    Ok(())
+}
+
+// Early check used when pricing a cart; the seats are only held at checkout
+pub async fn check_available(pool: &sqlx::PgPool, lines: &[PricedLine]) -> Result<(), String> {
+    let quantities = quantities(lines);
+    let ids: Vec<i32> = quantities.keys().copied().collect();
+    let availability = load_availability(pool, &ids, false)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+    check_seats(&availability, &quantities)
+}
+
+// Hold the cart's seats until the payment goes through or TICKET_HOLD_MINUTES pass, as part of
+// the checkout's unit of work
+pub async fn hold_for_cart(
+    conn: &mut sqlx::PgConnection,
+    cart_id: Uuid,
+    lines: &[PricedLine],
+) -> Result<(), WorkError<(StatusCode, String)>> {
+    let quantities = quantities(lines);
+    let ids: Vec<i32> = quantities.keys().copied().collect();
+
+    let availability = load_availability(&mut *conn, &ids, true).await?;
+    check_seats(&availability, &quantities).map_err(|e| WorkError::Aborted((StatusCode::CONFLICT, e)))?;
+    for event in &availability {
+        sqlx::query!(
+            "INSERT INTO ticket_holds (product_id, cart_id, quantity, expires_at)
+             VALUES ($1, $2, $3, NOW() + make_interval(mins => $4::INT))",
+            event.product_id,
+            cart_id,
+            quantities[&event.product_id] as i32,
+            app_config::get().checkout.ticket_hold_minutes as i32,
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+// Give back the seats of a checkout that never reached the payment provider, and the coupon,
+// points and store credit it held (a checkout of tickets only holds no stock to release them with)
+pub async fn release_cart_holds(pool: &sqlx::PgPool, cart_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!("UPDATE ticket_holds SET status = 'released' WHERE cart_id = $1 AND status = 'held'", cart_id)
+        .execute(pool)
+        .await?;
+    coupons::release_hold(pool, cart_id).await?;
+    loyalty::release_hold(pool, cart_id).await?;
+    store_credit::release_hold(pool, cart_id).await?;
+    Ok(())
+}
+
+// Keep the product's stock figure in line with the seats left, so the storefront shows sold out
+async fn sync_inventory(executor: impl sqlx::PgExecutor<'_>, product_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE products p SET inventory = e.capacity - e.sold, updated_at = NOW()
+         FROM ticket_events e WHERE e.product_id = p.id AND p.id = $1",
+        product_id,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+fn generate_code() -> String {
+    hex::encode_upper(rand::thread_rng().gen::<[u8; 16]>())
+}
+
+// Issue the tickets the order is still owed, selling the seats its checkout held. Seats
+// without a hold (Square, manual orders, lapsed holds) are taken from what is left; returns
+// the events that had too few seats, with how many tickets went unissued. Safe to call again
+async fn issue_for_order(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!("SELECT pg_advisory_xact_lock(hashtext($1::UUID::TEXT))", order_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let owed: Vec<(i32, String, Option<Uuid>, i64)> = sqlx::query!(
+        r#"SELECT i.product_id AS "product_id!", p.name, o.cart_id, SUM(i.quantity)::BIGINT
+             - (SELECT COUNT(*) FROM tickets t WHERE t.order_id = $1 AND t.product_id = i.product_id) AS "owed!"
+         FROM order_items i
+         JOIN orders o ON o.id = i.order_id
+         JOIN ticket_events e ON e.product_id = i.product_id
+         JOIN products p ON p.id = i.product_id
+         WHERE i.order_id = $1 AND o.status IN ('completed', 'authorized', 'awaiting_payment')
+         GROUP BY i.product_id, p.name, o.cart_id"#,
+        order_id,
+    )
+    .map(|r| (r.product_id, r.name, r.cart_id, r.owed))
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut shortfalls = Vec::new();
+    for (product_id, name, cart_id, owed) in owed.into_iter().filter(|(_, _, _, owed)| *owed > 0) {
+        let (capacity, sold): (i32, i32) =
+            sqlx::query!("SELECT capacity, sold FROM ticket_events WHERE product_id = $1 FOR UPDATE", product_id)
+                .map(|r| (r.capacity, r.sold))
+                .fetch_one(&mut *tx)
+                .await?;
+        let held: Option<i32> = sqlx::query_scalar!(
+            "UPDATE ticket_holds SET status = 'converted'
+             WHERE cart_id = $1 AND product_id = $2 AND status = 'held'
+             RETURNING quantity",
+            cart_id,
+            product_id,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let others_held: i64 = sqlx::query_scalar!(
+            r#"SELECT COALESCE(SUM(quantity), 0)::BIGINT AS "others_held!" FROM ticket_holds WHERE product_id = $1 AND status = 'held'"#,
+            product_id,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let from_hold = owed.min(held.unwrap_or(0) as i64);
+        let free = (capacity as i64 - sold as i64 - from_hold - others_held).max(0);
+        let issued = from_hold + (owed - from_hold).min(free);
+        sqlx::query!(
+            "UPDATE ticket_events SET sold = sold + $2, updated_at = NOW() WHERE product_id = $1",
+            product_id,
+            issued as i32,
+        )
+        .execute(&mut *tx)
+        .await?;
+        for _ in 0..issued {
+            sqlx::query!(
+                "INSERT INTO tickets (code, product_id, order_id) VALUES ($1, $2, $3)",
+                generate_code(),
+                product_id,
+                order_id,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        sync_inventory(&mut *tx, product_id).await?;
+        if issued < owed {
+            shortfalls.push((name, owed - issued));
+        }
+    }
+    tx.commit().await?;
+    Ok(shortfalls)
+}
+
+// The order's tickets with their events
+async fn order_tickets(pool: &sqlx::PgPool, order_id: Uuid) -> Result<Vec<TicketDetails>, sqlx::Error> {
+    let rows = ticket_details_query!(
+        "SELECT",
+        "FROM tickets t
+         JOIN ticket_events e ON e.product_id = t.product_id
+         JOIN products p ON p.id = t.product_id
+         WHERE t.order_id = $1 AND t.status = 'valid'
+         ORDER BY e.starts_at, t.created_at, t.code",
+        order_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(TicketDetails::from).collect())
+}
+
+async fn load_ticket(pool: &sqlx::PgPool, code: &str) -> Result<Option<TicketDetails>, sqlx::Error> {
+    let row = ticket_details_query!(
+        "SELECT",
+        "FROM tickets t
+         JOIN ticket_events e ON e.product_id = t.product_id
+         JOIN products p ON p.id = t.product_id
+         WHERE t.code = $1",
+        code.trim().to_uppercase(),
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(TicketDetails::from))
+}
+
+fn ticket_url(code: &str, file: &str) -> String {
+    let api_url = app_config::get().server.public_api_url.trim_end_matches('/').to_string();
+    format!("{}/api/tickets/{}/{}", api_url, code, file)
+}
+
+fn format_event_date(ticket: &TicketDetails) -> String {
+    ticket.starts_at.format("%A, %B %-d, %Y at %H:%M UTC").to_string()
+}
+
+// Email the order's tickets, each with its QR code and a printable PDF
+async fn send_tickets_email(pool: &sqlx::PgPool, order_id: Uuid) -> Result<(), String> {
+    let email: Option<Option<Encrypted>> = sqlx::query_scalar!(
+        r#"SELECT customer_email AS "email: Encrypted" FROM orders WHERE id = $1"#,
+        order_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("DB error: {}", e))?;
+    let Some(email) = email.flatten() else {
+        return Ok(());
+    };
+    let tickets = order_tickets(pool, order_id).await.map_err(|e| format!("DB error: {}", e))?;
+    if tickets.is_empty() {
+        return Ok(());
+    }
+
+    let blocks: String = tickets
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            format!(
+                "<div style=\"margin:16px 0;padding:12px;border:1px solid #ddd\">\
+                 <p><strong>{}</strong> &middot; Ticket {} of {}<br>{}{}</p>\
+                 <img src=\"{}\" alt=\"Ticket QR code\" width=\"180\" height=\"180\">\
+                 <p><code>{}</code><br><a href=\"{}\">Download PDF ticket</a></p></div>",
+                escape_html(&t.event_name),
+                i + 1,
+                tickets.len(),
+                format_event_date(t),
+                t.venue.as_deref().map(|v| format!("<br>{}", escape_html(v))).unwrap_or_default(),
+                ticket_url(&t.ticket.code, "qr.png"),
+                t.ticket.code,
+                ticket_url(&t.ticket.code, "ticket.pdf"),
+            )
+        })
+        .collect();
+    let html_body = load_branding(pool).await.email_html(
+        "Your Tickets",
+        &format!(
+            "<p>Here are your tickets. Show the QR code at the entrance; each ticket admits one person once.</p>\
+             <p>Order ID: {}</p>{}",
+            order_id, blocks
+        ),
+    );
+    queue_email(pool, &email, &format!("Your tickets - {}", order_id), &html_body, EmailPriority::Immediate).await
+}
+
+// Issues and emails tickets once an order is confirmed, and voids the tickets of refunded orders
+pub struct TicketSubscriber {
+    pool: Arc<sqlx::PgPool>,
+    ops: OpsNotifier,
+}
+
+impl TicketSubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>, ops: OpsNotifier) -> Self {
+        Self { pool, ops }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for TicketSubscriber {
+    fn name(&self) -> &'static str {
+        "tickets"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        match event {
+            DomainEvent::OrderCreated { order_id, .. } => {
+                let shortfalls = issue_for_order(&self.pool, *order_id)
+                    .await
+                    .map_err(|e| format!("DB error: {}", e))?;
+                for (name, missing) in shortfalls {
+                    self.ops.notify(format!(
+                        ":tickets: {} is sold out; order {} was paid for {} tickets that could not be issued (refund or raise capacity)",
+                        name, order_id, missing
+                    ));
+                }
+                send_tickets_email(&self.pool, *order_id).await?;
+            }
+            DomainEvent::OrderRefunded { order_id, .. } => {
+                // Partial refunds leave the tickets alone
+                let voided: Vec<(i32, i64)> = sqlx::query!(
+                    r#"WITH voided AS (
+                         UPDATE tickets SET status = 'void'
+                         WHERE order_id = $1 AND status = 'valid'
+                           AND EXISTS (SELECT 1 FROM orders WHERE id = $1 AND status = 'refunded')
+                         RETURNING product_id
+                     )
+                     SELECT product_id AS "product_id!", COUNT(*) AS "count!" FROM voided GROUP BY product_id"#,
+                    order_id,
+                )
+                .map(|r| (r.product_id, r.count))
+                .fetch_all(&*self.pool)
+                .await
+                .map_err(|e| format!("DB error: {}", e))?;
+                for (product_id, count) in voided {
+                    sqlx::query!(
+                        "UPDATE ticket_events SET sold = GREATEST(sold - $2, 0), updated_at = NOW() WHERE product_id = $1",
+                        product_id,
+                        count as i32,
+                    )
+                    .execute(&*self.pool)
+                    .await
+                    .map_err(|e| format!("DB error: {}", e))?;
+                    sync_inventory(&*self.pool, product_id)
+                        .await
+                        .map_err(|e| format!("DB error: {}", e))?;
+                    tracing::info!("Voided {} tickets of refunded order {}", count, order_id);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+// Release expired holds. The checkout's payment is cancelled first so it can't go through
+// afterwards; holds whose payment already succeeded (or is still processing) are left for the
+// order to convert, and holds of carts that became orders are converted here if that was missed
+async fn release_expired_holds(state: &AppState) -> Result<(), sqlx::Error> {
+    let carts: Vec<(Uuid, Uuid, Option<String>, bool)> = sqlx::query!(
+        r#"SELECT DISTINCT c.id, c.order_id, c.payment_intent_id, c.converted_at IS NOT NULL AS "converted!"
+         FROM ticket_holds h JOIN checkout_carts c ON c.id = h.cart_id
+         WHERE h.status = 'held' AND h.expires_at <= NOW()"#,
+    )
+    .map(|r| (r.id, r.order_id, r.payment_intent_id, r.converted))
+    .fetch_all(&*state.pool)
+    .await?;
+
+    for (cart_id, order_id, payment_intent_id, converted) in carts {
+        if converted {
+            issue_for_order(&state.pool, order_id).await?;
+            release_cart_holds(&state.pool, cart_id).await?;
+            continue;
+        }
+        if let Some(payment_intent_id) = payment_intent_id {
+            if !checkout_carts::cancel_abandoned_payment(state, &payment_intent_id).await {
+                continue;
+            }
+        }
+        release_cart_holds(&state.pool, cart_id).await?;
+        tracing::info!("Released expired ticket hold of checkout {}", cart_id);
+        if let Err(e) = checkout_carts::notify_hold_expired(&state.pool, cart_id).await {
+            tracing::error!("Failed to email expired hold notice for checkout {}: {}", cart_id, e);
+        }
+    }
+    Ok(())
+}
+
+// Background task that releases expired ticket holds (checked every ALERT_CHECK_INTERVAL_SECS)
+pub fn spawn_hold_release(state: Arc<AppState>) {
+    let interval_secs = app_config::get().schedules.alert_check_interval_secs;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            if let Err(e) = release_expired_holds(&state).await {
+                tracing::error!("Failed to release expired ticket holds: {}", e);
+            }
+        }
+    });
+}
+
+// Upcoming events with the seats still available
+async fn list_events(State(app_state): State<Arc<AppState>>) -> Result<Json<Vec<EventListing>>, (StatusCode, String)> {
+    let events = sqlx::query_as!(
+        EventListing,
+        r#"SELECT e.product_id, p.name, p.price, e.starts_at, e.ends_at, e.venue, e.capacity,
+                GREATEST(e.capacity - e.sold - COALESCE((SELECT SUM(h.quantity) FROM ticket_holds h
+                                                         WHERE h.product_id = e.product_id AND h.status = 'held'), 0), 0)::BIGINT AS "remaining!"
+         FROM ticket_events e JOIN products p ON p.id = e.product_id
+         WHERE p.status = 'published' AND e.starts_at > NOW()
+         ORDER BY e.starts_at, p.name"#,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(events))
+}
+
+// Make the product a ticketed event, or change its date, venue or capacity. Capacity can't go
+// below the seats already sold or held
+async fn set_event(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+    Json(input): Json<EventInput>,
+) -> Result<Json<TicketEvent>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    if input.capacity < 0 {
+        return Err((StatusCode::BAD_REQUEST, "capacity must be at least 0".to_string()));
+    }
+    if input.ends_at.is_some_and(|end| end <= input.starts_at) {
+        return Err((StatusCode::BAD_REQUEST, "ends_at must be after starts_at".to_string()));
+    }
+    let venue = input.venue.as_deref().map(str::trim).filter(|v| !v.is_empty());
+
+    let mut tx = app_state.pool.begin().await.map_err(db_error)?;
+    let committed: Option<i64> = sqlx::query_scalar!(
+        r#"SELECT (e.sold + COALESCE((SELECT SUM(h.quantity) FROM ticket_holds h
+                                    WHERE h.product_id = e.product_id AND h.status = 'held'), 0))::BIGINT AS "committed!"
+         FROM ticket_events e WHERE e.product_id = $1 FOR UPDATE OF e"#,
+        product_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    if let Some(committed) = committed.filter(|c| *c > input.capacity as i64) {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("{} seats are already sold or held; capacity can't be lower", committed),
+        ));
+    }
+
+    let event = sqlx::query_as!(
+        TicketEvent,
+        "INSERT INTO ticket_events (product_id, starts_at, ends_at, venue, capacity)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (product_id) DO UPDATE SET
+             starts_at = EXCLUDED.starts_at,
+             ends_at = EXCLUDED.ends_at,
+             venue = EXCLUDED.venue,
+             capacity = EXCLUDED.capacity,
+             updated_at = NOW()
+         RETURNING product_id, starts_at, ends_at, venue, capacity, sold, created_at, updated_at",
+        product_id,
+        input.starts_at,
+        input.ends_at,
+        venue,
+        input.capacity,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+            (StatusCode::NOT_FOUND, "Product not found".to_string())
+        }
+        _ => db_error(e),
+    })?;
+    sync_inventory(&mut *tx, product_id).await.map_err(db_error)?;
+    tx.commit().await.map_err(db_error)?;
+    tracing::info!("{} set product {} as an event with {} seats", admin.username, product_id, event.capacity);
+    Ok(Json(event))
+}
+
+// The event's tickets, newest first, with sold, held and checked-in counts
+async fn list_event_tickets(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+) -> Result<Json<EventTickets>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let event = sqlx::query_as!(TicketEvent, "SELECT product_id, starts_at, ends_at, venue, capacity, sold, created_at, updated_at FROM ticket_events WHERE product_id = $1", product_id)
+        .fetch_optional(&*app_state.pool)
+        .await
+        .map_err(db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Not an event product".to_string()))?;
+    let held: i64 = sqlx::query_scalar!(
+        r#"SELECT COALESCE(SUM(quantity), 0)::BIGINT AS "held!" FROM ticket_holds WHERE product_id = $1 AND status = 'held'"#,
+        product_id,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    let tickets = sqlx::query_as!(
+        Ticket,
+        "SELECT id, code, product_id, order_id, status, checked_in_at, checked_in_by, created_at FROM tickets WHERE product_id = $1 ORDER BY created_at DESC",
+        product_id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    let checked_in = tickets.iter().filter(|t| t.checked_in_at.is_some()).count() as i64;
+    Ok(Json(EventTickets { event, held, checked_in, tickets }))
+}
+
+// Scan a ticket at the door: valid tickets are checked in once; void, unknown, already used
+// and past-event tickets are refused
+async fn validate_ticket(
+    admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<ValidateTicketRequest>,
+) -> Result<Json<TicketDetails>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let mut ticket = load_ticket(&app_state.pool, &request.code)
+        .await
+        .map_err(db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Unknown ticket".to_string()))?;
+    if ticket.ticket.status == "void" {
+        return Err((StatusCode::CONFLICT, "Ticket is void (order refunded)".to_string()));
+    }
+    if ticket.ends_at.unwrap_or(ticket.starts_at + chrono::Duration::days(1)) < Utc::now() {
+        return Err((StatusCode::CONFLICT, format!("Ticket is for a past event ({})", ticket.event_name)));
+    }
+    let already = |at: DateTime<Utc>, by: Option<&str>| {
+        (
+            StatusCode::CONFLICT,
+            format!("Already checked in at {}{}", at.format("%Y-%m-%d %H:%M UTC"), by.map(|b| format!(" by {}", b)).unwrap_or_default()),
+        )
+    };
+    if let Some(at) = ticket.ticket.checked_in_at {
+        return Err(already(at, ticket.ticket.checked_in_by.as_deref()));
+    }
+    if !request.check_in {
+        return Ok(Json(ticket));
+    }
+
+    // Two scanners reading the same ticket at once: only one checks it in
+    let checked_in: Option<DateTime<Utc>> = sqlx::query_scalar!(
+        r#"UPDATE tickets SET checked_in_at = NOW(), checked_in_by = $2
+         WHERE id = $1 AND checked_in_at IS NULL RETURNING checked_in_at AS "checked_in!""#,
+        ticket.ticket.id,
+        &admin.username,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    let Some(at) = checked_in else {
+        return Err(already(Utc::now(), None));
+    };
+    ticket.ticket.checked_in_at = Some(at);
+    ticket.ticket.checked_in_by = Some(admin.username);
+    Ok(Json(ticket))
+}
+
+// Grayscale PNG of the QR code, with the quiet zone scanners need
+fn render_qr_png(data: &str) -> Result<Vec<u8>, String> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| e.to_string())?;
+    let modules = code.width();
+    let colors = code.to_colors();
+    let size = (modules + 2 * QR_QUIET_ZONE) * QR_MODULE_PX;
+    let mut pixels = vec![255u8; size * size];
+    for (i, color) in colors.iter().enumerate() {
+        if *color != qrcode::Color::Dark {
+            continue;
+        }
+        let (mx, my) = (i % modules + QR_QUIET_ZONE, i / modules + QR_QUIET_ZONE);
+        for y in my * QR_MODULE_PX..(my + 1) * QR_MODULE_PX {
+            pixels[y * size + mx * QR_MODULE_PX..y * size + (mx + 1) * QR_MODULE_PX].fill(0);
+        }
+    }
+
+    let mut png_bytes = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_bytes, size as u32, size as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        writer.write_image_data(&pixels).map_err(|e| e.to_string())?;
+    }
+    Ok(png_bytes)
+}
+
+// The ticket's QR code; the code in the URL is the ticket itself, so no login is needed
+async fn ticket_qr_png(
+    State(app_state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let ticket = load_ticket(&app_state.pool, &code)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Ticket not found".to_string()))?;
+    let png = render_qr_png(&ticket.ticket.code)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to render QR code: {}", e)))?;
+    Ok(([(header::CONTENT_TYPE, "image/png")], png))
+}
+
+// Printable ticket with the event details and the QR code
+async fn ticket_pdf(
+    State(app_state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let ticket = load_ticket(&app_state.pool, &code)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Ticket not found".to_string()))?;
+    let branding = load_branding(&app_state.pool).await;
+    let pdf = render_ticket_pdf(&branding, &ticket)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to render ticket: {}", e)))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (header::CONTENT_DISPOSITION, format!("inline; filename=\"ticket-{}.pdf\"", ticket.ticket.code)),
+        ],
+        pdf,
+    ))
+}
+
+// Half-letter ticket: store name, event, date and venue above a QR code drawn module by module
+fn render_ticket_pdf(branding: &Branding, ticket: &TicketDetails) -> Result<Vec<u8>, String> {
+    const PAGE_WIDTH: f32 = 139.7;
+    const PAGE_HEIGHT: f32 = 215.9;
+    const MARGIN: f32 = 15.0;
+    const QR_SIZE: f32 = 70.0;
+
+    let (doc, page, layer) = PdfDocument::new(
+        format!("Ticket {}", ticket.ticket.code),
+        Mm(PAGE_WIDTH),
+        Mm(PAGE_HEIGHT),
+        "Ticket",
+    );
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| e.to_string())?;
+    let layer = doc.get_page(page).get_layer(layer);
+
+    let mut y = PAGE_HEIGHT - MARGIN;
+    layer.use_text(&branding.store_name, 12.0, Mm(MARGIN), Mm(y), &regular);
+    y -= 12.0;
+    layer.use_text(&ticket.event_name, 18.0, Mm(MARGIN), Mm(y), &bold);
+    y -= 9.0;
+    layer.use_text(format_event_date(ticket), 11.0, Mm(MARGIN), Mm(y), &regular);
+    if let Some(venue) = &ticket.venue {
+        y -= 6.0;
+        layer.use_text(venue, 11.0, Mm(MARGIN), Mm(y), &regular);
+    }
+    y -= 10.0;
+
+    let code = QrCode::new(ticket.ticket.code.as_bytes()).map_err(|e| e.to_string())?;
+    let modules = code.width();
+    let module = QR_SIZE / modules as f32;
+    let left = (PAGE_WIDTH - QR_SIZE) / 2.0;
+    let top = y;
+    for (i, color) in code.to_colors().iter().enumerate() {
+        if *color != qrcode::Color::Dark {
+            continue;
+        }
+        let x = left + (i % modules) as f32 * module;
+        let y = top - (i / modules + 1) as f32 * module;
+        layer.add_rect(Rect::new(Mm(x), Mm(y), Mm(x + module), Mm(y + module)));
+    }
+    y = top - QR_SIZE - 8.0;
+
+    layer.use_text(&ticket.ticket.code, 10.0, Mm(left), Mm(y), &regular);
+    y -= 6.0;
+    layer.use_text(format!("Order {}", ticket.ticket.order_id), 8.0, Mm(left), Mm(y), &regular);
+    y -= 10.0;
+    layer.use_text("Admits one. Valid for a single entry.", 9.0, Mm(MARGIN), Mm(y), &regular);
+
+    doc.save_to_bytes().map_err(|e| e.to_string())
+}
@@ -0,0 +1,60 @@
+// TLS Module - Optional HTTPS termination with rustls
+// With TLS_CERT_PATH and TLS_KEY_PATH set the server speaks HTTPS itself and offers HTTP/2 and
+// HTTP/1.1 through ALPN, so it can run without a reverse proxy. Both files are checked every
+// CERT_CHECK_INTERVAL_SECS and reloaded when either changes (e.g. after a certbot renewal).
+// Open connections keep the certificate they started with, and a renewal that can't be loaded
+// is logged while the current certificate stays in use
+
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use std::net::SocketAddr;
+use std::time::SystemTime;
+
+const CERT_CHECK_INTERVAL_SECS: u64 = 30;
+
+// Serve the app over HTTPS until the process exits
+pub async fn serve(app: Router, addr: SocketAddr, cert_path: &str, key_path: &str) {
+    // ring is the crypto provider compiled in; installing fails only if one already is
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let config = match RustlsConfig::from_pem_file(cert_path, key_path).await {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("Failed to load TLS certificate {} and key {}: {}", cert_path, key_path, e);
+            std::process::exit(1);
+        }
+    };
+    spawn_certificate_reload(config.clone(), cert_path.to_string(), key_path.to_string());
+
+    axum_server::bind_rustls(addr, config)
+        .serve(app.into_make_service())
+        .await
+        .unwrap();
+}
+
+// Modification times of the certificate and key; symlinks (as certbot uses) are followed
+fn modified_times(cert_path: &str, key_path: &str) -> (Option<SystemTime>, Option<SystemTime>) {
+    let modified = |path: &str| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    (modified(cert_path), modified(key_path))
+}
+
+fn spawn_certificate_reload(config: RustlsConfig, cert_path: String, key_path: String) {
+    tokio::spawn(async move {
+        let mut loaded = modified_times(&cert_path, &key_path);
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(CERT_CHECK_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let current = modified_times(&cert_path, &key_path);
+            if current == loaded {
+                continue;
+            }
+            // Tried again next tick on failure, e.g. when the key is written after the certificate
+            match config.reload_from_pem_file(&cert_path, &key_path).await {
+                Ok(()) => {
+                    tracing::info!("Reloaded TLS certificate from {}", cert_path);
+                    loaded = current;
+                }
+                Err(e) => tracing::error!("Failed to reload TLS certificate, keeping the current one: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,131 @@
+// Unit Of Work Module - Multi-step writes that commit or roll back together
+// Creating an order from a webhook writes the order, its items, the checkout cart and its stock
+// reservations, the payment check and the coupon / store credit / loyalty redemptions; starting a
+// checkout saves the cart and holds its seats and stock. Run as separate statements, a failure
+// half way left e.g. an order without its items, or stock held for a cart that was never saved.
+// `run` does the work in one SERIALIZABLE transaction and starts it again when Postgres reports
+// a serialization failure or deadlock, so two webhooks for the same payment (Stripe sends
+// payment_intent.succeeded and charge.succeeded together) can't both create its order.
+// Domain events and ops messages raised by the work are held until it commits, so an attempt
+// that rolls back never emails a customer. The work may run more than once: it must not call
+// payment or other providers
+
+use futures_util::future::BoxFuture;
+use sqlx::{PgConnection, Postgres, Transaction};
+use std::time::Duration;
+
+use crate::events::DomainEvent;
+use crate::AppState;
+
+const MAX_ATTEMPTS: u32 = 5;
+const RETRY_DELAY_MS: u64 = 25; // Times the attempt number, plus up to as much again at random
+
+pub struct UnitOfWork {
+    tx: Transaction<'static, Postgres>,
+    events: Vec<DomainEvent>,
+    ops_messages: Vec<String>,
+}
+
+impl UnitOfWork {
+    // The transaction's connection, for queries and for helpers taking an executor
+    pub fn conn(&mut self) -> &mut PgConnection {
+        &mut self.tx
+    }
+
+    // Publish the event once the work has committed
+    pub fn publish(&mut self, event: DomainEvent) {
+        self.events.push(event);
+    }
+
+    // Post to the ops channel once the work has committed
+    pub fn notify_ops(&mut self, line: String) {
+        self.ops_messages.push(line);
+    }
+}
+
+// Why a unit of work failed: a database error (after any retries), or the work gave up with
+// its own error, e.g. a 409 for a product that sold out
+#[derive(Debug)]
+pub enum WorkError<E> {
+    Db(sqlx::Error),
+    Aborted(E),
+}
+
+impl<E> From<sqlx::Error> for WorkError<E> {
+    fn from(e: sqlx::Error) -> Self {
+        WorkError::Db(e)
+    }
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for WorkError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorkError::Db(e) => write!(f, "DB error: {}", e),
+            WorkError::Aborted(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<WorkError<(axum::http::StatusCode, String)>> for (axum::http::StatusCode, String) {
+    fn from(e: WorkError<(axum::http::StatusCode, String)>) -> Self {
+        match e {
+            WorkError::Db(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)),
+            WorkError::Aborted(e) => e,
+        }
+    }
+}
+
+// Serialization failures and deadlocks go away when the transaction is run again
+pub fn is_retryable(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .and_then(|e| e.code())
+        .is_some_and(|code| code == "40001" || code == "40P01")
+}
+
+// Run `work` in a serializable transaction, retrying it from the start on serialization
+// failures, then publish what it raised. The work's own errors roll it back without a retry
+pub async fn run<T, E, F>(state: &AppState, mut work: F) -> Result<T, WorkError<E>>
+where
+    F: for<'u> FnMut(&'u mut UnitOfWork) -> BoxFuture<'u, Result<T, WorkError<E>>>,
+{
+    let mut attempt = 1;
+    loop {
+        match attempt_once(state, &mut work).await {
+            Err(WorkError::Db(e)) if is_retryable(&e) && attempt < MAX_ATTEMPTS => {
+                let delay = RETRY_DELAY_MS * attempt as u64;
+                let jitter = rand::random::<u64>() % (delay + 1);
+                tracing::warn!("Transaction conflicted (attempt {} of {}), retrying: {}", attempt, MAX_ATTEMPTS, e);
+                tokio::time::sleep(Duration::from_millis(delay + jitter)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn attempt_once<T, E, F>(state: &AppState, work: &mut F) -> Result<T, WorkError<E>>
+where
+    F: for<'u> FnMut(&'u mut UnitOfWork) -> BoxFuture<'u, Result<T, WorkError<E>>>,
+{
+    let mut tx = state.pool.begin().await?;
+    sqlx::query!("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE")
+        .execute(&mut *tx)
+        .await?;
+    let mut uow = UnitOfWork {
+        tx,
+        events: Vec::new(),
+        ops_messages: Vec::new(),
+    };
+
+    // Dropping the transaction on an error rolls it back
+    let value = work(&mut uow).await?;
+    uow.tx.commit().await?;
+
+    for event in uow.events {
+        state.events.publish(event);
+    }
+    for line in uow.ops_messages {
+        state.ops.notify(line);
+    }
+    Ok(value)
+}
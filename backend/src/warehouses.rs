@@ -0,0 +1,430 @@
+// Warehouses Module - Multiple shipping origins with per-warehouse inventory
+// Plans which warehouse ships each cart line (closest stocked origin first) and
+// records the resulting split shipments as order fulfillments
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::easypost_shipping::Address;
+use crate::events::DomainEvent;
+use crate::pricing::CartLine;
+use crate::split_shipments;
+use crate::unit_of_work::WorkError;
+use crate::AppState;
+
+// Database model for warehouses
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Warehouse {
+    pub id: i32,
+    pub name: String,
+    pub street1: String,
+    pub street2: Option<String>,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    pub country: String,
+    pub phone: Option<String>,
+    pub active: bool,
+    pub handling_days: i32,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl Warehouse {
+    // Shipping origin address for rate requests and labels
+    pub fn address(&self) -> Address {
+        Address {
+            name: Some(self.name.clone()),
+            street1: self.street1.clone(),
+            street2: self.street2.clone(),
+            city: self.city.clone(),
+            state: self.state.clone(),
+            zip: self.zip.clone(),
+            country: Some(self.country.clone()),
+            phone: self.phone.clone(),
+            email: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WarehouseInput {
+    pub name: String,
+    pub street1: String,
+    pub street2: Option<String>,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    pub country: Option<String>,
+    pub phone: Option<String>,
+    pub active: Option<bool>,
+    pub handling_days: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow)]
+pub struct WarehouseStock {
+    pub warehouse_id: i32,
+    pub product_id: i32,
+    pub quantity: i32,
+}
+
+#[derive(Deserialize)]
+pub struct StockInput {
+    pub quantity: i32,
+}
+
+// Cart lines assigned to a single origin warehouse
+#[derive(Debug, Clone, Serialize)]
+pub struct FulfillmentGroup {
+    pub warehouse: Warehouse,
+    pub lines: Vec<CartLine>,
+}
+
+// Database model for order fulfillments
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct OrderFulfillment {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub warehouse_id: Option<i32>,
+    pub status: String,
+    pub carrier: Option<String>,
+    pub tracking_code: Option<String>,
+    pub shipment_id: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub order_shipment_id: Option<Uuid>,
+    pub shipped_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateFulfillmentsRequest {
+    pub to_address: Address,
+    pub items: Vec<CartLine>,
+}
+
+// Rough proximity score between two postal codes. Without a geocoder we use the
+// leading ZIP digits, which are assigned geographically in the US; a different
+// country is always treated as furthest away.
+pub fn distance_score(warehouse: &Warehouse, destination: &Address) -> u32 {
+    let dest_country = destination.country.as_deref().unwrap_or("US");
+    if !warehouse.country.eq_ignore_ascii_case(dest_country) {
+        return u32::MAX;
+    }
+    let prefix = |zip: &str| -> Option<u32> {
+        zip.chars().take(3).collect::<String>().parse().ok()
+    };
+    match (prefix(&warehouse.zip), prefix(&destination.zip)) {
+        (Some(a), Some(b)) => a.abs_diff(b),
+        _ => u32::MAX - 1,
+    }
+}
+
+// Assign each cart line to the closest warehouse holding stock, splitting a line
+// across warehouses when no single origin can cover the full quantity.
+pub async fn plan_fulfillment(
+    pool: &sqlx::PgPool,
+    destination: &Address,
+    lines: &[CartLine],
+) -> Result<Vec<FulfillmentGroup>, String> {
+    let mut warehouses = sqlx::query_as!(Warehouse, "SELECT id, name, street1, street2, city, state, zip, country, phone, active, handling_days, created_at FROM warehouses WHERE active = TRUE")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+    if warehouses.is_empty() {
+        return Err("No active warehouses configured".to_string());
+    }
+    warehouses.sort_by_key(|w| distance_score(w, destination));
+
+    let ids: Vec<i32> = lines.iter().map(|l| l.product_id).collect();
+    let stock_rows = sqlx::query_as!(
+        WarehouseStock,
+        "SELECT warehouse_id, product_id, quantity FROM warehouse_inventory WHERE product_id = ANY($1)",
+        &ids,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Database error: {}", e))?;
+    let mut stock: HashMap<(i32, i32), i32> = stock_rows
+        .into_iter()
+        .map(|s| ((s.warehouse_id, s.product_id), s.quantity))
+        .collect();
+
+    let mut assigned: HashMap<i32, Vec<CartLine>> = HashMap::new();
+    for line in lines {
+        let mut remaining = line.quantity;
+
+        // Prefer the closest warehouse that can ship the whole line on its own
+        let whole = warehouses.iter().find(|w| {
+            stock.get(&(w.id, line.product_id)).copied().unwrap_or(0) >= remaining
+        });
+        let order: Vec<&Warehouse> = match whole {
+            Some(w) => vec![w],
+            None => warehouses.iter().collect(),
+        };
+
+        for warehouse in order {
+            if remaining == 0 {
+                break;
+            }
+            let available = stock.entry((warehouse.id, line.product_id)).or_insert(0);
+            let take = remaining.min(*available);
+            if take > 0 {
+                *available -= take;
+                remaining -= take;
+                assigned.entry(warehouse.id).or_default().push(CartLine {
+                    product_id: line.product_id,
+                    quantity: take,
+                    ship_to: line.ship_to.clone(),
+                });
+            }
+        }
+
+        if remaining > 0 {
+            return Err(format!(
+                "Insufficient warehouse stock for product {} ({} short)",
+                line.product_id, remaining
+            ));
+        }
+    }
+
+    Ok(warehouses
+        .into_iter()
+        .filter_map(|w| assigned.remove(&w.id).map(|lines| FulfillmentGroup { warehouse: w, lines }))
+        .collect())
+}
+
+// Admin warehouse and fulfillment routes
+pub fn warehouse_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/warehouses", get(list_warehouses).post(create_warehouse))
+        .route("/api/admin/warehouses/:id", put(update_warehouse).delete(delete_warehouse))
+        .route("/api/admin/warehouses/:id/inventory", get(list_warehouse_inventory))
+        .route("/api/admin/warehouses/:id/inventory/:product_id", put(set_warehouse_inventory))
+        .route("/api/admin/orders/:id/fulfillments", get(list_order_fulfillments).post(create_order_fulfillments))
+        .with_state(app_state)
+}
+
+async fn list_warehouses(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Warehouse>>, (StatusCode, String)> {
+    let warehouses = sqlx::query_as!(Warehouse, "SELECT id, name, street1, street2, city, state, zip, country, phone, active, handling_days, created_at FROM warehouses ORDER BY id")
+        .fetch_all(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(warehouses))
+}
+
+async fn create_warehouse(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<WarehouseInput>,
+) -> Result<(StatusCode, Json<Warehouse>), (StatusCode, String)> {
+    let rec = sqlx::query_as!(
+        Warehouse,
+        "INSERT INTO warehouses (name, street1, street2, city, state, zip, country, phone, active, handling_days)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING id, name, street1, street2, city, state, zip, country, phone, active, handling_days, created_at",
+        &input.name,
+        &input.street1,
+        input.street2.as_deref(),
+        &input.city,
+        &input.state,
+        &input.zip,
+        input.country.as_deref().unwrap_or("US"),
+        input.phone.as_deref(),
+        input.active.unwrap_or(true),
+        input.handling_days.unwrap_or(1).max(0),
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?;
+    Ok((StatusCode::CREATED, Json(rec)))
+}
+
+async fn update_warehouse(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(input): Json<WarehouseInput>,
+) -> Result<Json<Warehouse>, (StatusCode, String)> {
+    sqlx::query_as!(
+        Warehouse,
+        "UPDATE warehouses SET name = $1, street1 = $2, street2 = $3, city = $4, state = $5,
+         zip = $6, country = $7, phone = $8, active = $9, handling_days = $10 WHERE id = $11 RETURNING id, name, street1, street2, city, state, zip, country, phone, active, handling_days, created_at",
+        &input.name,
+        &input.street1,
+        input.street2.as_deref(),
+        &input.city,
+        &input.state,
+        &input.zip,
+        input.country.as_deref().unwrap_or("US"),
+        input.phone.as_deref(),
+        input.active.unwrap_or(true),
+        input.handling_days.unwrap_or(1).max(0),
+        id,
+    )
+    .fetch_optional(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?
+    .map(Json)
+    .ok_or((StatusCode::NOT_FOUND, "Warehouse not found".to_string()))
+}
+
+async fn delete_warehouse(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<bool>, (StatusCode, String)> {
+    let res = sqlx::query!("DELETE FROM warehouses WHERE id = $1", id)
+        .execute(&*app_state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(res.rows_affected() > 0))
+}
+
+async fn list_warehouse_inventory(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<WarehouseStock>>, (StatusCode, String)> {
+    let stock = sqlx::query_as!(
+        WarehouseStock,
+        "SELECT warehouse_id, product_id, quantity FROM warehouse_inventory WHERE warehouse_id = $1 ORDER BY product_id",
+        id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(stock))
+}
+
+async fn set_warehouse_inventory(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path((id, product_id)): Path<(i32, i32)>,
+    Json(input): Json<StockInput>,
+) -> Result<Json<WarehouseStock>, (StatusCode, String)> {
+    if input.quantity < 0 {
+        return Err((StatusCode::BAD_REQUEST, "Quantity must not be negative".to_string()));
+    }
+    let rec = sqlx::query_as!(
+        WarehouseStock,
+        "INSERT INTO warehouse_inventory (warehouse_id, product_id, quantity)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (warehouse_id, product_id)
+         DO UPDATE SET quantity = EXCLUDED.quantity, updated_at = NOW()
+         RETURNING warehouse_id, product_id, quantity",
+        id,
+        product_id,
+        input.quantity,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("DB error: {}", e)))?;
+    Ok(Json(rec))
+}
+
+async fn list_order_fulfillments(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<Vec<OrderFulfillment>>, (StatusCode, String)> {
+    let fulfillments = sqlx::query_as!(
+        OrderFulfillment,
+        "SELECT id, order_id, warehouse_id, status, carrier, tracking_code, shipment_id, created_at, updated_at, order_shipment_id, shipped_at, delivered_at FROM order_fulfillments WHERE order_id = $1 ORDER BY created_at",
+        order_id,
+    )
+    .fetch_all(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(fulfillments))
+}
+
+// Plan origins for an order's items and record one fulfillment per warehouse,
+// reserving the shipped quantities from warehouse stock
+async fn create_order_fulfillments(
+    _admin: AuthenticatedAdmin,
+    State(app_state): State<Arc<AppState>>,
+    Path(order_id): Path<Uuid>,
+    Json(req): Json<CreateFulfillmentsRequest>,
+) -> Result<(StatusCode, Json<Vec<OrderFulfillment>>), (StatusCode, String)> {
+    let groups = plan_fulfillment(&app_state.pool, &req.to_address, &req.items)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let mut tx = app_state.pool.begin().await.map_err(db_err)?;
+    let created = record_fulfillments(&mut tx, order_id, None, &groups).await?;
+    tx.commit().await.map_err(db_err)?;
+    app_state.events.publish(DomainEvent::OrderFulfilled { order_id });
+
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+// Insert one fulfillment per planned origin and deduct the shipped stock. The plan was made from
+// stock read outside the transaction, so a warehouse that no longer has a line's quantity (another
+// fulfillment took it meanwhile) fails the whole thing with 409 instead of going negative. So does
+// fulfilling more of a product than the order has left
+pub async fn record_fulfillments(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    order_id: Uuid,
+    order_shipment_id: Option<Uuid>,
+    groups: &[FulfillmentGroup],
+) -> Result<Vec<OrderFulfillment>, WorkError<(StatusCode, String)>> {
+    let lines: Vec<(i32, i32)> = groups.iter().flat_map(|g| &g.lines).map(|l| (l.product_id, l.quantity)).collect();
+    split_shipments::check_remaining(tx, order_id, &lines).await?;
+    let mut created = Vec::new();
+    for group in groups {
+        let fulfillment = sqlx::query_as!(
+            OrderFulfillment,
+            "INSERT INTO order_fulfillments (order_id, warehouse_id, order_shipment_id) VALUES ($1, $2, $3) RETURNING id, order_id, warehouse_id, status, carrier, tracking_code, shipment_id, created_at, updated_at, order_shipment_id, shipped_at, delivered_at",
+            order_id,
+            group.warehouse.id,
+            order_shipment_id,
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        for line in &group.lines {
+            sqlx::query!(
+                "INSERT INTO order_fulfillment_items (fulfillment_id, product_id, quantity) VALUES ($1, $2, $3)",
+                fulfillment.id,
+                line.product_id,
+                line.quantity,
+            )
+            .execute(&mut **tx)
+            .await?;
+
+            let deducted = sqlx::query!(
+                "UPDATE warehouse_inventory SET quantity = quantity - $1, updated_at = NOW()
+                 WHERE warehouse_id = $2 AND product_id = $3 AND quantity >= $1",
+                line.quantity,
+                group.warehouse.id,
+                line.product_id,
+            )
+            .execute(&mut **tx)
+            .await?;
+            if deducted.rows_affected() == 0 {
+                return Err(WorkError::Aborted((
+                    StatusCode::CONFLICT,
+                    format!(
+                        "{} no longer has {} of product {} in stock; plan the fulfillment again",
+                        group.warehouse.name, line.quantity, line.product_id
+                    ),
+                )));
+            }
+        }
+        created.push(fulfillment);
+    }
+    Ok(created)
+}
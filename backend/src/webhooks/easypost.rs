@@ -0,0 +1,130 @@
+// EasyPost Webhook Handler
+// Processes tracker.updated events so fulfillments are marked delivered
+// Implements HMAC-SHA256 signature verification for security
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json, body::Bytes,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::app_config;
+use crate::AppState;
+use crate::events::DomainEvent;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// EasyPost webhook event structure (only the fields we use)
+#[derive(Debug, Deserialize)]
+pub struct EasyPostWebhookEvent {
+    pub id: String,
+    pub description: String,
+    pub result: Option<EasyPostTracker>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EasyPostTracker {
+    pub tracking_code: String,
+    pub status: String,
+    pub carrier: Option<String>,
+}
+
+// EasyPost webhook endpoint handler
+pub async fn handle_easypost_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let secret = app_config::get().easypost.webhook_secret.as_ref().map(|s| s.expose()).ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "EasyPost webhook secret not configured".to_string(),
+    ))?;
+
+    let signature = headers
+        .get("x-hmac-signature")
+        .and_then(|v| v.to_str().ok())
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            "Missing x-hmac-signature header".to_string(),
+        ))?;
+
+    if !verify_easypost_signature(&body, signature, secret) {
+        tracing::error!("EasyPost webhook signature verification failed");
+        state.ops.webhook_signature_failed("EasyPost");
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "Webhook signature verification failed".to_string(),
+        ));
+    }
+
+    let event: EasyPostWebhookEvent = serde_json::from_slice(&body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", e)))?;
+
+    let tracker = match (event.description.as_str(), event.result) {
+        ("tracker.updated", Some(tracker)) => tracker,
+        _ => {
+            tracing::info!("Received EasyPost event type: {}", event.description);
+            return Ok((StatusCode::OK, Json(json!({"received": true}))));
+        }
+    };
+
+    // The next tracking lookup fetches the update instead of a cached status
+    state.tracking_cache.invalidate(&tracker.tracking_code);
+
+    if tracker.status != "delivered" {
+        return Ok((StatusCode::OK, Json(json!({"received": true}))));
+    }
+
+    // Only the first delivered update flips the status, so retried events don't re-publish
+    let delivered: Vec<(uuid::Uuid, Option<String>)> = sqlx::query!(
+        "UPDATE order_fulfillments SET status = 'delivered', delivered_at = NOW(), updated_at = NOW()
+         WHERE tracking_code = $1 AND status <> 'delivered'
+         RETURNING order_id, carrier",
+        &tracker.tracking_code,
+    )
+    .map(|r| (r.order_id, r.carrier))
+    .fetch_all(&*state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    for (order_id, carrier) in delivered {
+        tracing::info!("Shipment {} for order {} delivered (event {})", tracker.tracking_code, order_id, event.id);
+        state.events.publish(DomainEvent::ShipmentDelivered {
+            order_id: Some(order_id),
+            tracking_code: tracker.tracking_code.clone(),
+            carrier: carrier.or_else(|| tracker.carrier.clone()).unwrap_or_default(),
+            customer_phone: None,
+        });
+    }
+
+    Ok((StatusCode::OK, Json(json!({"received": true}))))
+}
+
+// Verify EasyPost webhook signature: "hmac-sha256-hex=" + hex(HMAC-SHA256(body, secret))
+fn verify_easypost_signature(body: &[u8], signature: &str, secret: &str) -> bool {
+    let provided = match signature
+        .strip_prefix("hmac-sha256-hex=")
+        .and_then(|s| hex::decode(s).ok())
+    {
+        Some(bytes) => bytes,
+        None => return false,
+    };
+
+    let mut mac = match HmacSha256::new_from_slice(secret.as_bytes()) {
+        Ok(m) => m,
+        Err(e) => {
+            tracing::error!("Failed to create HMAC: {}", e);
+            return false;
+        }
+    };
+    mac.update(body);
+
+    // Constant-time comparison
+    mac.verify_slice(&provided).is_ok()
+}
@@ -3,12 +3,28 @@
 
 pub mod stripe;
 pub mod square;
+pub mod easypost;
+pub mod twilio;
 
-use axum::{Router, routing::post};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use sqlx::types::Uuid;
 use sqlx::types::chrono::{DateTime, Utc};
 use std::sync::Arc;
+use crate::admin_auth::AuthenticatedAdmin;
+use crate::checkout_carts::{self, CheckoutLink};
+use crate::order_status;
+pub use crate::order_status::OrderStatus;
+use crate::pagination::{self, Page};
+use crate::pii::{self, Encrypted};
+use crate::repos::WebhookEventFilter;
+use crate::stock_reservations;
 use crate::AppState;
 
 // Enum for payment providers
@@ -19,6 +35,8 @@ pub enum PaymentProvider {
     Stripe,
     #[sqlx(rename = "square")]
     Square,
+    #[sqlx(rename = "manual")]
+    Manual, // Entered by staff; paid offline, by invoice or through a payment link
 }
 
 impl std::fmt::Display for PaymentProvider {
@@ -26,36 +44,13 @@ impl std::fmt::Display for PaymentProvider {
         match self {
             PaymentProvider::Stripe => write!(f, "stripe"),
             PaymentProvider::Square => write!(f, "square"),
-        }
-    }
-}
-
-// Enum for order status
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
-#[sqlx(type_name = "varchar")]
-pub enum OrderStatus {
-    #[sqlx(rename = "pending")]
-    Pending,
-    #[sqlx(rename = "completed")]
-    Completed,
-    #[sqlx(rename = "failed")]
-    Failed,
-    #[sqlx(rename = "refunded")]
-    Refunded,
-}
-
-impl std::fmt::Display for OrderStatus {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            OrderStatus::Pending => write!(f, "pending"),
-            OrderStatus::Completed => write!(f, "completed"),
-            OrderStatus::Failed => write!(f, "failed"),
-            OrderStatus::Refunded => write!(f, "refunded"),
+            PaymentProvider::Manual => write!(f, "manual"),
         }
     }
 }
 
 // Database model for webhook events
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct WebhookEvent {
     pub id: Uuid,
@@ -67,26 +62,54 @@ pub struct WebhookEvent {
     pub processed_at: Option<DateTime<Utc>>,
     pub error_message: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub livemode: bool,
 }
 
 // Database model for orders
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Order {
     pub id: Uuid,
     pub payment_provider: String,
     pub payment_id: String,
     pub payment_intent_id: Option<String>,
-    pub customer_email: Option<String>,
+    pub customer_email: Option<Encrypted>,
     pub customer_name: Option<String>,
     pub total_amount: i64,
     pub currency: String,
-    pub status: String,
+    pub status: OrderStatus,
     pub webhook_event_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub gift_wrap: bool,
+    pub gift_message: Option<String>,
+    pub customer_id: Option<Uuid>,
+    pub cart_id: Option<Uuid>,
+    pub authorization_expires_at: Option<DateTime<Utc>>, // Authorized orders only
+    pub amount_captured: Option<i64>,
+    pub captured_at: Option<DateTime<Utc>>,
+    pub livemode: bool, // false for orders paid with test keys / the Square sandbox
+    pub payment_method: Option<String>, // Manual orders: offline or invoice
+    pub created_by: Option<String>,     // Manual orders: admin who entered it
+    pub payment_link_url: Option<String>,
+    pub payment_link_id: Option<Uuid>, // Stripe Payment Link the order was bought through
+    pub po_number: Option<String>, // Purchase order orders: the buyer's PO number
+    pub payment_due_at: Option<DateTime<Utc>>, // Purchase order orders: when the invoice is due
+    pub paid_at: Option<DateTime<Utc>>,
+    pub payment_reminder_sent_at: Option<DateTime<Utc>>,
+}
+
+impl Order {
+    pub fn gift_options(&self) -> GiftOptions {
+        GiftOptions {
+            gift_wrap: self.gift_wrap,
+            gift_message: self.gift_message.clone(),
+        }
+    }
 }
 
 // Database model for order items
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct OrderItem {
     pub id: Uuid,
@@ -106,9 +129,11 @@ pub struct CreateWebhookEvent {
     pub event_type: String,
     pub event_id: String,
     pub payload: serde_json::Value,
+    pub livemode: bool,
 }
 
 // Struct for creating new orders
+#[derive(Clone)]
 pub struct CreateOrder {
     pub payment_provider: PaymentProvider,
     pub payment_id: String,
@@ -119,104 +144,126 @@ pub struct CreateOrder {
     pub currency: String,
     pub status: OrderStatus,
     pub webhook_event_id: Option<Uuid>,
+    pub gift: GiftOptions,
+    pub checkout: CheckoutLink, // Reserved order id, cart and customer from checkout, when known
+    pub livemode: bool,
 }
 
-// Utility function to log webhook events to database
-pub async fn log_webhook_event(
-    pool: &sqlx::PgPool,
-    event: CreateWebhookEvent,
-) -> Result<Uuid, sqlx::Error> {
-    let provider_str = event.provider.to_string();
+// Gift options chosen at checkout, carried to the webhook in payment metadata
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GiftOptions {
+    #[serde(default)]
+    pub gift_wrap: bool,
+    #[serde(default)]
+    pub gift_message: Option<String>,
+}
 
-    let result = sqlx::query!(
-        r#"
-        INSERT INTO webhook_events (provider, event_type, event_id, payload, processed)
-        VALUES ($1, $2, $3, $4, FALSE)
-        RETURNING id
-        "#,
-        provider_str,
-        event.event_type,
-        event.event_id,
-        event.payload,
-    )
-    .fetch_one(pool)
-    .await?;
+impl GiftOptions {
+    pub const MAX_MESSAGE_LEN: usize = 500; // Stripe metadata values are limited to 500 characters
 
-    Ok(result.id)
-}
+    pub fn from_metadata(metadata: &HashMap<String, String>) -> Self {
+        GiftOptions {
+            gift_wrap: metadata.get("gift_wrap").is_some_and(|v| v == "true"),
+            gift_message: metadata.get("gift_message").filter(|m| !m.trim().is_empty()).cloned(),
+        }
+    }
 
-// Utility function to mark webhook event as processed
-pub async fn mark_webhook_processed(
-    pool: &sqlx::PgPool,
-    webhook_id: Uuid,
-    success: bool,
-    error_message: Option<String>,
-) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"
-        UPDATE webhook_events
-        SET processed = $1, processed_at = NOW(), error_message = $2
-        WHERE id = $3
-        "#,
-        success,
-        error_message,
-        webhook_id,
-    )
-    .execute(pool)
-    .await?;
+    pub fn to_metadata(&self) -> HashMap<String, String> {
+        let mut metadata = HashMap::new();
+        if self.gift_wrap {
+            metadata.insert("gift_wrap".to_string(), "true".to_string());
+        }
+        if let Some(message) = self.gift_message.as_deref().map(str::trim).filter(|m| !m.is_empty()) {
+            metadata.insert("gift_message".to_string(), message.to_string());
+        }
+        metadata
+    }
 
-    Ok(())
+    pub fn is_gift(&self) -> bool {
+        self.gift_wrap || self.gift_message.is_some()
+    }
 }
 
-// Utility function to create orders
+// Utility function to create orders, linking them to the customer for their email (or the
+// logged-in customer from checkout) and to the checkout cart under its reserved order id
 pub async fn create_order(
-    pool: &sqlx::PgPool,
+    conn: impl sqlx::Acquire<'_, Database = sqlx::Postgres>,
     order: CreateOrder,
 ) -> Result<Uuid, sqlx::Error> {
     let provider_str = order.payment_provider.to_string();
     let status_str = order.status.to_string();
+    let paid = order.status != OrderStatus::Pending;
 
+    // The order and its items are written together so an order never exists without its lines
+    let mut tx = conn.begin().await?;
+    let customer_id = match order.customer_email.as_deref().filter(|e| !e.trim().is_empty()) {
+        Some(email) => Some(crate::customers::upsert_customer(&mut *tx, email, order.customer_name.as_deref()).await?),
+        None => order.checkout.customer_id,
+    };
+    let customer_email_hash = order.customer_email.as_deref().map(pii::email_hash);
     let result = sqlx::query!(
         r#"
         INSERT INTO orders (
-            payment_provider, payment_id, payment_intent_id,
-            customer_email, customer_name, total_amount, currency,
-            status, webhook_event_id
+            id, payment_provider, payment_id, payment_intent_id,
+            customer_email, customer_email_hash, customer_name, total_amount, currency,
+            status, webhook_event_id, gift_wrap, gift_message, customer_id, cart_id, livemode
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        VALUES (COALESCE($13, gen_random_uuid()), $1, $2, $3, $4, $16, $5, $6, $7, $8, $9, $10, $11, $12, $14, $15)
         RETURNING id
         "#,
         provider_str,
         order.payment_id,
         order.payment_intent_id,
-        order.customer_email,
+        order.customer_email.map(Encrypted) as _,
         order.customer_name,
         order.total_amount,
         order.currency,
         status_str,
         order.webhook_event_id,
+        order.gift.gift_wrap,
+        order.gift.gift_message,
+        customer_id,
+        order.checkout.order_id,
+        order.checkout.cart_id,
+        order.livemode,
+        customer_email_hash,
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *tx)
     .await?;
 
+    order_status::record(&mut *tx, result.id, None, order.status, &provider_str).await?;
+    if let Some(cart_id) = order.checkout.cart_id {
+        checkout_carts::convert_to_order(&mut tx, cart_id, result.id).await?;
+        // Reserved stock now belongs to the order; a pending one keeps it held until it is paid
+        if paid {
+            stock_reservations::convert_cart(&mut *tx, cart_id).await?;
+        }
+    }
+    tx.commit().await?;
+
     Ok(result.id)
 }
 
-// Utility function to check if webhook event already processed (idempotency)
-pub async fn is_event_processed(
-    pool: &sqlx::PgPool,
-    event_id: &str,
-) -> Result<bool, sqlx::Error> {
-    let result = sqlx::query!(
-        r#"
-        SELECT EXISTS(SELECT 1 FROM webhook_events WHERE event_id = $1) as "exists!"
-        "#,
-        event_id,
+// Item table for order emails; empty for orders without recorded items
+pub fn items_table_html(items: &[OrderItem]) -> String {
+    if items.is_empty() {
+        return String::new();
+    }
+    let rows: String = items
+        .iter()
+        .map(|item| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>${:.2}</td></tr>",
+                crate::branding::escape_html(&item.product_name),
+                item.quantity,
+                item.total_price as f64 / 100.0
+            )
+        })
+        .collect();
+    format!(
+        "<table><thead><tr><th>Item</th><th>Quantity</th><th>Price</th></tr></thead><tbody>{}</tbody></table>",
+        rows
     )
-    .fetch_one(pool)
-    .await?;
-
-    Ok(result.exists)
 }
 
 // Export webhook routes for main.rs
@@ -224,5 +271,35 @@ pub fn webhook_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
         .route("/api/webhooks/stripe", post(stripe::handle_stripe_webhook))
         .route("/api/webhooks/square", post(square::handle_square_webhook))
+        .route("/api/webhooks/easypost", post(easypost::handle_easypost_webhook))
+        .route("/api/webhooks/sms", post(twilio::handle_twilio_sms_webhook))
+        .route("/api/admin/webhook-events", get(list_webhook_events))
         .with_state(app_state)
 }
+
+#[derive(Deserialize)]
+pub struct WebhookEventListQuery {
+    pub provider: Option<String>,
+    pub processed: Option<bool>,
+    pub cursor: Option<String>, // next_cursor of the previous page
+    pub limit: Option<i64>,     // Defaults to 50, at most 200
+}
+
+// Events received from payment providers, newest first, for tracing a missed or failed one
+async fn list_webhook_events(
+    _admin: AuthenticatedAdmin,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<WebhookEventListQuery>,
+) -> Result<Json<Page<WebhookEvent>>, (StatusCode, String)> {
+    let size = pagination::page_size(query.limit);
+    let filter = WebhookEventFilter {
+        provider: query.provider,
+        processed: query.processed,
+        before: pagination::after(query.cursor.as_deref())?,
+        limit: size + 1,
+    };
+    let events = state.repos.webhook_events.list(&filter)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(Page::new(events, size, |e| (e.created_at, e.id))))
+}
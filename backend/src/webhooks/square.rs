@@ -3,21 +3,25 @@
 // Implements HMAC-SHA256 signature verification for security
 
 use axum::{
-    extract::{Request, State},
+    extract::State,
     http::{HeaderMap, StatusCode},
     response::IntoResponse,
     Json, body::Bytes,
 };
+use base64::Engine;
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::sync::Arc;
 
+use crate::app_config;
+use crate::checkout_carts::CheckoutLink;
 use crate::AppState;
+use crate::events::DomainEvent;
+use crate::unit_of_work;
 use super::{
-    log_webhook_event, mark_webhook_processed, create_order, is_event_processed,
-    CreateWebhookEvent, CreateOrder, PaymentProvider, OrderStatus,
+    create_order, CreateWebhookEvent, CreateOrder, GiftOptions, PaymentProvider, OrderStatus,
 };
 
 type HmacSha256 = Hmac<Sha256>;
@@ -91,17 +95,15 @@ pub async fn handle_square_webhook(
             "Missing x-square-hmacsha256-signature header".to_string(),
         ))?;
 
-    // Get webhook signature key from environment
-    let webhook_signature_key = std::env::var("SQUARE_WEBHOOK_SIGNATURE_KEY")
-        .unwrap_or_else(|_| "your_webhook_signature_key".to_string());
-
-    // Get the webhook notification URL from environment (needed for signature verification)
-    let webhook_url = std::env::var("SQUARE_WEBHOOK_URL")
-        .unwrap_or_else(|_| "https://your-domain.com/api/webhooks/square".to_string());
+    // Get webhook signature key and notification URL (needed for signature verification) from config
+    let square = &app_config::get().square;
+    let webhook_signature_key = square.webhook_signature_key.expose();
+    let webhook_url = &square.webhook_url;
 
     // Verify webhook signature
-    if !verify_square_signature(&body, signature, &webhook_signature_key, &webhook_url) {
-        eprintln!("Square webhook signature verification failed");
+    if !verify_square_signature(&body, signature, webhook_signature_key, webhook_url) {
+        tracing::error!("Square webhook signature verification failed");
+        state.ops.webhook_signature_failed("Square");
         return Err((
             StatusCode::UNAUTHORIZED,
             "Webhook signature verification failed".to_string(),
@@ -115,37 +117,26 @@ pub async fn handle_square_webhook(
     let event: SquareWebhookEvent = serde_json::from_str(&body_str)
         .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", e)))?;
 
-    // Check if we've already processed this event (idempotency)
     let event_id = &event.event_id;
-    match is_event_processed(&state.pool, event_id).await {
-        Ok(true) => {
-            println!("Event {} already processed, returning 200 OK", event_id);
-            return Ok((StatusCode::OK, Json(json!({"received": true, "duplicate": true}))));
-        }
-        Ok(false) => {
-            // Continue processing
-        }
-        Err(e) => {
-            eprintln!("Error checking event idempotency: {}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Database error: {}", e),
-            ));
-        }
-    }
 
-    // Log the webhook event to database
+    // Log the webhook event to database; an event already logged is a redelivery (idempotency)
     let webhook_event = CreateWebhookEvent {
         provider: PaymentProvider::Square,
         event_type: event.event_type.clone(),
         event_id: event_id.clone(),
         payload: serde_json::to_value(&event).unwrap_or(json!({})),
+        // Square events carry no mode flag; SQUARE_ENVIRONMENT is validated against the server mode
+        livemode: state.mode.is_live(),
     };
 
-    let webhook_id = match log_webhook_event(&state.pool, webhook_event).await {
-        Ok(id) => id,
+    let webhook_id = match state.repos.webhook_events.log(webhook_event).await {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            tracing::info!("Event {} already received, returning 200 OK", event_id);
+            return Ok((StatusCode::OK, Json(json!({"received": true, "duplicate": true}))));
+        }
         Err(e) => {
-            eprintln!("Failed to log webhook event: {}", e);
+            tracing::error!("Failed to log webhook event: {}", e);
             return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Failed to log webhook: {}", e),
@@ -160,14 +151,14 @@ pub async fn handle_square_webhook(
         }
         "payment.created" => {
             // Log but don't create order until payment is completed
-            println!("Payment created event received: {:?}", event.data.id);
-            mark_webhook_processed(&state.pool, webhook_id, true, None).await.ok();
+            tracing::info!("Payment created event received: {:?}", event.data.id);
+            state.repos.webhook_events.mark_processed(webhook_id, true, None).await.ok();
             Ok(())
         }
         _ => {
             // For other events, just log and mark as processed
-            println!("Received Square event type: {}", event.event_type);
-            mark_webhook_processed(&state.pool, webhook_id, true, None).await.ok();
+            tracing::info!("Received Square event type: {}", event.event_type);
+            state.repos.webhook_events.mark_processed(webhook_id, true, None).await.ok();
             Ok(())
         }
     };
@@ -175,12 +166,12 @@ pub async fn handle_square_webhook(
     // Mark webhook as processed with error if any
     match result {
         Ok(_) => {
-            mark_webhook_processed(&state.pool, webhook_id, true, None).await.ok();
+            state.repos.webhook_events.mark_processed(webhook_id, true, None).await.ok();
             Ok((StatusCode::OK, Json(json!({"received": true}))))
         }
         Err(e) => {
-            eprintln!("Error processing webhook: {}", e);
-            mark_webhook_processed(&state.pool, webhook_id, false, Some(e.clone())).await.ok();
+            tracing::error!("Error processing webhook: {}", e);
+            state.repos.webhook_events.mark_processed(webhook_id, false, Some(e.clone())).await.ok();
             // Return 200 anyway to prevent retries for application errors
             Ok((StatusCode::OK, Json(json!({"received": true, "error": e}))))
         }
@@ -198,7 +189,7 @@ fn verify_square_signature(
     let mut mac = match HmacSha256::new_from_slice(signature_key.as_bytes()) {
         Ok(m) => m,
         Err(e) => {
-            eprintln!("Failed to create HMAC: {}", e);
+            tracing::error!("Failed to create HMAC: {}", e);
             return false;
         }
     };
@@ -209,7 +200,7 @@ fn verify_square_signature(
 
     // Compute the HMAC
     let result = mac.finalize();
-    let computed_signature = base64::encode(result.into_bytes());
+    let computed_signature = base64::engine::general_purpose::STANDARD.encode(result.into_bytes());
 
     // Compare with provided signature (constant-time comparison)
     computed_signature == signature
@@ -229,7 +220,7 @@ async fn handle_payment_updated(
         .and_then(|obj| obj.payment.as_ref())
         .ok_or("Missing payment object in event data".to_string())?;
 
-    println!(
+    tracing::info!(
         "Payment updated! Payment ID: {}, Status: {}, Amount: {} {}",
         payment.id,
         payment.status,
@@ -237,23 +228,21 @@ async fn handle_payment_updated(
         payment.amount_money.currency
     );
 
-    // Only create order if payment status is COMPLETED
-    if payment.status != "COMPLETED" {
-        println!("Payment status is {}, not creating order", payment.status);
-        return Ok(());
+    // Surface declined/cancelled payments to subscribers
+    if payment.status == "FAILED" || payment.status == "CANCELED" {
+        state.events.publish(DomainEvent::PaymentFailed {
+            provider: PaymentProvider::Square,
+            payment_id: payment.id.clone(),
+            customer_email: payment.buyer_email_address.clone(),
+            amount: payment.amount_money.amount,
+            currency: payment.amount_money.currency.clone(),
+            reason: Some(payment.status.to_lowercase()),
+        });
     }
 
-    // Check if order already exists for this payment
-    let existing = sqlx::query!(
-        "SELECT id FROM orders WHERE payment_id = $1 AND payment_provider = 'square'",
-        payment.id
-    )
-    .fetch_optional(&*state.pool)
-    .await
-    .map_err(|e| format!("Database error: {}", e))?;
-
-    if existing.is_some() {
-        println!("Order already exists for payment {}", payment.id);
+    // Only create order if payment status is COMPLETED
+    if payment.status != "COMPLETED" {
+        tracing::info!("Payment status is {}, not creating order", payment.status);
         return Ok(());
     }
 
@@ -268,28 +257,64 @@ async fn handle_payment_updated(
         currency: payment.amount_money.currency.clone(),
         status: OrderStatus::Completed,
         webhook_event_id: Some(webhook_id),
+        livemode: state.mode.is_live(),
+        gift: GiftOptions::default(),
+        checkout: CheckoutLink::default(),
     };
 
-    let order_id = create_order(&state.pool, order)
-        .await
-        .map_err(|e| format!("Failed to create order: {}", e))?;
-
-    println!("Created order with ID: {}", order_id);
+    // The check and the insert run as one unit of work, so a redelivered update can't create
+    // the order twice
+    let created = unit_of_work::run(state, |uow| {
+        let order = order.clone();
+        Box::pin(async move {
+            let existing = sqlx::query!(
+                "SELECT id FROM orders WHERE payment_id = $1 AND payment_provider = 'square'",
+                order.payment_id
+            )
+            .fetch_optional(uow.conn())
+            .await?;
+            if existing.is_some() {
+                tracing::info!("Order already exists for payment {}", order.payment_id);
+                return Ok(None);
+            }
+
+            let order_id = create_order(uow.conn(), order.clone()).await?;
+
+            // Notify subscribers (confirmation email, SMS, analytics, marketing sync)
+            uow.publish(DomainEvent::OrderCreated {
+                order_id,
+                provider: PaymentProvider::Square,
+                payment_id: order.payment_id,
+                customer_email: order.customer_email,
+                customer_name: None,
+                customer_phone: None,
+                total_amount: order.total_amount,
+                currency: order.currency,
+            });
+            Ok(Some(order_id))
+        })
+    })
+    .await
+    .map_err(|e: unit_of_work::WorkError<String>| format!("Failed to create order: {}", e))?;
 
-    // Send order confirmation email
-    if let Some(email) = &payment.buyer_email_address {
-        send_order_confirmation_email(email, &payment.id, payment.amount_money.amount).await;
+    if let Some(order_id) = created {
+        tracing::info!("Created order with ID: {}", order_id);
     }
-
     Ok(())
 }
 
 // Send order confirmation email using lettre
-async fn send_order_confirmation_email(email: &str, order_id: &str, amount: i64) {
+pub(crate) async fn send_order_confirmation_email(
+    pool: &sqlx::PgPool,
+    email: &str,
+    order_uuid: uuid::Uuid,
+    order_id: &str,
+    amount: i64,
+) {
+    use crate::branding::load_branding;
     use crate::lettre_email::EmailConfig;
-    use lettre::{Message, SmtpTransport, Transport, message::header::ContentType, transport::smtp::authentication::Credentials};
 
-    println!(
+    tracing::info!(
         "Sending order confirmation email to {} for order {} (${:.2})",
         email,
         order_id,
@@ -300,61 +325,61 @@ async fn send_order_confirmation_email(email: &str, order_id: &str, amount: i64)
     let config = match EmailConfig::from_env() {
         Some(c) => c,
         None => {
-            eprintln!("Email not configured. Set SMTP_HOST, SMTP_PORT, SMTP_USERNAME, SMTP_PASSWORD, FROM_EMAIL");
+            tracing::warn!("Email not configured. Set SMTP_HOST, SMTP_PORT, SMTP_USERNAME, SMTP_PASSWORD, FROM_EMAIL");
             return;
         }
     };
 
+    let items = match crate::repos::order_items(pool, order_uuid).await {
+        Ok(items) => items,
+        Err(e) => {
+            tracing::error!("Failed to load items for order {}: {}", order_uuid, e);
+            Vec::new()
+        }
+    };
+    // Keys are assigned here, not only by the license key subscriber, so they make it into this email
+    let license_keys = match crate::license_keys::assign_for_order(pool, order_uuid).await {
+        Ok(keys) => keys,
+        Err(e) => {
+            tracing::error!("Failed to assign license keys for order {}: {}", order_uuid, e);
+            Vec::new()
+        }
+    };
+
     // Build HTML email
-    let html_body = format!(
-        r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <style>
-        body {{ font-family: Arial, sans-serif; line-height: 1.6; color: #333; }}
-        .container {{ max-width: 600px; margin: 0 auto; padding: 20px; }}
-        .header {{ background: #006aff; color: white; padding: 20px; text-align: center; }}
-        .content {{ padding: 20px; background: #f9f9f9; }}
-        .footer {{ text-align: center; padding: 20px; color: #666; font-size: 12px; }}
-        .total {{ font-size: 18px; font-weight: bold; margin: 20px 0; }}
-    </style>
-</head>
-<body>
-    <div class="container">
-        <div class="header">
-            <h1>🎉 Payment Successful!</h1>
-        </div>
-        <div class="content">
-            <p>Hi there,</p>
+    let html_body = load_branding(pool).await.email_html(
+        "🎉 Payment Successful!",
+        &format!(
+            r#"<p>Hi there,</p>
             <p>Thank you for your payment via Square! Your transaction has been completed successfully.</p>
             <p><strong>Payment ID:</strong> {}</p>
+            {}
             <p class="total">Amount Paid: ${:.2}</p>
+            {}
             <p>We've received your payment and will process your order shortly. You'll receive a shipping confirmation email once your order ships.</p>
-            <p>If you have any questions, please don't hesitate to contact us.</p>
-        </div>
-        <div class="footer">
-            <p>© 2025 R-Com Store. All rights reserved.</p>
-        </div>
-    </div>
-</body>
-</html>
-        "#,
-        order_id,
-        amount as f64 / 100.0
+            <p>If you have any questions, please don't hesitate to contact us.</p>"#,
+            order_id,
+            super::items_table_html(&items),
+            amount as f64 / 100.0,
+            crate::license_keys::keys_html(&license_keys)
+        ),
     );
 
     // Send email using helper function
-    match send_html_email(&config, email, &format!("Payment Confirmation - {}", order_id), &html_body).await {
-        Ok(_) => println!("✓ Order confirmation email sent to {}", email),
-        Err(e) => eprintln!("✗ Failed to send email: {}", e),
+    match send_html_email(pool, &config, email, &format!("Payment Confirmation - {}", order_id), &html_body).await {
+        Ok(_) => tracing::info!("Order confirmation email sent to {}", email),
+        Err(e) => tracing::error!("Failed to send email: {}", e),
     }
 }
 
 // Helper function to send HTML email
-async fn send_html_email(config: &crate::lettre_email::EmailConfig, to: &str, subject: &str, html_body: &str) -> Result<(), String> {
+async fn send_html_email(pool: &sqlx::PgPool, config: &crate::lettre_email::EmailConfig, to: &str, subject: &str, html_body: &str) -> Result<(), String> {
     use lettre::{Message, SmtpTransport, Transport, message::header::ContentType, transport::smtp::authentication::Credentials};
 
+    if crate::email_log::is_suppressed(pool, to).await.map_err(|e| format!("DB error: {}", e))? {
+        return Err(format!("{} is on the suppression list", to));
+    }
+
     let from_mailbox = format!("{} <{}>", config.from_name, config.from_email)
         .parse()
         .map_err(|e| format!("Invalid from address: {}", e))?;
@@ -383,6 +408,7 @@ async fn send_html_email(config: &crate::lettre_email::EmailConfig, to: &str, su
 
     mailer.send(&email)
         .map_err(|e| format!("Failed to send email: {}", e))?;
+    crate::notifications::record_send(pool, crate::notifications::CHANNEL_EMAIL).await;
 
     Ok(())
 }
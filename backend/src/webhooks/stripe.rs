@@ -3,19 +3,32 @@
 // Implements signature verification for security
 
 use axum::{
-    extract::{Request, State},
+    extract::State,
     http::{HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
 use serde_json::json;
+use std::collections::HashMap;
 use std::sync::Arc;
 use stripe::{Event, EventObject, EventType, Webhook};
 
+use crate::app_config;
+use crate::checkout_carts::{self, CheckoutLink};
+use crate::disputes;
+use crate::manual_orders;
+use crate::payment_links;
 use crate::AppState;
+use crate::events::DomainEvent;
+use crate::order_shipments;
+use crate::order_status::{self, TransitionError};
+use crate::payment_capture;
+use crate::payment_verification;
+use crate::pii::Encrypted;
+use crate::unit_of_work::{self, UnitOfWork};
+use crate::{coupons, loyalty, referrals, store_credit};
 use super::{
-    log_webhook_event, mark_webhook_processed, create_order, is_event_processed,
-    CreateWebhookEvent, CreateOrder, PaymentProvider, OrderStatus,
+    create_order, CreateWebhookEvent, CreateOrder, GiftOptions, PaymentProvider, OrderStatus,
 };
 
 // Stripe webhook endpoint handler
@@ -33,51 +46,53 @@ pub async fn handle_stripe_webhook(
             "Missing stripe-signature header".to_string(),
         ))?;
 
-    // Get webhook secret from environment
-    let webhook_secret = std::env::var("STRIPE_WEBHOOK_SECRET")
-        .unwrap_or_else(|_| "whsec_test_secret".to_string());
+    // Get webhook secret from config
+    let webhook_secret = app_config::get().stripe.webhook_secret.expose();
 
     // Verify webhook signature and construct event
-    let event = Webhook::construct_event(&body, signature, &webhook_secret)
+    let event = Webhook::construct_event(&body, signature, webhook_secret)
         .map_err(|e| {
-            eprintln!("Stripe webhook signature verification failed: {}", e);
+            tracing::error!("Stripe webhook signature verification failed: {}", e);
+            state.ops.webhook_signature_failed("Stripe");
             (
                 StatusCode::BAD_REQUEST,
                 format!("Webhook signature verification failed: {}", e),
             )
         })?;
 
-    // Check if we've already processed this event (idempotency)
-    let event_id = event.id.as_str();
-    match is_event_processed(&state.pool, event_id).await {
-        Ok(true) => {
-            println!("Event {} already processed, returning 200 OK", event_id);
-            return Ok((StatusCode::OK, Json(json!({"received": true, "duplicate": true}))));
-        }
-        Ok(false) => {
-            // Continue processing
-        }
-        Err(e) => {
-            eprintln!("Error checking event idempotency: {}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Database error: {}", e),
-            ));
-        }
+    // Refuse events from the other mode so test payments never land in live data (and vice versa)
+    if event.livemode != state.mode.is_live() {
+        let event_mode = if event.livemode { "live" } else { "test" };
+        tracing::warn!("Refusing {} Stripe event {} while running in {} mode", event_mode, event.id, state.mode);
+        state.ops.notify(format!(
+            "Refused {} Stripe webhook {} ({}): server is running in {} mode",
+            event_mode, event.id, event.type_, state.mode
+        ));
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("{} mode event sent to a {} mode server", event_mode, state.mode),
+        ));
     }
 
-    // Log the webhook event to database
+    let event_id = event.id.as_str();
+
+    // Log the webhook event to database; an event already logged is a redelivery (idempotency)
     let webhook_event = CreateWebhookEvent {
         provider: PaymentProvider::Stripe,
         event_type: event.type_.to_string(),
         event_id: event_id.to_string(),
         payload: serde_json::to_value(&event).unwrap_or(json!({})),
+        livemode: event.livemode,
     };
 
-    let webhook_id = match log_webhook_event(&state.pool, webhook_event).await {
-        Ok(id) => id,
+    let webhook_id = match state.repos.webhook_events.log(webhook_event).await {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            tracing::info!("Event {} already received, returning 200 OK", event_id);
+            return Ok((StatusCode::OK, Json(json!({"received": true, "duplicate": true}))));
+        }
         Err(e) => {
-            eprintln!("Failed to log webhook event: {}", e);
+            tracing::error!("Failed to log webhook event: {}", e);
             return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Failed to log webhook: {}", e),
@@ -86,37 +101,83 @@ pub async fn handle_stripe_webhook(
     };
 
     // Process the event based on type
-    let result = match event.type_ {
+    let result = process_event(&state, &event, webhook_id).await;
+
+    // Mark webhook as processed with error if any
+    match result {
+        Ok(_) => {
+            state.repos.webhook_events.mark_processed(webhook_id, true, None).await.ok();
+            Ok((StatusCode::OK, Json(json!({"received": true}))))
+        }
+        Err(e) => {
+            tracing::error!("Error processing webhook: {}", e);
+            state.repos.webhook_events.mark_processed(webhook_id, false, Some(e.clone())).await.ok();
+            // Return 200 anyway to prevent retries for application errors
+            Ok((StatusCode::OK, Json(json!({"received": true, "error": e}))))
+        }
+    }
+}
+
+// Run the handler for an event already logged as `webhook_id`
+async fn process_event(state: &Arc<AppState>, event: &Event, webhook_id: uuid::Uuid) -> Result<(), String> {
+    match event.type_ {
         EventType::PaymentIntentSucceeded => {
-            handle_payment_intent_succeeded(&state, &event, webhook_id).await
+            handle_payment_intent_succeeded(state, event, webhook_id).await
+        }
+        EventType::PaymentIntentAmountCapturableUpdated => {
+            handle_payment_intent_authorized(state, event, webhook_id).await
+        }
+        EventType::PaymentIntentCanceled => {
+            handle_payment_intent_canceled(state, event).await
         }
         EventType::ChargeSucceeded => {
-            handle_charge_succeeded(&state, &event, webhook_id).await
+            handle_charge_succeeded(state, event, webhook_id).await
         }
         EventType::CheckoutSessionCompleted => {
-            handle_checkout_session_completed(&state, &event, webhook_id).await
+            handle_checkout_session_completed(state, event, webhook_id).await
+        }
+        EventType::PaymentIntentPaymentFailed => {
+            handle_payment_intent_failed(state, event).await
+        }
+        EventType::ChargeRefunded => {
+            handle_charge_refunded(state, event).await
         }
+        EventType::ChargeDisputeCreated
+        | EventType::ChargeDisputeUpdated
+        | EventType::ChargeDisputeClosed
+        | EventType::ChargeDisputeFundsWithdrawn
+        | EventType::ChargeDisputeFundsReinstated => match &event.data.object {
+            EventObject::Dispute(dispute) => disputes::record_stripe_dispute(state, dispute).await,
+            _ => Err("Expected Dispute object".to_string()),
+        },
         _ => {
             // For other events, just log and mark as processed
-            println!("Received Stripe event type: {:?}", event.type_);
-            mark_webhook_processed(&state.pool, webhook_id, true, None).await.ok();
+            tracing::info!("Received Stripe event type: {:?}", event.type_);
+            state.repos.webhook_events.mark_processed(webhook_id, true, None).await.ok();
             Ok(())
         }
-    };
+    }
+}
 
-    // Mark webhook as processed with error if any
-    match result {
-        Ok(_) => {
-            mark_webhook_processed(&state.pool, webhook_id, true, None).await.ok();
-            Ok((StatusCode::OK, Json(json!({"received": true}))))
-        }
-        Err(e) => {
-            eprintln!("Error processing webhook: {}", e);
-            mark_webhook_processed(&state.pool, webhook_id, false, Some(e.clone())).await.ok();
-            // Return 200 anyway to prevent retries for application errors
-            Ok((StatusCode::OK, Json(json!({"received": true, "error": e}))))
-        }
+// Process a logged event again, e.g. after fixing what made it fail. The stored payload has
+// customer details redacted, so the event is fetched from Stripe again (Stripe keeps events for
+// 30 days). Handlers skip work already done, such as an order that was created
+pub async fn reprocess_event(state: &Arc<AppState>, webhook_id: uuid::Uuid, event_id: &str) -> Result<(), String> {
+    let id = event_id.parse().map_err(|_| format!("{} is not a Stripe event id", event_id))?;
+    let event = Event::retrieve(&state.stripe_client, &id, &[])
+        .await
+        .map_err(|e| format!("Failed to fetch event {} from Stripe: {}", event_id, e))?;
+    if event.livemode != state.mode.is_live() {
+        return Err(format!("Event {} is from the other mode than this server ({})", event_id, state.mode));
     }
+    let result = process_event(state, &event, webhook_id).await;
+    state
+        .repos
+        .webhook_events
+        .mark_processed(webhook_id, result.is_ok(), result.as_ref().err().cloned())
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
+    result
 }
 
 // Handle payment_intent.succeeded event
@@ -130,13 +191,133 @@ async fn handle_payment_intent_succeeded(
         _ => return Err("Expected PaymentIntent object".to_string()),
     };
 
-    println!(
+    tracing::info!(
         "Payment succeeded! PaymentIntent ID: {}, Amount: {} {}",
         payment_intent.id,
         payment_intent.amount,
         payment_intent.currency
     );
 
+    if manual_orders::settle_payment(state, &payment_intent.metadata, Some(payment_intent.id.as_str())).await? {
+        return Ok(());
+    }
+    // Payment link sales get their order from checkout.session.completed
+    if payment_links::is_link_payment(&payment_intent.metadata) {
+        return Ok(());
+    }
+
+    // With manual capture the order was created on authorization; this is its capture
+    if order_exists_for_payment_intent(&*state.pool, payment_intent.id.as_str())
+        .await
+        .map_err(|e| format!("Database error: {}", e))?
+    {
+        match payment_capture::record_capture(&state.pool, payment_intent.id.as_str(), payment_intent.amount_received, "stripe").await {
+            Ok(Some(change)) => {
+                tracing::info!("Order {} captured {} cents", change.order_id, payment_intent.amount_received);
+                state.events.publish(change.event());
+            }
+            Ok(None) => tracing::info!("Order already recorded for payment intent {}", payment_intent.id),
+            Err(e) => return Err(format!("Failed to record capture: {}", e)),
+        }
+        return Ok(());
+    }
+
+    create_payment_intent_order(state, payment_intent, webhook_id, OrderStatus::Completed, payment_intent.amount_received).await
+}
+
+// Handle payment_intent.amount_capturable_updated: a manual-capture payment was authorized
+async fn handle_payment_intent_authorized(
+    state: &Arc<AppState>,
+    event: &Event,
+    webhook_id: uuid::Uuid,
+) -> Result<(), String> {
+    let payment_intent = match &event.data.object {
+        EventObject::PaymentIntent(pi) => pi,
+        _ => return Err("Expected PaymentIntent object".to_string()),
+    };
+
+    tracing::info!(
+        "Payment authorized! PaymentIntent ID: {}, Capturable: {} {}",
+        payment_intent.id,
+        payment_intent.amount_capturable,
+        payment_intent.currency
+    );
+
+    if payment_intent.amount_capturable == 0
+        || order_exists_for_payment_intent(&*state.pool, payment_intent.id.as_str())
+            .await
+            .map_err(|e| format!("Database error: {}", e))?
+    {
+        return Ok(());
+    }
+
+    create_payment_intent_order(state, payment_intent, webhook_id, OrderStatus::Authorized, payment_intent.amount_capturable).await
+}
+
+// Handle payment_intent.canceled event: an authorization was voided (by us or in the dashboard)
+async fn handle_payment_intent_canceled(
+    state: &Arc<AppState>,
+    event: &Event,
+) -> Result<(), String> {
+    let payment_intent = match &event.data.object {
+        EventObject::PaymentIntent(pi) => pi,
+        _ => return Err("Expected PaymentIntent object".to_string()),
+    };
+
+    let change = payment_capture::record_void(&state.pool, payment_intent.id.as_str(), "stripe")
+        .await
+        .map_err(|e| format!("Failed to record void: {}", e))?;
+    if let Some(change) = change {
+        tracing::info!("Authorization voided for order {}", change.order_id);
+        state.events.publish(DomainEvent::OrderVoided { order_id: change.order_id });
+        state.events.publish(change.event());
+    } else if let Some(cart_id) = CheckoutLink::from_metadata(&payment_intent.metadata).cart_id {
+        release_cancelled_checkout(state, cart_id)
+            .await
+            .map_err(|e| format!("Failed to release checkout {}: {}", cart_id, e))?;
+    }
+    Ok(())
+}
+
+// A payment cancelled before it became an order (in the dashboard, or by our own clean up)
+// gives back the seats, stock, points and store credit its checkout held
+async fn release_cancelled_checkout(state: &AppState, cart_id: uuid::Uuid) -> Result<(), sqlx::Error> {
+    let open = sqlx::query_scalar!(
+        "SELECT id FROM checkout_carts WHERE id = $1 AND converted_at IS NULL",
+        cart_id,
+    )
+    .fetch_optional(&*state.pool)
+    .await?;
+    if open.is_none() {
+        return Ok(());
+    }
+    checkout_carts::release_holds(state, cart_id).await?;
+    tracing::info!("Released the holds of cancelled checkout {}", cart_id);
+    Ok(())
+}
+
+async fn order_exists_for_payment_intent(
+    executor: impl sqlx::PgExecutor<'_>,
+    payment_intent_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let existing = sqlx::query!(
+        "SELECT id FROM orders WHERE payment_intent_id = $1",
+        payment_intent_id
+    )
+    .fetch_optional(executor)
+    .await?;
+    Ok(existing.is_some())
+}
+
+// Create the order for a paid or authorized payment intent; `amount` is what was received or
+// is capturable, checked against the checkout total
+async fn create_payment_intent_order(
+    state: &Arc<AppState>,
+    payment_intent: &stripe::PaymentIntent,
+    webhook_id: uuid::Uuid,
+    status: OrderStatus,
+    amount: i64,
+) -> Result<(), String> {
     // Extract customer information
     let customer_email = payment_intent
         .receipt_email
@@ -148,25 +329,122 @@ async fn handle_payment_intent_succeeded(
         payment_provider: PaymentProvider::Stripe,
         payment_id: payment_intent.id.to_string(),
         payment_intent_id: Some(payment_intent.id.to_string()),
-        customer_email: customer_email.clone(),
+        customer_email,
         customer_name: None, // Could extract from billing details if available
         total_amount: payment_intent.amount,
         currency: payment_intent.currency.to_string().to_uppercase(),
-        status: OrderStatus::Completed,
+        status,
         webhook_event_id: Some(webhook_id),
+        livemode: state.mode.is_live(),
+        gift: GiftOptions::from_metadata(&payment_intent.metadata),
+        checkout: CheckoutLink::from_metadata(&payment_intent.metadata),
     };
 
-    let order_id = create_order(&state.pool, order)
-        .await
-        .map_err(|e| format!("Failed to create order: {}", e))?;
+    create_stripe_order(
+        state,
+        StripeOrder {
+            order,
+            customer_phone: None,
+            received: Some((amount, payment_intent.currency.to_string())),
+            metadata: payment_intent.metadata.clone(),
+        },
+    )
+    .await
+}
 
-    println!("Created order with ID: {}", order_id);
+// A Stripe payment's order and the checkout details that came with it
+#[derive(Clone)]
+struct StripeOrder {
+    order: CreateOrder,
+    customer_phone: Option<String>,
+    received: Option<(i64, String)>, // Amount paid (or capturable) and currency, checked against checkout
+    metadata: HashMap<String, String>,
+}
 
-    // Send order confirmation email
-    if let Some(email) = customer_email {
-        send_order_confirmation_email(&email, &payment_intent.id.to_string(), payment_intent.amount)
-            .await;
+// Create the order for a Stripe payment in one unit of work: the order and its items, the
+// authorization window, per-address shipments, the paid amount check and the checkout's coupon,
+// store credit and loyalty redemptions. Skipped when another event for the same payment intent
+// created it first. The referral reward follows once the order is committed
+async fn create_stripe_order(state: &Arc<AppState>, new_order: StripeOrder) -> Result<(), String> {
+    let created = unit_of_work::run(state, |uow| {
+        let StripeOrder { order, customer_phone, received, metadata } = new_order.clone();
+        Box::pin(async move {
+            let payment_intent_id = order.payment_intent_id.clone();
+            if let Some(pi) = &payment_intent_id {
+                if order_exists_for_payment_intent(uow.conn(), pi).await? {
+                    tracing::info!("Order already exists for payment intent {}", pi);
+                    return Ok(None);
+                }
+            }
+            let order_id = create_order(uow.conn(), order.clone()).await?;
+
+            if matches!(order.status, OrderStatus::Authorized) {
+                payment_capture::record_authorization(uow.conn(), order_id).await?;
+            }
+            if let Some(pi) = &payment_intent_id {
+                // Link any per-address shipments created at checkout to the new order
+                let linked = order_shipments::attach_to_order(uow.conn(), pi, order_id).await?;
+                if linked > 0 {
+                    tracing::info!("Linked {} shipment(s) to order {}", linked, order_id);
+                }
+                if let Some((amount, currency)) = &received {
+                    verify_paid_amount(uow, pi, order_id, *amount, currency).await?;
+                }
+            }
+            redeem_checkout_discounts(uow.conn(), order_id, order.checkout.cart_id, &metadata).await?;
+
+            // Notify subscribers (confirmation email, SMS, analytics, marketing sync)
+            uow.publish(DomainEvent::OrderCreated {
+                order_id,
+                provider: PaymentProvider::Stripe,
+                payment_id: order.payment_id,
+                customer_email: order.customer_email,
+                customer_name: order.customer_name,
+                customer_phone,
+                total_amount: order.total_amount,
+                currency: order.currency,
+            });
+            Ok(Some(order_id))
+        })
+    })
+    .await
+    .map_err(|e: unit_of_work::WorkError<String>| format!("Failed to create order: {}", e))?;
+
+    if let Some(order_id) = created {
+        tracing::info!("Created order with ID: {}", order_id);
+        record_referral(state, order_id, &new_order.metadata).await;
     }
+    Ok(())
+}
+
+// Handle payment_intent.payment_failed event
+async fn handle_payment_intent_failed(
+    state: &Arc<AppState>,
+    event: &Event,
+) -> Result<(), String> {
+    let payment_intent = match &event.data.object {
+        EventObject::PaymentIntent(pi) => pi,
+        _ => return Err("Expected PaymentIntent object".to_string()),
+    };
+
+    let reason = payment_intent
+        .last_payment_error
+        .as_ref()
+        .and_then(|e| e.message.clone());
+
+    tracing::info!(
+        "Payment failed! PaymentIntent ID: {}, Reason: {:?}",
+        payment_intent.id, reason
+    );
+
+    state.events.publish(DomainEvent::PaymentFailed {
+        provider: PaymentProvider::Stripe,
+        payment_id: payment_intent.id.to_string(),
+        customer_email: payment_intent.receipt_email.clone(),
+        amount: payment_intent.amount,
+        currency: payment_intent.currency.to_string().to_uppercase(),
+        reason,
+    });
 
     Ok(())
 }
@@ -182,53 +460,214 @@ async fn handle_charge_succeeded(
         _ => return Err("Expected Charge object".to_string()),
     };
 
-    println!(
+    tracing::info!(
         "Charge succeeded! Charge ID: {}, Amount: {}",
         charge.id, charge.amount
     );
 
-    // Check if order already exists for this payment intent
+    // Uncaptured charges are authorizations; payment_intent.amount_capturable_updated creates their order
+    if !charge.captured {
+        return Ok(());
+    }
+
     let payment_intent_str = charge.payment_intent
         .as_ref()
         .map(|pi| pi.id().to_string());
 
-    if let Some(pi_str) = &payment_intent_str {
-        // Check if we already created an order for this payment intent
-        let existing = sqlx::query!(
-            "SELECT id FROM orders WHERE payment_intent_id = $1",
-            pi_str
-        )
-        .fetch_optional(&*state.pool)
-        .await
-        .map_err(|e| format!("Database error: {}", e))?;
-
-        if existing.is_some() {
-            println!("Order already exists for payment intent {}", pi_str);
-            return Ok(());
-        }
+    if manual_orders::settle_payment(state, &charge.metadata, payment_intent_str.as_deref()).await?
+        || payment_links::is_link_payment(&charge.metadata)
+    {
+        return Ok(());
     }
 
-    // Create order record
-    let customer_email = charge.billing_details.email.clone();
-    let customer_name = charge.billing_details.name.clone();
-
+    // Create order record, unless one already exists for this payment intent
     let order = CreateOrder {
         payment_provider: PaymentProvider::Stripe,
         payment_id: charge.id.to_string(),
         payment_intent_id: payment_intent_str,
-        customer_email: customer_email.clone(),
-        customer_name: customer_name.clone(),
+        customer_email: charge.billing_details.email.clone(),
+        customer_name: charge.billing_details.name.clone(),
         total_amount: charge.amount,
         currency: charge.currency.to_string().to_uppercase(),
         status: OrderStatus::Completed,
         webhook_event_id: Some(webhook_id),
+        livemode: state.mode.is_live(),
+        gift: GiftOptions::from_metadata(&charge.metadata),
+        checkout: CheckoutLink::from_metadata(&charge.metadata),
     };
 
-    let order_id = create_order(&state.pool, order)
-        .await
-        .map_err(|e| format!("Failed to create order: {}", e))?;
+    create_stripe_order(
+        state,
+        StripeOrder {
+            order,
+            customer_phone: charge.billing_details.phone.clone(),
+            received: Some((charge.amount, charge.currency.to_string())),
+            metadata: charge.metadata.clone(),
+        },
+    )
+    .await
+}
 
-    println!("Created order with ID: {}", order_id);
+// Compare what the payment collected with the amount checkout computed; a mismatch is
+// flagged for admins and posted to the ops channel. The order is still created.
+async fn verify_paid_amount(
+    uow: &mut UnitOfWork,
+    payment_intent_id: &str,
+    order_id: uuid::Uuid,
+    received_amount: i64,
+    currency: &str,
+) -> Result<(), sqlx::Error> {
+    let expected =
+        payment_verification::verify_received(uow.conn(), payment_intent_id, order_id, received_amount, currency).await?;
+    if let Some(expected) = expected.filter(|e| e.mismatch) {
+        tracing::error!(
+            "Payment {} for order {} received {} {} but checkout expected {} {}",
+            payment_intent_id,
+            order_id,
+            received_amount,
+            currency.to_uppercase(),
+            expected.expected_amount,
+            expected.currency
+        );
+        uow.notify_ops(format!(
+            ":warning: Order {} was paid ${:.2} but checkout expected ${:.2} ({})",
+            order_id,
+            received_amount as f64 / 100.0,
+            expected.expected_amount as f64 / 100.0,
+            payment_intent_id
+        ));
+    }
+    Ok(())
+}
+
+// Redeem the coupon, debit the store credit and redeem the loyalty points a new order's cart
+// held. A hold already released is logged only: the payment went through, so the order is kept
+async fn redeem_checkout_discounts(
+    conn: &mut sqlx::PgConnection,
+    order_id: uuid::Uuid,
+    cart_id: Option<uuid::Uuid>,
+    metadata: &HashMap<String, String>,
+) -> Result<(), sqlx::Error> {
+    if let Some(code) = metadata.get(coupons::METADATA_KEY) {
+        let redeemed = match cart_id {
+            Some(cart_id) => coupons::redeem_held(&mut *conn, cart_id, order_id).await?,
+            None => None,
+        };
+        match redeemed {
+            Some(_) => tracing::info!("Redeemed coupon {} on order {}", code, order_id),
+            None => tracing::error!("Coupon {} on order {} was not held by its checkout", code, order_id),
+        }
+    }
+    let credit_requested = metadata
+        .get(store_credit::METADATA_KEY)
+        .and_then(|amount| amount.parse::<i64>().ok());
+    if let Some(requested) = credit_requested {
+        let debited = match cart_id {
+            Some(cart_id) => store_credit::debit_held(&mut *conn, cart_id, order_id).await?,
+            None => None,
+        };
+        if debited != Some(requested) {
+            tracing::error!(
+                "Order {} took {} cents store credit off but its checkout held {:?}",
+                order_id, requested, debited
+            );
+        }
+    }
+    let points_requested = metadata
+        .get(loyalty::METADATA_KEY)
+        .and_then(|points| points.parse::<i64>().ok());
+    if let Some(requested) = points_requested {
+        let redeemed = match cart_id {
+            Some(cart_id) => loyalty::redeem_held(&mut *conn, cart_id, order_id).await?,
+            None => None,
+        };
+        if redeemed != Some(requested) {
+            tracing::error!(
+                "Order {} redeemed {} loyalty points but its checkout held {:?}",
+                order_id, requested, redeemed
+            );
+        }
+    }
+    Ok(())
+}
+
+// Record referral attribution for a committed order; it may email the referrer, so it stays
+// out of the order's unit of work. Failures are logged only
+async fn record_referral(state: &Arc<AppState>, order_id: uuid::Uuid, metadata: &HashMap<String, String>) {
+    if let Some(code) = metadata.get(referrals::METADATA_KEY) {
+        if let Err(e) = referrals::record_referred_order(&state.pool, order_id, code).await {
+            tracing::error!("Failed to record referral {} for order {}: {}", code, order_id, e);
+        }
+    }
+}
+
+// Handle charge.refunded event: mark the order refunded once the charge is fully refunded
+async fn handle_charge_refunded(
+    state: &Arc<AppState>,
+    event: &Event,
+) -> Result<(), String> {
+    let charge = match &event.data.object {
+        EventObject::Charge(c) => c,
+        _ => return Err("Expected Charge object".to_string()),
+    };
+
+    tracing::info!(
+        "Charge refunded! Charge ID: {}, Refunded: {} of {}",
+        charge.id, charge.amount_refunded, charge.amount
+    );
+
+    if !charge.refunded {
+        tracing::info!("Partial refund on charge {}, order status unchanged", charge.id);
+        return Ok(());
+    }
+
+    let payment_intent_str = charge.payment_intent
+        .as_ref()
+        .map(|pi| pi.id().to_string());
+
+    let mut tx = state.pool.begin().await.map_err(|e| format!("Database error: {}", e))?;
+    let order: Option<(uuid::Uuid, Option<Encrypted>)> = sqlx::query!(
+        r#"SELECT id, customer_email AS "customer_email: Encrypted" FROM orders
+         WHERE payment_provider = 'stripe' AND (payment_id = $1 OR payment_intent_id = $2)
+         ORDER BY created_at LIMIT 1"#,
+        charge.id.as_str(),
+        payment_intent_str.as_deref(),
+    )
+    .map(|r| (r.id, r.customer_email))
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| format!("Database error: {}", e))?;
+
+    let (order_id, order_email) = match order {
+        Some(order) => order,
+        None => {
+            tracing::info!("No order found for charge {}", charge.id);
+            return Ok(());
+        }
+    };
+
+    // Already refunded (e.g. from the admin) or never paid: nothing to change
+    let change = match order_status::transition(&mut tx, order_id, OrderStatus::Refunded, "stripe").await {
+        Ok(change) => change,
+        Err(TransitionError::NotAllowed { from, .. }) => {
+            tracing::info!("Order {} is {}, not marking it refunded", order_id, from);
+            return Ok(());
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+    tx.commit().await.map_err(|e| format!("Database error: {}", e))?;
+
+    tracing::info!("Marked order {} as refunded", order_id);
+
+    state.events.publish(DomainEvent::OrderRefunded {
+        order_id,
+        provider: PaymentProvider::Stripe,
+        payment_id: charge.id.to_string(),
+        customer_email: order_email.map(Encrypted::into_inner).or_else(|| charge.billing_details.email.clone()),
+        amount_refunded: charge.amount_refunded,
+        currency: charge.currency.to_string().to_uppercase(),
+    });
+    state.events.publish(change.event());
 
     Ok(())
 }
@@ -244,7 +683,7 @@ async fn handle_checkout_session_completed(
         _ => return Err("Expected CheckoutSession object".to_string()),
     };
 
-    println!(
+    tracing::info!(
         "Checkout session completed! Session ID: {}, Amount: {:?}",
         session.id, session.amount_total
     );
@@ -257,40 +696,54 @@ async fn handle_checkout_session_completed(
         .as_ref()
         .map(|pi| pi.id().to_string());
 
+    // Payment links for staff-entered orders settle that order instead of creating one
+    if manual_orders::settle_payment(state, &session.metadata, payment_intent_str.as_deref()).await? {
+        return Ok(());
+    }
+    if payment_links::record_sale(state, session, webhook_id).await? {
+        return Ok(());
+    }
+
     // Create order record
     let order = CreateOrder {
         payment_provider: PaymentProvider::Stripe,
         payment_id: session.id.to_string(),
         payment_intent_id: payment_intent_str,
-        customer_email: customer_email.clone(),
+        customer_email,
         customer_name: None,
         total_amount: session.amount_total.unwrap_or(0),
         currency: session.currency.as_ref().map(|c| c.to_string().to_uppercase()).unwrap_or_else(|| "USD".to_string()),
         status: OrderStatus::Completed,
         webhook_event_id: Some(webhook_id),
+        livemode: state.mode.is_live(),
+        gift: GiftOptions::from_metadata(&session.metadata),
+        checkout: CheckoutLink::from_metadata(&session.metadata),
     };
 
-    let order_id = create_order(&state.pool, order)
-        .await
-        .map_err(|e| format!("Failed to create order: {}", e))?;
-
-    println!("Created order with ID: {}", order_id);
-
-    // Send order confirmation email
-    if let Some(email) = customer_email {
-        send_order_confirmation_email(&email, &session.id.to_string(), session.amount_total.unwrap_or(0))
-            .await;
-    }
-
-    Ok(())
+    create_stripe_order(
+        state,
+        StripeOrder {
+            order,
+            customer_phone: None,
+            received: session.currency.as_ref().map(|c| (session.amount_total.unwrap_or(0), c.to_string())),
+            metadata: session.metadata.clone(),
+        },
+    )
+    .await
 }
 
 // Send order confirmation email using lettre
-async fn send_order_confirmation_email(email: &str, order_id: &str, amount: i64) {
-    use crate::lettre_email::{EmailConfig, OrderConfirmationRequest, OrderItem};
-    use lettre::{Message, SmtpTransport, Transport, message::header::ContentType, transport::smtp::authentication::Credentials};
-
-    println!(
+pub(crate) async fn send_order_confirmation_email(
+    pool: &sqlx::PgPool,
+    email: &str,
+    order_uuid: uuid::Uuid,
+    order_id: &str,
+    amount: i64,
+) {
+    use crate::branding::load_branding;
+    use crate::lettre_email::EmailConfig;
+
+    tracing::info!(
         "Sending order confirmation email to {} for order {} (${:.2})",
         email,
         order_id,
@@ -301,61 +754,61 @@ async fn send_order_confirmation_email(email: &str, order_id: &str, amount: i64)
     let config = match EmailConfig::from_env() {
         Some(c) => c,
         None => {
-            eprintln!("Email not configured. Set SMTP_HOST, SMTP_PORT, SMTP_USERNAME, SMTP_PASSWORD, FROM_EMAIL");
+            tracing::warn!("Email not configured. Set SMTP_HOST, SMTP_PORT, SMTP_USERNAME, SMTP_PASSWORD, FROM_EMAIL");
             return;
         }
     };
 
+    let items = match crate::repos::order_items(pool, order_uuid).await {
+        Ok(items) => items,
+        Err(e) => {
+            tracing::error!("Failed to load items for order {}: {}", order_uuid, e);
+            Vec::new()
+        }
+    };
+    // Keys are assigned here, not only by the license key subscriber, so they make it into this email
+    let license_keys = match crate::license_keys::assign_for_order(pool, order_uuid).await {
+        Ok(keys) => keys,
+        Err(e) => {
+            tracing::error!("Failed to assign license keys for order {}: {}", order_uuid, e);
+            Vec::new()
+        }
+    };
+
     // Build HTML email
-    let html_body = format!(
-        r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <style>
-        body {{ font-family: Arial, sans-serif; line-height: 1.6; color: #333; }}
-        .container {{ max-width: 600px; margin: 0 auto; padding: 20px; }}
-        .header {{ background: #1976d2; color: white; padding: 20px; text-align: center; }}
-        .content {{ padding: 20px; background: #f9f9f9; }}
-        .footer {{ text-align: center; padding: 20px; color: #666; font-size: 12px; }}
-        .total {{ font-size: 18px; font-weight: bold; margin: 20px 0; }}
-    </style>
-</head>
-<body>
-    <div class="container">
-        <div class="header">
-            <h1>🎉 Payment Successful!</h1>
-        </div>
-        <div class="content">
-            <p>Hi there,</p>
+    let html_body = load_branding(pool).await.email_html(
+        "🎉 Payment Successful!",
+        &format!(
+            r#"<p>Hi there,</p>
             <p>Thank you for your payment! Your transaction has been completed successfully.</p>
             <p><strong>Order ID:</strong> {}</p>
+            {}
             <p class="total">Amount Paid: ${:.2}</p>
+            {}
             <p>We've received your payment and will process your order shortly. You'll receive a shipping confirmation email once your order ships.</p>
-            <p>If you have any questions, please don't hesitate to contact us.</p>
-        </div>
-        <div class="footer">
-            <p>© 2025 R-Com Store. All rights reserved.</p>
-        </div>
-    </div>
-</body>
-</html>
-        "#,
-        order_id,
-        amount as f64 / 100.0
+            <p>If you have any questions, please don't hesitate to contact us.</p>"#,
+            order_id,
+            super::items_table_html(&items),
+            amount as f64 / 100.0,
+            crate::license_keys::keys_html(&license_keys)
+        ),
     );
 
     // Send email
-    match send_html_email(&config, email, &format!("Payment Confirmation - {}", order_id), &html_body).await {
-        Ok(_) => println!("✓ Order confirmation email sent to {}", email),
-        Err(e) => eprintln!("✗ Failed to send email: {}", e),
+    match send_html_email(pool, &config, email, &format!("Payment Confirmation - {}", order_id), &html_body).await {
+        Ok(_) => tracing::info!("Order confirmation email sent to {}", email),
+        Err(e) => tracing::error!("Failed to send email: {}", e),
     }
 }
 
 // Helper function to send HTML email
-async fn send_html_email(config: &crate::lettre_email::EmailConfig, to: &str, subject: &str, html_body: &str) -> Result<(), String> {
+pub(crate) async fn send_html_email(pool: &sqlx::PgPool, config: &crate::lettre_email::EmailConfig, to: &str, subject: &str, html_body: &str) -> Result<(), String> {
     use lettre::{Message, SmtpTransport, Transport, message::header::ContentType, transport::smtp::authentication::Credentials};
 
+    if crate::email_log::is_suppressed(pool, to).await.map_err(|e| format!("DB error: {}", e))? {
+        return Err(format!("{} is on the suppression list", to));
+    }
+
     let from_mailbox = format!("{} <{}>", config.from_name, config.from_email)
         .parse()
         .map_err(|e| format!("Invalid from address: {}", e))?;
@@ -384,6 +837,7 @@ async fn send_html_email(config: &crate::lettre_email::EmailConfig, to: &str, su
 
     mailer.send(&email)
         .map_err(|e| format!("Failed to send email: {}", e))?;
+    crate::notifications::record_send(pool, crate::notifications::CHANNEL_EMAIL).await;
 
     Ok(())
 }
@@ -0,0 +1,157 @@
+// Twilio Inbound SMS Webhook Handler
+// Logs every inbound text and handles the STOP / START / HELP compliance keywords,
+// replying with TwiML. Requests are verified with the X-Twilio-Signature header
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Form,
+};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::api_keys::constant_time_eq;
+use crate::app_config;
+use crate::branding::{escape_html, load_branding};
+use crate::sms_log::{opt_in, opt_out, record_inbound};
+use crate::textbelt_sms::format_phone_number;
+use crate::AppState;
+
+type HmacSha1 = Hmac<Sha1>;
+
+const STOP_KEYWORDS: [&str; 6] = ["STOP", "STOPALL", "UNSUBSCRIBE", "CANCEL", "END", "QUIT"];
+const START_KEYWORDS: [&str; 3] = ["START", "YES", "UNSTOP"];
+const HELP_KEYWORDS: [&str; 2] = ["HELP", "INFO"];
+
+// Compliance keyword a text consists of, if any
+fn keyword(body: &str) -> Option<&'static str> {
+    let word = body.trim().to_uppercase();
+    if STOP_KEYWORDS.contains(&word.as_str()) {
+        Some("stop")
+    } else if START_KEYWORDS.contains(&word.as_str()) {
+        Some("start")
+    } else if HELP_KEYWORDS.contains(&word.as_str()) {
+        Some("help")
+    } else {
+        None
+    }
+}
+
+// Twilio signature: base64(HMAC-SHA1(auth token, webhook URL + each POST param's name and value, sorted by name))
+fn verify_twilio_signature(url: &str, params: &BTreeMap<String, String>, signature: &str, auth_token: &str) -> bool {
+    let mut mac = match HmacSha1::new_from_slice(auth_token.as_bytes()) {
+        Ok(m) => m,
+        Err(e) => {
+            tracing::error!("Failed to create HMAC: {}", e);
+            return false;
+        }
+    };
+    mac.update(url.as_bytes());
+    for (name, value) in params {
+        mac.update(name.as_bytes());
+        mac.update(value.as_bytes());
+    }
+    let expected = base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes());
+    constant_time_eq(expected.as_bytes(), signature.as_bytes())
+}
+
+fn twiml(message: Option<String>) -> impl IntoResponse {
+    let body = match message {
+        Some(message) => format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><Response><Message>{}</Message></Response>"#,
+            escape_html(&message)
+        ),
+        None => r#"<?xml version="1.0" encoding="UTF-8"?><Response></Response>"#.to_string(),
+    };
+    (StatusCode::OK, [(header::CONTENT_TYPE, "text/xml")], body)
+}
+
+// Twilio inbound message webhook (configured as the number's "A message comes in" URL)
+pub async fn handle_twilio_sms_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Form(params): Form<BTreeMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let config = app_config::get();
+    let auth_token = config.twilio.auth_token.as_ref().map(|t| t.expose()).ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Twilio auth token not configured".to_string(),
+    ))?;
+
+    let signature = headers
+        .get("x-twilio-signature")
+        .and_then(|v| v.to_str().ok())
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            "Missing X-Twilio-Signature header".to_string(),
+        ))?;
+
+    // Twilio signs the public URL it was configured with
+    let url = format!("{}/api/webhooks/sms", config.server.public_api_url.trim_end_matches('/'));
+    if !verify_twilio_signature(&url, &params, signature, auth_token) {
+        tracing::error!("Twilio webhook signature verification failed");
+        state.ops.webhook_signature_failed("Twilio");
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "Webhook signature verification failed".to_string(),
+        ));
+    }
+
+    let param = |name: &str| params.get(name).map(String::as_str);
+    let (Some(message_sid), Some(from)) = (param("MessageSid"), param("From")) else {
+        return Err((StatusCode::BAD_REQUEST, "Missing MessageSid or From".to_string()));
+    };
+    let from = format_phone_number(from).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let body = param("Body").unwrap_or_default();
+    let keyword = keyword(body);
+
+    let recorded = record_inbound(&state.pool, message_sid, &from, param("To"), body, keyword)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    if !recorded {
+        // Already handled; Twilio is retrying
+        return Ok(twiml(None));
+    }
+
+    let branding = load_branding(&state.pool).await;
+    let reply = match keyword {
+        Some("stop") => {
+            opt_out(&state.pool, &from, &body.trim().to_uppercase())
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+            tracing::info!("{} opted out of SMS", from);
+            Some(format!(
+                "{}: You have been unsubscribed and will not receive more messages. Reply START to resubscribe.",
+                branding.store_name
+            ))
+        }
+        Some("start") => {
+            opt_in(&state.pool, &from)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+            tracing::info!("{} opted back in to SMS", from);
+            Some(format!(
+                "{}: You are resubscribed to order updates. Reply STOP to unsubscribe.",
+                branding.store_name
+            ))
+        }
+        Some("help") => {
+            let contact = branding
+                .support_email
+                .as_deref()
+                .map(|email| format!(" Contact {} for help.", email))
+                .unwrap_or_default();
+            Some(format!(
+                "{}: Order and delivery updates. Msg & data rates may apply.{} Reply STOP to unsubscribe.",
+                branding.store_name, contact
+            ))
+        }
+        _ => None,
+    };
+
+    Ok(twiml(reply))
+}
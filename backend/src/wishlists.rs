@@ -0,0 +1,325 @@
+// Wishlists Module - Saved products and price drop alerts
+// Customers save products to their wishlist, watching the price by default. A price watch
+// remembers the price the customer saw; when a PriceDropped event (admin repricing, ERP
+// price sync or a sale starting) takes the effective price below it, they get one email and
+// the watch moves down to the new price
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::app_config;
+use crate::branding::{escape_html, load_branding};
+use crate::customer_auth::AuthenticatedCustomer;
+use crate::email_log::is_suppressed;
+use crate::events::{DomainEvent, EventSubscriber};
+use crate::notifications::{queue_email, EmailPriority};
+use crate::pii::Encrypted;
+use crate::pricing::{to_cents, PriceBook};
+use crate::AppState;
+
+// A saved product with its current price
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct WishlistItem {
+    pub product_id: i32,
+    pub name: String,
+    pub slug: Option<String>,
+    pub image_url: Option<String>,
+    pub price: f64, // Effective price, with any running sale
+    pub inventory: i32,
+    pub price_watch: bool,
+    pub watched_price: Option<i64>, // in cents; an email goes out when the price drops below it
+    pub added_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct AddWishlistItem {
+    pub product_id: i32,
+    #[serde(default = "default_price_watch")]
+    pub price_watch: bool,
+}
+
+#[derive(Deserialize)]
+pub struct PriceWatchInput {
+    pub price_watch: bool,
+}
+
+fn default_price_watch() -> bool {
+    true
+}
+
+// Watcher due a price drop email
+#[derive(sqlx::FromRow)]
+struct DueWatch {
+    email: Encrypted,
+    name: Option<String>,
+    watched_price: i64,
+}
+
+pub fn wishlist_routes(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/customers/me/wishlist", get(get_my_wishlist).post(add_to_wishlist))
+        .route(
+            "/api/customers/me/wishlist/:product_id",
+            delete(remove_from_wishlist).put(set_price_watch),
+        )
+        .with_state(app_state)
+}
+
+// Current effective single-unit price of a product, in cents
+async fn effective_price(pool: &sqlx::PgPool, product_id: i32) -> Result<Option<i64>, sqlx::Error> {
+    let base: Option<f64> = sqlx::query_scalar!(
+        "SELECT price FROM products WHERE id = $1 AND status = 'published'",
+        product_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(base) = base else {
+        return Ok(None);
+    };
+    let book = PriceBook::load_active(pool).await?;
+    Ok(Some(to_cents(book.unit_price(product_id, base, 1))))
+}
+
+// Start (or restart) watching from the current price
+async fn watch(pool: &sqlx::PgPool, wishlist_item_id: i32, product_id: i32) -> Result<(), sqlx::Error> {
+    let Some(price) = effective_price(pool, product_id).await? else {
+        return Ok(());
+    };
+    sqlx::query!(
+        "INSERT INTO price_watches (wishlist_item_id, watched_price) VALUES ($1, $2)
+         ON CONFLICT (wishlist_item_id) DO NOTHING",
+        wishlist_item_id,
+        price,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn load_wishlist(pool: &sqlx::PgPool, customer_id: sqlx::types::Uuid) -> Result<Vec<WishlistItem>, sqlx::Error> {
+    let mut items = sqlx::query_as!(
+        WishlistItem,
+        r#"SELECT i.product_id, p.name, p.slug AS "slug?", p.image_url, p.price, p.inventory,
+                w.wishlist_item_id IS NOT NULL AS "price_watch!", w.watched_price AS "watched_price?", i.created_at AS added_at
+         FROM wishlist_items i
+         JOIN products p ON p.id = i.product_id
+         LEFT JOIN price_watches w ON w.wishlist_item_id = i.id
+         WHERE i.customer_id = $1 AND p.status = 'published'
+         ORDER BY i.created_at DESC"#,
+        customer_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    let book = PriceBook::load_active(pool).await?;
+    for item in &mut items {
+        item.price = book.unit_price(item.product_id, item.price, 1);
+    }
+    Ok(items)
+}
+
+async fn get_my_wishlist(
+    customer: AuthenticatedCustomer,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<WishlistItem>>, (StatusCode, String)> {
+    let items = load_wishlist(&app_state.pool, customer.customer_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(items))
+}
+
+// Save a product; adding one that is already saved just updates its price watch
+async fn add_to_wishlist(
+    customer: AuthenticatedCustomer,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<AddWishlistItem>,
+) -> Result<(StatusCode, Json<Vec<WishlistItem>>), (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let published: bool =
+        sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM products WHERE id = $1 AND status = 'published') AS "published!""#,
+            input.product_id,
+        )
+        .fetch_one(&*app_state.pool)
+        .await
+        .map_err(db_error)?;
+    if !published {
+        return Err((StatusCode::NOT_FOUND, "Product not found".to_string()));
+    }
+
+    let item_id: i32 = sqlx::query_scalar!(
+        "INSERT INTO wishlist_items (customer_id, product_id) VALUES ($1, $2)
+         ON CONFLICT (customer_id, product_id) DO UPDATE SET customer_id = EXCLUDED.customer_id
+         RETURNING id",
+        customer.customer_id,
+        input.product_id,
+    )
+    .fetch_one(&*app_state.pool)
+    .await
+    .map_err(db_error)?;
+    update_watch(&app_state.pool, item_id, input.product_id, input.price_watch)
+        .await
+        .map_err(db_error)?;
+
+    let items = load_wishlist(&app_state.pool, customer.customer_id).await.map_err(db_error)?;
+    Ok((StatusCode::CREATED, Json(items)))
+}
+
+async fn update_watch(pool: &sqlx::PgPool, item_id: i32, product_id: i32, price_watch: bool) -> Result<(), sqlx::Error> {
+    if price_watch {
+        watch(pool, item_id, product_id).await
+    } else {
+        sqlx::query!("DELETE FROM price_watches WHERE wishlist_item_id = $1", item_id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
+
+// Turn the price drop alert for a saved product on or off
+async fn set_price_watch(
+    customer: AuthenticatedCustomer,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+    Json(input): Json<PriceWatchInput>,
+) -> Result<Json<Vec<WishlistItem>>, (StatusCode, String)> {
+    let db_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e));
+    let item_id: Option<i32> =
+        sqlx::query_scalar!(
+            "SELECT id FROM wishlist_items WHERE customer_id = $1 AND product_id = $2",
+            customer.customer_id,
+            product_id,
+        )
+        .fetch_optional(&*app_state.pool)
+        .await
+        .map_err(db_error)?;
+    let item_id = item_id.ok_or((StatusCode::NOT_FOUND, "Product is not on your wishlist".to_string()))?;
+    update_watch(&app_state.pool, item_id, product_id, input.price_watch)
+        .await
+        .map_err(db_error)?;
+    let items = load_wishlist(&app_state.pool, customer.customer_id).await.map_err(db_error)?;
+    Ok(Json(items))
+}
+
+async fn remove_from_wishlist(
+    customer: AuthenticatedCustomer,
+    State(app_state): State<Arc<AppState>>,
+    Path(product_id): Path<i32>,
+) -> Result<Json<bool>, (StatusCode, String)> {
+    let res = sqlx::query!(
+        "DELETE FROM wishlist_items WHERE customer_id = $1 AND product_id = $2",
+        customer.customer_id,
+        product_id,
+    )
+    .execute(&*app_state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    Ok(Json(res.rows_affected() > 0))
+}
+
+// Emails customers watching a product's price when it drops below what they last saw
+pub struct PriceWatchSubscriber {
+    pool: Arc<sqlx::PgPool>,
+}
+
+impl PriceWatchSubscriber {
+    pub fn new(pool: Arc<sqlx::PgPool>) -> Self {
+        Self { pool }
+    }
+
+    async fn notify_watchers(&self, product_id: i32, product_name: &str) -> Result<(), String> {
+        let db_error = |e: sqlx::Error| format!("DB error: {}", e);
+        // The event says a price went down; what the customer pays is the effective price,
+        // which an earlier sale may already have taken lower
+        let Some(price) = effective_price(&self.pool, product_id).await.map_err(db_error)? else {
+            return Ok(());
+        };
+
+        // Claim the due watches so a second event for the same drop sends nothing
+        let due = sqlx::query_as!(
+            DueWatch,
+            r#"WITH due AS (
+                 SELECT w.wishlist_item_id, w.watched_price, c.email, c.name
+                 FROM price_watches w
+                 JOIN wishlist_items i ON i.id = w.wishlist_item_id
+                 JOIN customers c ON c.id = i.customer_id
+                 WHERE i.product_id = $1 AND w.watched_price > $2
+                   AND c.price_drop_emails AND NOT c.disabled
+                 FOR UPDATE OF w SKIP LOCKED
+             ), claimed AS (
+                 UPDATE price_watches w SET watched_price = $2, notified_at = NOW()
+                 FROM due WHERE w.wishlist_item_id = due.wishlist_item_id
+             )
+             SELECT email AS "email: Encrypted", name, watched_price FROM due"#,
+            product_id,
+            price,
+        )
+        .fetch_all(&*self.pool)
+        .await
+        .map_err(db_error)?;
+        if due.is_empty() {
+            return Ok(());
+        }
+
+        let slug: String = sqlx::query_scalar!("SELECT slug FROM products WHERE id = $1", product_id)
+            .fetch_one(&*self.pool)
+            .await
+            .map_err(db_error)?;
+        let storefront_url = app_config::get().server.storefront_url.trim_end_matches('/').to_string();
+        let link = format!("{}/product/{}", storefront_url, slug);
+        let branding = load_branding(&self.pool).await;
+
+        for watch in due {
+            if is_suppressed(&self.pool, &watch.email).await.map_err(db_error)? {
+                continue;
+            }
+            let greeting = watch
+                .name
+                .as_deref()
+                .map(|n| format!("Hi {},", escape_html(n)))
+                .unwrap_or_else(|| "Hi,".to_string());
+            let html_body = branding.email_html(
+                "Price Drop on Your Wishlist",
+                &format!(
+                    "<p>{}</p><p><strong>{}</strong> from your wishlist is now <strong>${:.2}</strong> \
+                     (was ${:.2}).</p><p><a href=\"{}\">View it in the store</a></p>\
+                     <p style=\"color:#888;font-size:12px\">You can turn off price drop emails in your \
+                     account's notification settings, or stop watching this item on your wishlist.</p>",
+                    greeting,
+                    escape_html(product_name),
+                    price as f64 / 100.0,
+                    watch.watched_price as f64 / 100.0,
+                    link
+                ),
+            );
+            let subject = format!("Price drop: {}", product_name);
+            if let Err(e) = queue_email(&self.pool, &watch.email, &subject, &html_body, EmailPriority::Batched).await {
+                tracing::error!("Failed to queue price drop email for product {}: {}", product_id, e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for PriceWatchSubscriber {
+    fn name(&self) -> &'static str {
+        "price_watch"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), String> {
+        match event {
+            DomainEvent::PriceDropped { product_id, product_name, .. } => {
+                self.notify_watchers(*product_id, product_name).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+// A 1,000,000-product CSV export, read through the admin route while the process's memory is
+// watched. Seeding and reading that many rows takes a while, so it only runs when asked for:
+// `cargo test --test exports -- --ignored`. Needs the migrated database in DATABASE_URL; the
+// products it creates are removed afterwards
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use backend::testing;
+use futures_util::StreamExt;
+use sqlx::types::Uuid;
+use tower::ServiceExt;
+
+const PRODUCTS: i64 = 1_000_000;
+const MAX_GROWTH_KB: u64 = 64 * 1024; // The whole file is about 96 MB
+
+fn set_default_env(key: &str, value: &str) {
+    if std::env::var(key).is_err() {
+        std::env::set_var(key, value);
+    }
+}
+
+// Resident memory of this process
+fn rss_kb() -> u64 {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap();
+    let line = status.lines().find(|l| l.starts_with("VmRSS:")).unwrap();
+    line.split_whitespace().nth(1).unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+#[ignore = "seeds and exports 1,000,000 products"]
+async fn million_product_export_streams_in_bounded_memory() {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL");
+    set_default_env("STRIPE_SECRET_KEY", "sk_test_exports");
+    set_default_env("STRIPE_WEBHOOK_SECRET", "whsec_exports");
+    set_default_env("JWT_SECRET", "exports-test");
+    let pool = sqlx::PgPool::connect(&database_url).await.expect("connect to DATABASE_URL");
+    let state = testing::app_state(pool.clone());
+
+    let tag = format!("export-test-{}", Uuid::new_v4().simple());
+    sqlx::query(
+        "INSERT INTO products (name, price, inventory, slug)
+         SELECT $1 || ' ' || n, 9.99, 10, $1 || '-' || n FROM generate_series(1, $2) AS n",
+    )
+    .bind(&tag)
+    .bind(PRODUCTS)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let app = testing::admin_product_routes(state.clone()).with_state(state);
+    let token = testing::issue_admin_token("test".to_string()).unwrap();
+    let request = Request::get("/api/admin/products/export")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+
+    let baseline = rss_kb();
+    let mut peak = baseline;
+    let (mut bytes, mut exported) = (0usize, 0usize);
+    let mut partial_line: Vec<u8> = Vec::new(); // Lines can be split across chunks
+    let mut body = response.into_body().into_data_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.expect("export body");
+        bytes += chunk.len();
+        partial_line.extend_from_slice(&chunk);
+        let complete = partial_line.iter().rposition(|&b| b == b'\n').map_or(0, |end| end + 1);
+        exported += partial_line[..complete]
+            .split(|&b| b == b'\n')
+            .filter(|line| line.windows(tag.len()).any(|w| w == tag.as_bytes()))
+            .count();
+        partial_line.drain(..complete);
+        peak = peak.max(rss_kb());
+    }
+
+    sqlx::query("DELETE FROM products WHERE slug LIKE $1 || '-%'").bind(&tag).execute(&pool).await.unwrap();
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(exported, PRODUCTS as usize, "every seeded product should be exported ({} bytes read)", bytes);
+    assert!(
+        peak - baseline < MAX_GROWTH_KB,
+        "memory grew by {} KB while exporting {} bytes",
+        peak - baseline,
+        bytes
+    );
+}
@@ -0,0 +1,117 @@
+// Concurrent checkouts racing for the last units of a product. Needs the migrated database in
+// DATABASE_URL, so it only runs when asked for:
+// `cargo test --test stock_reservations -- --ignored`. The product and carts it creates are
+// removed afterwards
+
+use backend::testing::{self, PricedLine, WorkError};
+use futures_util::future::join_all;
+use sqlx::types::Uuid;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+const CHECKOUTS: usize = 8;
+
+fn set_default_env(key: &str, value: &str) {
+    if std::env::var(key).is_err() {
+        std::env::set_var(key, value);
+    }
+}
+
+#[tokio::test]
+#[ignore = "requires DATABASE_URL"]
+async fn concurrent_checkouts_never_oversell() {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL");
+    set_default_env("STRIPE_SECRET_KEY", "sk_test_stock_reservations");
+    set_default_env("STRIPE_WEBHOOK_SECRET", "whsec_stock_reservations");
+    set_default_env("JWT_SECRET", "stock-reservations-test");
+    let pool = sqlx::PgPool::connect(&database_url).await.expect("connect to DATABASE_URL");
+    let state = testing::app_state(pool.clone());
+
+    let stock = CHECKOUTS as i32 - 1;
+    let slug = format!("stock-race-{}", Uuid::new_v4().simple());
+    let product_id: i32 = sqlx::query_scalar(
+        "INSERT INTO products (name, price, inventory, slug) VALUES ('Stock race', 10.0, $1, $2) RETURNING id",
+    )
+    .bind(stock)
+    .bind(&slug)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let mut carts = Vec::new();
+    for _ in 0..CHECKOUTS {
+        let cart_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO checkout_carts (order_id, subtotal_amount, total_amount) VALUES (gen_random_uuid(), 1000, 1000)
+             RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        carts.push(cart_id);
+    }
+
+    // Sample the inventory while the checkouts run
+    let running = Arc::new(AtomicBool::new(true));
+    let watcher = tokio::spawn({
+        let (pool, running) = (pool.clone(), running.clone());
+        async move {
+            let mut lowest = i32::MAX;
+            while running.load(Ordering::Relaxed) {
+                let inventory: i32 = sqlx::query_scalar("SELECT inventory FROM products WHERE id = $1")
+                    .bind(product_id)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+                lowest = lowest.min(inventory);
+                tokio::task::yield_now().await;
+            }
+            lowest
+        }
+    });
+
+    let checkouts = carts.iter().map(|&cart_id| {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let lines = vec![PricedLine {
+                product_id,
+                name: "Stock race".to_string(),
+                quantity: 1,
+                unit_price: 1000,
+                line_total: 1000,
+                ship_to: None,
+            }];
+            testing::run_unit_of_work(&state, |uow| {
+                let lines = lines.clone();
+                Box::pin(async move { testing::reserve_for_cart(uow, cart_id, &lines).await })
+            })
+            .await
+        })
+    });
+    let results: Vec<_> = join_all(checkouts).await.into_iter().map(|r| r.unwrap()).collect();
+    running.store(false, Ordering::Relaxed);
+    let lowest = watcher.await.unwrap();
+
+    let inventory: i32 = sqlx::query_scalar("SELECT inventory FROM products WHERE id = $1")
+        .bind(product_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let held: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM stock_reservations WHERE product_id = $1 AND status = 'held'")
+        .bind(product_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    sqlx::query("DELETE FROM checkout_carts WHERE id = ANY($1)").bind(&carts).execute(&pool).await.unwrap();
+    sqlx::query("DELETE FROM products WHERE id = $1").bind(product_id).execute(&pool).await.unwrap();
+
+    let failures: Vec<_> = results.iter().filter_map(|r| r.as_ref().err()).collect();
+    assert_eq!(failures.len(), 1, "exactly one checkout should miss out: {:?}", failures);
+    assert!(
+        matches!(failures[0], WorkError::Aborted((status, _)) if status.as_u16() == 409),
+        "the loser should get a 409, got {:?}",
+        failures[0]
+    );
+    assert!(lowest >= 0, "inventory went down to {}", lowest);
+    assert_eq!(inventory, 0);
+    assert_eq!(held, stock as i64);
+}
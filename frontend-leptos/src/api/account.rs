@@ -0,0 +1,89 @@
+// Customer account API (login, store credit, loyalty points, referrals)
+
+use super::{get, post, ApiError};
+use serde::{Deserialize, Serialize};
+
+const TOKEN_KEY: &str = "customer_token";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomerLoginResponse {
+    pub token: String,
+    pub customer_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreCreditEntry {
+    pub amount: i64, // in cents; negative for debits
+    pub reason: String,
+    pub note: Option<String>,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreCreditAccount {
+    pub balance: i64, // in cents
+    pub entries: Vec<StoreCreditEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoyaltyEntry {
+    pub points: i32, // negative for redemptions and reversals
+    pub reason: String,
+    pub note: Option<String>,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoyaltyAccount {
+    pub balance: i64,
+    pub balance_value: i64, // in cents
+    pub points_per_dollar: i64,
+    pub points_per_dollar_off: i64,
+    pub entries: Vec<LoyaltyEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReferralStats {
+    pub referral_code: String,
+    pub referral_link: String,
+    pub visits: i64,
+    pub successful_referrals: i64,
+    pub total_rewards: i64, // in cents
+}
+
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window().and_then(|w| w.local_storage().ok().flatten())
+}
+
+/// Saved customer token, if logged in
+pub fn load_token() -> Option<String> {
+    storage().and_then(|s| s.get_item(TOKEN_KEY).ok().flatten())
+}
+
+pub fn logout() {
+    if let Some(storage) = storage() {
+        let _ = storage.remove_item(TOKEN_KEY);
+    }
+}
+
+/// Log in and remember the token for later requests
+pub async fn login(email: String, password: String) -> Result<(), ApiError> {
+    let body = serde_json::json!({ "email": email, "password": password });
+    let response: CustomerLoginResponse = post("/api/customers/login", &body).await?;
+    if let Some(storage) = storage() {
+        let _ = storage.set_item(TOKEN_KEY, &response.token);
+    }
+    Ok(())
+}
+
+pub async fn fetch_store_credit() -> Result<StoreCreditAccount, ApiError> {
+    get("/api/customers/me/store-credit").await
+}
+
+pub async fn fetch_loyalty() -> Result<LoyaltyAccount, ApiError> {
+    get("/api/customers/me/loyalty").await
+}
+
+pub async fn fetch_referrals() -> Result<ReferralStats, ApiError> {
+    get("/api/customers/me/referrals").await
+}
@@ -0,0 +1,58 @@
+// Marketing attribution: UTM parameters from landing pages, sent with checkout
+
+use serde::{Deserialize, Serialize};
+
+const ATTRIBUTION_KEY: &str = "utm_attribution";
+const UTM_PARAMS: [&str; 3] = ["utm_source", "utm_medium", "utm_campaign"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Attribution {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utm_source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utm_medium: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utm_campaign: Option<String>,
+}
+
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window().and_then(|w| w.local_storage().ok().flatten())
+}
+
+/// Remember the UTM parameters of the page the shopper landed on; a later campaign link
+/// replaces them (last touch), plain visits keep them
+pub fn capture_from_url() {
+    let Some(search) = web_sys::window().and_then(|w| w.location().search().ok()) else {
+        return;
+    };
+    let mut attribution = Attribution::default();
+    for pair in search.trim_start_matches('?').split('&') {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        if !UTM_PARAMS.contains(&key) {
+            continue;
+        }
+        let value = urlencoding::decode(&value.replace('+', " ")).map(|v| v.trim().to_string()).unwrap_or_default();
+        if value.is_empty() {
+            continue;
+        }
+        match key {
+            "utm_source" => attribution.utm_source = Some(value),
+            "utm_medium" => attribution.utm_medium = Some(value),
+            _ => attribution.utm_campaign = Some(value),
+        }
+    }
+    if attribution.utm_source.is_none() && attribution.utm_medium.is_none() && attribution.utm_campaign.is_none() {
+        return;
+    }
+    if let (Ok(json), Some(storage)) = (serde_json::to_string(&attribution), storage()) {
+        let _ = storage.set_item(ATTRIBUTION_KEY, &json);
+    }
+}
+
+/// The remembered UTM parameters, for the checkout payload
+pub fn load_attribution() -> Attribution {
+    storage()
+        .and_then(|s| s.get_item(ATTRIBUTION_KEY).ok().flatten())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
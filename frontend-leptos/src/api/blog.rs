@@ -0,0 +1,52 @@
+// Blog API (published posts and RSS feed)
+
+use super::{get, ApiError, API_BASE};
+use serde::{Deserialize, Serialize};
+
+pub const POSTS_PER_PAGE: i64 = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostSummary {
+    pub slug: String,
+    pub title: String,
+    pub excerpt: Option<String>,
+    pub cover_image_url: Option<String>,
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    pub published_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostList {
+    pub posts: Vec<PostSummary>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Post {
+    #[serde(flatten)]
+    pub summary: PostSummary,
+    pub html: String, // Rendered from markdown and sanitized on the server
+    pub updated_at: Option<String>,
+}
+
+/// Fetch one page (1-based) of published posts, optionally filtered by tag
+pub async fn fetch_posts(page: i64, tag: Option<String>) -> Result<PostList, ApiError> {
+    let mut endpoint = format!("/api/posts?limit={}&offset={}", POSTS_PER_PAGE, (page - 1).max(0) * POSTS_PER_PAGE);
+    if let Some(tag) = tag {
+        endpoint.push_str(&format!("&tag={}", urlencoding::encode(&tag)));
+    }
+    get(&endpoint).await
+}
+
+/// Fetch a published post by slug
+pub async fn fetch_post(slug: &str) -> Result<Post, ApiError> {
+    get(&format!("/api/posts/{}", urlencoding::encode(slug))).await
+}
+
+/// RSS feed URL, for the <link rel="alternate"> tag
+pub fn rss_url() -> String {
+    format!("{}/api/blog/rss.xml", API_BASE)
+}
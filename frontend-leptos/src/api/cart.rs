@@ -1,6 +1,13 @@
-// Cart API (currently client-side only, can add backend sync later)
+// Cart API (cart kept in localStorage, totals priced by the backend)
 
-use crate::types::{Cart, Product};
+use crate::types::{Cart, CartLine, CartPrice, Product};
+use super::{post, ApiError};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+struct CartPriceRequest {
+    items: Vec<CartLine>,
+}
 
 /// Load cart from localStorage
 pub fn load_cart() -> Cart {
@@ -46,3 +53,9 @@ pub fn clear_cart(cart: &mut Cart) {
     cart.clear();
     save_cart(cart);
 }
+
+/// Fetch the totals checkout will charge for this cart
+pub async fn price_cart(cart: &Cart) -> Result<CartPrice, ApiError> {
+    let request = CartPriceRequest { items: cart.lines() };
+    post("/api/cart/price", &request).await
+}
@@ -1,32 +1,97 @@
 // Checkout and payment API
 
-use crate::types::{CheckoutRequest, Order};
-use super::{post, ApiError};
+use crate::types::{Cart, CartLine, CheckoutRequest, Order, ShippingAddress};
+use super::{attribution::{load_attribution, Attribution}, drops::load_admission, get, post, ApiError};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PaymentIntentRequest {
-    pub amount: i64,  // Amount in cents
     pub currency: String,
+    pub items: Vec<CartLine>, // Priced by the backend; the amount is never sent
+    pub to_address: ToAddress, // Shipping is quoted for this address and added to the charge
+    pub gift_wrap: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gift_message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redeem_points: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drop_token: Option<String>, // Admitted waiting room token for a running drop
+    #[serde(flatten)]
+    pub attribution: Attribution, // utm_source / utm_medium / utm_campaign the shopper arrived with
+}
+
+// Ship-to address in the backend's shipping format
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ToAddress {
+    pub street1: String,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    pub country: String, // Two-letter country code
+}
+
+impl From<&ShippingAddress> for ToAddress {
+    fn from(address: &ShippingAddress) -> Self {
+        Self {
+            street1: address.street.trim().to_string(),
+            city: address.city.trim().to_string(),
+            state: address.state.trim().to_string(),
+            zip: address.zip.trim().to_string(),
+            country: address.country.trim().to_uppercase(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PaymentIntentResponse {
     pub client_secret: String,
+    pub amount: i64, // in cents, as charged
+    #[serde(default)]
+    pub store_credit_applied: Option<i64>, // in cents, when logged in with credit
+    #[serde(default)]
+    pub loyalty_points_redeemed: Option<i64>,
+    #[serde(default)]
+    pub loyalty_discount: Option<i64>, // in cents
+    #[serde(default)]
+    pub order_id: Option<String>,
+    #[serde(default)]
+    pub hold_expires_at: Option<String>, // When reserved stock goes back, if anything was reserved
 }
 
-/// Create Stripe payment intent
-pub async fn create_payment_intent(amount: f64) -> Result<PaymentIntentResponse, ApiError> {
-    let amount_cents = (amount * 100.0) as i64;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckoutHold {
+    pub status: String, // held, expired, released, converted or none
+    pub expires_at: Option<String>,
+    pub seconds_remaining: i64,
+}
 
+/// Create Stripe payment intent for a ship-to address, with optional gift wrap, gift message and loyalty points
+pub async fn create_payment_intent(
+    cart: &Cart,
+    address: &ShippingAddress,
+    gift_wrap: bool,
+    gift_message: Option<String>,
+    redeem_points: Option<i64>,
+) -> Result<PaymentIntentResponse, ApiError> {
     let request = PaymentIntentRequest {
-        amount: amount_cents,
         currency: "usd".to_string(),
+        items: cart.lines(),
+        to_address: address.into(),
+        gift_wrap,
+        gift_message,
+        redeem_points,
+        drop_token: load_admission(),
+        attribution: load_attribution(),
     };
 
     post("/api/create-payment-intent", &request).await
 }
 
+/// Fetch how long the checkout's reserved stock is still held
+pub async fn fetch_hold(order_id: &str) -> Result<CheckoutHold, ApiError> {
+    get(&format!("/api/checkout/{}/hold", order_id)).await
+}
+
 /// Submit checkout order (placeholder - will integrate with backend)
 pub async fn submit_order(checkout: &CheckoutRequest) -> Result<Order, ApiError> {
     // TODO: Implement actual backend endpoint
@@ -34,5 +99,6 @@ pub async fn submit_order(checkout: &CheckoutRequest) -> Result<Order, ApiError>
     Err(ApiError {
         message: "Order submission not yet implemented".to_string(),
         status: 501,
+        request_id: None,
     })
 }
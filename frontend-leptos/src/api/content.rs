@@ -0,0 +1,18 @@
+// CMS content API (hero banner, announcement bar, homepage sections)
+
+use super::{get, ApiError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentBlock {
+    pub key: String,
+    pub placement: String,
+    pub title: Option<String>,
+    pub html: String, // Rendered on the server from markdown or HTML
+    pub position: i32,
+}
+
+/// Fetch the blocks currently live for a placement ("hero", "announcement" or "home_section")
+pub async fn fetch_content_blocks(placement: &str) -> Result<Vec<ContentBlock>, ApiError> {
+    get(&format!("/api/content-blocks?placement={}", urlencoding::encode(placement))).await
+}
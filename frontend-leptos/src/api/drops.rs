@@ -0,0 +1,80 @@
+// Waiting room API for high-demand drops
+
+use super::{get, post, ApiError};
+use serde::{Deserialize, Serialize};
+
+const ADMISSION_KEY: &str = "drop_admission";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DropInfo {
+    pub id: String,
+    pub name: String,
+    pub product_ids: Vec<i32>,
+    pub starts_at: String,
+    pub ends_at: Option<String>,
+    pub waiting: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueStatus {
+    pub drop_id: String,
+    pub token: String,
+    pub status: String, // not_started, waiting, admitted, expired, ended
+    pub position: i64,
+    pub ahead: i64,
+    #[serde(default)]
+    pub estimated_wait_secs: Option<i64>,
+    #[serde(default)]
+    pub admitted_until: Option<String>,
+    pub poll_after_secs: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct JoinRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+}
+
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window().and_then(|w| w.local_storage().ok().flatten())
+}
+
+fn queue_key(drop_id: &str) -> String {
+    format!("drop_queue:{}", drop_id)
+}
+
+/// Fetch a drop for the waiting room page
+pub async fn fetch_drop(drop_id: &str) -> Result<DropInfo, ApiError> {
+    get(&format!("/api/drops/{}", urlencoding::encode(drop_id))).await
+}
+
+/// Join the drop's queue, keeping the place saved in this browser if there is one
+pub async fn join_queue(drop_id: &str) -> Result<QueueStatus, ApiError> {
+    let token = storage().and_then(|s| s.get_item(&queue_key(drop_id)).ok().flatten());
+    let status: QueueStatus = post(&format!("/api/drops/{}/queue", urlencoding::encode(drop_id)), &JoinRequest { token }).await?;
+    if let Some(storage) = storage() {
+        let _ = storage.set_item(&queue_key(drop_id), &status.token);
+    }
+    Ok(status)
+}
+
+/// Current place in the queue; admitted tokens are kept for checkout
+pub async fn queue_status(drop_id: &str, token: &str) -> Result<QueueStatus, ApiError> {
+    let status: QueueStatus = get(&format!(
+        "/api/drops/{}/queue?token={}",
+        urlencoding::encode(drop_id),
+        urlencoding::encode(token)
+    ))
+    .await?;
+    if status.status == "admitted" {
+        if let Some(storage) = storage() {
+            let _ = storage.set_item(ADMISSION_KEY, &status.token);
+        }
+    }
+    Ok(status)
+}
+
+/// Admitted queue token sent with the payment intent, if any
+pub fn load_admission() -> Option<String> {
+    storage().and_then(|s| s.get_item(ADMISSION_KEY).ok().flatten())
+}
@@ -0,0 +1,77 @@
+// Catalog A/B test API: variant assignments and exposure logging
+
+use super::{get, post, ApiError};
+use serde::{Deserialize, Serialize};
+
+const VISITOR_KEY: &str = "visitor_id";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assignment {
+    pub experiment: String,
+    pub kind: String, // product_title, price_badge, free_shipping_message, custom
+    pub product_ids: Vec<i32>, // Empty when it applies to every product
+    pub variant: String,
+    #[serde(default)]
+    pub config: serde_json::Value,
+}
+
+impl Assignment {
+    pub fn applies_to(&self, product_id: i32) -> bool {
+        self.product_ids.is_empty() || self.product_ids.contains(&product_id)
+    }
+
+    /// A text setting of the variant, e.g. "title", "badge" or "message"
+    pub fn text(&self, field: &str) -> Option<String> {
+        self.config.get(field).and_then(|v| v.as_str()).map(str::to_string)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Assignments {
+    #[serde(default)]
+    visitor_id: Option<String>,
+    experiments: Vec<Assignment>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExposureRequest {}
+
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window().and_then(|w| w.local_storage().ok().flatten())
+}
+
+fn session() -> Option<web_sys::Storage> {
+    web_sys::window().and_then(|w| w.session_storage().ok().flatten())
+}
+
+/// Anonymous visitor id the backend assigned, sent with every request as X-Visitor-Id
+pub fn load_visitor_id() -> Option<String> {
+    storage().and_then(|s| s.get_item(VISITOR_KEY).ok().flatten())
+}
+
+/// This visitor's variant in every running experiment
+pub async fn fetch_assignments() -> Result<Vec<Assignment>, ApiError> {
+    let assignments: Assignments = get("/api/experiments").await?;
+    if let (Some(id), Some(storage)) = (assignments.visitor_id, storage()) {
+        let _ = storage.set_item(VISITOR_KEY, &id);
+    }
+    Ok(assignments.experiments)
+}
+
+/// Log that the visitor saw their variant; sent once per browser session per experiment
+pub fn record_exposure(experiment: &str) {
+    let key = format!("experiment_exposed:{}", experiment);
+    let session = session();
+    if session.as_ref().and_then(|s| s.get_item(&key).ok().flatten()).is_some() {
+        return;
+    }
+    if let Some(session) = &session {
+        let _ = session.set_item(&key, "1");
+    }
+    let endpoint = format!("/api/experiments/{}/exposures", urlencoding::encode(experiment));
+    leptos::spawn_local(async move {
+        if let Err(e) = post::<Assignment, _>(&endpoint, &ExposureRequest {}).await {
+            log::error!("Failed to record experiment exposure: {}", e.message);
+        }
+    });
+}
@@ -3,55 +3,117 @@
 pub mod products;
 pub mod cart;
 pub mod checkout;
-
-use gloo_net::http::Request;
-use serde::de::DeserializeOwned;
+pub mod shipping;
+pub mod reviews;
+pub mod account;
+pub mod content;
+pub mod pages;
+pub mod blog;
+pub mod orders;
+pub mod drops;
+pub mod experiments;
+pub mod attribution;
+
+use gloo_net::http::{Request, Response};
+use serde::{de::DeserializeOwned, Deserialize};
 
 /// Base URL for the API
-const API_BASE: &str = "http://localhost:3000";
+pub const API_BASE: &str = "http://localhost:3000";
 
 /// Generic API error type
 #[derive(Debug, Clone)]
 pub struct ApiError {
     pub message: String,
     pub status: u16,
+    /// Id the server gave the failed request, for quoting to support
+    pub request_id: Option<String>,
+}
+
+impl ApiError {
+    /// Message for showing to the customer, with the reference support can look up
+    pub fn user_message(&self) -> String {
+        match &self.request_id {
+            Some(id) => format!("{} (Reference: {})", self.message, id),
+            None => self.message.clone(),
+        }
+    }
 }
 
 impl std::fmt::Display for ApiError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "API Error {}: {}", self.status, self.message)
+        write!(f, "API Error {}: {}", self.status, self.message)?;
+        if let Some(id) = &self.request_id {
+            write!(f, " (request {})", id)?;
+        }
+        Ok(())
     }
 }
 
 impl std::error::Error for ApiError {}
 
+/// Error body the API returns: `{"error": "...", "request_id": "..."}`
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: String,
+    request_id: Option<String>,
+}
+
+/// Build the error for a failed response, keeping the server's request id
+async fn response_error(response: Response) -> ApiError {
+    let status = response.status();
+    let header_id = response.headers().get("x-request-id");
+    let text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+    match serde_json::from_str::<ErrorBody>(&text) {
+        Ok(body) => ApiError {
+            message: body.error,
+            status,
+            request_id: body.request_id.or(header_id),
+        },
+        Err(_) => ApiError {
+            message: text,
+            status,
+            request_id: header_id,
+        },
+    }
+}
+
+/// Bearer header for the logged-in customer, if any
+fn auth_header() -> Option<String> {
+    account::load_token().map(|token| format!("Bearer {}", token))
+}
+
 /// Helper function to make GET requests
 pub async fn get<T: DeserializeOwned>(endpoint: &str) -> Result<T, ApiError> {
     let url = format!("{}{}", API_BASE, endpoint);
 
     log::info!("GET {}", url);
 
-    let response = Request::get(&url)
+    let mut request = Request::get(&url);
+    if let Some(auth) = auth_header() {
+        request = request.header("Authorization", &auth);
+    }
+    if let Some(visitor_id) = experiments::load_visitor_id() {
+        request = request.header("X-Visitor-Id", &visitor_id);
+    }
+    let response = request
         .send()
         .await
         .map_err(|e| ApiError {
             message: format!("Network error: {}", e),
             status: 0,
+            request_id: None,
         })?;
 
     let status = response.status();
 
     if !response.ok() {
-        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(ApiError {
-            message: error_text,
-            status,
-        });
+        return Err(response_error(response).await);
     }
 
     response.json::<T>().await.map_err(|e| ApiError {
         message: format!("Failed to parse response: {}", e),
         status,
+        request_id: None,
     })
 }
 
@@ -64,31 +126,37 @@ pub async fn post<T: DeserializeOwned, B: serde::Serialize>(
 
     log::info!("POST {}", url);
 
-    let response = Request::post(&url)
+    let mut request = Request::post(&url);
+    if let Some(auth) = auth_header() {
+        request = request.header("Authorization", &auth);
+    }
+    if let Some(visitor_id) = experiments::load_visitor_id() {
+        request = request.header("X-Visitor-Id", &visitor_id);
+    }
+    let response = request
         .json(body)
         .map_err(|e| ApiError {
             message: format!("Failed to serialize request: {}", e),
             status: 0,
+            request_id: None,
         })?
         .send()
         .await
         .map_err(|e| ApiError {
             message: format!("Network error: {}", e),
             status: 0,
+            request_id: None,
         })?;
 
     let status = response.status();
 
     if !response.ok() {
-        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(ApiError {
-            message: error_text,
-            status,
-        });
+        return Err(response_error(response).await);
     }
 
     response.json::<T>().await.map_err(|e| ApiError {
         message: format!("Failed to parse response: {}", e),
         status,
+        request_id: None,
     })
 }
@@ -0,0 +1,22 @@
+// Order tracking API (live status stream)
+
+use super::{account::load_token, API_BASE};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusUpdate {
+    pub order_id: String,
+    pub status: String, // pending, paid, fulfilled, shipped, delivered, refunded, failed
+}
+
+/// Server-sent events URL for an order. EventSource can't send headers, so the
+/// customer token (or a guest's checkout email) goes in the query string.
+pub fn order_events_url(order_id: &str, email: Option<&str>) -> String {
+    let mut url = format!("{}/api/orders/{}/events", API_BASE, urlencoding::encode(order_id));
+    if let Some(token) = load_token() {
+        url.push_str(&format!("?token={}", urlencoding::encode(&token)));
+    } else if let Some(email) = email {
+        url.push_str(&format!("?email={}", urlencoding::encode(email)));
+    }
+    url
+}
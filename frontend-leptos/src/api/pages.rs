@@ -0,0 +1,18 @@
+// Static pages API (shipping info, returns policy, ...)
+
+use super::{get, ApiError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page {
+    pub slug: String,
+    pub title: String,
+    pub html: String, // Rendered from markdown and sanitized on the server
+    pub meta_description: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+/// Fetch a published page by slug
+pub async fn fetch_page(slug: &str) -> Result<Page, ApiError> {
+    get(&format!("/api/pages/{}", urlencoding::encode(slug))).await
+}
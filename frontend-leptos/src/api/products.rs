@@ -1,14 +1,102 @@
 // Product API client
 
-use crate::types::Product;
+use serde::{Deserialize, Serialize};
+use crate::types::{Product, product::ProductSortOrder};
 use super::{get, ApiError};
 
+pub const SEARCH_PAGE_SIZE: i64 = 24;
+
+/// Filters for the catalog search; None means "don't filter on this"
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    pub q: String,
+    pub category: Option<String>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    pub in_stock: bool,
+    pub sort: ProductSortOrder,
+    pub page: i64, // 1-based
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryFacet {
+    pub value: String,
+    pub count: i64,
+    pub selected: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceFacet {
+    pub min: f64,
+    pub max: Option<f64>, // None for the top bucket
+    pub count: i64,
+    pub selected: bool,
+}
+
+impl PriceFacet {
+    pub fn label(&self) -> String {
+        match self.max {
+            Some(max) if self.min == 0.0 => format!("Under ${:.0}", max),
+            Some(max) => format!("${:.0} - ${:.0}", self.min, max),
+            None => format!("${:.0} and up", self.min),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AvailabilityFacet {
+    pub in_stock: i64,
+    pub out_of_stock: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchFacets {
+    pub categories: Vec<CategoryFacet>,
+    pub price: Vec<PriceFacet>,
+    pub availability: AvailabilityFacet,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub products: Vec<Product>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    pub facets: SearchFacets,
+}
+
 /// Fetch all products from the backend
 pub async fn fetch_products() -> Result<Vec<Product>, ApiError> {
     get("/api/products").await
 }
 
-/// Fetch a single product by ID
-pub async fn fetch_product(id: i32) -> Result<Product, ApiError> {
-    get(&format!("/api/products/{}", id)).await
+/// Fetch a single product by its URL slug
+pub async fn fetch_product(slug: &str) -> Result<Product, ApiError> {
+    get(&format!("/api/products/{}", slug)).await
+}
+
+/// Search the catalog, with the facet counts for the filter sidebar
+pub async fn search_products(query: &SearchQuery) -> Result<SearchResults, ApiError> {
+    let mut endpoint = format!(
+        "/api/products/search?sort={}&limit={}&offset={}",
+        query.sort.param(),
+        SEARCH_PAGE_SIZE,
+        (query.page - 1).max(0) * SEARCH_PAGE_SIZE
+    );
+    if !query.q.trim().is_empty() {
+        endpoint.push_str(&format!("&q={}", urlencoding::encode(query.q.trim())));
+    }
+    if let Some(category) = &query.category {
+        endpoint.push_str(&format!("&category={}", urlencoding::encode(category)));
+    }
+    if let Some(min_price) = query.min_price {
+        endpoint.push_str(&format!("&min_price={}", min_price));
+    }
+    if let Some(max_price) = query.max_price {
+        endpoint.push_str(&format!("&max_price={}", max_price));
+    }
+    if query.in_stock {
+        endpoint.push_str("&in_stock=true");
+    }
+    get(&endpoint).await
 }
@@ -0,0 +1,38 @@
+// Review API (one-click ratings from review request emails)
+
+use super::{get, post, ApiError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewDraft {
+    pub order_id: String,
+    pub rating: Option<i32>,
+    pub body: Option<String>,
+    pub status: Option<String>,
+    pub items: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubmitReviewRequest {
+    pub token: String,
+    pub rating: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReviewResponse {
+    pub id: String,
+    pub rating: i32,
+    pub status: String,
+}
+
+/// Fetch the review draft recorded for a signed review link
+pub async fn fetch_review_draft(token: &str) -> Result<ReviewDraft, ApiError> {
+    get(&format!("/api/reviews/draft?token={}", urlencoding::encode(token))).await
+}
+
+/// Submit the rating with optional review text
+pub async fn submit_review(token: String, rating: i32, body: Option<String>) -> Result<ReviewResponse, ApiError> {
+    post("/api/reviews/submit", &SubmitReviewRequest { token, rating, body }).await
+}
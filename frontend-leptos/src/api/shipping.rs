@@ -0,0 +1,31 @@
+// Shipping API (delivery estimates)
+
+use super::{get, ApiError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeliveryEstimate {
+    pub zip: String,
+    pub handling_days: i32,
+    pub min_transit_days: i32,
+    pub max_transit_days: i32,
+    pub earliest_date: String,
+    pub latest_date: String,
+    pub message: String,
+}
+
+/// Fetch the estimated delivery window for a ZIP code and set of products
+pub async fn fetch_delivery_estimate(zip: &str, product_ids: &[i32]) -> Result<DeliveryEstimate, ApiError> {
+    let ids = product_ids
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    get(&format!(
+        "/api/shipping/estimate?zip={}&product_ids={}",
+        urlencoding::encode(zip.trim()),
+        ids
+    ))
+    .await
+}
@@ -4,7 +4,11 @@ use leptos::*;
 use leptos_meta::*;
 use leptos_router::*;
 
+use crate::api::attribution::capture_from_url;
+use crate::api::experiments::{fetch_assignments, Assignment};
 use crate::components::header::Header;
+use crate::components::content_block::AnnouncementBar;
+use crate::components::content_page::ContentPage;
 use crate::components::footer::Footer;
 use crate::pages::{
     home::HomePage,
@@ -12,6 +16,12 @@ use crate::pages::{
     product::ProductPage,
     cart::CartPage,
     checkout::CheckoutPage,
+    review::ReviewPage,
+    account::AccountPage,
+    content::PageBySlug,
+    blog::{BlogListPage, BlogPostPage},
+    order_tracking::OrderTrackingPage,
+    drop::DropPage,
     not_found::NotFoundPage,
 };
 
@@ -20,6 +30,14 @@ pub fn App() -> impl IntoView {
     // Provide meta context for SEO
     provide_meta_context();
 
+    // Campaign links (?utm_source=...) are remembered for checkout
+    capture_from_url();
+
+    // A/B test variants for this visitor, shared by the components that render them
+    let experiments: Resource<(), Vec<Assignment>> =
+        create_local_resource(|| (), |_| async { fetch_assignments().await.unwrap_or_default() });
+    provide_context(experiments);
+
     view! {
         <Router>
             <div class="app-container">
@@ -29,6 +47,9 @@ pub fn App() -> impl IntoView {
                 <Meta name="description" content="R-Com E-Commerce Platform - Your one-stop shop for quality products"/>
                 <Meta name="viewport" content="width=device-width, initial-scale=1.0"/>
 
+                // Announcement bar managed from the admin CMS
+                <AnnouncementBar/>
+
                 // Header (visible on all pages)
                 <Header/>
 
@@ -42,7 +63,7 @@ pub fn App() -> impl IntoView {
                         <Route path="/catalog" view=CatalogPage/>
 
                         // Individual product page
-                        <Route path="/product/:id" view=ProductPage/>
+                        <Route path="/product/:slug" view=ProductPage/>
 
                         // Shopping cart
                         <Route path="/cart" view=CartPage/>
@@ -50,6 +71,27 @@ pub fn App() -> impl IntoView {
                         // Checkout flow
                         <Route path="/checkout" view=CheckoutPage/>
 
+                        // Review page reached from one-click rating emails
+                        <Route path="/review" view=ReviewPage/>
+
+                        // Customer account (store credit, referrals)
+                        <Route path="/account" view=AccountPage/>
+
+                        // Live order tracking (?email= for guest orders)
+                        <Route path="/orders/:id" view=OrderTrackingPage/>
+
+                        // Waiting room for high-demand drops
+                        <Route path="/drops/:id" view=DropPage/>
+
+                        // Blog
+                        <Route path="/blog" view=BlogListPage/>
+                        <Route path="/blog/:slug" view=BlogPostPage/>
+
+                        // Backend-managed pages linked from the footer, and any other page by slug
+                        <Route path="/shipping" view=|| view! { <ContentPage slug={"shipping".to_string()}/> }/>
+                        <Route path="/returns" view=|| view! { <ContentPage slug={"returns".to_string()}/> }/>
+                        <Route path="/pages/:slug" view=PageBySlug/>
+
                         // 404 Not Found
                         <Route path="/*any" view=NotFoundPage/>
                     </Routes>
@@ -0,0 +1,55 @@
+// Cart totals priced by the backend: subtotal, discount, tax, shipping and total
+
+use leptos::*;
+use crate::{
+    api::cart::price_cart,
+    types::{Cart, CartPrice},
+};
+
+#[component]
+pub fn CartTotals(
+    /// Cart to price; totals are refetched whenever it changes
+    #[prop(into)] cart: Signal<Cart>,
+) -> impl IntoView {
+    let price = create_resource(move || cart.get(), |cart| async move { price_cart(&cart).await });
+
+    view! {
+        <Suspense fallback=|| view! { <p class="totals-loading">"Calculating totals..."</p> }>
+            {move || price.get().map(|result| match result {
+                Ok(price) => view! {
+                    <div class="summary-row">
+                        <span>"Subtotal:"</span>
+                        <span>{CartPrice::format(price.subtotal)}</span>
+                    </div>
+
+                    {(price.discount > 0).then(|| view! {
+                        <div class="summary-row">
+                            <span>"Discount:"</span>
+                            <span>{format!("-{}", CartPrice::format(price.discount))}</span>
+                        </div>
+                    })}
+
+                    <div class="summary-row">
+                        <span>{format!("Tax ({}%):", (price.tax_rate * 1000.0).round() / 10.0)}</span>
+                        <span>{CartPrice::format(price.tax)}</span>
+                    </div>
+
+                    {(price.shipping > 0).then(|| view! {
+                        <div class="summary-row">
+                            <span>"Shipping:"</span>
+                            <span>{CartPrice::format(price.shipping)}</span>
+                        </div>
+                    })}
+
+                    <div class="summary-row summary-total">
+                        <span>"Total:"</span>
+                        <span>{CartPrice::format(price.total)}</span>
+                    </div>
+                }.into_view(),
+                Err(e) => view! {
+                    <p class="totals-error">{format!("Could not price your cart: {}", e.user_message())}</p>
+                }.into_view(),
+            })}
+        </Suspense>
+    }
+}
@@ -0,0 +1,52 @@
+// CMS-driven content: announcement bar and rendered content blocks
+
+use leptos::*;
+use crate::api::content::{fetch_content_blocks, ContentBlock};
+
+/// Renders one content block's HTML, with its title as a heading when set
+#[component]
+pub fn ContentBlockView(block: ContentBlock) -> impl IntoView {
+    view! {
+        <div class="content-block" data-key=block.key.clone()>
+            {block.title.map(|title| view! { <h2 class="section-title">{title}</h2> })}
+            <div class="content-block-body" inner_html=block.html></div>
+        </div>
+    }
+}
+
+/// Site-wide announcement bar; renders nothing when no announcement is live
+#[component]
+pub fn AnnouncementBar() -> impl IntoView {
+    let blocks = create_resource(|| (), |_| async move {
+        fetch_content_blocks("announcement").await.unwrap_or_default()
+    });
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || blocks.get().and_then(|blocks| blocks.into_iter().next()).map(|block| view! {
+                <div class="announcement-bar" inner_html=block.html></div>
+            })}
+        </Suspense>
+
+        <style>
+            {r#"
+            .announcement-bar {
+                background: var(--color-gray-900);
+                color: white;
+                text-align: center;
+                padding: var(--spacing-sm) var(--spacing-md);
+                font-size: 0.9rem;
+            }
+
+            .announcement-bar p {
+                margin: 0;
+            }
+
+            .announcement-bar a {
+                color: inherit;
+                text-decoration: underline;
+            }
+            "#}
+        </style>
+    }
+}
@@ -0,0 +1,51 @@
+// Generic content page: renders a backend-managed page (sanitized HTML) by slug
+
+use leptos::*;
+use leptos_meta::*;
+use crate::{api::pages::fetch_page, pages::not_found::NotFoundPage};
+
+#[component]
+pub fn ContentPage(
+    /// Page slug, e.g. "shipping"
+    #[prop(into)] slug: Signal<String>,
+) -> impl IntoView {
+    let page = create_resource(move || slug.get(), |slug| async move { fetch_page(&slug).await });
+
+    view! {
+        <Suspense fallback=move || view! {
+            <div class="loading">
+                <div class="spinner"></div>
+            </div>
+        }>
+            {move || page.get().map(|result| match result {
+                Ok(page) => view! {
+                    <Title text=format!("{} - R-Com Store", page.title)/>
+                    {page.meta_description.clone().map(|description| view! { <Meta name="description" content=description/> })}
+                    <article class="content-page container">
+                        <h1 class="page-title">{page.title.clone()}</h1>
+                        <div class="content-page-body" inner_html=page.html.clone()></div>
+                    </article>
+                }.into_view(),
+                Err(e) if e.status == 404 => view! { <NotFoundPage/> }.into_view(),
+                Err(e) => view! {
+                    <div class="error-state container">
+                        <p>"Failed to load page: " {e.user_message()}</p>
+                    </div>
+                }.into_view(),
+            })}
+        </Suspense>
+
+        <style>
+            {r#"
+            .content-page {
+                max-width: 800px;
+                padding: var(--spacing-2xl) 0;
+            }
+
+            .content-page-body h2 {
+                margin-top: var(--spacing-xl);
+            }
+            "#}
+        </style>
+    }
+}
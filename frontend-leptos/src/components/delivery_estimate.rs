@@ -0,0 +1,98 @@
+// Delivery estimate component ("Get it by ...") with a ZIP code field
+
+use leptos::*;
+use crate::{
+    api::shipping::fetch_delivery_estimate,
+    utils::{get_local_storage, set_local_storage},
+};
+
+/// localStorage key remembering the shopper's ZIP code across pages
+const ZIP_STORAGE_KEY: &str = "delivery_zip";
+
+#[component]
+pub fn DeliveryEstimate(
+    /// Products being shipped: a single product on the product page, the whole cart on the cart page
+    #[prop(into)] product_ids: Signal<Vec<i32>>,
+) -> impl IntoView {
+    let (zip, set_zip) = create_signal(get_local_storage(ZIP_STORAGE_KEY).unwrap_or_default());
+    let (zip_input, set_zip_input) = create_signal(zip.get_untracked());
+
+    // Refetch whenever the ZIP code or the products change
+    let estimate = create_resource(
+        move || (zip.get(), product_ids.get()),
+        |(zip, ids)| async move {
+            if zip.trim().is_empty() {
+                return None;
+            }
+            fetch_delivery_estimate(&zip, &ids).await.ok()
+        },
+    );
+
+    let apply_zip = move |_| {
+        let value = zip_input.get().trim().to_string();
+        let _ = set_local_storage(ZIP_STORAGE_KEY, &value);
+        set_zip(value);
+    };
+
+    view! {
+        <div class="delivery-estimate">
+            <Suspense fallback=|| view! { <p class="estimate-message">"Checking delivery dates..."</p> }>
+                {move || {
+                    estimate.get().map(|result| match result {
+                        Some(e) => view! {
+                            <p class="estimate-message">
+                                <strong>{e.message.clone()}</strong>
+                                <span class="estimate-zip-label">{format!(" to {}", e.zip)}</span>
+                            </p>
+                        }.into_view(),
+                        None => view! {
+                            <p class="estimate-message">"Enter your ZIP code to see delivery dates"</p>
+                        }.into_view(),
+                    })
+                }}
+            </Suspense>
+
+            <div class="estimate-zip">
+                <input
+                    type="text"
+                    placeholder="ZIP code"
+                    maxlength="10"
+                    value=zip_input
+                    on:input=move |ev| set_zip_input(event_target_value(&ev))
+                />
+                <button class="btn btn-sm" on:click=apply_zip>"Update"</button>
+            </div>
+
+            <style>
+                {r#"
+                .delivery-estimate {
+                    display: flex;
+                    flex-direction: column;
+                    gap: var(--spacing-sm);
+                    padding: var(--spacing-md);
+                    margin-bottom: var(--spacing-lg);
+                    background: var(--color-gray-100);
+                    border-radius: var(--radius-md);
+                }
+
+                .estimate-message {
+                    margin: 0;
+                }
+
+                .estimate-zip-label {
+                    color: var(--color-gray-600);
+                }
+
+                .estimate-zip {
+                    display: flex;
+                    gap: var(--spacing-sm);
+                }
+
+                .estimate-zip input {
+                    max-width: 140px;
+                }
+                "#}
+            </style>
+        </div>
+    }
+}
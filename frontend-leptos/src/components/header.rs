@@ -25,6 +25,8 @@ pub fn Header() -> impl IntoView {
                     <div class="nav-links">
                         <A href="/" class="nav-link">"Home"</A>
                         <A href="/catalog" class="nav-link">"Shop"</A>
+                        <A href="/blog" class="nav-link">"Blog"</A>
+                        <A href="/account" class="nav-link">"Account"</A>
                         <A href="/cart" class="nav-link cart-link">
                             "Cart "
                             <Show
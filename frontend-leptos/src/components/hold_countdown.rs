@@ -0,0 +1,80 @@
+// Countdown of how long checkout holds the cart's stock. Ticks locally every second and re-checks
+// the server now and then, so a release or completed order shows up without a reload
+
+use leptos::*;
+use std::time::Duration;
+use crate::api::checkout::{fetch_hold, CheckoutHold};
+
+const REFRESH_SECS: i64 = 30;
+
+fn format_remaining(secs: i64) -> String {
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+#[component]
+pub fn HoldCountdown(
+    /// Order id returned by create-payment-intent
+    order_id: String,
+) -> impl IntoView {
+    let (hold, set_hold) = create_signal(Option::<CheckoutHold>::None);
+
+    let refresh = move |order_id: String| {
+        spawn_local(async move {
+            match fetch_hold(&order_id).await {
+                Ok(current) => set_hold(Some(current)),
+                Err(e) => log::error!("Failed to load checkout hold: {}", e.message),
+            }
+        });
+    };
+    refresh(order_id.clone());
+
+    let tick = move || {
+        let Some(mut current) = hold.get_untracked() else { return };
+        if current.status != "held" {
+            return;
+        }
+        current.seconds_remaining = (current.seconds_remaining - 1).max(0);
+        // Ask the server at the deadline and every REFRESH_SECS in between
+        let due = current.seconds_remaining == 0 || current.seconds_remaining % REFRESH_SECS == 0;
+        set_hold(Some(current));
+        if due {
+            refresh(order_id.clone());
+        }
+    };
+    if let Ok(handle) = set_interval_with_handle(tick, Duration::from_secs(1)) {
+        on_cleanup(move || handle.clear());
+    }
+
+    view! {
+        {move || hold.get().and_then(|h| match h.status.as_str() {
+            "held" => Some(view! {
+                <div class="hold-countdown" class:urgent=h.seconds_remaining < 120>
+                    "Your items are reserved for "
+                    <strong>{format_remaining(h.seconds_remaining)}</strong>
+                </div>
+            }.into_view()),
+            "expired" | "released" => Some(view! {
+                <div class="hold-countdown urgent">
+                    "Your reservation expired and the items were released. Place the order again to reserve what's still in stock."
+                </div>
+            }.into_view()),
+            _ => None,
+        })}
+
+        <style>
+            {r#"
+            .hold-countdown {
+                padding: var(--spacing-md);
+                margin-bottom: var(--spacing-md);
+                border: 1px solid var(--color-gray-200);
+                border-radius: var(--radius-md);
+            }
+
+            .hold-countdown.urgent {
+                color: var(--color-error);
+                border-color: var(--color-error);
+            }
+            "#}
+        </style>
+    }
+}
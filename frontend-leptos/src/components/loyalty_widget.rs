@@ -0,0 +1,81 @@
+// Loyalty points widget: balance, what it's worth, and an optional "use my points" toggle
+
+use leptos::*;
+use crate::api::account::{fetch_loyalty, load_token};
+
+#[component]
+pub fn LoyaltyWidget(
+    /// When set, shows a checkbox for redeeming the balance at checkout; holds the points to redeem
+    #[prop(optional)] redeem: Option<RwSignal<Option<i64>>>,
+    /// Also list the ledger history (account page)
+    #[prop(optional)] show_history: bool,
+) -> impl IntoView {
+    // Guests have no points; skip the request entirely
+    let account = create_resource(|| (), |_| async move {
+        if load_token().is_some() { fetch_loyalty().await.ok() } else { None }
+    });
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || account.get().flatten().map(|account| view! {
+                <div class="loyalty-widget">
+                    <p class="loyalty-balance">
+                        <strong>{account.balance} " points"</strong>
+                        {format!(" · worth ${:.2}", account.balance_value as f64 / 100.0)}
+                    </p>
+                    <p class="help-text">
+                        {format!(
+                            "Earn {} point(s) per $1 spent · {} points = $1 off",
+                            account.points_per_dollar, account.points_per_dollar_off
+                        )}
+                    </p>
+
+                    {redeem.filter(|_| account.balance_value > 0).map(|redeem| {
+                        let balance = account.balance;
+                        view! {
+                            <label class="checkbox-label">
+                                <input
+                                    type="checkbox"
+                                    prop:checked=move || redeem.get().is_some()
+                                    on:change=move |ev| redeem.set(event_target_checked(&ev).then_some(balance))
+                                />
+                                " Use my points on this order"
+                            </label>
+                        }
+                    })}
+
+                    {show_history.then(|| view! {
+                        <ul class="loyalty-history">
+                            {account.entries.into_iter().map(|entry| view! {
+                                <li>
+                                    <span class="loyalty-points">{format!("{:+}", entry.points)}</span>
+                                    " · " {entry.reason}
+                                    {entry.note.map(|note| format!(" · {}", note))}
+                                </li>
+                            }).collect_view()}
+                        </ul>
+                    })}
+                </div>
+            })}
+
+            <style>
+                {r#"
+                .loyalty-widget {
+                    padding: var(--spacing-md);
+                    margin-bottom: var(--spacing-lg);
+                    background: var(--color-gray-100);
+                    border-radius: var(--radius-md);
+                }
+
+                .loyalty-balance {
+                    margin: 0 0 var(--spacing-xs);
+                }
+
+                .loyalty-points {
+                    font-weight: 600;
+                }
+                "#}
+            </style>
+        </Suspense>
+    }
+}
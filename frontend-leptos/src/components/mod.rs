@@ -3,3 +3,11 @@
 pub mod header;
 pub mod footer;
 pub mod product_card;
+pub mod product_picture;
+pub mod delivery_estimate;
+pub mod loyalty_widget;
+pub mod content_block;
+pub mod content_page;
+pub mod cart_totals;
+pub mod waiting_room;
+pub mod hold_countdown;
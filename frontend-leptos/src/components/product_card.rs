@@ -2,27 +2,46 @@
 
 use leptos::*;
 use leptos_router::*;
+use crate::api::experiments::{record_exposure, Assignment};
+use crate::components::product_picture::ProductPicture;
 use crate::types::Product;
 
 #[component]
 pub fn ProductCard(product: Product) -> impl IntoView {
     let product_clone = product.clone();
 
+    // Title and price badge variants of running A/B tests; showing one logs the exposure
+    let experiments = use_context::<Resource<(), Vec<Assignment>>>();
+    let product_id = product.id;
+    let assigned = move |kind: &str| {
+        let assignment = experiments
+            .and_then(|r| r.get())
+            .unwrap_or_default()
+            .into_iter()
+            .find(|a| a.kind == kind && a.applies_to(product_id))?;
+        record_exposure(&assignment.experiment);
+        Some(assignment)
+    };
+    let title = {
+        let name = product.name.clone();
+        move || assigned("product_title").and_then(|a| a.text("title")).unwrap_or_else(|| name.clone())
+    };
+    let badge = move || assigned("price_badge").and_then(|a| a.text("badge"));
+
     view! {
         <div class="product-card card">
-            <A href=format!("/product/{}", product.id) class="product-link">
+            <A href=format!("/product/{}", product.slug) class="product-link">
                 // Product image
                 <div class="product-image">
-                    <img
-                        src={product.image_url()}
-                        alt={product.name.clone()}
-                        loading="lazy"
+                    <ProductPicture
+                        product=product.clone()
+                        sizes="(max-width: 640px) 100vw, (max-width: 1024px) 50vw, 320px"
                     />
                 </div>
 
                 // Product details
                 <div class="product-details">
-                    <h3 class="product-name">{product.name.clone()}</h3>
+                    <h3 class="product-name">{title}</h3>
 
                     <Show
                         when=move || product_clone.description.is_some()
@@ -39,6 +58,7 @@ pub fn ProductCard(product: Product) -> impl IntoView {
                     // Price and stock
                     <div class="product-footer">
                         <span class="price">{product.formatted_price()}</span>
+                        {move || badge().map(|text| view! { <span class="badge badge-primary">{text}</span> })}
                         <span class={format!("badge {}", product.stock_status_class())}>
                             {product.stock_status()}
                         </span>
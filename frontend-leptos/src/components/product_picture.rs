@@ -0,0 +1,41 @@
+// Responsive product image: AVIF and WebP sources with a JPEG/PNG fallback, so the browser
+// picks the smallest file for the slot it is shown in
+
+use leptos::*;
+use crate::types::Product;
+
+#[component]
+pub fn ProductPicture(
+    product: Product,
+    /// Rendered width of the image, for choosing a srcset entry (e.g. "(max-width: 640px) 100vw, 300px")
+    sizes: &'static str,
+    /// Load immediately instead of when scrolled near (for above-the-fold images)
+    #[prop(optional)] eager: bool,
+) -> impl IntoView {
+    let loading = if eager { "eager" } else { "lazy" };
+    match product.main_image().cloned() {
+        Some(image) => {
+            let alt = if image.alt.is_empty() { product.name.clone() } else { image.alt.clone() };
+            // display: contents keeps the container's `img` sizing rules working
+            view! {
+                <picture style="display: contents">
+                    {image.sources.into_iter().map(|source| view! {
+                        <source type=source.mime_type srcset=source.srcset sizes=sizes />
+                    }).collect_view()}
+                    <img
+                        src=image.src
+                        srcset=image.srcset
+                        sizes=sizes
+                        width=image.width
+                        height=image.height
+                        alt=alt
+                        loading=loading
+                    />
+                </picture>
+            }.into_view()
+        }
+        None => view! {
+            <img src=product.image_url() alt=product.name.clone() loading=loading />
+        }.into_view(),
+    }
+}
@@ -0,0 +1,114 @@
+// Waiting room for a high-demand drop: joins the queue, shows the shopper's place and sends them
+// to checkout as soon as they are admitted
+
+use leptos::*;
+use leptos_router::*;
+use std::time::Duration;
+use crate::api::drops::{join_queue, queue_status, QueueStatus};
+
+const POLL_SECS: u64 = 5; // The server's poll_after_secs
+
+fn format_wait(secs: i64) -> String {
+    match secs {
+        s if s < 60 => "less than a minute".to_string(),
+        s if s < 3600 => format!("about {} min", (s + 59) / 60),
+        s => format!("about {} h {} min", s / 3600, (s % 3600) / 60),
+    }
+}
+
+#[component]
+pub fn WaitingRoom(
+    /// Drop whose queue to join
+    drop_id: String,
+) -> impl IntoView {
+    let navigate = use_navigate();
+    let (status, set_status) = create_signal(Option::<QueueStatus>::None);
+    let (error, set_error) = create_signal(Option::<String>::None);
+
+    // Join once, then poll at the pace the server asks for
+    {
+        let drop_id = drop_id.clone();
+        spawn_local(async move {
+            match join_queue(&drop_id).await {
+                Ok(joined) => set_status(Some(joined)),
+                Err(e) => set_error(Some(e.user_message())),
+            }
+        });
+    }
+    let poll = {
+        let drop_id = drop_id.clone();
+        move || {
+            let drop_id = drop_id.clone();
+            let Some(token) = status.get_untracked().map(|s| s.token) else { return };
+            spawn_local(async move {
+                match queue_status(&drop_id, &token).await {
+                    Ok(current) => {
+                        set_status(Some(current));
+                        set_error(None);
+                    }
+                    Err(e) => set_error(Some(e.user_message())),
+                }
+            });
+        }
+    };
+    if let Ok(handle) = set_interval_with_handle(poll, Duration::from_secs(POLL_SECS)) {
+        on_cleanup(move || handle.clear());
+    }
+
+    // Admitted shoppers go straight to checkout
+    create_effect(move |_| {
+        if status.get().is_some_and(|s| s.status == "admitted" || s.status == "ended") {
+            navigate("/checkout", Default::default());
+        }
+    });
+
+    view! {
+        <div class="waiting-room card">
+            {move || match status.get() {
+                None if error.get().is_some() => view! {
+                    <p class="error-message">{error.get()}</p>
+                }.into_view(),
+                None => view! {
+                    <div class="loading">
+                        <div class="spinner"></div>
+                        <p>"Joining the queue..."</p>
+                    </div>
+                }.into_view(),
+                Some(s) if s.status == "not_started" => view! {
+                    <p class="queue-position">"You're number " <strong>{s.position}</strong> " in line"</p>
+                    <p>"The drop hasn't started yet. Keep this page open; checkout opens here automatically."</p>
+                }.into_view(),
+                Some(s) if s.status == "expired" => view! {
+                    <p>"Your checkout window has passed."</p>
+                    <button class="btn btn-primary" on:click=move |_| {
+                        let _ = web_sys::window().map(|w| w.location().reload());
+                    }>"Join the queue again"</button>
+                }.into_view(),
+                Some(s) if s.status == "waiting" => view! {
+                    <p class="queue-position">
+                        {if s.ahead == 0 { "You're next".to_string() } else { format!("{} people ahead of you", s.ahead) }}
+                    </p>
+                    {s.estimated_wait_secs.map(|secs| view! {
+                        <p class="help-text">"Estimated wait: " {format_wait(secs)}</p>
+                    })}
+                    <p class="help-text">"Keep this page open to hold your place. You'll go to checkout automatically."</p>
+                }.into_view(),
+                Some(_) => view! { <p>"Taking you to checkout..."</p> }.into_view(),
+            }}
+
+            <style>
+                {r#"
+                .waiting-room {
+                    text-align: center;
+                    padding: var(--spacing-xl);
+                }
+
+                .queue-position {
+                    font-size: 1.5rem;
+                    margin-bottom: var(--spacing-md);
+                }
+                "#}
+            </style>
+        </div>
+    }
+}
@@ -0,0 +1,145 @@
+// Customer account page: login, store credit balance, loyalty points and referral link
+
+use leptos::*;
+use crate::api::account::{fetch_referrals, fetch_store_credit, load_token, login, logout};
+use crate::components::loyalty_widget::LoyaltyWidget;
+
+fn format_cents(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}${:.2}", sign, cents.abs() as f64 / 100.0)
+}
+
+#[component]
+pub fn AccountPage() -> impl IntoView {
+    let (logged_in, set_logged_in) = create_signal(load_token().is_some());
+
+    // Login form state
+    let (email, set_email) = create_signal(String::new());
+    let (password, set_password) = create_signal(String::new());
+    let (is_processing, set_is_processing) = create_signal(false);
+    let (error_message, set_error_message) = create_signal(Option::<String>::None);
+
+    let credit = create_resource(logged_in, |logged_in| async move {
+        if logged_in { fetch_store_credit().await.ok() } else { None }
+    });
+    let referrals = create_resource(logged_in, |logged_in| async move {
+        if logged_in { fetch_referrals().await.ok() } else { None }
+    });
+
+    let handle_login = move |_| {
+        set_is_processing(true);
+        set_error_message(None);
+        spawn_local(async move {
+            match login(email.get_untracked(), password.get_untracked()).await {
+                Ok(()) => set_logged_in(true),
+                Err(e) => {
+                    log::error!("Login error: {}", e);
+                    set_error_message(Some("Invalid email or password".to_string()));
+                }
+            }
+            set_is_processing(false);
+        });
+    };
+
+    let handle_logout = move |_| {
+        logout();
+        set_logged_in(false);
+    };
+
+    view! {
+        <div class="account-page container">
+            <h1 class="page-title">"My Account"</h1>
+
+            <Show
+                when=move || logged_in.get()
+                fallback=move || view! {
+                    <div class="card account-login">
+                        <h2>"Log In"</h2>
+                        <form on:submit=|e| e.prevent_default()>
+                            <div class="form-group">
+                                <label>"Email"</label>
+                                <input
+                                    type="email"
+                                    prop:value=email
+                                    on:input=move |ev| set_email(event_target_value(&ev))
+                                    required
+                                />
+                            </div>
+                            <div class="form-group">
+                                <label>"Password"</label>
+                                <input
+                                    type="password"
+                                    prop:value=password
+                                    on:input=move |ev| set_password(event_target_value(&ev))
+                                    required
+                                />
+                            </div>
+
+                            <Show when=move || error_message.get().is_some()>
+                                <div class="error-message">
+                                    {move || error_message.get()}
+                                </div>
+                            </Show>
+
+                            <button
+                                type="button"
+                                class="btn btn-primary"
+                                on:click=handle_login
+                                disabled=move || is_processing.get()
+                            >
+                                "Log In"
+                            </button>
+                        </form>
+                    </div>
+                }
+            >
+                // Store credit balance and history
+                <div class="card store-credit">
+                    <h2>"Store Credit"</h2>
+                    <Suspense fallback=|| view! { <p>"Loading..."</p> }>
+                        {move || credit.get().flatten().map(|account| view! {
+                            <p class="credit-balance">
+                                "Available: " <strong>{format_cents(account.balance)}</strong>
+                            </p>
+                            <p class="help-text">"Your credit is applied automatically at checkout."</p>
+                            <ul class="credit-history">
+                                {account.entries.into_iter().map(|entry| view! {
+                                    <li>
+                                        <span class="credit-amount">{format_cents(entry.amount)}</span>
+                                        " · " {entry.reason}
+                                        {entry.note.map(|note| format!(" · {}", note))}
+                                    </li>
+                                }).collect_view()}
+                            </ul>
+                        })}
+                    </Suspense>
+                </div>
+
+                // Loyalty points balance and history
+                <div class="card loyalty">
+                    <h2>"Loyalty Points"</h2>
+                    <LoyaltyWidget show_history=true/>
+                </div>
+
+                // Referral program
+                <div class="card referrals">
+                    <h2>"Refer a Friend"</h2>
+                    <Suspense fallback=|| view! { <p>"Loading..."</p> }>
+                        {move || referrals.get().flatten().map(|stats| view! {
+                            <p>"Share your link. You'll earn a reward when a friend places their first order."</p>
+                            <input type="text" readonly prop:value=stats.referral_link.clone()/>
+                            <p class="help-text">
+                                {stats.visits} " visits · " {stats.successful_referrals} " successful referrals · "
+                                {format_cents(stats.total_rewards)} " earned"
+                            </p>
+                        })}
+                    </Suspense>
+                </div>
+
+                <button type="button" class="btn btn-secondary" on:click=handle_logout>
+                    "Log Out"
+                </button>
+            </Show>
+        </div>
+    }
+}
@@ -0,0 +1,205 @@
+// Blog list and post pages
+
+use leptos::*;
+use leptos_meta::*;
+use leptos_router::*;
+use crate::{
+    api::blog::{fetch_post, fetch_posts, rss_url, PostSummary, POSTS_PER_PAGE},
+    pages::not_found::NotFoundPage,
+};
+
+/// "2023-06-01T10:00:00Z" -> "June 1, 2023"
+fn format_date(value: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|date| date.format("%B %-d, %Y").to_string())
+        .unwrap_or_default()
+}
+
+#[component]
+fn PostCard(post: PostSummary) -> impl IntoView {
+    let href = format!("/blog/{}", post.slug);
+    view! {
+        <article class="post-card card">
+            {post.cover_image_url.map(|url| view! { <img class="post-cover" src=url alt=""/> })}
+            <h2><A href=href.clone()>{post.title}</A></h2>
+            <p class="post-meta">
+                {post.published_at.as_deref().map(format_date)}
+                {post.author.map(|author| format!(" · {}", author))}
+            </p>
+            {post.excerpt.map(|excerpt| view! { <p>{excerpt}</p> })}
+            <p class="post-tags">
+                {post.tags.into_iter().map(|tag| view! {
+                    <A href=format!("/blog?tag={}", urlencoding::encode(&tag))>{format!("#{}", tag)}</A>
+                }).collect_view()}
+            </p>
+        </article>
+    }
+}
+
+#[component]
+pub fn BlogListPage() -> impl IntoView {
+    let query = use_query_map();
+    let page = move || query.with(|q| q.get("page").and_then(|p| p.parse::<i64>().ok()).unwrap_or(1).max(1));
+    let tag = move || query.with(|q| q.get("tag").cloned().filter(|t| !t.is_empty()));
+
+    let posts = create_resource(move || (page(), tag()), |(page, tag)| async move { fetch_posts(page, tag).await });
+
+    let page_href = move |page: i64| match tag() {
+        Some(tag) => format!("/blog?tag={}&page={}", urlencoding::encode(&tag), page),
+        None => format!("/blog?page={}", page),
+    };
+
+    view! {
+        <Title text="Blog - R-Com Store"/>
+        <Meta name="description" content="News, guides and tips from R-Com Store"/>
+        <Link rel="alternate" type_="application/rss+xml" title="R-Com Store Blog" href=rss_url()/>
+
+        <div class="blog-page container">
+            <h1 class="page-title">
+                {move || tag().map(|tag| format!("Blog: #{}", tag)).unwrap_or_else(|| "Blog".to_string())}
+            </h1>
+
+            <Suspense fallback=move || view! {
+                <div class="loading">
+                    <div class="spinner"></div>
+                </div>
+            }>
+                {move || posts.get().map(|result| match result {
+                    Ok(list) if list.posts.is_empty() => view! {
+                        <div class="empty-state"><p>"No posts yet."</p></div>
+                    }.into_view(),
+                    Ok(list) => {
+                        let current = list.offset / POSTS_PER_PAGE + 1;
+                        let has_next = list.offset + list.limit < list.total;
+                        view! {
+                            <div class="post-list">
+                                {list.posts.into_iter().map(|post| view! { <PostCard post=post/> }).collect_view()}
+                            </div>
+                            <nav class="pagination">
+                                {(current > 1).then(|| view! { <A href=page_href(current - 1)>"← Newer posts"</A> })}
+                                {has_next.then(|| view! { <A href=page_href(current + 1)>"Older posts →"</A> })}
+                            </nav>
+                        }.into_view()
+                    }
+                    Err(e) => view! {
+                        <div class="error-state"><p>"Failed to load posts: " {e.user_message()}</p></div>
+                    }.into_view(),
+                })}
+            </Suspense>
+
+            <p class="rss-link"><a href=rss_url()>"RSS feed"</a></p>
+
+            <style>
+                {r#"
+                .blog-page {
+                    max-width: 800px;
+                    padding: var(--spacing-2xl) 0;
+                }
+
+                .post-card {
+                    margin-bottom: var(--spacing-lg);
+                }
+
+                .post-cover {
+                    width: 100%;
+                    border-radius: var(--radius-md);
+                    margin-bottom: var(--spacing-md);
+                }
+
+                .post-meta {
+                    color: var(--color-gray-500);
+                }
+
+                .post-tags a {
+                    margin-right: var(--spacing-sm);
+                }
+
+                .pagination {
+                    display: flex;
+                    justify-content: space-between;
+                    margin-top: var(--spacing-xl);
+                }
+                "#}
+            </style>
+        </div>
+    }
+}
+
+#[component]
+pub fn BlogPostPage() -> impl IntoView {
+    let params = use_params_map();
+    let slug = move || params.with(|p| p.get("slug").cloned().unwrap_or_default());
+
+    let post = create_resource(slug, |slug| async move { fetch_post(&slug).await });
+
+    view! {
+        <Link rel="alternate" type_="application/rss+xml" title="R-Com Store Blog" href=rss_url()/>
+
+        <Suspense fallback=move || view! {
+            <div class="loading">
+                <div class="spinner"></div>
+            </div>
+        }>
+            {move || post.get().map(|result| match result {
+                Ok(post) => {
+                    let summary = post.summary;
+                    view! {
+                        // SEO and social sharing tags
+                        <Title text=format!("{} - R-Com Store Blog", summary.title)/>
+                        <Meta property="og:type" content="article"/>
+                        <Meta property="og:title" content=summary.title.clone()/>
+                        {summary.excerpt.clone().map(|excerpt| view! {
+                            <Meta name="description" content=excerpt.clone()/>
+                            <Meta property="og:description" content=excerpt/>
+                        })}
+                        {summary.cover_image_url.clone().map(|url| view! { <Meta property="og:image" content=url/> })}
+                        {summary.published_at.clone().map(|at| view! { <Meta property="article:published_time" content=at/> })}
+
+                        <article class="blog-post container">
+                            <A href="/blog" class="back-link">"← All posts"</A>
+                            {summary.cover_image_url.map(|url| view! { <img class="post-cover" src=url alt=""/> })}
+                            <h1>{summary.title}</h1>
+                            <p class="post-meta">
+                                {summary.published_at.as_deref().map(format_date)}
+                                {summary.author.map(|author| format!(" · {}", author))}
+                            </p>
+                            <div class="post-body" inner_html=post.html></div>
+                            <p class="post-tags">
+                                {summary.tags.into_iter().map(|tag| view! {
+                                    <A href=format!("/blog?tag={}", urlencoding::encode(&tag))>{format!("#{}", tag)}</A>
+                                }).collect_view()}
+                            </p>
+                        </article>
+                    }.into_view()
+                }
+                Err(e) if e.status == 404 => view! { <NotFoundPage/> }.into_view(),
+                Err(e) => view! {
+                    <div class="error-state container"><p>"Failed to load post: " {e.user_message()}</p></div>
+                }.into_view(),
+            })}
+        </Suspense>
+
+        <style>
+            {r#"
+            .blog-post {
+                max-width: 800px;
+                padding: var(--spacing-2xl) 0;
+            }
+
+            .blog-post .post-meta {
+                color: var(--color-gray-500);
+            }
+
+            .blog-post .post-cover {
+                width: 100%;
+                border-radius: var(--radius-md);
+                margin: var(--spacing-md) 0;
+            }
+
+            .blog-post .post-tags a {
+                margin-right: var(--spacing-sm);
+            }
+            "#}
+        </style>
+    }
+}
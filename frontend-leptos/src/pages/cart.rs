@@ -4,6 +4,7 @@ use leptos::*;
 use leptos_router::*;
 use crate::{
     api::cart::{load_cart, save_cart, update_cart_quantity, remove_from_cart},
+    components::{cart_totals::CartTotals, delivery_estimate::DeliveryEstimate, product_picture::ProductPicture},
     types::Cart,
 };
 
@@ -52,9 +53,9 @@ pub fn CartPage() -> impl IntoView {
                                     <div class="cart-item card">
                                         // Product image
                                         <div class="item-image">
-                                            <img
-                                                src={item.product.image_url()}
-                                                alt={item.product.name.clone()}
+                                            <ProductPicture
+                                                product=item.product.clone()
+                                                sizes="100px"
                                             />
                                         </div>
 
@@ -108,20 +109,12 @@ pub fn CartPage() -> impl IntoView {
                     <div class="cart-summary card">
                         <h3>"Order Summary"</h3>
 
-                        <div class="summary-row">
-                            <span>"Subtotal:"</span>
-                            <span>{move || cart.get().formatted_subtotal()}</span>
-                        </div>
+                        <CartTotals cart=cart />
 
-                        <div class="summary-row">
-                            <span>"Tax (8%):"</span>
-                            <span>{move || cart.get().formatted_tax()}</span>
-                        </div>
-
-                        <div class="summary-row summary-total">
-                            <span>"Total:"</span>
-                            <span>{move || cart.get().formatted_total()}</span>
-                        </div>
+                        // Estimated delivery date for everything in the cart
+                        <DeliveryEstimate product_ids=Signal::derive(move || {
+                            cart.get().items.iter().map(|item| item.product.id).collect::<Vec<_>>()
+                        }) />
 
                         <A href="/checkout" class="btn btn-primary btn-lg checkout-btn">
                             "Proceed to Checkout"
@@ -2,52 +2,28 @@
 
 use leptos::*;
 use crate::{
-    api::products::fetch_products,
+    api::products::{search_products, SearchQuery, SEARCH_PAGE_SIZE},
     components::product_card::ProductCard,
-    types::{Product, product::ProductSortOrder},
+    types::product::ProductSortOrder,
 };
 
 #[component]
 pub fn CatalogPage() -> impl IntoView {
-    // Fetch all products
-    let products = create_resource(
-        || (),
-        |_| async move { fetch_products().await },
-    );
-
-    // Sort order state
-    let (sort_order, set_sort_order) = create_signal(ProductSortOrder::Newest);
-
-    // Search/filter state
-    let (search_query, set_search_query) = create_signal(String::new());
-
-    // Filtered and sorted products
-    let filtered_products = move || {
-        products.get().and_then(|result| {
-            result.ok().map(|mut prods| {
-                // Filter by search query
-                let query = search_query.get().to_lowercase();
-                if !query.is_empty() {
-                    prods.retain(|p| {
-                        p.name.to_lowercase().contains(&query)
-                            || p.description.as_ref().map_or(false, |d| d.to_lowercase().contains(&query))
-                    });
-                }
-
-                // Sort products
-                match sort_order.get() {
-                    ProductSortOrder::NameAsc => prods.sort_by(|a, b| a.name.cmp(&b.name)),
-                    ProductSortOrder::NameDesc => prods.sort_by(|a, b| b.name.cmp(&a.name)),
-                    ProductSortOrder::PriceAsc => prods.sort_by(|a, b| a.price.partial_cmp(&b.price).unwrap()),
-                    ProductSortOrder::PriceDesc => prods.sort_by(|a, b| b.price.partial_cmp(&a.price).unwrap()),
-                    ProductSortOrder::Newest => prods.reverse(),
-                }
-
-                prods
-            })
+    // Search and filter state; any change goes back to the first page
+    let (query, set_query) = create_signal(SearchQuery { page: 1, ..Default::default() });
+    let update = move |change: &dyn Fn(&mut SearchQuery)| {
+        set_query.update(|q| {
+            change(q);
+            q.page = 1;
         })
     };
 
+    // Matching products and facet counts, computed by the backend
+    let results = create_resource(
+        move || query.get(),
+        |query| async move { search_products(&query).await },
+    );
+
     view! {
         <div class="catalog-page container">
             <h1 class="page-title">"Shop All Products"</h1>
@@ -59,8 +35,11 @@ pub fn CatalogPage() -> impl IntoView {
                     <input
                         type="text"
                         placeholder="Search products..."
-                        value=search_query
-                        on:input=move |ev| set_search_query(event_target_value(&ev))
+                        prop:value=move || query.with(|q| q.q.clone())
+                        on:input=move |ev| {
+                            let value = event_target_value(&ev);
+                            update(&|q| q.q = value.clone());
+                        }
                     />
                 </div>
 
@@ -68,16 +47,17 @@ pub fn CatalogPage() -> impl IntoView {
                 <div class="sort-controls">
                     <label>"Sort by:"</label>
                     <select on:change=move |ev| {
-                        let value = event_target_value(&ev);
-                        let order = match value.as_str() {
+                        let order = match event_target_value(&ev).as_str() {
                             "name_asc" => ProductSortOrder::NameAsc,
                             "name_desc" => ProductSortOrder::NameDesc,
                             "price_asc" => ProductSortOrder::PriceAsc,
                             "price_desc" => ProductSortOrder::PriceDesc,
-                            _ => ProductSortOrder::Newest,
+                            "newest" => ProductSortOrder::Newest,
+                            _ => ProductSortOrder::Relevance,
                         };
-                        set_sort_order(order);
+                        update(&|q| q.sort = order.clone());
                     }>
+                        <option value="relevance">"Best Match"</option>
                         <option value="newest">"Newest First"</option>
                         <option value="name_asc">"Name (A-Z)"</option>
                         <option value="name_desc">"Name (Z-A)"</option>
@@ -87,47 +67,135 @@ pub fn CatalogPage() -> impl IntoView {
                 </div>
             </div>
 
-            // Products grid
             <Suspense fallback=move || view! {
                 <div class="loading">
                     <div class="spinner"></div>
                     <p>"Loading products..."</p>
                 </div>
             }>
-                {move || {
-                    filtered_products().map(|prods| {
-                        if prods.is_empty() {
-                            view! {
-                                <div class="empty-state">
-                                    <p>"No products found."</p>
-                                    <Show when=move || !search_query.get().is_empty()>
-                                        <button
-                                            class="btn btn-secondary"
-                                            on:click=move |_| set_search_query(String::new())
-                                        >
-                                            "Clear Search"
-                                        </button>
-                                    </Show>
-                                </div>
-                            }.into_view()
-                        } else {
-                            view! {
-                                <div>
-                                    <p class="results-count">
-                                        "Showing " {prods.len()} " product(s)"
-                                    </p>
-                                    <div class="grid grid-cols-4">
-                                        {prods
-                                            .into_iter()
-                                            .map(|product| view! { <ProductCard product=product/> })
-                                            .collect_view()
-                                        }
+                {move || results.get().map(|result| match result {
+                    Ok(found) => {
+                        let facets = found.facets.clone();
+                        let current = found.offset / SEARCH_PAGE_SIZE + 1;
+                        let has_next = found.offset + found.limit < found.total;
+                        view! {
+                            <div class="catalog-layout">
+                                // Facets: options that would match nothing are disabled
+                                <aside class="facets">
+                                    <div class="facet">
+                                        <h4>"Category"</h4>
+                                        {facets.categories.into_iter().map(|facet| {
+                                            let value = facet.value.clone();
+                                            view! {
+                                                <button
+                                                    class="facet-option"
+                                                    class:selected=facet.selected
+                                                    disabled=facet.count == 0 && !facet.selected
+                                                    on:click=move |_| {
+                                                        let next = (!facet.selected).then(|| value.clone());
+                                                        update(&|q| q.category = next.clone());
+                                                    }
+                                                >
+                                                    {facet.value.clone()}
+                                                    <span class="facet-count">{facet.count}</span>
+                                                </button>
+                                            }
+                                        }).collect_view()}
+                                    </div>
+
+                                    <div class="facet">
+                                        <h4>"Price"</h4>
+                                        {facets.price.into_iter().map(|facet| {
+                                            let label = facet.label();
+                                            view! {
+                                                <button
+                                                    class="facet-option"
+                                                    class:selected=facet.selected
+                                                    disabled=facet.count == 0 && !facet.selected
+                                                    on:click=move |_| {
+                                                        let (min, max) = if facet.selected {
+                                                            (None, None)
+                                                        } else {
+                                                            ((facet.min > 0.0).then_some(facet.min), facet.max)
+                                                        };
+                                                        update(&|q| {
+                                                            q.min_price = min;
+                                                            q.max_price = max;
+                                                        });
+                                                    }
+                                                >
+                                                    {label}
+                                                    <span class="facet-count">{facet.count}</span>
+                                                </button>
+                                            }
+                                        }).collect_view()}
                                     </div>
+
+                                    <div class="facet">
+                                        <h4>"Availability"</h4>
+                                        <label class="facet-option">
+                                            <input
+                                                type="checkbox"
+                                                prop:checked=move || query.with(|q| q.in_stock)
+                                                disabled=facets.availability.in_stock == 0
+                                                on:change=move |ev| {
+                                                    let checked = event_target_checked(&ev);
+                                                    update(&|q| q.in_stock = checked);
+                                                }
+                                            />
+                                            "In stock only"
+                                            <span class="facet-count">{facets.availability.in_stock}</span>
+                                        </label>
+                                    </div>
+                                </aside>
+
+                                <div class="catalog-results">
+                                    {if found.products.is_empty() {
+                                        view! {
+                                            <div class="empty-state">
+                                                <p>"No products found."</p>
+                                                <button
+                                                    class="btn btn-secondary"
+                                                    on:click=move |_| set_query(SearchQuery { page: 1, ..Default::default() })
+                                                >
+                                                    "Clear Filters"
+                                                </button>
+                                            </div>
+                                        }.into_view()
+                                    } else {
+                                        view! {
+                                            <p class="results-count">
+                                                "Showing " {found.products.len()} " of " {found.total} " product(s)"
+                                            </p>
+                                            <div class="grid grid-cols-3">
+                                                {found.products
+                                                    .into_iter()
+                                                    .map(|product| view! { <ProductCard product=product/> })
+                                                    .collect_view()
+                                                }
+                                            </div>
+                                            <nav class="pagination">
+                                                {(current > 1).then(|| view! {
+                                                    <button class="btn btn-secondary" on:click=move |_| set_query.update(|q| q.page = current - 1)>
+                                                        "← Previous"
+                                                    </button>
+                                                })}
+                                                {has_next.then(|| view! {
+                                                    <button class="btn btn-secondary" on:click=move |_| set_query.update(|q| q.page = current + 1)>
+                                                        "Next →"
+                                                    </button>
+                                                })}
+                                            </nav>
+                                        }.into_view()
+                                    }}
                                 </div>
-                            }.into_view()
-                        }
-                    })
-                }}
+                            </div>
+                        }.into_view()
+                    }
+                    Err(e) => view! {
+                        <div class="error-state"><p>"Failed to load products: " {e.user_message()}</p></div>
+                    }.into_view(),
+                })}
             </Suspense>
 
             <style>
@@ -167,6 +235,53 @@ pub fn CatalogPage() -> impl IntoView {
                     min-width: 200px;
                 }
 
+                .catalog-layout {
+                    display: grid;
+                    grid-template-columns: 220px 1fr;
+                    gap: var(--spacing-xl);
+                }
+
+                .facet {
+                    margin-bottom: var(--spacing-lg);
+                }
+
+                .facet h4 {
+                    margin-bottom: var(--spacing-sm);
+                }
+
+                .facet-option {
+                    display: flex;
+                    width: 100%;
+                    gap: var(--spacing-sm);
+                    align-items: center;
+                    padding: var(--spacing-xs) 0;
+                    background: none;
+                    border: none;
+                    text-align: left;
+                    cursor: pointer;
+                }
+
+                .facet-option.selected {
+                    font-weight: 600;
+                }
+
+                .facet-option:disabled {
+                    color: var(--color-gray-400);
+                    cursor: default;
+                }
+
+                .facet-count {
+                    margin-left: auto;
+                    color: var(--color-gray-600);
+                    font-size: 0.875rem;
+                }
+
+                .pagination {
+                    display: flex;
+                    justify-content: space-between;
+                    margin-top: var(--spacing-xl);
+                }
+
                 .results-count {
                     margin-bottom: var(--spacing-md);
                     color: var(--color-gray-600);
@@ -180,6 +295,10 @@ pub fn CatalogPage() -> impl IntoView {
                 }
 
                 @media (max-width: 768px) {
+                    .catalog-layout {
+                        grid-template-columns: 1fr;
+                    }
+
                     .catalog-controls {
                         flex-direction: column;
                         align-items: stretch;
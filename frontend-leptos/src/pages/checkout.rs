@@ -7,7 +7,8 @@ use crate::{
         cart::load_cart,
         checkout::create_payment_intent,
     },
-    types::{Cart, ShippingAddress},
+    components::{cart_totals::CartTotals, hold_countdown::HoldCountdown, loyalty_widget::LoyaltyWidget},
+    types::{Cart, CartPrice, ShippingAddress},
 };
 
 #[component]
@@ -29,32 +30,62 @@ pub fn CheckoutPage() -> impl IntoView {
     let (city, set_city) = create_signal(String::new());
     let (state, set_state) = create_signal(String::new());
     let (zip, set_zip) = create_signal(String::new());
-    let (country, set_country) = create_signal("United States".to_string());
+    let (country, set_country) = create_signal("US".to_string());
+
+    // Gift options (printed on the packing slip; prices are left off the invoice)
+    let (gift_wrap, set_gift_wrap) = create_signal(false);
+    let (gift_message, set_gift_message) = create_signal(String::new());
+
+    // Loyalty points to redeem, set by the loyalty widget (logged-in customers only)
+    let redeem_points = create_rw_signal(Option::<i64>::None);
 
     // Processing state
     let (is_processing, set_is_processing) = create_signal(false);
     let (error_message, set_error_message) = create_signal(Option::<String>::None);
 
+    // Checkout whose reserved stock is counting down
+    let (hold_order_id, set_hold_order_id) = create_signal(Option::<String>::None);
+
     // Handle checkout submission
     let handle_checkout = move |_| {
         set_is_processing(true);
         set_error_message(None);
 
         let current_cart = cart.0.get();
-        let total = current_cart.total();
+        let address = ShippingAddress {
+            street: street.get(),
+            city: city.get(),
+            state: state.get(),
+            zip: zip.get(),
+            country: country.get(),
+        };
+        let message = gift_message.get().trim().to_string();
+        let message = (!message.is_empty()).then_some(message);
 
         spawn_local(async move {
-            match create_payment_intent(total).await {
+            match create_payment_intent(&current_cart, &address, gift_wrap.get_untracked(), message, redeem_points.get_untracked()).await {
                 Ok(response) => {
                     log::info!("Payment intent created: {}", response.client_secret);
+                    if response.hold_expires_at.is_some() {
+                        set_hold_order_id(response.order_id.clone());
+                    }
                     // TODO: Integrate Stripe Elements here
                     // For now, just show success message
-                    set_error_message(Some("Payment processing not yet implemented. Order total: $".to_string() + &format!("{:.2}", total)));
+                    let credit = response
+                        .store_credit_applied
+                        .map(|cents| format!(" (store credit applied: ${:.2})", cents as f64 / 100.0))
+                        .unwrap_or_default();
+                    let points = response
+                        .loyalty_points_redeemed
+                        .zip(response.loyalty_discount)
+                        .map(|(points, cents)| format!(" ({} points redeemed: ${:.2} off)", points, cents as f64 / 100.0))
+                        .unwrap_or_default();
+                    set_error_message(Some("Payment processing not yet implemented. Order total: ".to_string() + &CartPrice::format(response.amount) + &points + &credit));
                     set_is_processing(false);
                 }
                 Err(e) => {
                     log::error!("Payment error: {}", e);
-                    set_error_message(Some(format!("Payment failed: {}", e.message)));
+                    set_error_message(Some(format!("Payment failed: {}", e.user_message())));
                     set_is_processing(false);
                 }
             }
@@ -121,12 +152,42 @@ pub fn CheckoutPage() -> impl IntoView {
                             <label>"Country"</label>
                             <input
                                 type="text"
+                                placeholder="US"
+                                maxlength="2"
                                 value=country
                                 on:input=move |ev| set_country(event_target_value(&ev))
                                 required
                             />
                         </div>
 
+                        <h2 class="gift-heading">"Gift Options"</h2>
+
+                        <div class="form-group">
+                            <label class="checkbox-label">
+                                <input
+                                    type="checkbox"
+                                    prop:checked=gift_wrap
+                                    on:change=move |ev| set_gift_wrap(event_target_checked(&ev))
+                                />
+                                " Gift wrap this order"
+                            </label>
+                        </div>
+
+                        <div class="form-group">
+                            <label>"Gift Message (optional)"</label>
+                            <textarea
+                                rows="3"
+                                maxlength="500"
+                                placeholder="Happy birthday!"
+                                prop:value=gift_message
+                                on:input=move |ev| set_gift_message(event_target_value(&ev))
+                            ></textarea>
+                        </div>
+
+                        <LoyaltyWidget redeem=redeem_points/>
+
+                        {move || hold_order_id.get().map(|order_id| view! { <HoldCountdown order_id=order_id/> })}
+
                         // Error message
                         <Show when=move || error_message.get().is_some()>
                             <div class="error-message">
@@ -174,20 +235,7 @@ pub fn CheckoutPage() -> impl IntoView {
 
                     // Totals
                     <div class="summary-totals">
-                        <div class="summary-row">
-                            <span>"Subtotal:"</span>
-                            <span>{move || cart.0.get().formatted_subtotal()}</span>
-                        </div>
-
-                        <div class="summary-row">
-                            <span>"Tax (8%):"</span>
-                            <span>{move || cart.0.get().formatted_tax()}</span>
-                        </div>
-
-                        <div class="summary-row summary-total">
-                            <span>"Total:"</span>
-                            <span>{move || cart.0.get().formatted_total()}</span>
-                        </div>
+                        <CartTotals cart=cart.0 />
                     </div>
                 </div>
             </div>
@@ -222,6 +270,17 @@ pub fn CheckoutPage() -> impl IntoView {
                     gap: var(--spacing-md);
                 }
 
+                .gift-heading {
+                    margin-top: var(--spacing-xl);
+                }
+
+                .checkbox-label {
+                    display: flex;
+                    align-items: center;
+                    gap: var(--spacing-sm);
+                    cursor: pointer;
+                }
+
                 .checkout-btn {
                     width: 100%;
                     margin-top: var(--spacing-lg);
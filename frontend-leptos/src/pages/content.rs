@@ -0,0 +1,13 @@
+// Backend-managed pages reached as /pages/:slug
+
+use leptos::*;
+use leptos_router::*;
+use crate::components::content_page::ContentPage;
+
+#[component]
+pub fn PageBySlug() -> impl IntoView {
+    let params = use_params_map();
+    let slug = Signal::derive(move || params.with(|p| p.get("slug").cloned().unwrap_or_default()));
+
+    view! { <ContentPage slug=slug/> }
+}
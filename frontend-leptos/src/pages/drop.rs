@@ -0,0 +1,29 @@
+// Drop page: the waiting room shoppers land on during a high-demand drop
+
+use leptos::*;
+use leptos_router::*;
+use crate::{api::drops::fetch_drop, components::waiting_room::WaitingRoom};
+
+#[component]
+pub fn DropPage() -> impl IntoView {
+    let params = use_params_map();
+    let drop_id = params.with_untracked(|p| p.get("id").cloned().unwrap_or_default());
+    let info = {
+        let drop_id = drop_id.clone();
+        create_resource(|| (), move |_| {
+            let drop_id = drop_id.clone();
+            async move { fetch_drop(&drop_id).await.ok() }
+        })
+    };
+
+    view! {
+        <div class="drop-page container">
+            <Suspense fallback=|| ()>
+                {move || info.get().flatten().map(|drop| view! {
+                    <h1 class="page-title">{drop.name}</h1>
+                })}
+            </Suspense>
+            <WaitingRoom drop_id=drop_id/>
+        </div>
+    }
+}
@@ -1,10 +1,10 @@
-// Homepage with hero section and featured products
+// Homepage with hero section, featured products and CMS sections
 
 use leptos::*;
 use leptos_router::*;
 use crate::{
-    api::products::fetch_products,
-    components::product_card::ProductCard,
+    api::{content::fetch_content_blocks, products::fetch_products},
+    components::{content_block::ContentBlockView, product_card::ProductCard},
     types::Product,
 };
 
@@ -21,28 +21,49 @@ pub fn HomePage() -> impl IntoView {
         },
     );
 
+    // Hero banner and homepage sections managed from the admin CMS
+    let hero = create_resource(|| (), |_| async move {
+        fetch_content_blocks("hero").await.ok().and_then(|blocks| blocks.into_iter().next())
+    });
+    let sections = create_resource(|| (), |_| async move {
+        fetch_content_blocks("home_section").await.unwrap_or_default()
+    });
+
     view! {
         <div class="home-page">
-            // Hero section
-            <section class="hero">
-                <div class="container">
-                    <div class="hero-content">
-                        <h1 class="hero-title">"Welcome to R-Com"</h1>
-                        <p class="hero-subtitle">
-                            "Discover amazing products at unbeatable prices. "
-                            "Shop with confidence backed by Rust performance."
-                        </p>
-                        <div class="hero-buttons">
-                            <A href="/catalog" class="btn btn-primary btn-lg">
-                                "Shop Now"
-                            </A>
-                            <a href="#featured" class="btn btn-outline btn-lg">
-                                "View Products"
-                            </a>
-                        </div>
-                    </div>
-                </div>
-            </section>
+            // Hero section: the live CMS hero, or the default copy
+            <Suspense fallback=|| ()>
+                {move || hero.get().map(|block| match block {
+                    Some(block) => view! {
+                        <section class="hero hero-cms">
+                            <div class="container">
+                                <div class="hero-content" inner_html=block.html></div>
+                            </div>
+                        </section>
+                    }.into_view(),
+                    None => view! {
+                        <section class="hero">
+                            <div class="container">
+                                <div class="hero-content">
+                                    <h1 class="hero-title">"Welcome to R-Com"</h1>
+                                    <p class="hero-subtitle">
+                                        "Discover amazing products at unbeatable prices. "
+                                        "Shop with confidence backed by Rust performance."
+                                    </p>
+                                    <div class="hero-buttons">
+                                        <A href="/catalog" class="btn btn-primary btn-lg">
+                                            "Shop Now"
+                                        </A>
+                                        <a href="#featured" class="btn btn-outline btn-lg">
+                                            "View Products"
+                                        </a>
+                                    </div>
+                                </div>
+                            </div>
+                        </section>
+                    }.into_view(),
+                })}
+            </Suspense>
 
             // Featured Products section
             <section id="featured" class="featured-products">
@@ -85,7 +106,7 @@ pub fn HomePage() -> impl IntoView {
                                     Err(e) => {
                                         view! {
                                             <div class="error-state">
-                                                <p>"Failed to load products: " {e.message}</p>
+                                                <p>"Failed to load products: " {e.user_message()}</p>
                                                 <button
                                                     class="btn btn-secondary"
                                                     on:click=move |_| products.refetch()
@@ -102,6 +123,17 @@ pub fn HomePage() -> impl IntoView {
                 </div>
             </section>
 
+            // CMS homepage sections
+            <Suspense fallback=|| ()>
+                {move || sections.get().map(|blocks| blocks.into_iter().map(|block| view! {
+                    <section class="home-section">
+                        <div class="container">
+                            <ContentBlockView block=block/>
+                        </div>
+                    </section>
+                }).collect_view())}
+            </Suspense>
+
             <style>
                 {r#"
                 .hero {
@@ -139,6 +171,20 @@ pub fn HomePage() -> impl IntoView {
                     flex-wrap: wrap;
                 }
 
+                .hero-cms h1 {
+                    font-size: 3.5rem;
+                    font-weight: 900;
+                    margin-bottom: var(--spacing-lg);
+                }
+
+                .hero-cms a {
+                    color: inherit;
+                }
+
+                .home-section {
+                    padding: var(--spacing-xl) 0;
+                }
+
                 .featured-products {
                     padding: var(--spacing-2xl) 0;
                 }
@@ -5,4 +5,10 @@ pub mod catalog;
 pub mod product;
 pub mod cart;
 pub mod checkout;
+pub mod review;
+pub mod account;
+pub mod content;
+pub mod blog;
+pub mod order_tracking;
+pub mod drop;
 pub mod not_found;
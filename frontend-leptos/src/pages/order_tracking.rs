@@ -0,0 +1,113 @@
+// Order tracking page: follows the order's status stream and advances the progress steps live
+
+use leptos::*;
+use leptos_router::*;
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{EventSource, MessageEvent};
+use crate::api::orders::{order_events_url, StatusUpdate};
+
+const STEPS: [(&str, &str); 4] = [
+    ("paid", "Paid"),
+    ("fulfilled", "Packed"),
+    ("shipped", "Shipped"),
+    ("delivered", "Delivered"),
+];
+
+#[component]
+pub fn OrderTrackingPage() -> impl IntoView {
+    let params = use_params_map();
+    let query = use_query_map();
+    let order_id = params.with_untracked(|p| p.get("id").cloned().unwrap_or_default());
+    let email = query.with_untracked(|q| q.get("email").cloned());
+
+    let (status, set_status) = create_signal(Option::<String>::None);
+    let (disconnected, set_disconnected) = create_signal(false);
+
+    // The browser reconnects on its own after network drops; 401/404 close the stream for good
+    if let Ok(source) = EventSource::new(&order_events_url(&order_id, email.as_deref())) {
+        let on_status = Closure::<dyn FnMut(MessageEvent)>::new(move |ev: MessageEvent| {
+            let update = ev.data().as_string().and_then(|data| serde_json::from_str::<StatusUpdate>(&data).ok());
+            if let Some(update) = update {
+                set_status(Some(update.status));
+                set_disconnected(false);
+            }
+        });
+        let on_error = Closure::<dyn FnMut(web_sys::Event)>::new(move |_| set_disconnected(true));
+        let _ = source.add_event_listener_with_callback("status", on_status.as_ref().unchecked_ref());
+        source.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+        on_cleanup(move || {
+            source.close();
+            drop(on_status);
+            drop(on_error);
+        });
+    } else {
+        set_disconnected(true);
+    }
+
+    let step_index = move || status.get().and_then(|s| STEPS.iter().position(|(key, _)| *key == s));
+
+    view! {
+        <div class="order-tracking-page container">
+            <h1 class="page-title">"Track Your Order"</h1>
+
+            {move || match status.get().as_deref() {
+                None if disconnected.get() => view! {
+                    <div class="card">
+                        <p>"We couldn't find that order. Log in, or open the link from your confirmation email."</p>
+                        <A href="/account">"Go to your account"</A>
+                    </div>
+                }.into_view(),
+                None => view! {
+                    <div class="loading">
+                        <div class="spinner"></div>
+                        <p>"Loading..."</p>
+                    </div>
+                }.into_view(),
+                Some("refunded") => view! { <div class="card"><p>"This order was refunded."</p></div> }.into_view(),
+                Some("failed") => view! { <div class="card"><p>"Payment for this order failed."</p></div> }.into_view(),
+                Some("pending") => view! { <div class="card"><p>"We're waiting for your payment to complete."</p></div> }.into_view(),
+                Some(_) => view! {
+                    <div class="card">
+                        <ol class="tracking-steps">
+                            {STEPS.iter().enumerate().map(|(index, (_, label))| view! {
+                                <li class:done=move || step_index().is_some_and(|current| index <= current)>
+                                    {*label}
+                                </li>
+                            }).collect_view()}
+                        </ol>
+                        <Show when=move || disconnected.get()>
+                            <p class="help-text">"Reconnecting for live updates..."</p>
+                        </Show>
+                    </div>
+                }.into_view(),
+            }}
+
+            <style>
+                {r#"
+                .tracking-steps {
+                    display: flex;
+                    justify-content: space-between;
+                    list-style: none;
+                    padding: 0;
+                    margin: 0;
+                    gap: var(--spacing-md);
+                }
+
+                .tracking-steps li {
+                    flex: 1;
+                    text-align: center;
+                    padding: var(--spacing-sm);
+                    border-top: 4px solid var(--color-gray-200);
+                    color: var(--color-gray-500);
+                }
+
+                .tracking-steps li.done {
+                    border-top-color: var(--color-primary);
+                    color: inherit;
+                    font-weight: 600;
+                }
+                "#}
+            </style>
+        </div>
+    }
+}
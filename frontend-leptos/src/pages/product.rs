@@ -4,9 +4,10 @@ use leptos::*;
 use leptos_router::*;
 use crate::{
     api::{
-        products::fetch_products,
+        products::fetch_product,
         cart::{load_cart, add_to_cart},
     },
+    components::{delivery_estimate::DeliveryEstimate, product_picture::ProductPicture},
     types::Product,
 };
 
@@ -18,29 +19,33 @@ pub fn ProductPage() -> impl IntoView {
     // Cart state
     let cart = create_rw_signal(load_cart());
 
-    // Get product ID from URL
-    let product_id = move || {
-        params.with(|p| {
-            p.get("id")
-                .and_then(|id| id.parse::<i32>().ok())
-        })
-    };
+    // Get product slug from URL
+    let product_slug = move || params.with(|p| p.get("slug").cloned());
 
-    // Fetch all products and filter for the one we want
-    // (In production, you'd have a fetch_product_by_id endpoint)
+    // Old slugs and numeric ids are redirected by the backend to the current slug
     let product = create_resource(
-        product_id,
-        |id| async move {
-            id.and_then(|product_id| {
-                fetch_products().await.ok()
-                    .and_then(|products| {
-                        products.into_iter()
-                            .find(|p| p.id == product_id)
-                    })
-            })
+        product_slug,
+        |slug| async move {
+            match slug {
+                Some(slug) => fetch_product(&slug).await.ok(),
+                None => None,
+            }
         },
     );
 
+    // Keep the address bar on the product's current slug
+    let canonical_navigate = use_navigate();
+    create_effect(move |_| {
+        if let (Some(Some(product)), Some(slug)) = (product.get(), product_slug()) {
+            if product.slug != slug {
+                canonical_navigate(
+                    &format!("/product/{}", product.slug),
+                    NavigateOptions { replace: true, ..Default::default() },
+                );
+            }
+        }
+    });
+
     // Quantity selector
     let (quantity, set_quantity) = create_signal(1u32);
 
@@ -84,9 +89,10 @@ pub fn ProductPage() -> impl IntoView {
                                         <div class="product-content">
                                             // Product image
                                             <div class="product-image-large">
-                                                <img
-                                                    src={product.image_url()}
-                                                    alt={product.name.clone()}
+                                                <ProductPicture
+                                                    product=product.clone()
+                                                    sizes="(max-width: 768px) 100vw, 50vw"
+                                                    eager=true
                                                 />
                                             </div>
 
@@ -121,6 +127,9 @@ pub fn ProductPage() -> impl IntoView {
                                                     }
                                                 >
                                                     <div class="add-to-cart-section">
+                                                        // Estimated delivery date
+                                                        <DeliveryEstimate product_ids=vec![product.id] />
+
                                                         // Quantity selector
                                                         <div class="quantity-selector">
                                                             <label>"Quantity:"</label>
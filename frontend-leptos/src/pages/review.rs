@@ -0,0 +1,122 @@
+// Review page reached from the one-click rating links in review request emails
+
+use leptos::*;
+use leptos_router::*;
+use crate::api::reviews::{fetch_review_draft, submit_review};
+
+#[component]
+pub fn ReviewPage() -> impl IntoView {
+    let query = use_query_map();
+    let token = move || query.with(|q| q.get("token").cloned().unwrap_or_default());
+
+    // Rating picked in the email (already recorded as a draft by the backend)
+    let (rating, set_rating) = create_signal(
+        query.with_untracked(|q| q.get("rating").and_then(|r| r.parse::<i32>().ok()).unwrap_or(5)),
+    );
+    let (body, set_body) = create_signal(String::new());
+
+    let (is_processing, set_is_processing) = create_signal(false);
+    let (submitted, set_submitted) = create_signal(false);
+    let (error_message, set_error_message) = create_signal(Option::<String>::None);
+
+    let draft = create_resource(token, |token| async move { fetch_review_draft(&token).await });
+
+    let handle_submit = move |_| {
+        set_is_processing(true);
+        set_error_message(None);
+
+        let text = body.get().trim().to_string();
+        let text = (!text.is_empty()).then_some(text);
+        let token = token();
+        let rating = rating.get();
+
+        spawn_local(async move {
+            match submit_review(token, rating, text).await {
+                Ok(_) => set_submitted(true),
+                Err(e) => {
+                    log::error!("Review error: {}", e);
+                    set_error_message(Some(format!("Could not save your review: {}", e.user_message())));
+                }
+            }
+            set_is_processing(false);
+        });
+    };
+
+    view! {
+        <div class="review-page container">
+            <h1 class="page-title">"Rate Your Order"</h1>
+
+            <Suspense fallback=move || view! {
+                <div class="loading">
+                    <div class="spinner"></div>
+                    <p>"Loading..."</p>
+                </div>
+            }>
+                {move || draft.get().map(|result| match result {
+                    Err(_) => view! {
+                        <div class="card">
+                            <p>"This review link is invalid or has expired."</p>
+                            <A href="/">"Back to the store"</A>
+                        </div>
+                    }.into_view(),
+                    Ok(draft) => view! {
+                        <div class="card">
+                            <Show
+                                when=move || !submitted.get()
+                                fallback=|| view! {
+                                    <p>"Thanks for your review!"</p>
+                                    <A href="/catalog">"Keep shopping"</A>
+                                }
+                            >
+                                <p>"Thanks for rating your order! Want to tell us a bit more?"</p>
+
+                                <ul class="review-items">
+                                    {draft.items.clone().into_iter().map(|name| view! { <li>{name}</li> }).collect_view()}
+                                </ul>
+
+                                <div class="form-group star-rating">
+                                    {(1..=5).map(|star| view! {
+                                        <button
+                                            type="button"
+                                            class="star"
+                                            class:selected=move || star <= rating.get()
+                                            on:click=move |_| set_rating(star)
+                                        >
+                                            "★"
+                                        </button>
+                                    }).collect_view()}
+                                </div>
+
+                                <div class="form-group">
+                                    <label>"Your Review (optional)"</label>
+                                    <textarea
+                                        rows="5"
+                                        maxlength="5000"
+                                        placeholder="What did you like or dislike?"
+                                        prop:value=body
+                                        on:input=move |ev| set_body(event_target_value(&ev))
+                                    ></textarea>
+                                </div>
+
+                                <Show when=move || error_message.get().is_some()>
+                                    <div class="error-message">
+                                        {move || error_message.get()}
+                                    </div>
+                                </Show>
+
+                                <button
+                                    type="button"
+                                    class="btn btn-primary"
+                                    on:click=handle_submit
+                                    disabled=move || is_processing.get()
+                                >
+                                    "Submit Review"
+                                </button>
+                            </Show>
+                        </div>
+                    }.into_view(),
+                })}
+            </Suspense>
+        </div>
+    }
+}
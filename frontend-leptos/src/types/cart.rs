@@ -25,6 +25,30 @@ impl CartItem {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CartLine {
+    pub product_id: i32,
+    pub quantity: u32,
+}
+
+/// Authoritative cart totals from the backend, all amounts in cents
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CartPrice {
+    pub subtotal: i64,
+    pub discount: i64,
+    pub tax_rate: f64,
+    pub tax: i64,
+    pub shipping: i64,
+    pub total: i64,
+}
+
+impl CartPrice {
+    /// Format an amount in cents as currency
+    pub fn format(cents: i64) -> String {
+        format!("${:.2}", cents as f64 / 100.0)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct Cart {
     pub items: Vec<CartItem>,
@@ -73,29 +97,20 @@ impl Cart {
         self.items.iter().map(|item| item.subtotal()).sum()
     }
 
-    /// Calculate tax (8% for now)
-    pub fn tax(&self) -> f64 {
-        self.subtotal() * 0.08
-    }
-
-    /// Calculate total (subtotal + tax)
-    pub fn total(&self) -> f64 {
-        self.subtotal() + self.tax()
-    }
-
     /// Format subtotal as currency
     pub fn formatted_subtotal(&self) -> String {
         format!("${:.2}", self.subtotal())
     }
 
-    /// Format tax as currency
-    pub fn formatted_tax(&self) -> String {
-        format!("${:.2}", self.tax())
-    }
-
-    /// Format total as currency
-    pub fn formatted_total(&self) -> String {
-        format!("${:.2}", self.total())
+    /// Lines sent to the backend, which prices them
+    pub fn lines(&self) -> Vec<CartLine> {
+        self.items
+            .iter()
+            .map(|item| CartLine {
+                product_id: item.product.id,
+                quantity: item.quantity,
+            })
+            .collect()
     }
 
     /// Check if cart is empty
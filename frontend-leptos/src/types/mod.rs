@@ -6,7 +6,7 @@ pub mod user;
 pub mod order;
 
 // Re-export commonly used types
-pub use product::Product;
-pub use cart::{Cart, CartItem};
+pub use product::{Product, ProductImage};
+pub use cart::{Cart, CartItem, CartLine, CartPrice};
 pub use user::User;
-pub use order::Order;
+pub use order::{CheckoutRequest, Order, ShippingAddress};
@@ -7,11 +7,34 @@ use serde::{Deserialize, Serialize};
 pub struct Product {
     pub id: i32,
     pub name: String,
+    #[serde(default)]  // Carts saved before slugs existed don't have one
+    pub slug: String,
     pub description: Option<String>,
     pub price: f64,
     pub inventory: i32,
     #[serde(rename = "created_at")]
     pub created_at: String,  // Backend sends NaiveDateTime as string
+    #[serde(default)]
+    pub images: Vec<ProductImage>,  // Main image first; empty until one is uploaded
+}
+
+/// An uploaded product image at several widths and formats
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProductImage {
+    pub id: i32,
+    pub alt: String,
+    pub width: i32,
+    pub height: i32,
+    pub src: String,
+    pub srcset: String,
+    pub sources: Vec<ImageSource>,  // AVIF then WebP, for <picture>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageSource {
+    #[serde(rename = "type")]
+    pub mime_type: String,
+    pub srcset: String,
 }
 
 impl Product {
@@ -25,7 +48,12 @@ impl Product {
         format!("${:.2}", self.price)
     }
 
-    /// Get product image URL (placeholder for now)
+    /// Main uploaded image, if there is one
+    pub fn main_image(&self) -> Option<&ProductImage> {
+        self.images.first()
+    }
+
+    /// Placeholder for products without an uploaded image
     pub fn image_url(&self) -> String {
         format!("https://via.placeholder.com/400x300?text={}",
             urlencoding::encode(&self.name))
@@ -51,8 +79,10 @@ impl Product {
 }
 
 // Product filter options
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub enum ProductSortOrder {
+    #[default]
+    Relevance,
     NameAsc,
     NameDesc,
     PriceAsc,
@@ -63,6 +93,7 @@ pub enum ProductSortOrder {
 impl ProductSortOrder {
     pub fn label(&self) -> &'static str {
         match self {
+            Self::Relevance => "Best Match",
             Self::NameAsc => "Name (A-Z)",
             Self::NameDesc => "Name (Z-A)",
             Self::PriceAsc => "Price (Low to High)",
@@ -70,4 +101,16 @@ impl ProductSortOrder {
             Self::Newest => "Newest First",
         }
     }
+
+    /// Value of the search endpoint's `sort` parameter
+    pub fn param(&self) -> &'static str {
+        match self {
+            Self::Relevance => "relevance", // Newest first until there's a search term
+            Self::NameAsc => "name_asc",
+            Self::NameDesc => "name_desc",
+            Self::PriceAsc => "price_asc",
+            Self::PriceDesc => "price_desc",
+            Self::Newest => "newest",
+        }
+    }
 }
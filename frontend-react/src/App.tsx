@@ -106,7 +106,12 @@ export default function App() {
         <CartDrawer cart={cart} onUpdateQuantity={handleUpdateQuantity} onRemove={handleRemove} onCheckout={handleCheckout} />
       </Drawer>
       {/* Checkout dialog for Stripe payment */}
-      <CheckoutDialog open={checkoutOpen} onClose={handleCloseCheckout} amount={subtotal} />
+      <CheckoutDialog
+        open={checkoutOpen}
+        onClose={handleCloseCheckout}
+        amount={subtotal}
+        items={cart.map(item => ({ product_id: item.id, quantity: item.quantity }))}
+      />
       <Snackbar
         open={snackbar.open}
         autoHideDuration={3000}
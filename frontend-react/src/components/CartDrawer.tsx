@@ -39,12 +39,12 @@ export default function CartDrawer({ cart, onUpdateQuantity, onRemove, onCheckou
                   />
                 </>
               }
-              secondary={`$${item.price * item.quantity}`}
+              secondary={`$${(item.price * item.quantity).toFixed(2)}`}
             />
           </ListItem>
         ))}
       </List>
-      <Typography sx={{ mt: 2 }}><strong>Subtotal:</strong> ${subtotal}</Typography>
+      <Typography sx={{ mt: 2 }}><strong>Subtotal:</strong> ${subtotal.toFixed(2)}</Typography>
       <Button variant="contained" fullWidth sx={{ mt: 2 }} onClick={onCheckout} disabled={cart.length === 0}>
         Checkout
       </Button>
@@ -8,7 +8,8 @@
  * Props:
  *   - open: boolean, whether the dialog is open
  *   - onClose: function, called to close the dialog
- *   - amount: number, the total amount (in cents) to charge
+ *   - amount: number, the estimated total (in cents) shown to the shopper
+ *   - items: cart lines sent to the backend, which computes the amount actually charged
  */
 import React, { useState } from 'react';
 import { Dialog, DialogTitle, DialogContent, DialogActions, Button, Typography, Box } from '@mui/material';
@@ -18,14 +19,20 @@ import { Elements, CardElement, useStripe, useElements } from '@stripe/react-str
 // Load Stripe public key from environment variable
 const stripePromise = loadStripe(import.meta.env.VITE_STRIPE_PUBLIC_KEY as string);
 
+export interface CheckoutLine {
+  product_id: number;
+  quantity: number;
+}
+
 interface CheckoutDialogProps {
   open: boolean;
   onClose: () => void;
   amount: number;
+  items: CheckoutLine[];
 }
 
 // Inner form component for handling payment logic
-function CheckoutForm({ amount, onClose }: { amount: number; onClose: () => void }) {
+function CheckoutForm({ amount, items, onClose }: { amount: number; items: CheckoutLine[]; onClose: () => void }) {
   const stripe = useStripe();
   const elements = useElements();
   const [status, setStatus] = useState<string | null>(null);
@@ -40,7 +47,7 @@ function CheckoutForm({ amount, onClose }: { amount: number; onClose: () => void
     const res = await fetch('/api/create-payment-intent', {
       method: 'POST',
       headers: { 'Content-Type': 'application/json' },
-      body: JSON.stringify({ amount, currency: 'usd' }),
+      body: JSON.stringify({ amount, currency: 'usd', items }),
     });
     const data = await res.json();
     if (!data.client_secret) {
@@ -88,13 +95,13 @@ function CheckoutForm({ amount, onClose }: { amount: number; onClose: () => void
 }
 
 // Main dialog component
-export default function CheckoutDialog({ open, onClose, amount }: CheckoutDialogProps) {
+export default function CheckoutDialog({ open, onClose, amount, items }: CheckoutDialogProps) {
   return (
     <Dialog open={open} onClose={onClose} maxWidth="xs" fullWidth>
       <DialogTitle>Checkout</DialogTitle>
       <DialogContent>
         <Elements stripe={stripePromise}>
-          <CheckoutForm amount={amount} onClose={onClose} />
+          <CheckoutForm amount={amount} items={items} onClose={onClose} />
         </Elements>
       </DialogContent>
     </Dialog>
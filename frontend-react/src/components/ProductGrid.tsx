@@ -1,10 +1,17 @@
 import { Grid, Card, CardContent, CardActions, Button, Typography } from '@mui/material';
 
+export interface PriceBreak {
+  min_quantity: number;
+  price: number;
+}
+
 export interface Product {
   id: number;
   name: string;
   description?: string;
-  price: number;
+  price: number; // Effective (server-computed) unit price
+  original_price?: number; // Present while a sale is active
+  price_breaks?: PriceBreak[];
   inventory: number;
   created_at?: string;
 }
@@ -23,7 +30,21 @@ export default function ProductGrid({ products, onAddToCart }: ProductGridProps)
             {/* Optionally add product image here */}
             <CardContent>
               <Typography variant="h6">{product.name}</Typography>
-              <Typography color="text.secondary">${product.price}</Typography>
+              <Typography color="text.secondary">
+                {product.original_price !== undefined && (
+                  <Typography component="span" sx={{ textDecoration: 'line-through', mr: 1 }}>
+                    ${product.original_price.toFixed(2)}
+                  </Typography>
+                )}
+                <Typography component="span" color={product.original_price !== undefined ? 'error' : 'inherit'}>
+                  ${product.price.toFixed(2)}
+                </Typography>
+              </Typography>
+              {product.price_breaks?.map(tier => (
+                <Typography key={tier.min_quantity} variant="caption" display="block">
+                  Buy {tier.min_quantity}+ for ${tier.price.toFixed(2)} each
+                </Typography>
+              ))}
               <Typography variant="body2">{product.description}</Typography>
             </CardContent>
             <CardActions>